@@ -0,0 +1,59 @@
+/* C API of rust-vulkan-experiments, built with the `ffi` feature. */
+#ifndef RVE_H
+#define RVE_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define RVE_OK 0
+#define RVE_ERROR (-1)
+
+typedef struct RveContext RveContext;
+
+typedef struct RveVertex {
+    float position[3];
+    float normal[3];
+    float uv[2];
+} RveVertex;
+
+typedef struct RveDraw {
+    uint32_t mesh;
+    uint32_t texture;
+    /* Column-major model matrix. */
+    float transform[16];
+} RveDraw;
+
+/* Message of the last error on this thread, or NULL. */
+const char *rve_last_error(void);
+
+/* Headless context rendering width x height frames, or NULL on failure. */
+RveContext *rve_context_create(uint32_t width, uint32_t height);
+void rve_context_destroy(RveContext *context);
+
+int32_t rve_mesh_create(RveContext *context, const RveVertex *vertices, uint32_t vertex_count,
+                        const uint32_t *indices, uint32_t index_count, uint32_t *out_mesh);
+/* Wavefront OBJ. */
+int32_t rve_mesh_load(RveContext *context, const char *path, uint32_t *out_mesh);
+
+/* Texture 0 is built in and white. */
+int32_t rve_texture_create(RveContext *context, uint32_t width, uint32_t height,
+                           const uint8_t *rgba, uint32_t *out_texture);
+/* Binary PPM. */
+int32_t rve_texture_load(RveContext *context, const char *path, uint32_t *out_texture);
+
+/* view_proj is a column-major 4x4 matrix. */
+int32_t rve_submit_draw_list(RveContext *context, const float *view_proj, const RveDraw *draws,
+                             uint32_t draw_count);
+/* rgba_out may be NULL, or hold width * height * 4 bytes. */
+int32_t rve_render_frame(RveContext *context, uint8_t *rgba_out);
+/* Copies the last rendered frame into a texture other than 0, scaled to its size. */
+int32_t rve_copy_frame_to_texture(RveContext *context, uint32_t texture);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
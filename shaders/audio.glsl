@@ -0,0 +1,45 @@
+// Audio-reactive inputs for visualizer experiments. Include after defining AUDIO_SET (the
+// descriptor set index of `AudioSet`), e.g.
+//
+//     #extension GL_GOOGLE_include_directive : require
+//     #define AUDIO_SET 1
+//     #include "audio.glsl"
+//
+// Band levels are smoothed and normalized to [0, 1], from the lowest band (20 Hz) up to the
+// Nyquist frequency on a logarithmic scale.
+
+#define AUDIO_BANDS 32
+
+layout(std140, set = AUDIO_SET, binding = 0) uniform AudioData {
+    // x: RMS amplitude, y: peak amplitude, z: beat pulse decaying from 1, w: seconds analyzed.
+    vec4 levels;
+    vec4 bands[AUDIO_BANDS / 4];
+} audio;
+
+float audio_rms() {
+    return audio.levels.x;
+}
+
+float audio_peak() {
+    return audio.levels.y;
+}
+
+float audio_beat() {
+    return audio.levels.z;
+}
+
+float audio_time() {
+    return audio.levels.w;
+}
+
+float audio_band(uint band) {
+    band = min(band, AUDIO_BANDS - 1);
+    return audio.bands[band / 4][band % 4];
+}
+
+// Spectrum level at `x` in [0, 1] along the band axis, interpolated between bands.
+float audio_spectrum(float x) {
+    float position = clamp(x, 0.0, 1.0) * float(AUDIO_BANDS - 1);
+    uint band = uint(position);
+    return mix(audio_band(band), audio_band(band + 1), fract(position));
+}
@@ -0,0 +1,20 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D scene_depth;
+
+layout(push_constant) uniform Reprojection {
+    mat4 current_to_previous;
+} reprojection;
+
+layout(location = 0) in vec2 in_uv;
+
+layout(location = 0) out vec2 out_velocity;
+
+// Camera-only motion of the static scene: the pixel's clip position is rebuilt from depth and
+// reprojected with previous_view_proj * inverse(view_proj).
+void main() {
+    float depth = texture(scene_depth, in_uv).r;
+    vec4 current = vec4(in_uv * 2.0 - 1.0, depth, 1.0);
+    vec4 previous = reprojection.current_to_previous * current;
+    out_velocity = (current.xy - previous.xy / previous.w) * 0.5;
+}
@@ -0,0 +1,13 @@
+#!/bin/sh
+# Compiles every GLSL source in this directory to bin/<name>.spv.
+# Requires glslc from the Vulkan SDK (or set GLSLC to another compatible compiler).
+set -e
+
+GLSLC="${GLSLC:-glslc}"
+
+cd "$(dirname "$0")"
+
+for source in *.vert *.frag *.comp; do
+    [ -e "$source" ] || continue
+    "$GLSLC" --target-env=vulkan1.3 "$source" -o "../bin/$source.spv"
+done
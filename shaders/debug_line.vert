@@ -0,0 +1,15 @@
+#version 450
+
+layout(location = 0) in vec3 in_position;
+layout(location = 1) in vec4 in_color;
+
+layout(location = 0) out vec4 out_color;
+
+layout(push_constant) uniform DebugLines {
+    mat4 view_proj;
+} lines;
+
+void main() {
+    out_color = in_color;
+    gl_Position = lines.view_proj * vec4(in_position, 1.0);
+}
@@ -0,0 +1,45 @@
+#version 450
+
+struct Decal {
+    mat4 model;
+    mat4 inv_model;
+    vec4 atlas_rect;
+    vec4 color;
+};
+
+layout(std430, set = 0, binding = 0) readonly buffer Decals {
+    Decal decals[];
+};
+
+layout(set = 0, binding = 1) uniform sampler2D depth_texture;
+layout(set = 0, binding = 2) uniform sampler2D atlas_texture;
+
+layout(push_constant) uniform Camera {
+    mat4 view_proj;
+    mat4 inv_view_proj;
+} camera;
+
+layout(location = 0) flat in uint in_decal_index;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    vec2 screen_uv = gl_FragCoord.xy / vec2(textureSize(depth_texture, 0));
+    float depth = texture(depth_texture, screen_uv).r;
+
+    vec4 world = camera.inv_view_proj * vec4(screen_uv * 2.0 - 1.0, depth, 1.0);
+    world /= world.w;
+
+    Decal decal = decals[in_decal_index];
+    vec3 local = (decal.inv_model * world).xyz;
+
+    if (any(greaterThan(abs(local), vec3(0.5)))) {
+        discard;
+    }
+
+    // Decals project along their local Y axis.
+    vec2 decal_uv = local.xz + 0.5;
+    vec2 atlas_uv = decal.atlas_rect.xy + decal_uv * decal.atlas_rect.zw;
+
+    out_color = texture(atlas_texture, atlas_uv) * decal.color;
+}
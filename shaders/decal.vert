@@ -0,0 +1,43 @@
+#version 450
+
+struct Decal {
+    mat4 model;
+    mat4 inv_model;
+    vec4 atlas_rect;
+    vec4 color;
+};
+
+layout(std430, set = 0, binding = 0) readonly buffer Decals {
+    Decal decals[];
+};
+
+layout(push_constant) uniform Camera {
+    mat4 view_proj;
+    mat4 inv_view_proj;
+} camera;
+
+layout(location = 0) flat out uint out_decal_index;
+
+const vec3 CORNERS[8] = vec3[](
+    vec3(-0.5, -0.5, -0.5), vec3(0.5, -0.5, -0.5),
+    vec3(0.5, 0.5, -0.5), vec3(-0.5, 0.5, -0.5),
+    vec3(-0.5, -0.5, 0.5), vec3(0.5, -0.5, 0.5),
+    vec3(0.5, 0.5, 0.5), vec3(-0.5, 0.5, 0.5)
+);
+
+const uint INDICES[36] = uint[](
+    0, 2, 1, 0, 3, 2,
+    4, 5, 6, 4, 6, 7,
+    0, 1, 5, 0, 5, 4,
+    3, 6, 2, 3, 7, 6,
+    0, 4, 7, 0, 7, 3,
+    1, 2, 6, 1, 6, 5
+);
+
+void main() {
+    uint decal_index = uint(gl_InstanceIndex);
+    vec3 corner = CORNERS[INDICES[gl_VertexIndex]];
+
+    gl_Position = camera.view_proj * decals[decal_index].model * vec4(corner, 1.0);
+    out_decal_index = decal_index;
+}
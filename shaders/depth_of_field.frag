@@ -0,0 +1,66 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D color_input;
+layout(set = 0, binding = 1) uniform sampler2D depth;
+
+layout(push_constant) uniform DepthOfField {
+    float near;
+    float far;
+    float focus_distance;
+    float coc_scale;
+    float max_coc_pixels;
+    uint sample_count;
+} dof;
+
+layout(location = 0) in vec2 in_uv;
+
+layout(location = 0) out vec4 out_color;
+
+const float GOLDEN_ANGLE = 2.39996323;
+
+float linear_depth(vec2 uv) {
+    float d = texture(depth, uv).r;
+    return dof.near * dof.far / (dof.far - d * (dof.far - dof.near));
+}
+
+// Circle of confusion diameter in pixels for a thin lens focused at focus_distance.
+float circle_of_confusion(vec2 uv) {
+    float z = linear_depth(uv);
+    float coc = dof.coc_scale * abs(z - dof.focus_distance) / z;
+    return min(coc, dof.max_coc_pixels);
+}
+
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(color_input, 0));
+    float center_depth = linear_depth(in_uv);
+    float center_coc = circle_of_confusion(in_uv);
+    vec4 center = texture(color_input, in_uv);
+
+    uint sample_count = max(dof.sample_count, 1u);
+    if (dof.max_coc_pixels < 1.0) {
+        out_color = center;
+        return;
+    }
+
+    // Scatter-as-gather: every sample on a disk of the maximum radius contributes if its own
+    // circle of confusion reaches this pixel. In-focus background samples are limited by the
+    // center's radius so sharp objects do not bleed into blurred foreground.
+    vec4 sum = center;
+    float weight_sum = 1.0;
+
+    for (uint i = 0u; i < sample_count; i++) {
+        float radius = sqrt((float(i) + 0.5) / float(sample_count)) * dof.max_coc_pixels * 0.5;
+        float angle = float(i) * GOLDEN_ANGLE;
+        vec2 uv = in_uv + vec2(cos(angle), sin(angle)) * radius * texel;
+
+        float sample_coc = circle_of_confusion(uv);
+        float sample_depth = linear_depth(uv);
+        float reach = sample_depth > center_depth ? min(sample_coc, center_coc) : sample_coc;
+
+        float weight = smoothstep(radius - 1.0, radius + 1.0, reach * 0.5);
+        sum += texture(color_input, uv) * weight;
+        weight_sum += weight;
+    }
+
+    out_color = sum / weight_sum;
+}
@@ -0,0 +1,19 @@
+#version 450
+
+layout(location = 0) in vec2 in_corner;
+layout(location = 1) in float in_speed;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    float r2 = dot(in_corner, in_corner);
+    if (r2 > 1.0) {
+        discard;
+    }
+
+    // View-space sphere normal; lit from the upper left of the screen.
+    vec3 normal = vec3(in_corner, sqrt(1.0 - r2));
+    float diffuse = max(dot(normal, normalize(vec3(-0.4, 0.6, 0.7))), 0.0);
+    vec3 color = mix(vec3(0.1, 0.35, 0.9), vec3(0.9, 0.95, 1.0), clamp(in_speed, 0.0, 1.0));
+    out_color = vec4(color * (0.25 + 0.75 * diffuse), 1.0);
+}
@@ -0,0 +1,38 @@
+#version 450
+
+layout(std430, set = 0, binding = 0) readonly buffer Positions {
+    vec4 positions[];
+};
+
+layout(std430, set = 0, binding = 1) readonly buffer Velocities {
+    vec4 velocities[];
+};
+
+layout(push_constant) uniform Particles {
+    mat4 view_proj;
+    // xyz: camera right, w: sphere radius.
+    vec4 camera_right;
+    // xyz: camera up, w: speed shown fully white.
+    vec4 camera_up;
+} particles;
+
+layout(location = 0) out vec2 out_corner;
+layout(location = 1) out float out_speed;
+
+const vec2 CORNERS[6] = vec2[](
+    vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+    vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0)
+);
+
+// One camera-facing quad per instance, shaded as a sphere impostor by fluid_particle.frag.
+void main() {
+    vec2 corner = CORNERS[gl_VertexIndex];
+    vec3 center = positions[gl_InstanceIndex].xyz;
+    float radius = particles.camera_right.w;
+    vec3 position = center
+        + (particles.camera_right.xyz * corner.x + particles.camera_up.xyz * corner.y) * radius;
+
+    out_corner = corner;
+    out_speed = length(velocities[gl_InstanceIndex].xyz) / max(particles.camera_up.w, 1e-6);
+    gl_Position = particles.view_proj * vec4(position, 1.0);
+}
@@ -0,0 +1,18 @@
+#version 450
+#extension GL_EXT_nonuniform_qualifier : require
+
+layout(set = 1, binding = 0) uniform sampler2D textures[];
+
+layout(location = 0) in vec3 in_normal;
+layout(location = 1) in vec2 in_uv;
+layout(location = 2) flat in uint in_texture;
+
+layout(location = 0) out vec4 out_color;
+
+const vec3 LIGHT_DIRECTION = normalize(vec3(0.4, 1.0, 0.3));
+
+void main() {
+    vec4 albedo = texture(textures[nonuniformEXT(in_texture)], in_uv);
+    float diffuse = max(dot(normalize(in_normal), LIGHT_DIRECTION), 0.0) * 0.8 + 0.2;
+    out_color = vec4(albedo.rgb * diffuse, albedo.a);
+}
@@ -0,0 +1,32 @@
+#version 450
+
+struct Object {
+    mat4 model;
+    uvec4 indices;
+};
+
+layout(std430, set = 0, binding = 0) readonly buffer Objects {
+    Object objects[];
+};
+
+layout(push_constant) uniform Camera {
+    mat4 view_proj;
+} camera;
+
+layout(location = 0) in vec3 in_position;
+layout(location = 1) in vec3 in_normal;
+layout(location = 2) in vec2 in_uv;
+
+layout(location = 0) out vec3 out_normal;
+layout(location = 1) out vec2 out_uv;
+layout(location = 2) flat out uint out_texture;
+
+void main() {
+    // The culling pass stores the object index as the draw's first instance.
+    Object object = objects[gl_InstanceIndex];
+
+    out_normal = mat3(object.model) * in_normal;
+    out_uv = in_uv;
+    out_texture = object.indices.y;
+    gl_Position = camera.view_proj * object.model * vec4(in_position, 1.0);
+}
@@ -0,0 +1,48 @@
+#version 450
+
+#extension GL_GOOGLE_include_directive : require
+
+#include "grid.glsl"
+
+layout(location = 0) in vec3 in_near;
+layout(location = 1) in vec3 in_far;
+
+layout(location = 0) out vec4 out_color;
+
+const vec4 X_AXIS_COLOR = vec4(0.9, 0.2, 0.2, 1.0);
+const vec4 Z_AXIS_COLOR = vec4(0.2, 0.4, 1.0, 1.0);
+
+// Coverage of the lines at integer values of `coord`, antialiased over `width` pixels.
+float line_coverage(vec2 coord, float width) {
+    vec2 distance = abs(fract(coord - 0.5) - 0.5) / fwidth(coord);
+    return 1.0 - clamp(min(distance.x, distance.y) - width * 0.5 + 0.5, 0.0, 1.0);
+}
+
+float axis_coverage(float coord, float width) {
+    return 1.0 - clamp(abs(coord) / fwidth(coord) - width * 0.5 + 0.5, 0.0, 1.0);
+}
+
+void main() {
+    float t = -in_near.y / (in_far.y - in_near.y);
+    if (!(t > 0.0)) {
+        discard;
+    }
+
+    vec3 position = mix(in_near, in_far, t);
+    vec4 clip = grid.view_proj * vec4(position, 1.0);
+    gl_FragDepth = clip.z / clip.w;
+
+    float width = grid.params.z;
+    vec2 cell = position.xz / grid.params.x;
+    vec4 color = grid.minor_color * line_coverage(cell, width);
+    color = mix(color, grid.major_color, line_coverage(cell / grid.params.y, width));
+    color = mix(color, Z_AXIS_COLOR, axis_coverage(position.x, width));
+    color = mix(color, X_AXIS_COLOR, axis_coverage(position.z, width));
+
+    float fade = 1.0 - smoothstep(0.0, grid.camera.w, distance(position.xz, grid.camera.xz));
+    color.a *= fade;
+    if (color.a <= 0.0) {
+        discard;
+    }
+    out_color = color;
+}
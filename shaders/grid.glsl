@@ -0,0 +1,11 @@
+// Push constants shared by grid.vert and grid.frag, mirrored by `GridPushConstants`.
+
+layout(push_constant) uniform Grid {
+    mat4 view_proj;
+    // xyz: camera position, w: fade distance.
+    vec4 camera;
+    // x: cell size, y: cells per major line, z: line width in pixels.
+    vec4 params;
+    vec4 minor_color;
+    vec4 major_color;
+} grid;
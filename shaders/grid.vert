@@ -0,0 +1,23 @@
+#version 450
+
+#extension GL_GOOGLE_include_directive : require
+
+#include "grid.glsl"
+
+layout(location = 0) out vec3 out_near;
+layout(location = 1) out vec3 out_far;
+
+vec3 unproject(vec2 ndc, float depth, mat4 inverse_view_proj) {
+    vec4 point = inverse_view_proj * vec4(ndc, depth, 1.0);
+    return point.xyz / point.w;
+}
+
+// Full-screen triangle carrying the world-space points of each pixel on the near and far
+// planes, so the fragment shader can intersect the view ray with the ground plane.
+void main() {
+    vec2 ndc = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2.0 - 1.0;
+    mat4 inverse_view_proj = inverse(grid.view_proj);
+    out_near = unproject(ndc, 0.0, inverse_view_proj);
+    out_far = unproject(ndc, 1.0, inverse_view_proj);
+    gl_Position = vec4(ndc, 0.0, 1.0);
+}
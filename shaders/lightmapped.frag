@@ -0,0 +1,30 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D albedo;
+layout(set = 0, binding = 1) uniform sampler2D lightmap;
+
+layout(location = 0) in vec2 in_uv;
+layout(location = 1) in vec2 in_lightmap_uv;
+
+layout(location = 0) out vec4 out_color;
+
+// The lightmap is 32-bit float, which devices are not required to filter linearly, so
+// interpolate the four nearest texels by hand.
+vec3 sample_lightmap(vec2 uv) {
+    ivec2 size = textureSize(lightmap, 0);
+    vec2 texel = uv * vec2(size) - 0.5;
+    ivec2 base = ivec2(floor(texel));
+    vec2 f = fract(texel);
+
+    vec3 c00 = texelFetch(lightmap, clamp(base, ivec2(0), size - 1), 0).rgb;
+    vec3 c10 = texelFetch(lightmap, clamp(base + ivec2(1, 0), ivec2(0), size - 1), 0).rgb;
+    vec3 c01 = texelFetch(lightmap, clamp(base + ivec2(0, 1), ivec2(0), size - 1), 0).rgb;
+    vec3 c11 = texelFetch(lightmap, clamp(base + ivec2(1, 1), ivec2(0), size - 1), 0).rgb;
+
+    return mix(mix(c00, c10, f.x), mix(c01, c11, f.x), f.y);
+}
+
+void main() {
+    vec4 base_color = texture(albedo, in_uv);
+    out_color = vec4(base_color.rgb * sample_lightmap(in_lightmap_uv), base_color.a);
+}
@@ -0,0 +1,19 @@
+#version 450
+
+layout(location = 0) in vec3 in_position;
+layout(location = 1) in vec3 in_normal;
+layout(location = 2) in vec2 in_uv;
+layout(location = 3) in vec2 in_lightmap_uv;
+
+layout(push_constant) uniform Object {
+    mat4 mvp;
+} object;
+
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) out vec2 out_lightmap_uv;
+
+void main() {
+    out_uv = in_uv;
+    out_lightmap_uv = in_lightmap_uv;
+    gl_Position = object.mvp * vec4(in_position, 1.0);
+}
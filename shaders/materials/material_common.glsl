@@ -0,0 +1,17 @@
+// Shared by the material templates; included through MaterialPipelineCache's preprocessor.
+#pragma once
+
+// Bits of MaterialFeatures in src/pipeline/material.rs.
+const uint FEATURE_ALPHA_TEST = 1u;
+const uint FEATURE_NORMAL_MAP = 2u;
+const uint FEATURE_SKINNED = 4u;
+const uint FEATURE_DOUBLE_SIDED = 8u;
+const uint FEATURE_TEXTURE_ARRAY = 16u;
+
+layout(set = 0, binding = 0) uniform MaterialParams {
+    vec4 base_color;
+    vec4 light_direction;
+    float alpha_cutoff;
+    uint features;
+    uint texture_layer;
+} params;
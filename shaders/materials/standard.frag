@@ -0,0 +1,54 @@
+#version 450
+
+layout(location = 0) in vec2 in_uv;
+layout(location = 1) in vec3 in_normal;
+
+#ifdef NORMAL_MAP
+layout(location = 2) in vec4 in_tangent;
+#endif
+
+#include "material_common.glsl"
+
+#ifdef TEXTURE_ARRAY
+#define MATERIAL_SAMPLER sampler2DArray
+#define SAMPLE_MATERIAL(map, uv) texture(map, vec3(uv, float(params.texture_layer)))
+#else
+#define MATERIAL_SAMPLER sampler2D
+#define SAMPLE_MATERIAL(map, uv) texture(map, uv)
+#endif
+
+layout(set = 0, binding = 1) uniform MATERIAL_SAMPLER albedo_map;
+
+#ifdef NORMAL_MAP
+layout(set = 0, binding = 2) uniform MATERIAL_SAMPLER normal_map;
+#endif
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    vec4 albedo = SAMPLE_MATERIAL(albedo_map, in_uv) * params.base_color;
+
+#ifdef ALPHA_TEST
+    if (albedo.a < params.alpha_cutoff) {
+        discard;
+    }
+#endif
+
+    vec3 normal = normalize(in_normal);
+
+#ifdef DOUBLE_SIDED
+    if (!gl_FrontFacing) {
+        normal = -normal;
+    }
+#endif
+
+#ifdef NORMAL_MAP
+    vec3 tangent = normalize(in_tangent.xyz - normal * dot(normal, in_tangent.xyz));
+    vec3 bitangent = cross(normal, tangent) * in_tangent.w;
+    vec3 tangent_normal = SAMPLE_MATERIAL(normal_map, in_uv).xyz * 2.0 - 1.0;
+    normal = normalize(mat3(tangent, bitangent, normal) * tangent_normal);
+#endif
+
+    float diffuse = max(dot(normal, -params.light_direction.xyz), 0.0);
+    out_color = vec4(albedo.rgb * (0.15 + 0.85 * diffuse), albedo.a);
+}
@@ -0,0 +1,56 @@
+#version 450
+
+// Material template: compiled at runtime with ALPHA_TEST, NORMAL_MAP and SKINNED defined per
+// material. See src/pipeline/material.rs for the matching vertex streams and descriptor sets.
+
+layout(location = 0) in vec3 in_position;
+layout(location = 1) in vec3 in_normal;
+layout(location = 2) in vec2 in_uv;
+
+#ifdef NORMAL_MAP
+layout(location = 3) in vec4 in_tangent;
+#endif
+
+#ifdef SKINNED
+layout(location = 4) in uvec4 in_joints;
+layout(location = 5) in vec4 in_weights;
+
+layout(std430, set = 1, binding = 0) readonly buffer Joints {
+    mat4 joints[];
+};
+#endif
+
+layout(push_constant) uniform Transforms {
+    mat4 view_proj;
+    mat4 model;
+} transforms;
+
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) out vec3 out_normal;
+
+#ifdef NORMAL_MAP
+layout(location = 2) out vec4 out_tangent;
+#endif
+
+void main() {
+    mat4 model = transforms.model;
+
+#ifdef SKINNED
+    mat4 skin = joints[in_joints.x] * in_weights.x
+        + joints[in_joints.y] * in_weights.y
+        + joints[in_joints.z] * in_weights.z
+        + joints[in_joints.w] * in_weights.w;
+    model = model * skin;
+#endif
+
+    mat3 normal_matrix = transpose(inverse(mat3(model)));
+
+    out_uv = in_uv;
+    out_normal = normalize(normal_matrix * in_normal);
+
+#ifdef NORMAL_MAP
+    out_tangent = vec4(normalize(mat3(model) * in_tangent.xyz), in_tangent.w);
+#endif
+
+    gl_Position = transforms.view_proj * model * vec4(in_position, 1.0);
+}
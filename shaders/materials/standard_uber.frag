@@ -0,0 +1,42 @@
+#version 450
+
+// Uber-shader variant of standard.frag, see standard_uber.vert.
+
+layout(constant_id = 0) const uint FEATURES = 0u;
+layout(constant_id = 1) const bool DYNAMIC_FEATURES = false;
+
+layout(location = 0) in vec2 in_uv;
+layout(location = 1) in vec3 in_normal;
+layout(location = 2) in vec4 in_tangent;
+
+#include "material_common.glsl"
+
+layout(set = 0, binding = 1) uniform sampler2D albedo_map;
+layout(set = 0, binding = 2) uniform sampler2D normal_map;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    uint features = DYNAMIC_FEATURES ? params.features : FEATURES;
+    vec4 albedo = texture(albedo_map, in_uv) * params.base_color;
+
+    if ((features & FEATURE_ALPHA_TEST) != 0u && albedo.a < params.alpha_cutoff) {
+        discard;
+    }
+
+    vec3 normal = normalize(in_normal);
+
+    if ((features & FEATURE_DOUBLE_SIDED) != 0u && !gl_FrontFacing) {
+        normal = -normal;
+    }
+
+    if ((features & FEATURE_NORMAL_MAP) != 0u) {
+        vec3 tangent = normalize(in_tangent.xyz - normal * dot(normal, in_tangent.xyz));
+        vec3 bitangent = cross(normal, tangent) * in_tangent.w;
+        vec3 tangent_normal = texture(normal_map, in_uv).xyz * 2.0 - 1.0;
+        normal = normalize(mat3(tangent, bitangent, normal) * tangent_normal);
+    }
+
+    float diffuse = max(dot(normal, -params.light_direction.xyz), 0.0);
+    out_color = vec4(albedo.rgb * (0.15 + 0.85 * diffuse), albedo.a);
+}
@@ -0,0 +1,55 @@
+#version 450
+
+// Uber-shader variant of standard.vert. Features are branched on instead of compiled out:
+// FEATURES fixes them per pipeline, or DYNAMIC_FEATURES reads them per material from the
+// parameter block.
+
+layout(constant_id = 0) const uint FEATURES = 0u;
+layout(constant_id = 1) const bool DYNAMIC_FEATURES = false;
+
+layout(location = 0) in vec3 in_position;
+layout(location = 1) in vec3 in_normal;
+layout(location = 2) in vec2 in_uv;
+layout(location = 3) in vec4 in_tangent;
+layout(location = 4) in uvec4 in_joints;
+layout(location = 5) in vec4 in_weights;
+
+#include "material_common.glsl"
+
+layout(std430, set = 1, binding = 0) readonly buffer Joints {
+    mat4 joints[];
+};
+
+layout(push_constant) uniform Transforms {
+    mat4 view_proj;
+    mat4 model;
+} transforms;
+
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) out vec3 out_normal;
+layout(location = 2) out vec4 out_tangent;
+
+void main() {
+    uint features = DYNAMIC_FEATURES ? params.features : FEATURES;
+    mat4 model = transforms.model;
+
+    if ((features & FEATURE_SKINNED) != 0u) {
+        mat4 skin = joints[in_joints.x] * in_weights.x
+            + joints[in_joints.y] * in_weights.y
+            + joints[in_joints.z] * in_weights.z
+            + joints[in_joints.w] * in_weights.w;
+        model = model * skin;
+    }
+
+    mat3 normal_matrix = transpose(inverse(mat3(model)));
+
+    out_uv = in_uv;
+    out_normal = normalize(normal_matrix * in_normal);
+    out_tangent = vec4(0.0);
+
+    if ((features & FEATURE_NORMAL_MAP) != 0u) {
+        out_tangent = vec4(normalize(mat3(model) * in_tangent.xyz), in_tangent.w);
+    }
+
+    gl_Position = transforms.view_proj * model * vec4(in_position, 1.0);
+}
@@ -0,0 +1,19 @@
+#version 450
+
+layout(location = 0) in vec3 in_normal;
+layout(location = 1) flat in uint in_meshlet;
+
+layout(location = 0) out vec4 out_color;
+
+const vec3 LIGHT_DIRECTION = normalize(vec3(0.4, 1.0, 0.3));
+
+// Colors each meshlet differently so clusters and culling are visible.
+vec3 meshlet_color(uint id) {
+    uint hash = id * 2654435761u;
+    return vec3(hash & 0xffu, (hash >> 8) & 0xffu, (hash >> 16) & 0xffu) / 255.0;
+}
+
+void main() {
+    float diffuse = max(dot(normalize(in_normal), LIGHT_DIRECTION), 0.0) * 0.8 + 0.2;
+    out_color = vec4(meshlet_color(in_meshlet) * diffuse, 1.0);
+}
@@ -0,0 +1,54 @@
+#version 450
+
+struct Meshlet {
+    uint vertex_offset;
+    uint vertex_count;
+    uint triangle_offset;
+    uint triangle_count;
+};
+
+struct Vertex {
+    float position[3];
+    float normal[3];
+    float uv[2];
+};
+
+layout(std430, set = 0, binding = 0) readonly buffer Meshlets {
+    Meshlet meshlets[];
+};
+
+layout(std430, set = 0, binding = 4) readonly buffer MeshletVertices {
+    uint meshlet_vertices[];
+};
+
+layout(std430, set = 0, binding = 5) readonly buffer MeshletTriangles {
+    uint meshlet_triangles[];
+};
+
+layout(std430, set = 0, binding = 6) readonly buffer Vertices {
+    Vertex vertices[];
+};
+
+layout(push_constant) uniform Draw {
+    mat4 mvp;
+    mat4 model;
+} draw;
+
+layout(location = 0) out vec3 out_normal;
+layout(location = 1) flat out uint out_meshlet;
+
+void main() {
+    // The culling pass stores the meshlet index as the draw's first instance.
+    Meshlet meshlet = meshlets[gl_InstanceIndex];
+
+    uint triangle = meshlet_triangles[meshlet.triangle_offset + gl_VertexIndex / 3];
+    uint local = (triangle >> (8 * (gl_VertexIndex % 3))) & 0xffu;
+    Vertex vertex = vertices[meshlet_vertices[meshlet.vertex_offset + local]];
+
+    vec3 position = vec3(vertex.position[0], vertex.position[1], vertex.position[2]);
+    vec3 normal = vec3(vertex.normal[0], vertex.normal[1], vertex.normal[2]);
+
+    out_normal = mat3(draw.model) * normal;
+    out_meshlet = gl_InstanceIndex;
+    gl_Position = draw.mvp * vec4(position, 1.0);
+}
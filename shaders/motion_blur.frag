@@ -0,0 +1,39 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D color_input;
+layout(set = 0, binding = 1) uniform sampler2D velocity;
+
+layout(push_constant) uniform MotionBlur {
+    float shutter_scale;
+    float max_blur_pixels;
+    uint sample_count;
+} blur;
+
+layout(location = 0) in vec2 in_uv;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    vec2 size = vec2(textureSize(color_input, 0));
+    vec2 motion = texture(velocity, in_uv).xy * blur.shutter_scale;
+
+    float length_pixels = length(motion * size);
+    if (length_pixels > blur.max_blur_pixels) {
+        motion *= blur.max_blur_pixels / length_pixels;
+    }
+
+    uint sample_count = max(blur.sample_count, 1u);
+    if (length_pixels < 0.5 || sample_count == 1u) {
+        out_color = texture(color_input, in_uv);
+        return;
+    }
+
+    // Samples are spread over the motion during the shutter interval, centered on the pixel.
+    vec4 sum = vec4(0.0);
+    for (uint i = 0u; i < sample_count; i++) {
+        float t = (float(i) + 0.5) / float(sample_count) - 0.5;
+        sum += texture(color_input, in_uv - motion * t);
+    }
+
+    out_color = sum / float(sample_count);
+}
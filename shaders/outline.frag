@@ -0,0 +1,49 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform usampler2D object_ids;
+
+layout(std430, set = 0, binding = 1) readonly buffer Selection {
+    uint words[];
+};
+
+layout(set = 0, binding = 2) uniform usampler2D nearest_seeds;
+
+layout(push_constant) uniform Outline {
+    vec4 color;
+    int width;
+    uint word_count;
+} outline;
+
+layout(location = 0) in vec2 in_uv;
+
+layout(location = 0) out vec4 out_color;
+
+const uint NO_SEED = 0xffffffffu;
+
+bool is_selected(uint id) {
+    uint word = id / 32u;
+    if (id == 0u || word >= outline.word_count) {
+        return false;
+    }
+    return (words[word] & (1u << (id % 32u))) != 0u;
+}
+
+void main() {
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+
+    if (is_selected(texelFetch(object_ids, pixel, 0).r)) {
+        discard;
+    }
+
+    uint seed = texelFetch(nearest_seeds, pixel, 0).r;
+    if (seed == NO_SEED) {
+        discard;
+    }
+
+    ivec2 offset = ivec2(seed & 0xffffu, seed >> 16) - pixel;
+    if (offset.x * offset.x + offset.y * offset.y > outline.width * outline.width) {
+        discard;
+    }
+
+    out_color = outline.color;
+}
@@ -0,0 +1,18 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D overlay_texture;
+
+layout(push_constant) uniform Overlay {
+    vec4 rect;
+    vec4 uv_rect;
+    float opacity;
+} overlay;
+
+layout(location = 0) in vec2 in_uv;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    vec4 color = texture(overlay_texture, in_uv);
+    out_color = vec4(color.rgb, color.a * overlay.opacity);
+}
@@ -0,0 +1,21 @@
+#version 450
+
+layout(push_constant) uniform Overlay {
+    vec4 rect;
+    vec4 uv_rect;
+    float opacity;
+} overlay;
+
+layout(location = 0) out vec2 out_uv;
+
+const vec2 CORNERS[6] = vec2[](
+    vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0),
+    vec2(0.0, 0.0), vec2(1.0, 1.0), vec2(0.0, 1.0)
+);
+
+void main() {
+    vec2 corner = CORNERS[gl_VertexIndex];
+
+    out_uv = overlay.uv_rect.xy + corner * overlay.uv_rect.zw;
+    gl_Position = vec4(mix(overlay.rect.xy, overlay.rect.zw, corner), 0.0, 1.0);
+}
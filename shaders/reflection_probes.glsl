@@ -0,0 +1,32 @@
+// Reflection probe sampling for shading passes. Include after defining REFLECTION_PROBE_SET
+// (the descriptor set index of `ReflectionProbeSet`), e.g.
+//
+//     #extension GL_GOOGLE_include_directive : require
+//     #define REFLECTION_PROBE_SET 2
+//     #include "reflection_probes.glsl"
+//
+// The blend comes from `resolve_probe_blend` and must be dynamically uniform, e.g. a push
+// constant per draw, since the probe array is indexed without non-uniform indexing.
+
+#define MAX_REFLECTION_PROBES 8
+
+layout(set = REFLECTION_PROBE_SET, binding = 0) uniform samplerCube reflection_probes[MAX_REFLECTION_PROBES];
+
+struct ProbeBlend {
+    uvec2 indices;
+    vec2 weights;
+};
+
+vec3 sample_reflection_probe(uint index, vec3 direction, float roughness) {
+    float max_lod = float(textureQueryLevels(reflection_probes[index]) - 1);
+    return textureLod(reflection_probes[index], direction, roughness * max_lod).rgb;
+}
+
+// Prefiltered radiance along `direction` for a surface of the given roughness.
+vec3 sample_reflection_probes(ProbeBlend blend, vec3 direction, float roughness) {
+    vec3 radiance = sample_reflection_probe(blend.indices.x, direction, roughness) * blend.weights.x;
+    if (blend.weights.y > 0.0) {
+        radiance += sample_reflection_probe(blend.indices.y, direction, roughness) * blend.weights.y;
+    }
+    return radiance;
+}
@@ -0,0 +1,13 @@
+// Shadertoy-style image shader for the playground: RVE_SHADERTOY=shaders/shadertoy/plasma.glsl
+// Edit while running; the shader is recompiled on save.
+
+void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+    vec2 uv = (2.0 * fragCoord - iResolution.xy) / iResolution.y;
+    vec2 mouse = iMouse.z > 0.0 ? (2.0 * iMouse.xy - iResolution.xy) / iResolution.y : vec2(0.0);
+
+    float d = length(uv - mouse);
+    float wave = sin(8.0 * d - 3.0 * iTime) + sin(6.0 * uv.x + iTime) + sin(5.0 * uv.y - iTime);
+
+    vec3 color = 0.5 + 0.5 * cos(wave + vec3(0.0, 2.0, 4.0));
+    fragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,86 @@
+// Workgroup-wide sums and exclusive prefix sums built on subgroup operations.
+//
+// Define WORKGROUP_SIZE (the x local size of a 1D workgroup) before including, and call the
+// helpers from uniform control flow with the whole workgroup. The host injects the defines of
+// `SubgroupProperties::shader_defines`:
+//
+//     SUBGROUP_SIZE, SUBGROUP_MIN_SIZE  default and smallest subgroup size
+//     SUBGROUP_ARITHMETIC               subgroupAdd and friends are supported in compute
+//
+// Without SUBGROUP_ARITHMETIC the helpers fall back to a shared-memory scan.
+#pragma once
+
+#ifdef SUBGROUP_ARITHMETIC
+#extension GL_KHR_shader_subgroup_basic : require
+#extension GL_KHR_shader_subgroup_arithmetic : require
+
+#ifndef SUBGROUP_MIN_SIZE
+#define SUBGROUP_MIN_SIZE 1
+#endif
+
+// One total per subgroup; subgroups may be as small as SUBGROUP_MIN_SIZE.
+shared uint subgroup_totals[(WORKGROUP_SIZE + SUBGROUP_MIN_SIZE - 1) / SUBGROUP_MIN_SIZE];
+
+uint workgroup_exclusive_add(uint value, out uint total) {
+    uint prefix = subgroupExclusiveAdd(value);
+    uint subgroup_total = subgroupAdd(value);
+    if (subgroupElect()) {
+        subgroup_totals[gl_SubgroupID] = subgroup_total;
+    }
+    barrier();
+
+    uint offset = 0u;
+    total = 0u;
+    for (uint i = 0u; i < gl_NumSubgroups; i++) {
+        offset += i < gl_SubgroupID ? subgroup_totals[i] : 0u;
+        total += subgroup_totals[i];
+    }
+    barrier();
+
+    return offset + prefix;
+}
+
+uint workgroup_add(uint value) {
+    uint subgroup_total = subgroupAdd(value);
+    if (subgroupElect()) {
+        subgroup_totals[gl_SubgroupID] = subgroup_total;
+    }
+    barrier();
+
+    uint total = 0u;
+    for (uint i = 0u; i < gl_NumSubgroups; i++) {
+        total += subgroup_totals[i];
+    }
+    barrier();
+
+    return total;
+}
+#else
+shared uint scan_values[WORKGROUP_SIZE];
+
+// Hillis-Steele scan over the whole workgroup.
+uint workgroup_exclusive_add(uint value, out uint total) {
+    uint index = gl_LocalInvocationIndex;
+    scan_values[index] = value;
+    barrier();
+
+    for (uint offset = 1u; offset < WORKGROUP_SIZE; offset <<= 1u) {
+        uint addend = index >= offset ? scan_values[index - offset] : 0u;
+        barrier();
+        scan_values[index] += addend;
+        barrier();
+    }
+
+    total = scan_values[WORKGROUP_SIZE - 1u];
+    uint inclusive = scan_values[index];
+    barrier();
+
+    return inclusive - value;
+}
+
+uint workgroup_add(uint value) {
+    uint total;
+    workgroup_exclusive_add(value, total);
+    return total;
+}
+#endif
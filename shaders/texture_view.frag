@@ -0,0 +1,41 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform TextureView {
+    vec4 channel_mask;
+    vec2 range;
+    float near;
+    float far;
+    uint flags;
+} view;
+
+layout(location = 0) in vec2 in_uv;
+
+layout(location = 0) out vec4 out_color;
+
+const uint GRAYSCALE = 1u;
+const uint LINEARIZE_DEPTH = 2u;
+const uint REVERSED_DEPTH = 4u;
+
+void main() {
+    vec4 value = textureLod(source, in_uv, 0.0);
+
+    if ((view.flags & LINEARIZE_DEPTH) != 0u) {
+        float depth = value.r;
+        if ((view.flags & REVERSED_DEPTH) != 0u) {
+            depth = 1.0 - depth;
+        }
+        // Inverse of a [0, 1] perspective depth mapping back to view distance.
+        float distance = view.near * view.far / (view.far - depth * (view.far - view.near));
+        value = vec4((distance - view.near) / (view.far - view.near));
+    }
+
+    vec3 color = value.rgb * view.channel_mask.rgb;
+    if ((view.flags & GRAYSCALE) != 0u) {
+        color = vec3(dot(value, view.channel_mask));
+    }
+
+    color = (color - view.range.x) / max(view.range.y - view.range.x, 1e-6);
+    out_color = vec4(color, 1.0);
+}
@@ -0,0 +1,70 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D color_input;
+layout(set = 0, binding = 1) uniform sampler3D color_lut;
+
+layout(push_constant) uniform Tonemap {
+    float exposure;
+    float lut_strength;
+    float lut_size;
+    uint operator_index;
+    vec4 lut_domain_min;
+    vec4 lut_domain_max;
+    // Set for UNORM targets, which don't encode sRGB on write.
+    uint encode_srgb;
+    uint padding[3];
+} tonemap;
+
+layout(location = 0) in vec2 in_uv;
+
+layout(location = 0) out vec4 out_color;
+
+vec3 aces_fitted(vec3 x) {
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
+}
+
+vec3 reinhard(vec3 x) {
+    return x / (1.0 + x);
+}
+
+vec3 linear_to_srgb(vec3 c) {
+    return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+}
+
+vec3 srgb_to_linear(vec3 c) {
+    return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+}
+
+void main() {
+    vec4 hdr = texture(color_input, in_uv);
+    vec3 color = hdr.rgb * tonemap.exposure;
+
+    if (tonemap.operator_index == 0u) {
+        color = aces_fitted(color);
+    } else if (tonemap.operator_index == 1u) {
+        color = reinhard(color);
+    } else {
+        color = clamp(color, 0.0, 1.0);
+    }
+
+    // LUTs are authored for display-encoded input, so grade in sRGB and return to linear.
+    if (tonemap.lut_strength > 0.0) {
+        vec3 encoded = linear_to_srgb(color);
+        vec3 domain_min = tonemap.lut_domain_min.rgb;
+        vec3 domain = clamp((encoded - domain_min) / (tonemap.lut_domain_max.rgb - domain_min), 0.0, 1.0);
+        vec3 lut_uv = domain * ((tonemap.lut_size - 1.0) / tonemap.lut_size) + 0.5 / tonemap.lut_size;
+        vec3 graded = srgb_to_linear(texture(color_lut, lut_uv).rgb);
+        color = mix(color, graded, tonemap.lut_strength);
+    }
+
+    if (tonemap.encode_srgb != 0u) {
+        color = linear_to_srgb(color);
+    }
+
+    out_color = vec4(color, hdr.a);
+}
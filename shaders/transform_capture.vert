@@ -0,0 +1,25 @@
+#version 450
+
+// Captures world-space vertices with transform feedback; built with rasterizer discard.
+
+layout(push_constant) uniform Object {
+    mat4 model;
+} object;
+
+layout(location = 0) in vec3 in_position;
+layout(location = 1) in vec3 in_normal;
+layout(location = 2) in vec2 in_uv;
+
+layout(xfb_buffer = 0, xfb_stride = 32) out;
+layout(location = 0, xfb_buffer = 0, xfb_offset = 0) out vec3 out_position;
+layout(location = 1, xfb_buffer = 0, xfb_offset = 12) out vec3 out_normal;
+layout(location = 2, xfb_buffer = 0, xfb_offset = 24) out vec2 out_uv;
+
+void main() {
+    vec4 world = object.model * vec4(in_position, 1.0);
+
+    out_position = world.xyz;
+    out_normal = mat3(object.model) * in_normal;
+    out_uv = in_uv;
+    gl_Position = world;
+}
@@ -0,0 +1,27 @@
+#version 450
+
+layout(location = 0) in vec2 in_position;
+layout(location = 1) in vec2 in_uv;
+layout(location = 2) in vec4 in_color;
+
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) out vec4 out_color;
+
+layout(push_constant) uniform Ui {
+    vec2 screen_size;
+} ui;
+
+// egui vertex colors are sRGB-encoded; the target is an sRGB attachment that expects linear
+// output.
+vec3 srgb_to_linear(vec3 color) {
+    bvec3 cutoff = lessThan(color, vec3(0.04045));
+    vec3 higher = pow((color + 0.055) / 1.055, vec3(2.4));
+    vec3 lower = color / 12.92;
+    return mix(higher, lower, cutoff);
+}
+
+void main() {
+    out_uv = in_uv;
+    out_color = vec4(srgb_to_linear(in_color.rgb), in_color.a);
+    gl_Position = vec4(in_position / ui.screen_size * 2.0 - 1.0, 0.0, 1.0);
+}
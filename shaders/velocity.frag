@@ -0,0 +1,13 @@
+#version 450
+
+layout(location = 0) in vec4 in_current;
+layout(location = 1) in vec4 in_previous;
+
+layout(location = 0) out vec2 out_velocity;
+
+// Screen-space motion in UV units: adding it to a pixel's previous UV gives its current UV.
+void main() {
+    vec2 current = in_current.xy / in_current.w;
+    vec2 previous = in_previous.xy / in_previous.w;
+    out_velocity = (current - previous) * 0.5;
+}
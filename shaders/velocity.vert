@@ -0,0 +1,17 @@
+#version 450
+
+layout(location = 0) in vec3 in_position;
+
+layout(push_constant) uniform Motion {
+    mat4 current_mvp;
+    mat4 previous_mvp;
+} motion;
+
+layout(location = 0) out vec4 out_current;
+layout(location = 1) out vec4 out_previous;
+
+void main() {
+    out_current = motion.current_mvp * vec4(in_position, 1.0);
+    out_previous = motion.previous_mvp * vec4(in_position, 1.0);
+    gl_Position = out_current;
+}
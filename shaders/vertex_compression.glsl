@@ -0,0 +1,15 @@
+// Decoders for vertex streams written by `VertexCompression` in
+// src/geometry/vertex_compression.rs.
+
+// `PositionEncoding::Quantized16`: the attribute arrives as unorm in [0, 1].
+vec3 dequantize_position(vec3 unorm, vec3 offset, vec3 scale) {
+    return offset + unorm * scale;
+}
+
+// `NormalEncoding::Octahedral8` and `Octahedral16`: the attribute arrives as snorm in [-1, 1].
+vec3 octahedral_decode(vec2 encoded) {
+    vec3 n = vec3(encoded, 1.0 - abs(encoded.x) - abs(encoded.y));
+    float fold = max(-n.z, 0.0);
+    n.xy += mix(vec2(fold), vec2(-fold), greaterThanEqual(n.xy, vec2(0.0)));
+    return normalize(n);
+}
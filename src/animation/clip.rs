@@ -0,0 +1,334 @@
+use anyhow::Result;
+use glam::{Mat4, Quat, Vec3};
+
+/// Local transform of one joint relative to its parent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JointTransform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for JointTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl JointTransform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn to_matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(other.translation, t),
+            rotation: self.rotation.slerp(other.rotation, t).normalize(),
+            scale: self.scale.lerp(other.scale, t),
+        }
+    }
+}
+
+/// Joint hierarchy of a skinned mesh. Parents must come before their children, as glTF
+/// importers and `Skeleton::new` assume.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Skeleton {
+    pub parents: Vec<Option<usize>>,
+    pub inverse_bind: Vec<Mat4>,
+    pub rest: Pose,
+}
+
+impl Skeleton {
+    pub fn new(parents: Vec<Option<usize>>, inverse_bind: Vec<Mat4>, rest: Pose) -> Result<Self> {
+        if inverse_bind.len() != parents.len() || rest.joints.len() != parents.len() {
+            return Err(anyhow::anyhow!(
+                "Skeleton has {} parents, {} inverse bind matrices and {} rest joints",
+                parents.len(),
+                inverse_bind.len(),
+                rest.joints.len()
+            ));
+        }
+        if let Some(joint) = parents
+            .iter()
+            .enumerate()
+            .position(|(joint, parent)| parent.is_some_and(|parent| parent >= joint))
+        {
+            return Err(anyhow::anyhow!("Joint {} comes before its parent", joint));
+        }
+
+        Ok(Self {
+            parents,
+            inverse_bind,
+            rest,
+        })
+    }
+
+    pub fn joint_count(&self) -> usize {
+        self.parents.len()
+    }
+
+    /// Skinning palette for `pose`, ready for `SkinnedMesh::set_joints`.
+    pub fn joint_matrices(&self, pose: &Pose) -> Vec<Mat4> {
+        let mut globals: Vec<Mat4> = Vec::with_capacity(self.joint_count());
+        for (joint, parent) in self.parents.iter().enumerate() {
+            let local = pose.joints[joint].to_matrix();
+            globals.push(match parent {
+                Some(parent) => globals[*parent] * local,
+                None => local,
+            });
+        }
+
+        globals
+            .iter()
+            .zip(&self.inverse_bind)
+            .map(|(global, inverse_bind)| *global * *inverse_bind)
+            .collect()
+    }
+}
+
+/// Local transforms of every joint of a skeleton.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Pose {
+    pub joints: Vec<JointTransform>,
+}
+
+impl Pose {
+    /// Blends towards `other` by `weight`, scaled per joint by `mask` when given.
+    pub fn blend(&self, other: &Pose, weight: f32, mask: Option<&[f32]>) -> Pose {
+        Pose {
+            joints: self
+                .joints
+                .iter()
+                .zip(&other.joints)
+                .enumerate()
+                .map(|(joint, (a, b))| a.lerp(b, weight * joint_mask(mask, joint)))
+                .collect(),
+        }
+    }
+
+    /// Difference from `reference` to this pose, to be layered on top of another pose with
+    /// `add`.
+    pub fn difference(&self, reference: &Pose) -> Pose {
+        Pose {
+            joints: self
+                .joints
+                .iter()
+                .zip(&reference.joints)
+                .map(|(pose, reference)| JointTransform {
+                    translation: pose.translation - reference.translation,
+                    rotation: (reference.rotation.inverse() * pose.rotation).normalize(),
+                    scale: pose.scale / reference.scale,
+                })
+                .collect(),
+        }
+    }
+
+    /// Applies an additive pose from `difference` on top of this one.
+    pub fn add(&self, additive: &Pose, weight: f32, mask: Option<&[f32]>) -> Pose {
+        Pose {
+            joints: self
+                .joints
+                .iter()
+                .zip(&additive.joints)
+                .enumerate()
+                .map(|(joint, (base, additive))| {
+                    let weight = weight * joint_mask(mask, joint);
+                    JointTransform {
+                        translation: base.translation + additive.translation * weight,
+                        rotation: (base.rotation * Quat::IDENTITY.slerp(additive.rotation, weight))
+                            .normalize(),
+                        scale: base.scale * Vec3::ONE.lerp(additive.scale, weight),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+fn joint_mask(mask: Option<&[f32]>, joint: usize) -> f32 {
+    mask.map_or(1.0, |mask| mask.get(joint).copied().unwrap_or(0.0))
+}
+
+/// Values interpolated between keyframes: linearly, or by slerp for rotations.
+pub trait Keyframe: Copy {
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Keyframe for f32 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Keyframe for Vec3 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self.lerp(*other, t)
+    }
+}
+
+impl Keyframe for Quat {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self.slerp(*other, t).normalize()
+    }
+}
+
+/// Keyframes sorted by time, like a glTF sampler with linear interpolation.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Keyframes<T> {
+    pub times: Vec<f32>,
+    pub values: Vec<T>,
+}
+
+impl<T: Keyframe> Keyframes<T> {
+    /// Value at `time`, clamped to the first and last keyframes.
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let last = self.values.len().min(self.times.len()).checked_sub(1)?;
+        let next = self.times[..=last].partition_point(|&t| t <= time);
+        if next == 0 {
+            return Some(self.values[0]);
+        }
+        if next > last {
+            return Some(self.values[last]);
+        }
+
+        let previous = next - 1;
+        let delta = self.times[next] - self.times[previous];
+        let t = if delta > 0.0 {
+            (time - self.times[previous]) / delta
+        } else {
+            0.0
+        };
+        Some(self.values[previous].interpolate(&self.values[next], t))
+    }
+}
+
+/// Animated channels of one joint. Channels left empty keep the rest pose.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct JointTrack {
+    pub joint: usize,
+    pub translation: Keyframes<Vec3>,
+    pub rotation: Keyframes<Quat>,
+    pub scale: Keyframes<Vec3>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AnimationClip {
+    pub name: String,
+    pub duration: f32,
+    pub tracks: Vec<JointTrack>,
+}
+
+impl AnimationClip {
+    /// Clip time of a playhead at `time`, wrapped when `looping` and clamped otherwise.
+    pub fn clip_time(&self, time: f32, looping: bool) -> f32 {
+        if self.duration <= 0.0 {
+            0.0
+        } else if looping {
+            time.rem_euclid(self.duration)
+        } else {
+            time.clamp(0.0, self.duration)
+        }
+    }
+
+    /// Pose at `time` in clip time; joints without tracks keep the skeleton's rest pose.
+    pub fn sample(&self, skeleton: &Skeleton, time: f32) -> Pose {
+        let mut pose = skeleton.rest.clone();
+        for track in &self.tracks {
+            let Some(joint) = pose.joints.get_mut(track.joint) else {
+                continue;
+            };
+            if let Some(translation) = track.translation.sample(time) {
+                joint.translation = translation;
+            }
+            if let Some(rotation) = track.rotation.sample(time) {
+                joint.rotation = rotation;
+            }
+            if let Some(scale) = track.scale.sample(time) {
+                joint.scale = scale;
+            }
+        }
+        pose
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain() -> Skeleton {
+        let rest = Pose {
+            joints: vec![
+                JointTransform::IDENTITY,
+                JointTransform {
+                    translation: Vec3::Y,
+                    ..JointTransform::IDENTITY
+                },
+            ],
+        };
+        Skeleton::new(
+            vec![None, Some(0)],
+            vec![Mat4::IDENTITY, Mat4::from_translation(-Vec3::Y)],
+            rest,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn rest_pose_gives_identity_palette() {
+        let skeleton = chain();
+        for matrix in skeleton.joint_matrices(&skeleton.rest) {
+            assert!(matrix.abs_diff_eq(Mat4::IDENTITY, 1e-6));
+        }
+
+        assert!(Skeleton::new(vec![Some(1), None], vec![Mat4::IDENTITY; 2], chain().rest).is_err());
+    }
+
+    #[test]
+    fn samples_tracks_over_rest_pose() {
+        let skeleton = chain();
+        let clip = AnimationClip {
+            name: "raise".into(),
+            duration: 2.0,
+            tracks: vec![JointTrack {
+                joint: 0,
+                translation: Keyframes {
+                    times: vec![0.0, 2.0],
+                    values: vec![Vec3::ZERO, Vec3::new(0.0, 0.0, 4.0)],
+                },
+                ..Default::default()
+            }],
+        };
+
+        let pose = clip.sample(&skeleton, 0.5);
+        assert_eq!(pose.joints[0].translation, Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(pose.joints[1], skeleton.rest.joints[1]);
+
+        assert_eq!(clip.clip_time(5.0, true), 1.0);
+        assert_eq!(clip.clip_time(5.0, false), 2.0);
+    }
+
+    #[test]
+    fn additive_difference_round_trips() {
+        let reference = chain().rest;
+        let mut target = reference.clone();
+        target.joints[1].rotation = Quat::from_rotation_z(0.5);
+        target.joints[1].translation += Vec3::X;
+
+        let additive = target.difference(&reference);
+        let restored = reference.add(&additive, 1.0, None);
+        for (a, b) in restored.joints.iter().zip(&target.joints) {
+            assert!(a.translation.abs_diff_eq(b.translation, 1e-6));
+            assert!(a.rotation.abs_diff_eq(b.rotation, 1e-6));
+        }
+
+        // A mask of zero leaves the joint untouched.
+        let masked = reference.add(&additive, 1.0, Some(&[1.0, 0.0]));
+        assert_eq!(masked.joints[1], reference.joints[1]);
+    }
+}
@@ -0,0 +1,7 @@
+pub mod clip;
+pub mod state_machine;
+pub mod timeline;
+
+pub use clip::*;
+pub use state_machine::*;
+pub use timeline::*;
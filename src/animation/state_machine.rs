@@ -0,0 +1,478 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::animation::{AnimationClip, Pose, Skeleton};
+
+/// A state plays one clip of the `clips` slice passed to `AnimationStateMachine::update`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationState {
+    pub name: String,
+    pub clip: usize,
+    pub speed: f32,
+    pub looping: bool,
+}
+
+impl AnimationState {
+    pub fn new(name: &str, clip: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            clip,
+            speed: 1.0,
+            looping: true,
+        }
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransitionCondition {
+    /// The named parameter is above the value.
+    Greater(String, f32),
+    /// The named parameter is below the value.
+    Less(String, f32),
+    /// The named trigger was set since the last transition that consumed it.
+    Trigger(String),
+    /// A non-looping clip reached its end, or a looping one completed a cycle this update.
+    Finished,
+}
+
+/// Crossfade to `to` once every condition holds. `from: None` transitions from any state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub from: Option<usize>,
+    pub to: usize,
+    pub conditions: Vec<TransitionCondition>,
+    pub duration: f32,
+}
+
+impl Transition {
+    pub fn new(from: Option<usize>, to: usize, duration: f32) -> Self {
+        Self {
+            from,
+            to,
+            conditions: Vec::new(),
+            duration,
+        }
+    }
+
+    pub fn with_condition(mut self, condition: TransitionCondition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Playback {
+    state: usize,
+    time: f32,
+    finished: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Crossfade {
+    from: Playback,
+    elapsed: f32,
+    duration: f32,
+}
+
+/// Picks the playing clip from float parameters and triggers, crossfading between states.
+///
+/// The state being faded out keeps advancing so locomotion cycles stay in step. Transitions
+/// aren't evaluated until a crossfade completes; triggers set meanwhile stay pending.
+#[derive(Debug, Clone)]
+pub struct AnimationStateMachine {
+    states: Vec<AnimationState>,
+    transitions: Vec<Transition>,
+    parameters: HashMap<String, f32>,
+    triggers: HashSet<String>,
+    current: Playback,
+    crossfade: Option<Crossfade>,
+}
+
+impl AnimationStateMachine {
+    /// Starts in `initial`, which becomes state 0.
+    pub fn new(initial: AnimationState) -> Self {
+        Self {
+            states: vec![initial],
+            transitions: Vec::new(),
+            parameters: HashMap::new(),
+            triggers: HashSet::new(),
+            current: Playback {
+                state: 0,
+                time: 0.0,
+                finished: false,
+            },
+            crossfade: None,
+        }
+    }
+
+    /// Adds a state and returns its index for transitions.
+    pub fn add_state(&mut self, state: AnimationState) -> usize {
+        self.states.push(state);
+        self.states.len() - 1
+    }
+
+    /// Adds a transition; earlier transitions win when several apply.
+    pub fn add_transition(&mut self, transition: Transition) {
+        self.transitions.push(transition);
+    }
+
+    pub fn set_parameter(&mut self, name: &str, value: f32) {
+        self.parameters.insert(name.to_string(), value);
+    }
+
+    pub fn parameter(&self, name: &str) -> f32 {
+        self.parameters.get(name).copied().unwrap_or(0.0)
+    }
+
+    pub fn trigger(&mut self, name: &str) {
+        self.triggers.insert(name.to_string());
+    }
+
+    pub fn current_state(&self) -> &AnimationState {
+        &self.states[self.current.state]
+    }
+
+    pub fn is_crossfading(&self) -> bool {
+        self.crossfade.is_some()
+    }
+
+    /// Advances the playheads by `dt` seconds and takes the first transition whose conditions
+    /// hold.
+    pub fn update(&mut self, dt: f32, clips: &[AnimationClip]) {
+        self.current = self.advance(self.current, dt, clips);
+        if let Some(crossfade) = &mut self.crossfade {
+            crossfade.elapsed += dt;
+            crossfade.from = Self::advance_state(&self.states, crossfade.from, dt, clips);
+            if crossfade.elapsed >= crossfade.duration {
+                self.crossfade = None;
+            }
+            return;
+        }
+
+        let Some(transition) = self
+            .transitions
+            .iter()
+            .find(|transition| {
+                transition
+                    .from
+                    .is_none_or(|from| from == self.current.state)
+                    && transition.to != self.current.state
+                    && transition
+                        .conditions
+                        .iter()
+                        .all(|condition| self.holds(condition))
+            })
+            .cloned()
+        else {
+            return;
+        };
+
+        for condition in &transition.conditions {
+            if let TransitionCondition::Trigger(name) = condition {
+                self.triggers.remove(name);
+            }
+        }
+
+        let from = self.current;
+        self.current = Playback {
+            state: transition.to,
+            time: 0.0,
+            finished: false,
+        };
+        if transition.duration > 0.0 {
+            self.crossfade = Some(Crossfade {
+                from,
+                elapsed: 0.0,
+                duration: transition.duration,
+            });
+        }
+    }
+
+    /// Pose of the current state, blended with the state being faded out.
+    pub fn sample(&self, clips: &[AnimationClip], skeleton: &Skeleton) -> Pose {
+        let pose = self.sample_playback(self.current, clips, skeleton);
+        match &self.crossfade {
+            Some(crossfade) => {
+                let weight = (crossfade.elapsed / crossfade.duration).clamp(0.0, 1.0);
+                self.sample_playback(crossfade.from, clips, skeleton)
+                    .blend(&pose, weight, None)
+            }
+            None => pose,
+        }
+    }
+
+    fn advance(&self, playback: Playback, dt: f32, clips: &[AnimationClip]) -> Playback {
+        Self::advance_state(&self.states, playback, dt, clips)
+    }
+
+    fn advance_state(
+        states: &[AnimationState],
+        playback: Playback,
+        dt: f32,
+        clips: &[AnimationClip],
+    ) -> Playback {
+        let state = &states[playback.state];
+        let duration = clips.get(state.clip).map_or(0.0, |clip| clip.duration);
+        let time = playback.time + dt * state.speed;
+        let finished = if state.looping {
+            duration > 0.0 && (time / duration).floor() != (playback.time / duration).floor()
+        } else {
+            time >= duration
+        };
+
+        Playback {
+            state: playback.state,
+            time,
+            finished,
+        }
+    }
+
+    fn sample_playback(
+        &self,
+        playback: Playback,
+        clips: &[AnimationClip],
+        skeleton: &Skeleton,
+    ) -> Pose {
+        let state = &self.states[playback.state];
+        match clips.get(state.clip) {
+            Some(clip) => clip.sample(skeleton, clip.clip_time(playback.time, state.looping)),
+            None => skeleton.rest.clone(),
+        }
+    }
+
+    fn holds(&self, condition: &TransitionCondition) -> bool {
+        match condition {
+            TransitionCondition::Greater(name, value) => self.parameter(name) > *value,
+            TransitionCondition::Less(name, value) => self.parameter(name) < *value,
+            TransitionCondition::Trigger(name) => self.triggers.contains(name),
+            TransitionCondition::Finished => self.current.finished,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationBlendMode {
+    /// Blends towards the layer's pose by its weight.
+    Override,
+    /// Adds the layer clip's offset from its first frame, e.g. breathing or a recoil on top
+    /// of locomotion.
+    Additive,
+}
+
+/// Clip layered over a base pose, optionally restricted to some joints, e.g. an upper-body
+/// wave on top of the state machine's walk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationLayer {
+    pub clip: usize,
+    pub time: f32,
+    pub speed: f32,
+    pub looping: bool,
+    pub weight: f32,
+    pub mode: AnimationBlendMode,
+    /// Per-joint weight multipliers; joints past the end of the mask aren't affected.
+    pub mask: Option<Vec<f32>>,
+}
+
+impl AnimationLayer {
+    pub fn new(clip: usize, mode: AnimationBlendMode) -> Self {
+        Self {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+            weight: 1.0,
+            mode,
+            mask: None,
+        }
+    }
+
+    pub fn with_mask(mut self, mask: Vec<f32>) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt * self.speed;
+    }
+
+    pub fn apply(&self, base: &Pose, clips: &[AnimationClip], skeleton: &Skeleton) -> Pose {
+        let Some(clip) = clips.get(self.clip) else {
+            return base.clone();
+        };
+        let pose = clip.sample(skeleton, clip.clip_time(self.time, self.looping));
+        let mask = self.mask.as_deref();
+
+        match self.mode {
+            AnimationBlendMode::Override => base.blend(&pose, self.weight, mask),
+            AnimationBlendMode::Additive => {
+                let additive = pose.difference(&clip.sample(skeleton, 0.0));
+                base.add(&additive, self.weight, mask)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::{JointTrack, JointTransform, Keyframes};
+    use glam::{Mat4, Vec3};
+
+    fn skeleton() -> Skeleton {
+        Skeleton::new(
+            vec![None],
+            vec![Mat4::IDENTITY],
+            Pose {
+                joints: vec![JointTransform::IDENTITY],
+            },
+        )
+        .unwrap()
+    }
+
+    fn hold(name: &str, x: f32, duration: f32) -> AnimationClip {
+        AnimationClip {
+            name: name.into(),
+            duration,
+            tracks: vec![JointTrack {
+                joint: 0,
+                translation: Keyframes {
+                    times: vec![0.0],
+                    values: vec![Vec3::new(x, 0.0, 0.0)],
+                },
+                ..Default::default()
+            }],
+        }
+    }
+
+    fn locomotion() -> (AnimationStateMachine, Vec<AnimationClip>) {
+        let clips = vec![
+            hold("idle", 0.0, 1.0),
+            hold("walk", 1.0, 1.0),
+            hold("run", 3.0, 1.0),
+        ];
+        let mut machine = AnimationStateMachine::new(AnimationState::new("idle", 0));
+        let walk = machine.add_state(AnimationState::new("walk", 1));
+        let run = machine.add_state(AnimationState::new("run", 2));
+        machine.add_transition(
+            Transition::new(Some(0), walk, 0.5)
+                .with_condition(TransitionCondition::Greater("speed".into(), 0.1)),
+        );
+        machine.add_transition(
+            Transition::new(Some(walk), run, 0.5)
+                .with_condition(TransitionCondition::Greater("speed".into(), 3.0)),
+        );
+        machine.add_transition(
+            Transition::new(None, 0, 0.0)
+                .with_condition(TransitionCondition::Trigger("stop".into())),
+        );
+        (machine, clips)
+    }
+
+    #[test]
+    fn crossfades_between_states() {
+        let (mut machine, clips) = locomotion();
+        let skeleton = skeleton();
+
+        machine.set_parameter("speed", 1.0);
+        machine.update(0.1, &clips);
+        assert_eq!(machine.current_state().name, "walk");
+        assert!(machine.is_crossfading());
+
+        machine.update(0.25, &clips);
+        let pose = machine.sample(&clips, &skeleton);
+        assert!((pose.joints[0].translation.x - 0.5).abs() < 1e-6);
+
+        // Run isn't reachable before the crossfade to walk completes.
+        machine.set_parameter("speed", 5.0);
+        machine.update(0.1, &clips);
+        assert_eq!(machine.current_state().name, "walk");
+        machine.update(0.2, &clips);
+        assert!(!machine.is_crossfading());
+        machine.update(0.1, &clips);
+        assert_eq!(machine.current_state().name, "run");
+    }
+
+    #[test]
+    fn triggers_are_consumed_by_transitions() {
+        let (mut machine, clips) = locomotion();
+        machine.set_parameter("speed", 1.0);
+        machine.update(0.1, &clips);
+        machine.update(1.0, &clips);
+
+        machine.set_parameter("speed", 0.0);
+        machine.trigger("stop");
+        machine.update(0.1, &clips);
+        assert_eq!(machine.current_state().name, "idle");
+        assert!(!machine.is_crossfading());
+
+        // The trigger was consumed, and speed is back at 0, so idle stays put.
+        machine.update(0.1, &clips);
+        assert_eq!(machine.current_state().name, "idle");
+    }
+
+    #[test]
+    fn finished_fires_at_the_end_of_a_clip() {
+        let clips = vec![hold("jump", 1.0, 0.5), hold("idle", 0.0, 1.0)];
+        let mut machine =
+            AnimationStateMachine::new(AnimationState::new("jump", 0).with_looping(false));
+        let idle = machine.add_state(AnimationState::new("idle", 1));
+        machine.add_transition(
+            Transition::new(Some(0), idle, 0.0).with_condition(TransitionCondition::Finished),
+        );
+
+        machine.update(0.4, &clips);
+        assert_eq!(machine.current_state().name, "jump");
+        machine.update(0.2, &clips);
+        assert_eq!(machine.current_state().name, "idle");
+    }
+
+    #[test]
+    fn layers_override_and_add() {
+        let skeleton = skeleton();
+        let clips = vec![
+            hold("pose", 2.0, 1.0),
+            AnimationClip {
+                name: "bob".into(),
+                duration: 1.0,
+                tracks: vec![JointTrack {
+                    joint: 0,
+                    translation: Keyframes {
+                        times: vec![0.0, 1.0],
+                        values: vec![Vec3::new(5.0, 0.0, 0.0), Vec3::new(5.0, 1.0, 0.0)],
+                    },
+                    ..Default::default()
+                }],
+            },
+        ];
+        let base = Pose {
+            joints: vec![JointTransform::IDENTITY],
+        };
+
+        let mut layer = AnimationLayer::new(0, AnimationBlendMode::Override);
+        layer.weight = 0.25;
+        assert_eq!(
+            layer.apply(&base, &clips, &skeleton).joints[0].translation,
+            Vec3::new(0.5, 0.0, 0.0)
+        );
+
+        let mut layer = AnimationLayer::new(1, AnimationBlendMode::Additive);
+        layer.looping = false;
+        layer.update(0.5);
+        assert_eq!(
+            layer.apply(&base, &clips, &skeleton).joints[0].translation,
+            Vec3::new(0.0, 0.5, 0.0)
+        );
+
+        let layer = layer.with_mask(vec![0.0]);
+        assert_eq!(layer.apply(&base, &clips, &skeleton), base);
+    }
+}
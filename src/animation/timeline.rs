@@ -0,0 +1,354 @@
+use std::collections::BTreeMap;
+
+use glam::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::animation::{JointTransform, Keyframes};
+
+/// Camera placement at one point of a `CameraPath`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraPose {
+    pub position: Vec3,
+    pub target: Vec3,
+    /// Vertical field of view in radians.
+    pub fov_y: f32,
+}
+
+impl CameraPose {
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_at_rh(self.position, self.target, Vec3::Y)
+    }
+
+    pub fn projection(&self, aspect: f32, near: f32, far: f32) -> Mat4 {
+        Mat4::perspective_rh(self.fov_y, aspect, near, far)
+    }
+}
+
+/// Keyframed camera flight. Positions and targets follow a Catmull-Rom spline through the
+/// keys, so the camera passes every key without stopping; the field of view is linear.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CameraPath {
+    pub times: Vec<f32>,
+    pub keys: Vec<CameraPose>,
+}
+
+impl CameraPath {
+    /// Pose at `time`, clamped to the first and last keys.
+    pub fn sample(&self, time: f32) -> Option<CameraPose> {
+        let last = self.keys.len().min(self.times.len()).checked_sub(1)?;
+        let next = self.times[..=last].partition_point(|&t| t <= time);
+        if next == 0 {
+            return Some(self.keys[0]);
+        }
+        if next > last {
+            return Some(self.keys[last]);
+        }
+
+        let previous = next - 1;
+        let delta = self.times[next] - self.times[previous];
+        let t = if delta > 0.0 {
+            (time - self.times[previous]) / delta
+        } else {
+            0.0
+        };
+        let key = |i: isize| self.keys[i.clamp(0, last as isize) as usize];
+        let [p0, p1, p2, p3] = [-1, 0, 1, 2].map(|offset| key(previous as isize + offset));
+
+        Some(CameraPose {
+            position: catmull_rom(p0.position, p1.position, p2.position, p3.position, t),
+            target: catmull_rom(p0.target, p1.target, p2.target, p3.target, t),
+            fov_y: p1.fov_y + (p2.fov_y - p1.fov_y) * t,
+        })
+    }
+}
+
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let (t2, t3) = (t * t, t * t * t);
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+/// Keyframed object transform. Channels left empty keep the identity.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TransformTrack {
+    pub translation: Keyframes<Vec3>,
+    pub rotation: Keyframes<Quat>,
+    pub scale: Keyframes<Vec3>,
+}
+
+impl TransformTrack {
+    pub fn sample(&self, time: f32) -> Mat4 {
+        JointTransform {
+            translation: self.translation.sample(time).unwrap_or(Vec3::ZERO),
+            rotation: self.rotation.sample(time).unwrap_or(Quat::IDENTITY),
+            scale: self.scale.sample(time).unwrap_or(Vec3::ONE),
+        }
+        .to_matrix()
+    }
+}
+
+/// Everything a `Timeline` drives at one instant.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TimelineFrame {
+    pub time: f32,
+    pub camera: Option<CameraPose>,
+    pub parameters: BTreeMap<String, f32>,
+    pub transforms: BTreeMap<String, Mat4>,
+}
+
+impl TimelineFrame {
+    /// Value of a parameter track, or `default` when the timeline doesn't animate it.
+    pub fn parameter(&self, name: &str, default: f32) -> f32 {
+        self.parameters.get(name).copied().unwrap_or(default)
+    }
+}
+
+/// Demo sequence: a camera path plus named parameter tracks (exposure, fog density, effect
+/// strengths, ...) and object transform tracks. The renderer applies a `TimelineFrame` to
+/// whatever each name refers to, e.g. `"exposure"` to `TonemapSettings::exposure`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Timeline {
+    pub duration: f32,
+    pub camera: CameraPath,
+    pub parameters: BTreeMap<String, Keyframes<f32>>,
+    pub transforms: BTreeMap<String, TransformTrack>,
+}
+
+impl Timeline {
+    pub fn new(duration: f32) -> Self {
+        Self {
+            duration,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_camera_key(mut self, time: f32, pose: CameraPose) -> Self {
+        let index = self.camera.times.partition_point(|&t| t <= time);
+        self.camera.times.insert(index, time);
+        self.camera.keys.insert(index, pose);
+        self
+    }
+
+    pub fn with_parameter_key(mut self, name: &str, time: f32, value: f32) -> Self {
+        let track = self.parameters.entry(name.to_string()).or_default();
+        let index = track.times.partition_point(|&t| t <= time);
+        track.times.insert(index, time);
+        track.values.insert(index, value);
+        self
+    }
+
+    pub fn with_transform(mut self, name: &str, track: TransformTrack) -> Self {
+        self.transforms.insert(name.to_string(), track);
+        self
+    }
+
+    pub fn evaluate(&self, time: f32) -> TimelineFrame {
+        TimelineFrame {
+            time,
+            camera: self.camera.sample(time),
+            parameters: self
+                .parameters
+                .iter()
+                .filter_map(|(name, track)| Some((name.clone(), track.sample(time)?)))
+                .collect(),
+            transforms: self
+                .transforms
+                .iter()
+                .map(|(name, track)| (name.clone(), track.sample(time)))
+                .collect(),
+        }
+    }
+}
+
+/// How a `TimelinePlayer` advances.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlaybackClock {
+    /// By the measured frame time.
+    Realtime,
+    /// By exactly one frame at this rate per `advance`, whatever the frame took, so recordings
+    /// and benchmarks see the same frames on every run.
+    Fixed { frames_per_second: u32 },
+}
+
+/// Playhead over a `Timeline` with play, pause, seeking and scrubbing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelinePlayer {
+    pub clock: PlaybackClock,
+    pub speed: f32,
+    pub looping: bool,
+    pub playing: bool,
+    duration: f32,
+    time: f32,
+    start: f32,
+    frame: u64,
+}
+
+impl TimelinePlayer {
+    pub fn new(timeline: &Timeline, clock: PlaybackClock) -> Self {
+        Self {
+            clock,
+            speed: 1.0,
+            looping: false,
+            playing: true,
+            duration: timeline.duration,
+            time: 0.0,
+            start: 0.0,
+            frame: 0,
+        }
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Frames advanced since the last seek, for naming recorded images. The fixed clock counts
+    /// from the last seek, so change `speed` only right after seeking.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.time >= self.duration
+    }
+
+    pub fn seek(&mut self, time: f32) {
+        self.time = self.wrap(time);
+        self.start = self.time;
+        self.frame = 0;
+    }
+
+    /// Moves the playhead by `delta` seconds, e.g. from a scrub bar drag, without playing.
+    pub fn scrub(&mut self, delta: f32) {
+        self.seek(self.time + delta);
+    }
+
+    /// Advances the playhead when playing and returns the time to evaluate the timeline at.
+    pub fn advance(&mut self, frame_time: f32) -> f32 {
+        if !self.playing || self.is_finished() {
+            return self.time;
+        }
+
+        match self.clock {
+            PlaybackClock::Realtime => {
+                self.time = self.wrap(self.time + frame_time * self.speed);
+            }
+            PlaybackClock::Fixed { frames_per_second } => {
+                // Derived from the frame count rather than accumulated, so long recordings
+                // don't drift.
+                let elapsed =
+                    (self.frame + 1) as f64 * self.speed as f64 / frames_per_second as f64;
+                self.time = self.wrap(self.start + elapsed as f32);
+            }
+        }
+        self.frame += 1;
+        self.time
+    }
+
+    fn wrap(&self, time: f32) -> f32 {
+        if self.duration <= 0.0 {
+            0.0
+        } else if self.looping {
+            time.rem_euclid(self.duration)
+        } else {
+            time.clamp(0.0, self.duration)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pose(x: f32) -> CameraPose {
+        CameraPose {
+            position: Vec3::new(x, 1.0, 5.0),
+            target: Vec3::new(x, 0.0, 0.0),
+            fov_y: 1.0,
+        }
+    }
+
+    #[test]
+    fn camera_path_passes_through_keys() {
+        let path = CameraPath {
+            times: vec![0.0, 1.0, 2.0, 3.0],
+            keys: vec![pose(0.0), pose(1.0), pose(2.0), pose(3.0)],
+        };
+
+        for (time, x) in [(0.0, 0.0), (1.0, 1.0), (2.0, 2.0), (3.0, 3.0)] {
+            assert_eq!(path.sample(time).unwrap().position.x, x);
+        }
+        // Evenly spaced keys on a line stay on the line.
+        assert!((path.sample(1.5).unwrap().position.x - 1.5).abs() < 1e-6);
+        assert_eq!(path.sample(10.0).unwrap(), pose(3.0));
+        assert!(CameraPath::default().sample(0.0).is_none());
+    }
+
+    #[test]
+    fn evaluates_parameter_and_transform_tracks() {
+        let timeline = Timeline::new(4.0)
+            .with_parameter_key("exposure", 2.0, 3.0)
+            .with_parameter_key("exposure", 0.0, 1.0)
+            .with_transform(
+                "cube",
+                TransformTrack {
+                    translation: Keyframes {
+                        times: vec![0.0, 4.0],
+                        values: vec![Vec3::ZERO, Vec3::new(0.0, 4.0, 0.0)],
+                    },
+                    ..Default::default()
+                },
+            );
+
+        let frame = timeline.evaluate(1.0);
+        assert_eq!(frame.parameter("exposure", 0.0), 2.0);
+        assert_eq!(frame.parameter("fog_density", 0.25), 0.25);
+        assert_eq!(
+            frame.transforms["cube"],
+            Mat4::from_translation(Vec3::new(0.0, 1.0, 0.0))
+        );
+        assert!(frame.camera.is_none());
+    }
+
+    #[test]
+    fn fixed_clock_is_deterministic() {
+        let timeline = Timeline::new(10.0);
+        let mut player = TimelinePlayer::new(
+            &timeline,
+            PlaybackClock::Fixed {
+                frames_per_second: 60,
+            },
+        );
+
+        // Frame times are ignored by the fixed clock.
+        for i in 0..120 {
+            player.advance(if i % 2 == 0 { 0.001 } else { 0.5 });
+        }
+        assert_eq!(player.frame(), 120);
+        assert!((player.time() - 2.0).abs() < 1e-5);
+
+        player.seek(9.99);
+        player.advance(0.0);
+        assert_eq!(player.time(), 10.0);
+        assert!(player.is_finished());
+        assert_eq!(player.advance(0.0), 10.0);
+    }
+
+    #[test]
+    fn scrubbing_and_looping() {
+        let timeline = Timeline::new(2.0);
+        let mut player = TimelinePlayer::new(&timeline, PlaybackClock::Realtime);
+        player.playing = false;
+
+        player.scrub(0.5);
+        assert_eq!(player.advance(1.0), 0.5);
+        player.scrub(-3.0);
+        assert_eq!(player.time(), 0.0);
+
+        player.looping = true;
+        player.playing = true;
+        player.seek(1.5);
+        assert_eq!(player.advance(1.0), 0.5);
+    }
+}
@@ -0,0 +1,535 @@
+use anyhow::Result;
+use ash::vk;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::geometry::{
+    CompressedVertices, HalfVertex, StripStats, VertexCompression, generate_tangents, stripify,
+};
+use crate::renderer::GpuVertex;
+use crate::vulkan::ColorSpace;
+
+/// A CPU-side asset that can be loaded from a file.
+pub trait Asset: Sized + 'static {
+    fn load(path: &Path, bytes: &[u8]) -> Result<Self>;
+}
+
+/// Indexed triangle mesh in the layout drawn by the renderers.
+#[derive(Debug, Clone, Default)]
+pub struct MeshAsset {
+    pub vertices: Vec<GpuVertex>,
+    pub indices: Vec<u32>,
+    /// Tangent in xyz and bitangent sign in w, one per vertex: the `NORMAL_MAP` tangent
+    /// stream. Empty when the source has none.
+    pub tangents: Vec<[f32; 4]>,
+}
+
+impl MeshAsset {
+    /// Parses a Wavefront OBJ: positions, normals and UVs, polygons triangulated as fans.
+    /// Materials, groups and smoothing are ignored. OBJ has no tangents, so they are generated
+    /// with `generate_tangents` when the file has UVs.
+    pub fn parse_obj(source: &str) -> Result<Self> {
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        let mut mesh = Self::default();
+        let mut unique: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+
+        for (line_number, line) in source.lines().enumerate() {
+            let mut tokens = line.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+
+            let floats = |tokens: std::str::SplitWhitespace, count: usize| -> Result<Vec<f32>> {
+                let values = tokens
+                    .take(count)
+                    .map(|t| t.parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| {
+                        anyhow::anyhow!("Invalid number on line {}: {}", line_number + 1, e)
+                    })?;
+                if values.len() < count {
+                    return Err(anyhow::anyhow!(
+                        "Expected {} values on line {}",
+                        count,
+                        line_number + 1
+                    ));
+                }
+                Ok(values)
+            };
+
+            match keyword {
+                "v" => {
+                    let v = floats(tokens, 3)?;
+                    positions.push([v[0], v[1], v[2]]);
+                }
+                "vn" => {
+                    let v = floats(tokens, 3)?;
+                    normals.push([v[0], v[1], v[2]]);
+                }
+                "vt" => {
+                    // OBJ puts v = 0 at the bottom of the image; Vulkan samples top-down.
+                    let v = floats(tokens, 2)?;
+                    uvs.push([v[0], 1.0 - v[1]]);
+                }
+                "f" => {
+                    let corners = tokens
+                        .map(|corner| {
+                            let index = |value: Option<&str>,
+                                         count: usize|
+                             -> Result<Option<usize>> {
+                                match value.filter(|v| !v.is_empty()) {
+                                    None => Ok(None),
+                                    Some(v) => {
+                                        let i: i64 = v.parse().map_err(|e| {
+                                            anyhow::anyhow!(
+                                                "Invalid index on line {}: {}",
+                                                line_number + 1,
+                                                e
+                                            )
+                                        })?;
+                                        // Negative indices count back from the last element.
+                                        let resolved = if i < 0 { count as i64 + i } else { i - 1 };
+                                        if resolved < 0 || resolved as usize >= count {
+                                            return Err(anyhow::anyhow!(
+                                                "Index {} out of range on line {}",
+                                                i,
+                                                line_number + 1
+                                            ));
+                                        }
+                                        Ok(Some(resolved as usize))
+                                    }
+                                }
+                            };
+
+                            let mut parts = corner.split('/');
+                            let position =
+                                index(parts.next(), positions.len())?.ok_or_else(|| {
+                                    anyhow::anyhow!("Missing position on line {}", line_number + 1)
+                                })?;
+                            let uv = index(parts.next(), uvs.len())?;
+                            let normal = index(parts.next(), normals.len())?;
+                            Ok((position, uv, normal))
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+
+                    if corners.len() < 3 {
+                        return Err(anyhow::anyhow!(
+                            "Face with fewer than 3 vertices on line {}",
+                            line_number + 1
+                        ));
+                    }
+
+                    let mut vertex_index = |key: (usize, Option<usize>, Option<usize>)| {
+                        *unique.entry(key).or_insert_with(|| {
+                            mesh.vertices.push(GpuVertex {
+                                position: positions[key.0],
+                                normal: key.2.map_or([0.0; 3], |n| normals[n]),
+                                uv: key.1.map_or([0.0; 2], |t| uvs[t]),
+                            });
+                            mesh.vertices.len() as u32 - 1
+                        })
+                    };
+
+                    let first = vertex_index(corners[0]);
+                    for pair in corners[1..].windows(2) {
+                        let b = vertex_index(pair[0]);
+                        let c = vertex_index(pair[1]);
+                        mesh.indices.extend_from_slice(&[first, b, c]);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if !uvs.is_empty() {
+            mesh.tangents = generate_tangents(&mesh.vertices, &mesh.indices);
+        }
+
+        Ok(mesh)
+    }
+}
+
+impl MeshAsset {
+    /// The vertices with 16-bit attributes, for large meshes where vertex fetch bandwidth
+    /// matters more than precision. Indices and tangents are unchanged.
+    pub fn half_vertices(&self) -> Vec<HalfVertex> {
+        self.vertices.iter().map(HalfVertex::from_vertex).collect()
+    }
+
+    /// The vertices encoded with `compression`, with a report of the size saved and the
+    /// precision lost.
+    pub fn compress(&self, compression: &VertexCompression) -> CompressedVertices {
+        compression.compress(&self.vertices)
+    }
+
+    /// The indices as triangle strips separated by `PRIMITIVE_RESTART_INDEX`, for pipelines
+    /// built with `TRIANGLE_STRIP` topology and primitive restart.
+    pub fn strip_indices(&self) -> (Vec<u32>, StripStats) {
+        stripify(&self.indices)
+    }
+}
+
+impl Asset for MeshAsset {
+    fn load(_path: &Path, bytes: &[u8]) -> Result<Self> {
+        let source = std::str::from_utf8(bytes)
+            .map_err(|e| anyhow::anyhow!("Failed to read OBJ as UTF-8: {}", e))?;
+        Self::parse_obj(source)
+    }
+}
+
+/// Uncompressed 2D texture data, tightly packed rows.
+#[derive(Debug, Clone)]
+pub struct TextureAsset {
+    pub width: u32,
+    pub height: u32,
+    pub format: vk::Format,
+    pub data: Vec<u8>,
+}
+
+impl TextureAsset {
+    pub fn color_space(&self) -> ColorSpace {
+        ColorSpace::of_format(self.format)
+    }
+
+    /// Reinterprets the texels with another encoding, e.g. `Linear` for normal maps, when the
+    /// format has a UNORM/sRGB twin. The data is not converted.
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.format = color_space.apply(self.format);
+        self
+    }
+
+    /// Parses a binary PPM (`P6`, 8-bit) into sRGB RGBA8.
+    pub fn parse_ppm(bytes: &[u8]) -> Result<Self> {
+        let mut fields = Vec::with_capacity(4);
+        let mut cursor = 0;
+
+        // Header: magic, width, height and max value, separated by whitespace or comments.
+        while fields.len() < 4 {
+            while cursor < bytes.len() {
+                if bytes[cursor] == b'#' {
+                    while cursor < bytes.len() && bytes[cursor] != b'\n' {
+                        cursor += 1;
+                    }
+                } else if bytes[cursor].is_ascii_whitespace() {
+                    cursor += 1;
+                } else {
+                    break;
+                }
+            }
+            let start = cursor;
+            while cursor < bytes.len() && !bytes[cursor].is_ascii_whitespace() {
+                cursor += 1;
+            }
+            if start == cursor {
+                return Err(anyhow::anyhow!("Truncated PPM header"));
+            }
+            fields.push(std::str::from_utf8(&bytes[start..cursor]).unwrap_or_default());
+        }
+        cursor += 1;
+
+        if fields[0] != "P6" {
+            return Err(anyhow::anyhow!("Only binary P6 PPM files are supported"));
+        }
+        let parse = |field: &str| {
+            field
+                .parse::<u32>()
+                .map_err(|e| anyhow::anyhow!("Invalid PPM header value {}: {}", field, e))
+        };
+        let (width, height, max_value) = (parse(fields[1])?, parse(fields[2])?, parse(fields[3])?);
+        if max_value != 255 {
+            return Err(anyhow::anyhow!("Only 8-bit PPM files are supported"));
+        }
+
+        let pixel_count = (width * height) as usize;
+        let pixels = bytes
+            .get(cursor..cursor + pixel_count * 3)
+            .ok_or_else(|| anyhow::anyhow!("Truncated PPM pixel data"))?;
+
+        let data = pixels
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            format: vk::Format::R8G8B8A8_SRGB,
+            data,
+        })
+    }
+}
+
+impl Asset for TextureAsset {
+    /// Tags the texture as linear or sRGB from its file name; see
+    /// `ColorSpace::from_texture_path`.
+    fn load(path: &Path, bytes: &[u8]) -> Result<Self> {
+        Ok(Self::parse_ppm(bytes)?.with_color_space(ColorSpace::from_texture_path(path)))
+    }
+}
+
+/// Uncompressed 2D array texture data: `layers` images of the same size and format, one after
+/// another, for `UploadBatcher::upload_image_layers` into a `VulkanImageInfo::array` image.
+#[derive(Debug, Clone)]
+pub struct TextureArrayAsset {
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
+    pub format: vk::Format,
+    pub data: Vec<u8>,
+}
+
+impl TextureArrayAsset {
+    /// Stacks `textures` in order, e.g. the splat maps of a terrain.
+    pub fn from_layers(textures: &[TextureAsset]) -> Result<Self> {
+        let first = textures
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("Texture array needs at least one layer"))?;
+        let mut data = Vec::with_capacity(first.data.len() * textures.len());
+        for (index, texture) in textures.iter().enumerate() {
+            if (texture.width, texture.height, texture.format)
+                != (first.width, first.height, first.format)
+                || texture.data.len() != first.data.len()
+            {
+                return Err(anyhow::anyhow!(
+                    "Texture array layer {} is {}x{} {:?}, expected {}x{} {:?}",
+                    index,
+                    texture.width,
+                    texture.height,
+                    texture.format,
+                    first.width,
+                    first.height,
+                    first.format
+                ));
+            }
+            data.extend_from_slice(&texture.data);
+        }
+
+        Ok(Self {
+            width: first.width,
+            height: first.height,
+            layers: textures.len() as u32,
+            format: first.format,
+            data,
+        })
+    }
+
+    /// Splits a grid atlas of `columns` by `rows` equally sized tiles into layers, row by row
+    /// from the top left, e.g. a decal atlas.
+    pub fn from_atlas(atlas: &TextureAsset, columns: u32, rows: u32) -> Result<Self> {
+        if columns == 0
+            || rows == 0
+            || !atlas.width.is_multiple_of(columns)
+            || !atlas.height.is_multiple_of(rows)
+        {
+            return Err(anyhow::anyhow!(
+                "{}x{} atlas doesn't split into {}x{} tiles",
+                atlas.width,
+                atlas.height,
+                columns,
+                rows
+            ));
+        }
+        let texel_count = atlas.width as usize * atlas.height as usize;
+        if texel_count == 0 || !atlas.data.len().is_multiple_of(texel_count) {
+            return Err(anyhow::anyhow!(
+                "Atlas data isn't tightly packed texels; compressed atlases can't be split"
+            ));
+        }
+        let texel_size = atlas.data.len() / texel_count;
+
+        let (width, height) = (atlas.width / columns, atlas.height / rows);
+        let row_size = width as usize * texel_size;
+        let atlas_row_size = atlas.width as usize * texel_size;
+        let mut data = Vec::with_capacity(atlas.data.len());
+        for tile_y in 0..rows as usize {
+            for tile_x in 0..columns as usize {
+                for y in 0..height as usize {
+                    let start = (tile_y * height as usize + y) * atlas_row_size + tile_x * row_size;
+                    data.extend_from_slice(&atlas.data[start..start + row_size]);
+                }
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            layers: columns * rows,
+            format: atlas.format,
+            data,
+        })
+    }
+
+    pub fn layer(&self, layer: u32) -> &[u8] {
+        let layer_size = self.data.len() / self.layers.max(1) as usize;
+        let start = layer as usize * layer_size;
+        &self.data[start..start + layer_size]
+    }
+}
+
+pub const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+/// Compiled SPIR-V module. The stage is taken from the file name, e.g. `lit.frag.spv`.
+#[derive(Debug, Clone)]
+pub struct ShaderAsset {
+    pub stage: vk::ShaderStageFlags,
+    pub code: Vec<u32>,
+}
+
+impl ShaderAsset {
+    pub fn stage_from_path(path: &Path) -> Option<vk::ShaderStageFlags> {
+        let name = path.file_name()?.to_str()?;
+        let name = name.strip_suffix(".spv").unwrap_or(name);
+        let extension = name.rsplit('.').next()?;
+
+        match extension {
+            "vert" => Some(vk::ShaderStageFlags::VERTEX),
+            "frag" => Some(vk::ShaderStageFlags::FRAGMENT),
+            "comp" => Some(vk::ShaderStageFlags::COMPUTE),
+            "geom" => Some(vk::ShaderStageFlags::GEOMETRY),
+            "tesc" => Some(vk::ShaderStageFlags::TESSELLATION_CONTROL),
+            "tese" => Some(vk::ShaderStageFlags::TESSELLATION_EVALUATION),
+            _ => None,
+        }
+    }
+
+    pub fn from_spirv(stage: vk::ShaderStageFlags, bytes: &[u8]) -> Result<Self> {
+        if !bytes.len().is_multiple_of(4) {
+            return Err(anyhow::anyhow!(
+                "SPIR-V size {} is not a multiple of 4",
+                bytes.len()
+            ));
+        }
+
+        let code: Vec<u32> = bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+
+        if code.first() != Some(&SPIRV_MAGIC) {
+            return Err(anyhow::anyhow!("Missing SPIR-V magic number"));
+        }
+
+        Ok(Self { stage, code })
+    }
+}
+
+impl Asset for ShaderAsset {
+    fn load(path: &Path, bytes: &[u8]) -> Result<Self> {
+        let stage = Self::stage_from_path(path)
+            .ok_or_else(|| anyhow::anyhow!("Unknown shader stage for {}", path.display()))?;
+        Self::from_spirv(stage, bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_obj_quad_with_shared_corners() {
+        let source = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\n\
+                      f 1/1/1 2/1/1 3/1/1 4/1/1\n";
+        let mesh = MeshAsset::parse_obj(source).unwrap();
+
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.vertices[2].position, [1.0, 1.0, 0.0]);
+        assert_eq!(mesh.vertices[0].normal, [0.0, 0.0, 1.0]);
+        assert_eq!(mesh.vertices[0].uv, [0.0, 1.0]);
+    }
+
+    #[test]
+    fn generates_tangents_for_obj_with_uvs() {
+        let source = "v 0 0 0\nv 1 0 0\nv 1 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvn 0 0 1\n\
+                      f 1/1/1 2/2/1 3/3/1\n";
+        let mesh = MeshAsset::parse_obj(source).unwrap();
+        // OBJ's V axis points up, ours down: the bitangent is mirrored.
+        assert_eq!(mesh.tangents, vec![[1.0, 0.0, 0.0, -1.0]; 3]);
+
+        let untextured = MeshAsset::parse_obj("v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 3\n").unwrap();
+        assert!(untextured.tangents.is_empty());
+    }
+
+    #[test]
+    fn parses_obj_negative_and_position_only_indices() {
+        let mesh = MeshAsset::parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n").unwrap();
+
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+        assert_eq!(mesh.vertices[1].normal, [0.0; 3]);
+    }
+
+    #[test]
+    fn rejects_malformed_obj() {
+        assert!(MeshAsset::parse_obj("v 0 0\n").is_err());
+        assert!(MeshAsset::parse_obj("v 0 0 0\nf 1 2 3\n").is_err());
+        assert!(MeshAsset::parse_obj("v 0 0 0\nv 1 0 0\nf 1 2\n").is_err());
+    }
+
+    #[test]
+    fn parses_ppm_with_comment() {
+        let mut bytes = b"P6\n# comment\n2 1\n255\n".to_vec();
+        bytes.extend_from_slice(&[255, 0, 0, 0, 255, 0]);
+        let texture = TextureAsset::parse_ppm(&bytes).unwrap();
+
+        assert_eq!((texture.width, texture.height), (2, 1));
+        assert_eq!(texture.data, vec![255, 0, 0, 255, 0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn rejects_truncated_ppm() {
+        assert!(TextureAsset::parse_ppm(b"P6\n2 2\n255\n\0\0\0").is_err());
+        assert!(TextureAsset::parse_ppm(b"P3\n1 1\n255\n0 0 0").is_err());
+        assert!(TextureAsset::parse_ppm(b"P6\n1").is_err());
+    }
+
+    #[test]
+    fn splits_atlases_into_array_layers() {
+        // 4x2 RGBA8 atlas of 2x2 tiles; every texel stores its tile index.
+        let data = (0..8u8).flat_map(|texel| [(texel % 4) / 2; 4]).collect();
+        let atlas = TextureAsset {
+            width: 4,
+            height: 2,
+            format: vk::Format::R8G8B8A8_SRGB,
+            data,
+        };
+
+        let array = TextureArrayAsset::from_atlas(&atlas, 2, 1).unwrap();
+        assert_eq!((array.width, array.height, array.layers), (2, 2, 2));
+        assert!(array.layer(0).iter().all(|&byte| byte == 0));
+        assert!(array.layer(1).iter().all(|&byte| byte == 1));
+        assert!(TextureArrayAsset::from_atlas(&atlas, 3, 1).is_err());
+
+        let stacked = TextureArrayAsset::from_layers(&[atlas.clone(), atlas.clone()]).unwrap();
+        assert_eq!(stacked.layers, 2);
+        assert_eq!(stacked.layer(1), atlas.data.as_slice());
+        let smaller = TextureAsset {
+            width: 2,
+            data: vec![0; 16],
+            ..atlas.clone()
+        };
+        assert!(TextureArrayAsset::from_layers(&[atlas, smaller]).is_err());
+    }
+
+    #[test]
+    fn shader_stage_and_validation() {
+        assert_eq!(
+            ShaderAsset::stage_from_path(Path::new("bin/lit.frag.spv")),
+            Some(vk::ShaderStageFlags::FRAGMENT)
+        );
+        assert_eq!(ShaderAsset::stage_from_path(Path::new("data.bin")), None);
+
+        let mut bytes = SPIRV_MAGIC.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&[0; 4]);
+        assert_eq!(
+            ShaderAsset::from_spirv(vk::ShaderStageFlags::VERTEX, &bytes)
+                .unwrap()
+                .code
+                .len(),
+            2
+        );
+        assert!(ShaderAsset::from_spirv(vk::ShaderStageFlags::VERTEX, &bytes[..6]).is_err());
+        assert!(ShaderAsset::from_spirv(vk::ShaderStageFlags::VERTEX, &[0; 8]).is_err());
+    }
+}
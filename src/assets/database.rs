@@ -0,0 +1,75 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::assets::{
+    AssetHandle, AssetReloadReport, AssetStore, MeshAsset, ShaderAsset, TextureAsset,
+};
+use crate::vulkan::DeletionQueue;
+
+/// Handles of everything reloaded by one `AssetDatabase::reload_changed` call.
+#[derive(Default)]
+pub struct AssetChanges {
+    pub meshes: AssetReloadReport<MeshAsset>,
+    pub textures: AssetReloadReport<TextureAsset>,
+    pub shaders: AssetReloadReport<ShaderAsset>,
+}
+
+impl AssetChanges {
+    pub fn is_empty(&self) -> bool {
+        self.meshes.reloaded.is_empty()
+            && self.textures.reloaded.is_empty()
+            && self.shaders.reloaded.is_empty()
+    }
+
+    /// Load errors of changed files, for logging.
+    pub fn errors(&self) -> impl Iterator<Item = &anyhow::Error> {
+        self.meshes
+            .failed
+            .iter()
+            .map(|(_, e)| e)
+            .chain(self.textures.failed.iter().map(|(_, e)| e))
+            .chain(self.shaders.failed.iter().map(|(_, e)| e))
+    }
+}
+
+/// Meshes (`.obj`), textures (`.ppm`) and shaders (`.spv`) loaded by path into handle-based
+/// stores, with polling hot reload.
+///
+/// Call `reload_changed` once per frame (or on a timer) and rebuild pipelines or descriptor
+/// sets for the handles it reports; replaced assets are retired through the `DeletionQueue`.
+#[derive(Default)]
+pub struct AssetDatabase {
+    pub meshes: AssetStore<MeshAsset>,
+    pub textures: AssetStore<TextureAsset>,
+    pub shaders: AssetStore<ShaderAsset>,
+}
+
+impl AssetDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load_mesh(&mut self, path: impl AsRef<Path>) -> Result<AssetHandle<MeshAsset>> {
+        self.meshes.load(path)
+    }
+
+    pub fn load_texture(&mut self, path: impl AsRef<Path>) -> Result<AssetHandle<TextureAsset>> {
+        self.textures.load(path)
+    }
+
+    pub fn load_shader(&mut self, path: impl AsRef<Path>) -> Result<AssetHandle<ShaderAsset>> {
+        self.shaders.load(path)
+    }
+
+    pub fn reload_changed(
+        &mut self,
+        deletion_queue: &mut DeletionQueue,
+        frame: u64,
+    ) -> AssetChanges {
+        AssetChanges {
+            meshes: self.meshes.reload_changed(deletion_queue, frame),
+            textures: self.textures.reload_changed(deletion_queue, frame),
+            shaders: self.shaders.reload_changed(deletion_queue, frame),
+        }
+    }
+}
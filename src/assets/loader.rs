@@ -0,0 +1,477 @@
+use anyhow::Result;
+use ash::vk;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::assets::{Asset, MeshAsset, TextureAsset};
+use crate::jobs::JobSystem;
+use crate::vulkan::{
+    UploadBatchId, UploadBatcher, UploadStats, VulkanBuffer, VulkanDevice, VulkanImage,
+    VulkanPhysicalDevice,
+};
+
+/// Identifies one request made to an `AssetDecoder` or `AssetLoader`.
+pub type LoadId = u64;
+
+/// CPU data produced by a decode job.
+#[derive(Debug)]
+pub enum DecodedAsset {
+    Mesh(MeshAsset),
+    Texture(TextureAsset),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecodeKind {
+    Mesh,
+    Texture,
+}
+
+/// Result of one decode job, sent back to the thread that owns the decoder.
+pub struct DecodeResult {
+    pub id: LoadId,
+    pub path: PathBuf,
+    pub asset: Result<DecodedAsset>,
+}
+
+/// Reads and decodes asset files as jobs on a shared `JobSystem`.
+pub struct AssetDecoder {
+    jobs: Arc<JobSystem>,
+    sender: Sender<DecodeResult>,
+    results: Receiver<DecodeResult>,
+    next_id: LoadId,
+}
+
+impl AssetDecoder {
+    pub fn new(jobs: Arc<JobSystem>) -> Self {
+        let (sender, results) = mpsc::channel();
+
+        Self {
+            jobs,
+            sender,
+            results,
+            next_id: 0,
+        }
+    }
+
+    pub fn decode_mesh(&mut self, path: impl AsRef<Path>) -> LoadId {
+        self.push(path.as_ref(), DecodeKind::Mesh)
+    }
+
+    pub fn decode_texture(&mut self, path: impl AsRef<Path>) -> LoadId {
+        self.push(path.as_ref(), DecodeKind::Texture)
+    }
+
+    /// Returns every result finished since the last call without blocking.
+    pub fn drain(&self) -> Vec<DecodeResult> {
+        self.results.try_iter().collect()
+    }
+
+    /// Blocks until the next result is available.
+    pub fn wait(&self) -> Option<DecodeResult> {
+        self.results.recv().ok()
+    }
+
+    fn push(&mut self, path: &Path, kind: DecodeKind) -> LoadId {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let path = path.to_path_buf();
+        let sender = self.sender.clone();
+        self.jobs.spawn("decode asset", move || {
+            let asset = decode(&path, kind);
+            let _ = sender.send(DecodeResult { id, path, asset });
+        });
+
+        id
+    }
+}
+
+fn decode(path: &Path, kind: DecodeKind) -> Result<DecodedAsset> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+
+    match kind {
+        DecodeKind::Mesh => MeshAsset::load(path, &bytes).map(DecodedAsset::Mesh),
+        DecodeKind::Texture => TextureAsset::load(path, &bytes).map(DecodedAsset::Texture),
+    }
+}
+
+/// Counts for a loading screen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadProgress {
+    pub total: usize,
+    pub completed: usize,
+    pub failed: usize,
+}
+
+impl LoadProgress {
+    pub fn pending(&self) -> usize {
+        self.total - self.completed - self.failed
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pending() == 0
+    }
+
+    /// Finished share of all requests in `[0, 1]`; 1 when nothing was requested.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            (self.completed + self.failed) as f32 / self.total as f32
+        }
+    }
+}
+
+/// Device-local vertex and index buffers for a `MeshAsset`.
+pub struct GpuMesh {
+    pub vertex_buffer: VulkanBuffer,
+    pub index_buffer: VulkanBuffer,
+    /// `MeshAsset::tangents`, bound at `MATERIAL_TANGENT_BINDING` for `NORMAL_MAP` materials.
+    pub tangent_buffer: Option<VulkanBuffer>,
+    pub index_count: u32,
+}
+
+/// Sampled device-local image for a `TextureAsset`.
+pub struct GpuTexture {
+    pub image: VulkanImage,
+}
+
+enum Completion {
+    Mesh(GpuMesh, Box<dyn FnOnce(Result<GpuMesh>)>),
+    Texture(GpuTexture, Box<dyn FnOnce(Result<GpuTexture>)>),
+}
+
+enum Callback {
+    Mesh(Box<dyn FnOnce(Result<GpuMesh>)>),
+    Texture(Box<dyn FnOnce(Result<GpuTexture>)>),
+}
+
+impl Callback {
+    fn fail(self, error: anyhow::Error) {
+        match self {
+            Callback::Mesh(callback) => callback(Err(error)),
+            Callback::Texture(callback) => callback(Err(error)),
+        }
+    }
+}
+
+/// Streams meshes and textures in the background: files are read and decoded as jobs, and `update` batches the GPU uploads onto the transfer queue.
+///
+/// Callbacks run on the thread calling `update` once the upload has finished on the GPU, so
+/// the resources can be used right away. When uploads run on a dedicated transfer family,
+/// call `record_acquires` on the graphics command buffer of the frame before first use.
+pub struct AssetLoader {
+    decoder: AssetDecoder,
+    uploads: UploadBatcher,
+    callbacks: HashMap<LoadId, Callback>,
+    uploading: Vec<(UploadBatchId, Completion)>,
+    progress: LoadProgress,
+}
+
+impl AssetLoader {
+    pub fn new(device: &VulkanDevice, jobs: Arc<JobSystem>) -> Result<Self> {
+        Ok(Self {
+            decoder: AssetDecoder::new(jobs),
+            uploads: UploadBatcher::new(device)?,
+            callbacks: HashMap::new(),
+            uploading: Vec::new(),
+            progress: LoadProgress::default(),
+        })
+    }
+
+    pub fn load_mesh(
+        &mut self,
+        path: impl AsRef<Path>,
+        on_loaded: impl FnOnce(Result<GpuMesh>) + 'static,
+    ) -> LoadId {
+        let id = self.decoder.decode_mesh(path);
+        self.callbacks
+            .insert(id, Callback::Mesh(Box::new(on_loaded)));
+        self.progress.total += 1;
+        id
+    }
+
+    pub fn load_texture(
+        &mut self,
+        path: impl AsRef<Path>,
+        on_loaded: impl FnOnce(Result<GpuTexture>) + 'static,
+    ) -> LoadId {
+        let id = self.decoder.decode_texture(path);
+        self.callbacks
+            .insert(id, Callback::Texture(Box::new(on_loaded)));
+        self.progress.total += 1;
+        id
+    }
+
+    /// How much mesh data went straight into host-visible VRAM versus through staging.
+    pub fn upload_stats(&self) -> UploadStats {
+        self.uploads.stats()
+    }
+
+    pub fn progress(&self) -> LoadProgress {
+        self.progress
+    }
+
+    /// Queues uploads for every decoded asset, submits them as one batch and runs the
+    /// callbacks of batches the GPU has finished. Call once per frame.
+    pub fn update(
+        &mut self,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+    ) -> Result<()> {
+        for decoded in self.decoder.drain() {
+            let Some(callback) = self.callbacks.remove(&decoded.id) else {
+                continue;
+            };
+
+            let created = decoded.asset.and_then(|asset| {
+                self.stage(device, physical_device, asset).map_err(|e| {
+                    anyhow::anyhow!("Failed to upload {}: {}", decoded.path.display(), e)
+                })
+            });
+
+            match (created, callback) {
+                (Ok(Staged::Mesh(mesh)), Callback::Mesh(callback)) => self.uploading.push((
+                    self.uploads.current_batch(),
+                    Completion::Mesh(mesh, callback),
+                )),
+                (Ok(Staged::Texture(texture)), Callback::Texture(callback)) => {
+                    self.uploading.push((
+                        self.uploads.current_batch(),
+                        Completion::Texture(texture, callback),
+                    ))
+                }
+                (Ok(_), _) => unreachable!("decode kind matches the request"),
+                (Err(error), callback) => {
+                    self.progress.failed += 1;
+                    callback.fail(error);
+                }
+            }
+        }
+
+        self.uploads.submit()?;
+        let completed = self.uploads.poll()?;
+        self.finish(&completed);
+
+        Ok(())
+    }
+
+    /// Blocks until every requested asset has been decoded, uploaded and handed to its
+    /// callback.
+    pub fn wait_all(
+        &mut self,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+    ) -> Result<()> {
+        loop {
+            self.update(device, physical_device)?;
+            if self.callbacks.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        let completed = self.uploads.wait_idle()?;
+        self.finish(&completed);
+        Ok(())
+    }
+
+    /// See `UploadBatcher::record_acquires`.
+    pub fn record_acquires(&mut self, command_buffer: vk::CommandBuffer) {
+        self.uploads.record_acquires(command_buffer);
+    }
+
+    fn finish(&mut self, completed: &[UploadBatchId]) {
+        if completed.is_empty() {
+            return;
+        }
+
+        let (done, still_uploading) = std::mem::take(&mut self.uploading)
+            .into_iter()
+            .partition(|(batch, _)| completed.contains(batch));
+        self.uploading = still_uploading;
+
+        for (_, completion) in done {
+            self.progress.completed += 1;
+            match completion {
+                Completion::Mesh(mesh, callback) => callback(Ok(mesh)),
+                Completion::Texture(texture, callback) => callback(Ok(texture)),
+            }
+        }
+    }
+
+    fn stage(
+        &mut self,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        asset: DecodedAsset,
+    ) -> Result<Staged> {
+        match asset {
+            DecodedAsset::Mesh(mesh) => {
+                let vertex_buffer = VulkanBuffer::new_streaming(
+                    device,
+                    physical_device,
+                    std::mem::size_of_val(mesh.vertices.as_slice()).max(4) as vk::DeviceSize,
+                    vk::BufferUsageFlags::VERTEX_BUFFER
+                        | vk::BufferUsageFlags::STORAGE_BUFFER
+                        | vk::BufferUsageFlags::TRANSFER_DST,
+                )?;
+                let index_buffer = VulkanBuffer::new_streaming(
+                    device,
+                    physical_device,
+                    std::mem::size_of_val(mesh.indices.as_slice()).max(4) as vk::DeviceSize,
+                    vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+                )?;
+
+                self.uploads.upload_buffer(
+                    physical_device,
+                    device,
+                    &vertex_buffer,
+                    &mesh.vertices,
+                    vk::PipelineStageFlags::VERTEX_INPUT,
+                    vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+                )?;
+                self.uploads.upload_buffer(
+                    physical_device,
+                    device,
+                    &index_buffer,
+                    &mesh.indices,
+                    vk::PipelineStageFlags::VERTEX_INPUT,
+                    vk::AccessFlags::INDEX_READ,
+                )?;
+
+                let tangent_buffer = if mesh.tangents.is_empty() {
+                    None
+                } else {
+                    let tangent_buffer = VulkanBuffer::new_streaming(
+                        device,
+                        physical_device,
+                        std::mem::size_of_val(mesh.tangents.as_slice()) as vk::DeviceSize,
+                        vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+                    )?;
+                    self.uploads.upload_buffer(
+                        physical_device,
+                        device,
+                        &tangent_buffer,
+                        &mesh.tangents,
+                        vk::PipelineStageFlags::VERTEX_INPUT,
+                        vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+                    )?;
+                    Some(tangent_buffer)
+                };
+
+                Ok(Staged::Mesh(GpuMesh {
+                    vertex_buffer,
+                    index_buffer,
+                    tangent_buffer,
+                    index_count: mesh.indices.len() as u32,
+                }))
+            }
+            DecodedAsset::Texture(texture) => {
+                let image = VulkanImage::new(
+                    device,
+                    physical_device,
+                    vk::Extent2D {
+                        width: texture.width,
+                        height: texture.height,
+                    },
+                    texture.format,
+                    vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+                    vk::ImageAspectFlags::COLOR,
+                )?;
+                self.uploads
+                    .upload_image(physical_device, device, &image, &texture.data)?;
+
+                Ok(Staged::Texture(GpuTexture { image }))
+            }
+        }
+    }
+}
+
+enum Staged {
+    Mesh(GpuMesh),
+    Texture(GpuTexture),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp(name: &str, contents: &[u8]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rve-loader-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn wait_for(decoder: &AssetDecoder, count: usize) -> Vec<DecodeResult> {
+        let mut results: Vec<_> = (0..count).map(|_| decoder.wait().unwrap()).collect();
+        results.sort_by_key(|result| result.id);
+        results
+    }
+
+    #[test]
+    fn decodes_meshes_and_textures_on_workers() {
+        let obj = write_temp("tri.obj", b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n");
+        let ppm = write_temp("pixel.ppm", b"P6\n1 1\n255\n\xff\x00\x00");
+
+        let mut decoder = AssetDecoder::new(Arc::new(JobSystem::new(2).unwrap()));
+        let mesh_id = decoder.decode_mesh(&obj);
+        let texture_id = decoder.decode_texture(&ppm);
+        let results = wait_for(&decoder, 2);
+
+        assert_eq!(results[0].id, mesh_id);
+        match &results[0].asset {
+            Ok(DecodedAsset::Mesh(mesh)) => assert_eq!(mesh.indices.len(), 3),
+            other => panic!("expected mesh, got {:?}", other.as_ref().err()),
+        }
+        assert_eq!(results[1].id, texture_id);
+        match &results[1].asset {
+            Ok(DecodedAsset::Texture(texture)) => {
+                assert_eq!(texture.data, vec![255, 0, 0, 255]);
+            }
+            other => panic!("expected texture, got {:?}", other.as_ref().err()),
+        }
+    }
+
+    #[test]
+    fn missing_files_report_errors() {
+        let mut decoder = AssetDecoder::new(Arc::new(JobSystem::new(1).unwrap()));
+        let id = decoder.decode_mesh("does/not/exist.obj");
+        let result = decoder.wait().unwrap();
+
+        assert_eq!(result.id, id);
+        assert!(result.asset.is_err());
+    }
+
+    #[test]
+    fn many_jobs_all_complete() {
+        let obj = write_temp("many.obj", b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n");
+        let mut decoder = AssetDecoder::new(Arc::new(JobSystem::new(3).unwrap()));
+        let ids: Vec<_> = (0..16).map(|_| decoder.decode_mesh(&obj)).collect();
+        let results = wait_for(&decoder, ids.len());
+
+        assert_eq!(results.iter().map(|r| r.id).collect::<Vec<_>>(), ids);
+        assert!(results.iter().all(|r| r.asset.is_ok()));
+    }
+
+    #[test]
+    fn progress_counts() {
+        let progress = LoadProgress {
+            total: 4,
+            completed: 2,
+            failed: 1,
+        };
+        assert_eq!(progress.pending(), 1);
+        assert!(!progress.is_done());
+        assert!((progress.fraction() - 0.75).abs() < 1e-6);
+
+        let empty = LoadProgress::default();
+        assert!(empty.is_done());
+        assert_eq!(empty.fraction(), 1.0);
+    }
+}
@@ -0,0 +1,13 @@
+pub mod asset;
+pub mod database;
+pub mod loader;
+pub mod pack;
+pub mod store;
+pub mod texture_compression;
+
+pub use asset::*;
+pub use database::*;
+pub use loader::*;
+pub use pack::*;
+pub use store::*;
+pub use texture_compression::*;
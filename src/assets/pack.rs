@@ -0,0 +1,459 @@
+use anyhow::Result;
+use ash::vk;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::assets::{MeshAsset, ShaderAsset, TextureAsset};
+use crate::renderer::GpuVertex;
+
+pub const ASSET_PACK_MAGIC: [u8; 8] = *b"RVEPACK\0";
+pub const ASSET_PACK_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetKind {
+    Mesh,
+    Texture,
+    Shader,
+}
+
+impl AssetKind {
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Mesh => 0,
+            Self::Texture => 1,
+            Self::Shader => 2,
+        }
+    }
+
+    fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Self::Mesh),
+            1 => Ok(Self::Texture),
+            2 => Ok(Self::Shader),
+            _ => Err(anyhow::anyhow!("Unknown asset kind {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetPackEntry {
+    pub name: String,
+    pub kind: AssetKind,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// Builds an asset bundle with payloads already in GPU upload layout.
+///
+/// Layout, all little endian: magic, version, entry count, then per entry a length-prefixed
+/// UTF-8 name, kind, payload offset and size, then the payloads. Meshes are `GpuVertex`
+/// arrays followed by `u32` indices and, when present, one `[f32; 4]` tangent per vertex; textures are ready-to-copy texel data with their
+/// `vk::Format`, shaders are SPIR-V words.
+#[derive(Default)]
+pub struct AssetPackWriter {
+    entries: Vec<(String, AssetKind, Vec<u8>)>,
+}
+
+impl AssetPackWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_mesh(&mut self, name: &str, mesh: &MeshAsset) -> Result<()> {
+        if !mesh.tangents.is_empty() && mesh.tangents.len() != mesh.vertices.len() {
+            return Err(anyhow::anyhow!(
+                "Mesh {} has {} tangents for {} vertices",
+                name,
+                mesh.tangents.len(),
+                mesh.vertices.len()
+            ));
+        }
+
+        let mut payload = Vec::with_capacity(
+            12 + std::mem::size_of_val(mesh.vertices.as_slice())
+                + mesh.indices.len() * 4
+                + std::mem::size_of_val(mesh.tangents.as_slice()),
+        );
+        payload.extend_from_slice(&(mesh.vertices.len() as u32).to_le_bytes());
+        payload.extend_from_slice(&(mesh.indices.len() as u32).to_le_bytes());
+        payload.extend_from_slice(&(mesh.tangents.len() as u32).to_le_bytes());
+        for vertex in &mesh.vertices {
+            for value in vertex
+                .position
+                .iter()
+                .chain(&vertex.normal)
+                .chain(&vertex.uv)
+            {
+                payload.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        for index in &mesh.indices {
+            payload.extend_from_slice(&index.to_le_bytes());
+        }
+        for value in mesh.tangents.iter().flatten() {
+            payload.extend_from_slice(&value.to_le_bytes());
+        }
+        self.add(name, AssetKind::Mesh, payload)
+    }
+
+    pub fn add_texture(&mut self, name: &str, texture: &TextureAsset) -> Result<()> {
+        let mut payload = Vec::with_capacity(12 + texture.data.len());
+        payload.extend_from_slice(&texture.width.to_le_bytes());
+        payload.extend_from_slice(&texture.height.to_le_bytes());
+        payload.extend_from_slice(&texture.format.as_raw().to_le_bytes());
+        payload.extend_from_slice(&texture.data);
+        self.add(name, AssetKind::Texture, payload)
+    }
+
+    pub fn add_shader(&mut self, name: &str, shader: &ShaderAsset) -> Result<()> {
+        let mut payload = Vec::with_capacity(4 + shader.code.len() * 4);
+        payload.extend_from_slice(&shader.stage.as_raw().to_le_bytes());
+        for word in &shader.code {
+            payload.extend_from_slice(&word.to_le_bytes());
+        }
+        self.add(name, AssetKind::Shader, payload)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let table_size: usize = self
+            .entries
+            .iter()
+            .map(|(name, _, _)| 2 + name.len() + 1 + 16)
+            .sum();
+        let mut offset = (16 + table_size) as u64;
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&ASSET_PACK_MAGIC);
+        bytes.extend_from_slice(&ASSET_PACK_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+
+        for (name, kind, payload) in &self.entries {
+            bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.push(kind.to_u8());
+            bytes.extend_from_slice(&offset.to_le_bytes());
+            bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+            offset += payload.len() as u64;
+        }
+
+        for (_, _, payload) in &self.entries {
+            bytes.extend_from_slice(payload);
+        }
+
+        bytes
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_bytes())
+            .map_err(|e| anyhow::anyhow!("Failed to write asset pack {}: {}", path.display(), e))
+    }
+
+    fn add(&mut self, name: &str, kind: AssetKind, payload: Vec<u8>) -> Result<()> {
+        if name.len() > u16::MAX as usize {
+            return Err(anyhow::anyhow!("Asset name too long: {}", name));
+        }
+        if self.entries.iter().any(|(existing, _, _)| existing == name) {
+            return Err(anyhow::anyhow!("Duplicate asset name {}", name));
+        }
+        self.entries.push((name.to_string(), kind, payload));
+        Ok(())
+    }
+}
+
+/// Runtime reader for bundles written by `AssetPackWriter`. The whole file is read once;
+/// assets are decoded on request without any text or image parsing.
+pub struct AssetPack {
+    bytes: Vec<u8>,
+    entries: Vec<AssetPackEntry>,
+    by_name: HashMap<String, usize>,
+}
+
+impl AssetPack {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read asset pack {}: {}", path.display(), e))?;
+        Self::from_bytes(bytes)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        let mut reader = ByteReader::new(&bytes);
+
+        if reader.take(8)? != ASSET_PACK_MAGIC {
+            return Err(anyhow::anyhow!("Not an asset pack"));
+        }
+        let version = reader.u32()?;
+        if version != ASSET_PACK_VERSION {
+            return Err(anyhow::anyhow!(
+                "Unsupported asset pack version {}",
+                version
+            ));
+        }
+
+        let count = reader.u32()?;
+        let mut entries = Vec::with_capacity(count as usize);
+        let mut by_name = HashMap::new();
+
+        for index in 0..count as usize {
+            let name_len = reader.u16()? as usize;
+            let name = std::str::from_utf8(reader.take(name_len)?)
+                .map_err(|e| anyhow::anyhow!("Invalid asset name: {}", e))?
+                .to_string();
+            let kind = AssetKind::from_u8(reader.u8()?)?;
+            let offset = reader.u64()?;
+            let size = reader.u64()?;
+
+            if offset
+                .checked_add(size)
+                .is_none_or(|end| end > bytes.len() as u64)
+            {
+                return Err(anyhow::anyhow!("Asset {} is out of bounds", name));
+            }
+
+            by_name.insert(name.clone(), index);
+            entries.push(AssetPackEntry {
+                name,
+                kind,
+                offset,
+                size,
+            });
+        }
+
+        Ok(Self {
+            bytes,
+            entries,
+            by_name,
+        })
+    }
+
+    pub fn entries(&self) -> &[AssetPackEntry] {
+        &self.entries
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&AssetPackEntry> {
+        self.by_name.get(name).map(|&index| &self.entries[index])
+    }
+
+    /// Raw payload of an entry.
+    pub fn payload(&self, name: &str, kind: AssetKind) -> Result<&[u8]> {
+        let entry = self
+            .entry(name)
+            .ok_or_else(|| anyhow::anyhow!("Asset {} not found in pack", name))?;
+        if entry.kind != kind {
+            return Err(anyhow::anyhow!(
+                "Asset {} is a {:?}, not a {:?}",
+                name,
+                entry.kind,
+                kind
+            ));
+        }
+        Ok(&self.bytes[entry.offset as usize..(entry.offset + entry.size) as usize])
+    }
+
+    pub fn mesh(&self, name: &str) -> Result<MeshAsset> {
+        let mut reader = ByteReader::new(self.payload(name, AssetKind::Mesh)?);
+        let vertex_count = reader.u32()? as usize;
+        let index_count = reader.u32()? as usize;
+        let tangent_count = reader.u32()? as usize;
+        if tangent_count != 0 && tangent_count != vertex_count {
+            return Err(anyhow::anyhow!(
+                "Mesh {} has {} tangents for {} vertices",
+                name,
+                tangent_count,
+                vertex_count
+            ));
+        }
+
+        let vertices = (0..vertex_count)
+            .map(|_| {
+                let mut values = [0.0f32; 8];
+                for value in &mut values {
+                    *value = reader.f32()?;
+                }
+                Ok(GpuVertex {
+                    position: [values[0], values[1], values[2]],
+                    normal: [values[3], values[4], values[5]],
+                    uv: [values[6], values[7]],
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let indices = (0..index_count)
+            .map(|_| reader.u32())
+            .collect::<Result<Vec<_>>>()?;
+        let tangents = (0..tangent_count)
+            .map(|_| Ok([reader.f32()?, reader.f32()?, reader.f32()?, reader.f32()?]))
+            .collect::<Result<Vec<_>>>()?;
+
+        if indices.iter().any(|&i| i as usize >= vertex_count) {
+            return Err(anyhow::anyhow!("Mesh {} has out of range indices", name));
+        }
+
+        Ok(MeshAsset {
+            vertices,
+            indices,
+            tangents,
+        })
+    }
+
+    pub fn texture(&self, name: &str) -> Result<TextureAsset> {
+        let mut reader = ByteReader::new(self.payload(name, AssetKind::Texture)?);
+        let width = reader.u32()?;
+        let height = reader.u32()?;
+        let format = vk::Format::from_raw(reader.u32()? as i32);
+
+        Ok(TextureAsset {
+            width,
+            height,
+            format,
+            data: reader.rest().to_vec(),
+        })
+    }
+
+    pub fn shader(&self, name: &str) -> Result<ShaderAsset> {
+        let mut reader = ByteReader::new(self.payload(name, AssetKind::Shader)?);
+        let stage = vk::ShaderStageFlags::from_raw(reader.u32()?);
+        ShaderAsset::from_spirv(stage, reader.rest())
+    }
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    cursor: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, cursor: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let slice = self
+            .bytes
+            .get(self.cursor..self.cursor + len)
+            .ok_or_else(|| anyhow::anyhow!("Unexpected end of asset pack data"))?;
+        self.cursor += len;
+        Ok(slice)
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.bytes[self.cursor..]
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::SPIRV_MAGIC;
+
+    fn sample_pack() -> Vec<u8> {
+        let mut writer = AssetPackWriter::new();
+        writer
+            .add_mesh(
+                "triangle",
+                &MeshAsset {
+                    vertices: vec![
+                        GpuVertex {
+                            position: [0.0, 1.0, 2.0],
+                            normal: [0.0, 0.0, 1.0],
+                            uv: [0.5, 0.25],
+                        };
+                        3
+                    ],
+                    indices: vec![0, 1, 2],
+                    tangents: vec![[1.0, 0.0, 0.0, -1.0]; 3],
+                },
+            )
+            .unwrap();
+        writer
+            .add_texture(
+                "white",
+                &TextureAsset {
+                    width: 1,
+                    height: 1,
+                    format: vk::Format::R8G8B8A8_SRGB,
+                    data: vec![255; 4],
+                },
+            )
+            .unwrap();
+        writer
+            .add_shader(
+                "lit.frag",
+                &ShaderAsset {
+                    stage: vk::ShaderStageFlags::FRAGMENT,
+                    code: vec![SPIRV_MAGIC, 0x10000],
+                },
+            )
+            .unwrap();
+        writer.to_bytes()
+    }
+
+    #[test]
+    fn round_trips_every_kind() {
+        let pack = AssetPack::from_bytes(sample_pack()).unwrap();
+
+        assert_eq!(pack.entries().len(), 3);
+
+        let mesh = pack.mesh("triangle").unwrap();
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+        assert_eq!(mesh.vertices[2].uv, [0.5, 0.25]);
+        assert_eq!(mesh.tangents, vec![[1.0, 0.0, 0.0, -1.0]; 3]);
+
+        let texture = pack.texture("white").unwrap();
+        assert_eq!(texture.format, vk::Format::R8G8B8A8_SRGB);
+        assert_eq!(texture.data, vec![255; 4]);
+
+        let shader = pack.shader("lit.frag").unwrap();
+        assert_eq!(shader.stage, vk::ShaderStageFlags::FRAGMENT);
+        assert_eq!(shader.code, vec![SPIRV_MAGIC, 0x10000]);
+    }
+
+    #[test]
+    fn rejects_wrong_kind_and_missing_names() {
+        let pack = AssetPack::from_bytes(sample_pack()).unwrap();
+
+        assert!(pack.texture("triangle").is_err());
+        assert!(pack.mesh("missing").is_err());
+    }
+
+    #[test]
+    fn rejects_duplicate_names() {
+        let mut writer = AssetPackWriter::new();
+        let mesh = MeshAsset::default();
+        writer.add_mesh("a", &mesh).unwrap();
+        assert!(writer.add_mesh("a", &mesh).is_err());
+    }
+
+    #[test]
+    fn rejects_corrupt_packs() {
+        let bytes = sample_pack();
+
+        assert!(AssetPack::from_bytes(b"NOTAPACK".to_vec()).is_err());
+        assert!(AssetPack::from_bytes(bytes[..20].to_vec()).is_err());
+        assert!(AssetPack::from_bytes(bytes[..bytes.len() - 1].to_vec()).is_err());
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[8] = 99;
+        assert!(AssetPack::from_bytes(wrong_version).is_err());
+    }
+}
@@ -0,0 +1,397 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::assets::Asset;
+use crate::vulkan::DeletionQueue;
+
+/// Typed handle into an `AssetStore`. Stale handles of released assets stop resolving once
+/// their slot is reused.
+pub struct AssetHandle<T> {
+    index: u32,
+    generation: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> AssetHandle<T> {
+    fn new(index: usize, generation: u32) -> Self {
+        Self {
+            index: index as u32,
+            generation,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for AssetHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for AssetHandle<T> {}
+
+impl<T> PartialEq for AssetHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for AssetHandle<T> {}
+
+impl<T> std::hash::Hash for AssetHandle<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> std::fmt::Debug for AssetHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AssetHandle({}v{})", self.index, self.generation)
+    }
+}
+
+struct AssetEntry<T> {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    ref_count: u32,
+    version: u32,
+    asset: T,
+}
+
+#[derive(Default)]
+struct AssetSlot<T> {
+    generation: u32,
+    entry: Option<AssetEntry<T>>,
+}
+
+/// Result of `AssetStore::reload_changed`.
+pub struct AssetReloadReport<T> {
+    pub reloaded: Vec<AssetHandle<T>>,
+    /// Assets whose file changed but failed to load; they keep their previous contents.
+    pub failed: Vec<(AssetHandle<T>, anyhow::Error)>,
+}
+
+impl<T> Default for AssetReloadReport<T> {
+    fn default() -> Self {
+        Self {
+            reloaded: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+}
+
+/// Handle-based storage for one asset type, keyed by canonical path so an asset shared by
+/// several materials is loaded once and reference counted.
+pub struct AssetStore<T: Asset> {
+    slots: Vec<AssetSlot<T>>,
+    by_path: HashMap<PathBuf, usize>,
+}
+
+impl<T: Asset> Default for AssetStore<T> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            by_path: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Asset> AssetStore<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the asset at `path`, or adds a reference to it if it is already loaded.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<AssetHandle<T>> {
+        let path = canonical_path(path.as_ref())?;
+
+        if let Some(&index) = self.by_path.get(&path) {
+            let slot = &mut self.slots[index];
+            if let Some(entry) = slot.entry.as_mut() {
+                entry.ref_count += 1;
+                return Ok(AssetHandle::new(index, slot.generation));
+            }
+        }
+
+        let (asset, modified) = read_asset::<T>(&path)?;
+        Ok(self.insert(path, asset, modified))
+    }
+
+    /// Adds an asset that does not come from a file, e.g. generated geometry. It is never
+    /// reloaded.
+    pub fn insert_generated(&mut self, asset: T) -> AssetHandle<T> {
+        let index = self.free_slot();
+        let slot = &mut self.slots[index];
+        slot.entry = Some(AssetEntry {
+            path: PathBuf::new(),
+            modified: None,
+            ref_count: 1,
+            version: 0,
+            asset,
+        });
+        AssetHandle::new(index, slot.generation)
+    }
+
+    pub fn get(&self, handle: AssetHandle<T>) -> Option<&T> {
+        self.entry(handle).map(|entry| &entry.asset)
+    }
+
+    /// Incremented on every reload, so users can rebuild GPU resources derived from the asset.
+    pub fn version(&self, handle: AssetHandle<T>) -> Option<u32> {
+        self.entry(handle).map(|entry| entry.version)
+    }
+
+    pub fn path(&self, handle: AssetHandle<T>) -> Option<&Path> {
+        self.entry(handle).map(|entry| entry.path.as_path())
+    }
+
+    pub fn handle_for_path(&self, path: impl AsRef<Path>) -> Option<AssetHandle<T>> {
+        let path = canonical_path(path.as_ref()).ok()?;
+        let &index = self.by_path.get(&path)?;
+        Some(AssetHandle::new(index, self.slots[index].generation))
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|slot| slot.entry.is_some())
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops one reference. The last release retires the asset to `deletion_queue` during
+    /// `frame`, so GPU resources it owns outlive the frames still using them.
+    pub fn release(
+        &mut self,
+        handle: AssetHandle<T>,
+        deletion_queue: &mut DeletionQueue,
+        frame: u64,
+    ) {
+        let Some(slot) = self
+            .slots
+            .get_mut(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)
+        else {
+            return;
+        };
+        let Some(entry) = slot.entry.as_mut() else {
+            return;
+        };
+
+        entry.ref_count -= 1;
+        if entry.ref_count > 0 {
+            return;
+        }
+
+        if let Some(entry) = slot.entry.take() {
+            slot.generation = slot.generation.wrapping_add(1);
+            self.by_path.remove(&entry.path);
+            deletion_queue.retire(frame, entry.asset);
+        }
+    }
+
+    /// Reloads every asset whose file modification time changed. Replaced assets are retired
+    /// to `deletion_queue` during `frame`; handles stay valid.
+    pub fn reload_changed(
+        &mut self,
+        deletion_queue: &mut DeletionQueue,
+        frame: u64,
+    ) -> AssetReloadReport<T> {
+        let mut report = AssetReloadReport::default();
+
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            let Some(entry) = slot.entry.as_mut() else {
+                continue;
+            };
+            if entry.modified.is_none() {
+                continue;
+            }
+
+            let modified = std::fs::metadata(&entry.path)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            if modified.is_none() || modified == entry.modified {
+                continue;
+            }
+
+            let handle = AssetHandle::new(index, slot.generation);
+            // Record the new time even on failure so a broken file is not retried every poll.
+            entry.modified = modified;
+
+            match read_asset::<T>(&entry.path) {
+                Ok((asset, _)) => {
+                    let previous = std::mem::replace(&mut entry.asset, asset);
+                    deletion_queue.retire(frame, previous);
+                    entry.version += 1;
+                    report.reloaded.push(handle);
+                }
+                Err(e) => report.failed.push((handle, e)),
+            }
+        }
+
+        report
+    }
+
+    fn entry(&self, handle: AssetHandle<T>) -> Option<&AssetEntry<T>> {
+        self.slots
+            .get(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.entry.as_ref())
+    }
+
+    fn free_slot(&mut self) -> usize {
+        match self.slots.iter().position(|slot| slot.entry.is_none()) {
+            Some(index) => index,
+            None => {
+                self.slots.push(AssetSlot {
+                    generation: 0,
+                    entry: None,
+                });
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    fn insert(&mut self, path: PathBuf, asset: T, modified: Option<SystemTime>) -> AssetHandle<T> {
+        let index = self.free_slot();
+        self.by_path.insert(path.clone(), index);
+
+        let slot = &mut self.slots[index];
+        slot.entry = Some(AssetEntry {
+            path,
+            modified,
+            ref_count: 1,
+            version: 0,
+            asset,
+        });
+        AssetHandle::new(index, slot.generation)
+    }
+}
+
+fn canonical_path(path: &Path) -> Result<PathBuf> {
+    path.canonicalize()
+        .map_err(|e| anyhow::anyhow!("Failed to resolve asset {}: {}", path.display(), e))
+}
+
+fn read_asset<T: Asset>(path: &Path) -> Result<(T, Option<SystemTime>)> {
+    let modified = std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok();
+    let bytes = std::fs::read(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read asset {}: {}", path.display(), e))?;
+    let asset = T::load(path, &bytes)
+        .map_err(|e| anyhow::anyhow!("Failed to load asset {}: {}", path.display(), e))?;
+    Ok((asset, modified))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[derive(Debug, PartialEq)]
+    struct Text(String);
+
+    impl Asset for Text {
+        fn load(_path: &Path, bytes: &[u8]) -> Result<Self> {
+            let text = String::from_utf8(bytes.to_vec())?;
+            if text == "bad" {
+                return Err(anyhow::anyhow!("bad asset"));
+            }
+            Ok(Self(text))
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("rve-asset-store-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_with_time(path: &Path, contents: &str, seconds: u64) {
+        std::fs::write(path, contents).unwrap();
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    #[test]
+    fn deduplicates_by_path() {
+        let dir = temp_dir("dedup");
+        let path = dir.join("a.txt");
+        write_with_time(&path, "a", 1);
+
+        let mut store = AssetStore::<Text>::new();
+        let first = store.load(&path).unwrap();
+        let second = store.load(dir.join(".").join("a.txt")).unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.handle_for_path(&path), Some(first));
+    }
+
+    #[test]
+    fn release_retires_after_last_reference() {
+        let dir = temp_dir("release");
+        let path = dir.join("a.txt");
+        write_with_time(&path, "a", 1);
+
+        let mut store = AssetStore::<Text>::new();
+        let mut queue = DeletionQueue::new();
+        let handle = store.load(&path).unwrap();
+        store.load(&path).unwrap();
+
+        store.release(handle, &mut queue, 0);
+        assert!(store.get(handle).is_some());
+        assert!(queue.is_empty());
+
+        store.release(handle, &mut queue, 0);
+        assert!(store.get(handle).is_none());
+        assert_eq!(queue.len(), 1);
+
+        let reloaded = store.load(&path).unwrap();
+        assert_ne!(reloaded, handle);
+        assert!(store.get(handle).is_none());
+    }
+
+    #[test]
+    fn reloads_changed_files_in_place() {
+        let dir = temp_dir("reload");
+        let path = dir.join("a.txt");
+        write_with_time(&path, "one", 1);
+
+        let mut store = AssetStore::<Text>::new();
+        let mut queue = DeletionQueue::new();
+        let handle = store.load(&path).unwrap();
+
+        assert!(store.reload_changed(&mut queue, 0).reloaded.is_empty());
+
+        write_with_time(&path, "two", 2);
+        let report = store.reload_changed(&mut queue, 3);
+        assert_eq!(report.reloaded, vec![handle]);
+        assert_eq!(store.get(handle), Some(&Text("two".into())));
+        assert_eq!(store.version(handle), Some(1));
+        assert_eq!(queue.len(), 1);
+
+        write_with_time(&path, "bad", 3);
+        let report = store.reload_changed(&mut queue, 4);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(store.get(handle), Some(&Text("two".into())));
+        assert!(store.reload_changed(&mut queue, 5).failed.is_empty());
+    }
+
+    #[test]
+    fn missing_file_fails_to_load() {
+        let mut store = AssetStore::<Text>::new();
+        assert!(store.load("/nonexistent/asset.txt").is_err());
+    }
+}
@@ -0,0 +1,259 @@
+use crate::assets::TextureAsset;
+use ash::vk;
+
+/// Encodes RGBA8 pixels into BC1 (DXT1) blocks, ignoring alpha. Edge blocks of textures whose
+/// size is not a multiple of 4 repeat their last row and column.
+pub fn compress_bc1(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let blocks_x = width.div_ceil(4);
+    let blocks_y = height.div_ceil(4);
+    let mut output = Vec::with_capacity((blocks_x * blocks_y * 8) as usize);
+
+    for block_y in 0..blocks_y {
+        for block_x in 0..blocks_x {
+            let mut block = [[0.0f32; 3]; 16];
+            for (i, texel) in block.iter_mut().enumerate() {
+                let x = (block_x * 4 + i as u32 % 4).min(width - 1);
+                let y = (block_y * 4 + i as u32 / 4).min(height - 1);
+                let offset = ((y * width + x) * 4) as usize;
+                *texel = [
+                    rgba[offset] as f32,
+                    rgba[offset + 1] as f32,
+                    rgba[offset + 2] as f32,
+                ];
+            }
+            output.extend_from_slice(&encode_bc1_block(&block));
+        }
+    }
+
+    output
+}
+
+/// Decodes BC1 blocks back to RGBA8, for tests and tooling.
+pub fn decompress_bc1(width: u32, height: u32, blocks: &[u8]) -> Vec<u8> {
+    let blocks_x = width.div_ceil(4);
+    let mut rgba = vec![0u8; (width * height * 4) as usize];
+
+    for (index, block) in blocks.chunks_exact(8).enumerate() {
+        let block_x = index as u32 % blocks_x;
+        let block_y = index as u32 / blocks_x;
+        let palette = bc1_palette(
+            u16::from_le_bytes([block[0], block[1]]),
+            u16::from_le_bytes([block[2], block[3]]),
+        );
+        let selectors = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+        for i in 0..16u32 {
+            let (x, y) = (block_x * 4 + i % 4, block_y * 4 + i / 4);
+            if x >= width || y >= height {
+                continue;
+            }
+            let color = palette[((selectors >> (i * 2)) & 3) as usize];
+            let offset = ((y * width + x) * 4) as usize;
+            rgba[offset..offset + 4].copy_from_slice(&[color[0], color[1], color[2], 255]);
+        }
+    }
+
+    rgba
+}
+
+impl TextureAsset {
+    /// BC1-compressed copy of an RGBA8 texture, or `None` for other formats.
+    pub fn to_bc1(&self) -> Option<Self> {
+        let format = match self.format {
+            vk::Format::R8G8B8A8_SRGB => vk::Format::BC1_RGB_SRGB_BLOCK,
+            vk::Format::R8G8B8A8_UNORM => vk::Format::BC1_RGB_UNORM_BLOCK,
+            _ => return None,
+        };
+
+        Some(Self {
+            width: self.width,
+            height: self.height,
+            format,
+            data: compress_bc1(self.width, self.height, &self.data),
+        })
+    }
+}
+
+fn to_565(color: [f32; 3]) -> u16 {
+    let r = (color[0].clamp(0.0, 255.0) * 31.0 / 255.0).round() as u16;
+    let g = (color[1].clamp(0.0, 255.0) * 63.0 / 255.0).round() as u16;
+    let b = (color[2].clamp(0.0, 255.0) * 31.0 / 255.0).round() as u16;
+    (r << 11) | (g << 5) | b
+}
+
+fn from_565(color: u16) -> [u8; 3] {
+    let r = (color >> 11) & 31;
+    let g = (color >> 5) & 63;
+    let b = color & 31;
+    [
+        ((r << 3) | (r >> 2)) as u8,
+        ((g << 2) | (g >> 4)) as u8,
+        ((b << 3) | (b >> 2)) as u8,
+    ]
+}
+
+fn bc1_palette(color0: u16, color1: u16) -> [[u8; 3]; 4] {
+    let (c0, c1) = (from_565(color0), from_565(color1));
+    let mix = |a: u8, b: u8, wa: u16, wb: u16, d: u16| ((a as u16 * wa + b as u16 * wb) / d) as u8;
+
+    if color0 > color1 {
+        [
+            c0,
+            c1,
+            std::array::from_fn(|i| mix(c0[i], c1[i], 2, 1, 3)),
+            std::array::from_fn(|i| mix(c0[i], c1[i], 1, 2, 3)),
+        ]
+    } else {
+        [
+            c0,
+            c1,
+            std::array::from_fn(|i| mix(c0[i], c1[i], 1, 1, 2)),
+            [0, 0, 0],
+        ]
+    }
+}
+
+/// Endpoints at the extremes of the block along its principal axis, then nearest-palette
+/// selectors.
+fn encode_bc1_block(block: &[[f32; 3]; 16]) -> [u8; 8] {
+    let mean = block
+        .iter()
+        .fold([0.0; 3], |acc, c| {
+            [acc[0] + c[0], acc[1] + c[1], acc[2] + c[2]]
+        })
+        .map(|v| v / 16.0);
+
+    // Power iteration on the covariance matrix for the principal axis.
+    let mut covariance = [[0.0f32; 3]; 3];
+    for color in block {
+        let d = [color[0] - mean[0], color[1] - mean[1], color[2] - mean[2]];
+        for (row, covariance_row) in covariance.iter_mut().enumerate() {
+            for (column, value) in covariance_row.iter_mut().enumerate() {
+                *value += d[row] * d[column];
+            }
+        }
+    }
+    let mut axis = [1.0f32, 1.0, 1.0];
+    for _ in 0..8 {
+        let next: [f32; 3] = std::array::from_fn(|row| {
+            (0..3)
+                .map(|column| covariance[row][column] * axis[column])
+                .sum()
+        });
+        let length = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if length < 1e-6 {
+            break;
+        }
+        axis = next.map(|v| v / length);
+    }
+
+    let project = |c: &[f32; 3]| {
+        (c[0] - mean[0]) * axis[0] + (c[1] - mean[1]) * axis[1] + (c[2] - mean[2]) * axis[2]
+    };
+    let (min_t, max_t) = block
+        .iter()
+        .map(project)
+        .fold((f32::MAX, f32::MIN), |(lo, hi), t| (lo.min(t), hi.max(t)));
+    let endpoint = |t: f32| std::array::from_fn(|i| mean[i] + axis[i] * t);
+
+    let mut color0 = to_565(endpoint(max_t));
+    let mut color1 = to_565(endpoint(min_t));
+    if color0 < color1 {
+        std::mem::swap(&mut color0, &mut color1);
+    }
+
+    let palette = bc1_palette(color0, color1);
+    let colors = if color0 == color1 { 1 } else { 4 };
+    let mut selectors = 0u32;
+    for (i, texel) in block.iter().enumerate() {
+        let best = (0..colors)
+            .min_by(|&a, &b| {
+                let distance = |p: usize| {
+                    (0..3)
+                        .map(|c| (palette[p][c] as f32 - texel[c]).powi(2))
+                        .sum::<f32>()
+                };
+                distance(a).total_cmp(&distance(b))
+            })
+            .unwrap_or(0);
+        selectors |= (best as u32) << (i * 2);
+    }
+
+    let mut output = [0u8; 8];
+    output[0..2].copy_from_slice(&color0.to_le_bytes());
+    output[2..4].copy_from_slice(&color1.to_le_bytes());
+    output[4..8].copy_from_slice(&selectors.to_le_bytes());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        (0..width * height)
+            .flat_map(|i| {
+                let (x, y) = (i % width, i / width);
+                [(x * 255 / width) as u8, (y * 255 / height) as u8, 64, 255]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn solid_block_round_trips() {
+        let rgba = [200u8, 100, 50, 255].repeat(16);
+        let blocks = compress_bc1(4, 4, &rgba);
+
+        assert_eq!(blocks.len(), 8);
+        let decoded = decompress_bc1(4, 4, &blocks);
+        for texel in decoded.chunks_exact(4) {
+            assert!(texel[0].abs_diff(200) <= 4);
+            assert!(texel[1].abs_diff(100) <= 2);
+            assert!(texel[2].abs_diff(50) <= 4);
+        }
+    }
+
+    #[test]
+    fn linear_gradient_error_is_bounded() {
+        // One color line per block, which BC1 represents well.
+        let (width, height) = (16, 16);
+        let rgba: Vec<u8> = (0..width * height)
+            .flat_map(|i| {
+                let x = (i % width) * 255 / width;
+                [x as u8, (x / 2) as u8, 64, 255]
+            })
+            .collect();
+        let decoded = decompress_bc1(width, height, &compress_bc1(width, height, &rgba));
+
+        let max_error = rgba
+            .iter()
+            .zip(&decoded)
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap();
+        assert!(max_error <= 12, "max error {}", max_error);
+    }
+
+    #[test]
+    fn handles_partial_blocks() {
+        let rgba = gradient(5, 3);
+        let blocks = compress_bc1(5, 3, &rgba);
+
+        assert_eq!(blocks.len(), 2 * 8);
+        assert_eq!(decompress_bc1(5, 3, &blocks).len(), rgba.len());
+    }
+
+    #[test]
+    fn texture_conversion_sets_format() {
+        let texture = TextureAsset {
+            width: 4,
+            height: 4,
+            format: vk::Format::R8G8B8A8_SRGB,
+            data: gradient(4, 4),
+        };
+        let compressed = texture.to_bc1().unwrap();
+
+        assert_eq!(compressed.format, vk::Format::BC1_RGB_SRGB_BLOCK);
+        assert_eq!(compressed.data.len(), 8);
+    }
+}
@@ -0,0 +1,307 @@
+use anyhow::Result;
+use ash::vk;
+use std::collections::HashMap;
+
+use crate::backend::{
+    CommandList, DrawCommand, FrameStatus, GraphicsPipelineDesc, PipelineHandle, PrimitiveTopology,
+    RendererBackend,
+};
+use crate::pipeline::{VulkanPipeline, VulkanPipelineBuilder};
+use crate::renderer::VulkanRenderer;
+use crate::vulkan::{
+    QueueFamilyIndices, SwapchainConfig, VulkanCommandPool, VulkanDevice, VulkanFramebuffers,
+    VulkanInstance, VulkanPhysicalDevice, VulkanRenderPass, VulkanSurface, VulkanSwapchain,
+    VulkanSyncObjects,
+};
+use crate::window::VulkanWindow;
+
+impl From<PrimitiveTopology> for vk::PrimitiveTopology {
+    fn from(topology: PrimitiveTopology) -> Self {
+        match topology {
+            PrimitiveTopology::TriangleList => vk::PrimitiveTopology::TRIANGLE_LIST,
+            PrimitiveTopology::TriangleStrip => vk::PrimitiveTopology::TRIANGLE_STRIP,
+            PrimitiveTopology::LineList => vk::PrimitiveTopology::LINE_LIST,
+            PrimitiveTopology::PointList => vk::PrimitiveTopology::POINT_LIST,
+        }
+    }
+}
+
+/// `RendererBackend` on Vulkan through ash, drawing straight into the swapchain images with
+/// `VulkanRenderer::draw_frame_with`.
+pub struct AshBackend {
+    // Fields drop in declaration order, so everything created from the device goes first.
+    pipelines: HashMap<PipelineHandle, VulkanPipeline>,
+    next_pipeline: u32,
+    renderer: VulkanRenderer,
+    sync_objects: VulkanSyncObjects,
+    command_pool: VulkanCommandPool,
+    framebuffers: VulkanFramebuffers,
+    render_pass: VulkanRenderPass,
+    swapchain: VulkanSwapchain,
+    device: VulkanDevice,
+    queue_families: QueueFamilyIndices,
+    surface: VulkanSurface,
+    physical_device: VulkanPhysicalDevice,
+    instance: VulkanInstance,
+}
+
+impl AshBackend {
+    pub fn new(window: &VulkanWindow) -> Result<Self> {
+        let instance = VulkanInstance::new(&VulkanWindow::get_required_extensions())?;
+        let surface = VulkanSurface::new(&instance, window)?;
+        let physical_device = VulkanPhysicalDevice::select_best_device(&instance)?;
+
+        let queue_families = physical_device.find_queue_families(&instance.instance, &surface)?;
+        if !queue_families.is_complete() {
+            return Err(anyhow::anyhow!(
+                "Device doesn't support required queue families"
+            ));
+        }
+        let device = VulkanDevice::new(&instance, &physical_device, queue_families.clone())?;
+
+        let size = window.window().inner_size();
+        let swapchain = VulkanSwapchain::new(
+            &instance,
+            &device,
+            &physical_device,
+            &surface,
+            size.width,
+            size.height,
+        )?;
+        let render_pass = VulkanRenderPass::new(&device, &swapchain)?;
+        let (framebuffers, command_pool) =
+            create_image_resources(&device, &queue_families, &render_pass, &swapchain)?;
+
+        let renderer = VulkanRenderer::new(&device, &instance);
+        let sync_objects = VulkanSyncObjects::new(&device, renderer.max_frames_in_flight)?;
+
+        Ok(Self {
+            pipelines: HashMap::new(),
+            next_pipeline: 0,
+            renderer,
+            sync_objects,
+            command_pool,
+            framebuffers,
+            render_pass,
+            swapchain,
+            device,
+            queue_families,
+            surface,
+            physical_device,
+            instance,
+        })
+    }
+
+    /// The Vulkan device, for resources the trait doesn't cover yet.
+    pub fn device(&self) -> &VulkanDevice {
+        &self.device
+    }
+
+    /// Command buffers available to record frames into, one per swapchain image.
+    pub fn command_buffer_count(&self) -> usize {
+        self.command_pool.command_buffers.len()
+    }
+
+    pub fn swapchain_image_count(&self) -> usize {
+        self.swapchain.images.len()
+    }
+}
+
+/// Framebuffers and command buffers for each image of `swapchain`. `draw_frame_with` records
+/// into the command buffer of the acquired image, and the driver may create more images than
+/// there are frames in flight.
+fn create_image_resources(
+    device: &VulkanDevice,
+    queue_families: &QueueFamilyIndices,
+    render_pass: &VulkanRenderPass,
+    swapchain: &VulkanSwapchain,
+) -> Result<(VulkanFramebuffers, VulkanCommandPool)> {
+    let framebuffers = VulkanFramebuffers::new(device, render_pass, swapchain)?;
+    let command_pool =
+        VulkanCommandPool::new(device, queue_families.clone(), swapchain.images.len())?;
+    Ok((framebuffers, command_pool))
+}
+
+impl RendererBackend for AshBackend {
+    fn name(&self) -> &str {
+        "vulkan"
+    }
+
+    fn extent(&self) -> (u32, u32) {
+        (self.swapchain.extent.width, self.swapchain.extent.height)
+    }
+
+    fn create_graphics_pipeline(&mut self, desc: &GraphicsPipelineDesc) -> Result<PipelineHandle> {
+        let mut builder = VulkanPipelineBuilder::new(&self.device)
+            .set_render_pass(self.render_pass.render_pass)
+            .set_extent(self.swapchain.extent)
+            .with_vertex_spv(desc.vertex_spv)?
+            .with_fragment_spv(desc.fragment_spv)?
+            .with_topology(desc.topology.into())
+            // Viewport and scissor are dynamic so pipelines survive `resize`.
+            .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]);
+        if desc.alpha_blending {
+            builder = builder.with_alpha_blending();
+        }
+        let pipeline = builder.build()?;
+
+        let handle = PipelineHandle(self.next_pipeline);
+        self.next_pipeline += 1;
+        self.pipelines.insert(handle, pipeline);
+        Ok(handle)
+    }
+
+    fn destroy_pipeline(&mut self, pipeline: PipelineHandle) -> Result<()> {
+        if !self.pipelines.contains_key(&pipeline) {
+            anyhow::bail!("Unknown pipeline {:?}", pipeline);
+        }
+        // Frames in flight may still use it.
+        self.device.wait_idle()?;
+        self.pipelines.remove(&pipeline);
+        Ok(())
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<()> {
+        self.device.wait_idle()?;
+
+        let swapchain = VulkanSwapchain::with_config(
+            &self.instance,
+            &self.device,
+            &self.physical_device,
+            &self.surface,
+            width,
+            height,
+            SwapchainConfig::new()
+                .with_suboptimal_policy(self.swapchain.suboptimal_policy)
+                .with_old_swapchain(self.swapchain.swapchain),
+        )?;
+        let (framebuffers, command_pool) = create_image_resources(
+            &self.device,
+            &self.queue_families,
+            &self.render_pass,
+            &swapchain,
+        )?;
+
+        self.framebuffers = framebuffers;
+        self.swapchain = swapchain;
+        self.command_pool = command_pool;
+        Ok(())
+    }
+
+    fn submit_frame(&mut self, commands: &CommandList) -> Result<FrameStatus> {
+        commands.validate(|pipeline| self.pipelines.contains_key(&pipeline))?;
+
+        let pipelines = &self.pipelines;
+        self.renderer.draw_frame_with(
+            &self.device,
+            &self.swapchain,
+            &self.render_pass,
+            &self.framebuffers,
+            &self.command_pool,
+            &self.sync_objects,
+            |render_pass| {
+                for command in &commands.commands {
+                    match *command {
+                        DrawCommand::BindPipeline(pipeline) => {
+                            render_pass.bind_pipeline(pipelines[&pipeline].pipeline)
+                        }
+                        DrawCommand::Draw {
+                            vertex_count,
+                            instance_count,
+                            first_vertex,
+                            first_instance,
+                        } => render_pass.draw(
+                            vertex_count,
+                            instance_count,
+                            first_vertex,
+                            first_instance,
+                        ),
+                    }
+                }
+            },
+        )?;
+
+        // A lost surface needs a new window surface, which the trait doesn't expose yet.
+        if self.renderer.take_surface_lost() {
+            return Err(anyhow::anyhow!("Failed to present frame: surface lost"));
+        }
+        if self.renderer.take_out_of_date() {
+            return Ok(FrameStatus::NeedsResize);
+        }
+        Ok(FrameStatus::Presented)
+    }
+
+    fn wait_idle(&self) -> Result<()> {
+        self.device.wait_idle()
+    }
+}
+
+impl Drop for AshBackend {
+    fn drop(&mut self) {
+        if let Err(e) = self.device.wait_idle() {
+            eprintln!("Failed to wait for device idle: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::application::ApplicationHandler;
+    use winit::event::WindowEvent;
+    use winit::event_loop::{ActiveEventLoop, EventLoop};
+    use winit::window::WindowId;
+
+    /// Creates a window on the first `resumed`, hands it to `check` and exits.
+    struct WindowCheck<F: FnMut(&VulkanWindow) -> Result<()>> {
+        check: F,
+        result: Option<Result<()>>,
+    }
+
+    impl<F: FnMut(&VulkanWindow) -> Result<()>> ApplicationHandler for WindowCheck<F> {
+        fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+            if self.result.is_none() {
+                self.result =
+                    Some(VulkanWindow::new(event_loop).and_then(|window| (self.check)(&window)));
+            }
+            event_loop.exit();
+        }
+
+        fn window_event(&mut self, _: &ActiveEventLoop, _: WindowId, _: WindowEvent) {}
+    }
+
+    #[cfg(target_os = "linux")]
+    fn event_loop() -> EventLoop<()> {
+        // Tests run off the main thread.
+        use winit::platform::wayland::EventLoopBuilderExtWayland;
+        EventLoop::builder().with_any_thread(true).build().unwrap()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn event_loop() -> EventLoop<()> {
+        EventLoop::new().unwrap()
+    }
+
+    #[test]
+    #[ignore = "needs a display and a Vulkan device"]
+    fn records_into_one_command_buffer_per_swapchain_image() {
+        let mut app = WindowCheck {
+            check: |window: &VulkanWindow| {
+                let mut backend = AshBackend::new(window)?;
+                assert_eq!(
+                    backend.command_buffer_count(),
+                    backend.swapchain_image_count()
+                );
+                backend.resize(640, 480)?;
+                assert_eq!(
+                    backend.command_buffer_count(),
+                    backend.swapchain_image_count()
+                );
+                Ok(())
+            },
+            result: None,
+        };
+        event_loop().run_app(&mut app).unwrap();
+        app.result.expect("event loop never resumed").unwrap();
+    }
+}
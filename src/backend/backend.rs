@@ -0,0 +1,143 @@
+use anyhow::Result;
+
+/// A pipeline created by a `RendererBackend`, valid until destroyed on that backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PipelineHandle(pub u32);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PrimitiveTopology {
+    #[default]
+    TriangleList,
+    TriangleStrip,
+    LineList,
+    PointList,
+}
+
+/// Graphics pipeline drawing into the backend's presentable target, without vertex buffers.
+#[derive(Debug, Clone, Copy)]
+pub struct GraphicsPipelineDesc<'a> {
+    /// SPIR-V vertex shader. Backends that don't consume SPIR-V translate or ignore it.
+    pub vertex_spv: &'a [u8],
+    pub fragment_spv: &'a [u8],
+    pub topology: PrimitiveTopology,
+    pub alpha_blending: bool,
+}
+
+impl<'a> GraphicsPipelineDesc<'a> {
+    pub fn new(vertex_spv: &'a [u8], fragment_spv: &'a [u8]) -> Self {
+        Self {
+            vertex_spv,
+            fragment_spv,
+            topology: PrimitiveTopology::default(),
+            alpha_blending: false,
+        }
+    }
+
+    pub fn with_topology(mut self, topology: PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn with_alpha_blending(mut self) -> Self {
+        self.alpha_blending = true;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawCommand {
+    BindPipeline(PipelineHandle),
+    Draw {
+        vertex_count: u32,
+        instance_count: u32,
+        first_vertex: u32,
+        first_instance: u32,
+    },
+}
+
+/// A frame's draws, recorded as plain data so any backend can replay them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandList {
+    pub commands: Vec<DrawCommand>,
+}
+
+impl CommandList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bind_pipeline(&mut self, pipeline: PipelineHandle) -> &mut Self {
+        self.commands.push(DrawCommand::BindPipeline(pipeline));
+        self
+    }
+
+    pub fn draw(&mut self, vertex_count: u32, instance_count: u32) -> &mut Self {
+        self.commands.push(DrawCommand::Draw {
+            vertex_count,
+            instance_count,
+            first_vertex: 0,
+            first_instance: 0,
+        });
+        self
+    }
+
+    /// Pipelines the list binds, in order.
+    pub fn pipelines(&self) -> impl Iterator<Item = PipelineHandle> + '_ {
+        self.commands.iter().filter_map(|command| match command {
+            DrawCommand::BindPipeline(pipeline) => Some(*pipeline),
+            DrawCommand::Draw { .. } => None,
+        })
+    }
+
+    /// Fails if a draw comes before any pipeline is bound, or a bound pipeline isn't one
+    /// `is_valid` accepts.
+    pub fn validate(&self, is_valid: impl Fn(PipelineHandle) -> bool) -> Result<()> {
+        let mut bound = false;
+        for command in &self.commands {
+            match command {
+                DrawCommand::BindPipeline(pipeline) if !is_valid(*pipeline) => {
+                    anyhow::bail!("Command list binds unknown pipeline {:?}", pipeline)
+                }
+                DrawCommand::BindPipeline(_) => bound = true,
+                DrawCommand::Draw { .. } if !bound => {
+                    anyhow::bail!("Command list draws before binding a pipeline")
+                }
+                DrawCommand::Draw { .. } => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of `RendererBackend::submit_frame`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameStatus {
+    Presented,
+    /// The frame was dropped or the target no longer matches the window; call `resize`
+    /// with the window's size before the next frame.
+    NeedsResize,
+}
+
+/// Device, swapchain, pipeline and command operations a renderer needs, so the code driving
+/// them doesn't depend on ash. `AshBackend` implements it on Vulkan; `NullBackend` records
+/// calls without a GPU, for tests.
+pub trait RendererBackend {
+    fn name(&self) -> &str;
+
+    /// Size of the presentable target in pixels.
+    fn extent(&self) -> (u32, u32);
+
+    fn create_graphics_pipeline(&mut self, desc: &GraphicsPipelineDesc) -> Result<PipelineHandle>;
+
+    /// Destroys `pipeline` once the GPU no longer uses it.
+    fn destroy_pipeline(&mut self, pipeline: PipelineHandle) -> Result<()>;
+
+    /// Recreates the presentable target for a window of `width` x `height` pixels.
+    fn resize(&mut self, width: u32, height: u32) -> Result<()>;
+
+    /// Records `commands` into the next frame and presents it.
+    fn submit_frame(&mut self, commands: &CommandList) -> Result<FrameStatus>;
+
+    /// Waits until the GPU has finished all submitted frames.
+    fn wait_idle(&self) -> Result<()>;
+}
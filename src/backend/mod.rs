@@ -0,0 +1,7 @@
+pub mod ash_backend;
+pub mod backend;
+pub mod null;
+
+pub use ash_backend::*;
+pub use backend::*;
+pub use null::*;
@@ -0,0 +1,161 @@
+use anyhow::Result;
+use std::collections::HashSet;
+
+use crate::backend::{
+    CommandList, DrawCommand, FrameStatus, GraphicsPipelineDesc, PipelineHandle, RendererBackend,
+};
+
+/// Backend without a GPU that validates and records what it is asked to do, for testing
+/// rendering code and running it headless.
+#[derive(Debug, Clone)]
+pub struct NullBackend {
+    extent: (u32, u32),
+    pipelines: HashSet<PipelineHandle>,
+    next_pipeline: u32,
+    frames: Vec<CommandList>,
+    resizes: u32,
+    /// Returned by the next `submit_frame` instead of `Presented`, e.g. to test recovery.
+    pub next_status: Option<FrameStatus>,
+}
+
+impl NullBackend {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            extent: (width, height),
+            pipelines: HashSet::new(),
+            next_pipeline: 0,
+            frames: Vec::new(),
+            resizes: 0,
+            next_status: None,
+        }
+    }
+
+    /// Command lists of the frames submitted so far.
+    pub fn frames(&self) -> &[CommandList] {
+        &self.frames
+    }
+
+    /// Draw calls submitted so far, over all frames.
+    pub fn draw_calls(&self) -> usize {
+        self.frames
+            .iter()
+            .flat_map(|frame| &frame.commands)
+            .filter(|command| matches!(command, DrawCommand::Draw { .. }))
+            .count()
+    }
+
+    pub fn live_pipelines(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn resizes(&self) -> u32 {
+        self.resizes
+    }
+}
+
+impl RendererBackend for NullBackend {
+    fn name(&self) -> &str {
+        "null"
+    }
+
+    fn extent(&self) -> (u32, u32) {
+        self.extent
+    }
+
+    fn create_graphics_pipeline(&mut self, desc: &GraphicsPipelineDesc) -> Result<PipelineHandle> {
+        for (stage, code) in [("vertex", desc.vertex_spv), ("fragment", desc.fragment_spv)] {
+            if code.is_empty() || code.len() % 4 != 0 {
+                anyhow::bail!("Invalid SPIR-V for the {} shader", stage);
+            }
+        }
+        let pipeline = PipelineHandle(self.next_pipeline);
+        self.next_pipeline += 1;
+        self.pipelines.insert(pipeline);
+        Ok(pipeline)
+    }
+
+    fn destroy_pipeline(&mut self, pipeline: PipelineHandle) -> Result<()> {
+        if !self.pipelines.remove(&pipeline) {
+            anyhow::bail!("Unknown pipeline {:?}", pipeline);
+        }
+        Ok(())
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<()> {
+        self.extent = (width, height);
+        self.resizes += 1;
+        Ok(())
+    }
+
+    fn submit_frame(&mut self, commands: &CommandList) -> Result<FrameStatus> {
+        commands.validate(|pipeline| self.pipelines.contains(&pipeline))?;
+        match self.next_status.take() {
+            Some(FrameStatus::NeedsResize) => Ok(FrameStatus::NeedsResize),
+            _ => {
+                self.frames.push(commands.clone());
+                Ok(FrameStatus::Presented)
+            }
+        }
+    }
+
+    fn wait_idle(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPV: [u8; 8] = [0x03, 0x02, 0x23, 0x07, 0, 0, 0, 0];
+
+    /// Renders `frames` frames of a triangle, resizing when the backend asks; written once
+    /// against the trait, like a renderer would be.
+    fn render_triangles(
+        backend: &mut impl RendererBackend,
+        frames: usize,
+        window: (u32, u32),
+    ) -> Result<()> {
+        let pipeline = backend.create_graphics_pipeline(&GraphicsPipelineDesc::new(&SPV, &SPV))?;
+        let mut commands = CommandList::new();
+        commands.bind_pipeline(pipeline).draw(3, 1);
+        for _ in 0..frames {
+            if backend.submit_frame(&commands)? == FrameStatus::NeedsResize {
+                backend.resize(window.0, window.1)?;
+            }
+        }
+        backend.wait_idle()?;
+        backend.destroy_pipeline(pipeline)
+    }
+
+    #[test]
+    fn records_frames_through_the_trait() {
+        let mut backend = NullBackend::new(640, 480);
+        backend.next_status = Some(FrameStatus::NeedsResize);
+        render_triangles(&mut backend, 3, (800, 600)).unwrap();
+
+        assert_eq!(backend.frames().len(), 2);
+        assert_eq!(backend.draw_calls(), 2);
+        assert_eq!(backend.resizes(), 1);
+        assert_eq!(backend.extent(), (800, 600));
+        assert_eq!(backend.live_pipelines(), 0);
+    }
+
+    #[test]
+    fn rejects_invalid_command_lists() {
+        let mut backend = NullBackend::new(640, 480);
+        let mut draw_only = CommandList::new();
+        draw_only.draw(3, 1);
+        assert!(backend.submit_frame(&draw_only).is_err());
+
+        let mut unknown = CommandList::new();
+        unknown.bind_pipeline(PipelineHandle(7)).draw(3, 1);
+        assert!(backend.submit_frame(&unknown).is_err());
+        assert!(
+            backend
+                .create_graphics_pipeline(&GraphicsPipelineDesc::new(&[], &SPV))
+                .is_err()
+        );
+        assert!(backend.frames().is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::Result;
+use std::path::Path;
+
+use rust_vulkan_experiments::Asset;
+use rust_vulkan_experiments::{
+    AssetKind, AssetPack, AssetPackWriter, MeshAsset, ShaderAsset, TextureAsset, generate_tangents,
+    optimize_mesh,
+};
+
+const USAGE: &str = "Usage:
+  asset_pack pack <output.pack> <files...>   Pack .obj meshes, .ppm textures and .spv shaders
+  asset_pack list <input.pack>               List the entries of a pack
+  asset_pack unpack <input.pack> <dir>       Write every payload to <dir>/<name>.<kind>";
+
+fn pack(output: &str, inputs: &[String]) -> Result<()> {
+    let mut writer = AssetPackWriter::new();
+
+    for input in inputs {
+        let path = Path::new(input);
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| anyhow::anyhow!("Invalid file name {}", input))?;
+        let bytes =
+            std::fs::read(path).map_err(|e| anyhow::anyhow!("Failed to read {}: {}", input, e))?;
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("obj") => {
+                let mut mesh = MeshAsset::load(path, &bytes)?;
+                let report = optimize_mesh(&mut mesh.vertices, &mut mesh.indices);
+                // Optimization reorders and drops vertices; tangents have to follow.
+                if !mesh.tangents.is_empty() {
+                    mesh.tangents = generate_tangents(&mesh.vertices, &mesh.indices);
+                }
+                println!("{}: {} -> {}", name, report.before, report.after);
+                writer.add_mesh(name, &mesh)?;
+            }
+            Some("ppm") => {
+                let texture = TextureAsset::load(path, &bytes)?;
+                let compressed = texture.to_bc1().unwrap_or(texture);
+                println!("{}: {:?}", name, compressed.format);
+                writer.add_texture(name, &compressed)?;
+            }
+            Some("spv") => writer.add_shader(name, &ShaderAsset::load(path, &bytes)?)?,
+            _ => return Err(anyhow::anyhow!("Unsupported asset type: {}", input)),
+        }
+    }
+
+    writer.write(output)
+}
+
+fn list(input: &str) -> Result<()> {
+    let pack = AssetPack::open(input)?;
+    for entry in pack.entries() {
+        println!("{:?}\t{}\t{} bytes", entry.kind, entry.name, entry.size);
+    }
+    Ok(())
+}
+
+fn unpack(input: &str, output: &str) -> Result<()> {
+    let pack = AssetPack::open(input)?;
+    std::fs::create_dir_all(output)
+        .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", output, e))?;
+
+    for entry in pack.entries() {
+        let extension = match entry.kind {
+            AssetKind::Mesh => "mesh",
+            AssetKind::Texture => "texture",
+            AssetKind::Shader => "shader",
+        };
+        let path = Path::new(output).join(format!("{}.{}", entry.name, extension));
+        std::fs::write(&path, pack.payload(&entry.name, entry.kind)?)
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.as_slice() {
+        [command, output, inputs @ ..] if command == "pack" && !inputs.is_empty() => {
+            pack(output, inputs)
+        }
+        [command, input] if command == "list" => list(input),
+        [command, input, output] if command == "unpack" => unpack(input, output),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::Vec3;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::vulkan::{
+    VulkanBuffer, VulkanDevice, VulkanImage, VulkanImageInfo, VulkanPhysicalDevice,
+};
+
+pub const LUT_FORMAT: vk::Format = vk::Format::A2B10G10R10_UNORM_PACK32;
+
+/// A 3D color lookup table mapping sRGB-encoded input colors to graded output colors.
+///
+/// Entries are stored with red varying fastest, then green, then blue, as in `.cube` files.
+/// The input domain maps the lowest and highest lattice points to input colors; entries are
+/// output colors and are kept as authored.
+#[derive(Debug, Clone)]
+pub struct ColorLut {
+    pub title: Option<String>,
+    size: u32,
+    domain_min: Vec3,
+    domain_max: Vec3,
+    entries: Vec<Vec3>,
+}
+
+impl ColorLut {
+    /// Identity LUT of `size`^3 entries.
+    pub fn neutral(size: u32) -> Self {
+        let size = size.max(2);
+        let max = (size - 1) as f32;
+        let mut entries = Vec::with_capacity((size * size * size) as usize);
+
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    entries.push(Vec3::new(r as f32, g as f32, b as f32) / max);
+                }
+            }
+        }
+
+        Self {
+            title: None,
+            size,
+            domain_min: Vec3::ZERO,
+            domain_max: Vec3::ONE,
+            entries,
+        }
+    }
+
+    pub fn load_cube(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read LUT {}: {}", path.display(), e))?;
+        Self::parse_cube(&source)
+    }
+
+    /// Parses an Adobe/Resolve `.cube` 3D LUT. Keywords other than `TITLE`, `LUT_3D_SIZE` and
+    /// `DOMAIN_MIN`/`DOMAIN_MAX` are skipped.
+    pub fn parse_cube(source: &str) -> Result<Self> {
+        let mut title = None;
+        let mut size = None;
+        let mut domain_min = Vec3::ZERO;
+        let mut domain_max = Vec3::ONE;
+        let mut entries = Vec::new();
+
+        for (line_number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let keyword = tokens.next().unwrap_or_default();
+            let parse_vec3 = |tokens: std::str::SplitWhitespace| -> Result<Vec3> {
+                let values = tokens
+                    .map(|t| t.parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| {
+                        anyhow::anyhow!("Invalid number on line {}: {}", line_number + 1, e)
+                    })?;
+                match values.as_slice() {
+                    [r, g, b] => Ok(Vec3::new(*r, *g, *b)),
+                    _ => Err(anyhow::anyhow!(
+                        "Expected 3 values on line {}",
+                        line_number + 1
+                    )),
+                }
+            };
+
+            match keyword {
+                "TITLE" => {
+                    title = Some(line["TITLE".len()..].trim().trim_matches('"').to_string());
+                }
+                "LUT_3D_SIZE" => {
+                    let value = tokens
+                        .next()
+                        .and_then(|t| t.parse::<u32>().ok())
+                        .ok_or_else(|| {
+                            anyhow::anyhow!("Invalid LUT_3D_SIZE on line {}", line_number + 1)
+                        })?;
+                    if value < 2 {
+                        return Err(anyhow::anyhow!("LUT_3D_SIZE must be at least 2"));
+                    }
+                    size = Some(value);
+                }
+                "LUT_1D_SIZE" => {
+                    return Err(anyhow::anyhow!("1D LUTs are not supported"));
+                }
+                "DOMAIN_MIN" => domain_min = parse_vec3(tokens)?,
+                "DOMAIN_MAX" => domain_max = parse_vec3(tokens)?,
+                _ if keyword.parse::<f32>().is_ok() => {
+                    entries.push(parse_vec3(line.split_whitespace())?)
+                }
+                _ => {}
+            }
+        }
+
+        let size = size.ok_or_else(|| anyhow::anyhow!("Missing LUT_3D_SIZE"))?;
+        let expected = (size * size * size) as usize;
+        if entries.len() != expected {
+            return Err(anyhow::anyhow!(
+                "Expected {} LUT entries, found {}",
+                expected,
+                entries.len()
+            ));
+        }
+
+        if domain_max.cmple(domain_min).any() {
+            return Err(anyhow::anyhow!(
+                "DOMAIN_MAX must be greater than DOMAIN_MIN"
+            ));
+        }
+
+        Ok(Self {
+            title,
+            size,
+            domain_min,
+            domain_max,
+            entries,
+        })
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn entries(&self) -> &[Vec3] {
+        &self.entries
+    }
+
+    /// Input colors at the first and last lattice points; inputs are remapped from this range
+    /// before the lookup.
+    pub fn domain(&self) -> (Vec3, Vec3) {
+        (self.domain_min, self.domain_max)
+    }
+
+    fn packed(&self) -> Vec<u32> {
+        self.entries
+            .iter()
+            .map(|entry| {
+                let channel = |value: f32| (value.clamp(0.0, 1.0) * 1023.0).round() as u32;
+                (3 << 30) | (channel(entry.z) << 20) | (channel(entry.y) << 10) | channel(entry.x)
+            })
+            .collect()
+    }
+}
+
+/// GPU copy of a `ColorLut`, sampled as a 3D texture with linear filtering.
+pub struct ColorLutTexture {
+    pub image: VulkanImage,
+    domain: (Vec3, Vec3),
+    staging: Option<VulkanBuffer>,
+    device: Arc<Device>,
+}
+
+impl ColorLutTexture {
+    /// Creates the image and stages its contents; `record_upload` must be recorded and
+    /// executed before the texture is sampled.
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        lut: &ColorLut,
+    ) -> Result<Self> {
+        let image = VulkanImage::from_info(
+            device,
+            physical_device,
+            &VulkanImageInfo::volume(
+                vk::Extent3D {
+                    width: lut.size,
+                    height: lut.size,
+                    depth: lut.size,
+                },
+                LUT_FORMAT,
+                vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+            ),
+        )?;
+
+        let packed = lut.packed();
+        let staging = VulkanBuffer::new_host_visible(
+            device,
+            physical_device,
+            std::mem::size_of_val(packed.as_slice()) as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+        )?;
+        staging.write(0, &packed)?;
+
+        Ok(Self {
+            image,
+            domain: lut.domain(),
+            staging: Some(staging),
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn size(&self) -> u32 {
+        self.image.depth
+    }
+
+    pub fn domain(&self) -> (Vec3, Vec3) {
+        self.domain
+    }
+
+    /// Copies the staged entries and leaves the image in `SHADER_READ_ONLY_OPTIMAL`. Must be
+    /// recorded once; does nothing after `release_staging`.
+    pub fn record_upload(&self, command_buffer: vk::CommandBuffer) {
+        let Some(staging) = &self.staging else {
+            return;
+        };
+
+        let range = self.image.subresource_range();
+
+        self.image.cmd_transition(
+            command_buffer,
+            range,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::AccessFlags::empty(),
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+        );
+
+        let region = vk::BufferImageCopy::default()
+            .image_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image_extent(vk::Extent3D {
+                width: self.image.extent.width,
+                height: self.image.extent.height,
+                depth: self.image.depth,
+            });
+
+        unsafe {
+            self.device.cmd_copy_buffer_to_image(
+                command_buffer,
+                staging.buffer,
+                self.image.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[region],
+            );
+        }
+
+        self.image.cmd_transition(
+            command_buffer,
+            range,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        );
+    }
+
+    /// Frees the staging buffer once the upload has finished executing.
+    pub fn release_staging(&mut self) {
+        self.staging = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity_cube(header: &str) -> String {
+        let mut source = format!("{header}\nLUT_3D_SIZE 2\n");
+        for entry in ColorLut::neutral(2).entries() {
+            source.push_str(&format!("{} {} {}\n", entry.x, entry.y, entry.z));
+        }
+        source
+    }
+
+    #[test]
+    fn neutral_maps_lattice_to_itself() {
+        let lut = ColorLut::neutral(3);
+
+        assert_eq!(lut.size(), 3);
+        assert_eq!(lut.entries().len(), 27);
+        assert_eq!(lut.entries()[0], Vec3::ZERO);
+        assert_eq!(lut.entries()[1], Vec3::new(0.5, 0.0, 0.0));
+        assert_eq!(lut.entries()[3], Vec3::new(0.0, 0.5, 0.0));
+        assert_eq!(lut.entries()[9], Vec3::new(0.0, 0.0, 0.5));
+        assert_eq!(lut.entries()[26], Vec3::ONE);
+        assert_eq!(lut.domain(), (Vec3::ZERO, Vec3::ONE));
+    }
+
+    #[test]
+    fn neutral_clamps_size() {
+        assert_eq!(ColorLut::neutral(0).size(), 2);
+    }
+
+    #[test]
+    fn parses_identity() {
+        let lut = ColorLut::parse_cube(&identity_cube("# comment\nTITLE \"Identity\"")).unwrap();
+
+        assert_eq!(lut.title.as_deref(), Some("Identity"));
+        assert_eq!(lut.size(), 2);
+        assert_eq!(lut.entries(), ColorLut::neutral(2).entries());
+        assert_eq!(lut.domain(), (Vec3::ZERO, Vec3::ONE));
+    }
+
+    #[test]
+    fn keeps_entries_raw_and_stores_domain() {
+        let source = "LUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\nDOMAIN_MAX 2 2 2\n\
+                      0 0 0\n2 0 0\n0 2 0\n2 2 0\n0 0 2\n2 0 2\n0 2 2\n2 2 2\n";
+        let lut = ColorLut::parse_cube(source).unwrap();
+
+        assert_eq!(lut.domain(), (Vec3::ZERO, Vec3::splat(2.0)));
+        assert_eq!(lut.entries()[7], Vec3::splat(2.0));
+    }
+
+    #[test]
+    fn skips_unknown_keywords() {
+        let lut = ColorLut::parse_cube(&identity_cube("LUT_3D_INPUT_RANGE 0.0 1.0")).unwrap();
+
+        assert_eq!(lut.entries().len(), 8);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(ColorLut::parse_cube("0 0 0\n").is_err());
+        assert!(ColorLut::parse_cube("LUT_3D_SIZE 1\n").is_err());
+        assert!(ColorLut::parse_cube("LUT_3D_SIZE x\n").is_err());
+        assert!(ColorLut::parse_cube("LUT_1D_SIZE 16\n").is_err());
+        assert!(ColorLut::parse_cube("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+        assert!(ColorLut::parse_cube("LUT_3D_SIZE 2\n0 0\n").is_err());
+        assert!(ColorLut::parse_cube("LUT_3D_SIZE 2\n0 0 nope\n").is_err());
+        assert!(ColorLut::parse_cube(&identity_cube("DOMAIN_MAX 0 0 0")).is_err());
+    }
+}
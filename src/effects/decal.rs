@@ -0,0 +1,276 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::{Mat4, Vec4};
+use std::sync::Arc;
+
+use crate::pipeline::{VulkanPipeline, VulkanPipelineBuilder};
+use crate::vulkan::{
+    RecordCommands, VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanPhysicalDevice,
+};
+
+/// A texture atlas holding decal images, addressed by region index.
+pub struct DecalAtlas {
+    pub image_view: vk::ImageView,
+    pub sampler: vk::Sampler,
+    pub size: vk::Extent2D,
+    regions: Vec<[f32; 4]>,
+}
+
+impl DecalAtlas {
+    pub fn new(image_view: vk::ImageView, sampler: vk::Sampler, size: vk::Extent2D) -> Self {
+        Self {
+            image_view,
+            sampler,
+            size,
+            regions: Vec::new(),
+        }
+    }
+
+    /// Registers a pixel rectangle of the atlas and returns its region index.
+    pub fn add_region(&mut self, offset: vk::Offset2D, extent: vk::Extent2D) -> usize {
+        let width = self.size.width as f32;
+        let height = self.size.height as f32;
+
+        self.regions.push([
+            offset.x as f32 / width,
+            offset.y as f32 / height,
+            extent.width as f32 / width,
+            extent.height as f32 / height,
+        ]);
+
+        self.regions.len() - 1
+    }
+
+    pub fn region(&self, index: usize) -> Option<[f32; 4]> {
+        self.regions.get(index).copied()
+    }
+
+    pub fn region_count(&self) -> usize {
+        self.regions.len()
+    }
+}
+
+/// A box-shaped decal projected along its local Y axis.
+///
+/// `transform` maps the unit cube centered on the origin to the decal volume in world space.
+#[derive(Debug, Clone, Copy)]
+pub struct Decal {
+    pub transform: Mat4,
+    pub region: usize,
+    pub color: Vec4,
+}
+
+impl Decal {
+    pub fn new(transform: Mat4, region: usize) -> Self {
+        Self {
+            transform,
+            region,
+            color: Vec4::ONE,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GpuDecal {
+    model: Mat4,
+    inv_model: Mat4,
+    atlas_rect: [f32; 4],
+    color: Vec4,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DecalPushConstants {
+    view_proj: Mat4,
+    inv_view_proj: Mat4,
+}
+
+/// Screen-space decal pass.
+///
+/// Each decal is drawn as a cube whose fragments reconstruct the scene position from the depth
+/// buffer and discard everything outside the decal volume. The pass must run in a render pass
+/// that does not write the depth image it samples.
+pub struct DecalRenderer {
+    pub pipeline: VulkanPipeline,
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    descriptor_pool: VulkanDescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    decal_buffer: VulkanBuffer,
+    max_decals: usize,
+    decal_count: u32,
+    push_constants: DecalPushConstants,
+    device: Arc<Device>,
+}
+
+impl DecalRenderer {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        max_decals: usize,
+    ) -> Result<Self> {
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[
+                VulkanDescriptorSetLayout::binding(
+                    0,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    1,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::FRAGMENT,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    2,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::FRAGMENT,
+                ),
+            ],
+        )?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(device, &descriptor_set_layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&descriptor_set_layout)?;
+
+        let decal_buffer = VulkanBuffer::new_host_visible(
+            device,
+            physical_device,
+            (max_decals.max(1) * std::mem::size_of::<GpuDecal>()) as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+        )?;
+
+        descriptor_pool.write_buffer(
+            descriptor_set,
+            0,
+            vk::DescriptorType::STORAGE_BUFFER,
+            &decal_buffer,
+        );
+
+        let pipeline = VulkanPipelineBuilder::new(device)
+            .set_render_pass(render_pass)
+            .set_extent(extent)
+            .with_vertex_spv(include_bytes!("../../bin/decal.vert.spv"))?
+            .with_fragment_spv(include_bytes!("../../bin/decal.frag.spv"))?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+                    .offset(0)
+                    .size(std::mem::size_of::<DecalPushConstants>() as u32),
+            )
+            .with_cull_mode(vk::CullModeFlags::FRONT)
+            .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .with_alpha_blending()
+            .build()?;
+
+        Ok(Self {
+            pipeline,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_set,
+            decal_buffer,
+            max_decals,
+            decal_count: 0,
+            push_constants: DecalPushConstants {
+                view_proj: Mat4::IDENTITY,
+                inv_view_proj: Mat4::IDENTITY,
+            },
+            device: device.device.clone(),
+        })
+    }
+
+    /// Binds the scene depth buffer and the decal atlas. Must be called again whenever either
+    /// image is recreated (e.g. after a swapchain resize).
+    pub fn set_inputs(
+        &self,
+        depth_view: vk::ImageView,
+        depth_sampler: vk::Sampler,
+        atlas: &DecalAtlas,
+    ) {
+        self.descriptor_pool.write_image(
+            self.descriptor_set,
+            1,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            depth_view,
+            depth_sampler,
+            vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+        );
+
+        self.descriptor_pool.write_image(
+            self.descriptor_set,
+            2,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            atlas.image_view,
+            atlas.sampler,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+    }
+
+    /// Uploads the decal list for the next frame. The buffer is host coherent, so callers must
+    /// make sure the previous frame using it has completed.
+    pub fn update(&mut self, view_proj: Mat4, atlas: &DecalAtlas, decals: &[Decal]) -> Result<()> {
+        if decals.len() > self.max_decals {
+            return Err(anyhow::anyhow!(
+                "Too many decals: {} (max {})",
+                decals.len(),
+                self.max_decals
+            ));
+        }
+
+        let gpu_decals = decals
+            .iter()
+            .map(|decal| {
+                let atlas_rect = atlas
+                    .region(decal.region)
+                    .ok_or_else(|| anyhow::anyhow!("Decal atlas has no region {}", decal.region))?;
+
+                Ok(GpuDecal {
+                    model: decal.transform,
+                    inv_model: decal.transform.inverse(),
+                    atlas_rect,
+                    color: decal.color,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.decal_buffer.write(0, &gpu_decals)?;
+        self.decal_count = gpu_decals.len() as u32;
+        self.push_constants = DecalPushConstants {
+            view_proj,
+            inv_view_proj: view_proj.inverse(),
+        };
+
+        Ok(())
+    }
+
+    pub fn decal_count(&self) -> u32 {
+        self.decal_count
+    }
+
+    /// Records the decal draw. Viewport and scissor are dynamic and must already be set.
+    pub fn record(&self, command_buffer: vk::CommandBuffer) {
+        if self.decal_count == 0 {
+            return;
+        }
+
+        self.pipeline.bind(command_buffer);
+        self.pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.descriptor_set]);
+        self.pipeline.push_constants(
+            command_buffer,
+            vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+            0,
+            &self.push_constants,
+        );
+
+        self.device.draw(command_buffer, 36, self.decal_count, 0, 0);
+    }
+
+    pub fn descriptor_set_layout(&self) -> &VulkanDescriptorSetLayout {
+        &self.descriptor_set_layout
+    }
+}
@@ -0,0 +1,474 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::{Mat4, Vec4};
+use std::sync::Arc;
+
+use crate::pipeline::{VulkanComputePipeline, VulkanComputePipelineBuilder};
+use crate::vulkan::{
+    HistoryBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice, VulkanImage,
+    VulkanPhysicalDevice, VulkanSampler,
+};
+
+/// Guide image the denoiser needs next to the noisy signal: world-space normal in xyz and
+/// linear view depth in w, 0 where nothing was drawn.
+pub const NORMAL_DEPTH_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// Format of the history, intermediate and output images.
+const DENOISE_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// A-trous iterations are capped so the widest kernel (`4 << iterations` pixels) stays local.
+pub const MAX_DENOISE_ITERATIONS: u32 = 5;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SvgfSettings {
+    /// Minimum weight of the new frame in the color history.
+    pub color_alpha: f32,
+    /// Minimum weight of the new frame in the luminance moments.
+    pub moments_alpha: f32,
+    /// Relative view depth difference above which history is rejected as disoccluded.
+    pub depth_threshold: f32,
+    /// Minimum cosine between current and previous normals for history to be reused.
+    pub normal_threshold: f32,
+    /// A-trous wavelet iterations, at most `MAX_DENOISE_ITERATIONS`.
+    pub iterations: u32,
+    pub phi_color: f32,
+    pub phi_normal: f32,
+    pub phi_depth: f32,
+}
+
+impl Default for SvgfSettings {
+    fn default() -> Self {
+        Self {
+            color_alpha: 0.2,
+            moments_alpha: 0.2,
+            depth_threshold: 0.1,
+            normal_threshold: 0.9,
+            iterations: 4,
+            phi_color: 4.0,
+            phi_normal: 128.0,
+            phi_depth: 1.0,
+        }
+    }
+}
+
+/// Whether the history texel `previous` (a `NORMAL_DEPTH_FORMAT` value) belongs to another
+/// surface than `current`, the same test as `svgf_temporal.comp`.
+pub fn is_disoccluded(current: Vec4, previous: Vec4, settings: &SvgfSettings) -> bool {
+    !(previous.w > 0.0
+        && (current.w - previous.w).abs() <= settings.depth_threshold * current.w
+        && current.truncate().dot(previous.truncate()) >= settings.normal_threshold)
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TemporalPushConstants {
+    color_alpha: f32,
+    moments_alpha: f32,
+    depth_threshold: f32,
+    normal_threshold: f32,
+    reset: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct AtrousPushConstants {
+    step: i32,
+    phi_color: f32,
+    phi_normal: f32,
+    phi_depth: f32,
+    write_feedback: u32,
+}
+
+/// Spatiotemporal variance-guided filtering (SVGF) of a noisy ray-traced signal in compute.
+///
+/// Each frame, `svgf_temporal.comp` reprojects the history with the velocity image, rejects
+/// it where the normal or depth no longer match (disocclusion), and accumulates color and
+/// luminance moments into ping-ponged history images. `svgf_atrous.comp` then runs
+/// edge-avoiding wavelet iterations guided by the variance; the first iteration's result is
+/// fed back as the color history, as in the original SVGF.
+///
+/// Use one denoiser per signal (shadows, reflections, GI). There is no frame graph to declare
+/// the passes in, so `record` inserts its own barriers; inputs must be in
+/// `SHADER_READ_ONLY_OPTIMAL`, written before `record`, and the same size as the denoiser.
+pub struct SvgfDenoiser {
+    pub settings: SvgfSettings,
+    temporal_pipeline: VulkanComputePipeline,
+    atrous_pipeline: VulkanComputePipeline,
+    _temporal_layout: VulkanDescriptorSetLayout,
+    _atrous_layout: VulkanDescriptorSetLayout,
+    descriptor_pool: VulkanDescriptorPool,
+    /// Indexed by frame parity.
+    temporal_sets: [vk::DescriptorSet; 2],
+    /// First iteration by frame parity, then ping 0 to 1 and ping 1 to 0.
+    atrous_sets: [vk::DescriptorSet; 4],
+    color_history: HistoryBuffer,
+    moments_history: HistoryBuffer,
+    normal_depth_history: HistoryBuffer,
+    integrated: VulkanImage,
+    ping: [VulkanImage; 2],
+    sampler: VulkanSampler,
+    initialized: bool,
+    device: Arc<Device>,
+}
+
+impl SvgfDenoiser {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        extent: vk::Extent2D,
+    ) -> Result<Self> {
+        let binding = |binding: u32, descriptor_type: vk::DescriptorType| {
+            VulkanDescriptorSetLayout::binding(
+                binding,
+                descriptor_type,
+                vk::ShaderStageFlags::COMPUTE,
+            )
+        };
+
+        let temporal_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &(0..10)
+                .map(|index| {
+                    binding(
+                        index,
+                        if index < 3 {
+                            vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+                        } else {
+                            vk::DescriptorType::STORAGE_IMAGE
+                        },
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )?;
+
+        let atrous_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[
+                binding(0, vk::DescriptorType::STORAGE_IMAGE),
+                binding(1, vk::DescriptorType::STORAGE_IMAGE),
+                binding(2, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+                binding(3, vk::DescriptorType::STORAGE_IMAGE),
+            ],
+        )?;
+
+        let pool_sizes: Vec<vk::DescriptorPoolSize> =
+            [temporal_layout.pool_sizes(2), atrous_layout.pool_sizes(4)].concat();
+        let descriptor_pool = VulkanDescriptorPool::new(device, &pool_sizes, 6)?;
+
+        let temporal_sets = [
+            descriptor_pool.allocate(&temporal_layout)?,
+            descriptor_pool.allocate(&temporal_layout)?,
+        ];
+        let atrous_sets = [
+            descriptor_pool.allocate(&atrous_layout)?,
+            descriptor_pool.allocate(&atrous_layout)?,
+            descriptor_pool.allocate(&atrous_layout)?,
+            descriptor_pool.allocate(&atrous_layout)?,
+        ];
+
+        let create_image = || {
+            VulkanImage::new(
+                device,
+                physical_device,
+                extent,
+                DENOISE_FORMAT,
+                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+                vk::ImageAspectFlags::COLOR,
+            )
+        };
+
+        let create_history = || HistoryBuffer::new(device, physical_device, extent, DENOISE_FORMAT);
+        let color_history = create_history()?;
+        let moments_history = create_history()?;
+        let normal_depth_history = create_history()?;
+        let integrated = create_image()?;
+        let ping = [create_image()?, create_image()?];
+
+        let storage = |set: vk::DescriptorSet, binding: u32, view: vk::ImageView| {
+            descriptor_pool.write_storage_image(set, binding, view);
+        };
+
+        for (current, &set) in temporal_sets.iter().enumerate() {
+            let previous = 1 - current;
+            storage(set, 3, color_history.image(previous).view());
+            storage(set, 4, moments_history.image(previous).view());
+            storage(set, 5, normal_depth_history.image(previous).view());
+            storage(set, 6, color_history.image(current).view());
+            storage(set, 7, moments_history.image(current).view());
+            storage(set, 8, normal_depth_history.image(current).view());
+            storage(set, 9, integrated.view);
+        }
+
+        let atrous_images = [
+            (&integrated, &ping[0], color_history.image(0)),
+            (&integrated, &ping[0], color_history.image(1)),
+            // Later iterations don't write the feedback binding.
+            (&ping[0], &ping[1], color_history.image(0)),
+            (&ping[1], &ping[0], color_history.image(0)),
+        ];
+        for (&set, (source, destination, feedback)) in atrous_sets.iter().zip(atrous_images) {
+            storage(set, 0, source.view);
+            storage(set, 1, destination.view);
+            storage(set, 3, feedback.view());
+        }
+
+        let push_constant_range = |size: usize| {
+            vk::PushConstantRange::default()
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .offset(0)
+                .size(size as u32)
+        };
+
+        let temporal_pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/svgf_temporal.comp.spv"), None)?
+            .with_descriptor_set_layout(temporal_layout.layout)
+            .with_push_constant_range(push_constant_range(std::mem::size_of::<
+                TemporalPushConstants,
+            >()))
+            .build()?;
+
+        let atrous_pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/svgf_atrous.comp.spv"), None)?
+            .with_descriptor_set_layout(atrous_layout.layout)
+            .with_push_constant_range(push_constant_range(
+                std::mem::size_of::<AtrousPushConstants>(),
+            ))
+            .build()?;
+
+        Ok(Self {
+            settings: SvgfSettings::default(),
+            temporal_pipeline,
+            atrous_pipeline,
+            _temporal_layout: temporal_layout,
+            _atrous_layout: atrous_layout,
+            descriptor_pool,
+            temporal_sets,
+            atrous_sets,
+            color_history,
+            moments_history,
+            normal_depth_history,
+            integrated,
+            ping,
+            sampler: VulkanSampler::nearest_clamp(device)?,
+            initialized: false,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Binds the noisy signal, the velocity image (`VELOCITY_FORMAT`) and the
+    /// `NORMAL_DEPTH_FORMAT` guide, all in `SHADER_READ_ONLY_OPTIMAL` when recorded.
+    pub fn set_inputs(
+        &self,
+        noisy: vk::ImageView,
+        velocity: vk::ImageView,
+        normal_depth: vk::ImageView,
+    ) {
+        let sampled = |set: vk::DescriptorSet, binding: u32, view: vk::ImageView| {
+            self.descriptor_pool.write_image(
+                set,
+                binding,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                view,
+                self.sampler.sampler,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            );
+        };
+
+        for &set in &self.temporal_sets {
+            sampled(set, 0, noisy);
+            sampled(set, 1, velocity);
+            sampled(set, 2, normal_depth);
+        }
+        for &set in &self.atrous_sets {
+            sampled(set, 2, normal_depth);
+        }
+    }
+
+    /// Drops the history, e.g. after a camera cut, so the next frame starts accumulating
+    /// from scratch.
+    pub fn reset(&mut self) {
+        for history in self.histories_mut() {
+            history.invalidate();
+        }
+    }
+
+    /// Resets the history when `view` is a camera cut from the previous frame's view; see
+    /// `HistoryBuffer::observe_camera`. Call once per frame before `record`.
+    pub fn observe_camera(&mut self, view: Mat4) {
+        if self.color_history.observe_camera(view) {
+            self.reset();
+        }
+    }
+
+    fn histories_mut(&mut self) -> [&mut HistoryBuffer; 3] {
+        [
+            &mut self.color_history,
+            &mut self.moments_history,
+            &mut self.normal_depth_history,
+        ]
+    }
+
+    /// Denoised color in `GENERAL` layout, valid after `record`, readable by fragment and
+    /// compute shaders.
+    pub fn output(&self) -> &VulkanImage {
+        match self.iterations() {
+            0 => &self.integrated,
+            iterations => &self.ping[((iterations - 1) % 2) as usize],
+        }
+    }
+
+    fn iterations(&self) -> u32 {
+        self.settings.iterations.min(MAX_DENOISE_ITERATIONS)
+    }
+
+    /// Records the temporal pass and the a-trous iterations, outside a render pass.
+    pub fn record(&mut self, command_buffer: vk::CommandBuffer) {
+        if !self.initialized {
+            self.record_initialize(command_buffer);
+            self.initialized = true;
+        }
+
+        let current = self.color_history.current_index();
+        let extent = self.integrated.extent;
+        let groups = (extent.width.div_ceil(8), extent.height.div_ceil(8));
+
+        // The previous frame's passes, and readers of its output, must be done first.
+        self.record_barrier(command_buffer);
+
+        let settings = &self.settings;
+        self.temporal_pipeline.bind(command_buffer);
+        self.temporal_pipeline.bind_descriptor_sets(
+            command_buffer,
+            0,
+            &[self.temporal_sets[current]],
+        );
+        self.temporal_pipeline.push_constants(
+            command_buffer,
+            &TemporalPushConstants {
+                color_alpha: settings.color_alpha,
+                moments_alpha: settings.moments_alpha,
+                depth_threshold: settings.depth_threshold,
+                normal_threshold: settings.normal_threshold,
+                reset: u32::from(!self.color_history.is_valid()),
+            },
+        );
+        self.temporal_pipeline
+            .dispatch(command_buffer, groups.0, groups.1, 1);
+
+        for iteration in 0..self.iterations() {
+            self.record_barrier(command_buffer);
+
+            let set = if iteration == 0 {
+                self.atrous_sets[current]
+            } else {
+                self.atrous_sets[2 + ((iteration - 1) % 2) as usize]
+            };
+
+            self.atrous_pipeline.bind(command_buffer);
+            self.atrous_pipeline
+                .bind_descriptor_sets(command_buffer, 0, &[set]);
+            self.atrous_pipeline.push_constants(
+                command_buffer,
+                &AtrousPushConstants {
+                    step: 1 << iteration,
+                    phi_color: settings.phi_color,
+                    phi_normal: settings.phi_normal,
+                    phi_depth: settings.phi_depth,
+                    write_feedback: u32::from(iteration == 0),
+                },
+            );
+            self.atrous_pipeline
+                .dispatch(command_buffer, groups.0, groups.1, 1);
+        }
+
+        self.record_barrier(command_buffer);
+
+        for history in self.histories_mut() {
+            history.advance();
+        }
+    }
+
+    /// Makes compute writes to the denoiser images visible to the next pass and to fragment
+    /// shaders. Every image stays in `GENERAL`, so a global memory barrier is enough.
+    fn record_barrier(&self, command_buffer: vk::CommandBuffer) {
+        let barrier = vk::MemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE);
+
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                std::slice::from_ref(&barrier),
+                &[],
+                &[],
+            );
+        }
+    }
+
+    fn record_initialize(&self, command_buffer: vk::CommandBuffer) {
+        let images = [
+            &self.color_history,
+            &self.moments_history,
+            &self.normal_depth_history,
+        ]
+        .into_iter()
+        .flat_map(|history| [&history.image(0).image, &history.image(1).image])
+        .chain(std::iter::once(&self.integrated))
+        .chain(&self.ping);
+
+        for image in images {
+            image.cmd_transition(
+                command_buffer,
+                image.subresource_range(),
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::GENERAL,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::AccessFlags::empty(),
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ShaderReflection;
+    use glam::Vec3;
+
+    #[test]
+    fn rejects_history_of_other_surfaces() {
+        let settings = SvgfSettings::default();
+        let surface = Vec3::Y.extend(10.0);
+
+        assert!(!is_disoccluded(surface, Vec3::Y.extend(10.5), &settings));
+        // Previously empty, farther away, or facing another way.
+        assert!(is_disoccluded(surface, Vec4::ZERO, &settings));
+        assert!(is_disoccluded(surface, Vec3::Y.extend(12.0), &settings));
+        assert!(is_disoccluded(surface, Vec3::X.extend(10.0), &settings));
+    }
+
+    #[test]
+    fn push_constants_match_shaders() {
+        let temporal =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/svgf_temporal.comp.spv"))
+                .unwrap();
+        let atrous =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/svgf_atrous.comp.spv")).unwrap();
+
+        assert_eq!(
+            temporal.push_constant_size,
+            Some(std::mem::size_of::<TemporalPushConstants>() as u32)
+        );
+        assert_eq!(temporal.layout_bindings(0).len(), 10);
+        assert_eq!(
+            atrous.push_constant_size,
+            Some(std::mem::size_of::<AtrousPushConstants>() as u32)
+        );
+        assert_eq!(atrous.layout_bindings(0).len(), 4);
+    }
+}
@@ -0,0 +1,219 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::sync::Arc;
+
+use crate::effects::{PostChain, PostEffect};
+use crate::pipeline::{VulkanPipeline, VulkanPipelineBuilder};
+use crate::vulkan::{
+    RecordCommands, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice, VulkanSampler,
+};
+
+/// Thin lens camera parameters. Distances are in meters, sensor and focal length in
+/// millimeters.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraLens {
+    pub focal_length_mm: f32,
+    pub f_number: f32,
+    pub focus_distance: f32,
+    pub sensor_height_mm: f32,
+    /// Clip planes of the projection that produced the depth buffer.
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for CameraLens {
+    fn default() -> Self {
+        Self {
+            focal_length_mm: 50.0,
+            f_number: 2.8,
+            focus_distance: 5.0,
+            sensor_height_mm: 24.0,
+            near: 0.1,
+            far: 1000.0,
+        }
+    }
+}
+
+impl CameraLens {
+    /// Factor turning `|z - focus_distance| / z` into a circle of confusion diameter in pixels
+    /// for an image `image_height` pixels tall.
+    pub fn coc_scale(&self, image_height: u32) -> f32 {
+        let focal_length = self.focal_length_mm / 1000.0;
+        let focus_distance = self.focus_distance.max(focal_length + 1e-3);
+        let aperture = focal_length / self.f_number.max(0.1);
+        let coc_on_sensor = aperture * focal_length / (focus_distance - focal_length);
+
+        coc_on_sensor / (self.sensor_height_mm / 1000.0) * image_height as f32
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DepthOfFieldSettings {
+    pub enabled: bool,
+    pub lens: CameraLens,
+    pub sample_count: u32,
+    /// Largest blur diameter in pixels; also the radius of the gather disk.
+    pub max_coc_pixels: f32,
+}
+
+impl Default for DepthOfFieldSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            lens: CameraLens::default(),
+            sample_count: 32,
+            max_coc_pixels: 16.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DepthOfFieldPushConstants {
+    near: f32,
+    far: f32,
+    focus_distance: f32,
+    coc_scale: f32,
+    max_coc_pixels: f32,
+    sample_count: u32,
+}
+
+/// Depth of field with the circle of confusion computed from scene depth and a `CameraLens`,
+/// blurred in a single scatter-as-gather pass.
+pub struct DepthOfFieldEffect {
+    pub pipeline: VulkanPipeline,
+    pub settings: DepthOfFieldSettings,
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    descriptor_pool: VulkanDescriptorPool,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    color_sampler: VulkanSampler,
+    depth_sampler: VulkanSampler,
+    extent: vk::Extent2D,
+    device: Arc<Device>,
+}
+
+impl DepthOfFieldEffect {
+    pub fn new(
+        device: &VulkanDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+    ) -> Result<Self> {
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[
+                VulkanDescriptorSetLayout::binding(
+                    0,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::FRAGMENT,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    1,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::FRAGMENT,
+                ),
+            ],
+        )?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(
+            device,
+            &descriptor_set_layout,
+            PostChain::INPUT_COUNT as u32,
+        )?;
+        let descriptor_sets = (0..PostChain::INPUT_COUNT)
+            .map(|_| descriptor_pool.allocate(&descriptor_set_layout))
+            .collect::<Result<Vec<_>>>()?;
+
+        let color_sampler = VulkanSampler::linear_clamp(device)?;
+        let depth_sampler = VulkanSampler::nearest_clamp(device)?;
+
+        let pipeline = VulkanPipelineBuilder::new(device)
+            .set_render_pass(render_pass)
+            .set_extent(extent)
+            .with_vertex_spv(include_bytes!("../../bin/fullscreen.vert.spv"))?
+            .with_fragment_spv(include_bytes!("../../bin/depth_of_field.frag.spv"))?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .offset(0)
+                    .size(std::mem::size_of::<DepthOfFieldPushConstants>() as u32),
+            )
+            .with_cull_mode(vk::CullModeFlags::NONE)
+            .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .build()?;
+
+        Ok(Self {
+            pipeline,
+            settings: DepthOfFieldSettings::default(),
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            color_sampler,
+            depth_sampler,
+            extent,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Binds the scene depth buffer, which must be in `DEPTH_STENCIL_READ_ONLY_OPTIMAL` when
+    /// recorded.
+    pub fn set_depth(&self, depth_view: vk::ImageView) {
+        for &descriptor_set in &self.descriptor_sets {
+            self.descriptor_pool.write_image(
+                descriptor_set,
+                1,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                depth_view,
+                self.depth_sampler.sampler,
+                vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            );
+        }
+    }
+
+    pub fn descriptor_set_layout(&self) -> &VulkanDescriptorSetLayout {
+        &self.descriptor_set_layout
+    }
+}
+
+impl PostEffect for DepthOfFieldEffect {
+    fn is_enabled(&self) -> bool {
+        self.settings.enabled
+    }
+
+    fn set_inputs(&mut self, inputs: &[vk::ImageView]) {
+        for (&descriptor_set, &input) in self.descriptor_sets.iter().zip(inputs) {
+            self.descriptor_pool.write_image(
+                descriptor_set,
+                0,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                input,
+                self.color_sampler.sampler,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            );
+        }
+    }
+
+    fn record(&self, command_buffer: vk::CommandBuffer, input: usize) {
+        let lens = &self.settings.lens;
+        let push_constants = DepthOfFieldPushConstants {
+            near: lens.near,
+            far: lens.far,
+            focus_distance: lens.focus_distance,
+            coc_scale: lens.coc_scale(self.extent.height),
+            max_coc_pixels: self.settings.max_coc_pixels,
+            sample_count: self.settings.sample_count,
+        };
+
+        self.pipeline.bind(command_buffer);
+        self.pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.descriptor_sets[input]]);
+        self.pipeline.push_constants(
+            command_buffer,
+            vk::ShaderStageFlags::FRAGMENT,
+            0,
+            &push_constants,
+        );
+
+        self.device.draw(command_buffer, 3, 1, 0, 0);
+    }
+}
@@ -0,0 +1,632 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use std::sync::Arc;
+
+use crate::pipeline::{
+    VulkanComputePipeline, VulkanComputePipelineBuilder, VulkanPipeline, VulkanPipelineBuilder,
+};
+use crate::vulkan::{
+    RecordCommands, VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanImage, VulkanPhysicalDevice, VulkanSampler,
+};
+
+pub const LIGHTMAP_FORMAT: vk::Format = vk::Format::R32G32B32A32_SFLOAT;
+
+/// Vertex layout for static lightmapped geometry: a material UV channel plus a second,
+/// non-overlapping UV channel addressing the lightmap.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LightmappedVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    pub lightmap_uv: [f32; 2],
+}
+
+impl LightmappedVertex {
+    pub fn binding_description(binding: u32) -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription::default()
+            .binding(binding)
+            .stride(std::mem::size_of::<Self>() as u32)
+            .input_rate(vk::VertexInputRate::VERTEX)
+    }
+
+    /// Locations 0..=3: position, normal, uv, lightmap_uv.
+    pub fn attribute_descriptions(binding: u32) -> [vk::VertexInputAttributeDescription; 4] {
+        let attribute = |location: u32, format: vk::Format, offset: usize| {
+            vk::VertexInputAttributeDescription::default()
+                .binding(binding)
+                .location(location)
+                .format(format)
+                .offset(offset as u32)
+        };
+
+        [
+            attribute(0, vk::Format::R32G32B32_SFLOAT, 0),
+            attribute(1, vk::Format::R32G32B32_SFLOAT, 12),
+            attribute(2, vk::Format::R32G32_SFLOAT, 24),
+            attribute(3, vk::Format::R32G32_SFLOAT, 32),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LightmapBakeSettings {
+    /// Direction the sun light travels in, world space.
+    pub sun_direction: Vec3,
+    pub sun_color: Vec3,
+    pub sun_intensity: f32,
+    pub sky_color: Vec3,
+    /// Diffuse reflectance used for light bounced off the baked geometry; zero disables
+    /// indirect bounces.
+    pub albedo: Vec3,
+    /// Hemisphere samples per texel for each progressive pass.
+    pub sky_samples: u32,
+}
+
+impl Default for LightmapBakeSettings {
+    fn default() -> Self {
+        Self {
+            sun_direction: Vec3::new(-0.4, -1.0, -0.3).normalize(),
+            sun_color: Vec3::new(1.0, 0.95, 0.85),
+            sun_intensity: 3.0,
+            sky_color: Vec3::new(0.4, 0.55, 0.8),
+            albedo: Vec3::splat(0.6),
+            sky_samples: 16,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct BakeTexel {
+    position: Vec4,
+    normal: Vec4,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct BakeTriangle {
+    v0: Vec4,
+    v1: Vec4,
+    v2: Vec4,
+    lightmap_uv01: Vec4,
+    lightmap_uv2: Vec4,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct BakePushConstants {
+    sun_direction: Vec4,
+    sun_color: Vec4,
+    sky_color: Vec4,
+    albedo: Vec4,
+    width: u32,
+    texel_count: u32,
+    triangle_count: u32,
+    pass_index: u32,
+    sky_samples: u32,
+}
+
+/// Progressive static global illumination baker running in compute.
+///
+/// Lightmap texels are mapped to surface points on the CPU by rasterizing triangles in
+/// lightmap UV space. Each pass then shades every texel with the sun and a number of
+/// hemisphere samples: samples that escape see the sky, samples that hit geometry pick up the
+/// light stored at the hit texel by the previous pass. Every pass therefore adds one bounce of
+/// indirect light. Rays are tested against every triangle, so the cost is
+/// O(texels * triangles) per pass, which suits the small scenes of this crate; ray tracing
+/// pipeline support is not implemented.
+pub struct LightmapBaker {
+    pub lightmap: VulkanImage,
+    pub settings: LightmapBakeSettings,
+    previous_lightmap: VulkanImage,
+    pipeline: VulkanComputePipeline,
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    _descriptor_pool: VulkanDescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    texel_buffer: VulkanBuffer,
+    triangle_buffer: VulkanBuffer,
+    texel_count: u32,
+    triangle_count: u32,
+    pass_index: u32,
+    device: Arc<Device>,
+}
+
+impl LightmapBaker {
+    /// `vertices` must be in world space. `indices` is a triangle list.
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        vertices: &[LightmappedVertex],
+        indices: &[u32],
+        resolution: u32,
+    ) -> Result<Self> {
+        if resolution == 0 {
+            return Err(anyhow::anyhow!("Lightmap resolution must be at least 1"));
+        }
+
+        if !indices.len().is_multiple_of(3) {
+            return Err(anyhow::anyhow!(
+                "Lightmap index count {} is not a multiple of 3",
+                indices.len()
+            ));
+        }
+
+        let triangles = indices
+            .chunks_exact(3)
+            .map(|tri| {
+                let vertex = |i: u32| {
+                    vertices.get(i as usize).copied().ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Lightmap index {} out of range ({} vertices)",
+                            i,
+                            vertices.len()
+                        )
+                    })
+                };
+                Ok([vertex(tri[0])?, vertex(tri[1])?, vertex(tri[2])?])
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let texels = rasterize_texels(&triangles, resolution);
+
+        let gpu_triangles: Vec<BakeTriangle> = triangles
+            .iter()
+            .map(|tri| {
+                let [uv0, uv1, uv2] = tri.map(|v| Vec2::from(v.lightmap_uv));
+                BakeTriangle {
+                    v0: Vec3::from(tri[0].position).extend(1.0),
+                    v1: Vec3::from(tri[1].position).extend(1.0),
+                    v2: Vec3::from(tri[2].position).extend(1.0),
+                    lightmap_uv01: Vec4::new(uv0.x, uv0.y, uv1.x, uv1.y),
+                    lightmap_uv2: Vec4::new(uv2.x, uv2.y, 0.0, 0.0),
+                }
+            })
+            .collect();
+
+        let texel_buffer = VulkanBuffer::new_host_visible(
+            device,
+            physical_device,
+            std::mem::size_of_val(texels.as_slice()) as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+        )?;
+        texel_buffer.write(0, &texels)?;
+
+        let triangle_buffer = VulkanBuffer::new_host_visible(
+            device,
+            physical_device,
+            (gpu_triangles.len().max(1) * std::mem::size_of::<BakeTriangle>()) as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+        )?;
+        triangle_buffer.write(0, &gpu_triangles)?;
+
+        let extent = vk::Extent2D {
+            width: resolution,
+            height: resolution,
+        };
+
+        let lightmap = VulkanImage::new(
+            device,
+            physical_device,
+            extent,
+            LIGHTMAP_FORMAT,
+            vk::ImageUsageFlags::STORAGE
+                | vk::ImageUsageFlags::SAMPLED
+                | vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::TRANSFER_DST,
+            vk::ImageAspectFlags::COLOR,
+        )?;
+
+        let previous_lightmap = VulkanImage::new(
+            device,
+            physical_device,
+            extent,
+            LIGHTMAP_FORMAT,
+            vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_DST,
+            vk::ImageAspectFlags::COLOR,
+        )?;
+
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[
+                VulkanDescriptorSetLayout::binding(
+                    0,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    1,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    2,
+                    vk::DescriptorType::STORAGE_IMAGE,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    3,
+                    vk::DescriptorType::STORAGE_IMAGE,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+            ],
+        )?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(device, &descriptor_set_layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&descriptor_set_layout)?;
+
+        descriptor_pool.write_buffer(
+            descriptor_set,
+            0,
+            vk::DescriptorType::STORAGE_BUFFER,
+            &texel_buffer,
+        );
+        descriptor_pool.write_buffer(
+            descriptor_set,
+            1,
+            vk::DescriptorType::STORAGE_BUFFER,
+            &triangle_buffer,
+        );
+        descriptor_pool.write_storage_image(descriptor_set, 2, lightmap.view);
+        descriptor_pool.write_storage_image(descriptor_set, 3, previous_lightmap.view);
+
+        let pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/lightmap_bake.comp.spv"), None)?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(std::mem::size_of::<BakePushConstants>() as u32),
+            )
+            .build()?;
+
+        Ok(Self {
+            lightmap,
+            settings: LightmapBakeSettings::default(),
+            previous_lightmap,
+            pipeline,
+            descriptor_set_layout,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            texel_buffer,
+            triangle_buffer,
+            texel_count: texels.len() as u32,
+            triangle_count: gpu_triangles.len() as u32,
+            pass_index: 0,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Clears the lightmap and restarts accumulation. The lightmap stays in `GENERAL` layout,
+    /// which is also the layout to sample it with.
+    pub fn record_reset(&mut self, command_buffer: vk::CommandBuffer) {
+        for image in [&self.lightmap, &self.previous_lightmap] {
+            let range = image.subresource_range();
+
+            image.cmd_transition(
+                command_buffer,
+                range,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::GENERAL,
+                vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::empty(),
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_WRITE,
+            );
+
+            unsafe {
+                self.device.cmd_clear_color_image(
+                    command_buffer,
+                    image.image,
+                    vk::ImageLayout::GENERAL,
+                    &vk::ClearColorValue {
+                        float32: [0.0, 0.0, 0.0, 0.0],
+                    },
+                    &[range],
+                );
+            }
+        }
+
+        self.pass_index = 0;
+    }
+
+    /// Records one progressive pass. `record_reset` must have been recorded before the first
+    /// pass.
+    pub fn record_pass(&mut self, command_buffer: vk::CommandBuffer) {
+        self.record_snapshot(command_buffer);
+
+        let settings = &self.settings;
+        let push_constants = BakePushConstants {
+            sun_direction: settings
+                .sun_direction
+                .normalize_or_zero()
+                .extend(settings.sun_intensity),
+            sun_color: settings.sun_color.extend(1.0),
+            sky_color: settings.sky_color.extend(1.0),
+            albedo: settings.albedo.extend(1.0),
+            width: self.lightmap.extent.width,
+            texel_count: self.texel_count,
+            triangle_count: self.triangle_count,
+            pass_index: self.pass_index,
+            sky_samples: settings.sky_samples,
+        };
+
+        self.pipeline.bind(command_buffer);
+        self.pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.descriptor_set]);
+        self.pipeline
+            .push_constants(command_buffer, &push_constants);
+        self.pipeline
+            .dispatch(command_buffer, self.texel_count.div_ceil(64), 1, 1);
+
+        self.lightmap.cmd_transition(
+            command_buffer,
+            self.lightmap.subresource_range(),
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::GENERAL,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+        );
+
+        self.pass_index += 1;
+    }
+
+    /// Copies the lightmap into `previous_lightmap` so the next pass reads a stable snapshot
+    /// for bounced light while it overwrites the lightmap.
+    fn record_snapshot(&self, command_buffer: vk::CommandBuffer) {
+        let range = self.lightmap.subresource_range();
+
+        self.lightmap.cmd_transition(
+            command_buffer,
+            range,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::GENERAL,
+            vk::PipelineStageFlags::TRANSFER | vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::TRANSFER_WRITE | vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_READ,
+        );
+        self.previous_lightmap.cmd_transition(
+            command_buffer,
+            range,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::GENERAL,
+            vk::PipelineStageFlags::TRANSFER | vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+        );
+
+        let subresource = vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let region = vk::ImageCopy::default()
+            .src_subresource(subresource)
+            .dst_subresource(subresource)
+            .extent(vk::Extent3D {
+                width: self.lightmap.extent.width,
+                height: self.lightmap.extent.height,
+                depth: 1,
+            });
+
+        unsafe {
+            self.device.cmd_copy_image(
+                command_buffer,
+                self.lightmap.image,
+                vk::ImageLayout::GENERAL,
+                self.previous_lightmap.image,
+                vk::ImageLayout::GENERAL,
+                std::slice::from_ref(&region),
+            );
+        }
+
+        self.lightmap.cmd_transition(
+            command_buffer,
+            range,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::GENERAL,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::empty(),
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+        );
+        self.previous_lightmap.cmd_transition(
+            command_buffer,
+            range,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::GENERAL,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        );
+    }
+
+    pub fn passes(&self) -> u32 {
+        self.pass_index
+    }
+
+    pub fn descriptor_set_layout(&self) -> &VulkanDescriptorSetLayout {
+        &self.descriptor_set_layout
+    }
+
+    pub fn texel_buffer(&self) -> &VulkanBuffer {
+        &self.texel_buffer
+    }
+
+    pub fn triangle_buffer(&self) -> &VulkanBuffer {
+        &self.triangle_buffer
+    }
+}
+
+/// Draws static geometry with `LightmappedVertex` vertices, modulating an albedo texture by
+/// the baked lightmap through the second UV channel.
+pub struct LightmappedMeshRenderer {
+    pub pipeline: VulkanPipeline,
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    descriptor_pool: VulkanDescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    sampler: VulkanSampler,
+    device: Arc<Device>,
+}
+
+impl LightmappedMeshRenderer {
+    pub fn new(
+        device: &VulkanDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+    ) -> Result<Self> {
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[
+                VulkanDescriptorSetLayout::binding(
+                    0,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::FRAGMENT,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    1,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::FRAGMENT,
+                ),
+            ],
+        )?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(device, &descriptor_set_layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&descriptor_set_layout)?;
+        let sampler = VulkanSampler::linear_clamp(device)?;
+
+        let mut builder = VulkanPipelineBuilder::new(device)
+            .set_render_pass(render_pass)
+            .set_extent(extent)
+            .with_vertex_spv(include_bytes!("../../bin/lightmapped.vert.spv"))?
+            .with_fragment_spv(include_bytes!("../../bin/lightmapped.frag.spv"))?
+            .with_vertex_binding(LightmappedVertex::binding_description(0))
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::VERTEX)
+                    .offset(0)
+                    .size(std::mem::size_of::<Mat4>() as u32),
+            )
+            .with_depth_test(true, true, vk::CompareOp::LESS)
+            .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]);
+
+        for attribute in LightmappedVertex::attribute_descriptions(0) {
+            builder = builder.with_vertex_attribute(attribute);
+        }
+
+        let pipeline = builder.build()?;
+
+        Ok(Self {
+            pipeline,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_set,
+            sampler,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Binds the albedo texture (in `SHADER_READ_ONLY_OPTIMAL`) and the baked lightmap (in
+    /// `GENERAL`, as left by `LightmapBaker`). No in-flight frame may still be using the set.
+    pub fn set_textures(&self, albedo: vk::ImageView, lightmap: &VulkanImage) {
+        self.descriptor_pool.write_image(
+            self.descriptor_set,
+            0,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            albedo,
+            self.sampler.sampler,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+        self.descriptor_pool.write_image(
+            self.descriptor_set,
+            1,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            lightmap.view,
+            self.sampler.sampler,
+            vk::ImageLayout::GENERAL,
+        );
+    }
+
+    pub fn descriptor_set_layout(&self) -> &VulkanDescriptorSetLayout {
+        &self.descriptor_set_layout
+    }
+
+    /// Binds the pipeline and textures. Viewport and scissor are dynamic and must be set by
+    /// the caller.
+    pub fn bind(&self, command_buffer: vk::CommandBuffer) {
+        self.pipeline.bind(command_buffer);
+        self.pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.descriptor_set]);
+    }
+
+    /// Records an indexed draw of a mesh whose vertex and index buffers are already bound.
+    pub fn record_draw(&self, command_buffer: vk::CommandBuffer, mvp: Mat4, index_count: u32) {
+        self.pipeline
+            .push_constants(command_buffer, vk::ShaderStageFlags::VERTEX, 0, &mvp);
+
+        self.device
+            .draw_indexed(command_buffer, index_count, 1, 0, 0, 0);
+    }
+}
+
+/// Maps every lightmap texel center covered by a triangle to its interpolated world position
+/// and normal. Uncovered texels keep a zero normal and are skipped by the bake shader.
+fn rasterize_texels(triangles: &[[LightmappedVertex; 3]], resolution: u32) -> Vec<BakeTexel> {
+    let size = resolution as usize;
+    let mut texels = vec![BakeTexel::default(); size * size];
+
+    for tri in triangles {
+        let uv = tri.map(|v| Vec2::from(v.lightmap_uv) * resolution as f32);
+
+        let area = (uv[1] - uv[0]).perp_dot(uv[2] - uv[0]);
+        if area.abs() <= f32::EPSILON {
+            continue;
+        }
+
+        let min = uv[0].min(uv[1]).min(uv[2]).floor().max(Vec2::ZERO);
+        let max = uv[0]
+            .max(uv[1])
+            .max(uv[2])
+            .ceil()
+            .min(Vec2::splat(resolution as f32));
+
+        for y in min.y as usize..max.y as usize {
+            for x in min.x as usize..max.x as usize {
+                let p = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+
+                let w0 = (uv[2] - uv[1]).perp_dot(p - uv[1]) / area;
+                let w1 = (uv[0] - uv[2]).perp_dot(p - uv[2]) / area;
+                let w2 = 1.0 - w0 - w1;
+
+                if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                    continue;
+                }
+
+                let position = Vec3::from(tri[0].position) * w0
+                    + Vec3::from(tri[1].position) * w1
+                    + Vec3::from(tri[2].position) * w2;
+                let normal = (Vec3::from(tri[0].normal) * w0
+                    + Vec3::from(tri[1].normal) * w1
+                    + Vec3::from(tri[2].normal) * w2)
+                    .normalize_or_zero();
+
+                texels[y * size + x] = BakeTexel {
+                    position: position.extend(1.0),
+                    normal: normal.extend(1.0),
+                };
+            }
+        }
+    }
+
+    texels
+}
@@ -0,0 +1,33 @@
+pub mod color_grading;
+pub mod decal;
+pub mod denoiser;
+pub mod depth_of_field;
+pub mod lightmap;
+pub mod motion_blur;
+pub mod nan_check;
+pub mod outline;
+pub mod overlay;
+pub mod post;
+pub mod procedural;
+pub mod reduction;
+pub mod reflection_probe;
+pub mod shadertoy;
+pub mod tonemap;
+pub mod velocity;
+
+pub use color_grading::*;
+pub use decal::*;
+pub use denoiser::*;
+pub use depth_of_field::*;
+pub use lightmap::*;
+pub use motion_blur::*;
+pub use nan_check::*;
+pub use outline::*;
+pub use overlay::*;
+pub use post::*;
+pub use procedural::*;
+pub use reduction::*;
+pub use reflection_probe::*;
+pub use shadertoy::*;
+pub use tonemap::*;
+pub use velocity::*;
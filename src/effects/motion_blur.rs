@@ -0,0 +1,168 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::sync::Arc;
+
+use crate::effects::{PostChain, PostEffect};
+use crate::pipeline::{VulkanPipeline, VulkanPipelineBuilder};
+use crate::vulkan::{
+    RecordCommands, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice, VulkanSampler,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct MotionBlurSettings {
+    pub enabled: bool,
+    pub sample_count: u32,
+    /// Fraction of the frame time the shutter is open; 0.5 matches a 180 degree shutter.
+    pub shutter_scale: f32,
+    /// Upper bound on the blur length in pixels.
+    pub max_blur_pixels: f32,
+}
+
+impl Default for MotionBlurSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sample_count: 12,
+            shutter_scale: 0.5,
+            max_blur_pixels: 32.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MotionBlurPushConstants {
+    shutter_scale: f32,
+    max_blur_pixels: f32,
+    sample_count: u32,
+}
+
+/// Camera and object motion blur along the per-pixel motion of a `VelocityTarget`. Camera
+/// blur relies on `VelocityRenderer::record_camera` having filled the target first.
+pub struct MotionBlurEffect {
+    pub pipeline: VulkanPipeline,
+    pub settings: MotionBlurSettings,
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    descriptor_pool: VulkanDescriptorPool,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    sampler: VulkanSampler,
+    device: Arc<Device>,
+}
+
+impl MotionBlurEffect {
+    pub fn new(
+        device: &VulkanDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+    ) -> Result<Self> {
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[
+                VulkanDescriptorSetLayout::binding(
+                    0,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::FRAGMENT,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    1,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::FRAGMENT,
+                ),
+            ],
+        )?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(
+            device,
+            &descriptor_set_layout,
+            PostChain::INPUT_COUNT as u32,
+        )?;
+        let descriptor_sets = (0..PostChain::INPUT_COUNT)
+            .map(|_| descriptor_pool.allocate(&descriptor_set_layout))
+            .collect::<Result<Vec<_>>>()?;
+
+        let sampler = VulkanSampler::linear_clamp(device)?;
+
+        let pipeline = VulkanPipelineBuilder::new(device)
+            .set_render_pass(render_pass)
+            .set_extent(extent)
+            .with_vertex_spv(include_bytes!("../../bin/fullscreen.vert.spv"))?
+            .with_fragment_spv(include_bytes!("../../bin/motion_blur.frag.spv"))?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .offset(0)
+                    .size(std::mem::size_of::<MotionBlurPushConstants>() as u32),
+            )
+            .with_cull_mode(vk::CullModeFlags::NONE)
+            .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .build()?;
+
+        Ok(Self {
+            pipeline,
+            settings: MotionBlurSettings::default(),
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            sampler,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Binds the velocity image, which must be in `SHADER_READ_ONLY_OPTIMAL` when recorded.
+    pub fn set_velocity(&self, velocity_view: vk::ImageView) {
+        for &descriptor_set in &self.descriptor_sets {
+            self.descriptor_pool.write_image(
+                descriptor_set,
+                1,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                velocity_view,
+                self.sampler.sampler,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            );
+        }
+    }
+
+    pub fn descriptor_set_layout(&self) -> &VulkanDescriptorSetLayout {
+        &self.descriptor_set_layout
+    }
+}
+
+impl PostEffect for MotionBlurEffect {
+    fn is_enabled(&self) -> bool {
+        self.settings.enabled
+    }
+
+    fn set_inputs(&mut self, inputs: &[vk::ImageView]) {
+        for (&descriptor_set, &input) in self.descriptor_sets.iter().zip(inputs) {
+            self.descriptor_pool.write_image(
+                descriptor_set,
+                0,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                input,
+                self.sampler.sampler,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            );
+        }
+    }
+
+    fn record(&self, command_buffer: vk::CommandBuffer, input: usize) {
+        let push_constants = MotionBlurPushConstants {
+            shutter_scale: self.settings.shutter_scale,
+            max_blur_pixels: self.settings.max_blur_pixels,
+            sample_count: self.settings.sample_count,
+        };
+
+        self.pipeline.bind(command_buffer);
+        self.pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.descriptor_sets[input]]);
+        self.pipeline.push_constants(
+            command_buffer,
+            vk::ShaderStageFlags::FRAGMENT,
+            0,
+            &push_constants,
+        );
+
+        self.device.draw(command_buffer, 3, 1, 0, 0);
+    }
+}
@@ -0,0 +1,324 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::UVec2;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::pipeline::{VulkanComputePipeline, VulkanComputePipelineBuilder};
+use crate::vulkan::{
+    VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanPhysicalDevice, VulkanSampler,
+};
+
+/// Texels per side of the workgroups in `shaders/nan_check.comp`.
+const GROUP_SIDE: u32 = 16;
+/// Words of each `Record` in `shaders/nan_check.comp`.
+const RECORD_WORDS: usize = 4;
+const KIND_NAN: u32 = 1;
+const KIND_INFINITY: u32 = 2;
+
+/// The first check of a frame that found NaN or infinite texels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NanReport {
+    /// Name the check was recorded with, usually the pass that wrote the image.
+    pub pass: String,
+    /// First bad texel in row-major order.
+    pub pixel: UVec2,
+    /// Bad texels in the image.
+    pub count: u32,
+    pub has_nan: bool,
+    pub has_infinity: bool,
+}
+
+impl fmt::Display for NanReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match (self.has_nan, self.has_infinity) {
+            (true, true) => "NaN and Inf",
+            (true, false) => "NaN",
+            _ => "Inf",
+        };
+        write!(
+            f,
+            "{} in the output of '{}', first at pixel ({}, {}), {} bad pixel(s)",
+            kind, self.pass, self.pixel.x, self.pixel.y, self.count
+        )
+    }
+}
+
+/// Mirrors the `NanCheck` push constant block in `shaders/nan_check.comp`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct NanCheckPushConstants {
+    record: u32,
+}
+
+/// The first record with bad texels, in the order the checks were recorded.
+fn first_report(words: &[u32], checks: &[(String, vk::Extent2D)]) -> Option<NanReport> {
+    words
+        .chunks_exact(RECORD_WORDS)
+        .zip(checks)
+        .find(|(record, _)| record[0] > 0)
+        .map(|(record, (pass, extent))| {
+            let index = u32::MAX - record[1];
+            NanReport {
+                pass: pass.clone(),
+                pixel: UVec2::new(index % extent.width, index / extent.width),
+                count: record[0],
+                has_nan: record[2] & KIND_NAN != 0,
+                has_infinity: record[2] & KIND_INFINITY != 0,
+            }
+        })
+}
+
+struct NanCheckFrame {
+    buffer: VulkanBuffer,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    /// Name and extent of each check recorded since `begin_frame`.
+    checks: Vec<(String, vk::Extent2D)>,
+}
+
+/// Debug pass scanning render targets for NaN and infinite texels, which otherwise spread
+/// through blurs and tonemapping until the whole screen turns black. Checking the output of
+/// each pass tells which one produced them first.
+///
+/// Like `ImageReduction`, results go through one buffer per frame in flight: `begin_frame`,
+/// `check` the output of each pass after it is written, then `read` the frame once its fence
+/// has signaled.
+pub struct NanCheck {
+    pipeline: VulkanComputePipeline,
+    _descriptor_set_layout: VulkanDescriptorSetLayout,
+    descriptor_pool: VulkanDescriptorPool,
+    frames: Vec<NanCheckFrame>,
+    sampler: VulkanSampler,
+    device: Arc<Device>,
+}
+
+impl NanCheck {
+    /// Up to `checks_per_frame` checks in each of `frames` frames in flight.
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        frames: u32,
+        checks_per_frame: u32,
+    ) -> Result<Self> {
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[
+                VulkanDescriptorSetLayout::binding(
+                    0,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    1,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+            ],
+        )?;
+        let (frames, checks_per_frame) = (frames.max(1), checks_per_frame.max(1));
+        let descriptor_pool = VulkanDescriptorPool::for_layout(
+            device,
+            &descriptor_set_layout,
+            frames * checks_per_frame,
+        )?;
+
+        let buffer_size = (checks_per_frame as usize * RECORD_WORDS * std::mem::size_of::<u32>())
+            as vk::DeviceSize;
+        let frames = (0..frames)
+            .map(|_| {
+                let buffer = VulkanBuffer::new_host_visible(
+                    device,
+                    physical_device,
+                    buffer_size,
+                    vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+                )?;
+                let descriptor_sets = (0..checks_per_frame)
+                    .map(|_| {
+                        let set = descriptor_pool.allocate(&descriptor_set_layout)?;
+                        descriptor_pool.write_buffer(
+                            set,
+                            1,
+                            vk::DescriptorType::STORAGE_BUFFER,
+                            &buffer,
+                        );
+                        Ok(set)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(NanCheckFrame {
+                    buffer,
+                    descriptor_sets,
+                    checks: Vec::new(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/nan_check.comp.spv"), None)?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(std::mem::size_of::<NanCheckPushConstants>() as u32),
+            )
+            .build()?;
+
+        Ok(Self {
+            pipeline,
+            _descriptor_set_layout: descriptor_set_layout,
+            descriptor_pool,
+            frames,
+            sampler: VulkanSampler::nearest_clamp(device)?,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Clears the results of `frame`, whose previous checks must have finished executing.
+    /// Must be recorded outside a render pass, before the frame's checks.
+    pub fn begin_frame(&mut self, command_buffer: vk::CommandBuffer, frame: usize) -> Result<()> {
+        let nan_frame = self
+            .frames
+            .get_mut(frame)
+            .ok_or_else(|| anyhow::anyhow!("Unknown NaN check frame {}", frame))?;
+        nan_frame.checks.clear();
+
+        let buffer = &nan_frame.buffer;
+        unsafe {
+            self.device
+                .cmd_fill_buffer(command_buffer, buffer.buffer, 0, vk::WHOLE_SIZE, 0);
+        }
+        buffer.cmd_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+        );
+        Ok(())
+    }
+
+    /// Records a scan of `view`, an `extent`-sized image in `SHADER_READ_ONLY_OPTIMAL` whose
+    /// writes are already visible to compute shaders, reported as `pass`. Must be recorded
+    /// outside a render pass.
+    pub fn check(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        pass: &str,
+        view: vk::ImageView,
+        extent: vk::Extent2D,
+    ) -> Result<()> {
+        let nan_frame = self
+            .frames
+            .get_mut(frame)
+            .ok_or_else(|| anyhow::anyhow!("Unknown NaN check frame {}", frame))?;
+        let record = nan_frame.checks.len();
+        let Some(&descriptor_set) = nan_frame.descriptor_sets.get(record) else {
+            return Err(anyhow::anyhow!(
+                "More than {} NaN checks in a frame",
+                nan_frame.descriptor_sets.len()
+            ));
+        };
+
+        self.descriptor_pool.write_image(
+            descriptor_set,
+            0,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            view,
+            self.sampler.sampler,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+
+        self.pipeline.bind(command_buffer);
+        self.pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[descriptor_set]);
+        self.pipeline.push_constants(
+            command_buffer,
+            &NanCheckPushConstants {
+                record: record as u32,
+            },
+        );
+        self.pipeline.dispatch(
+            command_buffer,
+            extent.width.div_ceil(GROUP_SIDE),
+            extent.height.div_ceil(GROUP_SIDE),
+            1,
+        );
+
+        nan_frame.buffer.cmd_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::HOST,
+            vk::AccessFlags::HOST_READ,
+        );
+
+        nan_frame.checks.push((pass.to_string(), extent));
+        Ok(())
+    }
+
+    /// The first check of `frame` that found bad texels, once the frame has finished
+    /// executing; `None` when every checked image was clean.
+    pub fn read(&self, frame: usize) -> Result<Option<NanReport>> {
+        let nan_frame = self
+            .frames
+            .get(frame)
+            .ok_or_else(|| anyhow::anyhow!("Unknown NaN check frame {}", frame))?;
+        if nan_frame.checks.is_empty() {
+            return Ok(None);
+        }
+
+        let words = nan_frame
+            .buffer
+            .read::<u32>(0, nan_frame.checks.len() * RECORD_WORDS)?;
+        Ok(first_report(&words, &nan_frame.checks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ShaderReflection;
+
+    #[test]
+    fn shader_layout_matches_nan_check_push_constants() {
+        let reflection =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/nan_check.comp.spv")).unwrap();
+        assert_eq!(
+            reflection.push_constant_size,
+            Some(std::mem::size_of::<NanCheckPushConstants>() as u32)
+        );
+    }
+
+    #[test]
+    fn reports_the_first_pass_with_bad_texels() {
+        let extent = vk::Extent2D {
+            width: 100,
+            height: 50,
+        };
+        let checks = [
+            ("gbuffer".to_string(), extent),
+            ("bloom".to_string(), extent),
+            ("tonemap".to_string(), extent),
+        ];
+        let words = [
+            [0, 0, 0, 0],
+            [3, u32::MAX - (2 * 100 + 7), KIND_NAN | KIND_INFINITY, 0],
+            [9, u32::MAX, KIND_NAN, 0],
+        ]
+        .concat();
+
+        let report = first_report(&words, &checks).unwrap();
+        assert_eq!(report.pass, "bloom");
+        assert_eq!(report.pixel, UVec2::new(7, 2));
+        assert_eq!(report.count, 3);
+        assert!(report.has_nan && report.has_infinity);
+        assert_eq!(
+            report.to_string(),
+            "NaN and Inf in the output of 'bloom', first at pixel (7, 2), 3 bad pixel(s)"
+        );
+
+        assert_eq!(first_report(&words[..4], &checks), None);
+    }
+}
@@ -0,0 +1,510 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::Vec4;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use crate::pipeline::{
+    VulkanComputePipeline, VulkanComputePipelineBuilder, VulkanPipeline, VulkanPipelineBuilder,
+};
+use crate::vulkan::{
+    RecordCommands, VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanImage, VulkanPhysicalDevice, VulkanSampler,
+};
+
+/// Identifies an object in the object id target. Id 0 is reserved for "no object".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjectHandle(pub u32);
+
+impl ObjectHandle {
+    pub const NONE: ObjectHandle = ObjectHandle(0);
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SelectionSet {
+    handles: BTreeSet<ObjectHandle>,
+}
+
+impl SelectionSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn select(&mut self, handle: ObjectHandle) {
+        if handle != ObjectHandle::NONE {
+            self.handles.insert(handle);
+        }
+    }
+
+    pub fn deselect(&mut self, handle: ObjectHandle) {
+        self.handles.remove(&handle);
+    }
+
+    pub fn toggle(&mut self, handle: ObjectHandle) {
+        if !self.handles.remove(&handle) {
+            self.select(handle);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.handles.clear();
+    }
+
+    pub fn contains(&self, handle: ObjectHandle) -> bool {
+        self.handles.contains(&handle)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ObjectHandle> + '_ {
+        self.handles.iter().copied()
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct OutlinePushConstants {
+    color: Vec4,
+    width: i32,
+    word_count: u32,
+}
+
+/// Nearest-seed image written by the jump flooding passes. Each texel packs the coordinates of
+/// the closest selected pixel as `x | y << 16`, or `u32::MAX` when there is none.
+const SEED_FORMAT: vk::Format = vk::Format::R32_UINT;
+
+/// Screen-space selection outline using the jump flooding algorithm.
+///
+/// The scene writes object ids into an `OBJECT_ID_FORMAT` color target. `record_flood` seeds the
+/// selected pixels and runs `log2(width) + 1` flood passes in compute, outside any render pass;
+/// `record` then draws a full-screen triangle that colors every pixel within `width` pixels of
+/// its nearest seed. The cost is independent of the outline width.
+pub struct OutlineRenderer {
+    pub pipeline: VulkanPipeline,
+    pub color: Vec4,
+    pub width: u32,
+    seed_pipeline: VulkanComputePipeline,
+    flood_pipeline: VulkanComputePipeline,
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    _seed_layout: VulkanDescriptorSetLayout,
+    _flood_layout: VulkanDescriptorSetLayout,
+    descriptor_pool: VulkanDescriptorPool,
+    seed_set: vk::DescriptorSet,
+    flood_sets: [vk::DescriptorSet; 2],
+    composite_sets: [vk::DescriptorSet; 2],
+    seeds: [VulkanImage; 2],
+    selection_buffer: VulkanBuffer,
+    sampler: VulkanSampler,
+    word_count: u32,
+    has_selection: bool,
+    device: Arc<Device>,
+}
+
+impl OutlineRenderer {
+    pub const OBJECT_ID_FORMAT: vk::Format = vk::Format::R32_UINT;
+
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        max_objects: u32,
+    ) -> Result<Self> {
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[
+                VulkanDescriptorSetLayout::binding(
+                    0,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::FRAGMENT,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    1,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    vk::ShaderStageFlags::FRAGMENT,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    2,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::FRAGMENT,
+                ),
+            ],
+        )?;
+
+        let seed_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[
+                VulkanDescriptorSetLayout::binding(
+                    0,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    1,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    2,
+                    vk::DescriptorType::STORAGE_IMAGE,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+            ],
+        )?;
+
+        let flood_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[
+                VulkanDescriptorSetLayout::binding(
+                    0,
+                    vk::DescriptorType::STORAGE_IMAGE,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    1,
+                    vk::DescriptorType::STORAGE_IMAGE,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+            ],
+        )?;
+
+        let pool_sizes: Vec<vk::DescriptorPoolSize> = [
+            descriptor_set_layout.pool_sizes(2),
+            seed_layout.pool_sizes(1),
+            flood_layout.pool_sizes(2),
+        ]
+        .concat();
+        let descriptor_pool = VulkanDescriptorPool::new(device, &pool_sizes, 5)?;
+
+        let seed_set = descriptor_pool.allocate(&seed_layout)?;
+        let flood_sets = [
+            descriptor_pool.allocate(&flood_layout)?,
+            descriptor_pool.allocate(&flood_layout)?,
+        ];
+        let composite_sets = [
+            descriptor_pool.allocate(&descriptor_set_layout)?,
+            descriptor_pool.allocate(&descriptor_set_layout)?,
+        ];
+
+        let word_count = max_objects.div_ceil(32).max(1);
+        let selection_buffer = VulkanBuffer::new_host_visible(
+            device,
+            physical_device,
+            (word_count as usize * std::mem::size_of::<u32>()) as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+        )?;
+        selection_buffer.write(0, &vec![0u32; word_count as usize])?;
+
+        let create_seed_image = || {
+            VulkanImage::new(
+                device,
+                physical_device,
+                extent,
+                SEED_FORMAT,
+                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+                vk::ImageAspectFlags::COLOR,
+            )
+        };
+        let seeds = [create_seed_image()?, create_seed_image()?];
+
+        let sampler = VulkanSampler::nearest_clamp(device)?;
+
+        for set in [seed_set, composite_sets[0], composite_sets[1]] {
+            descriptor_pool.write_buffer(
+                set,
+                1,
+                vk::DescriptorType::STORAGE_BUFFER,
+                &selection_buffer,
+            );
+        }
+
+        descriptor_pool.write_storage_image(seed_set, 2, seeds[0].view);
+
+        // Flood pass `i` reads seeds[i % 2] and writes the other image.
+        for (i, &set) in flood_sets.iter().enumerate() {
+            for (binding, seed) in [&seeds[i], &seeds[1 - i]].into_iter().enumerate() {
+                descriptor_pool.write_storage_image(set, binding as u32, seed.view);
+            }
+        }
+
+        for (set, seed) in composite_sets.iter().zip(&seeds) {
+            descriptor_pool.write_image(
+                *set,
+                2,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                seed.view,
+                sampler.sampler,
+                vk::ImageLayout::GENERAL,
+            );
+        }
+
+        let seed_pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/outline_seed.comp.spv"), None)?
+            .with_descriptor_set_layout(seed_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(std::mem::size_of::<u32>() as u32),
+            )
+            .build()?;
+
+        let flood_pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/outline_flood.comp.spv"), None)?
+            .with_descriptor_set_layout(flood_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(std::mem::size_of::<i32>() as u32),
+            )
+            .build()?;
+
+        let pipeline = VulkanPipelineBuilder::new(device)
+            .set_render_pass(render_pass)
+            .set_extent(extent)
+            .with_vertex_spv(include_bytes!("../../bin/fullscreen.vert.spv"))?
+            .with_fragment_spv(include_bytes!("../../bin/outline.frag.spv"))?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .offset(0)
+                    .size(std::mem::size_of::<OutlinePushConstants>() as u32),
+            )
+            .with_cull_mode(vk::CullModeFlags::NONE)
+            .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .with_alpha_blending()
+            .build()?;
+
+        Ok(Self {
+            pipeline,
+            color: Vec4::new(1.0, 0.6, 0.1, 1.0),
+            width: 2,
+            seed_pipeline,
+            flood_pipeline,
+            descriptor_set_layout,
+            _seed_layout: seed_layout,
+            _flood_layout: flood_layout,
+            descriptor_pool,
+            seed_set,
+            flood_sets,
+            composite_sets,
+            seeds,
+            selection_buffer,
+            sampler,
+            word_count,
+            has_selection: false,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Binds the object id image. Must be called again whenever the image is recreated.
+    pub fn set_object_ids(&self, object_id_view: vk::ImageView) {
+        for set in [
+            self.seed_set,
+            self.composite_sets[0],
+            self.composite_sets[1],
+        ] {
+            self.descriptor_pool.write_image(
+                set,
+                0,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                object_id_view,
+                self.sampler.sampler,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            );
+        }
+    }
+
+    /// Uploads the selected handles as a bitset. Callers must make sure no in-flight frame is
+    /// still reading the previous selection.
+    pub fn update_selection(&mut self, selection: &SelectionSet) -> Result<()> {
+        let mut words = vec![0u32; self.word_count as usize];
+
+        for handle in selection.iter() {
+            let word = (handle.0 / 32) as usize;
+            if word >= words.len() {
+                return Err(anyhow::anyhow!(
+                    "Object handle {} exceeds outline capacity of {} objects",
+                    handle.0,
+                    self.word_count * 32
+                ));
+            }
+            words[word] |= 1 << (handle.0 % 32);
+        }
+
+        self.selection_buffer.write(0, &words)?;
+        self.has_selection = !selection.is_empty();
+
+        Ok(())
+    }
+
+    pub fn descriptor_set_layout(&self) -> &VulkanDescriptorSetLayout {
+        &self.descriptor_set_layout
+    }
+
+    /// Jump distances of the flood passes, halving from the largest power of two that still
+    /// reaches `width` pixels down to 1.
+    fn flood_steps(&self) -> impl Iterator<Item = u32> {
+        let first = (self.width + 1).next_power_of_two() / 2;
+        std::iter::successors(Some(first), |&step| (step > 1).then_some(step / 2))
+    }
+
+    /// Records the seed and jump flooding passes. Must be recorded outside a render pass, after
+    /// the object id image has been written and transitioned to `SHADER_READ_ONLY_OPTIMAL`.
+    pub fn record_flood(&self, command_buffer: vk::CommandBuffer) {
+        if !self.has_selection || self.width == 0 {
+            return;
+        }
+
+        let extent = self.seeds[0].extent;
+        let (groups_x, groups_y) = (extent.width.div_ceil(8), extent.height.div_ceil(8));
+
+        // Every texel is rewritten each frame, so the previous contents can be discarded.
+        for seed in &self.seeds {
+            seed.cmd_transition(
+                command_buffer,
+                seed.subresource_range(),
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::GENERAL,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::AccessFlags::empty(),
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::SHADER_WRITE,
+            );
+        }
+
+        self.seed_pipeline.bind(command_buffer);
+        self.seed_pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.seed_set]);
+        self.seed_pipeline
+            .push_constants(command_buffer, &self.word_count);
+        self.seed_pipeline
+            .dispatch(command_buffer, groups_x, groups_y, 1);
+
+        self.flood_pipeline.bind(command_buffer);
+
+        let mut current = 0;
+        for step in self.flood_steps() {
+            self.seed_barrier(
+                command_buffer,
+                current,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::SHADER_READ,
+            );
+
+            self.flood_pipeline.bind_descriptor_sets(
+                command_buffer,
+                0,
+                &[self.flood_sets[current]],
+            );
+            self.flood_pipeline
+                .push_constants(command_buffer, &(step as i32));
+            self.flood_pipeline
+                .dispatch(command_buffer, groups_x, groups_y, 1);
+
+            current = 1 - current;
+        }
+
+        self.seed_barrier(
+            command_buffer,
+            current,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        );
+    }
+
+    fn seed_barrier(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        index: usize,
+        dst_stage: vk::PipelineStageFlags,
+        dst_access: vk::AccessFlags,
+    ) {
+        let seed = &self.seeds[index];
+        seed.cmd_transition(
+            command_buffer,
+            seed.subresource_range(),
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::GENERAL,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            dst_stage,
+            dst_access,
+        );
+    }
+
+    /// Records the outline composite. `record_flood` must have been recorded earlier in the same
+    /// command buffer. Viewport and scissor are dynamic and must already be set.
+    pub fn record(&self, command_buffer: vk::CommandBuffer) {
+        if !self.has_selection || self.width == 0 {
+            return;
+        }
+
+        let push_constants = OutlinePushConstants {
+            color: self.color,
+            width: self.width as i32,
+            word_count: self.word_count,
+        };
+
+        let result = self.flood_steps().count() % 2;
+
+        self.pipeline.bind(command_buffer);
+        self.pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.composite_sets[result]]);
+        self.pipeline.push_constants(
+            command_buffer,
+            vk::ShaderStageFlags::FRAGMENT,
+            0,
+            &push_constants,
+        );
+
+        self.device.draw(command_buffer, 3, 1, 0, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn none_handle_is_never_selected() {
+        let mut selection = SelectionSet::new();
+        selection.select(ObjectHandle::NONE);
+        selection.toggle(ObjectHandle::NONE);
+
+        assert!(selection.is_empty());
+        assert!(!selection.contains(ObjectHandle::NONE));
+    }
+
+    #[test]
+    fn toggle_adds_then_removes() {
+        let mut selection = SelectionSet::new();
+        let handle = ObjectHandle(7);
+
+        selection.toggle(handle);
+        assert!(selection.contains(handle));
+
+        selection.toggle(handle);
+        assert!(!selection.contains(handle));
+        assert!(selection.is_empty());
+    }
+
+    #[test]
+    fn iterates_in_handle_order_without_duplicates() {
+        let mut selection = SelectionSet::new();
+        for id in [5, 1, 5, 3] {
+            selection.select(ObjectHandle(id));
+        }
+        selection.deselect(ObjectHandle(3));
+
+        let handles: Vec<u32> = selection.iter().map(|h| h.0).collect();
+        assert_eq!(handles, [1, 5]);
+
+        selection.clear();
+        assert!(selection.is_empty());
+    }
+}
@@ -0,0 +1,281 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::Vec4;
+use std::sync::Arc;
+
+use crate::pipeline::{VulkanPipeline, VulkanPipelineBuilder};
+use crate::vulkan::{
+    RecordCommands, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice, VulkanSampler,
+};
+
+/// Screen rectangle in pixels, origin at the top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OverlayRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl OverlayRect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn to_ndc(self, extent: vk::Extent2D) -> Vec4 {
+        let width = extent.width.max(1) as f32;
+        let height = extent.height.max(1) as f32;
+
+        Vec4::new(
+            self.x / width * 2.0 - 1.0,
+            self.y / height * 2.0 - 1.0,
+            (self.x + self.width) / width * 2.0 - 1.0,
+            (self.y + self.height) / height * 2.0 - 1.0,
+        )
+    }
+}
+
+/// Slot index plus the slot's generation, so ids of removed overlays stop resolving once the
+/// slot is reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OverlayId {
+    index: usize,
+    generation: u32,
+}
+
+#[derive(Default)]
+struct OverlaySlot {
+    generation: u32,
+    overlay: Option<Overlay>,
+}
+
+struct Overlay {
+    descriptor_set: vk::DescriptorSet,
+    rect: OverlayRect,
+    uv_rect: Vec4,
+    opacity: f32,
+    visible: bool,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct OverlayPushConstants {
+    rect: Vec4,
+    uv_rect: Vec4,
+    opacity: f32,
+}
+
+/// Draws textures into screen-space quads, e.g. a minimap, a rear-view camera rendered into a
+/// `VulkanOffscreenTarget`, or a debug view of any sampled image.
+pub struct OverlayRenderer {
+    pub pipeline: VulkanPipeline,
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    descriptor_pool: VulkanDescriptorPool,
+    sampler: VulkanSampler,
+    overlays: Vec<OverlaySlot>,
+    free_descriptor_sets: Vec<vk::DescriptorSet>,
+    max_overlays: usize,
+    device: Arc<Device>,
+}
+
+impl OverlayRenderer {
+    pub fn new(
+        device: &VulkanDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        max_overlays: usize,
+    ) -> Result<Self> {
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[VulkanDescriptorSetLayout::binding(
+                0,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                vk::ShaderStageFlags::FRAGMENT,
+            )],
+        )?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(
+            device,
+            &descriptor_set_layout,
+            max_overlays.max(1) as u32,
+        )?;
+
+        let sampler = VulkanSampler::linear_clamp(device)?;
+
+        let pipeline = VulkanPipelineBuilder::new(device)
+            .set_render_pass(render_pass)
+            .set_extent(extent)
+            .with_vertex_spv(include_bytes!("../../bin/overlay.vert.spv"))?
+            .with_fragment_spv(include_bytes!("../../bin/overlay.frag.spv"))?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+                    .offset(0)
+                    .size(std::mem::size_of::<OverlayPushConstants>() as u32),
+            )
+            .with_cull_mode(vk::CullModeFlags::NONE)
+            .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .with_alpha_blending()
+            .build()?;
+
+        Ok(Self {
+            pipeline,
+            descriptor_set_layout,
+            descriptor_pool,
+            sampler,
+            overlays: Vec::new(),
+            free_descriptor_sets: Vec::new(),
+            max_overlays,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Adds a quad displaying `texture`, which must be in `SHADER_READ_ONLY_OPTIMAL` when drawn.
+    pub fn add_overlay(&mut self, texture: vk::ImageView, rect: OverlayRect) -> Result<OverlayId> {
+        let active = self
+            .overlays
+            .iter()
+            .filter(|slot| slot.overlay.is_some())
+            .count();
+        if active >= self.max_overlays {
+            return Err(anyhow::anyhow!(
+                "Overlay limit of {} reached",
+                self.max_overlays
+            ));
+        }
+
+        let descriptor_set = match self.free_descriptor_sets.pop() {
+            Some(set) => set,
+            None => self.descriptor_pool.allocate(&self.descriptor_set_layout)?,
+        };
+
+        let overlay = Overlay {
+            descriptor_set,
+            rect,
+            uv_rect: Vec4::new(0.0, 0.0, 1.0, 1.0),
+            opacity: 1.0,
+            visible: true,
+        };
+
+        let index = match self.overlays.iter().position(|slot| slot.overlay.is_none()) {
+            Some(index) => index,
+            None => {
+                self.overlays.push(OverlaySlot::default());
+                self.overlays.len() - 1
+            }
+        };
+
+        let slot = &mut self.overlays[index];
+        slot.overlay = Some(overlay);
+
+        let id = OverlayId {
+            index,
+            generation: slot.generation,
+        };
+        self.set_texture(id, texture)?;
+
+        Ok(id)
+    }
+
+    pub fn remove_overlay(&mut self, id: OverlayId) {
+        let Some(slot) = self
+            .overlays
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation)
+        else {
+            return;
+        };
+
+        if let Some(overlay) = slot.overlay.take() {
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free_descriptor_sets.push(overlay.descriptor_set);
+        }
+    }
+
+    /// Points an overlay at a new texture, e.g. after its source target was recreated. The
+    /// descriptor set is updated immediately, so no in-flight frame may still be using it.
+    pub fn set_texture(&mut self, id: OverlayId, texture: vk::ImageView) -> Result<()> {
+        let descriptor_set = self.overlay_mut(id)?.descriptor_set;
+
+        self.descriptor_pool.write_image(
+            descriptor_set,
+            0,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            texture,
+            self.sampler.sampler,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+
+        Ok(())
+    }
+
+    pub fn set_rect(&mut self, id: OverlayId, rect: OverlayRect) -> Result<()> {
+        self.overlay_mut(id)?.rect = rect;
+        Ok(())
+    }
+
+    /// Sets the displayed sub-rectangle of the texture as (u, v, width, height).
+    pub fn set_uv_rect(&mut self, id: OverlayId, uv_rect: Vec4) -> Result<()> {
+        self.overlay_mut(id)?.uv_rect = uv_rect;
+        Ok(())
+    }
+
+    pub fn set_opacity(&mut self, id: OverlayId, opacity: f32) -> Result<()> {
+        self.overlay_mut(id)?.opacity = opacity.clamp(0.0, 1.0);
+        Ok(())
+    }
+
+    pub fn set_visible(&mut self, id: OverlayId, visible: bool) -> Result<()> {
+        self.overlay_mut(id)?.visible = visible;
+        Ok(())
+    }
+
+    /// Records all visible overlays in insertion order. Viewport and scissor are dynamic and
+    /// must already be set to cover `extent`.
+    pub fn record(&self, command_buffer: vk::CommandBuffer, extent: vk::Extent2D) {
+        let mut bound = false;
+
+        let overlays = self
+            .overlays
+            .iter()
+            .filter_map(|slot| slot.overlay.as_ref());
+
+        for overlay in overlays.filter(|o| o.visible) {
+            if !bound {
+                self.pipeline.bind(command_buffer);
+                bound = true;
+            }
+
+            let push_constants = OverlayPushConstants {
+                rect: overlay.rect.to_ndc(extent),
+                uv_rect: overlay.uv_rect,
+                opacity: overlay.opacity,
+            };
+
+            self.pipeline
+                .bind_descriptor_sets(command_buffer, 0, &[overlay.descriptor_set]);
+            self.pipeline.push_constants(
+                command_buffer,
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                0,
+                &push_constants,
+            );
+
+            self.device.draw(command_buffer, 6, 1, 0, 0);
+        }
+    }
+
+    fn overlay_mut(&mut self, id: OverlayId) -> Result<&mut Overlay> {
+        self.overlays
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.overlay.as_mut())
+            .ok_or_else(|| anyhow::anyhow!("Unknown overlay {:?}", id))
+    }
+}
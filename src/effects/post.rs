@@ -0,0 +1,131 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::sync::Arc;
+
+use crate::vulkan::{VulkanDevice, VulkanOffscreenTarget, VulkanPhysicalDevice};
+
+pub const POST_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// A full-screen pass of a `PostChain`.
+///
+/// Effects read one of the chain inputs returned by `PostChain::input_views` and write the
+/// chain's color target. Their pipelines must be built against `PostChain::render_pass`.
+pub trait PostEffect {
+    fn is_enabled(&self) -> bool {
+        true
+    }
+
+    /// Binds the possible chain inputs, indexed like `PostChain::input_views`. Called again
+    /// whenever the chain or the scene color image is recreated.
+    fn set_inputs(&mut self, inputs: &[vk::ImageView]);
+
+    /// Records the effect inside a begun render pass with viewport and scissor already set.
+    fn record(&self, command_buffer: vk::CommandBuffer, input: usize);
+}
+
+/// Ping-pong color targets running a sequence of `PostEffect`s over the scene color image.
+pub struct PostChain {
+    targets: [VulkanOffscreenTarget; 2],
+    device: Arc<Device>,
+}
+
+impl PostChain {
+    /// Input index of the scene color image.
+    pub const SCENE_INPUT: usize = 0;
+    /// Number of images returned by `input_views`.
+    pub const INPUT_COUNT: usize = 3;
+
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        extent: vk::Extent2D,
+    ) -> Result<Self> {
+        Ok(Self {
+            targets: [
+                VulkanOffscreenTarget::new(device, physical_device, extent, POST_FORMAT)?,
+                VulkanOffscreenTarget::new(device, physical_device, extent, POST_FORMAT)?,
+            ],
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn render_pass(&self) -> vk::RenderPass {
+        self.targets[0].render_pass.render_pass
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.targets[0].extent()
+    }
+
+    /// Every image an effect may read: the scene color image followed by both chain targets.
+    /// The scene image must be in `SHADER_READ_ONLY_OPTIMAL` when the chain is recorded.
+    pub fn input_views(&self, scene_view: vk::ImageView) -> [vk::ImageView; Self::INPUT_COUNT] {
+        [
+            scene_view,
+            self.targets[0].image.view,
+            self.targets[1].image.view,
+        ]
+    }
+
+    /// Records every enabled effect in order and returns the input index holding the final
+    /// image, `SCENE_INPUT` if no effect is enabled. The result is left in
+    /// `SHADER_READ_ONLY_OPTIMAL`.
+    pub fn record(&self, command_buffer: vk::CommandBuffer, effects: &[&dyn PostEffect]) -> usize {
+        let extent = self.extent();
+        let mut input = Self::SCENE_INPUT;
+
+        for effect in effects.iter().filter(|effect| effect.is_enabled()) {
+            let output = if input == 1 { 2 } else { 1 };
+            let target = &self.targets[output - 1];
+
+            let clear_values = [vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 1.0],
+                },
+            }];
+
+            let render_pass_info = vk::RenderPassBeginInfo::default()
+                .render_pass(target.render_pass.render_pass)
+                .framebuffer(target.framebuffer)
+                .render_area(vk::Rect2D {
+                    offset: vk::Offset2D { x: 0, y: 0 },
+                    extent,
+                })
+                .clear_values(&clear_values);
+
+            let viewport = vk::Viewport {
+                x: 0.0,
+                y: 0.0,
+                width: extent.width as f32,
+                height: extent.height as f32,
+                min_depth: 0.0,
+                max_depth: 1.0,
+            };
+
+            let scissor = vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            };
+
+            unsafe {
+                self.device.cmd_begin_render_pass(
+                    command_buffer,
+                    &render_pass_info,
+                    vk::SubpassContents::INLINE,
+                );
+                self.device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+                self.device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+            }
+
+            effect.record(command_buffer, input);
+
+            unsafe {
+                self.device.cmd_end_render_pass(command_buffer);
+            }
+
+            input = output;
+        }
+
+        input
+    }
+}
@@ -0,0 +1,265 @@
+use anyhow::Result;
+use ash::vk;
+use glam::{Vec2, Vec4};
+
+use crate::pipeline::{VulkanComputePipeline, VulkanComputePipelineBuilder};
+use crate::vulkan::{
+    StorageImage, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanPhysicalDevice,
+};
+
+/// Format of procedural textures, matching the `rgba8` image in `shaders/noise.comp`. Storage
+/// support for it is required by Vulkan. Values are written as-is, so they are linear.
+pub const PROCEDURAL_TEXTURE_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NoiseKind {
+    /// Gradient noise on a square lattice.
+    #[default]
+    Perlin = 0,
+    /// Simplex noise, smoother and without Perlin's axis-aligned artifacts.
+    Simplex = 1,
+    /// Distance to the nearest feature point (F1 cellular noise), for cells, stones and scales.
+    Worley = 2,
+}
+
+/// Parameters of a fractal noise texture. Every octave tiles across the texture, so the result
+/// wraps seamlessly with a `REPEAT` sampler.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseSettings {
+    pub kind: NoiseKind,
+    /// Lattice cells across the texture for the first octave.
+    pub period: u32,
+    pub octaves: u32,
+    /// Period multiplier between octaves. Whole numbers keep every octave tiling.
+    pub lacunarity: u32,
+    /// Amplitude multiplier between octaves.
+    pub gain: f32,
+    pub seed: u32,
+    /// Shift of the noise in texture widths. Whole numbers give the same texture back.
+    pub offset: Vec2,
+    /// Colors the noise value blends between, from 0 to 1.
+    pub color_low: Vec4,
+    pub color_high: Vec4,
+}
+
+impl Default for NoiseSettings {
+    fn default() -> Self {
+        Self {
+            kind: NoiseKind::Perlin,
+            period: 8,
+            octaves: 4,
+            lacunarity: 2,
+            gain: 0.5,
+            seed: 0,
+            offset: Vec2::ZERO,
+            color_low: Vec4::new(0.0, 0.0, 0.0, 1.0),
+            color_high: Vec4::ONE,
+        }
+    }
+}
+
+impl NoiseSettings {
+    pub fn new(kind: NoiseKind) -> Self {
+        Self {
+            kind,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_period(mut self, period: u32) -> Self {
+        self.period = period;
+        self
+    }
+
+    pub fn with_octaves(mut self, octaves: u32, lacunarity: u32, gain: f32) -> Self {
+        self.octaves = octaves;
+        self.lacunarity = lacunarity;
+        self.gain = gain;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_colors(mut self, low: Vec4, high: Vec4) -> Self {
+        self.color_low = low;
+        self.color_high = high;
+        self
+    }
+}
+
+/// Highest octave count recorded; later octaves are far below a texel at any useful period.
+const MAX_OCTAVES: u32 = 12;
+
+/// Mirrors the `Noise` push constant block in `shaders/noise.comp`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct NoisePushConstants {
+    color_low: Vec4,
+    color_high: Vec4,
+    params: [u32; 4],
+    gain: f32,
+    seed: u32,
+    offset: Vec2,
+}
+
+impl NoisePushConstants {
+    fn new(settings: &NoiseSettings) -> Self {
+        Self {
+            color_low: settings.color_low,
+            color_high: settings.color_high,
+            params: [
+                settings.kind as u32,
+                settings.period.max(1),
+                settings.octaves.clamp(1, MAX_OCTAVES),
+                settings.lacunarity.max(1),
+            ],
+            gain: settings.gain,
+            seed: settings.seed,
+            offset: settings.offset,
+        }
+    }
+}
+
+/// A noise texture written by `ProceduralTextureGenerator`. After `generate` its view can be
+/// used like any sampled texture, e.g. as `MaterialTextures::albedo`.
+pub struct ProceduralTexture {
+    pub image: StorageImage,
+    pub settings: NoiseSettings,
+    descriptor_set: vk::DescriptorSet,
+    _descriptor_pool: VulkanDescriptorPool,
+}
+
+impl ProceduralTexture {
+    pub fn view(&self) -> vk::ImageView {
+        self.image.view()
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.image.extent()
+    }
+}
+
+/// Generates Perlin, simplex and Worley noise textures on the GPU with a compute shader.
+pub struct ProceduralTextureGenerator {
+    pipeline: VulkanComputePipeline,
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+}
+
+impl ProceduralTextureGenerator {
+    pub fn new(device: &VulkanDevice) -> Result<Self> {
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[VulkanDescriptorSetLayout::binding(
+                0,
+                vk::DescriptorType::STORAGE_IMAGE,
+                vk::ShaderStageFlags::COMPUTE,
+            )],
+        )?;
+
+        let pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/noise.comp.spv"), None)?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(std::mem::size_of::<NoisePushConstants>() as u32),
+            )
+            .build()?;
+
+        Ok(Self {
+            pipeline,
+            descriptor_set_layout,
+        })
+    }
+
+    /// Creates an unwritten texture; record `generate` before sampling it.
+    pub fn create_texture(
+        &self,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        extent: vk::Extent2D,
+        settings: NoiseSettings,
+    ) -> Result<ProceduralTexture> {
+        let image = StorageImage::new(device, physical_device, extent, PROCEDURAL_TEXTURE_FORMAT)?;
+        let descriptor_pool =
+            VulkanDescriptorPool::for_layout(device, &self.descriptor_set_layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&self.descriptor_set_layout)?;
+        image.write_storage_descriptor(&descriptor_pool, descriptor_set, 0);
+
+        Ok(ProceduralTexture {
+            image,
+            settings,
+            descriptor_set,
+            _descriptor_pool: descriptor_pool,
+        })
+    }
+
+    /// Records the compute pass writing `texture` from its settings, then moves it to
+    /// `SHADER_READ_ONLY_OPTIMAL` for sampling in `read_stage`. Must be recorded outside a
+    /// render pass; record it again after changing the settings.
+    pub fn generate(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        texture: &mut ProceduralTexture,
+        read_stage: vk::PipelineStageFlags,
+    ) {
+        let extent = texture.extent();
+
+        // Every texel is rewritten, so the previous contents can be discarded.
+        texture.image.reset();
+        texture
+            .image
+            .cmd_prepare_write(command_buffer, vk::PipelineStageFlags::COMPUTE_SHADER);
+
+        self.pipeline.bind(command_buffer);
+        self.pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[texture.descriptor_set]);
+        self.pipeline
+            .push_constants(command_buffer, &NoisePushConstants::new(&texture.settings));
+        self.pipeline.dispatch(
+            command_buffer,
+            extent.width.div_ceil(8),
+            extent.height.div_ceil(8),
+            1,
+        );
+
+        texture
+            .image
+            .cmd_prepare_sampled_read(command_buffer, read_stage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ShaderReflection;
+
+    #[test]
+    fn shader_layout_matches_noise_push_constants() {
+        let reflection =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/noise.comp.spv")).unwrap();
+        assert_eq!(
+            reflection.push_constant_size,
+            Some(std::mem::size_of::<NoisePushConstants>() as u32)
+        );
+    }
+
+    #[test]
+    fn push_constants_clamp_degenerate_settings() {
+        let settings = NoiseSettings::new(NoiseKind::Worley)
+            .with_period(0)
+            .with_octaves(100, 0, 0.5);
+        let constants = NoisePushConstants::new(&settings);
+
+        assert_eq!(constants.params, [2, 1, MAX_OCTAVES, 1]);
+
+        let settings = settings.with_octaves(0, 3, 0.5);
+        assert_eq!(NoisePushConstants::new(&settings).params[2], 1);
+    }
+}
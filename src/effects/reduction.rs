@@ -0,0 +1,385 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::sync::Arc;
+
+use crate::pipeline::{VulkanComputePipeline, VulkanComputePipelineBuilder};
+use crate::vulkan::{
+    VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanPhysicalDevice, VulkanSampler,
+};
+
+/// Texels per side of the workgroups in `shaders/reduce.comp`.
+const GROUP_SIDE: u32 = 16;
+/// `min_key`, `max_key` and padding before the partial sums in the result buffer.
+const HEADER_WORDS: usize = 4;
+
+/// Which value of each texel is reduced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReductionSource {
+    /// Channel 0-3 (R, G, B, A), e.g. 0 for depth or single-channel images.
+    Channel(u32),
+    /// Rec. 709 luminance of the RGB channels.
+    Luminance,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReductionSettings {
+    pub source: ReductionSource,
+    /// Averages `ln(value)` and reports `exp` of the mean, the usual input of auto-exposure.
+    pub log_average: bool,
+    /// Values outside this range are skipped, e.g. `1.0` to leave the far plane out of a
+    /// depth range.
+    pub min_value: f32,
+    pub max_value: f32,
+}
+
+impl Default for ReductionSettings {
+    fn default() -> Self {
+        Self {
+            source: ReductionSource::Channel(0),
+            log_average: false,
+            min_value: f32::MIN,
+            max_value: f32::MAX,
+        }
+    }
+}
+
+impl ReductionSettings {
+    /// Log-average luminance of an HDR color image.
+    pub fn luminance() -> Self {
+        Self {
+            source: ReductionSource::Luminance,
+            log_average: true,
+            ..Default::default()
+        }
+    }
+
+    /// Depth range of the covered pixels, skipping the cleared far plane.
+    pub fn depth() -> Self {
+        Self {
+            max_value: 1.0 - f32::EPSILON,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReductionStats {
+    pub min: f32,
+    pub max: f32,
+    /// Arithmetic mean, or geometric mean with `log_average`.
+    pub average: f32,
+    /// Texels within the settings' value range.
+    pub count: u64,
+}
+
+/// Mirrors the `Reduction` push constant block in `shaders/reduce.comp`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ReductionPushConstants {
+    channel: u32,
+    log_average: u32,
+    range_min: f32,
+    range_max: f32,
+}
+
+impl ReductionPushConstants {
+    fn new(settings: &ReductionSettings) -> Self {
+        Self {
+            channel: match settings.source {
+                ReductionSource::Channel(channel) => channel.min(3),
+                ReductionSource::Luminance => 4,
+            },
+            log_average: u32::from(settings.log_average),
+            range_min: settings.min_value,
+            range_max: settings.max_value,
+        }
+    }
+}
+
+/// Inverse of `float_key` in `shaders/reduce.comp`, which maps floats to integers in the same
+/// order so they can go through `atomicMin` and `atomicMax`.
+fn key_to_float(key: u32) -> f32 {
+    if key & 0x8000_0000 != 0 {
+        f32::from_bits(key & 0x7fff_ffff)
+    } else {
+        f32::from_bits(!key)
+    }
+}
+
+/// Adds up the per-workgroup sums of a result buffer read back as words.
+fn finish(words: &[u32], log_average: bool) -> Option<ReductionStats> {
+    let (header, partials) = words.split_at(HEADER_WORDS);
+    let (sum, count) = partials
+        .chunks_exact(2)
+        .fold((0.0f64, 0u64), |(sum, count), partial| {
+            (
+                sum + f32::from_bits(partial[0]) as f64,
+                count + partial[1] as u64,
+            )
+        });
+    if count == 0 {
+        return None;
+    }
+
+    let mean = sum / count as f64;
+    Some(ReductionStats {
+        min: key_to_float(header[0]),
+        max: key_to_float(header[1]),
+        average: if log_average { mean.exp() } else { mean } as f32,
+        count,
+    })
+}
+
+struct ReductionSlot {
+    buffer: VulkanBuffer,
+    descriptor_set: vk::DescriptorSet,
+    /// Workgroups and settings of the last recorded reduction, if any.
+    recorded: Option<(u32, bool)>,
+}
+
+/// Reduces one value per texel of an image to its min, max and average on the GPU, into a
+/// small host-visible buffer, for auto-exposure and debug HUD statistics.
+///
+/// Results go through one of `slots` buffers, usually one per frame in flight: `record` into
+/// a slot, then `read` it once that frame's fence has signaled.
+pub struct ImageReduction {
+    pipeline: VulkanComputePipeline,
+    _descriptor_set_layout: VulkanDescriptorSetLayout,
+    descriptor_pool: VulkanDescriptorPool,
+    slots: Vec<ReductionSlot>,
+    sampler: VulkanSampler,
+    max_groups: u32,
+    device: Arc<Device>,
+}
+
+impl ImageReduction {
+    /// Reductions of images up to `max_extent`.
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        max_extent: vk::Extent2D,
+        slots: u32,
+    ) -> Result<Self> {
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[
+                VulkanDescriptorSetLayout::binding(
+                    0,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    1,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+            ],
+        )?;
+        let slots = slots.max(1);
+        let descriptor_pool =
+            VulkanDescriptorPool::for_layout(device, &descriptor_set_layout, slots)?;
+
+        let max_groups =
+            max_extent.width.div_ceil(GROUP_SIDE) * max_extent.height.div_ceil(GROUP_SIDE);
+        let buffer_size = ((HEADER_WORDS + 2 * max_groups as usize) * std::mem::size_of::<u32>())
+            as vk::DeviceSize;
+
+        let slots = (0..slots)
+            .map(|_| {
+                let buffer = VulkanBuffer::new_host_visible(
+                    device,
+                    physical_device,
+                    buffer_size,
+                    vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+                )?;
+                let descriptor_set = descriptor_pool.allocate(&descriptor_set_layout)?;
+                descriptor_pool.write_buffer(
+                    descriptor_set,
+                    1,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    &buffer,
+                );
+                Ok(ReductionSlot {
+                    buffer,
+                    descriptor_set,
+                    recorded: None,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/reduce.comp.spv"), None)?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(std::mem::size_of::<ReductionPushConstants>() as u32),
+            )
+            .build()?;
+
+        Ok(Self {
+            pipeline,
+            _descriptor_set_layout: descriptor_set_layout,
+            descriptor_pool,
+            slots,
+            sampler: VulkanSampler::nearest_clamp(device)?,
+            max_groups,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Records the reduction of `view`, an `extent`-sized image in `SHADER_READ_ONLY_OPTIMAL`
+    /// whose writes are already visible to compute shaders, into `slot`. Must be recorded
+    /// outside a render pass, and not while an earlier reduction into `slot` may still run.
+    pub fn record(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        slot: usize,
+        view: vk::ImageView,
+        extent: vk::Extent2D,
+        settings: &ReductionSettings,
+    ) -> Result<()> {
+        let groups = (
+            extent.width.div_ceil(GROUP_SIDE),
+            extent.height.div_ceil(GROUP_SIDE),
+        );
+        if groups.0 * groups.1 > self.max_groups {
+            return Err(anyhow::anyhow!(
+                "Image of {}x{} is larger than the reduction was created for",
+                extent.width,
+                extent.height
+            ));
+        }
+        let Some(reduction_slot) = self.slots.get_mut(slot) else {
+            return Err(anyhow::anyhow!("Unknown reduction slot {}", slot));
+        };
+
+        self.descriptor_pool.write_image(
+            reduction_slot.descriptor_set,
+            0,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            view,
+            self.sampler.sampler,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+
+        let buffer = &reduction_slot.buffer;
+        unsafe {
+            self.device
+                .cmd_fill_buffer(command_buffer, buffer.buffer, 0, 4, u32::MAX);
+            self.device
+                .cmd_fill_buffer(command_buffer, buffer.buffer, 4, 4, 0);
+        }
+        buffer.cmd_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+        );
+
+        self.pipeline.bind(command_buffer);
+        self.pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[reduction_slot.descriptor_set]);
+        self.pipeline
+            .push_constants(command_buffer, &ReductionPushConstants::new(settings));
+        self.pipeline
+            .dispatch(command_buffer, groups.0, groups.1, 1);
+
+        buffer.cmd_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::HOST,
+            vk::AccessFlags::HOST_READ,
+        );
+
+        reduction_slot.recorded = Some((groups.0 * groups.1, settings.log_average));
+        Ok(())
+    }
+
+    /// Statistics of the last reduction recorded into `slot`, which must have finished
+    /// executing. `None` when nothing was recorded or no texel was in range.
+    pub fn read(&self, slot: usize) -> Result<Option<ReductionStats>> {
+        let reduction_slot = self
+            .slots
+            .get(slot)
+            .ok_or_else(|| anyhow::anyhow!("Unknown reduction slot {}", slot))?;
+        let Some((groups, log_average)) = reduction_slot.recorded else {
+            return Ok(None);
+        };
+
+        let words = reduction_slot
+            .buffer
+            .read::<u32>(0, HEADER_WORDS + 2 * groups as usize)?;
+        Ok(finish(&words, log_average))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ShaderReflection;
+
+    /// `float_key` from `shaders/reduce.comp`.
+    fn float_key(value: f32) -> u32 {
+        let bits = value.to_bits();
+        if bits & 0x8000_0000 != 0 {
+            !bits
+        } else {
+            bits | 0x8000_0000
+        }
+    }
+
+    #[test]
+    fn shader_layout_matches_reduction_push_constants() {
+        let reflection =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/reduce.comp.spv")).unwrap();
+        assert_eq!(
+            reflection.push_constant_size,
+            Some(std::mem::size_of::<ReductionPushConstants>() as u32)
+        );
+    }
+
+    #[test]
+    fn float_keys_keep_order_and_round_trip() {
+        let values = [-1e9, -2.5, -0.0, 0.0, 1e-7, 0.5, 3.0, f32::MAX];
+        for pair in values.windows(2) {
+            assert!(float_key(pair[0]) <= float_key(pair[1]));
+        }
+        for value in values {
+            assert_eq!(key_to_float(float_key(value)), value);
+        }
+    }
+
+    #[test]
+    fn finishes_partial_sums() {
+        let header = [float_key(0.5), float_key(4.0), 0, 0];
+        let partials = [
+            2.0f32.to_bits(),
+            2,
+            0.0f32.to_bits(),
+            0,
+            4.0f32.to_bits(),
+            2,
+        ];
+        let words = [header.as_slice(), &partials].concat();
+
+        let stats = finish(&words, false).unwrap();
+        assert_eq!((stats.min, stats.max, stats.count), (0.5, 4.0, 4));
+        assert_eq!(stats.average, 1.5);
+
+        // Sums of logarithms average geometrically.
+        let log_partials = [(2.0f32.ln() * 2.0).to_bits(), 2];
+        let words = [header.as_slice(), &log_partials].concat();
+        assert!((finish(&words, true).unwrap().average - 2.0).abs() < 1e-5);
+
+        assert_eq!(finish(&[0, 0, 0, 0, 0, 0], false), None);
+    }
+}
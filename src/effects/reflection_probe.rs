@@ -0,0 +1,762 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::{Mat4, Vec3};
+use std::sync::Arc;
+
+use crate::pipeline::{VulkanComputePipeline, VulkanComputePipelineBuilder};
+use crate::vulkan::{
+    VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice, VulkanImage, VulkanImageInfo,
+    VulkanPhysicalDevice, VulkanRenderPass, VulkanSampler,
+};
+
+pub const PROBE_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// Size of the probe array bound by `ReflectionProbeSet`; matches `MAX_REFLECTION_PROBES` in
+/// `shaders/reflection_probes.glsl`.
+pub const MAX_REFLECTION_PROBES: usize = 8;
+
+const FACE_DIRECTIONS: [(Vec3, Vec3); 6] = [
+    (Vec3::X, Vec3::NEG_Y),
+    (Vec3::NEG_X, Vec3::NEG_Y),
+    (Vec3::Y, Vec3::Z),
+    (Vec3::NEG_Y, Vec3::NEG_Z),
+    (Vec3::Z, Vec3::NEG_Y),
+    (Vec3::NEG_Z, Vec3::NEG_Y),
+];
+
+/// A captured environment cube map. Mip `n` of `prefiltered` holds the radiance convolved with
+/// a GGX lobe of roughness `n / (mip_levels - 1)`.
+pub struct ReflectionProbe {
+    pub position: Vec3,
+    pub radius: f32,
+    pub near: f32,
+    pub far: f32,
+    pub prefiltered: VulkanImage,
+    capture: VulkanImage,
+    depth: VulkanImage,
+    face_views: Vec<vk::ImageView>,
+    framebuffers: Vec<vk::Framebuffer>,
+    mip_views: Vec<vk::ImageView>,
+    descriptor_pool: VulkanDescriptorPool,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    baked: bool,
+    device: Arc<Device>,
+}
+
+impl ReflectionProbe {
+    pub fn face_view_proj(&self, face: usize) -> Mat4 {
+        let (direction, up) = FACE_DIRECTIONS[face];
+        let view = Mat4::look_at_rh(self.position, self.position + direction, up);
+        let proj = Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, self.near, self.far);
+        proj * view
+    }
+
+    pub fn resolution(&self) -> u32 {
+        self.capture.extent.width
+    }
+
+    pub fn is_baked(&self) -> bool {
+        self.baked
+    }
+
+    /// Requests a re-bake, e.g. after the static scene around the probe changed.
+    pub fn mark_dirty(&mut self) {
+        self.baked = false;
+    }
+}
+
+impl Drop for ReflectionProbe {
+    fn drop(&mut self) {
+        unsafe {
+            for &framebuffer in &self.framebuffers {
+                self.device.destroy_framebuffer(framebuffer, None);
+            }
+            for &view in self.face_views.iter().chain(&self.mip_views) {
+                self.device.destroy_image_view(view, None);
+            }
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PrefilterPushConstants {
+    roughness: f32,
+    size: u32,
+    sample_count: u32,
+}
+
+/// Captures the scene into reflection probes and prefilters the result for glossy reflections.
+pub struct ReflectionProbeBaker {
+    pub render_pass: VulkanRenderPass,
+    pub sample_count: u32,
+    prefilter_pipeline: VulkanComputePipeline,
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    sampler: VulkanSampler,
+    depth_format: vk::Format,
+    device: Arc<Device>,
+}
+
+impl ReflectionProbeBaker {
+    pub fn new(device: &VulkanDevice, depth_format: vk::Format) -> Result<Self> {
+        let render_pass = VulkanRenderPass::with_formats(
+            device,
+            PROBE_FORMAT,
+            Some(depth_format),
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )?;
+
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[
+                VulkanDescriptorSetLayout::binding(
+                    0,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    1,
+                    vk::DescriptorType::STORAGE_IMAGE,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+            ],
+        )?;
+
+        let prefilter_pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/probe_prefilter.comp.spv"), None)?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(std::mem::size_of::<PrefilterPushConstants>() as u32),
+            )
+            .build()?;
+
+        let sampler = VulkanSampler::linear_clamp(device)?;
+
+        Ok(Self {
+            render_pass,
+            sample_count: 512,
+            prefilter_pipeline,
+            descriptor_set_layout,
+            sampler,
+            depth_format,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn create_probe(
+        &self,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        position: Vec3,
+        radius: f32,
+        resolution: u32,
+        mip_levels: u32,
+    ) -> Result<ReflectionProbe> {
+        let extent = vk::Extent2D {
+            width: resolution,
+            height: resolution,
+        };
+        let mip_levels = mip_levels.clamp(1, resolution.max(1).ilog2() + 1);
+
+        // The capture keeps a full mip chain so the prefilter can sample wide lobes without
+        // aliasing.
+        let capture = VulkanImage::from_info(
+            device,
+            physical_device,
+            &VulkanImageInfo::cube(
+                resolution,
+                PROBE_FORMAT,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT
+                    | vk::ImageUsageFlags::SAMPLED
+                    | vk::ImageUsageFlags::TRANSFER_SRC
+                    | vk::ImageUsageFlags::TRANSFER_DST,
+            )
+            .with_mip_levels(resolution.max(1).ilog2() + 1),
+        )?;
+
+        let prefiltered = VulkanImage::from_info(
+            device,
+            physical_device,
+            &VulkanImageInfo::cube(
+                resolution,
+                PROBE_FORMAT,
+                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+            )
+            .with_mip_levels(mip_levels),
+        )?;
+
+        let depth = VulkanImage::new(
+            device,
+            physical_device,
+            extent,
+            self.depth_format,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            vk::ImageAspectFlags::DEPTH,
+        )?;
+
+        let descriptor_pool =
+            VulkanDescriptorPool::for_layout(device, &self.descriptor_set_layout, mip_levels)?;
+
+        let mut probe = ReflectionProbe {
+            position,
+            radius,
+            near: 0.05,
+            far: radius.max(1.0) * 4.0,
+            prefiltered,
+            capture,
+            depth,
+            face_views: Vec::with_capacity(6),
+            framebuffers: Vec::with_capacity(6),
+            mip_views: Vec::with_capacity(mip_levels as usize),
+            descriptor_pool,
+            descriptor_sets: Vec::with_capacity(mip_levels as usize),
+            baked: false,
+            device: device.device.clone(),
+        };
+
+        for face in 0..6 {
+            let face_view = probe.capture.create_view(
+                vk::ImageViewType::TYPE_2D,
+                vk::ImageSubresourceRange {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: face,
+                    layer_count: 1,
+                },
+            )?;
+            probe.face_views.push(face_view);
+
+            let attachments = [face_view, probe.depth.view];
+            let framebuffer_info = vk::FramebufferCreateInfo::default()
+                .render_pass(self.render_pass.render_pass)
+                .attachments(&attachments)
+                .width(resolution)
+                .height(resolution)
+                .layers(1);
+
+            let framebuffer = unsafe {
+                device
+                    .device
+                    .create_framebuffer(&framebuffer_info, None)
+                    .map_err(|e| anyhow::anyhow!("Failed to create probe framebuffer: {}", e))?
+            };
+            probe.framebuffers.push(framebuffer);
+        }
+
+        for mip in 0..mip_levels {
+            let mip_view = probe.prefiltered.create_view(
+                vk::ImageViewType::TYPE_2D_ARRAY,
+                vk::ImageSubresourceRange {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    base_mip_level: mip,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: 6,
+                },
+            )?;
+            probe.mip_views.push(mip_view);
+
+            let descriptor_set = probe
+                .descriptor_pool
+                .allocate(&self.descriptor_set_layout)?;
+            probe.descriptor_pool.write_image(
+                descriptor_set,
+                0,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                probe.capture.view,
+                self.sampler.sampler,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            );
+            probe
+                .descriptor_pool
+                .write_storage_image(descriptor_set, 1, mip_view);
+            probe.descriptor_sets.push(descriptor_set);
+        }
+
+        Ok(probe)
+    }
+
+    /// Records the capture of all six faces, the capture mip chain, then the prefilter
+    /// dispatches.
+    ///
+    /// `draw_scene` is called once per face inside the baker's render pass with the face's
+    /// view-projection matrix; viewport and scissor are already set to the probe resolution.
+    pub fn record_bake(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        probe: &mut ReflectionProbe,
+        mut draw_scene: impl FnMut(vk::CommandBuffer, usize, Mat4),
+    ) {
+        let resolution = probe.resolution();
+        let extent = vk::Extent2D {
+            width: resolution,
+            height: resolution,
+        };
+
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 1.0],
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+
+        for face in 0..6 {
+            let render_pass_info = vk::RenderPassBeginInfo::default()
+                .render_pass(self.render_pass.render_pass)
+                .framebuffer(probe.framebuffers[face])
+                .render_area(vk::Rect2D {
+                    offset: vk::Offset2D { x: 0, y: 0 },
+                    extent,
+                })
+                .clear_values(&clear_values);
+
+            unsafe {
+                self.device.cmd_begin_render_pass(
+                    command_buffer,
+                    &render_pass_info,
+                    vk::SubpassContents::INLINE,
+                );
+
+                self.device.cmd_set_viewport(
+                    command_buffer,
+                    0,
+                    &[vk::Viewport {
+                        x: 0.0,
+                        y: 0.0,
+                        width: resolution as f32,
+                        height: resolution as f32,
+                        min_depth: 0.0,
+                        max_depth: 1.0,
+                    }],
+                );
+                self.device.cmd_set_scissor(
+                    command_buffer,
+                    0,
+                    &[vk::Rect2D {
+                        offset: vk::Offset2D { x: 0, y: 0 },
+                        extent,
+                    }],
+                );
+            }
+
+            draw_scene(command_buffer, face, probe.face_view_proj(face));
+
+            unsafe {
+                self.device.cmd_end_render_pass(command_buffer);
+            }
+        }
+
+        Self::record_capture_mips(command_buffer, &probe.capture);
+
+        probe.prefiltered.cmd_transition(
+            command_buffer,
+            probe.prefiltered.subresource_range(),
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::empty(),
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+        );
+
+        self.prefilter_pipeline.bind(command_buffer);
+
+        let mip_levels = probe.prefiltered.mip_levels;
+        for (mip, &descriptor_set) in probe.descriptor_sets.iter().enumerate() {
+            let size = (resolution >> mip).max(1);
+            let roughness = if mip_levels > 1 {
+                mip as f32 / (mip_levels - 1) as f32
+            } else {
+                0.0
+            };
+
+            self.prefilter_pipeline
+                .bind_descriptor_sets(command_buffer, 0, &[descriptor_set]);
+            self.prefilter_pipeline.push_constants(
+                command_buffer,
+                &PrefilterPushConstants {
+                    roughness,
+                    size,
+                    sample_count: self.sample_count,
+                },
+            );
+            self.prefilter_pipeline
+                .dispatch(command_buffer, size.div_ceil(8), size.div_ceil(8), 6);
+        }
+
+        probe.prefiltered.cmd_transition(
+            command_buffer,
+            probe.prefiltered.subresource_range(),
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        );
+
+        probe.baked = true;
+    }
+}
+
+impl ReflectionProbeBaker {
+    /// Downsamples mip 0 of every face into the rest of the chain and leaves the whole image in
+    /// `SHADER_READ_ONLY_OPTIMAL` for the prefilter. Mip 0 must have just been rendered.
+    fn record_capture_mips(command_buffer: vk::CommandBuffer, capture: &VulkanImage) {
+        let mip_range = |base_mip_level: u32, level_count: u32| vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level,
+            level_count,
+            base_array_layer: 0,
+            layer_count: 6,
+        };
+        let mip_layers = |mip_level: u32| vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level,
+            base_array_layer: 0,
+            layer_count: 6,
+        };
+        let mip_size = |mip: u32| (capture.extent.width >> mip).max(1) as i32;
+
+        capture.cmd_transition(
+            command_buffer,
+            mip_range(0, 1),
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_READ,
+        );
+
+        if capture.mip_levels > 1 {
+            capture.cmd_transition(
+                command_buffer,
+                mip_range(1, capture.mip_levels - 1),
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::AccessFlags::empty(),
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_WRITE,
+            );
+        }
+
+        for mip in 1..capture.mip_levels {
+            let blit = vk::ImageBlit::default()
+                .src_subresource(mip_layers(mip - 1))
+                .src_offsets([
+                    vk::Offset3D::default(),
+                    vk::Offset3D {
+                        x: mip_size(mip - 1),
+                        y: mip_size(mip - 1),
+                        z: 1,
+                    },
+                ])
+                .dst_subresource(mip_layers(mip))
+                .dst_offsets([
+                    vk::Offset3D::default(),
+                    vk::Offset3D {
+                        x: mip_size(mip),
+                        y: mip_size(mip),
+                        z: 1,
+                    },
+                ]);
+
+            unsafe {
+                capture.device.cmd_blit_image(
+                    command_buffer,
+                    capture.image,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    capture.image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    std::slice::from_ref(&blit),
+                    vk::Filter::LINEAR,
+                );
+            }
+
+            capture.cmd_transition(
+                command_buffer,
+                mip_range(mip, 1),
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_READ,
+            );
+        }
+
+        capture.cmd_transition(
+            command_buffer,
+            capture.subresource_range(),
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE | vk::AccessFlags::TRANSFER_READ,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        );
+    }
+}
+
+/// Binds baked probes as an array of prefiltered cube maps for shading passes that include
+/// `shaders/reflection_probes.glsl`. Probe `i` of the slice passed to `update` is array element
+/// `i`, matching the indices returned by `resolve_probe_blend`.
+pub struct ReflectionProbeSet {
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    _descriptor_pool: VulkanDescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    sampler: VulkanSampler,
+    device: Arc<Device>,
+}
+
+impl ReflectionProbeSet {
+    pub fn new(device: &VulkanDevice) -> Result<Self> {
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[VulkanDescriptorSetLayout::binding(
+                0,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                vk::ShaderStageFlags::FRAGMENT,
+            )
+            .descriptor_count(MAX_REFLECTION_PROBES as u32)],
+        )?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(device, &descriptor_set_layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&descriptor_set_layout)?;
+        let sampler = VulkanSampler::linear_clamp(device)?;
+
+        Ok(Self {
+            descriptor_set_layout,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            sampler,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn descriptor_set_layout(&self) -> &VulkanDescriptorSetLayout {
+        &self.descriptor_set_layout
+    }
+
+    pub fn descriptor_set(&self) -> vk::DescriptorSet {
+        self.descriptor_set
+    }
+
+    /// Writes every array element; elements past `probes.len()` repeat the last probe so the
+    /// array is always fully bound. No in-flight frame may still be using the set.
+    pub fn update(&self, probes: &[ReflectionProbe]) -> Result<()> {
+        if probes.is_empty() || probes.len() > MAX_REFLECTION_PROBES {
+            return Err(anyhow::anyhow!(
+                "Reflection probe set needs 1 to {} probes, got {}",
+                MAX_REFLECTION_PROBES,
+                probes.len()
+            ));
+        }
+
+        let image_infos: Vec<vk::DescriptorImageInfo> = (0..MAX_REFLECTION_PROBES)
+            .map(|i| {
+                vk::DescriptorImageInfo::default()
+                    .image_view(probes[i.min(probes.len() - 1)].prefiltered.view)
+                    .sampler(self.sampler.sampler)
+                    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            })
+            .collect();
+
+        let write = vk::WriteDescriptorSet::default()
+            .dst_set(self.descriptor_set)
+            .dst_binding(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&image_infos);
+
+        unsafe {
+            self.device
+                .update_descriptor_sets(std::slice::from_ref(&write), &[]);
+        }
+
+        Ok(())
+    }
+}
+
+/// How an object picks the probe(s) it samples reflections from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProbeSelection {
+    Fixed(usize),
+    Nearest,
+    Blend,
+}
+
+/// Up to two probe indices with normalized weights, laid out like `ProbeBlend` in
+/// `shaders/reflection_probes.glsl` so it can be pushed per draw.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProbeBlend {
+    pub indices: [u32; 2],
+    pub weights: [f32; 2],
+}
+
+impl ProbeBlend {
+    fn single(index: usize) -> Self {
+        Self {
+            indices: [index as u32, index as u32],
+            weights: [1.0, 0.0],
+        }
+    }
+}
+
+/// Placement of a probe as seen by `resolve_probe_blend`.
+pub trait ProbePlacement {
+    fn position(&self) -> Vec3;
+    fn radius(&self) -> f32;
+    fn is_baked(&self) -> bool;
+}
+
+impl ProbePlacement for ReflectionProbe {
+    fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn is_baked(&self) -> bool {
+        self.baked
+    }
+}
+
+/// Resolves which baked probes shade a point at `position`. Returns `None` when no probe has
+/// been baked yet.
+pub fn resolve_probe_blend<P: ProbePlacement>(
+    probes: &[P],
+    selection: ProbeSelection,
+    position: Vec3,
+) -> Option<ProbeBlend> {
+    let baked = || probes.iter().enumerate().filter(|(_, p)| p.is_baked());
+
+    let nearest = || {
+        baked()
+            .min_by(|(_, a), (_, b)| {
+                a.position()
+                    .distance_squared(position)
+                    .total_cmp(&b.position().distance_squared(position))
+            })
+            .map(|(index, _)| ProbeBlend::single(index))
+    };
+
+    match selection {
+        ProbeSelection::Fixed(index) => probes
+            .get(index)
+            .filter(|p| p.is_baked())
+            .map(|_| ProbeBlend::single(index)),
+        ProbeSelection::Nearest => nearest(),
+        ProbeSelection::Blend => {
+            let mut influences: Vec<(usize, f32)> = baked()
+                .map(|(index, probe)| {
+                    let distance = probe.position().distance(position);
+                    (index, 1.0 - distance / probe.radius().max(f32::EPSILON))
+                })
+                .filter(|(_, influence)| *influence > 0.0)
+                .collect();
+
+            influences.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+            match influences.as_slice() {
+                [] => nearest(),
+                [(index, _)] => Some(ProbeBlend::single(*index)),
+                [(a, wa), (b, wb), ..] => Some(ProbeBlend {
+                    indices: [*a as u32, *b as u32],
+                    weights: [wa / (wa + wb), wb / (wa + wb)],
+                }),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestProbe {
+        position: Vec3,
+        radius: f32,
+        baked: bool,
+    }
+
+    impl ProbePlacement for TestProbe {
+        fn position(&self) -> Vec3 {
+            self.position
+        }
+
+        fn radius(&self) -> f32 {
+            self.radius
+        }
+
+        fn is_baked(&self) -> bool {
+            self.baked
+        }
+    }
+
+    fn probe(x: f32, radius: f32, baked: bool) -> TestProbe {
+        TestProbe {
+            position: Vec3::new(x, 0.0, 0.0),
+            radius,
+            baked,
+        }
+    }
+
+    #[test]
+    fn no_baked_probe_resolves_to_none() {
+        let probes = [probe(0.0, 5.0, false)];
+
+        for selection in [
+            ProbeSelection::Fixed(0),
+            ProbeSelection::Nearest,
+            ProbeSelection::Blend,
+        ] {
+            assert_eq!(resolve_probe_blend(&probes, selection, Vec3::ZERO), None);
+        }
+    }
+
+    #[test]
+    fn nearest_skips_unbaked_probes() {
+        let probes = [probe(0.0, 5.0, false), probe(10.0, 5.0, true)];
+
+        let blend = resolve_probe_blend(&probes, ProbeSelection::Nearest, Vec3::ZERO).unwrap();
+        assert_eq!(blend.indices[0], 1);
+        assert_eq!(blend.weights, [1.0, 0.0]);
+    }
+
+    #[test]
+    fn blend_weights_are_normalized_and_favor_the_closer_probe() {
+        let probes = [probe(0.0, 10.0, true), probe(4.0, 10.0, true)];
+
+        let blend =
+            resolve_probe_blend(&probes, ProbeSelection::Blend, Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert_eq!(blend.indices, [0, 1]);
+        assert!((blend.weights[0] + blend.weights[1] - 1.0).abs() < 1e-6);
+        assert!(blend.weights[0] > blend.weights[1]);
+    }
+
+    #[test]
+    fn blend_outside_every_radius_falls_back_to_nearest() {
+        let probes = [probe(0.0, 1.0, true), probe(10.0, 1.0, true)];
+
+        let blend =
+            resolve_probe_blend(&probes, ProbeSelection::Blend, Vec3::new(7.0, 0.0, 0.0)).unwrap();
+        assert_eq!(blend.indices[0], 1);
+        assert_eq!(blend.weights, [1.0, 0.0]);
+    }
+}
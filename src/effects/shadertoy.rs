@@ -0,0 +1,384 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::{Vec2, Vec3, Vec4};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::pipeline::{GlslcCompiler, ShaderCompiler, VulkanPipeline, VulkanPipelineBuilder};
+use crate::vulkan::{
+    RecordCommands, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice, VulkanSampler,
+};
+
+/// Path of a Shadertoy image shader to run instead of the default scene, see `ShaderToy`.
+pub const SHADERTOY_ENV: &str = "RVE_SHADERTOY";
+
+/// Number of `iChannel` samplers a shader may read.
+pub const SHADERTOY_CHANNELS: usize = 4;
+
+/// Shadertoy's built-in inputs, pushed as constants every frame. Laid out like the
+/// `ShaderToyInputs` block of `shadertoy_fragment_source`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ShaderToyUniforms {
+    /// `xy`: cursor position while a button is held; `zw`: where it was pressed, negated once
+    /// released. Pixels from the bottom-left corner, like Shadertoy.
+    pub mouse: Vec4,
+    /// Viewport size in pixels; `z` is the pixel aspect ratio, always 1.
+    pub resolution: Vec3,
+    pub time: f32,
+    pub time_delta: f32,
+    pub frame: i32,
+    /// `Vec4` makes the struct 16-byte aligned; the shader block ends at `frame`.
+    _padding: [u32; 2],
+}
+
+impl ShaderToyUniforms {
+    pub fn new(extent: vk::Extent2D) -> Self {
+        let mut uniforms = Self::default();
+        uniforms.set_extent(extent);
+        uniforms
+    }
+
+    pub fn set_extent(&mut self, extent: vk::Extent2D) {
+        self.resolution = Vec3::new(extent.width as f32, extent.height as f32, 1.0);
+    }
+
+    /// Advances to the next frame, `delta_time` seconds after the previous one.
+    pub fn advance(&mut self, delta_time: f32) {
+        self.time += delta_time;
+        self.time_delta = delta_time;
+        self.frame += 1;
+    }
+
+    /// Cursor moved to `position`, in window pixels from the top-left corner.
+    pub fn set_cursor(&mut self, position: Vec2) {
+        if self.mouse.z > 0.0 {
+            let position = self.window_to_shadertoy(position);
+            self.mouse.x = position.x;
+            self.mouse.y = position.y;
+        }
+    }
+
+    /// Mouse button pressed or released at `position`, in window pixels from the top-left.
+    pub fn set_button(&mut self, pressed: bool, position: Vec2) {
+        let position = self.window_to_shadertoy(position);
+        if pressed {
+            self.mouse = Vec4::new(position.x, position.y, position.x, position.y);
+        } else {
+            self.mouse.z = -self.mouse.z.abs();
+            self.mouse.w = -self.mouse.w.abs();
+        }
+    }
+
+    fn window_to_shadertoy(&self, position: Vec2) -> Vec2 {
+        Vec2::new(position.x, self.resolution.y - position.y)
+    }
+}
+
+/// Wraps a Shadertoy image shader, which defines
+/// `void mainImage(out vec4 fragColor, in vec2 fragCoord)`, into a Vulkan GLSL fragment
+/// shader for `fullscreen.vert`. `iChannel0` to `iChannel3` are combined image samplers at set
+/// 0, bindings 0 to 3.
+///
+/// Shadertoy writes display-encoded color; with `srgb_target` the output is linearized so an
+/// sRGB render target encodes it back instead of doing it twice.
+pub fn shadertoy_fragment_source(user_source: &str, srgb_target: bool) -> String {
+    let mut source = String::from(
+        "#version 450\n\
+         layout(push_constant) uniform ShaderToyInputs {\n\
+         \x20   vec4 iMouse;\n\
+         \x20   vec3 iResolution;\n\
+         \x20   float iTime;\n\
+         \x20   float iTimeDelta;\n\
+         \x20   int iFrame;\n\
+         };\n",
+    );
+    for channel in 0..SHADERTOY_CHANNELS {
+        source.push_str(&format!(
+            "layout(set = 0, binding = {0}) uniform sampler2D iChannel{0};\n",
+            channel
+        ));
+    }
+    source.push_str("layout(location = 0) out vec4 shadertoy_color;\n");
+
+    // Keep compile errors pointing at lines of the user's file.
+    source.push_str("#line 1\n");
+    source.push_str(user_source);
+
+    let output = if srgb_target {
+        "pow(clamp(color.rgb, 0.0, 1.0), vec3(2.2))"
+    } else {
+        "color.rgb"
+    };
+    source.push_str(&format!(
+        "\nvoid main() {{\n\
+         \x20   vec4 color = vec4(0.0, 0.0, 0.0, 1.0);\n\
+         \x20   mainImage(color, vec2(gl_FragCoord.x, iResolution.y - gl_FragCoord.y));\n\
+         \x20   shadertoy_color = vec4({}, 1.0);\n\
+         }}\n",
+        output
+    ));
+    source
+}
+
+/// Playground running a Shadertoy image shader from a file as a full-screen pass, recompiled
+/// with glslc whenever the file changes.
+///
+/// Channels the shader samples must be bound with `set_channel` before recording.
+pub struct ShaderToy {
+    pub pipeline: VulkanPipeline,
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    srgb_target: bool,
+    render_pass: vk::RenderPass,
+    extent: vk::Extent2D,
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    descriptor_pool: VulkanDescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    sampler: VulkanSampler,
+    device: Arc<Device>,
+}
+
+impl ShaderToy {
+    /// Compiles the shader at `path` for `render_pass`. `srgb_target` tells whether the
+    /// color attachment has an sRGB format, see `shadertoy_fragment_source`.
+    pub fn new(
+        device: &VulkanDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        srgb_target: bool,
+        path: impl Into<PathBuf>,
+    ) -> Result<Self> {
+        let path = path.into();
+        let modified = modified_time(&path);
+
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &(0..SHADERTOY_CHANNELS as u32)
+                .map(|binding| {
+                    VulkanDescriptorSetLayout::binding(
+                        binding,
+                        vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                        vk::ShaderStageFlags::FRAGMENT,
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )?;
+        let descriptor_pool = VulkanDescriptorPool::for_layout(device, &descriptor_set_layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&descriptor_set_layout)?;
+        let sampler = VulkanSampler::linear_clamp(device)?;
+
+        let pipeline = build_pipeline(
+            device,
+            &path,
+            srgb_target,
+            render_pass,
+            extent,
+            descriptor_set_layout.layout,
+        )?;
+
+        Ok(Self {
+            pipeline,
+            path,
+            modified,
+            srgb_target,
+            render_pass,
+            extent,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_set,
+            sampler,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Recompiles the shader if its file changed. Returns whether the pipeline was replaced;
+    /// on a compile error the previous pipeline is kept and the error returned. Waits for the
+    /// device to go idle before replacing the pipeline.
+    pub fn reload_if_changed(&mut self, device: &VulkanDevice) -> Result<bool> {
+        let modified = modified_time(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return Ok(false);
+        }
+        // Record the new time even on failure so a broken file is not recompiled every poll.
+        self.modified = modified;
+        self.reload(device)?;
+        Ok(true)
+    }
+
+    /// Recompiles the shader unconditionally, keeping the previous pipeline on a compile
+    /// error. Waits for the device to go idle before replacing the pipeline.
+    pub fn reload(&mut self, device: &VulkanDevice) -> Result<()> {
+        let pipeline = build_pipeline(
+            device,
+            &self.path,
+            self.srgb_target,
+            self.render_pass,
+            self.extent,
+            self.descriptor_set_layout.layout,
+        )?;
+        device.wait_idle()?;
+        self.pipeline = pipeline;
+        Ok(())
+    }
+
+    /// Binds `view`, in `SHADER_READ_ONLY_OPTIMAL`, as `iChannel<index>`. The descriptor is
+    /// updated immediately, so no in-flight frame may still be using it.
+    pub fn set_channel(&mut self, index: usize, view: vk::ImageView) {
+        assert!(index < SHADERTOY_CHANNELS, "Channel {} out of range", index);
+        self.descriptor_pool.write_image(
+            self.descriptor_set,
+            index as u32,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            view,
+            self.sampler.sampler,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+    }
+
+    /// Records the pass inside a begun render pass with viewport and scissor already set.
+    pub fn record(&self, command_buffer: vk::CommandBuffer, uniforms: &ShaderToyUniforms) {
+        self.pipeline.bind(command_buffer);
+        self.pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.descriptor_set]);
+        self.pipeline
+            .push_constants(command_buffer, vk::ShaderStageFlags::FRAGMENT, 0, uniforms);
+
+        self.device.draw(command_buffer, 3, 1, 0, 0);
+    }
+}
+
+fn build_pipeline(
+    device: &VulkanDevice,
+    path: &Path,
+    srgb_target: bool,
+    render_pass: vk::RenderPass,
+    extent: vk::Extent2D,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+) -> Result<VulkanPipeline> {
+    let user_source = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read shader {}: {}", path.display(), e))?;
+    let spirv = GlslcCompiler::new().compile(
+        &shadertoy_fragment_source(&user_source, srgb_target),
+        vk::ShaderStageFlags::FRAGMENT,
+        &path.display().to_string(),
+    )?;
+
+    VulkanPipelineBuilder::new(device)
+        .set_render_pass(render_pass)
+        .set_extent(extent)
+        .with_vertex_spv(include_bytes!("../../bin/fullscreen.vert.spv"))?
+        .with_fragment_spv(&spirv)?
+        .with_descriptor_set_layout(descriptor_set_layout)
+        .with_push_constant_range(
+            vk::PushConstantRange::default()
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .offset(0)
+                .size(std::mem::size_of::<ShaderToyUniforms>() as u32),
+        )
+        .with_cull_mode(vk::CullModeFlags::NONE)
+        .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+        .build()
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ShaderReflection;
+
+    #[test]
+    fn tracks_mouse_like_shadertoy() {
+        let mut uniforms = ShaderToyUniforms::new(vk::Extent2D {
+            width: 800,
+            height: 600,
+        });
+        uniforms.set_cursor(Vec2::new(10.0, 10.0));
+        assert_eq!(uniforms.mouse, Vec4::ZERO);
+
+        uniforms.set_button(true, Vec2::new(100.0, 200.0));
+        assert_eq!(uniforms.mouse, Vec4::new(100.0, 400.0, 100.0, 400.0));
+        uniforms.set_cursor(Vec2::new(150.0, 100.0));
+        assert_eq!(uniforms.mouse, Vec4::new(150.0, 500.0, 100.0, 400.0));
+
+        uniforms.set_button(false, Vec2::new(150.0, 100.0));
+        assert_eq!(uniforms.mouse, Vec4::new(150.0, 500.0, -100.0, -400.0));
+        uniforms.set_cursor(Vec2::new(0.0, 0.0));
+        assert_eq!(uniforms.mouse.x, 150.0);
+
+        uniforms.advance(0.5);
+        uniforms.advance(0.25);
+        assert_eq!(
+            (uniforms.time, uniforms.time_delta, uniforms.frame),
+            (0.75, 0.25, 2)
+        );
+    }
+
+    #[test]
+    fn wrapped_shaders_compile() {
+        if std::process::Command::new("glslc")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            eprintln!("glslc not found, skipping shadertoy compilation");
+            return;
+        }
+
+        let user = "void mainImage(out vec4 fragColor, in vec2 fragCoord) {\n\
+                    \x20   vec2 uv = fragCoord / iResolution.xy;\n\
+                    \x20   vec3 col = 0.5 + 0.5 * cos(iTime + uv.xyx + vec3(0, 2, 4));\n\
+                    \x20   fragColor = vec4(col * texture(iChannel1, uv).rgb, 1.0) + iMouse / 1e4;\n\
+                    }\n";
+        let spirv = GlslcCompiler::new()
+            .compile(
+                &shadertoy_fragment_source(user, true),
+                vk::ShaderStageFlags::FRAGMENT,
+                "shadertoy_test",
+            )
+            .unwrap();
+        let reflection = ShaderReflection::from_spirv(&spirv).unwrap();
+        assert_eq!(
+            reflection.push_constant_size,
+            Some(std::mem::offset_of!(ShaderToyUniforms, _padding) as u32)
+        );
+        assert!(
+            reflection
+                .layout_bindings(0)
+                .iter()
+                .any(|binding| binding.binding == 1)
+        );
+
+        let example = Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders/shadertoy/plasma.glsl");
+        GlslcCompiler::new()
+            .compile(
+                &shadertoy_fragment_source(&std::fs::read_to_string(example).unwrap(), false),
+                vk::ShaderStageFlags::FRAGMENT,
+                "shadertoy_plasma",
+            )
+            .unwrap();
+
+        let error = GlslcCompiler::new()
+            .compile(
+                &shadertoy_fragment_source(
+                    "void mainImage(out vec4 c, in vec2 p) {\n  c = oops;\n}\n",
+                    false,
+                ),
+                vk::ShaderStageFlags::FRAGMENT,
+                "shadertoy_error",
+            )
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains(":2:"), "{}", error);
+    }
+}
@@ -0,0 +1,200 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::{Vec3, Vec4};
+use std::sync::Arc;
+
+use crate::effects::{ColorLutTexture, PostChain, PostEffect};
+use crate::pipeline::{VulkanPipeline, VulkanPipelineBuilder};
+use crate::vulkan::{
+    ColorWorkflow, RecordCommands, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanSampler,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TonemapOperator {
+    Aces,
+    Reinhard,
+    Clamp,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TonemapSettings {
+    pub exposure: f32,
+    pub operator: TonemapOperator,
+    /// Blend between the tonemapped (0.0) and fully graded (1.0) image.
+    pub lut_strength: f32,
+    /// With `ColorWorkflow::ManualEncode` the output is sRGB-encoded for a UNORM target;
+    /// match it to the target with `ColorWorkflow::for_target`.
+    pub output: ColorWorkflow,
+}
+
+impl Default for TonemapSettings {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            operator: TonemapOperator::Aces,
+            lut_strength: 1.0,
+            output: ColorWorkflow::SrgbTarget,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TonemapPushConstants {
+    exposure: f32,
+    lut_strength: f32,
+    lut_size: f32,
+    operator_index: u32,
+    lut_domain_min: Vec4,
+    lut_domain_max: Vec4,
+    encode_srgb: u32,
+    padding: [u32; 3],
+}
+
+/// Maps HDR color to display range and applies a 3D color grading LUT. The output stays
+/// linear for an sRGB target to encode, unless `settings.output` asks for manual encoding.
+pub struct TonemapEffect {
+    pub pipeline: VulkanPipeline,
+    pub settings: TonemapSettings,
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    descriptor_pool: VulkanDescriptorPool,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    sampler: VulkanSampler,
+    lut_size: u32,
+    lut_domain: (Vec3, Vec3),
+    device: Arc<Device>,
+}
+
+impl TonemapEffect {
+    pub fn new(
+        device: &VulkanDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        lut: &ColorLutTexture,
+    ) -> Result<Self> {
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[
+                VulkanDescriptorSetLayout::binding(
+                    0,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::FRAGMENT,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    1,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::ShaderStageFlags::FRAGMENT,
+                ),
+            ],
+        )?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(
+            device,
+            &descriptor_set_layout,
+            PostChain::INPUT_COUNT as u32,
+        )?;
+        let descriptor_sets = (0..PostChain::INPUT_COUNT)
+            .map(|_| descriptor_pool.allocate(&descriptor_set_layout))
+            .collect::<Result<Vec<_>>>()?;
+
+        let sampler = VulkanSampler::linear_clamp(device)?;
+
+        let pipeline = VulkanPipelineBuilder::new(device)
+            .set_render_pass(render_pass)
+            .set_extent(extent)
+            .with_vertex_spv(include_bytes!("../../bin/fullscreen.vert.spv"))?
+            .with_fragment_spv(include_bytes!("../../bin/tonemap.frag.spv"))?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .offset(0)
+                    .size(std::mem::size_of::<TonemapPushConstants>() as u32),
+            )
+            .with_cull_mode(vk::CullModeFlags::NONE)
+            .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .build()?;
+
+        let mut effect = Self {
+            pipeline,
+            settings: TonemapSettings::default(),
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            sampler,
+            lut_size: 0,
+            lut_domain: (Vec3::ZERO, Vec3::ONE),
+            device: device.device.clone(),
+        };
+        effect.set_lut(lut);
+
+        Ok(effect)
+    }
+
+    /// Switches the grading LUT. The descriptor sets are updated immediately, so no in-flight
+    /// frame may still be using them, and the texture must outlive its use by this effect.
+    pub fn set_lut(&mut self, lut: &ColorLutTexture) {
+        for &descriptor_set in &self.descriptor_sets {
+            self.descriptor_pool.write_image(
+                descriptor_set,
+                1,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                lut.image.view,
+                self.sampler.sampler,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            );
+        }
+
+        self.lut_size = lut.size();
+        self.lut_domain = lut.domain();
+    }
+
+    pub fn descriptor_set_layout(&self) -> &VulkanDescriptorSetLayout {
+        &self.descriptor_set_layout
+    }
+}
+
+impl PostEffect for TonemapEffect {
+    fn set_inputs(&mut self, inputs: &[vk::ImageView]) {
+        for (&descriptor_set, &input) in self.descriptor_sets.iter().zip(inputs) {
+            self.descriptor_pool.write_image(
+                descriptor_set,
+                0,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                input,
+                self.sampler.sampler,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            );
+        }
+    }
+
+    fn record(&self, command_buffer: vk::CommandBuffer, input: usize) {
+        let push_constants = TonemapPushConstants {
+            exposure: self.settings.exposure,
+            lut_strength: self.settings.lut_strength.clamp(0.0, 1.0),
+            lut_size: self.lut_size as f32,
+            operator_index: match self.settings.operator {
+                TonemapOperator::Aces => 0,
+                TonemapOperator::Reinhard => 1,
+                TonemapOperator::Clamp => 2,
+            },
+            lut_domain_min: self.lut_domain.0.extend(0.0),
+            lut_domain_max: self.lut_domain.1.extend(1.0),
+            encode_srgb: u32::from(self.settings.output.shader_encodes()),
+            padding: [0; 3],
+        };
+
+        self.pipeline.bind(command_buffer);
+        self.pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.descriptor_sets[input]]);
+        self.pipeline.push_constants(
+            command_buffer,
+            vk::ShaderStageFlags::FRAGMENT,
+            0,
+            &push_constants,
+        );
+
+        self.device.draw(command_buffer, 3, 1, 0, 0);
+    }
+}
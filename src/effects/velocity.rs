@@ -0,0 +1,483 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::Mat4;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::effects::ObjectHandle;
+use crate::pipeline::{VulkanPipeline, VulkanPipelineBuilder};
+use crate::vulkan::{
+    RecordCommands, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice, VulkanImage,
+    VulkanPhysicalDevice, VulkanRenderPass, VulkanSampler,
+};
+
+/// Screen-space motion in UV units, current minus previous position.
+pub const VELOCITY_FORMAT: vk::Format = vk::Format::R16G16_SFLOAT;
+
+#[derive(Debug, Clone, Copy)]
+struct ObjectMotion {
+    current: Mat4,
+    previous: Mat4,
+}
+
+/// Tracks current and previous-frame transforms of the camera and of every moving object.
+#[derive(Debug, Clone)]
+pub struct MotionTracker {
+    current_view_proj: Mat4,
+    previous_view_proj: Mat4,
+    objects: HashMap<ObjectHandle, ObjectMotion>,
+    has_history: bool,
+}
+
+impl Default for MotionTracker {
+    fn default() -> Self {
+        Self {
+            current_view_proj: Mat4::IDENTITY,
+            previous_view_proj: Mat4::IDENTITY,
+            objects: HashMap::new(),
+            has_history: false,
+        }
+    }
+}
+
+impl MotionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a new frame: the transforms of the last frame become the previous transforms.
+    /// Objects keep their transform until `set_transform` is called again.
+    pub fn begin_frame(&mut self, view_proj: Mat4) {
+        self.previous_view_proj = if self.has_history {
+            self.current_view_proj
+        } else {
+            view_proj
+        };
+        self.current_view_proj = view_proj;
+        self.has_history = true;
+
+        for motion in self.objects.values_mut() {
+            motion.previous = motion.current;
+        }
+    }
+
+    /// Sets an object's model matrix for the current frame. Newly added objects report no
+    /// motion on their first frame.
+    pub fn set_transform(&mut self, handle: ObjectHandle, model: Mat4) {
+        self.objects
+            .entry(handle)
+            .and_modify(|motion| motion.current = model)
+            .or_insert(ObjectMotion {
+                current: model,
+                previous: model,
+            });
+    }
+
+    pub fn remove(&mut self, handle: ObjectHandle) {
+        self.objects.remove(&handle);
+    }
+
+    /// Discards all history, e.g. after a camera cut or teleport, so the next frame reports
+    /// no motion.
+    pub fn reset_history(&mut self) {
+        self.has_history = false;
+        for motion in self.objects.values_mut() {
+            motion.previous = motion.current;
+        }
+    }
+
+    pub fn view_proj(&self) -> Mat4 {
+        self.current_view_proj
+    }
+
+    pub fn previous_view_proj(&self) -> Mat4 {
+        self.previous_view_proj
+    }
+
+    /// Maps current clip-space positions of static geometry to the previous frame:
+    /// `previous_view_proj * inverse(view_proj)`.
+    pub fn reprojection(&self) -> Mat4 {
+        self.previous_view_proj * self.current_view_proj.inverse()
+    }
+
+    pub fn previous_transform(&self, handle: ObjectHandle) -> Option<Mat4> {
+        self.objects.get(&handle).map(|motion| motion.previous)
+    }
+
+    /// Current and previous model-view-projection matrices of an object.
+    pub fn object_mvps(&self, handle: ObjectHandle) -> Option<(Mat4, Mat4)> {
+        self.objects.get(&handle).map(|motion| {
+            (
+                self.current_view_proj * motion.current,
+                self.previous_view_proj * motion.previous,
+            )
+        })
+    }
+}
+
+/// `VELOCITY_FORMAT` color target with a depth attachment, left in `SHADER_READ_ONLY_OPTIMAL`
+/// so TAA, motion blur or upscaling passes can sample it.
+pub struct VelocityTarget {
+    pub framebuffer: vk::Framebuffer,
+    pub render_pass: VulkanRenderPass,
+    pub image: VulkanImage,
+    pub depth: VulkanImage,
+    device: Arc<Device>,
+}
+
+impl VelocityTarget {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        extent: vk::Extent2D,
+        depth_format: vk::Format,
+    ) -> Result<Self> {
+        let image = VulkanImage::new(
+            device,
+            physical_device,
+            extent,
+            VELOCITY_FORMAT,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::ImageAspectFlags::COLOR,
+        )?;
+
+        let depth = VulkanImage::new_depth(device, physical_device, extent, depth_format)?;
+
+        let render_pass = VulkanRenderPass::with_formats(
+            device,
+            VELOCITY_FORMAT,
+            Some(depth_format),
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )?;
+
+        let attachments = [image.view, depth.view];
+
+        let framebuffer_info = vk::FramebufferCreateInfo::default()
+            .render_pass(render_pass.render_pass)
+            .attachments(&attachments)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+
+        let framebuffer = unsafe {
+            device
+                .device
+                .create_framebuffer(&framebuffer_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create velocity framebuffer: {}", e))?
+        };
+
+        Ok(Self {
+            framebuffer,
+            render_pass,
+            image,
+            depth,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.image.extent
+    }
+
+    /// Clear values for the color and depth attachments: zero motion and far depth.
+    pub fn clear_values() -> [vk::ClearValue; 2] {
+        [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 0.0],
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ]
+    }
+}
+
+impl Drop for VelocityTarget {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_framebuffer(self.framebuffer, None);
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MotionPushConstants {
+    current_mvp: Mat4,
+    previous_mvp: Mat4,
+}
+
+/// Writes per-pixel motion vectors into a `VelocityTarget`.
+///
+/// `record_camera` runs first and fills every pixel with the camera motion reconstructed from
+/// scene depth, so static geometry and the sky get motion without being drawn. Tracked objects
+/// are then drawn over it with `record_object`. Only the vertex position is read, as a `vec3`
+/// at location 0 and offset 0 of a vertex buffer with `vertex_stride`, so any vertex layout
+/// starting with its position works.
+pub struct VelocityRenderer {
+    pub pipeline: VulkanPipeline,
+    pub camera_pipeline: VulkanPipeline,
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    descriptor_pool: VulkanDescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    depth_sampler: VulkanSampler,
+    device: Arc<Device>,
+}
+
+impl VelocityRenderer {
+    pub fn new(
+        device: &VulkanDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        vertex_stride: u32,
+    ) -> Result<Self> {
+        let pipeline = VulkanPipelineBuilder::new(device)
+            .set_render_pass(render_pass)
+            .set_extent(extent)
+            .with_vertex_spv(include_bytes!("../../bin/velocity.vert.spv"))?
+            .with_fragment_spv(include_bytes!("../../bin/velocity.frag.spv"))?
+            .with_vertex_binding(
+                vk::VertexInputBindingDescription::default()
+                    .binding(0)
+                    .stride(vertex_stride)
+                    .input_rate(vk::VertexInputRate::VERTEX),
+            )
+            .with_vertex_attribute(
+                vk::VertexInputAttributeDescription::default()
+                    .binding(0)
+                    .location(0)
+                    .format(vk::Format::R32G32B32_SFLOAT)
+                    .offset(0),
+            )
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::VERTEX)
+                    .offset(0)
+                    .size(std::mem::size_of::<MotionPushConstants>() as u32),
+            )
+            .with_depth_test(true, true, vk::CompareOp::LESS)
+            .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .build()?;
+
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[VulkanDescriptorSetLayout::binding(
+                0,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                vk::ShaderStageFlags::FRAGMENT,
+            )],
+        )?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(device, &descriptor_set_layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&descriptor_set_layout)?;
+        let depth_sampler = VulkanSampler::nearest_clamp(device)?;
+
+        let camera_pipeline = VulkanPipelineBuilder::new(device)
+            .set_render_pass(render_pass)
+            .set_extent(extent)
+            .with_vertex_spv(include_bytes!("../../bin/fullscreen.vert.spv"))?
+            .with_fragment_spv(include_bytes!("../../bin/camera_velocity.frag.spv"))?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .offset(0)
+                    .size(std::mem::size_of::<Mat4>() as u32),
+            )
+            .with_cull_mode(vk::CullModeFlags::NONE)
+            .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .build()?;
+
+        Ok(Self {
+            pipeline,
+            camera_pipeline,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_set,
+            depth_sampler,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Binds the scene depth buffer, which must be in `DEPTH_STENCIL_READ_ONLY_OPTIMAL` when
+    /// `record_camera` is recorded.
+    pub fn set_scene_depth(&self, depth_view: vk::ImageView) {
+        self.descriptor_pool.write_image(
+            self.descriptor_set,
+            0,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            depth_view,
+            self.depth_sampler.sampler,
+            vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+        );
+    }
+
+    pub fn descriptor_set_layout(&self) -> &VulkanDescriptorSetLayout {
+        &self.descriptor_set_layout
+    }
+
+    /// Fills the whole target with camera motion reprojected from scene depth. Must be the
+    /// first draw of the velocity pass; viewport and scissor must already be set.
+    pub fn record_camera(&self, command_buffer: vk::CommandBuffer, tracker: &MotionTracker) {
+        self.camera_pipeline.bind(command_buffer);
+        self.camera_pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.descriptor_set]);
+        self.camera_pipeline.push_constants(
+            command_buffer,
+            vk::ShaderStageFlags::FRAGMENT,
+            0,
+            &tracker.reprojection(),
+        );
+
+        self.device.draw(command_buffer, 3, 1, 0, 0);
+    }
+
+    /// Binds the object pipeline. Viewport and scissor are dynamic and must be set by the
+    /// caller.
+    pub fn bind(&self, command_buffer: vk::CommandBuffer) {
+        self.pipeline.bind(command_buffer);
+    }
+
+    /// Records an indexed draw of one object with its current and previous transforms. The
+    /// object's vertex and index buffers must already be bound. Returns `false` and records
+    /// nothing if the object is not tracked.
+    pub fn record_object(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        tracker: &MotionTracker,
+        handle: ObjectHandle,
+        index_count: u32,
+    ) -> bool {
+        let Some((current_mvp, previous_mvp)) = tracker.object_mvps(handle) else {
+            return false;
+        };
+
+        let push_constants = MotionPushConstants {
+            current_mvp,
+            previous_mvp,
+        };
+
+        self.pipeline.push_constants(
+            command_buffer,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            &push_constants,
+        );
+
+        self.device
+            .draw_indexed(command_buffer, index_count, 1, 0, 0, 0);
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{Vec3, Vec4};
+
+    fn view_proj(eye: Vec3) -> Mat4 {
+        Mat4::perspective_rh(1.0, 1.0, 0.1, 100.0)
+            * Mat4::look_at_rh(eye, eye + Vec3::NEG_Z, Vec3::Y)
+    }
+
+    #[test]
+    fn first_frame_has_no_camera_motion() {
+        let mut tracker = MotionTracker::new();
+        tracker.begin_frame(view_proj(Vec3::ZERO));
+
+        assert_eq!(tracker.previous_view_proj(), tracker.view_proj());
+        assert!(tracker.reprojection().abs_diff_eq(Mat4::IDENTITY, 1e-5));
+    }
+
+    #[test]
+    fn begin_frame_shifts_history() {
+        let mut tracker = MotionTracker::new();
+        let first = view_proj(Vec3::ZERO);
+        let second = view_proj(Vec3::X);
+
+        tracker.begin_frame(first);
+        tracker.begin_frame(second);
+
+        assert_eq!(tracker.previous_view_proj(), first);
+        assert_eq!(tracker.view_proj(), second);
+    }
+
+    #[test]
+    fn reprojection_maps_to_previous_clip_position() {
+        let mut tracker = MotionTracker::new();
+        let first = view_proj(Vec3::ZERO);
+        let second = view_proj(Vec3::new(0.5, 0.0, 0.0));
+        tracker.begin_frame(first);
+        tracker.begin_frame(second);
+
+        let world = Vec4::new(1.0, 2.0, -10.0, 1.0);
+        let current = second * world;
+        let reprojected = tracker.reprojection() * (current / current.w);
+        let expected = first * world;
+
+        assert!(
+            (reprojected.truncate() / reprojected.w)
+                .abs_diff_eq(expected.truncate() / expected.w, 1e-4)
+        );
+    }
+
+    #[test]
+    fn new_object_reports_no_motion() {
+        let mut tracker = MotionTracker::new();
+        let handle = ObjectHandle(0);
+        tracker.begin_frame(Mat4::IDENTITY);
+        tracker.set_transform(handle, Mat4::from_translation(Vec3::X));
+
+        let (current, previous) = tracker.object_mvps(handle).unwrap();
+        assert_eq!(current, previous);
+    }
+
+    #[test]
+    fn object_transform_becomes_previous_next_frame() {
+        let mut tracker = MotionTracker::new();
+        let handle = ObjectHandle(0);
+        let first = Mat4::from_translation(Vec3::X);
+        let second = Mat4::from_translation(Vec3::Y);
+
+        tracker.begin_frame(Mat4::IDENTITY);
+        tracker.set_transform(handle, first);
+        tracker.begin_frame(Mat4::IDENTITY);
+        tracker.set_transform(handle, second);
+
+        assert_eq!(tracker.previous_transform(handle), Some(first));
+        assert_eq!(tracker.object_mvps(handle), Some((second, first)));
+    }
+
+    #[test]
+    fn reset_history_clears_motion() {
+        let mut tracker = MotionTracker::new();
+        let handle = ObjectHandle(0);
+
+        tracker.begin_frame(view_proj(Vec3::ZERO));
+        tracker.set_transform(handle, Mat4::IDENTITY);
+        tracker.begin_frame(view_proj(Vec3::X));
+        tracker.set_transform(handle, Mat4::from_translation(Vec3::Y));
+        tracker.reset_history();
+        tracker.begin_frame(view_proj(Vec3::Z));
+
+        assert_eq!(tracker.previous_view_proj(), tracker.view_proj());
+        let (current, previous) = tracker.object_mvps(handle).unwrap();
+        assert_eq!(current, previous);
+    }
+
+    #[test]
+    fn removed_object_is_untracked() {
+        let mut tracker = MotionTracker::new();
+        let handle = ObjectHandle(3);
+        tracker.set_transform(handle, Mat4::IDENTITY);
+        tracker.remove(handle);
+
+        assert!(tracker.object_mvps(handle).is_none());
+    }
+}
@@ -0,0 +1,309 @@
+//! C API over `RenderContext`, declared in `include/rve.h`.
+//!
+//! Functions returning `i32` return `RVE_OK` or `RVE_ERROR`; after an error,
+//! `rve_last_error` describes it. A context must only be used from one thread at a time.
+
+use anyhow::Result;
+use glam::Mat4;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+use crate::ffi::{DrawItem, RenderContext};
+use crate::renderer::GpuVertex;
+
+pub const RVE_OK: i32 = 0;
+pub const RVE_ERROR: i32 = -1;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Opaque to C.
+pub struct RveContext(RenderContext);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RveVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RveDraw {
+    pub mesh: u32,
+    pub texture: u32,
+    /// Column-major model matrix.
+    pub transform: [f32; 16],
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|error| *error.borrow_mut() = Some(message));
+}
+
+/// Runs `f`, turning errors and panics into `RVE_ERROR` and `rve_last_error`.
+fn call(f: impl FnOnce() -> Result<()>) -> i32 {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => RVE_OK,
+        Ok(Err(e)) => {
+            set_last_error(format!("{:#}", e));
+            RVE_ERROR
+        }
+        Err(_) => {
+            set_last_error("Panic in renderer".to_string());
+            RVE_ERROR
+        }
+    }
+}
+
+unsafe fn context<'a>(context: *mut RveContext) -> Result<&'a mut RenderContext> {
+    unsafe { context.as_mut() }
+        .map(|context| &mut context.0)
+        .ok_or_else(|| anyhow::anyhow!("Context is null"))
+}
+
+unsafe fn slice<'a, T>(data: *const T, count: u32) -> Result<&'a [T]> {
+    if count == 0 {
+        return Ok(&[]);
+    }
+    if data.is_null() {
+        return Err(anyhow::anyhow!("Data pointer is null"));
+    }
+    Ok(unsafe { std::slice::from_raw_parts(data, count as usize) })
+}
+
+unsafe fn path<'a>(path: *const c_char) -> Result<&'a str> {
+    if path.is_null() {
+        return Err(anyhow::anyhow!("Path is null"));
+    }
+    unsafe { CStr::from_ptr(path) }
+        .to_str()
+        .map_err(|e| anyhow::anyhow!("Path is not UTF-8: {}", e))
+}
+
+unsafe fn write_handle(out: *mut u32, handle: u32) -> Result<()> {
+    if out.is_null() {
+        return Err(anyhow::anyhow!("Output pointer is null"));
+    }
+    unsafe { out.write(handle) };
+    Ok(())
+}
+
+/// Message of the last error on this thread, or null. Valid until the next failing call.
+#[unsafe(no_mangle)]
+pub extern "C" fn rve_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| {
+        error
+            .borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Creates a headless context rendering `width` x `height` frames. Returns null on failure.
+#[unsafe(no_mangle)]
+pub extern "C" fn rve_context_create(width: u32, height: u32) -> *mut RveContext {
+    let mut created = None;
+    call(|| {
+        created = Some(RenderContext::new(width, height)?);
+        Ok(())
+    });
+    created.map_or(std::ptr::null_mut(), |context| {
+        Box::into_raw(Box::new(RveContext(context)))
+    })
+}
+
+/// # Safety
+/// `context` must be null or come from `rve_context_create`, and not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rve_context_destroy(context: *mut RveContext) {
+    if !context.is_null() {
+        drop(unsafe { Box::from_raw(context) });
+    }
+}
+
+/// Adds an indexed triangle mesh and writes its handle to `out_mesh`.
+///
+/// # Safety
+/// `context` must be a live context, `vertices` and `indices` must point to the given
+/// number of elements, and `out_mesh` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rve_mesh_create(
+    context: *mut RveContext,
+    vertices: *const RveVertex,
+    vertex_count: u32,
+    indices: *const u32,
+    index_count: u32,
+    out_mesh: *mut u32,
+) -> i32 {
+    call(|| {
+        let context = unsafe { self::context(context) }?;
+        let vertices: Vec<GpuVertex> = unsafe { slice(vertices, vertex_count) }?
+            .iter()
+            .map(|vertex| GpuVertex {
+                position: vertex.position,
+                normal: vertex.normal,
+                uv: vertex.uv,
+            })
+            .collect();
+        let indices = unsafe { slice(indices, index_count) }?;
+        let mesh = context.add_mesh(&vertices, indices)?;
+        unsafe { write_handle(out_mesh, mesh) }
+    })
+}
+
+/// Loads a Wavefront OBJ file and writes its handle to `out_mesh`.
+///
+/// # Safety
+/// `context` must be a live context, `path` a NUL-terminated UTF-8 string and `out_mesh`
+/// writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rve_mesh_load(
+    context: *mut RveContext,
+    path: *const c_char,
+    out_mesh: *mut u32,
+) -> i32 {
+    call(|| {
+        let context = unsafe { self::context(context) }?;
+        let mesh = context.load_mesh(unsafe { self::path(path) }?)?;
+        unsafe { write_handle(out_mesh, mesh) }
+    })
+}
+
+/// Uploads `width * height` sRGB RGBA8 texels and writes the texture's handle to
+/// `out_texture`. Texture 0 is built in and white.
+///
+/// # Safety
+/// `context` must be a live context, `rgba` must point to `width * height * 4` bytes and
+/// `out_texture` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rve_texture_create(
+    context: *mut RveContext,
+    width: u32,
+    height: u32,
+    rgba: *const u8,
+    out_texture: *mut u32,
+) -> i32 {
+    call(|| {
+        let context = unsafe { self::context(context) }?;
+        let size = width
+            .checked_mul(height)
+            .and_then(|texels| texels.checked_mul(4))
+            .ok_or_else(|| anyhow::anyhow!("Texture is too large"))?;
+        let data = unsafe { slice(rgba, size) }?;
+        let texture = context.add_texture(width, height, ash::vk::Format::R8G8B8A8_SRGB, data)?;
+        unsafe { write_handle(out_texture, texture) }
+    })
+}
+
+/// Loads a binary PPM file and writes the texture's handle to `out_texture`.
+///
+/// # Safety
+/// `context` must be a live context, `path` a NUL-terminated UTF-8 string and `out_texture`
+/// writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rve_texture_load(
+    context: *mut RveContext,
+    path: *const c_char,
+    out_texture: *mut u32,
+) -> i32 {
+    call(|| {
+        let context = unsafe { self::context(context) }?;
+        let texture = context.load_texture(unsafe { self::path(path) }?)?;
+        unsafe { write_handle(out_texture, texture) }
+    })
+}
+
+/// Replaces the objects drawn by `rve_render_frame`.
+///
+/// # Safety
+/// `context` must be a live context, `view_proj` must point to 16 floats (column-major) and
+/// `draws` to `draw_count` draws.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rve_submit_draw_list(
+    context: *mut RveContext,
+    view_proj: *const f32,
+    draws: *const RveDraw,
+    draw_count: u32,
+) -> i32 {
+    call(|| {
+        let context = unsafe { self::context(context) }?;
+        let view_proj = unsafe { slice(view_proj, 16) }?;
+        let draws: Vec<DrawItem> = unsafe { slice(draws, draw_count) }?
+            .iter()
+            .map(|draw| DrawItem {
+                mesh: draw.mesh,
+                texture: draw.texture,
+                transform: Mat4::from_cols_array(&draw.transform),
+            })
+            .collect();
+        context.submit_draw_list(Mat4::from_cols_slice(view_proj), &draws)
+    })
+}
+
+/// Renders the current draw list and waits for it. When `rgba_out` is not null, the frame is
+/// written to it as `width * height` RGBA8 texels, top row first.
+///
+/// # Safety
+/// `context` must be a live context and `rgba_out` null or writable for
+/// `width * height * 4` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rve_render_frame(context: *mut RveContext, rgba_out: *mut u8) -> i32 {
+    call(|| {
+        let context = unsafe { self::context(context) }?;
+        let extent = context.extent();
+        let pixels = (!rgba_out.is_null()).then(|| unsafe {
+            std::slice::from_raw_parts_mut(
+                rgba_out,
+                extent.width as usize * extent.height as usize * 4,
+            )
+        });
+        context.render(pixels)
+    })
+}
+
+/// Copies the last frame rendered by `rve_render_frame` into `texture`, scaled to its size.
+/// Texture 0 can't be a destination.
+///
+/// # Safety
+/// `context` must be a live context.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rve_copy_frame_to_texture(context: *mut RveContext, texture: u32) -> i32 {
+    call(|| {
+        let context = unsafe { self::context(context) }?;
+        context.copy_backbuffer_to(texture)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_errors_through_last_error() {
+        let result = unsafe {
+            rve_mesh_create(
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                0,
+                std::ptr::null(),
+                0,
+                std::ptr::null_mut(),
+            )
+        };
+        assert_eq!(result, RVE_ERROR);
+
+        let message = unsafe { CStr::from_ptr(rve_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "Context is null");
+
+        assert_eq!(call(|| panic!("boom")), RVE_ERROR);
+        let message = unsafe { CStr::from_ptr(rve_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "Panic in renderer");
+
+        assert_eq!(std::mem::size_of::<RveVertex>(), 32);
+        assert_eq!(std::mem::size_of::<RveDraw>(), 72);
+    }
+}
@@ -0,0 +1,407 @@
+use anyhow::Result;
+use ash::vk;
+use glam::Mat4;
+use std::path::Path;
+
+use crate::assets::{MeshAsset, TextureAsset};
+use crate::renderer::{
+    BindlessTextures, GpuDrivenRenderer, GpuMeshId, GpuScene, GpuSceneLimits, GpuVertex,
+};
+use crate::vulkan::{
+    ClearValues, ImageAccess, QueueFamilyIndices, TransferImage, UploadBatcher, VulkanBuffer,
+    VulkanCommandPool, VulkanDevice, VulkanImage, VulkanInstance, VulkanPhysicalDevice,
+    VulkanRenderPass, VulkanSampler, cmd_blit_image, cmd_copy_image_to_buffer,
+};
+
+const COLOR_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+const MAX_TEXTURES: u32 = 1024;
+
+/// One object of a draw list.
+#[derive(Debug, Clone, Copy)]
+pub struct DrawItem {
+    pub mesh: u32,
+    pub texture: u32,
+    pub transform: Mat4,
+}
+
+/// A headless renderer for embedding: owns its own instance and device, draws meshes with the
+/// GPU-driven backend into an offscreen RGBA8 image and reads frames back to the CPU. This is
+/// what the C API in `ffi::api` wraps.
+///
+/// Texture 0 is a white 1x1 texture, so untextured meshes can use it.
+pub struct RenderContext {
+    extent: vk::Extent2D,
+    /// Whether `color` holds a rendered frame, in `TRANSFER_SRC_OPTIMAL`.
+    has_frame: bool,
+    view_proj: Mat4,
+    clear_color: [f32; 4],
+    meshes: Vec<GpuMeshId>,
+    renderer: GpuDrivenRenderer,
+    textures: BindlessTextures,
+    texture_images: Vec<VulkanImage>,
+    sampler: VulkanSampler,
+    scene: GpuScene,
+    uploads: UploadBatcher,
+    readback: VulkanBuffer,
+    framebuffer: vk::Framebuffer,
+    render_pass: VulkanRenderPass,
+    _depth: VulkanImage,
+    color: VulkanImage,
+    fence: vk::Fence,
+    command_pool: VulkanCommandPool,
+    device: VulkanDevice,
+    physical_device: VulkanPhysicalDevice,
+    _instance: VulkanInstance,
+}
+
+impl RenderContext {
+    pub fn new(width: u32, height: u32) -> Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(anyhow::anyhow!("Render size must not be zero"));
+        }
+        let extent = vk::Extent2D { width, height };
+
+        let instance = VulkanInstance::new(&[])?;
+        let physical_device = VulkanPhysicalDevice::select_best_device(&instance)?;
+
+        let queue_family_properties = unsafe {
+            instance
+                .instance
+                .get_physical_device_queue_family_properties(physical_device.physical_device)
+        };
+        let graphics_family = queue_family_properties
+            .iter()
+            .position(|family| family.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+            .ok_or_else(|| anyhow::anyhow!("Device has no graphics queue"))?
+            as u32;
+        let queue_families = QueueFamilyIndices {
+            graphics_family: Some(graphics_family),
+            compute_family: None,
+            transfer_family: None,
+            present_family: None,
+        };
+
+        let device = VulkanDevice::new(&instance, &physical_device, queue_families.clone())?;
+        let command_pool = VulkanCommandPool::new(&device, queue_families, 1)?;
+
+        let fence = unsafe {
+            device
+                .device
+                .create_fence(&vk::FenceCreateInfo::default(), None)
+                .map_err(|e| anyhow::anyhow!("Failed to create fence: {}", e))?
+        };
+
+        let color = VulkanImage::new(
+            &device,
+            &physical_device,
+            extent,
+            COLOR_FORMAT,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::ImageAspectFlags::COLOR,
+        )?;
+        let depth_format = physical_device.find_depth_format(&instance.instance)?;
+        let depth = VulkanImage::new_depth(&device, &physical_device, extent, depth_format)?;
+        let render_pass = VulkanRenderPass::with_formats(
+            &device,
+            COLOR_FORMAT,
+            Some(depth_format),
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        )?;
+
+        let attachments = [color.view, depth.view];
+        let framebuffer_info = vk::FramebufferCreateInfo::default()
+            .render_pass(render_pass.render_pass)
+            .attachments(&attachments)
+            .width(width)
+            .height(height)
+            .layers(1);
+        let framebuffer = unsafe {
+            device
+                .device
+                .create_framebuffer(&framebuffer_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create framebuffer: {}", e))?
+        };
+
+        let readback = VulkanBuffer::new_host_visible(
+            &device,
+            &physical_device,
+            width as vk::DeviceSize * height as vk::DeviceSize * 4,
+            vk::BufferUsageFlags::TRANSFER_DST,
+        )?;
+
+        let scene = GpuScene::new(&device, &physical_device, GpuSceneLimits::default())?;
+        let textures = BindlessTextures::new(&device, MAX_TEXTURES)?;
+        let renderer = GpuDrivenRenderer::new(
+            &device,
+            &physical_device,
+            render_pass.render_pass,
+            extent,
+            &scene,
+            &textures,
+        )?;
+
+        let mut context = Self {
+            extent,
+            has_frame: false,
+            view_proj: Mat4::IDENTITY,
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            meshes: Vec::new(),
+            renderer,
+            textures,
+            texture_images: Vec::new(),
+            sampler: VulkanSampler::linear_clamp(&device)?,
+            scene,
+            uploads: UploadBatcher::new(&device)?,
+            readback,
+            framebuffer,
+            render_pass,
+            _depth: depth,
+            color,
+            fence,
+            command_pool,
+            device,
+            physical_device,
+            _instance: instance,
+        };
+        context.add_texture(1, 1, vk::Format::R8G8B8A8_UNORM, &[255; 4])?;
+        Ok(context)
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    pub fn add_mesh(&mut self, vertices: &[GpuVertex], indices: &[u32]) -> Result<u32> {
+        if let Some(&index) = indices
+            .iter()
+            .find(|&&index| index as usize >= vertices.len())
+        {
+            return Err(anyhow::anyhow!(
+                "Index {} out of range for {} vertices",
+                index,
+                vertices.len()
+            ));
+        }
+        let mesh = self.scene.add_mesh(vertices, indices)?;
+        self.meshes.push(mesh);
+        Ok(self.meshes.len() as u32 - 1)
+    }
+
+    /// Loads a Wavefront OBJ; see `MeshAsset::parse_obj`.
+    pub fn load_mesh(&mut self, path: impl AsRef<Path>) -> Result<u32> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read mesh {}: {}", path.display(), e))?;
+        let mesh = MeshAsset::parse_obj(&source)?;
+        self.add_mesh(&mesh.vertices, &mesh.indices)
+    }
+
+    /// Uploads tightly packed RGBA8 texels and returns the texture's index.
+    pub fn add_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: vk::Format,
+        data: &[u8],
+    ) -> Result<u32> {
+        if data.len() != width as usize * height as usize * 4 {
+            return Err(anyhow::anyhow!(
+                "Expected {} bytes of RGBA8 texels, got {}",
+                width as usize * height as usize * 4,
+                data.len()
+            ));
+        }
+
+        let image = VulkanImage::new(
+            &self.device,
+            &self.physical_device,
+            vk::Extent2D { width, height },
+            format,
+            vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+            vk::ImageAspectFlags::COLOR,
+        )?;
+        self.uploads
+            .upload_image(&self.physical_device, &self.device, &image, data)?;
+        self.uploads.submit()?;
+        self.uploads.wait_idle()?;
+
+        let index = self.textures.add(image.view, self.sampler.sampler)?;
+        self.texture_images.push(image);
+        Ok(index)
+    }
+
+    /// Loads a binary PPM as an sRGB texture; see `TextureAsset::parse_ppm`.
+    pub fn load_texture(&mut self, path: impl AsRef<Path>) -> Result<u32> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read texture {}: {}", path.display(), e))?;
+        let texture = TextureAsset::parse_ppm(&bytes)?;
+        self.add_texture(texture.width, texture.height, texture.format, &texture.data)
+    }
+
+    pub fn set_clear_color(&mut self, clear_color: [f32; 4]) {
+        self.clear_color = clear_color;
+    }
+
+    /// Replaces the objects drawn by `render`.
+    pub fn submit_draw_list(&mut self, view_proj: Mat4, draws: &[DrawItem]) -> Result<()> {
+        self.scene.clear_objects();
+        for draw in draws {
+            let mesh = *self
+                .meshes
+                .get(draw.mesh as usize)
+                .ok_or_else(|| anyhow::anyhow!("Unknown mesh {}", draw.mesh))?;
+            if draw.texture as usize >= self.texture_images.len() {
+                return Err(anyhow::anyhow!("Unknown texture {}", draw.texture));
+            }
+            self.scene.add_object(mesh, draw.transform, draw.texture)?;
+        }
+        self.view_proj = view_proj;
+        Ok(())
+    }
+
+    /// Renders the current draw list and waits for it. When given, `pixels` receives the
+    /// frame as tightly packed RGBA8 rows, top row first.
+    pub fn render(&mut self, pixels: Option<&mut [u8]>) -> Result<()> {
+        let size = self.extent.width as usize * self.extent.height as usize * 4;
+        if let Some(pixels) = &pixels
+            && pixels.len() < size
+        {
+            return Err(anyhow::anyhow!(
+                "Pixel buffer holds {} bytes, need {}",
+                pixels.len(),
+                size
+            ));
+        }
+
+        // The previous frame was waited for, so the tables are free to rewrite.
+        self.scene.upload()?;
+
+        let mut encoder = self.command_pool.encoder(0)?;
+        let command_buffer = encoder.command_buffer();
+
+        self.uploads.record_acquires(command_buffer);
+        self.renderer
+            .record_cull(command_buffer, &self.scene, self.view_proj);
+
+        {
+            let mut render_pass = encoder.begin_render_pass(
+                &self.render_pass,
+                self.framebuffer,
+                self.extent,
+                ClearValues::new()
+                    .with_color(self.clear_color)
+                    .with_depth(1.0),
+            )?;
+            render_pass.set_viewport_and_scissor(self.extent);
+            self.renderer
+                .record_draw(command_buffer, &self.scene, &self.textures, self.view_proj);
+        }
+
+        // The render pass leaves the color target in TRANSFER_SRC_OPTIMAL.
+        let rendered = ImageAccess::new(
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        );
+        cmd_copy_image_to_buffer(
+            command_buffer,
+            &TransferImage::new(&self.color, rendered, rendered),
+            &self.readback,
+            0,
+        )?;
+        self.readback.cmd_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::HOST,
+            vk::AccessFlags::HOST_READ,
+        );
+
+        encoder.finish()?;
+
+        self.submit_and_wait(command_buffer)?;
+        self.has_frame = true;
+
+        if let Some(pixels) = pixels {
+            pixels[..size].copy_from_slice(&self.readback.read::<u8>(0, size)?);
+        }
+        Ok(())
+    }
+
+    /// Copies the last frame drawn by `render` into texture `texture`, scaling it to the
+    /// texture's size and converting to its format, e.g. for feedback effects or transitions
+    /// that sample the previous frame. Frames are rendered single-sampled, so there is nothing
+    /// to resolve. Texture 0 is the built-in white texture and can't be overwritten.
+    pub fn copy_backbuffer_to(&mut self, texture: u32) -> Result<()> {
+        if !self.has_frame {
+            return Err(anyhow::anyhow!("No frame has been rendered yet"));
+        }
+        if texture == 0 {
+            return Err(anyhow::anyhow!(
+                "Texture 0 is built in and can't be overwritten"
+            ));
+        }
+        let image = self
+            .texture_images
+            .get(texture as usize)
+            .ok_or_else(|| anyhow::anyhow!("Unknown texture {}", texture))?;
+
+        let encoder = self.command_pool.encoder(0)?;
+        let command_buffer = encoder.command_buffer();
+
+        // Frames end in TRANSFER_SRC_OPTIMAL and `render` waits for them, so the only
+        // hazard left is the next frame's render pass.
+        let frame = ImageAccess::new(
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        );
+        let sampled = ImageAccess::sampled(vk::PipelineStageFlags::FRAGMENT_SHADER);
+        cmd_blit_image(
+            command_buffer,
+            &TransferImage::new(&self.color, frame, frame),
+            &TransferImage::new(image, ImageAccess::UNDEFINED, sampled),
+            vk::Filter::LINEAR,
+        )?;
+
+        encoder.finish()?;
+        self.submit_and_wait(command_buffer)
+    }
+
+    fn submit_and_wait(&self, command_buffer: vk::CommandBuffer) -> Result<()> {
+        let submit_info =
+            vk::SubmitInfo::default().command_buffers(std::slice::from_ref(&command_buffer));
+        unsafe {
+            self.device
+                .device
+                .queue_submit(
+                    self.device.graphics_queue,
+                    std::slice::from_ref(&submit_info),
+                    self.fence,
+                )
+                .map_err(|e| anyhow::anyhow!("Failed to submit frame: {}", e))?;
+            self.device
+                .device
+                .wait_for_fences(std::slice::from_ref(&self.fence), true, u64::MAX)
+                .map_err(|e| anyhow::anyhow!("Failed to wait for frame: {}", e))?;
+            self.device
+                .device
+                .reset_fences(std::slice::from_ref(&self.fence))
+                .map_err(|e| anyhow::anyhow!("Failed to reset fence: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RenderContext {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.device.device.device_wait_idle();
+            self.device
+                .device
+                .destroy_framebuffer(self.framebuffer, None);
+            self.device.device.destroy_fence(self.fence, None);
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod api;
+pub mod context;
+
+pub use api::*;
+pub use context::*;
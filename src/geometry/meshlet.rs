@@ -0,0 +1,284 @@
+use glam::Vec3;
+
+/// Limits matching common mesh shader hardware; also used by the compute path.
+pub const MESHLET_MAX_VERTICES: usize = 64;
+pub const MESHLET_MAX_TRIANGLES: usize = 124;
+
+/// A small cluster of triangles. Offsets index into `MeshletMesh::vertices` and
+/// `MeshletMesh::triangles`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Meshlet {
+    pub vertex_offset: u32,
+    pub vertex_count: u32,
+    pub triangle_offset: u32,
+    pub triangle_count: u32,
+}
+
+/// Bounding sphere and normal cone of a meshlet, used for frustum and backface culling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshletBounds {
+    pub center: Vec3,
+    pub radius: f32,
+    pub cone_axis: Vec3,
+    /// Sine of the normal cone's half angle; 1.0 disables cone culling.
+    pub cone_cutoff: f32,
+}
+
+impl MeshletBounds {
+    /// True if every triangle of the meshlet faces away from `camera_position` (same space as
+    /// the bounds).
+    pub fn is_backfacing(&self, camera_position: Vec3) -> bool {
+        let to_center = self.center - camera_position;
+        to_center.dot(self.cone_axis) >= self.cone_cutoff * to_center.length() + self.radius
+    }
+}
+
+/// A mesh split into meshlets.
+///
+/// `vertices` maps each meshlet-local vertex to an index into the source vertex buffer;
+/// `triangles` holds three meshlet-local vertex indices per triangle.
+#[derive(Debug, Clone, Default)]
+pub struct MeshletMesh {
+    pub meshlets: Vec<Meshlet>,
+    pub bounds: Vec<MeshletBounds>,
+    pub vertices: Vec<u32>,
+    pub triangles: Vec<u8>,
+}
+
+impl MeshletMesh {
+    /// Each triangle packed as `a | b << 8 | c << 16`, for shaders without 8-bit storage.
+    pub fn packed_triangles(&self) -> Vec<u32> {
+        self.triangles
+            .chunks_exact(3)
+            .map(|t| t[0] as u32 | (t[1] as u32) << 8 | (t[2] as u32) << 16)
+            .collect()
+    }
+}
+
+/// Splits an indexed triangle list into meshlets of at most `max_vertices` vertices and
+/// `max_triangles` triangles, in index order.
+///
+/// Indices should be optimized for vertex cache locality first, which keeps neighbouring
+/// triangles together and meshlets compact.
+pub fn build_meshlets(
+    indices: &[u32],
+    positions: &[Vec3],
+    max_vertices: usize,
+    max_triangles: usize,
+) -> MeshletMesh {
+    let max_vertices = max_vertices.clamp(3, 256);
+    let max_triangles = max_triangles.max(1);
+
+    let mut mesh = MeshletMesh::default();
+    let mut local_index: Vec<Option<u8>> = vec![None; positions.len()];
+    let mut current = Meshlet::default();
+
+    for triangle in indices.chunks_exact(3) {
+        let new_vertices = triangle
+            .iter()
+            .enumerate()
+            .filter(|&(i, v)| local_index[*v as usize].is_none() && !triangle[..i].contains(v))
+            .count();
+
+        if current.vertex_count as usize + new_vertices > max_vertices
+            || current.triangle_count as usize >= max_triangles
+        {
+            finish_meshlet(&mut mesh, &mut current, &mut local_index, positions);
+        }
+
+        for &vertex in triangle {
+            let local = match local_index[vertex as usize] {
+                Some(local) => local,
+                None => {
+                    let local = current.vertex_count as u8;
+                    local_index[vertex as usize] = Some(local);
+                    mesh.vertices.push(vertex);
+                    current.vertex_count += 1;
+                    local
+                }
+            };
+            mesh.triangles.push(local);
+        }
+
+        current.triangle_count += 1;
+    }
+
+    finish_meshlet(&mut mesh, &mut current, &mut local_index, positions);
+
+    mesh
+}
+
+fn finish_meshlet(
+    mesh: &mut MeshletMesh,
+    current: &mut Meshlet,
+    local_index: &mut [Option<u8>],
+    positions: &[Vec3],
+) {
+    if current.triangle_count == 0 {
+        return;
+    }
+
+    let vertices =
+        &mesh.vertices[current.vertex_offset as usize..][..current.vertex_count as usize];
+    let triangles = &mesh.triangles[current.triangle_offset as usize * 3..]
+        [..current.triangle_count as usize * 3];
+
+    for &vertex in vertices {
+        local_index[vertex as usize] = None;
+    }
+
+    mesh.bounds
+        .push(compute_bounds(vertices, triangles, positions));
+    mesh.meshlets.push(*current);
+
+    *current = Meshlet {
+        vertex_offset: mesh.vertices.len() as u32,
+        vertex_count: 0,
+        triangle_offset: (mesh.triangles.len() / 3) as u32,
+        triangle_count: 0,
+    };
+}
+
+fn compute_bounds(vertices: &[u32], triangles: &[u8], positions: &[Vec3]) -> MeshletBounds {
+    let position = |local: u8| positions[vertices[local as usize] as usize];
+
+    let (min, max) = vertices.iter().fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), &v| {
+            (
+                min.min(positions[v as usize]),
+                max.max(positions[v as usize]),
+            )
+        },
+    );
+    let center = (min + max) * 0.5;
+    let radius = vertices
+        .iter()
+        .map(|&v| positions[v as usize].distance(center))
+        .fold(0.0, f32::max);
+
+    let normals: Vec<Vec3> = triangles
+        .chunks_exact(3)
+        .filter_map(|t| {
+            let (a, b, c) = (position(t[0]), position(t[1]), position(t[2]));
+            let normal = (b - a).cross(c - a);
+            (normal.length_squared() > 0.0).then(|| normal.normalize())
+        })
+        .collect();
+
+    let axis = normals.iter().copied().sum::<Vec3>().normalize_or_zero();
+    let min_dot = normals.iter().map(|n| n.dot(axis)).fold(1.0, f32::min);
+
+    // A cone wider than a hemisphere can never be entirely backfacing.
+    let cone_cutoff = if axis == Vec3::ZERO || min_dot <= 0.0 {
+        1.0
+    } else {
+        (1.0 - min_dot * min_dot).sqrt()
+    };
+
+    MeshletBounds {
+        center,
+        radius,
+        cone_axis: axis,
+        cone_cutoff,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `n` x `n` quads in the XY plane facing +Z.
+    fn grid(n: u32) -> (Vec<u32>, Vec<Vec3>) {
+        let positions = (0..=n)
+            .flat_map(|y| (0..=n).map(move |x| Vec3::new(x as f32, y as f32, 0.0)))
+            .collect();
+        let indices = (0..n)
+            .flat_map(|y| {
+                (0..n).flat_map(move |x| {
+                    let i = y * (n + 1) + x;
+                    [i, i + 1, i + n + 2, i, i + n + 2, i + n + 1]
+                })
+            })
+            .collect();
+        (indices, positions)
+    }
+
+    fn source_indices(mesh: &MeshletMesh) -> Vec<u32> {
+        mesh.meshlets
+            .iter()
+            .flat_map(|m| {
+                let vertices = &mesh.vertices[m.vertex_offset as usize..];
+                mesh.triangles[m.triangle_offset as usize * 3..][..m.triangle_count as usize * 3]
+                    .iter()
+                    .map(move |&local| vertices[local as usize])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn preserves_triangles_in_order() {
+        let (indices, positions) = grid(16);
+        let mesh = build_meshlets(&indices, &positions, 64, 124);
+
+        assert_eq!(source_indices(&mesh), indices);
+        assert_eq!(mesh.meshlets.len(), mesh.bounds.len());
+    }
+
+    #[test]
+    fn respects_limits() {
+        let (indices, positions) = grid(16);
+        let mesh = build_meshlets(&indices, &positions, 16, 10);
+
+        assert!(mesh.meshlets.len() > 1);
+        for meshlet in &mesh.meshlets {
+            assert!(meshlet.vertex_count <= 16);
+            assert!(meshlet.triangle_count <= 10);
+            assert!(meshlet.triangle_count > 0);
+        }
+    }
+
+    #[test]
+    fn bounds_contain_vertices() {
+        let (indices, positions) = grid(8);
+        let mesh = build_meshlets(&indices, &positions, 32, 32);
+
+        for (meshlet, bounds) in mesh.meshlets.iter().zip(&mesh.bounds) {
+            let vertices =
+                &mesh.vertices[meshlet.vertex_offset as usize..][..meshlet.vertex_count as usize];
+            for &v in vertices {
+                assert!(positions[v as usize].distance(bounds.center) <= bounds.radius + 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn flat_meshlet_has_tight_cone() {
+        let (indices, positions) = grid(2);
+        let mesh = build_meshlets(&indices, &positions, 64, 124);
+        let bounds = mesh.bounds[0];
+
+        assert!(bounds.cone_axis.abs_diff_eq(Vec3::Z, 1e-5));
+        assert!(bounds.cone_cutoff.abs() < 1e-3);
+        assert!(bounds.is_backfacing(Vec3::new(1.0, 1.0, -10.0)));
+        assert!(!bounds.is_backfacing(Vec3::new(1.0, 1.0, 10.0)));
+    }
+
+    #[test]
+    fn packs_triangles() {
+        let mesh = MeshletMesh {
+            triangles: vec![1, 2, 3, 4, 5, 6],
+            ..Default::default()
+        };
+
+        assert_eq!(mesh.packed_triangles(), vec![0x030201, 0x060504]);
+    }
+
+    #[test]
+    fn empty_input_has_no_meshlets() {
+        let mesh = build_meshlets(&[], &[], 64, 124);
+
+        assert!(mesh.meshlets.is_empty());
+        assert!(mesh.vertices.is_empty());
+    }
+}
@@ -0,0 +1,17 @@
+pub mod half;
+pub mod meshlet;
+pub mod optimize;
+pub mod primitives;
+pub mod quantize;
+pub mod strip;
+pub mod tangents;
+pub mod vertex_compression;
+
+pub use half::*;
+pub use meshlet::*;
+pub use optimize::*;
+pub use primitives::*;
+pub use quantize::*;
+pub use strip::*;
+pub use tangents::*;
+pub use vertex_compression::*;
@@ -0,0 +1,506 @@
+use glam::Vec3;
+
+use crate::geometry::{MeshVertex, QuantizationStats, quantize_vertices};
+
+/// Size of the simulated post-transform vertex cache.
+pub const VERTEX_CACHE_SIZE: usize = 32;
+
+/// Vertex cache efficiency of an index buffer under a FIFO cache simulation.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VertexCacheStats {
+    pub vertices_transformed: u32,
+    /// Average cache miss ratio: transformed vertices per triangle (0.5 is ideal, 3.0 worst).
+    pub acmr: f32,
+    /// Average transform to vertex ratio: transformed vertices per unique vertex (1.0 ideal).
+    pub atvr: f32,
+}
+
+/// Vertex fetch efficiency: how much vertex memory is read compared to its size.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VertexFetchStats {
+    pub bytes_fetched: u64,
+    /// Bytes fetched per byte of vertex data (1.0 is ideal).
+    pub overfetch: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MeshStats {
+    pub cache: VertexCacheStats,
+    pub fetch: VertexFetchStats,
+}
+
+impl MeshStats {
+    pub fn analyze(indices: &[u32], vertex_count: usize, vertex_size: usize) -> Self {
+        Self {
+            cache: analyze_vertex_cache(indices, vertex_count, VERTEX_CACHE_SIZE),
+            fetch: analyze_vertex_fetch(indices, vertex_count, vertex_size),
+        }
+    }
+}
+
+impl std::fmt::Display for MeshStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ACMR {:.3}, ATVR {:.3}, overfetch {:.3}",
+            self.cache.acmr, self.cache.atvr, self.fetch.overfetch
+        )
+    }
+}
+
+/// Statistics of a mesh before and after optimization, and of quantizing the optimized
+/// vertices with `quantize_vertices`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MeshOptimizationReport {
+    pub before: MeshStats,
+    pub after: MeshStats,
+    pub quantization: QuantizationStats,
+}
+
+pub fn analyze_vertex_cache(
+    indices: &[u32],
+    vertex_count: usize,
+    cache_size: usize,
+) -> VertexCacheStats {
+    let mut cache_timestamps = vec![0u32; vertex_count];
+    let mut timestamp = cache_size as u32 + 1;
+    let mut transformed = 0u32;
+
+    for &index in indices {
+        // FIFO: a vertex is cached if it was inserted within the last `cache_size` misses.
+        if timestamp - cache_timestamps[index as usize] > cache_size as u32 {
+            cache_timestamps[index as usize] = timestamp;
+            timestamp += 1;
+            transformed += 1;
+        }
+    }
+
+    let triangles = (indices.len() / 3).max(1);
+    let unique = {
+        let mut seen = vec![false; vertex_count];
+        indices
+            .iter()
+            .filter(|&&i| !std::mem::replace(&mut seen[i as usize], true))
+            .count()
+            .max(1)
+    };
+
+    VertexCacheStats {
+        vertices_transformed: transformed,
+        acmr: transformed as f32 / triangles as f32,
+        atvr: transformed as f32 / unique as f32,
+    }
+}
+
+/// Simulates fetching vertices through 64-byte cache lines.
+pub fn analyze_vertex_fetch(
+    indices: &[u32],
+    vertex_count: usize,
+    vertex_size: usize,
+) -> VertexFetchStats {
+    const LINE_SIZE: usize = 64;
+    const CACHE_LINES: usize = 64;
+
+    let mut lines: std::collections::VecDeque<usize> = std::collections::VecDeque::new();
+    let mut bytes_fetched = 0u64;
+
+    for &index in indices {
+        let start = index as usize * vertex_size / LINE_SIZE;
+        let end = ((index as usize + 1) * vertex_size).div_ceil(LINE_SIZE);
+
+        for line in start..end {
+            if !lines.contains(&line) {
+                lines.push_back(line);
+                if lines.len() > CACHE_LINES {
+                    lines.pop_front();
+                }
+                bytes_fetched += LINE_SIZE as u64;
+            }
+        }
+    }
+
+    VertexFetchStats {
+        bytes_fetched,
+        overfetch: bytes_fetched as f32 / (vertex_count * vertex_size).max(1) as f32,
+    }
+}
+
+/// Reorders triangles for post-transform cache reuse (Forsyth's linear-speed algorithm).
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    const CACHE_DECAY_POWER: f32 = 1.5;
+    const LAST_TRIANGLE_SCORE: f32 = 0.75;
+    const VALENCE_BOOST_SCALE: f32 = 2.0;
+    const VALENCE_BOOST_POWER: f32 = 0.5;
+
+    let triangle_count = indices.len() / 3;
+
+    let mut triangles_of_vertex = vec![Vec::new(); vertex_count];
+    for triangle in 0..triangle_count {
+        for &vertex in &indices[triangle * 3..triangle * 3 + 3] {
+            triangles_of_vertex[vertex as usize].push(triangle as u32);
+        }
+    }
+
+    let mut remaining: Vec<u32> = triangles_of_vertex.iter().map(|t| t.len() as u32).collect();
+    let mut emitted = vec![false; triangle_count];
+
+    let vertex_score = |position: Option<usize>, remaining: u32| -> f32 {
+        if remaining == 0 {
+            return -1.0;
+        }
+
+        let cache_score = match position {
+            None => 0.0,
+            Some(p) if p < 3 => LAST_TRIANGLE_SCORE,
+            Some(p) => {
+                let scale = 1.0 / (VERTEX_CACHE_SIZE - 3) as f32;
+                (1.0 - (p - 3) as f32 * scale).powf(CACHE_DECAY_POWER)
+            }
+        };
+
+        cache_score + VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER)
+    };
+
+    let mut scores: Vec<f32> = (0..vertex_count)
+        .map(|v| vertex_score(None, remaining[v]))
+        .collect();
+    let triangle_score = |scores: &[f32], triangle: usize| -> f32 {
+        indices[triangle * 3..triangle * 3 + 3]
+            .iter()
+            .map(|&v| scores[v as usize])
+            .sum()
+    };
+
+    let mut cache: Vec<u32> = Vec::with_capacity(VERTEX_CACHE_SIZE + 3);
+    let mut result = Vec::with_capacity(indices.len());
+    let mut next_unemitted = 0;
+
+    for _ in 0..triangle_count {
+        // Best candidate among triangles touching the cache, else the next unemitted one.
+        let best = cache
+            .iter()
+            .flat_map(|&v| triangles_of_vertex[v as usize].iter().copied())
+            .filter(|&t| !emitted[t as usize])
+            .map(|t| (t as usize, triangle_score(&scores, t as usize)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(t, _)| t);
+
+        let triangle = match best {
+            Some(t) => t,
+            None => {
+                while emitted[next_unemitted] {
+                    next_unemitted += 1;
+                }
+                next_unemitted
+            }
+        };
+
+        emitted[triangle] = true;
+        let vertices = &indices[triangle * 3..triangle * 3 + 3];
+        result.extend_from_slice(vertices);
+
+        for &vertex in vertices {
+            remaining[vertex as usize] -= 1;
+            let list = &mut triangles_of_vertex[vertex as usize];
+            if let Some(position) = list.iter().position(|&t| t as usize == triangle) {
+                list.swap_remove(position);
+            }
+        }
+
+        // Move the triangle's vertices to the front of the LRU cache.
+        for &vertex in vertices.iter().rev() {
+            if let Some(position) = cache.iter().position(|&v| v == vertex) {
+                cache.remove(position);
+            }
+            cache.insert(0, vertex);
+        }
+
+        for &evicted in cache.iter().skip(VERTEX_CACHE_SIZE) {
+            scores[evicted as usize] = vertex_score(None, remaining[evicted as usize]);
+        }
+        cache.truncate(VERTEX_CACHE_SIZE);
+
+        for (position, &vertex) in cache.iter().enumerate() {
+            scores[vertex as usize] = vertex_score(Some(position), remaining[vertex as usize]);
+        }
+    }
+
+    result
+}
+
+/// Reorders clusters of cache-optimized triangles so that outward-facing clusters on the
+/// outside of the mesh are drawn first, reducing overdraw. Cluster boundaries are placed where
+/// the vertex cache is cold anyway; the result is discarded if it degrades vertex cache
+/// efficiency by more than `threshold` (e.g. 1.05 allows a 5% ACMR increase).
+pub fn optimize_overdraw(indices: &[u32], positions: &[Vec3], threshold: f32) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return indices.to_vec();
+    }
+
+    let triangle_acmr =
+        |range: &[u32]| analyze_vertex_cache(range, positions.len(), VERTEX_CACHE_SIZE).acmr;
+    let mesh_acmr = triangle_acmr(indices);
+
+    // Cut clusters where the cache is cold anyway: triangles whose three vertices all miss.
+    let mut cache_timestamps = vec![0u32; positions.len()];
+    let mut timestamp = VERTEX_CACHE_SIZE as u32 + 1;
+    let mut clusters: Vec<(usize, usize)> = Vec::new();
+    let mut start = 0;
+
+    for triangle in 0..triangle_count {
+        let mut misses = 0;
+        for &index in &indices[triangle * 3..triangle * 3 + 3] {
+            if timestamp - cache_timestamps[index as usize] > VERTEX_CACHE_SIZE as u32 {
+                cache_timestamps[index as usize] = timestamp;
+                timestamp += 1;
+                misses += 1;
+            }
+        }
+
+        if misses == 3 && triangle - start >= 16 {
+            clusters.push((start, triangle));
+            start = triangle;
+        }
+    }
+    clusters.push((start, triangle_count));
+
+    let mesh_centroid =
+        indices.iter().map(|&i| positions[i as usize]).sum::<Vec3>() / indices.len() as f32;
+
+    let mut sorted: Vec<(f32, usize, usize)> = clusters
+        .into_iter()
+        .map(|(start, end)| {
+            let mut centroid = Vec3::ZERO;
+            let mut normal = Vec3::ZERO;
+            for triangle in indices[start * 3..end * 3].chunks_exact(3) {
+                let (a, b, c) = (
+                    positions[triangle[0] as usize],
+                    positions[triangle[1] as usize],
+                    positions[triangle[2] as usize],
+                );
+                let area_normal = (b - a).cross(c - a);
+                centroid += (a + b + c) / 3.0;
+                normal += area_normal;
+            }
+            centroid /= (end - start) as f32;
+            let score = (centroid - mesh_centroid).dot(normal.normalize_or_zero());
+            (score, start, end)
+        })
+        .collect();
+
+    sorted.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let reordered: Vec<u32> = sorted
+        .iter()
+        .flat_map(|&(_, start, end)| indices[start * 3..end * 3].iter().copied())
+        .collect();
+
+    // Keep the input order if the reordering cost more cache efficiency than allowed.
+    if triangle_acmr(&reordered) > mesh_acmr * threshold {
+        indices.to_vec()
+    } else {
+        reordered
+    }
+}
+
+/// Reorders vertices by first use so vertex fetches walk memory linearly. Remaps `indices` in
+/// place and drops unreferenced vertices.
+pub fn optimize_vertex_fetch<V: Copy>(indices: &mut [u32], vertices: &[V]) -> Vec<V> {
+    let mut remap: Vec<Option<u32>> = vec![None; vertices.len()];
+    let mut result = Vec::with_capacity(vertices.len());
+
+    for index in indices.iter_mut() {
+        let new_index = *remap[*index as usize].get_or_insert_with(|| {
+            result.push(vertices[*index as usize]);
+            result.len() as u32 - 1
+        });
+        *index = new_index;
+    }
+
+    result
+}
+
+/// Runs vertex cache, overdraw and vertex fetch optimization on a mesh, as done on import,
+/// and measures what quantizing the result would save and cost.
+pub fn optimize_mesh<V: MeshVertex + Copy>(
+    vertices: &mut Vec<V>,
+    indices: &mut Vec<u32>,
+) -> MeshOptimizationReport {
+    let vertex_size = std::mem::size_of::<V>();
+    let before = MeshStats::analyze(indices, vertices.len(), vertex_size);
+
+    let positions: Vec<Vec3> = vertices.iter().map(MeshVertex::position).collect();
+    let cache_optimized = optimize_vertex_cache(indices, vertices.len());
+    *indices = optimize_overdraw(&cache_optimized, &positions, 1.05);
+    *vertices = optimize_vertex_fetch(indices, vertices);
+
+    let after = MeshStats::analyze(indices, vertices.len(), vertex_size);
+
+    let (quantized, bounds) = quantize_vertices(vertices);
+    let quantization = QuantizationStats::measure(vertices, &quantized, &bounds);
+
+    MeshOptimizationReport {
+        before,
+        after,
+        quantization,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec2;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct TestVertex {
+        position: Vec3,
+    }
+
+    impl MeshVertex for TestVertex {
+        fn position(&self) -> Vec3 {
+            self.position
+        }
+
+        fn normal(&self) -> Vec3 {
+            Vec3::Z
+        }
+
+        fn uv(&self) -> Vec2 {
+            self.position.truncate() / 32.0
+        }
+    }
+
+    /// `n` x `n` quads in the XY plane, emitted column by column so the input order is cache
+    /// unfriendly.
+    fn grid(n: u32) -> (Vec<u32>, Vec<Vec3>) {
+        let positions = (0..=n)
+            .flat_map(|y| (0..=n).map(move |x| Vec3::new(x as f32, y as f32, 0.0)))
+            .collect();
+        let indices = (0..n)
+            .flat_map(|x| {
+                (0..n).flat_map(move |y| {
+                    let i = y * (n + 1) + x;
+                    [i, i + 1, i + n + 2, i, i + n + 2, i + n + 1]
+                })
+            })
+            .collect();
+        (indices, positions)
+    }
+
+    fn sorted_triangles(indices: &[u32]) -> Vec<[u32; 3]> {
+        let mut triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|t| {
+                // Rotate so the smallest index comes first, keeping the winding.
+                let start = (0..3).min_by_key(|&i| t[i]).unwrap();
+                [t[start], t[(start + 1) % 3], t[(start + 2) % 3]]
+            })
+            .collect();
+        triangles.sort();
+        triangles
+    }
+
+    #[test]
+    fn cache_stats_of_strip() {
+        let stats = analyze_vertex_cache(&[0, 1, 2, 2, 1, 3], 4, VERTEX_CACHE_SIZE);
+
+        assert_eq!(stats.vertices_transformed, 4);
+        assert_eq!(stats.acmr, 2.0);
+        assert_eq!(stats.atvr, 1.0);
+    }
+
+    #[test]
+    fn vertex_cache_keeps_triangles_and_improves_acmr() {
+        let (indices, positions) = grid(64);
+        let optimized = optimize_vertex_cache(&indices, positions.len());
+
+        assert_eq!(sorted_triangles(&optimized), sorted_triangles(&indices));
+
+        let before = analyze_vertex_cache(&indices, positions.len(), 16);
+        let after = analyze_vertex_cache(&optimized, positions.len(), 16);
+        assert!(after.acmr < before.acmr);
+    }
+
+    #[test]
+    fn overdraw_keeps_triangles_within_threshold() {
+        let (indices, positions) = grid(32);
+        let cache_optimized = optimize_vertex_cache(&indices, positions.len());
+        let optimized = optimize_overdraw(&cache_optimized, &positions, 1.05);
+
+        assert_eq!(sorted_triangles(&optimized), sorted_triangles(&indices));
+
+        let before = analyze_vertex_cache(&cache_optimized, positions.len(), VERTEX_CACHE_SIZE);
+        let after = analyze_vertex_cache(&optimized, positions.len(), VERTEX_CACHE_SIZE);
+        assert!(after.acmr <= before.acmr * 1.05);
+    }
+
+    #[test]
+    fn overdraw_of_empty_mesh() {
+        assert!(optimize_overdraw(&[], &[], 1.05).is_empty());
+    }
+
+    #[test]
+    fn vertex_fetch_orders_by_first_use_and_drops_unused() {
+        let vertices = ['a', 'b', 'c', 'd', 'e'];
+        let mut indices = vec![3, 1, 4, 4, 1, 3];
+
+        let reordered = optimize_vertex_fetch(&mut indices, &vertices);
+
+        assert_eq!(reordered, vec!['d', 'b', 'e']);
+        assert_eq!(indices, vec![0, 1, 2, 2, 1, 0]);
+    }
+
+    #[test]
+    fn optimize_mesh_reports_improvement_and_quantization() {
+        let (mut indices, positions) = grid(32);
+        let mut vertices: Vec<TestVertex> = positions
+            .iter()
+            .rev()
+            .map(|&position| TestVertex { position })
+            .collect();
+        let vertex_count = vertices.len() as u32;
+        for index in &mut indices {
+            *index = vertex_count - 1 - *index;
+        }
+        let original = sorted_positions(&indices, &vertices);
+
+        let report = optimize_mesh(&mut vertices, &mut indices);
+
+        assert_eq!(sorted_positions(&indices, &vertices), original);
+        assert!(report.after.cache.acmr < report.before.cache.acmr);
+        let mut next = 0;
+        for &index in &indices {
+            assert!(index <= next);
+            next = next.max(index + 1);
+        }
+        assert_eq!(
+            report.quantization.original_size,
+            std::mem::size_of_val(vertices.as_slice())
+        );
+        assert!(report.quantization.quantized_size > 0);
+        assert!(report.quantization.max_position_error < 1.0e-3);
+    }
+
+    fn sorted_positions(indices: &[u32], vertices: &[TestVertex]) -> Vec<[i32; 9]> {
+        let mut triangles: Vec<[i32; 9]> = indices
+            .chunks_exact(3)
+            .map(|t| {
+                let start = (0..3)
+                    .min_by_key(|&i| {
+                        let p = vertices[t[i] as usize].position;
+                        (p.y as i32, p.x as i32)
+                    })
+                    .unwrap();
+                let mut out = [0; 9];
+                for k in 0..3 {
+                    let p = vertices[t[(start + k) % 3] as usize].position;
+                    out[k * 3..k * 3 + 3].copy_from_slice(&[p.x as i32, p.y as i32, p.z as i32]);
+                }
+                out
+            })
+            .collect();
+        triangles.sort();
+        triangles
+    }
+}
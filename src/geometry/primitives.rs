@@ -0,0 +1,325 @@
+use glam::{Vec2, Vec3};
+use std::f32::consts::{PI, TAU};
+
+use crate::assets::MeshAsset;
+use crate::renderer::GpuVertex;
+
+/// Point of a parametric surface: position, unit normal, unit tangent along +u and the
+/// direction of +v, which only decides the bitangent sign.
+struct SurfacePoint {
+    position: Vec3,
+    normal: Vec3,
+    tangent: Vec3,
+    along_v: Vec3,
+}
+
+/// Appends a `columns` x `rows` quad grid sampled from `surface` at (u, v) in [0, 1]², with
+/// UVs (u, v) and triangles wound counter-clockwise seen from the normal side.
+fn append_grid(
+    mesh: &mut MeshAsset,
+    columns: u32,
+    rows: u32,
+    surface: impl Fn(f32, f32) -> SurfacePoint,
+) {
+    let base = mesh.vertices.len() as u32;
+    let mut counter_clockwise = true;
+
+    for row in 0..=rows {
+        for column in 0..=columns {
+            let uv = Vec2::new(column as f32 / columns as f32, row as f32 / rows as f32);
+            let point = surface(uv.x, uv.y);
+            let sign = point
+                .normal
+                .cross(point.tangent)
+                .dot(point.along_v)
+                .signum();
+            if row == 0 && column == 0 {
+                counter_clockwise = point.tangent.cross(point.along_v).dot(point.normal) > 0.0;
+            }
+
+            mesh.vertices.push(GpuVertex {
+                position: point.position.to_array(),
+                normal: point.normal.to_array(),
+                uv: uv.to_array(),
+            });
+            mesh.tangents.push(point.tangent.extend(sign).to_array());
+        }
+    }
+
+    for row in 0..rows {
+        for column in 0..columns {
+            let corner = base + row * (columns + 1) + column;
+            let right = corner + 1;
+            let below = corner + columns + 1;
+            if counter_clockwise {
+                mesh.indices.extend_from_slice(&[
+                    corner,
+                    right,
+                    below + 1,
+                    corner,
+                    below + 1,
+                    below,
+                ]);
+            } else {
+                mesh.indices.extend_from_slice(&[
+                    corner,
+                    below + 1,
+                    right,
+                    corner,
+                    below,
+                    below + 1,
+                ]);
+            }
+        }
+    }
+}
+
+/// Disc at height `y` facing +Y or -Y, mapped like the top and bottom faces of `cube`.
+fn append_disc(mesh: &mut MeshAsset, radius: f32, y: f32, facing_up: bool, segments: u32) {
+    let base = mesh.vertices.len() as u32;
+    let normal = if facing_up { Vec3::Y } else { Vec3::NEG_Y };
+    let sign = if facing_up { 1.0 } else { -1.0 };
+
+    let rim = (0..segments).map(|segment| {
+        let angle = segment as f32 / segments as f32 * TAU;
+        Vec3::new(angle.cos(), 0.0, -angle.sin())
+    });
+    for offset in std::iter::once(Vec3::ZERO).chain(rim) {
+        mesh.vertices.push(GpuVertex {
+            position: (offset * radius + Vec3::new(0.0, y, 0.0)).to_array(),
+            normal: normal.to_array(),
+            uv: [offset.x * 0.5 + 0.5, offset.z * sign * 0.5 + 0.5],
+        });
+        mesh.tangents.push([1.0, 0.0, 0.0, -1.0]);
+    }
+
+    for segment in 0..segments {
+        let current = base + 1 + segment;
+        let next = base + 1 + (segment + 1) % segments;
+        if facing_up {
+            mesh.indices.extend_from_slice(&[base, current, next]);
+        } else {
+            mesh.indices.extend_from_slice(&[base, next, current]);
+        }
+    }
+}
+
+/// Flat square in the XZ plane facing +Y, `size` wide and split into `subdivisions` quads per
+/// side. U runs along +X and V along +Z.
+pub fn plane(size: f32, subdivisions: u32) -> MeshAsset {
+    let subdivisions = subdivisions.max(1);
+    let mut mesh = MeshAsset::default();
+    append_grid(&mut mesh, subdivisions, subdivisions, |u, v| SurfacePoint {
+        position: Vec3::new(u - 0.5, 0.0, v - 0.5) * size,
+        normal: Vec3::Y,
+        tangent: Vec3::X,
+        along_v: Vec3::Z,
+    });
+    mesh
+}
+
+/// Axis-aligned cube of edge `size` centered at the origin, with a full [0, 1] UV square on
+/// every face, upright on the side faces.
+pub fn cube(size: f32) -> MeshAsset {
+    let mut mesh = MeshAsset::default();
+    // Normal, +u and +v of every face.
+    let faces = [
+        (Vec3::X, Vec3::NEG_Z, Vec3::NEG_Y),
+        (Vec3::NEG_X, Vec3::Z, Vec3::NEG_Y),
+        (Vec3::Z, Vec3::X, Vec3::NEG_Y),
+        (Vec3::NEG_Z, Vec3::NEG_X, Vec3::NEG_Y),
+        (Vec3::Y, Vec3::X, Vec3::Z),
+        (Vec3::NEG_Y, Vec3::X, Vec3::NEG_Z),
+    ];
+
+    for (normal, tangent, along_v) in faces {
+        append_grid(&mut mesh, 1, 1, |u, v| SurfacePoint {
+            position: (normal * 0.5 + tangent * (u - 0.5) + along_v * (v - 0.5)) * size,
+            normal,
+            tangent,
+            along_v,
+        });
+    }
+    mesh
+}
+
+/// UV sphere centered at the origin with `segments` around the Y axis and `rings` from pole
+/// to pole. The seam vertices are duplicated so U wraps from 0 to 1.
+pub fn sphere(radius: f32, segments: u32, rings: u32) -> MeshAsset {
+    let mut mesh = MeshAsset::default();
+    append_grid(&mut mesh, segments.max(3), rings.max(2), |u, v| {
+        let (sin_phi, cos_phi) = (u * TAU).sin_cos();
+        let (sin_theta, cos_theta) = (v * PI).sin_cos();
+        let normal = Vec3::new(sin_theta * cos_phi, cos_theta, -sin_theta * sin_phi);
+        SurfacePoint {
+            position: normal * radius,
+            normal,
+            tangent: Vec3::new(-sin_phi, 0.0, -cos_phi),
+            along_v: Vec3::new(cos_theta * cos_phi, -sin_theta, -cos_theta * sin_phi),
+        }
+    });
+    mesh
+}
+
+/// Capped cylinder around the Y axis, centered at the origin.
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> MeshAsset {
+    let segments = segments.max(3);
+    let mut mesh = MeshAsset::default();
+    append_grid(&mut mesh, segments, 1, |u, v| {
+        let (sin_phi, cos_phi) = (u * TAU).sin_cos();
+        let normal = Vec3::new(cos_phi, 0.0, -sin_phi);
+        SurfacePoint {
+            position: normal * radius + Vec3::new(0.0, (0.5 - v) * height, 0.0),
+            normal,
+            tangent: Vec3::new(-sin_phi, 0.0, -cos_phi),
+            along_v: Vec3::NEG_Y,
+        }
+    });
+    append_disc(&mut mesh, radius, height * 0.5, true, segments);
+    append_disc(&mut mesh, radius, -height * 0.5, false, segments);
+    mesh
+}
+
+/// Cone around the Y axis with its apex at `height / 2` and a capped base at `-height / 2`.
+/// The apex is one vertex per segment so every side triangle gets its own normal.
+pub fn cone(radius: f32, height: f32, segments: u32) -> MeshAsset {
+    let segments = segments.max(3);
+    let slope = Vec2::new(height, radius).normalize_or_zero();
+    let mut mesh = MeshAsset::default();
+    append_grid(&mut mesh, segments, 1, |u, v| {
+        let (sin_phi, cos_phi) = (u * TAU).sin_cos();
+        let outward = Vec3::new(cos_phi, 0.0, -sin_phi);
+        let apex = Vec3::new(0.0, height * 0.5, 0.0);
+        let rim = outward * radius - apex;
+        SurfacePoint {
+            position: apex + (rim - apex) * v,
+            normal: outward * slope.x + Vec3::Y * slope.y,
+            tangent: Vec3::new(-sin_phi, 0.0, -cos_phi),
+            along_v: rim - apex,
+        }
+    });
+    append_disc(&mut mesh, radius, -height * 0.5, false, segments);
+    mesh
+}
+
+/// Torus around the Y axis: a tube of `minor_radius` swept around a circle of
+/// `major_radius`, with `segments` along the sweep and `sides` around the tube.
+pub fn torus(major_radius: f32, minor_radius: f32, segments: u32, sides: u32) -> MeshAsset {
+    let mut mesh = MeshAsset::default();
+    append_grid(&mut mesh, segments.max(3), sides.max(3), |u, v| {
+        let (sin_alpha, cos_alpha) = (u * TAU).sin_cos();
+        let (sin_beta, cos_beta) = (v * TAU).sin_cos();
+        let outward = Vec3::new(cos_alpha, 0.0, -sin_alpha);
+        let normal = outward * cos_beta + Vec3::Y * sin_beta;
+        SurfacePoint {
+            position: outward * major_radius + normal * minor_radius,
+            normal,
+            tangent: Vec3::new(-sin_alpha, 0.0, -cos_alpha),
+            along_v: Vec3::Y * cos_beta - outward * sin_beta,
+        }
+    });
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks every triangle against its vertex frames: counter-clockwise around the normal,
+    /// and tangent and bitangent sign agreeing with the UV gradients.
+    fn assert_consistent(name: &str, mesh: &MeshAsset) {
+        assert_eq!(mesh.tangents.len(), mesh.vertices.len(), "{}", name);
+        assert_eq!(mesh.indices.len() % 3, 0, "{}", name);
+
+        for (vertex, tangent) in mesh.vertices.iter().zip(&mesh.tangents) {
+            let normal = Vec3::from(vertex.normal);
+            let tangent_xyz = Vec3::from_slice(tangent);
+            assert!((normal.length() - 1.0).abs() < 1e-4, "{} normal", name);
+            assert!(
+                (tangent_xyz.length() - 1.0).abs() < 1e-4,
+                "{} tangent",
+                name
+            );
+            assert!(normal.dot(tangent_xyz).abs() < 1e-4, "{} orthogonal", name);
+            assert!(tangent[3].abs() == 1.0, "{} sign", name);
+        }
+
+        for triangle in mesh.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| &mesh.vertices[triangle[i] as usize]);
+            let edge1 = Vec3::from(b.position) - Vec3::from(a.position);
+            let edge2 = Vec3::from(c.position) - Vec3::from(a.position);
+            let face = edge1.cross(edge2);
+            if face.length() < 1e-6 {
+                continue;
+            }
+            let uv1 = Vec2::from(b.uv) - Vec2::from(a.uv);
+            let uv2 = Vec2::from(c.uv) - Vec2::from(a.uv);
+            let det = uv1.x * uv2.y - uv2.x * uv1.y;
+            let along_u = (edge1 * uv2.y - edge2 * uv1.y) / det;
+            let along_v = (edge2 * uv1.x - edge1 * uv2.x) / det;
+
+            for &index in triangle {
+                let normal = Vec3::from(mesh.vertices[index as usize].normal);
+                let tangent = mesh.tangents[index as usize];
+                let bitangent = normal.cross(Vec3::from_slice(&tangent)) * tangent[3];
+                assert!(face.dot(normal) > 0.0, "{} winding", name);
+                assert!(
+                    along_u.dot(Vec3::from_slice(&tangent)) > 0.0,
+                    "{} tangent",
+                    name
+                );
+                assert!(along_v.dot(bitangent) > 0.0, "{} bitangent", name);
+            }
+        }
+    }
+
+    #[test]
+    fn primitives_have_consistent_frames() {
+        for (name, mesh) in [
+            ("plane", plane(2.0, 4)),
+            ("cube", cube(1.0)),
+            ("sphere", sphere(1.0, 16, 8)),
+            ("cylinder", cylinder(0.5, 2.0, 12)),
+            ("cone", cone(0.5, 1.0, 12)),
+            ("torus", torus(1.0, 0.25, 16, 8)),
+        ] {
+            assert_consistent(name, &mesh);
+        }
+    }
+
+    #[test]
+    fn primitives_have_expected_extents() {
+        let cube = cube(2.0);
+        assert_eq!(cube.vertices.len(), 24);
+        assert_eq!(cube.indices.len(), 36);
+        assert!(
+            cube.vertices
+                .iter()
+                .all(|v| Vec3::from(v.position).abs().max_element() == 1.0)
+        );
+
+        let sphere = sphere(3.0, 16, 8);
+        assert_eq!(sphere.vertices.len(), 17 * 9);
+        assert!(
+            sphere
+                .vertices
+                .iter()
+                .all(|v| (Vec3::from(v.position).length() - 3.0).abs() < 1e-5)
+        );
+
+        let torus = torus(1.0, 0.25, 16, 8);
+        assert!(torus.vertices.iter().all(|v| {
+            let position = Vec3::from(v.position);
+            let ring = Vec3::new(position.x, 0.0, position.z).normalize();
+            ((position - ring).length() - 0.25).abs() < 1e-5
+        }));
+
+        let cone = cone(0.5, 1.0, 12);
+        let top = cone
+            .vertices
+            .iter()
+            .map(|v| v.position[1])
+            .fold(f32::MIN, f32::max);
+        assert_eq!(top, 0.5);
+    }
+}
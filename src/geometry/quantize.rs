@@ -0,0 +1,386 @@
+use ash::vk;
+use glam::{Vec2, Vec3};
+
+/// Attribute access for vertex types that can be quantized.
+pub trait MeshVertex {
+    fn position(&self) -> Vec3;
+    fn normal(&self) -> Vec3;
+    fn uv(&self) -> Vec2;
+}
+
+/// Maps `value` in [0, 1] to an unsigned normalized integer of `bits` bits.
+pub fn quantize_unorm(value: f32, bits: u32) -> u32 {
+    let scale = ((1u32 << bits) - 1) as f32;
+    (value.clamp(0.0, 1.0) * scale + 0.5) as u32
+}
+
+/// Maps `value` in [-1, 1] to a signed normalized integer of `bits` bits.
+pub fn quantize_snorm(value: f32, bits: u32) -> i32 {
+    let scale = ((1u32 << (bits - 1)) - 1) as f32;
+    (value.clamp(-1.0, 1.0) * scale).round() as i32
+}
+
+/// Converts to an IEEE half float, rounding to nearest and flushing denormals to zero.
+pub fn quantize_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    if exponent == 0xff {
+        // Infinity stays infinity, NaN stays NaN.
+        let nan = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if half_exponent <= 0 {
+        return sign;
+    }
+
+    // Adding lets a mantissa that rounds up carry into the exponent.
+    let rounded = ((half_exponent as u32) << 10) + ((mantissa + 0x1000) >> 13);
+    sign | rounded.min(0x7c00) as u16
+}
+
+/// Converts an IEEE half float back to `f32`; denormals decode as zero.
+pub fn dequantize_half(value: u16) -> f32 {
+    let sign = ((value & 0x8000) as u32) << 16;
+    let exponent = ((value >> 10) & 0x1f) as u32;
+    let mantissa = (value & 0x03ff) as u32;
+
+    let bits = match exponent {
+        0 => sign,
+        0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+/// Compact vertex: 16-bit positions normalized to the mesh bounds, 8-bit normals and
+/// half-float UVs. 16 bytes instead of 32 for a full-precision position, normal and UV.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuantizedVertex {
+    pub position: [u16; 4],
+    pub normal: [i8; 4],
+    pub uv: [u16; 2],
+}
+
+impl QuantizedVertex {
+    pub fn binding_description(binding: u32) -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription::default()
+            .binding(binding)
+            .stride(std::mem::size_of::<Self>() as u32)
+            .input_rate(vk::VertexInputRate::VERTEX)
+    }
+
+    /// Locations 0..=2: position (unorm, decode with `QuantizationBounds`), normal, uv.
+    pub fn attribute_descriptions(binding: u32) -> [vk::VertexInputAttributeDescription; 3] {
+        let attribute = |location: u32, format: vk::Format, offset: u32| {
+            vk::VertexInputAttributeDescription::default()
+                .binding(binding)
+                .location(location)
+                .format(format)
+                .offset(offset)
+        };
+
+        [
+            attribute(0, vk::Format::R16G16B16A16_UNORM, 0),
+            attribute(1, vk::Format::R8G8B8A8_SNORM, 8),
+            attribute(2, vk::Format::R16G16_SFLOAT, 12),
+        ]
+    }
+
+    /// Decodes the position as the vertex shader does.
+    pub fn decode_position(&self, bounds: &QuantizationBounds) -> Vec3 {
+        let unorm = Vec3::new(
+            self.position[0] as f32,
+            self.position[1] as f32,
+            self.position[2] as f32,
+        ) / u16::MAX as f32;
+        bounds.offset + unorm * bounds.scale
+    }
+
+    pub fn decode_normal(&self) -> Vec3 {
+        Vec3::new(
+            self.normal[0] as f32,
+            self.normal[1] as f32,
+            self.normal[2] as f32,
+        ) / i8::MAX as f32
+    }
+
+    pub fn decode_uv(&self) -> Vec2 {
+        Vec2::new(dequantize_half(self.uv[0]), dequantize_half(self.uv[1]))
+    }
+}
+
+/// Decodes quantized positions: `position = offset + unorm * scale`, which folds into the
+/// model matrix as `model * translation(offset) * scale(scale)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuantizationBounds {
+    pub offset: Vec3,
+    pub scale: Vec3,
+}
+
+/// Full-precision attributes read back from a compact vertex.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DecodedVertex {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub uv: Vec2,
+}
+
+impl MeshVertex for DecodedVertex {
+    fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    fn normal(&self) -> Vec3 {
+        self.normal
+    }
+
+    fn uv(&self) -> Vec2 {
+        self.uv
+    }
+}
+
+/// Size saved and precision lost by quantizing a mesh's vertices.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QuantizationStats {
+    pub original_size: usize,
+    pub quantized_size: usize,
+    /// Largest position error, in mesh units.
+    pub max_position_error: f32,
+    /// Largest angle between an original and a decoded normal, in radians.
+    pub max_normal_error: f32,
+    pub max_uv_error: f32,
+}
+
+impl QuantizationStats {
+    pub fn measure<V: MeshVertex>(
+        vertices: &[V],
+        quantized: &[QuantizedVertex],
+        bounds: &QuantizationBounds,
+    ) -> Self {
+        let decoded: Vec<DecodedVertex> = quantized
+            .iter()
+            .map(|vertex| DecodedVertex {
+                position: vertex.decode_position(bounds),
+                normal: vertex.decode_normal(),
+                uv: vertex.decode_uv(),
+            })
+            .collect();
+        Self::compare(vertices, &decoded, std::mem::size_of_val(quantized))
+    }
+
+    /// Errors between `vertices` and the same vertices decoded from `compressed_size` bytes
+    /// of some compact encoding.
+    pub fn compare<V: MeshVertex, D: MeshVertex>(
+        vertices: &[V],
+        decoded: &[D],
+        compressed_size: usize,
+    ) -> Self {
+        let mut stats = Self {
+            original_size: std::mem::size_of_val(vertices),
+            quantized_size: compressed_size,
+            ..Default::default()
+        };
+
+        for (vertex, decoded) in vertices.iter().zip(decoded) {
+            let position_error = vertex.position().distance(decoded.position());
+            let normal_error = vertex
+                .normal()
+                .normalize_or_zero()
+                .angle_between(decoded.normal().normalize_or_zero());
+            let uv_error = (vertex.uv() - decoded.uv()).abs().max_element();
+
+            stats.max_position_error = stats.max_position_error.max(position_error);
+            if normal_error.is_finite() {
+                stats.max_normal_error = stats.max_normal_error.max(normal_error);
+            }
+            stats.max_uv_error = stats.max_uv_error.max(uv_error);
+        }
+
+        stats
+    }
+
+    /// Quantized size as a fraction of the original size.
+    pub fn ratio(&self) -> f32 {
+        self.quantized_size as f32 / self.original_size.max(1) as f32
+    }
+}
+
+impl std::fmt::Display for QuantizationStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> {} bytes, position error {:.6}, normal error {:.4} rad, uv error {:.6}",
+            self.original_size,
+            self.quantized_size,
+            self.max_position_error,
+            self.max_normal_error,
+            self.max_uv_error
+        )
+    }
+}
+
+pub fn quantize_vertices<V: MeshVertex>(
+    vertices: &[V],
+) -> (Vec<QuantizedVertex>, QuantizationBounds) {
+    let (min, max) = vertices.iter().fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), v| {
+            let p = v.position();
+            (min.min(p), max.max(p))
+        },
+    );
+    let (min, max) = if vertices.is_empty() {
+        (Vec3::ZERO, Vec3::ONE)
+    } else {
+        (min, max)
+    };
+    let scale = (max - min).max(Vec3::splat(f32::EPSILON));
+
+    let quantized = vertices
+        .iter()
+        .map(|v| {
+            let p = (v.position() - min) / scale;
+            let n = v.normal().normalize_or_zero();
+            let uv = v.uv();
+
+            QuantizedVertex {
+                position: [
+                    quantize_unorm(p.x, 16) as u16,
+                    quantize_unorm(p.y, 16) as u16,
+                    quantize_unorm(p.z, 16) as u16,
+                    0,
+                ],
+                normal: [
+                    quantize_snorm(n.x, 8) as i8,
+                    quantize_snorm(n.y, 8) as i8,
+                    quantize_snorm(n.z, 8) as i8,
+                    0,
+                ],
+                uv: [quantize_half(uv.x), quantize_half(uv.y)],
+            }
+        })
+        .collect();
+
+    (quantized, QuantizationBounds { offset: min, scale })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy)]
+    struct TestVertex {
+        position: Vec3,
+        normal: Vec3,
+        uv: Vec2,
+    }
+
+    impl MeshVertex for TestVertex {
+        fn position(&self) -> Vec3 {
+            self.position
+        }
+
+        fn normal(&self) -> Vec3 {
+            self.normal
+        }
+
+        fn uv(&self) -> Vec2 {
+            self.uv
+        }
+    }
+
+    #[test]
+    fn half_exact_values() {
+        assert_eq!(quantize_half(0.0), 0x0000);
+        assert_eq!(quantize_half(-0.0), 0x8000);
+        assert_eq!(quantize_half(1.0), 0x3c00);
+        assert_eq!(quantize_half(-2.0), 0xc000);
+        assert_eq!(quantize_half(0.5), 0x3800);
+        assert_eq!(quantize_half(65504.0), 0x7bff);
+    }
+
+    #[test]
+    fn half_rounding_carries_into_exponent() {
+        assert_eq!(quantize_half(f32::from_bits(2.0f32.to_bits() - 1)), 0x4000);
+        assert_eq!(quantize_half(1.9999), 0x4000);
+        assert_eq!(quantize_half(f32::from_bits(4.0f32.to_bits() - 1)), 0x4400);
+        assert_eq!(quantize_half(3.9999), 0x4400);
+    }
+
+    #[test]
+    fn half_overflow_and_special_values() {
+        assert_eq!(quantize_half(65520.0), 0x7c00);
+        assert_eq!(quantize_half(1.0e6), 0x7c00);
+        assert_eq!(quantize_half(f32::NEG_INFINITY), 0xfc00);
+        assert_eq!(quantize_half(f32::NAN) & 0x7c00, 0x7c00);
+        assert_ne!(quantize_half(f32::NAN) & 0x03ff, 0);
+        assert_eq!(quantize_half(1.0e-8), 0x0000);
+    }
+
+    #[test]
+    fn half_round_trip() {
+        for value in [0.25, 0.1, 1.5, 3.3, -7.75, 1000.0] {
+            let decoded = dequantize_half(quantize_half(value));
+            assert!((decoded - value).abs() <= value.abs() / 1024.0);
+        }
+        assert!(dequantize_half(0x7e00).is_nan());
+        assert_eq!(dequantize_half(0xfc00), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn unorm_and_snorm_ranges() {
+        assert_eq!(quantize_unorm(0.0, 16), 0);
+        assert_eq!(quantize_unorm(1.0, 16), 65535);
+        assert_eq!(quantize_unorm(2.0, 8), 255);
+        assert_eq!(quantize_unorm(0.5, 8), 128);
+        assert_eq!(quantize_snorm(1.0, 8), 127);
+        assert_eq!(quantize_snorm(-1.0, 8), -127);
+        assert_eq!(quantize_snorm(-3.0, 8), -127);
+        assert_eq!(quantize_snorm(0.0, 8), 0);
+    }
+
+    #[test]
+    fn vertices_round_trip_within_precision() {
+        let vertices = [
+            TestVertex {
+                position: Vec3::new(-1.0, 2.0, 10.0),
+                normal: Vec3::new(0.0, 3.0, 0.0),
+                uv: Vec2::new(0.0, 1.0),
+            },
+            TestVertex {
+                position: Vec3::new(3.0, -4.0, 10.5),
+                normal: Vec3::new(1.0, 1.0, 0.0),
+                uv: Vec2::new(0.3, 0.7),
+            },
+        ];
+
+        let (quantized, bounds) = quantize_vertices(&vertices);
+        let stats = QuantizationStats::measure(&vertices, &quantized, &bounds);
+
+        assert_eq!(quantized[0].decode_position(&bounds), vertices[0].position);
+        assert_eq!(stats.original_size, 64);
+        assert_eq!(stats.quantized_size, 32);
+        assert_eq!(stats.ratio(), 0.5);
+        assert!(stats.max_position_error < 1.0e-4);
+        assert!(stats.max_normal_error < 0.02);
+        assert!(stats.max_uv_error < 1.0e-3);
+    }
+
+    #[test]
+    fn empty_vertices_have_unit_bounds() {
+        let (quantized, bounds) = quantize_vertices::<TestVertex>(&[]);
+
+        assert!(quantized.is_empty());
+        assert_eq!(bounds.offset, Vec3::ZERO);
+        assert_eq!(bounds.scale, Vec3::ONE);
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+/// Index that ends a strip in a `UINT32` index buffer drawn with primitive restart.
+pub const PRIMITIVE_RESTART_INDEX: u32 = u32::MAX;
+
+/// Index counts of a triangle list and of the strips built from it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StripStats {
+    pub list_indices: usize,
+    /// Including one restart index between strips.
+    pub strip_indices: usize,
+    pub strips: usize,
+    pub triangles: usize,
+}
+
+impl StripStats {
+    /// Strip index count as a fraction of the list's.
+    pub fn ratio(&self) -> f32 {
+        self.strip_indices as f32 / self.list_indices.max(1) as f32
+    }
+
+    /// Average triangles per strip.
+    pub fn average_strip_length(&self) -> f32 {
+        self.triangles as f32 / self.strips.max(1) as f32
+    }
+}
+
+impl std::fmt::Display for StripStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> {} indices ({:.0}%), {} strips of {:.1} triangles on average",
+            self.list_indices,
+            self.strip_indices,
+            self.ratio() * 100.0,
+            self.strips,
+            self.average_strip_length()
+        )
+    }
+}
+
+fn is_degenerate([a, b, c]: [u32; 3]) -> bool {
+    a == b || b == c || a == c
+}
+
+/// Triangle `i` of a strip, wound like the list triangle it came from: Vulkan flips every
+/// odd triangle to keep the strip's winding consistent.
+fn strip_triangle(strip: &[u32], i: usize) -> [u32; 3] {
+    if i.is_multiple_of(2) {
+        [strip[i], strip[i + 1], strip[i + 2]]
+    } else {
+        [strip[i], strip[i + 2], strip[i + 1]]
+    }
+}
+
+/// Converts a triangle list into strips separated by `PRIMITIVE_RESTART_INDEX`, to draw with
+/// `TRIANGLE_STRIP` and `with_primitive_restart(true)`. Winding is preserved; degenerate
+/// triangles are dropped. Regular meshes like terrain grids need about one index per
+/// triangle instead of three.
+///
+/// Strips are grown greedily from the first unused triangle, so the triangle order, and with
+/// it vertex cache behavior, changes.
+pub fn stripify(indices: &[u32]) -> (Vec<u32>, StripStats) {
+    let triangles: Vec<[u32; 3]> = indices
+        .chunks_exact(3)
+        .map(|t| [t[0], t[1], t[2]])
+        .filter(|&t| !is_degenerate(t))
+        .collect();
+
+    // Directed edge, in winding order, to the triangles that have it.
+    let mut edges: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+    for (index, &[a, b, c]) in triangles.iter().enumerate() {
+        for edge in [(a, b), (b, c), (c, a)] {
+            edges.entry(edge).or_default().push(index);
+        }
+    }
+
+    let mut used = vec![false; triangles.len()];
+    // Third vertex of an unused triangle continuing the strip over directed edge `edge`.
+    let next = |used: &[bool], edge: (u32, u32)| {
+        edges.get(&edge).and_then(|candidates| {
+            candidates.iter().find(|&&t| !used[t]).map(|&t| {
+                let triangle = triangles[t];
+                let third = triangle
+                    .into_iter()
+                    .find(|&v| v != edge.0 && v != edge.1)
+                    .unwrap();
+                (t, third)
+            })
+        })
+    };
+
+    let mut strip = Vec::new();
+    let mut stats = StripStats {
+        list_indices: indices.len(),
+        triangles: triangles.len(),
+        ..Default::default()
+    };
+    for start in 0..triangles.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+
+        // Start with the rotation whose last edge can be continued, if any. The second
+        // triangle of a strip is odd, so it must have the edge reversed.
+        let [a, b, c] = triangles[start];
+        let rotations = [[a, b, c], [b, c, a], [c, a, b]];
+        let first = rotations
+            .into_iter()
+            .find(|&[_, b, c]| next(&used, (c, b)).is_some())
+            .unwrap_or(rotations[0]);
+
+        if !strip.is_empty() {
+            strip.push(PRIMITIVE_RESTART_INDEX);
+        }
+        let strip_start = strip.len();
+        strip.extend_from_slice(&first);
+        stats.strips += 1;
+
+        loop {
+            let len = strip.len();
+            let (p, q) = (strip[len - 2], strip[len - 1]);
+            // Triangle `len - 2` is added; odd ones contain the shared edge reversed.
+            let edge = if (len - 2 - strip_start).is_multiple_of(2) {
+                (p, q)
+            } else {
+                (q, p)
+            };
+            let Some((t, third)) = next(&used, edge) else {
+                break;
+            };
+            used[t] = true;
+            strip.push(third);
+        }
+    }
+
+    stats.strip_indices = strip.len();
+    (strip, stats)
+}
+
+/// Expands strips separated by `PRIMITIVE_RESTART_INDEX` back into a triangle list, skipping
+/// degenerate triangles.
+pub fn unstripify(strip: &[u32]) -> Vec<u32> {
+    strip
+        .split(|&index| index == PRIMITIVE_RESTART_INDEX)
+        .flat_map(|segment| {
+            (0..segment.len().saturating_sub(2))
+                .map(move |i| strip_triangle(segment, i))
+                .filter(|&t| !is_degenerate(t))
+        })
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Triangles rotated to start at their smallest index and sorted, for comparing
+    /// lists regardless of where each triangle starts.
+    fn canonical(indices: &[u32]) -> Vec<[u32; 3]> {
+        let mut triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|t| {
+                let mut t = [t[0], t[1], t[2]];
+                let min = (0..3).min_by_key(|&i| t[i]).unwrap();
+                t.rotate_left(min);
+                t
+            })
+            .collect();
+        triangles.sort();
+        triangles
+    }
+
+    fn grid(size: u32) -> Vec<u32> {
+        let mut indices = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let i = y * (size + 1) + x;
+                let below = i + size + 1;
+                indices.extend_from_slice(&[i, below, i + 1, i + 1, below, below + 1]);
+            }
+        }
+        indices
+    }
+
+    #[test]
+    fn strips_keep_triangles_and_winding() {
+        let indices = grid(16);
+        let (strip, stats) = stripify(&indices);
+
+        assert_eq!(canonical(&unstripify(&strip)), canonical(&indices));
+        assert_eq!(stats.triangles, 512);
+        assert_eq!(stats.strip_indices, strip.len());
+        assert_eq!(
+            strip
+                .iter()
+                .filter(|&&i| i == PRIMITIVE_RESTART_INDEX)
+                .count(),
+            stats.strips - 1
+        );
+        // A row of a grid becomes one strip.
+        assert!(stats.ratio() < 0.4, "{}", stats);
+    }
+
+    #[test]
+    fn drops_degenerate_triangles() {
+        let indices = [0, 1, 2, 2, 1, 3, 4, 4, 5];
+        let (strip, stats) = stripify(&indices);
+
+        assert_eq!(strip, vec![0, 1, 2, 3]);
+        assert_eq!((stats.triangles, stats.strips), (2, 1));
+        assert_eq!(canonical(&unstripify(&strip)), canonical(&indices[..6]));
+        assert_eq!(stripify(&[]).0, Vec::<u32>::new());
+    }
+}
@@ -0,0 +1,136 @@
+use glam::{Vec2, Vec3};
+
+use crate::geometry::MeshVertex;
+
+/// Per-vertex tangents for normal mapping, in the MikkTSpace convention the common bakers use:
+/// xyz is the tangent along +U, orthogonalized against the vertex normal, and w the sign such
+/// that `cross(normal, tangent) * w` points along +V.
+///
+/// Like MikkTSpace, every triangle contributes its UV-gradient tangent and bitangent,
+/// projected onto the vertex's tangent plane and weighted by the triangle's angle at that
+/// corner. MikkTSpace additionally splits vertices whose triangles disagree on handedness;
+/// here such vertices keep the majority sign, which only matters on mirrored UV islands that
+/// share vertices. Triangles with degenerate UVs are skipped, and vertices left without a
+/// tangent get an arbitrary one perpendicular to the normal.
+pub fn generate_tangents<V: MeshVertex>(vertices: &[V], indices: &[u32]) -> Vec<[f32; 4]> {
+    let mut tangents = vec![Vec3::ZERO; vertices.len()];
+    let mut bitangents = vec![Vec3::ZERO; vertices.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let corners = [0, 1, 2].map(|i| &vertices[triangle[i] as usize]);
+        let positions = corners.map(MeshVertex::position);
+        let uvs = corners.map(MeshVertex::uv);
+
+        let edges = [positions[1] - positions[0], positions[2] - positions[0]];
+        let uv_edges: [Vec2; 2] = [uvs[1] - uvs[0], uvs[2] - uvs[0]];
+        let det = uv_edges[0].x * uv_edges[1].y - uv_edges[1].x * uv_edges[0].y;
+        if det.abs() < 1e-12 {
+            continue;
+        }
+        let face_tangent = (edges[0] * uv_edges[1].y - edges[1] * uv_edges[0].y) / det;
+        let face_bitangent = (edges[1] * uv_edges[0].x - edges[0] * uv_edges[1].x) / det;
+
+        for corner in 0..3 {
+            let index = triangle[corner] as usize;
+            let to_next = positions[(corner + 1) % 3] - positions[corner];
+            let to_previous = positions[(corner + 2) % 3] - positions[corner];
+            let angle = to_next.angle_between(to_previous);
+            if !angle.is_finite() {
+                continue;
+            }
+
+            let normal = corners[corner].normal();
+            let project = |v: Vec3| (v - normal * normal.dot(v)).normalize_or_zero();
+            tangents[index] += project(face_tangent) * angle;
+            bitangents[index] += project(face_bitangent) * angle;
+        }
+    }
+
+    vertices
+        .iter()
+        .zip(tangents.iter().zip(&bitangents))
+        .map(|(vertex, (&tangent, &bitangent))| {
+            let normal = vertex.normal();
+            let tangent = (tangent - normal * normal.dot(tangent))
+                .try_normalize()
+                .unwrap_or_else(|| normal.any_orthonormal_vector());
+            let sign = if normal.cross(tangent).dot(bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            tangent.extend(sign).to_array()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{sphere, torus};
+    use crate::renderer::GpuVertex;
+
+    fn quad(uvs: [[f32; 2]; 4]) -> (Vec<GpuVertex>, Vec<u32>) {
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let vertices = positions
+            .iter()
+            .zip(uvs)
+            .map(|(&position, uv)| GpuVertex {
+                position,
+                normal: [0.0, 0.0, 1.0],
+                uv,
+            })
+            .collect();
+        (vertices, vec![0, 1, 2, 0, 2, 3])
+    }
+
+    #[test]
+    fn follows_uv_direction_and_mirroring() {
+        let (vertices, indices) = quad([[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+        for tangent in generate_tangents(&vertices, &indices) {
+            assert_eq!(tangent, [1.0, 0.0, 0.0, 1.0]);
+        }
+
+        // V flipped, as with textures authored with the origin at the top-left.
+        let (vertices, indices) = quad([[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]);
+        for tangent in generate_tangents(&vertices, &indices) {
+            assert_eq!(tangent, [1.0, 0.0, 0.0, -1.0]);
+        }
+
+        // U flipped.
+        let (vertices, indices) = quad([[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]]);
+        for tangent in generate_tangents(&vertices, &indices) {
+            assert_eq!(tangent, [-1.0, 0.0, 0.0, -1.0]);
+        }
+    }
+
+    #[test]
+    fn degenerate_uvs_fall_back_to_a_perpendicular_tangent() {
+        let (vertices, indices) = quad([[0.5, 0.5]; 4]);
+        for tangent in generate_tangents(&vertices, &indices) {
+            assert!(Vec3::from_slice(&tangent).dot(Vec3::Z).abs() < 1e-6);
+            assert!((Vec3::from_slice(&tangent).length() - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn matches_analytic_tangents_of_smooth_primitives() {
+        for mesh in [sphere(1.0, 32, 16), torus(1.0, 0.3, 32, 16)] {
+            let generated = generate_tangents(&mesh.vertices, &mesh.indices);
+            for (index, (generated, analytic)) in generated.iter().zip(&mesh.tangents).enumerate() {
+                // The poles of the sphere have no meaningful tangent direction.
+                if mesh.vertices[index].normal[1].abs() > 0.99 {
+                    continue;
+                }
+                let alignment = Vec3::from_slice(generated).dot(Vec3::from_slice(analytic));
+                assert!(alignment > 0.99, "vertex {}: {}", index, alignment);
+                assert_eq!(generated[3], analytic[3]);
+            }
+        }
+    }
+}
@@ -0,0 +1,489 @@
+use ash::vk;
+use glam::{Vec2, Vec3};
+
+use crate::geometry::{
+    DecodedVertex, MeshVertex, QuantizationBounds, QuantizationStats, dequantize_half, pack_half,
+    pack_snorm16, pack_unorm16, quantize_snorm,
+};
+
+/// Maps a unit vector to [-1, 1]^2 by projecting it on an octahedron and unfolding the lower
+/// half over the corners. Two components keep the error even over the whole sphere, unlike
+/// dropping z or storing xyz at lower precision.
+pub fn octahedral_encode(normal: Vec3) -> Vec2 {
+    let sum = normal.abs().element_sum();
+    if sum == 0.0 {
+        return Vec2::ZERO;
+    }
+    let n = normal / sum;
+    if n.z >= 0.0 {
+        n.truncate()
+    } else {
+        let sign = Vec2::new(
+            if n.x >= 0.0 { 1.0 } else { -1.0 },
+            if n.y >= 0.0 { 1.0 } else { -1.0 },
+        );
+        (Vec2::ONE - Vec2::new(n.y, n.x).abs()) * sign
+    }
+}
+
+/// Inverse of `octahedral_encode`, as `octahedral_decode` in `shaders/vertex_compression.glsl`.
+pub fn octahedral_decode(encoded: Vec2) -> Vec3 {
+    let mut n = Vec3::new(
+        encoded.x,
+        encoded.y,
+        1.0 - encoded.x.abs() - encoded.y.abs(),
+    );
+    let fold = (-n.z).max(0.0);
+    n.x += if n.x >= 0.0 { -fold } else { fold };
+    n.y += if n.y >= 0.0 { -fold } else { fold };
+    n.normalize_or_zero()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Float32,
+    /// Half floats; precision drops with the distance from the origin.
+    Half,
+    /// 16-bit unorm relative to the mesh bounds, decoded with `CompressedVertices::bounds`.
+    Quantized16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalEncoding {
+    Float32,
+    /// xyz as 8-bit snorm.
+    Snorm8,
+    /// Octahedral-encoded as two 8-bit snorm values.
+    Octahedral8,
+    /// Octahedral-encoded as two 16-bit snorm values.
+    Octahedral16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UvEncoding {
+    Float32,
+    Half,
+    /// 16-bit unorm, clamped to [0, 1]: only for meshes without tiling UVs.
+    Unorm16,
+}
+
+impl PositionEncoding {
+    fn format(self) -> vk::Format {
+        match self {
+            Self::Float32 => vk::Format::R32G32B32_SFLOAT,
+            Self::Half => vk::Format::R16G16B16A16_SFLOAT,
+            Self::Quantized16 => vk::Format::R16G16B16A16_UNORM,
+        }
+    }
+
+    fn size(self) -> u32 {
+        match self {
+            Self::Float32 => 12,
+            Self::Half | Self::Quantized16 => 8,
+        }
+    }
+}
+
+impl NormalEncoding {
+    fn format(self) -> vk::Format {
+        match self {
+            Self::Float32 => vk::Format::R32G32B32_SFLOAT,
+            Self::Snorm8 => vk::Format::R8G8B8A8_SNORM,
+            Self::Octahedral8 => vk::Format::R8G8_SNORM,
+            Self::Octahedral16 => vk::Format::R16G16_SNORM,
+        }
+    }
+
+    /// Octahedral normals are padded to 4 bytes to keep the next attribute aligned.
+    fn size(self) -> u32 {
+        match self {
+            Self::Float32 => 12,
+            Self::Snorm8 | Self::Octahedral8 | Self::Octahedral16 => 4,
+        }
+    }
+}
+
+impl UvEncoding {
+    fn format(self) -> vk::Format {
+        match self {
+            Self::Float32 => vk::Format::R32G32_SFLOAT,
+            Self::Half => vk::Format::R16G16_SFLOAT,
+            Self::Unorm16 => vk::Format::R16G16_UNORM,
+        }
+    }
+
+    fn size(self) -> u32 {
+        match self {
+            Self::Float32 => 8,
+            Self::Half | Self::Unorm16 => 4,
+        }
+    }
+}
+
+/// How each vertex attribute is stored on import. The default, quantized positions,
+/// 16-bit octahedral normals and half-float UVs, takes 16 bytes per vertex instead of 32
+/// and keeps tiling UVs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexCompression {
+    pub position: PositionEncoding,
+    pub normal: NormalEncoding,
+    pub uv: UvEncoding,
+}
+
+impl Default for VertexCompression {
+    fn default() -> Self {
+        Self {
+            position: PositionEncoding::Quantized16,
+            normal: NormalEncoding::Octahedral16,
+            uv: UvEncoding::Half,
+        }
+    }
+}
+
+impl VertexCompression {
+    /// Full precision, to compare compressed meshes against.
+    pub fn none() -> Self {
+        Self {
+            position: PositionEncoding::Float32,
+            normal: NormalEncoding::Float32,
+            uv: UvEncoding::Float32,
+        }
+    }
+
+    pub fn with_position(mut self, encoding: PositionEncoding) -> Self {
+        self.position = encoding;
+        self
+    }
+
+    pub fn with_normal(mut self, encoding: NormalEncoding) -> Self {
+        self.normal = encoding;
+        self
+    }
+
+    pub fn with_uv(mut self, encoding: UvEncoding) -> Self {
+        self.uv = encoding;
+        self
+    }
+
+    /// Bytes per vertex.
+    pub fn stride(&self) -> u32 {
+        self.position.size() + self.normal.size() + self.uv.size()
+    }
+
+    /// Encodes `vertices` and measures the error against the originals.
+    pub fn compress<V: MeshVertex>(&self, vertices: &[V]) -> CompressedVertices {
+        let bounds = match self.position {
+            PositionEncoding::Quantized16 => mesh_bounds(vertices),
+            PositionEncoding::Float32 | PositionEncoding::Half => QuantizationBounds {
+                offset: Vec3::ZERO,
+                scale: Vec3::ONE,
+            },
+        };
+
+        let mut data = Vec::with_capacity(vertices.len() * self.stride() as usize);
+        for vertex in vertices {
+            self.encode_position(vertex.position(), &bounds, &mut data);
+            self.encode_normal(vertex.normal(), &mut data);
+            self.encode_uv(vertex.uv(), &mut data);
+        }
+
+        let mut compressed = CompressedVertices {
+            data,
+            compression: *self,
+            bounds,
+            report: QuantizationStats::default(),
+        };
+        compressed.report =
+            QuantizationStats::compare(vertices, &compressed.decode(), compressed.data.len());
+        compressed
+    }
+
+    fn encode_position(&self, position: Vec3, bounds: &QuantizationBounds, data: &mut Vec<u8>) {
+        match self.position {
+            PositionEncoding::Float32 => extend_f32(data, &position.to_array()),
+            PositionEncoding::Half => extend_u16(data, &pack_half(position.extend(1.0).to_array())),
+            PositionEncoding::Quantized16 => {
+                let unorm = (position - bounds.offset) / bounds.scale;
+                extend_u16(data, &pack_unorm16(unorm.extend(0.0).to_array()));
+            }
+        }
+    }
+
+    fn encode_normal(&self, normal: Vec3, data: &mut Vec<u8>) {
+        let normal = normal.normalize_or_zero();
+        match self.normal {
+            NormalEncoding::Float32 => extend_f32(data, &normal.to_array()),
+            NormalEncoding::Snorm8 => {
+                let packed = normal
+                    .extend(0.0)
+                    .to_array()
+                    .map(|value| quantize_snorm(value, 8) as i8 as u8);
+                data.extend_from_slice(&packed);
+            }
+            NormalEncoding::Octahedral8 => {
+                let encoded = octahedral_encode(normal);
+                data.extend_from_slice(&[
+                    quantize_snorm(encoded.x, 8) as i8 as u8,
+                    quantize_snorm(encoded.y, 8) as i8 as u8,
+                    0,
+                    0,
+                ]);
+            }
+            NormalEncoding::Octahedral16 => {
+                let encoded = pack_snorm16(octahedral_encode(normal).to_array());
+                extend_u16(data, &encoded.map(|value| value as u16));
+            }
+        }
+    }
+
+    fn encode_uv(&self, uv: Vec2, data: &mut Vec<u8>) {
+        match self.uv {
+            UvEncoding::Float32 => extend_f32(data, &uv.to_array()),
+            UvEncoding::Half => extend_u16(data, &pack_half(uv.to_array())),
+            UvEncoding::Unorm16 => extend_u16(data, &pack_unorm16(uv.to_array())),
+        }
+    }
+}
+
+fn extend_f32(data: &mut Vec<u8>, values: &[f32]) {
+    data.extend(values.iter().flat_map(|value| value.to_le_bytes()));
+}
+
+fn extend_u16(data: &mut Vec<u8>, values: &[u16]) {
+    data.extend(values.iter().flat_map(|value| value.to_le_bytes()));
+}
+
+fn mesh_bounds<V: MeshVertex>(vertices: &[V]) -> QuantizationBounds {
+    if vertices.is_empty() {
+        return QuantizationBounds {
+            offset: Vec3::ZERO,
+            scale: Vec3::ONE,
+        };
+    }
+    let (min, max) = vertices.iter().fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), vertex| (min.min(vertex.position()), max.max(vertex.position())),
+    );
+    QuantizationBounds {
+        offset: min,
+        scale: (max - min).max(Vec3::splat(f32::EPSILON)),
+    }
+}
+
+/// Little-endian reader over one compressed vertex.
+struct VertexReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl VertexReader<'_> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let (head, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        head.try_into().unwrap()
+    }
+
+    fn f32(&mut self) -> f32 {
+        f32::from_le_bytes(self.take())
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.take())
+    }
+
+    fn i8(&mut self) -> f32 {
+        (i8::from_le_bytes(self.take()) as f32 / i8::MAX as f32).max(-1.0)
+    }
+
+    fn vec3(&mut self) -> Vec3 {
+        Vec3::new(self.f32(), self.f32(), self.f32())
+    }
+}
+
+/// A vertex stream encoded with a `VertexCompression`, ready for upload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedVertices {
+    pub data: Vec<u8>,
+    pub compression: VertexCompression,
+    /// Decodes `Quantized16` positions: fold into the model matrix as
+    /// `model * translation(offset) * scale(scale)`. Identity for the other encodings.
+    pub bounds: QuantizationBounds,
+    /// Size and precision compared to the source vertices.
+    pub report: QuantizationStats,
+}
+
+impl CompressedVertices {
+    pub fn len(&self) -> usize {
+        self.data.len() / self.compression.stride() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn binding_description(&self, binding: u32) -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription::default()
+            .binding(binding)
+            .stride(self.compression.stride())
+            .input_rate(vk::VertexInputRate::VERTEX)
+    }
+
+    /// Locations 0..=2: position, normal, uv, matching the layout of `GpuVertex`. Shaders
+    /// decode quantized positions and octahedral normals with
+    /// `shaders/vertex_compression.glsl`.
+    pub fn attribute_descriptions(&self, binding: u32) -> [vk::VertexInputAttributeDescription; 3] {
+        let compression = &self.compression;
+        let attribute = |location: u32, format: vk::Format, offset: u32| {
+            vk::VertexInputAttributeDescription::default()
+                .binding(binding)
+                .location(location)
+                .format(format)
+                .offset(offset)
+        };
+
+        let normal_offset = compression.position.size();
+        let uv_offset = normal_offset + compression.normal.size();
+        [
+            attribute(0, compression.position.format(), 0),
+            attribute(1, compression.normal.format(), normal_offset),
+            attribute(2, compression.uv.format(), uv_offset),
+        ]
+    }
+
+    /// Reads every vertex back at full precision, as the vertex shader sees it.
+    pub fn decode(&self) -> Vec<DecodedVertex> {
+        let compression = self.compression;
+        self.data
+            .chunks_exact(compression.stride() as usize)
+            .map(|bytes| {
+                let mut reader = VertexReader { bytes };
+                let position = match compression.position {
+                    PositionEncoding::Float32 => reader.vec3(),
+                    PositionEncoding::Half => {
+                        let half = [reader.u16(), reader.u16(), reader.u16(), reader.u16()];
+                        Vec3::new(
+                            dequantize_half(half[0]),
+                            dequantize_half(half[1]),
+                            dequantize_half(half[2]),
+                        )
+                    }
+                    PositionEncoding::Quantized16 => {
+                        let unorm = [reader.u16(), reader.u16(), reader.u16(), reader.u16()];
+                        let unorm = Vec3::new(unorm[0] as f32, unorm[1] as f32, unorm[2] as f32)
+                            / u16::MAX as f32;
+                        self.bounds.offset + unorm * self.bounds.scale
+                    }
+                };
+                let normal = match compression.normal {
+                    NormalEncoding::Float32 => reader.vec3(),
+                    NormalEncoding::Snorm8 => {
+                        let snorm = [reader.i8(), reader.i8(), reader.i8(), reader.i8()];
+                        Vec3::new(snorm[0], snorm[1], snorm[2])
+                    }
+                    NormalEncoding::Octahedral8 => {
+                        let snorm = [reader.i8(), reader.i8(), reader.i8(), reader.i8()];
+                        octahedral_decode(Vec2::new(snorm[0], snorm[1]))
+                    }
+                    NormalEncoding::Octahedral16 => {
+                        let snorm = [reader.u16(), reader.u16()]
+                            .map(|value| (value as i16 as f32 / i16::MAX as f32).max(-1.0));
+                        octahedral_decode(Vec2::from(snorm))
+                    }
+                };
+                let uv = match compression.uv {
+                    UvEncoding::Float32 => Vec2::new(reader.f32(), reader.f32()),
+                    UvEncoding::Half => {
+                        Vec2::new(dequantize_half(reader.u16()), dequantize_half(reader.u16()))
+                    }
+                    UvEncoding::Unorm16 => {
+                        Vec2::new(reader.u16() as f32, reader.u16() as f32) / u16::MAX as f32
+                    }
+                };
+                DecodedVertex {
+                    position,
+                    normal,
+                    uv,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sphere_vertices() -> Vec<DecodedVertex> {
+        let mut vertices = Vec::new();
+        for i in 0..16 {
+            for j in 0..9 {
+                let (theta, phi) = (
+                    i as f32 / 16.0 * std::f32::consts::TAU,
+                    j as f32 / 8.0 * std::f32::consts::PI,
+                );
+                let normal = Vec3::new(phi.sin() * theta.cos(), phi.sin() * theta.sin(), phi.cos());
+                vertices.push(DecodedVertex {
+                    position: Vec3::new(5.0, -2.0, 40.0) + normal * 3.0,
+                    normal,
+                    uv: Vec2::new(i as f32 / 16.0, j as f32 / 8.0),
+                });
+            }
+        }
+        vertices
+    }
+
+    #[test]
+    fn octahedral_normals_round_trip() {
+        for normal in sphere_vertices().iter().map(|vertex| vertex.normal) {
+            let decoded = octahedral_decode(octahedral_encode(normal));
+            assert!(decoded.distance(normal) < 1.0e-5, "{} {}", normal, decoded);
+        }
+        assert_eq!(octahedral_encode(Vec3::ZERO), Vec2::ZERO);
+    }
+
+    #[test]
+    fn compresses_with_matching_layout_and_report() {
+        let vertices = sphere_vertices();
+        let compressed = VertexCompression::default().compress(&vertices);
+
+        assert_eq!(compressed.compression.stride(), 16);
+        assert_eq!(compressed.len(), vertices.len());
+        assert_eq!(compressed.report.quantized_size, vertices.len() * 16);
+        assert_eq!(compressed.report.ratio(), 0.5);
+        assert!(compressed.report.max_position_error < 1.0e-3);
+        assert!(compressed.report.max_normal_error < 1.0e-3);
+        assert!(compressed.report.max_uv_error < 1.0e-3);
+
+        let attributes = compressed.attribute_descriptions(0);
+        assert_eq!(
+            attributes.map(|attribute| (attribute.format, attribute.offset)),
+            [
+                (vk::Format::R16G16B16A16_UNORM, 0),
+                (vk::Format::R16G16_SNORM, 8),
+                (vk::Format::R16G16_SFLOAT, 12),
+            ]
+        );
+    }
+
+    #[test]
+    fn every_encoding_decodes() {
+        let vertices = sphere_vertices();
+        let uncompressed = VertexCompression::none().compress(&vertices);
+        assert_eq!(uncompressed.report.max_position_error, 0.0);
+        assert_eq!(uncompressed.report.ratio(), 1.0);
+
+        for (normal, max_normal_error) in [
+            (NormalEncoding::Snorm8, 0.02),
+            (NormalEncoding::Octahedral8, 0.02),
+        ] {
+            let compressed = VertexCompression::default()
+                .with_position(PositionEncoding::Half)
+                .with_normal(normal)
+                .with_uv(UvEncoding::Unorm16)
+                .compress(&vertices);
+            let report = compressed.report;
+            assert!(report.max_position_error < 0.05, "{}", report);
+            assert!(report.max_normal_error < max_normal_error, "{}", report);
+            assert!(report.max_uv_error < 1.0e-4, "{}", report);
+        }
+    }
+}
@@ -0,0 +1,442 @@
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+type Job = Box<dyn FnOnce(usize) + Send + 'static>;
+
+/// Timing of one finished job, reported to the `JobProfiler`.
+#[derive(Debug, Clone, Copy)]
+pub struct JobTiming {
+    pub name: &'static str,
+    /// Worker index, or `JobSystem::worker_count()` when a waiting caller ran the job.
+    pub worker: usize,
+    pub start: Instant,
+    pub duration: Duration,
+}
+
+/// Receives a `JobTiming` for every job, from the thread that ran it.
+pub trait JobProfiler: Send + Sync {
+    fn job_finished(&self, timing: &JobTiming);
+}
+
+/// Profiler that keeps every timing until `take`, e.g. once per frame for a timeline view.
+#[derive(Default)]
+pub struct JobTimeline {
+    timings: Mutex<Vec<JobTiming>>,
+}
+
+impl JobTimeline {
+    pub fn take(&self) -> Vec<JobTiming> {
+        std::mem::take(&mut *self.timings.lock().unwrap())
+    }
+
+    /// Summed duration per job name, sorted by name.
+    pub fn totals(&self) -> Vec<(&'static str, Duration)> {
+        let mut totals: Vec<(&'static str, Duration)> = Vec::new();
+        for timing in self.timings.lock().unwrap().iter() {
+            match totals.iter_mut().find(|(name, _)| *name == timing.name) {
+                Some((_, total)) => *total += timing.duration,
+                None => totals.push((timing.name, timing.duration)),
+            }
+        }
+        totals.sort_by_key(|(name, _)| *name);
+        totals
+    }
+}
+
+impl JobProfiler for JobTimeline {
+    fn job_finished(&self, timing: &JobTiming) {
+        self.timings.lock().unwrap().push(*timing);
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    jobs: VecDeque<Job>,
+    shutdown: bool,
+}
+
+#[derive(Default)]
+struct JobQueue {
+    state: Mutex<QueueState>,
+    available: Condvar,
+}
+
+struct Latch {
+    remaining: Mutex<usize>,
+    done: Condvar,
+}
+
+impl Latch {
+    fn new(count: usize) -> Self {
+        Self {
+            remaining: Mutex::new(count),
+            done: Condvar::new(),
+        }
+    }
+
+    fn count_down(&self) {
+        let mut remaining = self.remaining.lock().unwrap();
+        *remaining -= 1;
+        if *remaining == 0 {
+            self.done.notify_all();
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        *self.remaining.lock().unwrap() == 0
+    }
+
+    fn wait_timeout(&self, timeout: Duration) {
+        let remaining = self.remaining.lock().unwrap();
+        if *remaining > 0 {
+            let _ = self.done.wait_timeout(remaining, timeout).unwrap();
+        }
+    }
+}
+
+/// Handle to a job started with `JobSystem::spawn`.
+pub struct JobHandle {
+    name: &'static str,
+    latch: Arc<Latch>,
+    panicked: Arc<AtomicBool>,
+}
+
+impl JobHandle {
+    pub fn is_finished(&self) -> bool {
+        self.latch.is_done()
+    }
+
+    /// Blocks until the job has run. Fails if it panicked. Unlike `parallel_for`, this does not
+    /// run other jobs while waiting, so don't wait on a handle from inside a job.
+    pub fn wait(self) -> Result<()> {
+        while !self.latch.is_done() {
+            self.latch.wait_timeout(Duration::from_millis(10));
+        }
+
+        if self.panicked.load(Ordering::Acquire) {
+            return Err(anyhow::anyhow!("Job '{}' panicked", self.name));
+        }
+        Ok(())
+    }
+}
+
+/// Fixed pool of worker threads shared by frame work and asset streaming.
+///
+/// Jobs run in submission order on whichever worker is free. Blocking calls (`parallel_for`,
+/// `TaskGraph::run`) run queued jobs on the calling thread while they wait, so they may be
+/// nested inside jobs without deadlocking the pool.
+pub struct JobSystem {
+    queue: Arc<JobQueue>,
+    profiler: RwLock<Option<Arc<dyn JobProfiler>>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl JobSystem {
+    pub fn new(worker_count: usize) -> Result<Self> {
+        let queue = Arc::new(JobQueue::default());
+
+        let workers = (0..worker_count.max(1))
+            .map(|index| {
+                let queue = queue.clone();
+                std::thread::Builder::new()
+                    .name(format!("job-worker-{}", index))
+                    .spawn(move || worker_loop(&queue, index))
+                    .map_err(|e| anyhow::anyhow!("Failed to spawn job worker: {}", e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            queue,
+            profiler: RwLock::new(None),
+            workers,
+        })
+    }
+
+    /// One worker per available core, leaving one for the render thread.
+    pub fn with_default_workers() -> Result<Self> {
+        let cores = std::thread::available_parallelism().map_or(2, |n| n.get());
+        Self::new(cores.saturating_sub(1).max(1))
+    }
+
+    pub fn worker_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Installs a profiler for jobs submitted from now on, or removes it with `None`.
+    pub fn set_profiler(&self, profiler: Option<Arc<dyn JobProfiler>>) {
+        *self.profiler.write().unwrap() = profiler;
+    }
+
+    /// Runs `job` on a worker.
+    pub fn spawn(&self, name: &'static str, job: impl FnOnce() + Send + 'static) -> JobHandle {
+        let latch = Arc::new(Latch::new(1));
+        let panicked = Arc::new(AtomicBool::new(false));
+
+        let done_latch = latch.clone();
+        let done_panicked = panicked.clone();
+        self.enqueue(
+            name,
+            Box::new(job),
+            Box::new(move |did_panic| {
+                done_panicked.store(did_panic, Ordering::Release);
+                done_latch.count_down();
+            }),
+        );
+
+        JobHandle {
+            name,
+            latch,
+            panicked,
+        }
+    }
+
+    /// Calls `f(first_index, chunk)` for every `chunk_size` slice of `items` in parallel and
+    /// returns once all chunks are done. Fails if any chunk panicked.
+    pub fn parallel_for<T: Sync>(
+        &self,
+        name: &'static str,
+        items: &[T],
+        chunk_size: usize,
+        f: impl Fn(usize, &[T]) + Sync,
+    ) -> Result<()> {
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let chunk_size = chunk_size.max(1);
+        let latch = Arc::new(Latch::new(items.len().div_ceil(chunk_size)));
+        let panicked = Arc::new(AtomicBool::new(false));
+        let f = &f;
+
+        for (chunk_index, chunk) in items.chunks(chunk_size).enumerate() {
+            let job: Box<dyn FnOnce() + Send + '_> =
+                Box::new(move || f(chunk_index * chunk_size, chunk));
+            // SAFETY: the job borrows `items` and `f`, and this function does not return
+            // before the latch reports every job as finished, panicking or not.
+            let job: Box<dyn FnOnce() + Send + 'static> = unsafe { std::mem::transmute(job) };
+
+            let latch = latch.clone();
+            let panicked = panicked.clone();
+            self.enqueue(
+                name,
+                job,
+                Box::new(move |did_panic| {
+                    if did_panic {
+                        panicked.store(true, Ordering::Release);
+                    }
+                    latch.count_down();
+                }),
+            );
+        }
+
+        while !latch.is_done() {
+            if !self.help() {
+                latch.wait_timeout(Duration::from_millis(1));
+            }
+        }
+
+        if panicked.load(Ordering::Acquire) {
+            return Err(anyhow::anyhow!("Job '{}' panicked", name));
+        }
+        Ok(())
+    }
+
+    /// Maps `items` in parallel, keeping their order.
+    pub fn parallel_map<T: Sync, R: Send>(
+        &self,
+        name: &'static str,
+        items: &[T],
+        chunk_size: usize,
+        f: impl Fn(&T) -> R + Sync,
+    ) -> Result<Vec<R>> {
+        let chunks = Mutex::new(Vec::new());
+        self.parallel_for(name, items, chunk_size, |first, chunk| {
+            let mapped: Vec<R> = chunk.iter().map(&f).collect();
+            chunks.lock().unwrap().push((first, mapped));
+        })?;
+
+        let mut chunks = chunks.into_inner().unwrap();
+        chunks.sort_by_key(|(first, _)| *first);
+        Ok(chunks.into_iter().flat_map(|(_, mapped)| mapped).collect())
+    }
+
+    /// Queues `job`; `done` runs right after it on the same thread with whether it panicked.
+    pub(crate) fn enqueue(
+        &self,
+        name: &'static str,
+        job: Box<dyn FnOnce() + Send + 'static>,
+        done: Box<dyn FnOnce(bool) + Send + 'static>,
+    ) {
+        let profiler = self.profiler.read().unwrap().clone();
+        let job: Job = Box::new(move |worker| {
+            let start = Instant::now();
+            let did_panic = std::panic::catch_unwind(AssertUnwindSafe(job)).is_err();
+            if let Some(profiler) = profiler {
+                profiler.job_finished(&JobTiming {
+                    name,
+                    worker,
+                    start,
+                    duration: start.elapsed(),
+                });
+            }
+            done(did_panic);
+        });
+
+        self.queue.state.lock().unwrap().jobs.push_back(job);
+        self.queue.available.notify_one();
+    }
+
+    /// Runs one queued job on the calling thread. Returns false if the queue was empty.
+    pub(crate) fn help(&self) -> bool {
+        let job = self.queue.state.lock().unwrap().jobs.pop_front();
+        match job {
+            Some(job) => {
+                job(self.worker_count());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Drop for JobSystem {
+    fn drop(&mut self) {
+        self.queue.state.lock().unwrap().shutdown = true;
+        self.queue.available.notify_all();
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn worker_loop(queue: &JobQueue, index: usize) {
+    loop {
+        let job = {
+            let mut state = queue.state.lock().unwrap();
+            loop {
+                if let Some(job) = state.jobs.pop_front() {
+                    break job;
+                }
+                if state.shutdown {
+                    return;
+                }
+                state = queue.available.wait(state).unwrap();
+            }
+        };
+
+        job(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn parallel_for_visits_every_item_once() {
+        let jobs = JobSystem::new(4).unwrap();
+        let items: Vec<usize> = (0..1000).collect();
+        let visited: Vec<AtomicUsize> = (0..1000).map(|_| AtomicUsize::new(0)).collect();
+
+        jobs.parallel_for("visit", &items, 37, |first, chunk| {
+            for (offset, item) in chunk.iter().enumerate() {
+                assert_eq!(*item, first + offset);
+                visited[*item].fetch_add(1, Ordering::Relaxed);
+            }
+        })
+        .unwrap();
+
+        assert!(
+            visited
+                .iter()
+                .all(|count| count.load(Ordering::Relaxed) == 1)
+        );
+    }
+
+    #[test]
+    fn parallel_map_keeps_order() {
+        let jobs = JobSystem::new(3).unwrap();
+        let items: Vec<u32> = (0..257).collect();
+        let squares = jobs.parallel_map("square", &items, 16, |x| x * x).unwrap();
+
+        assert_eq!(squares, items.iter().map(|x| x * x).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn spawned_jobs_can_be_awaited() {
+        let jobs = JobSystem::new(2).unwrap();
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let counter = counter.clone();
+                jobs.spawn("count", move || {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.wait().unwrap();
+        }
+
+        assert_eq!(counter.load(Ordering::Relaxed), 8);
+    }
+
+    #[test]
+    fn panics_are_reported_and_workers_survive() {
+        let jobs = JobSystem::new(1).unwrap();
+
+        assert!(jobs.spawn("boom", || panic!("boom")).wait().is_err());
+        assert!(
+            jobs.parallel_for("boom", &[1, 2, 3], 1, |first, _| assert_ne!(first, 1))
+                .is_err()
+        );
+        assert!(jobs.spawn("fine", || {}).wait().is_ok());
+    }
+
+    #[test]
+    fn nested_parallel_for_does_not_deadlock() {
+        let jobs = JobSystem::new(1).unwrap();
+        let total = AtomicUsize::new(0);
+        let outer = [0; 4];
+        let inner = [0; 8];
+
+        jobs.parallel_for("outer", &outer, 1, |_, _| {
+            jobs.parallel_for("inner", &inner, 2, |_, chunk| {
+                total.fetch_add(chunk.len(), Ordering::Relaxed);
+            })
+            .unwrap();
+        })
+        .unwrap();
+
+        assert_eq!(total.load(Ordering::Relaxed), 32);
+    }
+
+    #[test]
+    fn profiler_receives_timings() {
+        let jobs = JobSystem::new(2).unwrap();
+        let timeline = Arc::new(JobTimeline::default());
+        jobs.set_profiler(Some(timeline.clone()));
+
+        jobs.parallel_for("work", &[0u8; 10], 5, |_, _| {}).unwrap();
+        jobs.spawn("other", || {}).wait().unwrap();
+
+        let totals = timeline.totals();
+        assert_eq!(
+            totals.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+            ["other", "work"]
+        );
+
+        let timings = timeline.take();
+        assert_eq!(timings.len(), 3);
+        assert!(timings.iter().all(|t| t.worker <= jobs.worker_count()));
+        assert!(timeline.take().is_empty());
+    }
+}
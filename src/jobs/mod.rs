@@ -0,0 +1,5 @@
+pub mod job_system;
+pub mod task_graph;
+
+pub use job_system::*;
+pub use task_graph::*;
@@ -0,0 +1,258 @@
+use anyhow::Result;
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::Duration;
+
+use crate::jobs::JobSystem;
+
+/// A task added to a `TaskGraph`, used to declare dependencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TaskId(usize);
+
+struct TaskNode<'a> {
+    name: &'static str,
+    dependencies: Vec<usize>,
+    run: Box<dyn FnOnce() -> Result<()> + Send + 'a>,
+}
+
+/// Per-frame graph of tasks with dependencies, executed on a `JobSystem`.
+///
+/// Tasks may borrow frame data; `run` only returns once every started task has finished. A
+/// task starts as soon as all of its dependencies have succeeded. When a task fails or
+/// panics, tasks depending on it are skipped and `run` returns the first error.
+#[derive(Default)]
+pub struct TaskGraph<'a> {
+    tasks: Vec<TaskNode<'a>>,
+}
+
+impl<'a> TaskGraph<'a> {
+    pub fn new() -> Self {
+        Self { tasks: Vec::new() }
+    }
+
+    pub fn add(
+        &mut self,
+        name: &'static str,
+        task: impl FnOnce() -> Result<()> + Send + 'a,
+    ) -> TaskId {
+        self.add_after(name, &[], task)
+    }
+
+    /// Adds a task that starts once every task in `dependencies` has succeeded. Dependencies
+    /// must come from this graph, which also rules out cycles.
+    pub fn add_after(
+        &mut self,
+        name: &'static str,
+        dependencies: &[TaskId],
+        task: impl FnOnce() -> Result<()> + Send + 'a,
+    ) -> TaskId {
+        assert!(
+            dependencies.iter().all(|id| id.0 < self.tasks.len()),
+            "Task '{}' depends on a task from another graph",
+            name
+        );
+
+        self.tasks.push(TaskNode {
+            name,
+            dependencies: dependencies.iter().map(|id| id.0).collect(),
+            run: Box::new(task),
+        });
+        TaskId(self.tasks.len() - 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Runs every task and blocks until the graph is done, running queued jobs on the
+    /// calling thread meanwhile.
+    pub fn run(self, jobs: &JobSystem) -> Result<()> {
+        let count = self.tasks.len();
+        let mut dependents = vec![Vec::new(); count];
+        let mut waiting_on = vec![0usize; count];
+        let mut names = Vec::with_capacity(count);
+        let mut runs = Vec::with_capacity(count);
+
+        for (index, task) in self.tasks.into_iter().enumerate() {
+            waiting_on[index] = task.dependencies.len();
+            for dependency in task.dependencies {
+                dependents[dependency].push(index);
+            }
+            names.push(task.name);
+            runs.push(Some(task.run));
+        }
+
+        let (sender, receiver) = mpsc::channel::<(usize, Result<()>)>();
+        let mut skipped = vec![false; count];
+        let mut ready: Vec<usize> = (0..count).filter(|&i| waiting_on[i] == 0).collect();
+        let mut finished = 0;
+        let mut first_error = None;
+
+        while finished < count {
+            for index in ready.drain(..) {
+                let run = runs[index].take().expect("task runs once");
+                let name = names[index];
+
+                let result = Arc::new(Mutex::new(None));
+                let job_result = result.clone();
+                let job: Box<dyn FnOnce() + Send + 'a> = Box::new(move || {
+                    *job_result.lock().unwrap() = Some(run());
+                });
+                // SAFETY: the job may borrow data living for 'a, and `run` does not return
+                // before every started task has reported back through the channel, panicking
+                // or not.
+                let job: Box<dyn FnOnce() + Send + 'static> = unsafe { std::mem::transmute(job) };
+
+                let sender = sender.clone();
+                jobs.enqueue(
+                    name,
+                    job,
+                    Box::new(move |did_panic| {
+                        let result = match result.lock().unwrap().take() {
+                            Some(result) if !did_panic => result,
+                            _ => Err(anyhow::anyhow!("Task '{}' panicked", name)),
+                        };
+                        let _ = sender.send((index, result));
+                    }),
+                );
+            }
+
+            let (index, result) = loop {
+                match receiver.try_recv() {
+                    Ok(message) => break message,
+                    Err(_) if jobs.help() => {}
+                    Err(_) => {
+                        if let Ok(message) = receiver.recv_timeout(Duration::from_millis(1)) {
+                            break message;
+                        }
+                    }
+                }
+            };
+
+            finished += 1;
+            let failed = match result {
+                Ok(()) => false,
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                    true
+                }
+            };
+
+            let mut released = vec![(index, failed)];
+            while let Some((index, failed)) = released.pop() {
+                for &dependent in &dependents[index] {
+                    skipped[dependent] |= failed;
+                    waiting_on[dependent] -= 1;
+                    if waiting_on[dependent] > 0 {
+                        continue;
+                    }
+
+                    if skipped[dependent] {
+                        finished += 1;
+                        released.push((dependent, true));
+                    } else {
+                        ready.push(dependent);
+                    }
+                }
+            }
+        }
+
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn dependencies_run_first() {
+        let jobs = JobSystem::new(4).unwrap();
+        let order = Mutex::new(Vec::new());
+        let log = |name: &'static str| {
+            let order = &order;
+            move || {
+                order.lock().unwrap().push(name);
+                Ok(())
+            }
+        };
+
+        let mut graph = TaskGraph::new();
+        let cull = graph.add("cull", log("cull"));
+        let shadows = graph.add("shadows", log("shadows"));
+        let opaque = graph.add_after("opaque", &[cull], log("opaque"));
+        graph.add_after("submit", &[opaque, shadows], log("submit"));
+        graph.run(&jobs).unwrap();
+
+        let order = order.into_inner().unwrap();
+        let position = |name| order.iter().position(|n| *n == name).unwrap();
+        assert_eq!(order.len(), 4);
+        assert!(position("cull") < position("opaque"));
+        assert!(position("opaque") < position("submit"));
+        assert!(position("shadows") < position("submit"));
+    }
+
+    #[test]
+    fn failures_skip_dependents() {
+        let jobs = JobSystem::new(2).unwrap();
+        let ran = AtomicUsize::new(0);
+        let count = || {
+            ran.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        };
+
+        let mut graph = TaskGraph::new();
+        let failing = graph.add("fail", || Err(anyhow::anyhow!("culling failed")));
+        let skipped = graph.add_after("record", &[failing], count);
+        graph.add_after("submit", &[skipped], count);
+        graph.add("independent", count);
+
+        let error = graph.run(&jobs).unwrap_err();
+        assert_eq!(error.to_string(), "culling failed");
+        assert_eq!(ran.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn panics_become_errors() {
+        let jobs = JobSystem::new(1).unwrap();
+        let mut graph = TaskGraph::new();
+        graph.add("boom", || panic!("boom"));
+
+        assert!(graph.run(&jobs).is_err());
+    }
+
+    #[test]
+    fn tasks_can_borrow_and_write_frame_data() {
+        let jobs = JobSystem::new(2).unwrap();
+        let mut first = vec![0u32; 4];
+        let mut second = vec![0u32; 4];
+
+        let mut graph = TaskGraph::new();
+        graph.add("first", || {
+            first.iter_mut().for_each(|x| *x = 1);
+            Ok(())
+        });
+        graph.add("second", || {
+            second.iter_mut().for_each(|x| *x = 2);
+            Ok(())
+        });
+        graph.run(&jobs).unwrap();
+
+        assert_eq!(first, [1; 4]);
+        assert_eq!(second, [2; 4]);
+    }
+
+    #[test]
+    fn empty_graph_finishes() {
+        let jobs = JobSystem::new(1).unwrap();
+        assert!(TaskGraph::new().is_empty());
+        TaskGraph::new().run(&jobs).unwrap();
+    }
+}
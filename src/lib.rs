@@ -1,9 +1,41 @@
+#![allow(clippy::module_inception)]
+
+pub mod animation;
+pub mod assets;
+pub mod backend;
+pub mod effects;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod geometry;
+pub mod jobs;
+#[cfg(feature = "rapier")]
+pub mod physics;
 pub mod pipeline;
+pub mod remote;
 pub mod renderer;
+pub mod replay;
+pub mod scene;
+pub mod scripting;
+pub mod ui;
 pub mod vulkan;
 pub mod window;
 
+pub use animation::*;
+pub use assets::*;
+pub use backend::*;
+pub use effects::*;
+#[cfg(feature = "ffi")]
+pub use ffi::*;
+pub use geometry::*;
+pub use jobs::*;
+#[cfg(feature = "rapier")]
+pub use physics::*;
 pub use pipeline::*;
+pub use remote::*;
 pub use renderer::*;
+pub use replay::*;
+pub use scene::*;
+pub use scripting::*;
+pub use ui::*;
 pub use vulkan::*;
 pub use window::*;
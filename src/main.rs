@@ -1,65 +1,124 @@
 use anyhow::Result;
-use winit::application::ApplicationHandler;
-use winit::event::WindowEvent;
-use winit::event_loop::{ActiveEventLoop, EventLoop};
+use glam::Vec2;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use winit::event::{ElementState, MouseButton, WindowEvent};
 
-use rust_vulkan_experiments::VulkanWindow;
 use rust_vulkan_experiments::{
-    VulkanCommandPool, VulkanDevice, VulkanFramebuffers, VulkanInstance, VulkanPhysicalDevice,
-    VulkanRenderPass, VulkanRenderer, VulkanSurface, VulkanSwapchain, VulkanSyncObjects,
+    ACTION_CYCLE_COLOR_CHANNELS, ACTION_RELOAD_SHADERS, ACTION_SWITCH_DEVICE, HotkeyMap, Hotkeys,
 };
-use rust_vulkan_experiments::{VulkanPipeline, VulkanPipelineBuilder};
+use rust_vulkan_experiments::{
+    AppCallbacks, AppRunner, BackgroundMode, RedrawMode, ResizeDebouncer, VulkanWindow,
+};
+use rust_vulkan_experiments::{
+    BindingOverhead, ColorWorkflow, DescriptorBackend, PresentSource, PresentThread,
+    SuboptimalPolicy, SwapchainConfig, ValidationOptions, VulkanCommandPool, VulkanDevice,
+    VulkanInstance, VulkanOffscreenTarget, VulkanPhysicalDevice, VulkanRenderer, VulkanSurface,
+    VulkanSwapchain,
+};
+use rust_vulkan_experiments::{
+    GraphicsBackend, ShaderObjectBuilder, ShaderObjectState, VulkanPipeline, VulkanPipelineBuilder,
+    VulkanShaderObjects,
+};
+use rust_vulkan_experiments::{SHADERTOY_ENV, ShaderToy, ShaderToyUniforms};
 
 struct App {
+    present_thread: Option<PresentThread>,
     renderer: Option<VulkanRenderer>,
     pipeline: Option<VulkanPipeline>,
-    sync_objects: Option<VulkanSyncObjects>,
+    /// Draws the triangle instead of `pipeline` with `RVE_GRAPHICS_BACKEND=shader_objects`.
+    shader_objects: Option<VulkanShaderObjects>,
+    /// Set instead of `pipeline` in playground mode, when `RVE_SHADERTOY` names a shader.
+    shadertoy: Option<ShaderToy>,
+    shadertoy_uniforms: ShaderToyUniforms,
+    cursor: Vec2,
+    hotkeys: Hotkeys,
+    /// Set when the present thread reports a lost surface, until it has been recreated.
+    surface_lost: bool,
+    /// Set when the present thread reports an out-of-date swapchain, or a suboptimal one its
+    /// `SuboptimalPolicy` recreates.
+    swapchain_out_of_date: bool,
+    resize: ResizeDebouncer,
     command_pool: Option<VulkanCommandPool>,
-    framebuffers: Option<VulkanFramebuffers>,
-    render_pass: Option<VulkanRenderPass>,
+    targets: Vec<VulkanOffscreenTarget>,
     swapchain: Option<VulkanSwapchain>,
     logical_device: Option<VulkanDevice>,
     surface: Option<VulkanSurface>,
     physical_device: Option<VulkanPhysicalDevice>,
     instance: Option<VulkanInstance>,
-    window: Option<VulkanWindow>,
 }
 
 impl App {
     fn new() -> Self {
         Self {
+            present_thread: None,
             renderer: None,
             pipeline: None,
-            sync_objects: None,
+            shader_objects: None,
+            shadertoy: None,
+            shadertoy_uniforms: ShaderToyUniforms::default(),
+            cursor: Vec2::ZERO,
+            hotkeys: Hotkeys::default(),
+            surface_lost: false,
+            swapchain_out_of_date: false,
+            resize: ResizeDebouncer::default(),
             command_pool: None,
-            framebuffers: None,
-            render_pass: None,
+            targets: Vec::new(),
             swapchain: None,
             instance: None,
             physical_device: None,
             surface: None,
-            window: None,
             logical_device: None,
         }
     }
 
-    fn initalize(&mut self, event_loop: &ActiveEventLoop) -> Result<()> {
-        let window = VulkanWindow::new(event_loop)?;
-        println!("Window created");
+    fn initalize(&mut self, window: &VulkanWindow) -> Result<()> {
+        match HotkeyMap::from_env() {
+            Ok(map) => self.hotkeys.map = map,
+            Err(e) => eprintln!("{}, using default hotkeys", e),
+        }
 
         let extensions = VulkanWindow::get_required_extensions();
 
-        let vulkan_instance = VulkanInstance::new(&extensions)?;
+        let vulkan_instance =
+            VulkanInstance::with_validation(&extensions, ValidationOptions::from_env())?;
         println!("Vulkan instance created");
 
-        let surface = VulkanSurface::new(&vulkan_instance, &window)?;
+        let surface = VulkanSurface::new(&vulkan_instance, window)?;
         println!("Surface created");
 
         let vulkan_physical_device = VulkanPhysicalDevice::select_best_device(&vulkan_instance)?;
         println!("Physical device selected");
 
+        self.instance = Some(vulkan_instance);
+        self.surface = Some(surface);
+
+        let shadertoy_path = std::env::var_os(SHADERTOY_ENV).map(PathBuf::from);
+        self.create_device_objects(window, vulkan_physical_device, shadertoy_path.as_deref())?;
+        if let Some(swapchain) = &self.swapchain {
+            self.shadertoy_uniforms = ShaderToyUniforms::new(swapchain.extent);
+        }
+
+        Ok(())
+    }
+
+    /// Creates everything that belongs to a logical device on `vulkan_physical_device`: the
+    /// swapchain, render targets, pipelines and the present thread. The instance and surface
+    /// outlive these, so they can be rebuilt on another device by `switch_device`.
+    fn create_device_objects(
+        &mut self,
+        window: &VulkanWindow,
+        vulkan_physical_device: VulkanPhysicalDevice,
+        shadertoy_path: Option<&Path>,
+    ) -> Result<()> {
+        let (Some(vulkan_instance), Some(surface)) = (&self.instance, &self.surface) else {
+            return Err(anyhow::anyhow!(
+                "Vulkan instance or surface is not initialized"
+            ));
+        };
+
         let queue_families =
-            vulkan_physical_device.find_queue_families(&vulkan_instance.instance, &surface)?;
+            vulkan_physical_device.find_queue_families(&vulkan_instance.instance, surface)?;
         if !queue_families.is_complete() {
             return Err(anyhow::anyhow!(
                 "Device doesn't support required queue families"
@@ -68,147 +127,565 @@ impl App {
         println!("Queue families found");
 
         let logical_device = VulkanDevice::new(
-            &vulkan_instance,
+            vulkan_instance,
             &vulkan_physical_device,
             queue_families.clone(),
         )?;
         println!("Logical device created");
 
-        let swapchain = VulkanSwapchain::new(
-            &vulkan_instance,
+        let swapchain = create_swapchain(
+            vulkan_instance,
             &logical_device,
             &vulkan_physical_device,
-            &surface,
-            window.window().inner_size().width,
-            window.window().inner_size().height,
+            surface,
+            window,
+            None,
         )?;
         println!("Swapchain created");
 
-        let render_pass = VulkanRenderPass::new(&logical_device, &swapchain)?;
-        println!("Render pass created");
+        let renderer = VulkanRenderer::new(&logical_device, vulkan_instance);
+        println!("Renderer created");
 
-        let framebuffers = VulkanFramebuffers::new(&logical_device, &render_pass, &swapchain)?;
-        println!("Framebuffers created");
+        // The scene is rendered into one target per frame in flight; the present thread copies
+        // finished frames to the swapchain.
+        let targets = create_targets(
+            &logical_device,
+            &vulkan_physical_device,
+            &swapchain,
+            renderer.max_frames_in_flight,
+        )?;
+        println!("Render targets created");
 
         let command_pool = VulkanCommandPool::new(
             &logical_device,
             queue_families.clone(),
-            swapchain.images.len(),
+            renderer.max_frames_in_flight,
         )?;
         println!("Command pool created");
 
-        let sync_objects = VulkanSyncObjects::new(&logical_device, swapchain.images.len())?;
-        println!("Sync objects created");
+        match DescriptorBackend::from_env(&logical_device.features) {
+            Ok(DescriptorBackend::Buffer) => {
+                let encoder = command_pool.encoder(0)?;
+                match BindingOverhead::measure(
+                    vulkan_instance,
+                    &logical_device,
+                    &vulkan_physical_device,
+                    encoder.command_buffer(),
+                    10_000,
+                ) {
+                    Ok(overhead) => println!("Descriptor backend: buffer ({})", overhead),
+                    Err(e) => eprintln!("Failed to measure descriptor binding overhead: {}", e),
+                }
+                encoder.finish()?;
+            }
+            Ok(backend) => println!("Descriptor backend: {:?}", backend),
+            Err(e) => eprintln!("{}, using descriptor sets", e),
+        }
 
-        let renderer = VulkanRenderer::new(&logical_device, &vulkan_instance);
-        println!("Renderer created");
+        let present_thread =
+            PresentThread::new(&logical_device, &swapchain, &present_sources(&targets))?;
+        println!("Present thread started");
+
+        if let Some(path) = shadertoy_path {
+            let srgb_target = swapchain.workflow() == ColorWorkflow::SrgbTarget;
+            let shadertoy = ShaderToy::new(
+                &logical_device,
+                targets[0].render_pass.render_pass,
+                swapchain.extent,
+                srgb_target,
+                path,
+            )?;
+            println!("Playground shader {} loaded", shadertoy.path().display());
+            self.shadertoy = Some(shadertoy);
+        } else {
+            let start = Instant::now();
+            let pipeline = VulkanPipelineBuilder::new(&logical_device)
+                .set_render_pass(targets[0].render_pass.render_pass)
+                .set_extent(swapchain.extent)
+                .with_vertex_spv(include_bytes!("../bin/triangle.vert.spv"))?
+                .with_fragment_spv(include_bytes!("../bin/triangle.frag.spv"))?
+                .with_topology(ash::vk::PrimitiveTopology::TRIANGLE_LIST)
+                .with_dynamic_states(&[
+                    ash::vk::DynamicState::VIEWPORT,
+                    ash::vk::DynamicState::SCISSOR,
+                ])
+                .with_alpha_blending()
+                .with_dynamic_color_write_mask(&logical_device)
+                .build()?;
+            let pipeline_time = start.elapsed();
+            self.pipeline = Some(pipeline);
+
+            match GraphicsBackend::from_env(&logical_device.features) {
+                Ok(GraphicsBackend::ShaderObjects) => {
+                    let start = Instant::now();
+                    let shader_objects = ShaderObjectBuilder::new(&logical_device)
+                        .with_vertex_spv(include_bytes!("../bin/triangle.vert.spv"))?
+                        .with_fragment_spv(include_bytes!("../bin/triangle.frag.spv"))?
+                        .with_state(ShaderObjectState::default().with_alpha_blending())
+                        .build()?;
+                    println!(
+                        "Graphics backend: shader objects ({:?} to create, pipeline {:?})",
+                        start.elapsed(),
+                        pipeline_time
+                    );
+                    self.shader_objects = Some(shader_objects);
+                }
+                Ok(backend) => println!("Graphics backend: {:?}", backend),
+                Err(e) => eprintln!("{}, using pipelines", e),
+            }
+        }
 
-        let pipeline = VulkanPipelineBuilder::new(&logical_device)
-            .set_render_pass(render_pass.render_pass)
-            .set_extent(swapchain.extent)
-            .with_vertex_spv(include_bytes!("../bin/triangle.vert.spv"))?
-            .with_fragment_spv(include_bytes!("../bin/triangle.frag.spv"))?
-            .with_topology(ash::vk::PrimitiveTopology::TRIANGLE_LIST)
-            .with_dynamic_states(&[
-                ash::vk::DynamicState::VIEWPORT,
-                ash::vk::DynamicState::SCISSOR,
-            ])
-            .with_alpha_blending()
-            .build()?;
-
-        self.window = Some(window);
-        self.instance = Some(vulkan_instance);
         self.physical_device = Some(vulkan_physical_device);
-        self.surface = Some(surface);
         self.logical_device = Some(logical_device);
         self.swapchain = Some(swapchain);
-        self.render_pass = Some(render_pass);
-        self.framebuffers = Some(framebuffers);
+        self.targets = targets;
         self.command_pool = Some(command_pool);
-        self.sync_objects = Some(sync_objects);
         self.renderer = Some(renderer);
-        self.pipeline = Some(pipeline);
+        self.present_thread = Some(present_thread);
 
         Ok(())
     }
 
-    fn render_frame(&mut self) {
-        if let (
-            Some(renderer),
-            Some(sync_objects),
-            Some(command_pool),
-            Some(framebuffers),
-            Some(render_pass),
-            Some(swapchain),
-            Some(logical_device),
-            Some(pipeline),
-        ) = (
-            &mut self.renderer,
-            &self.sync_objects,
-            &self.command_pool,
-            &self.framebuffers,
-            &self.render_pass,
-            &self.swapchain,
-            &self.logical_device,
-            &self.pipeline,
-        ) {
-            if let Err(e) = renderer.draw_frame(
-                logical_device,
-                swapchain,
-                render_pass,
-                framebuffers,
-                command_pool,
-                sync_objects,
-                pipeline,
-            ) {
-                eprintln!("Failed to draw frame: {}", e);
+    /// Replaces the swapchain and the present thread, and first the surface with a new one
+    /// for the same window if it was lost. The device and the render targets are kept; the
+    /// present thread scales them to the new swapchain's extent.
+    fn recreate_swapchain(&mut self, window: &VulkanWindow, new_surface: bool) -> Result<()> {
+        let (Some(instance), Some(physical_device), Some(logical_device)) =
+            (&self.instance, &self.physical_device, &self.logical_device)
+        else {
+            return Ok(());
+        };
+        // Dropping the present thread waits for its own work only, not for the device.
+        self.present_thread = None;
+        // Handed to the new swapchain so the window keeps its last image in the meantime; a
+        // lost surface's swapchain has to go before the surface.
+        let old_swapchain = if new_surface {
+            self.swapchain = None;
+            None
+        } else {
+            self.swapchain.take()
+        };
+        if new_surface {
+            self.surface = None;
+            let surface = VulkanSurface::new(instance, window)?;
+            let present_family = logical_device
+                .queue_family_indices
+                .present_family
+                .ok_or_else(|| anyhow::anyhow!("Device has no present queue family"))?;
+            if !surface.check_surface_support(physical_device, present_family)? {
+                return Err(anyhow::anyhow!(
+                    "Present queue family {} can't present to the new surface",
+                    present_family
+                ));
+            }
+            self.surface = Some(surface);
+        }
+        let Some(surface) = &self.surface else {
+            return Err(anyhow::anyhow!("Surface is not initialized"));
+        };
+
+        let swapchain = create_swapchain(
+            instance,
+            logical_device,
+            physical_device,
+            surface,
+            window,
+            old_swapchain.as_ref(),
+        )?;
+        drop(old_swapchain);
+        let present_thread =
+            PresentThread::new(logical_device, &swapchain, &present_sources(&self.targets))?;
+
+        self.swapchain = Some(swapchain);
+        self.present_thread = Some(present_thread);
+        Ok(())
+    }
+
+    /// Reallocates the render targets at the swapchain's extent once a resize has settled.
+    /// Until then frames render at the last target size and the present thread scales them,
+    /// so dragging the window doesn't wait for the GPU on every size change.
+    fn resize_targets(&mut self, window: &VulkanWindow) -> Result<()> {
+        let size = window.window().inner_size();
+        if self
+            .swapchain
+            .as_ref()
+            .is_some_and(|s| (s.extent.width, s.extent.height) != (size.width, size.height))
+        {
+            self.recreate_swapchain(window, false)?;
+        }
+
+        let (Some(physical_device), Some(logical_device), Some(swapchain)) =
+            (&self.physical_device, &self.logical_device, &self.swapchain)
+        else {
+            return Ok(());
+        };
+        let extent = swapchain.extent;
+        if self.targets.first().map(|target| target.extent()) == Some(extent) {
+            return Ok(());
+        }
+
+        let start = Instant::now();
+        // The targets may still be rendered or presented from; this is the only wait of
+        // the resize.
+        logical_device.wait_idle()?;
+        self.present_thread = None;
+        self.targets = create_targets(
+            logical_device,
+            physical_device,
+            swapchain,
+            self.targets.len(),
+        )?;
+        self.present_thread = Some(PresentThread::new(
+            logical_device,
+            swapchain,
+            &present_sources(&self.targets),
+        )?);
+        self.shadertoy_uniforms.set_extent(extent);
+        println!(
+            "Render targets resized to {}x{} in {:?}",
+            extent.width,
+            extent.height,
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Releases everything `create_device_objects` made, in dependency order.
+    fn destroy_device_objects(&mut self) {
+        if let Some(device) = &self.logical_device {
+            let _ = device.wait_idle();
+        }
+        self.present_thread = None;
+        self.renderer = None;
+        self.pipeline = None;
+        self.shader_objects = None;
+        self.shadertoy = None;
+        self.command_pool = None;
+        self.targets.clear();
+        self.swapchain = None;
+        self.logical_device = None;
+        self.physical_device = None;
+    }
+
+    /// Rebuilds the device-level objects on the next physical device that can present to the
+    /// window, e.g. to move from an integrated to a discrete GPU. Settings that describe the
+    /// scene rather than GPU state (the playground shader, its clock and input, the shown
+    /// channels) carry over; GPU resources are recreated from them. If no other device works,
+    /// the previous one is restored.
+    fn switch_device(&mut self, window: &VulkanWindow) -> Result<()> {
+        let (Some(instance), Some(current)) = (&self.instance, &self.physical_device) else {
+            return Ok(());
+        };
+        let previous = current.physical_device;
+        let mut devices: Vec<Option<VulkanPhysicalDevice>> =
+            VulkanPhysicalDevice::enumerate(instance)?
+                .into_iter()
+                .map(Some)
+                .collect();
+        let handles: Vec<_> = devices
+            .iter()
+            .flatten()
+            .map(|device| device.physical_device)
+            .collect();
+        let order = VulkanPhysicalDevice::switch_order(&handles, previous);
+        if order.is_empty() {
+            println!("No other physical device to switch to");
+            return Ok(());
+        }
+
+        let channel_isolation = self.renderer.as_ref().map(|r| r.channel_isolation);
+        let shadertoy_path = self.shadertoy.as_ref().map(|s| s.path().to_path_buf());
+        let start = Instant::now();
+        self.destroy_device_objects();
+
+        let mut result = Err(anyhow::anyhow!("No physical device could be initialized"));
+        for index in order {
+            let Some(device) = devices[index].take() else {
+                continue;
+            };
+            let name = device.name();
+            match self.create_device_objects(window, device, shadertoy_path.as_deref()) {
+                Ok(()) => {
+                    println!("Switched to {} in {:?}", name, start.elapsed());
+                    result = Ok(());
+                    break;
+                }
+                Err(e) => eprintln!("Failed to switch to {}: {}", name, e),
             }
         }
+        if result.is_err() {
+            let Some(instance) = &self.instance else {
+                return result;
+            };
+            let device = VulkanPhysicalDevice::from_raw(instance, previous);
+            eprintln!("Restoring {}", device.name());
+            self.create_device_objects(window, device, shadertoy_path.as_deref())?;
+        }
+
+        if let (Some(renderer), Some(channel_isolation)) = (&mut self.renderer, channel_isolation) {
+            renderer.channel_isolation = channel_isolation;
+        }
+        result
+    }
+
+    fn run_action(&mut self, window: &VulkanWindow, action: &str) {
+        match action {
+            ACTION_SWITCH_DEVICE => {
+                if let Err(e) = self.switch_device(window) {
+                    eprintln!("Failed to switch device: {}", e);
+                }
+            }
+            ACTION_RELOAD_SHADERS => {
+                if let (Some(shadertoy), Some(logical_device)) =
+                    (&mut self.shadertoy, &self.logical_device)
+                {
+                    match shadertoy.reload(logical_device) {
+                        Ok(()) => println!("Playground shader reloaded"),
+                        Err(e) => eprintln!("Failed to reload playground shader: {}", e),
+                    }
+                }
+            }
+            ACTION_CYCLE_COLOR_CHANNELS => match (&mut self.renderer, &self.logical_device) {
+                (Some(renderer), Some(device)) if device.features.dynamic_color_write_mask => {
+                    renderer.channel_isolation = renderer.channel_isolation.next();
+                    println!("Showing channels: {:?}", renderer.channel_isolation);
+                }
+                _ => println!("Channel isolation needs VK_EXT_extended_dynamic_state3"),
+            },
+            _ => println!("Hotkey action '{}' is not supported by this demo", action),
+        }
     }
 }
 
-impl ApplicationHandler for App {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        if self.window.is_none() {
-            if let Err(e) = self.initalize(event_loop) {
-                eprintln!("Failed to initialize: {}", e);
-                event_loop.exit();
+impl AppCallbacks for App {
+    fn on_init(&mut self, window: &VulkanWindow) -> Result<()> {
+        self.initalize(window)
+    }
+
+    fn on_update(&mut self, window: &VulkanWindow, delta_time: f32) {
+        self.shadertoy_uniforms.advance(delta_time);
+
+        for action in self.hotkeys.take_triggered() {
+            self.run_action(window, &action);
+        }
+
+        if let (Some(shadertoy), Some(logical_device)) = (&mut self.shadertoy, &self.logical_device)
+        {
+            match shadertoy.reload_if_changed(logical_device) {
+                Ok(true) => println!("Playground shader reloaded"),
+                Ok(false) => {}
+                Err(e) => eprintln!("Failed to reload playground shader: {}", e),
             }
+            // Playground shaders animate with `iTime`, so they keep rendering on demand too.
+            window.request_redraw();
         }
     }
 
-    fn window_event(
-        &mut self,
-        event_loop: &ActiveEventLoop,
-        _: winit::window::WindowId,
-        event: WindowEvent,
-    ) {
+    fn on_event(&mut self, window: &VulkanWindow, event: &WindowEvent) {
+        self.hotkeys.handle_window_event(event);
+
+        // With `RVE_REDRAW=on_demand`, frames are only rendered when requested. Hotkeys are
+        // handled in `on_update`, so key presses need a frame to take effect.
+        if matches!(event, WindowEvent::KeyboardInput { .. }) {
+            window.request_redraw();
+        }
+
         match event {
-            WindowEvent::CloseRequested => {
-                if let Some(ref device) = self.logical_device {
-                    let _ = device.wait_idle();
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = Vec2::new(position.x as f32, position.y as f32);
+                self.shadertoy_uniforms.set_cursor(self.cursor);
+                if self.shadertoy.is_some() {
+                    window.request_redraw();
                 }
-                event_loop.exit();
             }
-            WindowEvent::RedrawRequested => {
-                self.render_frame();
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.shadertoy_uniforms
+                    .set_button(*state == ElementState::Pressed, self.cursor);
+                if self.shadertoy.is_some() {
+                    window.request_redraw();
+                }
             }
             _ => {}
         }
     }
 
-    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
-        if let Some(ref vulkan_window) = self.window {
-            vulkan_window.window().request_redraw();
+    fn on_resize(&mut self, _window: &VulkanWindow, width: u32, height: u32) {
+        self.resize.resized(width, height, Instant::now());
+    }
+
+    fn on_render(&mut self, window: &VulkanWindow) -> Result<()> {
+        // A minimized window has no area to create a swapchain for.
+        let size = window.window().inner_size();
+        if size.width == 0 || size.height == 0 {
+            return Ok(());
+        }
+
+        if self.surface_lost || self.swapchain_out_of_date {
+            // Retried every frame, since the window may not be able to host a surface again
+            // right away, e.g. while an Android activity is in the background.
+            match self.recreate_swapchain(window, self.surface_lost) {
+                Ok(()) => {
+                    if self.surface_lost {
+                        println!("Surface recreated");
+                    }
+                    self.surface_lost = false;
+                    self.swapchain_out_of_date = false;
+                }
+                Err(e) => {
+                    eprintln!("Failed to recreate swapchain: {}", e);
+                    window.request_redraw();
+                    return Ok(());
+                }
+            }
+        }
+        if self.resize.settle(Instant::now()).is_some()
+            && let Err(e) = self.resize_targets(window)
+        {
+            eprintln!("Failed to resize render targets: {}", e);
+        }
+
+        let (Some(renderer), Some(present_thread), Some(command_pool), Some(logical_device)) = (
+            &mut self.renderer,
+            &mut self.present_thread,
+            &self.command_pool,
+            &self.logical_device,
+        ) else {
+            return Ok(());
+        };
+
+        if let Some(shadertoy) = &self.shadertoy {
+            let uniforms = self.shadertoy_uniforms;
+            renderer.draw_frame_threaded_with(
+                logical_device,
+                &self.targets,
+                command_pool,
+                present_thread,
+                |render_pass| shadertoy.record(render_pass.command_buffer(), &uniforms),
+            )?;
+        } else if let Some(shader_objects) = &mut self.shader_objects {
+            shader_objects.state.color_write_mask = renderer.channel_isolation.color_write_mask();
+            let extent = self.targets[0].extent();
+            renderer.draw_frame_threaded_dynamic_with(
+                logical_device,
+                &self.targets,
+                command_pool,
+                present_thread,
+                |render_pass| {
+                    shader_objects.bind(render_pass.command_buffer(), extent);
+                    render_pass.draw(3, 1, 0, 0);
+                },
+            )?;
+        } else if let Some(pipeline) = &self.pipeline {
+            renderer.draw_frame_threaded(
+                logical_device,
+                &self.targets,
+                command_pool,
+                pipeline,
+                present_thread,
+            )?;
+        }
+
+        // Recreated before the next frame. With `SuboptimalPolicy::RecreateImmediately` the
+        // present thread has already dropped the frames that acquired suboptimal images.
+        if present_thread.is_surface_lost() {
+            eprintln!("Surface lost, recreating it");
+            self.surface_lost = true;
+        } else if present_thread.is_out_of_date() {
+            self.swapchain_out_of_date = true;
+        }
+        // Recovery and resize settling happen at the start of a frame, so keep frames coming
+        // until they are done.
+        if self.surface_lost || self.swapchain_out_of_date || self.resize.is_resizing() {
+            window.request_redraw();
         }
+        Ok(())
+    }
+
+    fn on_exit(&mut self) {
+        self.destroy_device_objects();
     }
 }
 
-fn main() -> Result<()> {
-    let event_loop = EventLoop::new()?;
+fn create_swapchain(
+    instance: &VulkanInstance,
+    logical_device: &VulkanDevice,
+    physical_device: &VulkanPhysicalDevice,
+    surface: &VulkanSurface,
+    window: &VulkanWindow,
+    old_swapchain: Option<&VulkanSwapchain>,
+) -> Result<VulkanSwapchain> {
+    let workflow = ColorWorkflow::from_env()?;
+    let suboptimal_policy = SuboptimalPolicy::from_env().unwrap_or_else(|e| {
+        eprintln!("{}, recreating at the end of the frame", e);
+        SuboptimalPolicy::default()
+    });
+    let swapchain = VulkanSwapchain::with_config(
+        instance,
+        logical_device,
+        physical_device,
+        surface,
+        window.window().inner_size().width,
+        window.window().inner_size().height,
+        SwapchainConfig::new()
+            .with_workflow(workflow)
+            .with_suboptimal_policy(suboptimal_policy)
+            .with_old_swapchain(
+                old_swapchain.map_or(ash::vk::SwapchainKHR::null(), |s| s.swapchain),
+            ),
+    )?;
+    if let Err(e) = swapchain
+        .workflow()
+        .validate_target(swapchain.format.format)
+    {
+        eprintln!("Warning: {}", e);
+    }
+    Ok(swapchain)
+}
 
-    let mut app = App::new();
+/// One render target per frame in flight, matching the swapchain's extent and format.
+fn create_targets(
+    logical_device: &VulkanDevice,
+    physical_device: &VulkanPhysicalDevice,
+    swapchain: &VulkanSwapchain,
+    count: usize,
+) -> Result<Vec<VulkanOffscreenTarget>> {
+    (0..count)
+        .map(|_| {
+            VulkanOffscreenTarget::new(
+                logical_device,
+                physical_device,
+                swapchain.extent,
+                swapchain.format.format,
+            )
+        })
+        .collect()
+}
 
-    event_loop.run_app(&mut app)?;
-    Ok(())
+fn present_sources(targets: &[VulkanOffscreenTarget]) -> Vec<PresentSource> {
+    targets
+        .iter()
+        .map(|target| PresentSource {
+            image: target.image.image,
+            extent: target.extent(),
+        })
+        .collect()
+}
+
+fn main() -> Result<()> {
+    let background_mode = BackgroundMode::from_env().unwrap_or_else(|e| {
+        eprintln!("{}, rendering at full rate in the background", e);
+        BackgroundMode::default()
+    });
+    let redraw_mode = RedrawMode::from_env().unwrap_or_else(|e| {
+        eprintln!("{}, rendering continuously", e);
+        RedrawMode::default()
+    });
+    AppRunner::new()
+        .with_background_mode(background_mode)
+        .with_redraw_mode(redraw_mode)
+        .run(App::new())
 }
@@ -0,0 +1,3 @@
+pub mod rapier;
+
+pub use rapier::*;
@@ -0,0 +1,191 @@
+use glam::{Mat4, Quat, Vec3, Vec4};
+use rapier3d::dynamics::{RigidBody, RigidBodyHandle};
+use rapier3d::math::Vector;
+use rapier3d::pipeline::{
+    DebugColor, DebugRenderBackend, DebugRenderMode, DebugRenderObject, DebugRenderPipeline,
+    DebugRenderStyle, PhysicsWorld,
+};
+
+use crate::renderer::DebugDraw;
+
+/// Most fixed steps `PhysicsScene::step` takes per call, so a long frame slows the
+/// simulation down instead of making the next frame longer still.
+pub const MAX_PHYSICS_STEPS: u32 = 8;
+
+/// A rapier world stepped at a fixed rate, whose bodies drive instance transforms.
+///
+/// Bodies are tied to a slot of the caller's transform array through their `user_data`, so
+/// the bodies and colliders stay fully accessible through `world` for building scenes.
+pub struct PhysicsScene {
+    pub world: PhysicsWorld,
+    pub debug_mode: DebugRenderMode,
+    debug_render: DebugRenderPipeline,
+    accumulator: f32,
+}
+
+impl Default for PhysicsScene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PhysicsScene {
+    pub fn new() -> Self {
+        let debug_mode = DebugRenderMode::COLLIDER_SHAPES | DebugRenderMode::JOINTS;
+        Self {
+            world: PhysicsWorld::new(),
+            debug_mode,
+            debug_render: DebugRenderPipeline::new(DebugRenderStyle::default(), debug_mode),
+            accumulator: 0.0,
+        }
+    }
+
+    /// Makes `sync_transforms` write `body`'s transform into `transforms[slot]`.
+    pub fn bind(&mut self, body: RigidBodyHandle, slot: usize) {
+        if let Some(body) = self.world.bodies.get_mut(body) {
+            body.user_data = slot as u128 + 1;
+        }
+    }
+
+    pub fn unbind(&mut self, body: RigidBodyHandle) {
+        if let Some(body) = self.world.bodies.get_mut(body) {
+            body.user_data = 0;
+        }
+    }
+
+    /// Advances the simulation by `dt` seconds in fixed steps of
+    /// `world.integration_parameters.dt`, carrying the remainder over to the next call.
+    /// Returns the number of steps taken.
+    pub fn step(&mut self, dt: f32) -> u32 {
+        let step = self.world.integration_parameters.dt;
+        self.accumulator += dt;
+
+        let mut steps = 0;
+        while self.accumulator >= step && steps < MAX_PHYSICS_STEPS {
+            self.world.step();
+            self.accumulator -= step;
+            steps += 1;
+        }
+        if steps == MAX_PHYSICS_STEPS {
+            self.accumulator = self.accumulator.min(step);
+        }
+        steps
+    }
+
+    /// Writes the pose of every bound body into its slot, keeping the slot's scale since
+    /// rigid bodies don't have one. Slots past the end of `transforms` are skipped.
+    pub fn sync_transforms(&self, transforms: &mut [Mat4]) {
+        for (_, body) in self.world.bodies.iter() {
+            let Some(transform) = body
+                .user_data
+                .checked_sub(1)
+                .and_then(|slot| transforms.get_mut(slot as usize))
+            else {
+                continue;
+            };
+            let (scale, _, _) = transform.to_scale_rotation_translation();
+            *transform = body_transform(body) * Mat4::from_scale(scale);
+        }
+    }
+
+    /// Appends the collider shapes, and whatever else `debug_mode` selects, to `debug_draw`.
+    pub fn draw_debug(&mut self, debug_draw: &mut DebugDraw) {
+        self.debug_render.mode = self.debug_mode;
+        self.world
+            .debug_render(&mut self.debug_render, &mut DebugDrawBackend(debug_draw));
+    }
+}
+
+/// World transform of `body`, without scale.
+pub fn body_transform(body: &RigidBody) -> Mat4 {
+    let pose = body.position();
+    Mat4::from_rotation_translation(
+        Quat::from_array(pose.rotation.to_array()),
+        Vec3::from_array(pose.translation.to_array()),
+    )
+}
+
+struct DebugDrawBackend<'a>(&'a mut DebugDraw);
+
+impl DebugRenderBackend for DebugDrawBackend<'_> {
+    fn draw_line(&mut self, _object: DebugRenderObject, a: Vector, b: Vector, color: DebugColor) {
+        self.0.line(
+            Vec3::from_array(a.to_array()),
+            Vec3::from_array(b.to_array()),
+            hsla_to_rgba(color),
+        );
+    }
+}
+
+/// Converts rapier's HSLA debug colors (hue in degrees) to linear RGBA.
+fn hsla_to_rgba([hue, saturation, lightness, alpha]: DebugColor) -> Vec4 {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma * 0.5;
+    Vec4::new(r + m, g + m, b + m, alpha)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rapier3d::dynamics::RigidBodyBuilder;
+    use rapier3d::geometry::ColliderBuilder;
+
+    #[test]
+    fn converts_debug_colors() {
+        assert_eq!(
+            hsla_to_rgba([0.0, 1.0, 0.5, 1.0]),
+            Vec4::new(1.0, 0.0, 0.0, 1.0)
+        );
+        assert_eq!(
+            hsla_to_rgba([120.0, 1.0, 0.5, 0.5]),
+            Vec4::new(0.0, 1.0, 0.0, 0.5)
+        );
+        assert_eq!(hsla_to_rgba([240.0, 0.0, 1.0, 1.0]), Vec4::ONE);
+    }
+
+    #[test]
+    fn falling_body_drives_its_transform_slot() {
+        let mut scene = PhysicsScene::new();
+        let body = scene
+            .world
+            .bodies
+            .insert(RigidBodyBuilder::dynamic().translation(Vector::new(0.0, 10.0, 0.0)));
+        let collider = ColliderBuilder::ball(0.5).build();
+        let world = &mut scene.world;
+        world
+            .colliders
+            .insert_with_parent(collider, body, &mut world.bodies);
+        scene.bind(body, 1);
+
+        let steps: u32 = (0..5).map(|_| scene.step(0.1)).sum();
+        assert_eq!(
+            steps,
+            (0.5 / scene.world.integration_parameters.dt).round() as u32
+        );
+
+        // A long frame is capped rather than simulated in full.
+        let mut capped = PhysicsScene::new();
+        assert_eq!(capped.step(1.0), MAX_PHYSICS_STEPS);
+
+        let mut transforms = [Mat4::IDENTITY, Mat4::from_scale(Vec3::splat(2.0))];
+        scene.sync_transforms(&mut transforms);
+        assert_eq!(transforms[0], Mat4::IDENTITY);
+        let (scale, _, translation) = transforms[1].to_scale_rotation_translation();
+        assert!(scale.abs_diff_eq(Vec3::splat(2.0), 1e-5));
+        assert!(translation.y < 10.0 - 0.5 * 9.81 * 0.5 * 0.5 * 0.9);
+
+        let mut debug_draw = DebugDraw::new();
+        scene.draw_debug(&mut debug_draw);
+        assert!(debug_draw.line_count() > 0);
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::{Result, bail};
+use ash::{Device, vk};
+use std::ffi::CString;
+use std::sync::Arc;
+
+use crate::pipeline::create_shader_module;
+use crate::vulkan::{RecordCommands, VulkanDevice};
+
+pub struct VulkanComputePipeline {
+    pub pipeline: vk::Pipeline,
+    pub layout: vk::PipelineLayout,
+    device: Arc<Device>,
+}
+
+pub struct VulkanComputePipelineBuilder {
+    device: Arc<Device>,
+    shader: Option<(vk::ShaderModule, CString)>,
+    descriptor_set_layouts: Vec<vk::DescriptorSetLayout>,
+    push_constant_ranges: Vec<vk::PushConstantRange>,
+    flags: vk::PipelineCreateFlags,
+}
+
+impl VulkanComputePipelineBuilder {
+    pub fn new(device: &VulkanDevice) -> Self {
+        Self {
+            device: device.device.clone(),
+            shader: None,
+            descriptor_set_layouts: Vec::new(),
+            push_constant_ranges: Vec::new(),
+            flags: vk::PipelineCreateFlags::empty(),
+        }
+    }
+
+    pub fn with_shader_spv(mut self, code: &[u8], entry_point: Option<&CString>) -> Result<Self> {
+        let module = create_shader_module(&self.device, code)?;
+        let name_cstr = match entry_point {
+            Some(c) => c.to_owned(),
+            None => CString::new("main").unwrap(),
+        };
+
+        if let Some((previous, _)) = self.shader.replace((module, name_cstr)) {
+            unsafe { self.device.destroy_shader_module(previous, None) };
+        }
+
+        Ok(self)
+    }
+
+    /// Uses the compute entry point of a WGSL module, or the one called `entry_point`.
+    #[cfg(feature = "wgsl")]
+    pub fn with_wgsl(
+        self,
+        shader: &crate::pipeline::WgslShader,
+        entry_point: Option<&str>,
+    ) -> Result<Self> {
+        let entry = shader.entry_point(vk::ShaderStageFlags::COMPUTE, entry_point)?;
+        self.with_shader_spv(&shader.spirv, Some(&entry))
+    }
+
+    pub fn with_descriptor_set_layout(mut self, layout: vk::DescriptorSetLayout) -> Self {
+        self.descriptor_set_layouts.push(layout);
+        self
+    }
+
+    pub fn with_push_constant_range(mut self, range: vk::PushConstantRange) -> Self {
+        self.push_constant_ranges.push(range);
+        self
+    }
+
+    /// E.g. `DESCRIPTOR_BUFFER_EXT` for pipelines bound with a `DescriptorBuffer`.
+    pub fn with_flags(mut self, flags: vk::PipelineCreateFlags) -> Self {
+        self.flags |= flags;
+        self
+    }
+
+    pub fn build(mut self) -> Result<VulkanComputePipeline> {
+        let (module, entry_point) = match self.shader.take() {
+            Some(shader) => shader,
+            None => bail!("a compute shader is required"),
+        };
+
+        let layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(&self.descriptor_set_layouts)
+            .push_constant_ranges(&self.push_constant_ranges);
+        let layout = unsafe { self.device.create_pipeline_layout(&layout_info, None)? };
+
+        let stage_info = vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(module)
+            .name(entry_point.as_c_str());
+
+        let pipeline_info = vk::ComputePipelineCreateInfo::default()
+            .flags(self.flags)
+            .stage(stage_info)
+            .layout(layout);
+
+        let pipeline = unsafe {
+            self.device.create_compute_pipelines(
+                vk::PipelineCache::null(),
+                std::slice::from_ref(&pipeline_info),
+                None,
+            )
+        };
+
+        unsafe { self.device.destroy_shader_module(module, None) };
+
+        let pipeline = match pipeline {
+            Ok(pipelines) => pipelines[0],
+            Err((_, e)) => {
+                unsafe { self.device.destroy_pipeline_layout(layout, None) };
+                return Err(e.into());
+            }
+        };
+
+        Ok(VulkanComputePipeline {
+            pipeline,
+            layout,
+            device: self.device.clone(),
+        })
+    }
+}
+
+impl Drop for VulkanComputePipelineBuilder {
+    fn drop(&mut self) {
+        if let Some((module, _)) = self.shader.take() {
+            unsafe { self.device.destroy_shader_module(module, None) };
+        }
+    }
+}
+
+impl VulkanComputePipeline {
+    pub fn bind(&self, command_buffer: vk::CommandBuffer) {
+        self.device.bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.pipeline,
+        );
+    }
+
+    pub fn bind_descriptor_sets(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        first_set: u32,
+        descriptor_sets: &[vk::DescriptorSet],
+    ) {
+        self.device.bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.layout,
+            first_set,
+            descriptor_sets,
+            &[],
+        );
+    }
+
+    pub fn push_constants<T: Copy>(&self, command_buffer: vk::CommandBuffer, constants: &T) {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(constants as *const T as *const u8, std::mem::size_of::<T>())
+        };
+
+        unsafe {
+            self.device.cmd_push_constants(
+                command_buffer,
+                self.layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                bytes,
+            );
+        }
+    }
+
+    pub fn dispatch(&self, command_buffer: vk::CommandBuffer, x: u32, y: u32, z: u32) {
+        self.device.dispatch(command_buffer, x, y, z);
+    }
+}
+
+impl Drop for VulkanComputePipeline {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device.destroy_pipeline_layout(self.layout, None);
+        }
+    }
+}
@@ -0,0 +1,453 @@
+use anyhow::Result;
+use ash::vk;
+
+use crate::pipeline::VulkanComputePipelineBuilder;
+use crate::vulkan::{
+    QueueFamilyIndices, VulkanBuffer, VulkanCommandPool, VulkanDescriptorPool,
+    VulkanDescriptorSetLayout, VulkanDevice, VulkanInstance, VulkanPhysicalDevice,
+};
+
+/// Headless compute context for testing kernels against CPU references: owns its own
+/// instance and device, fills host-visible storage buffers, records and waits for one-shot
+/// submissions, and reads buffers back as typed slices.
+///
+/// ```ignore
+/// let harness = ComputeHarness::new()?;
+/// let input = harness.buffer_from(&[1u32, 2, 3])?;
+/// let output = harness.zeroed_buffer::<u32>(3)?;
+/// harness.dispatch(&spirv, &[&input, &output], &3u32, [1, 1, 1])?;
+/// let result: Vec<u32> = harness.read(&output)?;
+/// ```
+pub struct ComputeHarness {
+    command_pool: VulkanCommandPool,
+    pub device: VulkanDevice,
+    pub physical_device: VulkanPhysicalDevice,
+    _instance: VulkanInstance,
+}
+
+impl ComputeHarness {
+    pub fn new() -> Result<Self> {
+        let instance = VulkanInstance::new(&[])?;
+        let physical_device = VulkanPhysicalDevice::select_best_device(&instance)?;
+
+        let queue_family_properties = unsafe {
+            instance
+                .instance
+                .get_physical_device_queue_family_properties(physical_device.physical_device)
+        };
+        let compute_family = queue_family_properties
+            .iter()
+            .position(|family| family.queue_flags.contains(vk::QueueFlags::COMPUTE))
+            .ok_or_else(|| anyhow::anyhow!("Device has no compute queue"))?
+            as u32;
+        // Submissions go to `graphics_queue`, the queue `VulkanCommandPool` allocates for.
+        let queue_families = QueueFamilyIndices {
+            graphics_family: Some(compute_family),
+            compute_family: None,
+            transfer_family: None,
+            present_family: None,
+        };
+
+        let device = VulkanDevice::new(&instance, &physical_device, queue_families.clone())?;
+        let command_pool = VulkanCommandPool::new(&device, queue_families, 1)?;
+        Ok(Self {
+            command_pool,
+            device,
+            physical_device,
+            _instance: instance,
+        })
+    }
+
+    /// Host-visible storage buffer holding `data`.
+    pub fn buffer_from<T: Copy>(&self, data: &[T]) -> Result<VulkanBuffer> {
+        let buffer = self.storage_buffer(std::mem::size_of_val(data))?;
+        buffer.write(0, data)?;
+        Ok(buffer)
+    }
+
+    /// Host-visible storage buffer of `count` zeroed `T`s.
+    pub fn zeroed_buffer<T: Copy>(&self, count: usize) -> Result<VulkanBuffer> {
+        let buffer = self.storage_buffer(count * std::mem::size_of::<T>())?;
+        buffer.write(0, &vec![0u8; buffer.size as usize])?;
+        Ok(buffer)
+    }
+
+    fn storage_buffer(&self, size: usize) -> Result<VulkanBuffer> {
+        // Zero-sized buffers are invalid; keep one word so empty inputs still bind.
+        VulkanBuffer::new_host_visible(
+            &self.device,
+            &self.physical_device,
+            size.max(4) as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_SRC
+                | vk::BufferUsageFlags::TRANSFER_DST,
+        )
+    }
+
+    /// Every `T` in `buffer`, after the last submission finished.
+    pub fn read<T: Copy>(&self, buffer: &VulkanBuffer) -> Result<Vec<T>> {
+        buffer.read(0, buffer.size as usize / std::mem::size_of::<T>())
+    }
+
+    /// Records commands with `record`, submits them and waits. Shader and transfer writes are
+    /// made visible to the host afterwards.
+    pub fn submit(&self, record: impl FnOnce(vk::CommandBuffer)) -> Result<()> {
+        self.command_pool
+            .immediate_submit(self.device.graphics_queue, |encoder| {
+                record(encoder.command_buffer());
+
+                encoder.memory_barrier(
+                    vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER,
+                    vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::TRANSFER_WRITE,
+                    vk::PipelineStageFlags::HOST,
+                    vk::AccessFlags::HOST_READ,
+                );
+            })
+            .map_err(|e| anyhow::anyhow!("Failed to run compute work: {}", e))
+    }
+
+    /// Runs a compute kernel once and waits. `buffers[i]` is bound as the storage buffer at
+    /// set 0, binding `i`; `push_constants` is pushed at offset 0 unless it is zero-sized,
+    /// e.g. `&()`.
+    pub fn dispatch<P: Copy>(
+        &self,
+        spirv: &[u8],
+        buffers: &[&VulkanBuffer],
+        push_constants: &P,
+        group_count: [u32; 3],
+    ) -> Result<()> {
+        let layout = VulkanDescriptorSetLayout::new(
+            &self.device,
+            &(0..buffers.len() as u32)
+                .map(|binding| {
+                    VulkanDescriptorSetLayout::binding(
+                        binding,
+                        vk::DescriptorType::STORAGE_BUFFER,
+                        vk::ShaderStageFlags::COMPUTE,
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )?;
+
+        let mut builder = VulkanComputePipelineBuilder::new(&self.device)
+            .with_shader_spv(spirv, None)?
+            .with_descriptor_set_layout(layout.layout);
+        let push_constant_size = std::mem::size_of::<P>() as u32;
+        if push_constant_size > 0 {
+            builder = builder.with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(push_constant_size),
+            );
+        }
+        let pipeline = builder.build()?;
+
+        let descriptor_pool = if buffers.is_empty() {
+            None
+        } else {
+            Some(VulkanDescriptorPool::for_layout(&self.device, &layout, 1)?)
+        };
+        let descriptor_set = descriptor_pool
+            .as_ref()
+            .map(|pool| -> Result<vk::DescriptorSet> {
+                let set = pool.allocate(&layout)?;
+                for (binding, buffer) in buffers.iter().enumerate() {
+                    pool.write_buffer(
+                        set,
+                        binding as u32,
+                        vk::DescriptorType::STORAGE_BUFFER,
+                        buffer,
+                    );
+                }
+                Ok(set)
+            })
+            .transpose()?;
+
+        self.submit(|command_buffer| {
+            pipeline.bind(command_buffer);
+            if let Some(set) = descriptor_set {
+                pipeline.bind_descriptor_sets(command_buffer, 0, &[set]);
+            }
+            if push_constant_size > 0 {
+                pipeline.push_constants(command_buffer, push_constants);
+            }
+            let [x, y, z] = group_count;
+            pipeline.dispatch(command_buffer, x, y, z);
+        })
+    }
+}
+
+impl Drop for ComputeHarness {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.device.device.device_wait_idle();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::JobSystem;
+    use crate::pipeline::{
+        GlslcCompiler, PrefixSum, RadixSort, ShaderPermutationCache, SubgroupPipelines,
+    };
+    use crate::renderer::{GpuVertex, MorphTarget, cull_spheres, frustum_planes, morph_vertices};
+    use glam::{Mat4, Vec3, Vec4};
+    use std::path::Path;
+
+    // GPU tests are ignored by default; run them with `cargo test -- --ignored` on a machine
+    // with a Vulkan device and glslc. They fail rather than skip when either is missing.
+    fn harness() -> ComputeHarness {
+        ComputeHarness::new().expect("Failed to create compute harness")
+    }
+
+    fn subgroup_pipelines(harness: &ComputeHarness) -> SubgroupPipelines {
+        let mut shaders = ShaderPermutationCache::new(GlslcCompiler::new());
+        let templates = Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders/subgroup");
+        SubgroupPipelines::new(
+            &harness.device,
+            &harness.physical_device,
+            &mut shaders,
+            &templates,
+        )
+        .expect("Failed to build subgroup pipelines")
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn morph_kernel_matches_cpu_reference() {
+        let harness = harness();
+
+        let vertices: Vec<GpuVertex> = (0..100)
+            .map(|i| GpuVertex {
+                position: [i as f32, 0.0, 1.0],
+                normal: [0.0, 1.0, 0.0],
+                uv: [0.5, i as f32 / 100.0],
+            })
+            .collect();
+        let targets = [MorphTarget {
+            position_deltas: vec![[0.0, 1.0, 0.0]; 100],
+            normal_deltas: vec![[1.0, 0.0, 0.0]; 100],
+        }];
+        let weights = [0.5f32];
+
+        let deltas: Vec<[f32; 4]> = targets
+            .iter()
+            .flat_map(|target| {
+                target
+                    .position_deltas
+                    .iter()
+                    .zip(&target.normal_deltas)
+                    .flat_map(|(p, n)| [[p[0], p[1], p[2], 0.0], [n[0], n[1], n[2], 0.0]])
+            })
+            .collect();
+        let base = harness.buffer_from(&vertices).unwrap();
+        let deltas = harness.buffer_from(&deltas).unwrap();
+        let weight_buffer = harness.buffer_from(&weights).unwrap();
+        let morphed = harness.zeroed_buffer::<GpuVertex>(vertices.len()).unwrap();
+        harness
+            .dispatch(
+                include_bytes!("../../bin/morph.comp.spv"),
+                &[&base, &deltas, &weight_buffer, &morphed],
+                &[vertices.len() as u32, targets.len() as u32],
+                [(vertices.len() as u32).div_ceil(64), 1, 1],
+            )
+            .unwrap();
+
+        let expected = morph_vertices(&vertices, &targets, &weights);
+        let actual: Vec<GpuVertex> = harness.read(&morphed).unwrap();
+        for (actual, expected) in actual.iter().zip(&expected) {
+            for (a, e) in actual.position.iter().zip(&expected.position) {
+                assert!((a - e).abs() < 1e-5);
+            }
+            for (a, e) in actual.normal.iter().zip(&expected.normal) {
+                assert!((a - e).abs() < 1e-5);
+            }
+            assert_eq!(actual.uv, expected.uv);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device and glslc"]
+    fn prefix_sum_and_radix_sort_match_cpu_reference() {
+        let harness = harness();
+        let pipelines = subgroup_pipelines(&harness);
+
+        // Spans several workgroups and a partial last block.
+        let values: Vec<u32> = (0..1000u32).map(|i| (i * 7919) % 13).collect();
+        let input = harness.buffer_from(&values).unwrap();
+        let output = harness.zeroed_buffer::<u32>(values.len()).unwrap();
+        let scan = PrefixSum::new(
+            &harness.device,
+            &harness.physical_device,
+            &pipelines,
+            &input,
+            &output,
+            values.len() as u32,
+        )
+        .unwrap();
+        harness
+            .submit(|command_buffer| scan.record(command_buffer, &pipelines, &output))
+            .unwrap();
+
+        let expected: Vec<u32> = values
+            .iter()
+            .scan(0, |sum, &value| {
+                let exclusive = *sum;
+                *sum += value;
+                Some(exclusive)
+            })
+            .collect();
+        assert_eq!(harness.read::<u32>(&output).unwrap(), expected);
+
+        let keys: Vec<u32> = (0..1000u32)
+            .map(|i| i.wrapping_mul(2654435761) >> 16)
+            .collect();
+        let payload: Vec<u32> = (0..1000).collect();
+        let key_buffer = harness.buffer_from(&keys).unwrap();
+        let value_buffer = harness.buffer_from(&payload).unwrap();
+        let sort = RadixSort::new(
+            &harness.device,
+            &harness.physical_device,
+            &pipelines,
+            &key_buffer,
+            &value_buffer,
+            keys.len() as u32,
+        )
+        .unwrap();
+        harness
+            .submit(|command_buffer| {
+                sort.record(command_buffer, &pipelines, &key_buffer, &value_buffer, 16)
+            })
+            .unwrap();
+
+        let mut expected: Vec<(u32, u32)> = keys.iter().copied().zip(payload).collect();
+        expected.sort_by_key(|&(key, _)| key);
+        let sorted: Vec<(u32, u32)> = harness
+            .read::<u32>(&key_buffer)
+            .unwrap()
+            .into_iter()
+            .zip(harness.read::<u32>(&value_buffer).unwrap())
+            .collect();
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn cull_kernel_matches_cpu_reference() {
+        // Mirror the structs in `gpu_cull.comp`.
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        struct Object {
+            model: Mat4,
+            indices: [u32; 4],
+        }
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        struct Mesh {
+            first_index: u32,
+            index_count: u32,
+            vertex_offset: i32,
+            padding: u32,
+            bounds: Vec4,
+        }
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        struct Cull {
+            planes: [Vec4; 6],
+            object_count: u32,
+        }
+
+        let harness = harness();
+        let view_proj = Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, 0.1, 100.0)
+            * Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+
+        // The second mesh's sphere is offset from its origin, and some objects are scaled, so
+        // the kernel's transform of the bounds is covered too.
+        let meshes = [
+            Mesh {
+                first_index: 0,
+                index_count: 36,
+                vertex_offset: 0,
+                padding: 0,
+                bounds: Vec4::new(0.0, 0.0, 0.0, 1.0),
+            },
+            Mesh {
+                first_index: 36,
+                index_count: 6,
+                vertex_offset: 24,
+                padding: 0,
+                bounds: Vec4::new(0.0, 2.0, 0.0, 0.5),
+            },
+        ];
+        let placements = [
+            (Vec3::new(0.0, 0.0, -10.0), 1.0, 0),
+            (Vec3::new(0.0, 0.0, 10.0), 1.0, 0),
+            (Vec3::new(50.0, 0.0, -10.0), 1.0, 1),
+            (Vec3::new(10.5, 0.0, -10.0), 1.0, 0),
+            (Vec3::new(0.0, 0.0, -200.0), 1.0, 1),
+            (Vec3::new(14.0, 0.0, -10.0), 6.0, 0),
+            (Vec3::new(0.0, -3.0, -20.0), 2.0, 1),
+        ];
+        let objects: Vec<Object> = placements
+            .iter()
+            .map(|&(translation, scale, mesh)| Object {
+                model: Mat4::from_translation(translation) * Mat4::from_scale(Vec3::splat(scale)),
+                indices: [mesh, 0, 0, 0],
+            })
+            .collect();
+
+        let object_buffer = harness.buffer_from(&objects).unwrap();
+        let mesh_buffer = harness.buffer_from(&meshes).unwrap();
+        let commands = harness.zeroed_buffer::<[u32; 5]>(objects.len()).unwrap();
+        let draw_count = harness.zeroed_buffer::<u32>(1).unwrap();
+        harness
+            .dispatch(
+                include_bytes!("../../bin/gpu_cull.comp.spv"),
+                &[&object_buffer, &mesh_buffer, &commands, &draw_count],
+                &Cull {
+                    planes: frustum_planes(view_proj),
+                    object_count: objects.len() as u32,
+                },
+                [(objects.len() as u32).div_ceil(64), 1, 1],
+            )
+            .unwrap();
+
+        let spheres: Vec<Vec4> = objects
+            .iter()
+            .map(|object| {
+                let bounds = meshes[object.indices[0] as usize].bounds;
+                let scale = object.model.x_axis.truncate().length();
+                object
+                    .model
+                    .transform_point3(bounds.truncate())
+                    .extend(bounds.w * scale)
+            })
+            .collect();
+        let jobs = JobSystem::new(1).unwrap();
+        let expected = cull_spheres(&jobs, view_proj, &spheres).unwrap();
+        assert_eq!(expected, [0, 3, 5, 6]);
+
+        // Draws are appended in whatever order invocations finish.
+        let count = harness.read::<u32>(&draw_count).unwrap()[0] as usize;
+        let mut draws = harness.read::<[u32; 5]>(&commands).unwrap();
+        draws.truncate(count);
+        draws.sort_by_key(|draw| draw[4]);
+        let visible: Vec<u32> = draws.iter().map(|draw| draw[4]).collect();
+        assert_eq!(visible, expected);
+        for draw in &draws {
+            let mesh = &meshes[objects[draw[4] as usize].indices[0] as usize];
+            assert_eq!(
+                draw[..4],
+                [
+                    mesh.index_count,
+                    1,
+                    mesh.first_index,
+                    mesh.vertex_offset as u32
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use anyhow::Result;
+use ash::vk;
+use std::path::PathBuf;
+
+use crate::pipeline::{ShaderCompiler, ShaderReflection, run_shader_tool};
+
+/// Locations the renderer's vertex layouts use, by HLSL semantic, as in
+/// `material_vertex_input`.
+pub const HLSL_VERTEX_SEMANTICS: &[(&str, u32)] = &[
+    ("POSITION0", 0),
+    ("NORMAL0", 1),
+    ("TEXCOORD0", 2),
+    ("TANGENT0", 3),
+    ("BLENDINDICES0", 4),
+    ("BLENDWEIGHT0", 5),
+];
+
+/// Upper-cases a semantic and gives it an explicit index, so `texcoord` matches `TEXCOORD0`.
+pub fn normalize_semantic(semantic: &str) -> String {
+    let semantic = semantic.to_ascii_uppercase();
+    if semantic.ends_with(|c: char| c.is_ascii_digit()) {
+        semantic
+    } else {
+        format!("{}0", semantic)
+    }
+}
+
+/// Location of `semantic` in the renderer's vertex layouts, see `HLSL_VERTEX_SEMANTICS`.
+pub fn semantic_location(semantic: &str) -> Option<u32> {
+    let semantic = normalize_semantic(semantic);
+    HLSL_VERTEX_SEMANTICS
+        .iter()
+        .find(|(name, _)| *name == semantic)
+        .map(|(_, location)| *location)
+}
+
+/// Retargets vertex attributes laid out for the renderer's locations (see
+/// `HLSL_VERTEX_SEMANTICS`) to the locations DXC assigned to the shader's inputs, matching them
+/// by semantic. The shader must be compiled with `-fspv-reflect`, which `DxcCompiler` does.
+pub fn semantic_vertex_attributes(
+    reflection: &ShaderReflection,
+    attributes: &[vk::VertexInputAttributeDescription],
+) -> Result<Vec<vk::VertexInputAttributeDescription>> {
+    reflection
+        .stage_inputs(vk::ShaderStageFlags::VERTEX)
+        .map(|input| {
+            let semantic = input.semantic.as_deref().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Vertex input at location {} has no semantic, compile with -fspv-reflect",
+                    input.location
+                )
+            })?;
+            let location = semantic_location(semantic)
+                .ok_or_else(|| anyhow::anyhow!("Unknown vertex semantic {}", semantic))?;
+            let attribute = attributes
+                .iter()
+                .find(|attribute| attribute.location == location)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Vertex layout provides no attribute for {}", semantic)
+                })?;
+
+            Ok(attribute.location(input.location))
+        })
+        .collect()
+}
+
+fn hlsl_profile_prefix(stage: vk::ShaderStageFlags) -> Result<&'static str> {
+    Ok(match stage {
+        vk::ShaderStageFlags::VERTEX => "vs",
+        vk::ShaderStageFlags::FRAGMENT => "ps",
+        vk::ShaderStageFlags::COMPUTE => "cs",
+        vk::ShaderStageFlags::GEOMETRY => "gs",
+        vk::ShaderStageFlags::TESSELLATION_CONTROL => "hs",
+        vk::ShaderStageFlags::TESSELLATION_EVALUATION => "ds",
+        _ => return Err(anyhow::anyhow!("Unsupported shader stage {:?}", stage)),
+    })
+}
+
+/// Compiles HLSL with `dxc` (or the compiler named by the `DXC` environment variable).
+///
+/// Works with `ShaderPermutationCache` like `GlslcCompiler`: defines are injected as
+/// `#define` lines at the top of the source, and errors are mapped back to the original files.
+pub struct DxcCompiler {
+    executable: PathBuf,
+    entry_point: String,
+    shader_model: String,
+}
+
+impl DxcCompiler {
+    pub fn new() -> Self {
+        Self {
+            executable: std::env::var_os("DXC")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("dxc")),
+            entry_point: "main".to_string(),
+            shader_model: "6_0".to_string(),
+        }
+    }
+
+    pub fn with_executable(mut self, executable: impl Into<PathBuf>) -> Self {
+        self.executable = executable.into();
+        self
+    }
+
+    pub fn with_entry_point(mut self, entry_point: &str) -> Self {
+        self.entry_point = entry_point.to_string();
+        self
+    }
+
+    /// Shader model of the target profile, e.g. `6_6` for `vs_6_6`.
+    pub fn with_shader_model(mut self, shader_model: &str) -> Self {
+        self.shader_model = shader_model.to_string();
+        self
+    }
+
+    /// Target profile for `stage`, e.g. `ps_6_0`.
+    pub fn profile(&self, stage: vk::ShaderStageFlags) -> Result<String> {
+        Ok(format!(
+            "{}_{}",
+            hlsl_profile_prefix(stage)?,
+            self.shader_model
+        ))
+    }
+}
+
+impl Default for DxcCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderCompiler for DxcCompiler {
+    fn compile(&self, source: &str, stage: vk::ShaderStageFlags, label: &str) -> Result<Vec<u8>> {
+        let profile = self.profile(stage)?;
+
+        run_shader_tool(
+            &self.executable,
+            source,
+            label,
+            "hlsl",
+            |command, input, output| {
+                command
+                    .arg("-spirv")
+                    .arg("-fspv-target-env=vulkan1.3")
+                    .arg("-fspv-reflect")
+                    .arg("-T")
+                    .arg(&profile)
+                    .arg("-E")
+                    .arg(&self.entry_point)
+                    .arg("-Fo")
+                    .arg(output)
+                    .arg(input);
+            },
+        )
+    }
+}
+
+/// Compiles Slang with `slangc` (or the compiler named by the `SLANGC` environment variable).
+pub struct SlangCompiler {
+    executable: PathBuf,
+    entry_point: String,
+}
+
+impl SlangCompiler {
+    pub fn new() -> Self {
+        Self {
+            executable: std::env::var_os("SLANGC")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("slangc")),
+            entry_point: "main".to_string(),
+        }
+    }
+
+    pub fn with_executable(mut self, executable: impl Into<PathBuf>) -> Self {
+        self.executable = executable.into();
+        self
+    }
+
+    pub fn with_entry_point(mut self, entry_point: &str) -> Self {
+        self.entry_point = entry_point.to_string();
+        self
+    }
+}
+
+impl Default for SlangCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderCompiler for SlangCompiler {
+    fn compile(&self, source: &str, stage: vk::ShaderStageFlags, label: &str) -> Result<Vec<u8>> {
+        let stage_name = match stage {
+            vk::ShaderStageFlags::VERTEX => "vertex",
+            vk::ShaderStageFlags::FRAGMENT => "fragment",
+            vk::ShaderStageFlags::COMPUTE => "compute",
+            _ => return Err(anyhow::anyhow!("Unsupported shader stage {:?}", stage)),
+        };
+
+        run_shader_tool(
+            &self.executable,
+            source,
+            label,
+            "slang",
+            |command, input, output| {
+                command
+                    .arg(input)
+                    .arg("-target")
+                    .arg("spirv")
+                    .arg("-profile")
+                    .arg("spirv_1_6")
+                    .arg("-stage")
+                    .arg(stage_name)
+                    .arg("-entry")
+                    .arg(&self.entry_point)
+                    .arg("-o")
+                    .arg(output);
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::{MaterialFeatures, StageInput, material_vertex_input};
+
+    #[test]
+    fn maps_semantics_to_renderer_locations() {
+        assert_eq!(semantic_location("POSITION"), Some(0));
+        assert_eq!(semantic_location("texcoord"), Some(2));
+        assert_eq!(semantic_location("TEXCOORD1"), None);
+        assert_eq!(normalize_semantic("Normal"), "NORMAL0");
+    }
+
+    #[test]
+    fn retargets_attributes_to_shader_locations() {
+        // DXC numbers inputs in declaration order: here uv, then position.
+        let reflection = ShaderReflection {
+            inputs: [(0, "TEXCOORD"), (1, "POSITION")]
+                .into_iter()
+                .map(|(location, semantic)| StageInput {
+                    location,
+                    stage: vk::ShaderStageFlags::VERTEX,
+                    name: None,
+                    semantic: Some(semantic.into()),
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let (_, attributes) = material_vertex_input(MaterialFeatures::NONE);
+
+        let retargeted = semantic_vertex_attributes(&reflection, &attributes).unwrap();
+
+        assert_eq!(retargeted.len(), 2);
+        assert_eq!((retargeted[0].location, retargeted[0].offset), (0, 24));
+        assert_eq!((retargeted[1].location, retargeted[1].offset), (1, 0));
+
+        let tangent_only = ShaderReflection {
+            inputs: vec![StageInput {
+                semantic: Some("TANGENT".into()),
+                ..reflection.inputs[0].clone()
+            }],
+            ..Default::default()
+        };
+        assert!(semantic_vertex_attributes(&tangent_only, &attributes).is_err());
+    }
+
+    #[test]
+    fn selects_profiles_per_stage() {
+        let dxc = DxcCompiler::new().with_shader_model("6_6");
+        assert_eq!(
+            dxc.profile(vk::ShaderStageFlags::FRAGMENT).unwrap(),
+            "ps_6_6"
+        );
+        assert_eq!(
+            dxc.profile(vk::ShaderStageFlags::COMPUTE).unwrap(),
+            "cs_6_6"
+        );
+        assert!(dxc.profile(vk::ShaderStageFlags::MESH_EXT).is_err());
+    }
+
+    #[test]
+    fn reports_missing_compiler() {
+        let dxc = DxcCompiler::new().with_executable("/nonexistent/dxc");
+        let error = dxc
+            .compile(
+                "float4 main() : SV_Target { return 1; }",
+                vk::ShaderStageFlags::FRAGMENT,
+                "missing",
+            )
+            .unwrap_err();
+        assert!(error.to_string().contains("Failed to run"));
+    }
+}
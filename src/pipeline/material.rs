@@ -0,0 +1,870 @@
+use anyhow::Result;
+use ash::vk;
+use glam::{Mat4, Vec3, Vec4};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::assets::Asset;
+use crate::pipeline::{ShaderPermutationCache, VulkanPipeline, VulkanPipelineBuilder};
+use crate::renderer::GpuVertex;
+use crate::vulkan::{
+    VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanPhysicalDevice,
+};
+
+/// Shader features a material can enable. Each one is a `#define` in the template shaders.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct MaterialFeatures(u32);
+
+impl MaterialFeatures {
+    pub const NONE: Self = Self(0);
+    /// Discards fragments below `alpha_cutoff`.
+    pub const ALPHA_TEST: Self = Self(1 << 0);
+    /// Samples a tangent-space normal map; needs the tangent vertex stream.
+    pub const NORMAL_MAP: Self = Self(1 << 1);
+    /// Linear blend skinning; needs the skin vertex stream and a joint buffer in set 1.
+    pub const SKINNED: Self = Self(1 << 2);
+    /// Draws back faces too, shading them with the flipped normal, e.g. for cloth.
+    pub const DOUBLE_SIDED: Self = Self(1 << 3);
+    /// Samples the albedo and normal map as `sampler2DArray`s at the material's
+    /// `texture_layer`, e.g. for terrain splats or decal atlases. Only with
+    /// `MaterialBackend::Permutations`, since the uber-shaders bind plain 2D textures.
+    pub const TEXTURE_ARRAY: Self = Self(1 << 4);
+    /// Every feature the uber-shaders support.
+    pub const ALL: Self = Self(0b1111);
+
+    const NAMES: [(Self, &'static str); 5] = [
+        (Self::ALPHA_TEST, "ALPHA_TEST"),
+        (Self::NORMAL_MAP, "NORMAL_MAP"),
+        (Self::SKINNED, "SKINNED"),
+        (Self::DOUBLE_SIDED, "DOUBLE_SIDED"),
+        (Self::TEXTURE_ARRAY, "TEXTURE_ARRAY"),
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(feature, _)| *feature)
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Shader defines for the enabled features, in a fixed order.
+    pub fn defines(self) -> Vec<&'static str> {
+        Self::NAMES
+            .iter()
+            .filter(|(feature, _)| self.contains(*feature))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+impl std::ops::BitOr for MaterialFeatures {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for MaterialFeatures {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// A material read from a `.material` file:
+///
+/// ```text
+/// # Comments start with '#'.
+/// template = standard
+/// features = ALPHA_TEST, NORMAL_MAP
+/// base_color = 1.0 0.9 0.8 1.0
+/// alpha_cutoff = 0.5
+/// albedo = textures/leaves.ppm
+/// normal_map = textures/leaves_normal.ppm
+/// ```
+///
+/// Texture paths are relative to the material file. `TEXTURE_ARRAY` materials pick their layer
+/// with `texture_layer = 3`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialDefinition {
+    pub template: String,
+    pub features: MaterialFeatures,
+    pub base_color: Vec4,
+    pub alpha_cutoff: f32,
+    pub albedo: Option<PathBuf>,
+    pub normal_map: Option<PathBuf>,
+    /// Array layer sampled by `TEXTURE_ARRAY` materials.
+    pub texture_layer: u32,
+}
+
+impl Default for MaterialDefinition {
+    fn default() -> Self {
+        Self {
+            template: "standard".to_string(),
+            features: MaterialFeatures::NONE,
+            base_color: Vec4::ONE,
+            alpha_cutoff: 0.5,
+            albedo: None,
+            normal_map: None,
+            texture_layer: 0,
+        }
+    }
+}
+
+impl MaterialDefinition {
+    pub fn parse(source: &str, base_dir: &Path) -> Result<Self> {
+        let mut material = Self::default();
+
+        for (line_number, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error =
+                |message: String| anyhow::anyhow!("Material line {}: {}", line_number + 1, message);
+
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| error(format!("expected 'key = value', got '{}'", line)))?;
+
+            match key {
+                "template" => material.template = value.to_string(),
+                "features" => {
+                    material.features = MaterialFeatures::NONE;
+                    for name in value
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .filter(|name| !name.is_empty())
+                    {
+                        material.features |= MaterialFeatures::from_name(name)
+                            .ok_or_else(|| error(format!("unknown feature '{}'", name)))?;
+                    }
+                }
+                "base_color" => {
+                    let components = value
+                        .split_whitespace()
+                        .map(str::parse::<f32>)
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|e| error(format!("invalid base_color: {}", e)))?;
+                    material.base_color = match components[..] {
+                        [r, g, b] => Vec4::new(r, g, b, 1.0),
+                        [r, g, b, a] => Vec4::new(r, g, b, a),
+                        _ => return Err(error("base_color needs 3 or 4 components".into())),
+                    };
+                }
+                "alpha_cutoff" => {
+                    material.alpha_cutoff = value
+                        .parse()
+                        .map_err(|e| error(format!("invalid alpha_cutoff: {}", e)))?;
+                }
+                "texture_layer" => {
+                    material.texture_layer = value
+                        .parse()
+                        .map_err(|e| error(format!("invalid texture_layer: {}", e)))?;
+                }
+                "albedo" => material.albedo = Some(base_dir.join(value)),
+                "normal_map" => material.normal_map = Some(base_dir.join(value)),
+                _ => return Err(error(format!("unknown key '{}'", key))),
+            }
+        }
+
+        if material.features.contains(MaterialFeatures::NORMAL_MAP) && material.normal_map.is_none()
+        {
+            return Err(anyhow::anyhow!(
+                "Material enables NORMAL_MAP but has no normal_map texture"
+            ));
+        }
+
+        Ok(material)
+    }
+}
+
+impl Asset for MaterialDefinition {
+    fn load(path: &Path, bytes: &[u8]) -> Result<Self> {
+        let source = std::str::from_utf8(bytes)
+            .map_err(|e| anyhow::anyhow!("Failed to read material as UTF-8: {}", e))?;
+        Self::parse(source, path.parent().unwrap_or(Path::new("")))
+    }
+}
+
+/// Per-material uniform block, set 0 binding 0 of the material templates (std140).
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MaterialParams {
+    pub base_color: Vec4,
+    /// Direction the light travels in, world space; w unused.
+    pub light_direction: Vec4,
+    pub alpha_cutoff: f32,
+    /// `MaterialFeatures` bits, read by `MaterialBackend::UberDynamic`.
+    pub features: u32,
+    /// Array layer sampled with `TEXTURE_ARRAY`.
+    pub texture_layer: u32,
+    pub padding: f32,
+}
+
+/// Per-vertex joints and weights of the `SKINNED` stream, vertex binding 2.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SkinVertex {
+    pub joints: [u32; 4],
+    pub weights: [f32; 4],
+}
+
+/// Push constants shared by every material template.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MaterialTransforms {
+    pub view_proj: Mat4,
+    pub model: Mat4,
+}
+
+/// Vertex binding of the `GpuVertex` stream.
+pub const MATERIAL_VERTEX_BINDING: u32 = 0;
+/// Vertex binding of the `NORMAL_MAP` tangent stream (`[f32; 4]`, w is the handedness).
+pub const MATERIAL_TANGENT_BINDING: u32 = 1;
+/// Vertex binding of the `SKINNED` `SkinVertex` stream.
+pub const MATERIAL_SKIN_BINDING: u32 = 2;
+
+/// Vertex streams a permutation reads.
+pub fn material_vertex_input(
+    features: MaterialFeatures,
+) -> (
+    Vec<vk::VertexInputBindingDescription>,
+    Vec<vk::VertexInputAttributeDescription>,
+) {
+    let binding = |binding: u32, stride: usize| {
+        vk::VertexInputBindingDescription::default()
+            .binding(binding)
+            .stride(stride as u32)
+            .input_rate(vk::VertexInputRate::VERTEX)
+    };
+    let attribute = |binding: u32, location: u32, format: vk::Format, offset: usize| {
+        vk::VertexInputAttributeDescription::default()
+            .binding(binding)
+            .location(location)
+            .format(format)
+            .offset(offset as u32)
+    };
+
+    let mut bindings = vec![binding(
+        MATERIAL_VERTEX_BINDING,
+        std::mem::size_of::<GpuVertex>(),
+    )];
+    let mut attributes = vec![
+        attribute(MATERIAL_VERTEX_BINDING, 0, vk::Format::R32G32B32_SFLOAT, 0),
+        attribute(MATERIAL_VERTEX_BINDING, 1, vk::Format::R32G32B32_SFLOAT, 12),
+        attribute(MATERIAL_VERTEX_BINDING, 2, vk::Format::R32G32_SFLOAT, 24),
+    ];
+
+    if features.contains(MaterialFeatures::NORMAL_MAP) {
+        bindings.push(binding(MATERIAL_TANGENT_BINDING, 16));
+        attributes.push(attribute(
+            MATERIAL_TANGENT_BINDING,
+            3,
+            vk::Format::R32G32B32A32_SFLOAT,
+            0,
+        ));
+    }
+
+    if features.contains(MaterialFeatures::SKINNED) {
+        bindings.push(binding(
+            MATERIAL_SKIN_BINDING,
+            std::mem::size_of::<SkinVertex>(),
+        ));
+        attributes.push(attribute(
+            MATERIAL_SKIN_BINDING,
+            4,
+            vk::Format::R32G32B32A32_UINT,
+            0,
+        ));
+        attributes.push(attribute(
+            MATERIAL_SKIN_BINDING,
+            5,
+            vk::Format::R32G32B32A32_SFLOAT,
+            16,
+        ));
+    }
+
+    (bindings, attributes)
+}
+
+/// How `MaterialPipelineCache` turns material features into pipelines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum MaterialBackend {
+    /// One shader compile and pipeline per feature set from `<template>.vert/.frag`, with
+    /// unused features compiled out.
+    #[default]
+    Permutations,
+    /// One uber-shader (`<template>_uber.vert/.frag`) compiled once, specialized per feature
+    /// set with specialization constants: still one pipeline per feature set, but no shader
+    /// recompiles and the driver folds the branches away.
+    UberSpecialized,
+    /// One uber-shader pipeline for every material, branching on the feature bits in the
+    /// parameter block at runtime. Fewest pipelines and binds, most shader work.
+    UberDynamic,
+}
+
+impl MaterialBackend {
+    pub const ALL: [Self; 3] = [Self::Permutations, Self::UberSpecialized, Self::UberDynamic];
+
+    /// Features a pipeline is built for when materials with `features` are drawn.
+    pub fn pipeline_features(self, features: MaterialFeatures) -> MaterialFeatures {
+        match self {
+            Self::Permutations | Self::UberSpecialized => features,
+            Self::UberDynamic => MaterialFeatures::ALL,
+        }
+    }
+}
+
+/// Pipeline and descriptor layouts of one template permutation.
+pub struct MaterialPipeline {
+    pub pipeline: VulkanPipeline,
+    /// Set 0: parameters, albedo and, with `NORMAL_MAP`, the normal map.
+    pub material_layout: VulkanDescriptorSetLayout,
+    /// Set 1 with `SKINNED`: joint matrices.
+    pub skin_layout: Option<VulkanDescriptorSetLayout>,
+    /// Features the pipeline can draw; every feature for `MaterialBackend::UberDynamic`.
+    pub features: MaterialFeatures,
+    pub backend: MaterialBackend,
+}
+
+/// Builds and caches material pipelines from the GLSL templates in `template_dir`, replacing
+/// one hand-built pipeline per material variant. See `MaterialBackend` for how feature sets
+/// map to shaders and pipelines.
+///
+/// Pipelines depend on the render pass; call `clear` when it changes or a template is edited.
+pub struct MaterialPipelineCache {
+    template_dir: PathBuf,
+    shaders: ShaderPermutationCache,
+    backend: MaterialBackend,
+    render_pass: vk::RenderPass,
+    extent: vk::Extent2D,
+    pipelines: HashMap<(String, MaterialFeatures), Arc<MaterialPipeline>>,
+}
+
+impl MaterialPipelineCache {
+    pub fn new(
+        template_dir: impl Into<PathBuf>,
+        shaders: ShaderPermutationCache,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+    ) -> Self {
+        Self {
+            template_dir: template_dir.into(),
+            shaders,
+            backend: MaterialBackend::default(),
+            render_pass,
+            extent,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    pub fn with_backend(mut self, backend: MaterialBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn backend(&self) -> MaterialBackend {
+        self.backend
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    pub fn shaders(&self) -> &ShaderPermutationCache {
+        &self.shaders
+    }
+
+    /// Drops every pipeline and targets `render_pass` from now on. The caller must make sure
+    /// no in-flight frame still uses the old pipelines.
+    pub fn clear(&mut self, render_pass: vk::RenderPass, extent: vk::Extent2D) {
+        self.pipelines.clear();
+        self.shaders.clear();
+        self.render_pass = render_pass;
+        self.extent = extent;
+    }
+
+    pub fn get(
+        &mut self,
+        device: &VulkanDevice,
+        template: &str,
+        features: MaterialFeatures,
+    ) -> Result<Arc<MaterialPipeline>> {
+        let features = self.backend.pipeline_features(features);
+        let key = (template.to_string(), features);
+        if let Some(pipeline) = self.pipelines.get(&key) {
+            return Ok(pipeline.clone());
+        }
+
+        let pipeline = Arc::new(self.build(device, template, features)?);
+        self.pipelines.insert(key, pipeline.clone());
+        Ok(pipeline)
+    }
+
+    /// Creates a material instance with its own parameter buffer and descriptor sets.
+    pub fn create_material(
+        &mut self,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        definition: &MaterialDefinition,
+        textures: MaterialTextures,
+    ) -> Result<Material> {
+        let pipeline = self.get(device, &definition.template, definition.features)?;
+        Material::new(device, physical_device, pipeline, definition, textures)
+    }
+
+    fn build(
+        &mut self,
+        device: &VulkanDevice,
+        template: &str,
+        features: MaterialFeatures,
+    ) -> Result<MaterialPipeline> {
+        if features.contains(MaterialFeatures::TEXTURE_ARRAY)
+            && self.backend != MaterialBackend::Permutations
+        {
+            return Err(anyhow::anyhow!(
+                "TEXTURE_ARRAY materials need the Permutations material backend"
+            ));
+        }
+
+        let (source_name, defines, layout_features) = match self.backend {
+            MaterialBackend::Permutations => (template.to_string(), features.defines(), features),
+            MaterialBackend::UberSpecialized | MaterialBackend::UberDynamic => (
+                format!("{}_uber", template),
+                Vec::new(),
+                MaterialFeatures::ALL,
+            ),
+        };
+
+        let vertex = self.shaders.get(
+            &self.template_dir.join(format!("{}.vert", source_name)),
+            vk::ShaderStageFlags::VERTEX,
+            &defines,
+        )?;
+        let fragment = self.shaders.get(
+            &self.template_dir.join(format!("{}.frag", source_name)),
+            vk::ShaderStageFlags::FRAGMENT,
+            &defines,
+        )?;
+        let params_stages = if self.backend == MaterialBackend::Permutations {
+            vk::ShaderStageFlags::FRAGMENT
+        } else {
+            vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT
+        };
+
+        let mut bindings = vec![
+            VulkanDescriptorSetLayout::binding(
+                0,
+                vk::DescriptorType::UNIFORM_BUFFER,
+                params_stages,
+            ),
+            VulkanDescriptorSetLayout::binding(
+                1,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                vk::ShaderStageFlags::FRAGMENT,
+            ),
+        ];
+        if layout_features.contains(MaterialFeatures::NORMAL_MAP) {
+            bindings.push(VulkanDescriptorSetLayout::binding(
+                2,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                vk::ShaderStageFlags::FRAGMENT,
+            ));
+        }
+        let material_layout = VulkanDescriptorSetLayout::new(device, &bindings)?;
+
+        let skin_layout = layout_features
+            .contains(MaterialFeatures::SKINNED)
+            .then(|| {
+                VulkanDescriptorSetLayout::new(
+                    device,
+                    &[VulkanDescriptorSetLayout::binding(
+                        0,
+                        vk::DescriptorType::STORAGE_BUFFER,
+                        vk::ShaderStageFlags::VERTEX,
+                    )],
+                )
+            })
+            .transpose()?;
+
+        let mut builder = VulkanPipelineBuilder::new(device)
+            .set_render_pass(self.render_pass)
+            .set_extent(self.extent)
+            .with_vertex_spv(&vertex)?
+            .with_fragment_spv(&fragment)?
+            .with_descriptor_set_layout(material_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::VERTEX)
+                    .offset(0)
+                    .size(std::mem::size_of::<MaterialTransforms>() as u32),
+            )
+            .with_depth_test(true, true, vk::CompareOp::LESS)
+            .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]);
+
+        if let Some(skin_layout) = &skin_layout {
+            builder = builder.with_descriptor_set_layout(skin_layout.layout);
+        }
+
+        // Alpha-tested surfaces like foliage are usually single quads seen from both sides.
+        // The dynamic uber pipeline is shared with opaque materials and keeps back-face culling.
+        if (features.contains(MaterialFeatures::ALPHA_TEST)
+            || features.contains(MaterialFeatures::DOUBLE_SIDED))
+            && self.backend != MaterialBackend::UberDynamic
+        {
+            builder = builder.with_cull_mode(vk::CullModeFlags::NONE);
+        }
+
+        if self.backend != MaterialBackend::Permutations {
+            let stages = [vk::ShaderStageFlags::VERTEX, vk::ShaderStageFlags::FRAGMENT];
+            let dynamic = self.backend == MaterialBackend::UberDynamic;
+            for stage in stages {
+                builder = builder
+                    .with_specialization_constant(stage, 0, features.bits())
+                    .with_specialization_constant(stage, 1, dynamic as u32);
+            }
+        }
+
+        let (vertex_bindings, vertex_attributes) = material_vertex_input(layout_features);
+        for binding in vertex_bindings {
+            builder = builder.with_vertex_binding(binding);
+        }
+        for attribute in vertex_attributes {
+            builder = builder.with_vertex_attribute(attribute);
+        }
+
+        Ok(MaterialPipeline {
+            pipeline: builder.build()?,
+            material_layout,
+            skin_layout,
+            features,
+            backend: self.backend,
+        })
+    }
+}
+
+/// Textures bound by a material, all in `SHADER_READ_ONLY_OPTIMAL`. With `TEXTURE_ARRAY` they
+/// are `TYPE_2D_ARRAY` views, see `VulkanImageInfo::array`.
+#[derive(Debug, Clone, Copy)]
+pub struct MaterialTextures {
+    pub albedo: vk::ImageView,
+    /// Required with `NORMAL_MAP`.
+    pub normal_map: Option<vk::ImageView>,
+    pub sampler: vk::Sampler,
+}
+
+/// A material instance: shared permutation pipeline plus its own parameters and textures.
+pub struct Material {
+    pub pipeline: Arc<MaterialPipeline>,
+    params: MaterialParams,
+    params_buffer: VulkanBuffer,
+    descriptor_pool: VulkanDescriptorPool,
+    material_set: vk::DescriptorSet,
+    skin_set: Option<vk::DescriptorSet>,
+    _identity_joints: Option<VulkanBuffer>,
+}
+
+impl Material {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        pipeline: Arc<MaterialPipeline>,
+        definition: &MaterialDefinition,
+        textures: MaterialTextures,
+    ) -> Result<Self> {
+        if !pipeline.features.contains(definition.features) {
+            return Err(anyhow::anyhow!(
+                "Material features {:?} aren't supported by the pipeline",
+                definition.features.defines()
+            ));
+        }
+
+        let mut pool_sizes = pipeline.material_layout.pool_sizes(1);
+        if let Some(skin_layout) = &pipeline.skin_layout {
+            pool_sizes.extend(skin_layout.pool_sizes(1));
+        }
+        let descriptor_pool = VulkanDescriptorPool::new(device, &pool_sizes, 2)?;
+        let material_set = descriptor_pool.allocate(&pipeline.material_layout)?;
+        let skin_set = pipeline
+            .skin_layout
+            .as_ref()
+            .map(|layout| descriptor_pool.allocate(layout))
+            .transpose()?;
+
+        let params = MaterialParams {
+            base_color: definition.base_color,
+            light_direction: Vec3::new(-0.4, -1.0, -0.3).normalize().extend(0.0),
+            alpha_cutoff: definition.alpha_cutoff,
+            features: definition.features.bits(),
+            texture_layer: definition.texture_layer,
+            padding: 0.0,
+        };
+        let params_buffer = VulkanBuffer::new_host_visible(
+            device,
+            physical_device,
+            std::mem::size_of::<MaterialParams>() as vk::DeviceSize,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+        )?;
+        params_buffer.write(0, &[params])?;
+
+        descriptor_pool.write_buffer(
+            material_set,
+            0,
+            vk::DescriptorType::UNIFORM_BUFFER,
+            &params_buffer,
+        );
+        descriptor_pool.write_image(
+            material_set,
+            1,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            textures.albedo,
+            textures.sampler,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+
+        if pipeline.features.contains(MaterialFeatures::NORMAL_MAP) {
+            // Uber-shader layouts always have the slot; materials without a normal map bind
+            // the albedo there, which the shader never samples.
+            let normal_map = match textures.normal_map {
+                Some(normal_map) => normal_map,
+                None if !definition.features.contains(MaterialFeatures::NORMAL_MAP) => {
+                    textures.albedo
+                }
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "Material enables NORMAL_MAP but no normal map was bound"
+                    ));
+                }
+            };
+            descriptor_pool.write_image(
+                material_set,
+                2,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                normal_map,
+                textures.sampler,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            );
+        }
+
+        // Same for the joint buffer of non-skinned materials in uber-shader layouts.
+        let identity_joints = match skin_set {
+            Some(skin_set) if !definition.features.contains(MaterialFeatures::SKINNED) => {
+                let joints = VulkanBuffer::new_host_visible(
+                    device,
+                    physical_device,
+                    std::mem::size_of::<Mat4>() as vk::DeviceSize,
+                    vk::BufferUsageFlags::STORAGE_BUFFER,
+                )?;
+                joints.write(0, &[Mat4::IDENTITY])?;
+                descriptor_pool.write_buffer(
+                    skin_set,
+                    0,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    &joints,
+                );
+                Some(joints)
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            pipeline,
+            params,
+            params_buffer,
+            descriptor_pool,
+            material_set,
+            skin_set,
+            _identity_joints: identity_joints,
+        })
+    }
+
+    pub fn params(&self) -> &MaterialParams {
+        &self.params
+    }
+
+    /// Updates the parameter block. The caller must make sure no in-flight frame reads it.
+    pub fn set_params(&mut self, params: MaterialParams) -> Result<()> {
+        self.params = params;
+        self.params_buffer.write(0, &[params])
+    }
+
+    /// Binds the joint matrices (`Mat4` array) of a `SKINNED` material.
+    pub fn set_joints(&self, joints: &VulkanBuffer) -> Result<()> {
+        let skin_set = self
+            .skin_set
+            .ok_or_else(|| anyhow::anyhow!("Material is not skinned"))?;
+        self.descriptor_pool
+            .write_buffer(skin_set, 0, vk::DescriptorType::STORAGE_BUFFER, joints);
+        Ok(())
+    }
+
+    /// Binds the pipeline and descriptor sets. Vertex streams are bound by the caller, see
+    /// `material_vertex_input` with `pipeline.features`; with an uber-shader backend, streams
+    /// the material doesn't use still need a buffer bound, e.g. the `GpuVertex` buffer, which
+    /// is large enough and never read through them.
+    pub fn bind(&self, command_buffer: vk::CommandBuffer) {
+        self.pipeline.pipeline.bind(command_buffer);
+
+        let mut sets = vec![self.material_set];
+        sets.extend(self.skin_set);
+        self.pipeline
+            .pipeline
+            .bind_descriptor_sets(command_buffer, 0, &sets);
+    }
+
+    pub fn push_transforms(&self, command_buffer: vk::CommandBuffer, view_proj: Mat4, model: Mat4) {
+        self.pipeline.pipeline.push_constants(
+            command_buffer,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            &MaterialTransforms { view_proj, model },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_material_files() {
+        let source = "\
+# Foliage
+template = standard
+features = ALPHA_TEST, NORMAL_MAP
+base_color = 0.5 1 0.25
+alpha_cutoff = 0.3
+albedo = leaves.ppm
+normal_map = leaves_n.ppm  # tangent space
+";
+        let material = MaterialDefinition::parse(source, Path::new("materials")).unwrap();
+
+        assert_eq!(material.template, "standard");
+        assert_eq!(
+            material.features,
+            MaterialFeatures::ALPHA_TEST | MaterialFeatures::NORMAL_MAP
+        );
+        assert_eq!(material.base_color, Vec4::new(0.5, 1.0, 0.25, 1.0));
+        assert_eq!(material.alpha_cutoff, 0.3);
+        assert_eq!(
+            material.albedo.as_deref(),
+            Some(Path::new("materials/leaves.ppm"))
+        );
+        assert_eq!(
+            material.normal_map.as_deref(),
+            Some(Path::new("materials/leaves_n.ppm"))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_materials() {
+        let base = Path::new("");
+        let error = |source| {
+            MaterialDefinition::parse(source, base)
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert!(error("features = GLOW").contains("unknown feature 'GLOW'"));
+        assert!(error("shininess = 4").contains("line 1: unknown key"));
+        assert!(error("\nbase_color = 1 2").contains("line 2"));
+        assert!(error("template standard").contains("key = value"));
+        assert!(error("features = NORMAL_MAP").contains("no normal_map"));
+        assert!(error("texture_layer = -1").contains("invalid texture_layer"));
+    }
+
+    #[test]
+    fn features_map_to_sorted_defines() {
+        let features = MaterialFeatures::SKINNED | MaterialFeatures::ALPHA_TEST;
+
+        assert_eq!(features.defines(), ["ALPHA_TEST", "SKINNED"]);
+        assert!(features.contains(MaterialFeatures::SKINNED));
+        assert!(!features.contains(MaterialFeatures::NORMAL_MAP));
+        assert!(MaterialFeatures::NONE.defines().is_empty());
+        assert_eq!(
+            MaterialFeatures::from_name("NORMAL_MAP"),
+            Some(MaterialFeatures::NORMAL_MAP)
+        );
+    }
+
+    #[test]
+    fn vertex_streams_follow_features() {
+        let (bindings, attributes) = material_vertex_input(MaterialFeatures::NONE);
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(attributes.len(), 3);
+
+        let (bindings, attributes) =
+            material_vertex_input(MaterialFeatures::NORMAL_MAP | MaterialFeatures::SKINNED);
+        assert_eq!(
+            bindings.iter().map(|b| b.binding).collect::<Vec<_>>(),
+            [
+                MATERIAL_VERTEX_BINDING,
+                MATERIAL_TANGENT_BINDING,
+                MATERIAL_SKIN_BINDING
+            ]
+        );
+        assert_eq!(
+            attributes.iter().map(|a| a.location).collect::<Vec<_>>(),
+            [0, 1, 2, 3, 4, 5]
+        );
+        assert_eq!(bindings[2].stride, 32);
+    }
+
+    #[test]
+    fn params_match_std140_size() {
+        assert_eq!(std::mem::size_of::<MaterialParams>(), 48);
+        assert_eq!(std::mem::size_of::<MaterialTransforms>(), 128);
+    }
+
+    #[test]
+    fn templates_compile_for_every_feature_set() {
+        if std::process::Command::new("glslc")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            eprintln!("glslc not found, skipping template compilation");
+            return;
+        }
+
+        let templates = Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders/materials");
+        let mut shaders = ShaderPermutationCache::new(crate::pipeline::GlslcCompiler::new());
+
+        for bits in 0..=(MaterialFeatures::ALL | MaterialFeatures::TEXTURE_ARRAY).bits() {
+            let defines = MaterialFeatures(bits).defines();
+            for (file, stage) in [
+                ("standard.vert", vk::ShaderStageFlags::VERTEX),
+                ("standard.frag", vk::ShaderStageFlags::FRAGMENT),
+            ] {
+                shaders
+                    .get(&templates.join(file), stage, &defines)
+                    .unwrap_or_else(|e| panic!("{} {:?}: {}", file, defines, e));
+            }
+        }
+
+        for (file, stage) in [
+            ("standard_uber.vert", vk::ShaderStageFlags::VERTEX),
+            ("standard_uber.frag", vk::ShaderStageFlags::FRAGMENT),
+        ] {
+            shaders
+                .get(&templates.join(file), stage, &[])
+                .unwrap_or_else(|e| panic!("{}: {}", file, e));
+        }
+    }
+}
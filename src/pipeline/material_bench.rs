@@ -0,0 +1,269 @@
+use anyhow::Result;
+use ash::vk;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::pipeline::{
+    MaterialBackend, MaterialDefinition, MaterialFeatures, MaterialPipelineCache,
+};
+use crate::vulkan::{GpuTimer, VulkanDevice, VulkanPhysicalDevice};
+
+/// Pipeline binds needed to draw materials with `draws` features in that order.
+pub fn count_pipeline_binds(backend: MaterialBackend, draws: &[MaterialFeatures]) -> usize {
+    let mut binds = 0;
+    let mut bound = None;
+
+    for features in draws {
+        let pipeline = backend.pipeline_features(*features);
+        if bound != Some(pipeline) {
+            binds += 1;
+            bound = Some(pipeline);
+        }
+    }
+
+    binds
+}
+
+/// Measurements of one backend. Fields stay `None` until measured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaterialBackendResult {
+    pub backend: MaterialBackend,
+    pub pipelines: Option<usize>,
+    pub shader_compiles: Option<u32>,
+    pub setup_time: Option<Duration>,
+    pub pipeline_binds: Option<usize>,
+    pub gpu_time_ms: Option<f64>,
+}
+
+impl MaterialBackendResult {
+    fn new(backend: MaterialBackend) -> Self {
+        Self {
+            backend,
+            pipelines: None,
+            shader_compiles: None,
+            setup_time: None,
+            pipeline_binds: None,
+            gpu_time_ms: None,
+        }
+    }
+}
+
+/// Side-by-side results of every `MaterialBackend`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialBenchmarkReport {
+    pub results: Vec<MaterialBackendResult>,
+}
+
+impl fmt::Display for MaterialBenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn cell<T: fmt::Display>(value: Option<T>) -> String {
+            value.map_or_else(|| "-".to_string(), |value| value.to_string())
+        }
+
+        writeln!(
+            f,
+            "{:<18} {:>9} {:>9} {:>10} {:>7} {:>9}",
+            "backend", "pipelines", "compiles", "setup ms", "binds", "gpu ms"
+        )?;
+
+        for result in &self.results {
+            writeln!(
+                f,
+                "{:<18} {:>9} {:>9} {:>10} {:>7} {:>9}",
+                format!("{:?}", result.backend),
+                cell(result.pipelines),
+                cell(result.shader_compiles),
+                cell(
+                    result
+                        .setup_time
+                        .map(|time| format!("{:.2}", time.as_secs_f64() * 1000.0))
+                ),
+                cell(result.pipeline_binds),
+                cell(result.gpu_time_ms.map(|ms| format!("{:.3}", ms))),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares the material backends on the same scene.
+///
+/// For each backend, build its pipelines with `measure_setup` on a fresh cache (with no disk
+/// cache, so compile times count), then draw the same material list between `begin_pass` and
+/// `end_pass`. `collect` reads the GPU times once the frame has completed.
+pub struct MaterialBenchmark {
+    timer: Option<GpuTimer>,
+    results: Vec<MaterialBackendResult>,
+}
+
+impl MaterialBenchmark {
+    /// GPU timing is skipped on devices without timestamp queries.
+    pub fn new(device: &VulkanDevice, physical_device: &VulkanPhysicalDevice) -> Self {
+        let timer = GpuTimer::new(
+            device,
+            physical_device,
+            MaterialBackend::ALL.len() as u32 * 2,
+        )
+        .ok();
+
+        Self {
+            timer,
+            results: MaterialBackend::ALL
+                .iter()
+                .map(|backend| MaterialBackendResult::new(*backend))
+                .collect(),
+        }
+    }
+
+    /// Builds the pipelines of every material with `cache` and records the setup cost for
+    /// the cache's backend, plus the binds needed to draw `materials` in order.
+    pub fn measure_setup(
+        &mut self,
+        device: &VulkanDevice,
+        cache: &mut MaterialPipelineCache,
+        materials: &[MaterialDefinition],
+    ) -> Result<()> {
+        let backend = cache.backend();
+        let compiles_before = cache.shaders().stats().compiled;
+        let start = Instant::now();
+
+        for material in materials {
+            cache.get(device, &material.template, material.features)?;
+        }
+
+        let draws: Vec<MaterialFeatures> = materials.iter().map(|m| m.features).collect();
+        let result = self.result_mut(backend);
+        result.setup_time = Some(start.elapsed());
+        result.pipelines = Some(cache.len());
+        result.shader_compiles = Some(cache.shaders().stats().compiled - compiles_before);
+        result.pipeline_binds = Some(count_pipeline_binds(backend, &draws));
+
+        Ok(())
+    }
+
+    /// Resets the timestamps; record once per benchmark frame outside a render pass.
+    pub fn cmd_reset(&self, command_buffer: vk::CommandBuffer) {
+        if let Some(timer) = &self.timer {
+            timer.cmd_reset(command_buffer);
+        }
+    }
+
+    pub fn begin_pass(&self, command_buffer: vk::CommandBuffer, backend: MaterialBackend) {
+        if let Some(timer) = &self.timer {
+            timer.cmd_write(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                Self::index(backend) as u32 * 2,
+            );
+        }
+    }
+
+    pub fn end_pass(&self, command_buffer: vk::CommandBuffer, backend: MaterialBackend) {
+        if let Some(timer) = &self.timer {
+            timer.cmd_write(
+                command_buffer,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                Self::index(backend) as u32 * 2 + 1,
+            );
+        }
+    }
+
+    /// Reads the GPU times of every backend. Returns false while they aren't available;
+    /// every backend must have been drawn in the benchmark frame.
+    pub fn collect(&mut self) -> Result<bool> {
+        let Some(timer) = &self.timer else {
+            return Ok(true);
+        };
+        let Some(timestamps) = timer.read(timer.capacity())? else {
+            return Ok(false);
+        };
+
+        for (index, result) in self.results.iter_mut().enumerate() {
+            result.gpu_time_ms =
+                Some(timer.elapsed_ms(timestamps[index * 2], timestamps[index * 2 + 1]));
+        }
+
+        Ok(true)
+    }
+
+    pub fn report(&self) -> MaterialBenchmarkReport {
+        MaterialBenchmarkReport {
+            results: self.results.clone(),
+        }
+    }
+
+    fn index(backend: MaterialBackend) -> usize {
+        MaterialBackend::ALL
+            .iter()
+            .position(|b| *b == backend)
+            .expect("backend is listed in ALL")
+    }
+
+    fn result_mut(&mut self, backend: MaterialBackend) -> &mut MaterialBackendResult {
+        &mut self.results[Self::index(backend)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipeline_binds_per_backend() {
+        let opaque = MaterialFeatures::NONE;
+        let foliage = MaterialFeatures::ALPHA_TEST;
+        let character = MaterialFeatures::SKINNED | MaterialFeatures::NORMAL_MAP;
+        let draws = [opaque, opaque, foliage, character, character, opaque];
+
+        assert_eq!(
+            count_pipeline_binds(MaterialBackend::Permutations, &draws),
+            4
+        );
+        assert_eq!(
+            count_pipeline_binds(MaterialBackend::UberSpecialized, &draws),
+            4
+        );
+        assert_eq!(
+            count_pipeline_binds(MaterialBackend::UberDynamic, &draws),
+            1
+        );
+        assert_eq!(count_pipeline_binds(MaterialBackend::UberDynamic, &[]), 0);
+    }
+
+    #[test]
+    fn report_lists_every_backend() {
+        let mut results: Vec<_> = MaterialBackend::ALL
+            .iter()
+            .map(|backend| MaterialBackendResult::new(*backend))
+            .collect();
+        results[2].pipelines = Some(1);
+        results[2].setup_time = Some(Duration::from_micros(1500));
+        results[2].gpu_time_ms = Some(0.25);
+
+        let report = MaterialBenchmarkReport { results }.to_string();
+        let lines: Vec<&str> = report.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("backend"));
+        assert!(lines[1].starts_with("Permutations"));
+        assert!(lines[3].starts_with("UberDynamic"));
+        assert!(lines[3].contains("1.50"));
+        assert!(lines[3].contains("0.250"));
+        assert!(lines[1].trim_end().ends_with('-'));
+    }
+
+    #[test]
+    fn dynamic_backend_shares_one_pipeline() {
+        for features in [MaterialFeatures::NONE, MaterialFeatures::ALL] {
+            assert_eq!(
+                MaterialBackend::UberDynamic.pipeline_features(features),
+                MaterialFeatures::ALL
+            );
+            assert_eq!(
+                MaterialBackend::Permutations.pipeline_features(features),
+                features
+            );
+        }
+    }
+}
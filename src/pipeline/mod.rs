@@ -1,3 +1,31 @@
+pub mod compute;
+pub mod compute_harness;
+pub mod hlsl;
+pub mod material;
+pub mod material_bench;
+pub mod permutation;
 pub mod pipeline;
+pub mod preprocess;
+pub mod reflect;
+#[cfg(feature = "rust-gpu")]
+pub mod rust_gpu;
+pub mod shader_object;
+pub mod subgroup;
+#[cfg(feature = "wgsl")]
+pub mod wgsl;
 
-pub use pipeline::*;
\ No newline at end of file
+pub use compute::*;
+pub use compute_harness::*;
+pub use hlsl::*;
+pub use material::*;
+pub use material_bench::*;
+pub use permutation::*;
+pub use pipeline::*;
+pub use preprocess::*;
+pub use reflect::*;
+#[cfg(feature = "rust-gpu")]
+pub use rust_gpu::*;
+pub use shader_object::*;
+pub use subgroup::*;
+#[cfg(feature = "wgsl")]
+pub use wgsl::*;
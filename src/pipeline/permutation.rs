@@ -0,0 +1,408 @@
+use anyhow::Result;
+use ash::vk;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+use crate::pipeline::{ShaderIncludes, preprocess_shader};
+
+/// Compiles shader source to SPIR-V at runtime: GLSL for `GlslcCompiler`, HLSL or Slang for
+/// `DxcCompiler` and `SlangCompiler`.
+pub trait ShaderCompiler: Send + Sync {
+    /// `label` names the shader in error messages and temporary files.
+    fn compile(&self, source: &str, stage: vk::ShaderStageFlags, label: &str) -> Result<Vec<u8>>;
+}
+
+/// Runs `glslc` (or the compiler named by the `GLSLC` environment variable), the same tool
+/// `shaders/compile.sh` uses.
+pub struct GlslcCompiler {
+    executable: PathBuf,
+}
+
+impl GlslcCompiler {
+    pub fn new() -> Self {
+        Self {
+            executable: std::env::var_os("GLSLC")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("glslc")),
+        }
+    }
+
+    pub fn with_executable(executable: impl Into<PathBuf>) -> Self {
+        Self {
+            executable: executable.into(),
+        }
+    }
+}
+
+impl Default for GlslcCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderCompiler for GlslcCompiler {
+    fn compile(&self, source: &str, stage: vk::ShaderStageFlags, label: &str) -> Result<Vec<u8>> {
+        let extension = match stage {
+            vk::ShaderStageFlags::VERTEX => "vert",
+            vk::ShaderStageFlags::FRAGMENT => "frag",
+            vk::ShaderStageFlags::COMPUTE => "comp",
+            _ => return Err(anyhow::anyhow!("Unsupported shader stage {:?}", stage)),
+        };
+
+        run_shader_tool(
+            &self.executable,
+            source,
+            label,
+            extension,
+            |command, input, output| {
+                command
+                    .arg("--target-env=vulkan1.3")
+                    .arg(input)
+                    .arg("-o")
+                    .arg(output);
+            },
+        )
+    }
+}
+
+/// Writes `source` to a temporary `<label>.<extension>` file, runs `executable` with the
+/// arguments added by `args` (given the input and output paths) and reads back the SPIR-V.
+pub(crate) fn run_shader_tool(
+    executable: &Path,
+    source: &str,
+    label: &str,
+    extension: &str,
+    args: impl FnOnce(&mut Command, &Path, &Path),
+) -> Result<Vec<u8>> {
+    let directory = std::env::temp_dir().join(format!("rve-shaders-{}", std::process::id()));
+    std::fs::create_dir_all(&directory)
+        .map_err(|e| anyhow::anyhow!("Failed to create shader temp directory: {}", e))?;
+
+    let file_name: String = label
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let input = directory.join(format!("{}.{}", file_name, extension));
+    let output = directory.join(format!("{}.{}.spv", file_name, extension));
+
+    std::fs::write(&input, source)
+        .map_err(|e| anyhow::anyhow!("Failed to write shader source: {}", e))?;
+
+    let mut command = Command::new(executable);
+    args(&mut command, &input, &output);
+    let result = command
+        .output()
+        .map_err(|e| anyhow::anyhow!("Failed to run {}: {}", executable.display(), e))?;
+
+    if !result.status.success() {
+        // DXC reports errors on stdout.
+        let mut message = String::from_utf8_lossy(&result.stderr).into_owned();
+        message.push_str(&String::from_utf8_lossy(&result.stdout));
+        return Err(anyhow::anyhow!(
+            "Failed to compile {}: {}",
+            label,
+            message.trim()
+        ));
+    }
+
+    let spirv = std::fs::read(&output)
+        .map_err(|e| anyhow::anyhow!("Failed to read compiled shader {}: {}", label, e))?;
+    let _ = std::fs::remove_file(&input);
+    let _ = std::fs::remove_file(&output);
+
+    Ok(spirv)
+}
+
+/// Stable 64-bit FNV-1a, used to name permutations on disk.
+fn fnv1a(bytes: &[u8], mut hash: u64) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PermutationStats {
+    /// Permutations served from memory.
+    pub memory_hits: u32,
+    /// Permutations loaded from the disk cache.
+    pub disk_hits: u32,
+    /// Permutations compiled from their template.
+    pub compiled: u32,
+}
+
+/// Compiles template shaders with sets of defines and caches the resulting SPIR-V in memory
+/// and, optionally, on disk across runs.
+///
+/// Templates are run through `preprocess_shader` first, so they may `#include` files next to
+/// them or from `with_includes`, and compile errors point at the original files. Entries are
+/// keyed by the expanded source, so editing a template or one of its includes yields a new
+/// permutation instead of a stale one.
+pub struct ShaderPermutationCache {
+    compiler: Box<dyn ShaderCompiler>,
+    includes: ShaderIncludes,
+    disk_cache: Option<PathBuf>,
+    spirv: HashMap<u64, Arc<[u8]>>,
+    stats: PermutationStats,
+}
+
+impl ShaderPermutationCache {
+    pub fn new(compiler: impl ShaderCompiler + 'static) -> Self {
+        Self {
+            compiler: Box::new(compiler),
+            includes: ShaderIncludes::new(),
+            disk_cache: None,
+            spirv: HashMap::new(),
+            stats: PermutationStats::default(),
+        }
+    }
+
+    pub fn with_includes(mut self, includes: ShaderIncludes) -> Self {
+        self.includes = includes;
+        self
+    }
+
+    /// Also stores compiled permutations in `directory`.
+    pub fn with_disk_cache(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.disk_cache = Some(directory.into());
+        self
+    }
+
+    pub fn stats(&self) -> PermutationStats {
+        self.stats
+    }
+
+    pub fn len(&self) -> usize {
+        self.spirv.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spirv.is_empty()
+    }
+
+    /// Drops the in-memory entries; the disk cache is kept.
+    pub fn clear(&mut self) {
+        self.spirv.clear();
+    }
+
+    /// Reads `template` and returns the SPIR-V for it compiled with `defines`.
+    pub fn get(
+        &mut self,
+        template: &Path,
+        stage: vk::ShaderStageFlags,
+        defines: &[&str],
+    ) -> Result<Arc<[u8]>> {
+        let source = std::fs::read_to_string(template).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to read shader template {}: {}",
+                template.display(),
+                e
+            )
+        })?;
+
+        self.get_source(&template.to_string_lossy(), &source, stage, defines)
+    }
+
+    /// Same as `get` for a template already in memory. `label` is used as its file name for
+    /// relative includes and error messages.
+    pub fn get_source(
+        &mut self,
+        label: &str,
+        source: &str,
+        stage: vk::ShaderStageFlags,
+        defines: &[&str],
+    ) -> Result<Arc<[u8]>> {
+        let mut defines = defines.to_vec();
+        defines.sort_unstable();
+        defines.dedup();
+
+        let shader = preprocess_shader(label, source, &self.includes, &defines)?;
+        let key = fnv1a(
+            &stage.as_raw().to_le_bytes(),
+            fnv1a(shader.source.as_bytes(), 0xcbf2_9ce4_8422_2325),
+        );
+
+        if let Some(spirv) = self.spirv.get(&key) {
+            self.stats.memory_hits += 1;
+            return Ok(spirv.clone());
+        }
+
+        let disk_path = self
+            .disk_cache
+            .as_ref()
+            .map(|directory| directory.join(format!("{:016x}.spv", key)));
+
+        if let Some(spirv) = disk_path.as_ref().and_then(|path| std::fs::read(path).ok()) {
+            self.stats.disk_hits += 1;
+            let spirv: Arc<[u8]> = spirv.into();
+            self.spirv.insert(key, spirv.clone());
+            return Ok(spirv);
+        }
+
+        let file_name = Path::new(label)
+            .file_name()
+            .map_or_else(|| label.to_string(), |n| n.to_string_lossy().into_owned());
+        let permutation_label = if defines.is_empty() {
+            file_name
+        } else {
+            format!("{}[{}]", file_name, defines.join(","))
+        };
+        let spirv = self
+            .compiler
+            .compile(&shader.source, stage, &permutation_label)
+            .map_err(|e| anyhow::anyhow!("{}", shader.remap_errors(&e.to_string())))?;
+        self.stats.compiled += 1;
+
+        if let Some(path) = disk_path {
+            if let Some(directory) = path.parent() {
+                let _ = std::fs::create_dir_all(directory);
+            }
+            if let Err(e) = std::fs::write(&path, &spirv) {
+                eprintln!(
+                    "Failed to write shader cache entry {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+
+        let spirv: Arc<[u8]> = spirv.into();
+        self.spirv.insert(key, spirv.clone());
+        Ok(spirv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Returns the source it was given, so tests can inspect what would be compiled.
+    #[derive(Clone, Default)]
+    struct EchoCompiler {
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl ShaderCompiler for EchoCompiler {
+        fn compile(
+            &self,
+            source: &str,
+            _stage: vk::ShaderStageFlags,
+            label: &str,
+        ) -> Result<Vec<u8>> {
+            self.calls.lock().unwrap().push(label.to_string());
+            Ok(source.as_bytes().to_vec())
+        }
+    }
+
+    /// Fails like glslang does on line 4 of whatever it was given.
+    struct FailingCompiler;
+
+    impl ShaderCompiler for FailingCompiler {
+        fn compile(&self, _: &str, _: vk::ShaderStageFlags, _: &str) -> Result<Vec<u8>> {
+            Err(anyhow::anyhow!("ERROR: 0:4: 'x' : undeclared identifier"))
+        }
+    }
+
+    const TEMPLATE: &str = "#version 450\nvoid main() {}\n";
+
+    #[test]
+    fn permutations_are_cached_regardless_of_define_order() {
+        let compiler = EchoCompiler::default();
+        let mut cache = ShaderPermutationCache::new(compiler.clone());
+        let stage = vk::ShaderStageFlags::FRAGMENT;
+
+        let a = cache
+            .get_source("t.frag", TEMPLATE, stage, &["X", "Y"])
+            .unwrap();
+        let b = cache
+            .get_source("t.frag", TEMPLATE, stage, &["Y", "X", "X"])
+            .unwrap();
+        let plain = cache.get_source("t.frag", TEMPLATE, stage, &[]).unwrap();
+
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_ne!(a, plain);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            cache.stats(),
+            PermutationStats {
+                memory_hits: 1,
+                disk_hits: 0,
+                compiled: 2
+            }
+        );
+        assert_eq!(*compiler.calls.lock().unwrap(), ["t.frag[X,Y]", "t.frag"]);
+    }
+
+    #[test]
+    fn stage_and_source_are_part_of_the_key() {
+        let mut cache = ShaderPermutationCache::new(EchoCompiler::default());
+
+        cache
+            .get_source("t", TEMPLATE, vk::ShaderStageFlags::VERTEX, &[])
+            .unwrap();
+        cache
+            .get_source("t", TEMPLATE, vk::ShaderStageFlags::FRAGMENT, &[])
+            .unwrap();
+        cache
+            .get_source("t", "#version 460\n", vk::ShaderStageFlags::FRAGMENT, &[])
+            .unwrap();
+
+        assert_eq!(cache.stats().compiled, 3);
+    }
+
+    #[test]
+    fn disk_cache_survives_new_instances() {
+        let directory =
+            std::env::temp_dir().join(format!("rve-permutations-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let stage = vk::ShaderStageFlags::VERTEX;
+
+        let mut first =
+            ShaderPermutationCache::new(EchoCompiler::default()).with_disk_cache(&directory);
+        let compiled = first.get_source("t", TEMPLATE, stage, &["A"]).unwrap();
+
+        let compiler = EchoCompiler::default();
+        let mut second = ShaderPermutationCache::new(compiler.clone()).with_disk_cache(&directory);
+        let cached = second.get_source("t", TEMPLATE, stage, &["A"]).unwrap();
+
+        assert_eq!(compiled, cached);
+        assert_eq!(second.stats().disk_hits, 1);
+        assert!(compiler.calls.lock().unwrap().is_empty());
+
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn includes_change_the_key_and_errors_point_at_them() {
+        let template = "#version 450\n#include \"common.glsl\"\nvoid main() {}\n";
+        let stage = vk::ShaderStageFlags::FRAGMENT;
+
+        let mut cache = ShaderPermutationCache::new(EchoCompiler::default())
+            .with_includes(ShaderIncludes::new().with_file("common.glsl", "float a;\n"));
+        let first = cache.get_source("t.frag", template, stage, &[]).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&first).unwrap(),
+            "#version 450\nfloat a;\nvoid main() {}\n"
+        );
+
+        let mut edited = ShaderPermutationCache::new(EchoCompiler::default())
+            .with_includes(ShaderIncludes::new().with_file("common.glsl", "float b;\n"));
+        assert_ne!(
+            edited.get_source("t.frag", template, stage, &[]).unwrap(),
+            first
+        );
+
+        let mut failing = ShaderPermutationCache::new(FailingCompiler).with_includes(
+            ShaderIncludes::new().with_file("common.glsl", "float a;\nfloat b = x;\n"),
+        );
+        let error = failing
+            .get_source("shaders/t.frag", template, stage, &["A"])
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "ERROR: common.glsl:2: 'x' : undeclared identifier");
+    }
+}
@@ -3,7 +3,8 @@ use ash::{Device, vk};
 use std::ffi::CString;
 use std::sync::Arc;
 
-use crate::vulkan::VulkanDevice;
+use crate::pipeline::ShaderReflection;
+use crate::vulkan::{RecordCommands, VulkanDevice};
 
 pub struct VulkanPipeline {
     pub pipeline: vk::Pipeline,
@@ -17,6 +18,9 @@ pub struct VulkanPipelineBuilder {
     extent: Option<vk::Extent2D>,
 
     shader_entries: Vec<(vk::ShaderModule, vk::ShaderStageFlags, CString)>,
+    /// Interface of each entry of `shader_entries`, when its SPIR-V could be reflected.
+    shader_reflections: Vec<Option<ShaderReflection>>,
+    specialization_constants: Vec<(vk::ShaderStageFlags, u32, u32)>,
 
     descriptor_set_layouts: Vec<vk::DescriptorSetLayout>,
     push_constant_ranges: Vec<vk::PushConstantRange>,
@@ -36,6 +40,7 @@ pub struct VulkanPipelineBuilder {
     front_face: vk::FrontFace,
     line_width: f32,
     depth_clamp_enable: bool,
+    rasterizer_discard_enable: bool,
 
     rasterization_samples: vk::SampleCountFlags,
     sample_shading_enable: bool,
@@ -47,6 +52,8 @@ pub struct VulkanPipelineBuilder {
     stencil_test_enable: bool,
 
     color_blend_attachments: Vec<vk::PipelineColorBlendAttachmentState>,
+    /// Overrides the write mask of every color attachment.
+    color_write_mask: Option<vk::ColorComponentFlags>,
     logic_op_enable: bool,
     logic_op: vk::LogicOp,
     blend_constants: [f32; 4],
@@ -59,6 +66,8 @@ impl VulkanPipelineBuilder {
             render_pass: None,
             extent: None,
             shader_entries: Vec::new(),
+            shader_reflections: Vec::new(),
+            specialization_constants: Vec::new(),
             descriptor_set_layouts: Vec::new(),
             push_constant_ranges: Vec::new(),
             vertex_input_bindings: Vec::new(),
@@ -73,6 +82,7 @@ impl VulkanPipelineBuilder {
             front_face: vk::FrontFace::CLOCKWISE,
             line_width: 1.0,
             depth_clamp_enable: false,
+            rasterizer_discard_enable: false,
             rasterization_samples: vk::SampleCountFlags::TYPE_1,
             sample_shading_enable: false,
             depth_test_enable: false,
@@ -81,6 +91,7 @@ impl VulkanPipelineBuilder {
             depth_bounds_test_enable: false,
             stencil_test_enable: false,
             color_blend_attachments: Vec::new(),
+            color_write_mask: None,
             logic_op_enable: false,
             logic_op: vk::LogicOp::COPY,
             blend_constants: [0.0; 4],
@@ -119,6 +130,8 @@ impl VulkanPipelineBuilder {
             None => CString::new("main").unwrap(),
         };
         self.shader_entries.push((module, stage, name_cstr));
+        self.shader_reflections
+            .push(ShaderReflection::from_spirv(code).ok());
         Ok(self)
     }
 
@@ -130,6 +143,35 @@ impl VulkanPipelineBuilder {
         self.with_shader_spv(code, vk::ShaderStageFlags::FRAGMENT, None)
     }
 
+    /// Adds the vertex entry point of a WGSL module and its fragment entry point, if it has one.
+    #[cfg(feature = "wgsl")]
+    pub fn with_wgsl(mut self, shader: &crate::pipeline::WgslShader) -> Result<Self> {
+        let vertex = shader.entry_point(vk::ShaderStageFlags::VERTEX, None)?;
+        self = self.with_shader_spv(&shader.spirv, vk::ShaderStageFlags::VERTEX, Some(&vertex))?;
+
+        if let Ok(fragment) = shader.entry_point(vk::ShaderStageFlags::FRAGMENT, None) {
+            self = self.with_shader_spv(
+                &shader.spirv,
+                vk::ShaderStageFlags::FRAGMENT,
+                Some(&fragment),
+            )?;
+        }
+
+        Ok(self)
+    }
+
+    /// Sets the 32-bit specialization constant `constant_id` of every shader of `stage`.
+    pub fn with_specialization_constant(
+        mut self,
+        stage: vk::ShaderStageFlags,
+        constant_id: u32,
+        value: u32,
+    ) -> Self {
+        self.specialization_constants
+            .push((stage, constant_id, value));
+        self
+    }
+
     pub fn with_vertex_binding(mut self, binding: vk::VertexInputBindingDescription) -> Self {
         self.vertex_input_bindings.push(binding);
         self
@@ -175,6 +217,9 @@ impl VulkanPipelineBuilder {
         self
     }
 
+    /// Defaults to `CLOCKWISE`, the crate-wide convention: meshes are wound counter-clockwise
+    /// in a right-handed space and projected with an unflipped `Mat4::perspective_rh`, whose
+    /// clip-space Y then points down the Vulkan framebuffer and mirrors them to clockwise.
     pub fn with_front_face(mut self, face: vk::FrontFace) -> Self {
         self.front_face = face;
         self
@@ -190,6 +235,13 @@ impl VulkanPipelineBuilder {
         self
     }
 
+    /// Drops primitives before rasterization, for pipelines that only capture vertex output
+    /// with transform feedback.
+    pub fn with_rasterizer_discard(mut self, enable: bool) -> Self {
+        self.rasterizer_discard_enable = enable;
+        self
+    }
+
     pub fn with_multisampling(mut self, samples: vk::SampleCountFlags) -> Self {
         self.rasterization_samples = samples;
         self
@@ -249,6 +301,24 @@ impl VulkanPipelineBuilder {
         self
     }
 
+    /// Writes only the `mask` channels of every color attachment, whatever the blend
+    /// attachments say.
+    pub fn with_color_write_mask(mut self, mask: vk::ColorComponentFlags) -> Self {
+        self.color_write_mask = Some(mask);
+        self
+    }
+
+    /// Makes the color write mask dynamic when `VK_EXT_extended_dynamic_state3` supports it,
+    /// so it can be set with `cmd_set_color_write_mask` while recording; the fixed mask stays
+    /// otherwise.
+    pub fn with_dynamic_color_write_mask(mut self, device: &VulkanDevice) -> Self {
+        if device.features.dynamic_color_write_mask {
+            self.dynamic_states
+                .push(vk::DynamicState::COLOR_WRITE_MASK_EXT);
+        }
+        self
+    }
+
     pub fn with_logic_op(mut self, enable: bool, op: vk::LogicOp) -> Self {
         self.logic_op_enable = enable;
         self.logic_op = op;
@@ -260,33 +330,119 @@ impl VulkanPipelineBuilder {
         self
     }
 
-    pub fn build(self) -> Result<VulkanPipeline> {
-        let render_pass = match self.render_pass {
-            Some(rp) => rp,
-            None => bail!("render_pass is required"),
-        };
-        let extent = match self.extent {
-            Some(e) => e,
-            None => bail!("extent is required"),
-        };
+    fn stage_interfaces(&self) -> Vec<ShaderStageInterface<'_>> {
+        self.shader_entries
+            .iter()
+            .zip(&self.shader_reflections)
+            .map(|((_, stage, name), reflection)| ShaderStageInterface {
+                stage: *stage,
+                entry_point: name.to_str().unwrap_or_default(),
+                reflection: reflection.as_ref(),
+            })
+            .collect()
+    }
+
+    /// Checks the configuration before any Vulkan object is created, so mistakes are reported
+    /// by name instead of as a driver error or undefined behavior at draw time.
+    fn validate(&self) -> Result<()> {
+        if self.render_pass.is_none() {
+            bail!("render_pass is required");
+        }
+        if self.extent.is_none() {
+            bail!("extent is required");
+        }
 
-        if self.shader_entries.is_empty() {
-            bail!("at least one shader stage is required")
+        let stages = self.stage_interfaces();
+        check_shader_stages(&stages)?;
+        check_vertex_input(
+            &self.vertex_input_bindings,
+            &self.vertex_input_attributes,
+            &stages,
+        )?;
+        if !self.rasterizer_discard_enable {
+            check_color_blend_attachments(
+                fragment_output_count(&stages),
+                self.color_blend_attachments.len(),
+            )?;
         }
+        check_dynamic_states(
+            &self.dynamic_states,
+            FixedState {
+                viewport: self.viewport.is_some(),
+                scissor: self.scissor.is_some(),
+                depth_bounds_test: self.depth_bounds_test_enable,
+                stencil_test: self.stencil_test_enable,
+                blend_constants: self.blend_constants != [0.0; 4],
+                color_write_mask: self.color_write_mask.is_some(),
+            },
+        )
+    }
+
+    pub fn build(self) -> Result<VulkanPipeline> {
+        if let Err(e) = self.validate() {
+            for (module, _, _) in &self.shader_entries {
+                unsafe { self.device.destroy_shader_module(*module, None) };
+            }
+            return Err(e);
+        }
+        let render_pass = self.render_pass.unwrap();
+        let extent = self.extent.unwrap();
 
         let layout_info = vk::PipelineLayoutCreateInfo::default()
             .set_layouts(&self.descriptor_set_layouts)
             .push_constant_ranges(&self.push_constant_ranges);
-        let layout = unsafe { self.device.create_pipeline_layout(&layout_info, None)? };
+        let layout = unsafe {
+            self.device
+                .create_pipeline_layout(&layout_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create pipeline layout: {}", e))?
+        };
+
+        let specialization_data: Vec<(Vec<vk::SpecializationMapEntry>, Vec<u8>)> = self
+            .shader_entries
+            .iter()
+            .map(|(_, stage, _)| {
+                let constants = self
+                    .specialization_constants
+                    .iter()
+                    .filter(|(constant_stage, _, _)| constant_stage == stage);
+
+                let mut entries = Vec::new();
+                let mut data = Vec::new();
+                for (_, constant_id, value) in constants {
+                    entries.push(
+                        vk::SpecializationMapEntry::default()
+                            .constant_id(*constant_id)
+                            .offset(data.len() as u32)
+                            .size(std::mem::size_of::<u32>()),
+                    );
+                    data.extend_from_slice(&value.to_ne_bytes());
+                }
+                (entries, data)
+            })
+            .collect();
+        let specialization_infos: Vec<vk::SpecializationInfo> = specialization_data
+            .iter()
+            .map(|(entries, data)| {
+                vk::SpecializationInfo::default()
+                    .map_entries(entries)
+                    .data(data)
+            })
+            .collect();
 
         let stage_infos: Vec<vk::PipelineShaderStageCreateInfo> = self
             .shader_entries
             .iter()
-            .map(|(module, stage, name)| {
-                vk::PipelineShaderStageCreateInfo::default()
+            .zip(&specialization_infos)
+            .map(|((module, stage, name), specialization)| {
+                let info = vk::PipelineShaderStageCreateInfo::default()
                     .stage(*stage)
                     .module(*module)
-                    .name(name.as_c_str())
+                    .name(name.as_c_str());
+                if specialization.map_entry_count > 0 {
+                    info.specialization_info(specialization)
+                } else {
+                    info
+                }
             })
             .collect();
 
@@ -320,7 +476,7 @@ impl VulkanPipelineBuilder {
 
         let rasterization = vk::PipelineRasterizationStateCreateInfo::default()
             .depth_clamp_enable(self.depth_clamp_enable)
-            .rasterizer_discard_enable(false)
+            .rasterizer_discard_enable(self.rasterizer_discard_enable)
             .polygon_mode(self.polygon_mode)
             .line_width(self.line_width)
             .cull_mode(self.cull_mode)
@@ -347,10 +503,25 @@ impl VulkanPipelineBuilder {
             None
         };
 
+        // Without explicit attachments, every fragment output is written unblended.
+        let mut color_blend_attachments = if self.color_blend_attachments.is_empty() {
+            vec![
+                vk::PipelineColorBlendAttachmentState::default()
+                    .color_write_mask(vk::ColorComponentFlags::RGBA);
+                fragment_output_count(&self.stage_interfaces()) as usize
+            ]
+        } else {
+            self.color_blend_attachments.clone()
+        };
+        if let Some(mask) = self.color_write_mask {
+            for attachment in &mut color_blend_attachments {
+                attachment.color_write_mask = mask;
+            }
+        }
         let color_blend = vk::PipelineColorBlendStateCreateInfo::default()
             .logic_op_enable(self.logic_op_enable)
             .logic_op(self.logic_op)
-            .attachments(&self.color_blend_attachments)
+            .attachments(&color_blend_attachments)
             .blend_constants(self.blend_constants);
 
         let dynamic_state_info = if self.dynamic_states.is_empty() {
@@ -384,7 +555,7 @@ impl VulkanPipelineBuilder {
                 None,
             )
         }
-        .map_err(|(_, e)| e)?[0];
+        .map_err(|(_, e)| anyhow::anyhow!("Failed to create graphics pipeline: {}", e))?[0];
 
         for (module, _, _) in self.shader_entries {
             unsafe { self.device.destroy_shader_module(module, None) };
@@ -398,24 +569,76 @@ impl VulkanPipelineBuilder {
     }
 
     fn create_shader_module(&self, code: &[u8]) -> Result<vk::ShaderModule> {
-        let words =
-            unsafe { std::slice::from_raw_parts(code.as_ptr() as *const u32, code.len() / 4) };
-        let info = vk::ShaderModuleCreateInfo::default().code(words);
-        let module = unsafe { self.device.create_shader_module(&info, None)? };
-        Ok(module)
+        create_shader_module(&self.device, code)
     }
 }
 
+pub(crate) fn create_shader_module(device: &Device, code: &[u8]) -> Result<vk::ShaderModule> {
+    let words = spirv_words(code)?;
+    let info = vk::ShaderModuleCreateInfo::default().code(&words);
+    let module = unsafe {
+        device
+            .create_shader_module(&info, None)
+            .map_err(|e| anyhow::anyhow!("Failed to create shader module: {}", e))?
+    };
+    Ok(module)
+}
+
+/// Copies SPIR-V bytes into words, since `include_bytes!` and file data are not guaranteed to
+/// be 4-byte aligned, and checks that they hold a SPIR-V module at all. Big-endian modules are
+/// byte-swapped.
+pub fn spirv_words(code: &[u8]) -> Result<Vec<u32>> {
+    if code.len() < 20 {
+        bail!(
+            "SPIR-V is {} bytes, too short for a module header",
+            code.len()
+        );
+    }
+    ash::util::read_spv(&mut std::io::Cursor::new(code))
+        .map_err(|e| anyhow::anyhow!("Failed to read SPIR-V: {}", e))
+}
+
 impl VulkanPipeline {
     pub fn bind(&self, command_buffer: vk::CommandBuffer) {
+        self.device.bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+    }
+
+    pub fn push_constants<T: Copy>(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        stage_flags: vk::ShaderStageFlags,
+        offset: u32,
+        constants: &T,
+    ) {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(constants as *const T as *const u8, std::mem::size_of::<T>())
+        };
+
         unsafe {
-            self.device.cmd_bind_pipeline(
-                command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline,
-            );
+            self.device
+                .cmd_push_constants(command_buffer, self.layout, stage_flags, offset, bytes);
         }
     }
+
+    pub fn bind_descriptor_sets(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        first_set: u32,
+        descriptor_sets: &[vk::DescriptorSet],
+    ) {
+        self.device.bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.layout,
+            first_set,
+            descriptor_sets,
+            &[],
+        );
+    }
 }
 
 impl Drop for VulkanPipeline {
@@ -426,3 +649,403 @@ impl Drop for VulkanPipeline {
         }
     }
 }
+
+/// A shader stage added to a `VulkanPipelineBuilder`, as seen by its validation.
+struct ShaderStageInterface<'a> {
+    stage: vk::ShaderStageFlags,
+    entry_point: &'a str,
+    reflection: Option<&'a ShaderReflection>,
+}
+
+/// Fixed-function state that a dynamic state can contradict.
+#[derive(Debug, Clone, Copy, Default)]
+struct FixedState {
+    viewport: bool,
+    scissor: bool,
+    depth_bounds_test: bool,
+    stencil_test: bool,
+    blend_constants: bool,
+    color_write_mask: bool,
+}
+
+fn check_shader_stages(stages: &[ShaderStageInterface]) -> Result<()> {
+    if stages.is_empty() {
+        bail!("at least one shader stage is required");
+    }
+
+    for (index, stage) in stages.iter().enumerate() {
+        if stages[..index]
+            .iter()
+            .any(|other| other.stage == stage.stage)
+        {
+            bail!("Shader stage {:?} is added more than once", stage.stage);
+        }
+
+        let Some(reflection) = stage.reflection else {
+            continue;
+        };
+        if !reflection
+            .entry_points
+            .iter()
+            .any(|entry| entry.stage == stage.stage && entry.name == stage.entry_point)
+        {
+            let found: Vec<String> = reflection
+                .entry_points
+                .iter()
+                .map(|entry| format!("{:?} '{}'", entry.stage, entry.name))
+                .collect();
+            bail!(
+                "Shader added as {:?} has no {:?} entry point '{}' (it declares {})",
+                stage.stage,
+                stage.stage,
+                stage.entry_point,
+                found.join(", ")
+            );
+        }
+    }
+
+    if !stages.iter().any(|stage| {
+        stage.stage == vk::ShaderStageFlags::VERTEX || stage.stage == vk::ShaderStageFlags::MESH_EXT
+    }) {
+        bail!("A graphics pipeline needs a vertex or mesh shader");
+    }
+    Ok(())
+}
+
+fn check_vertex_input(
+    bindings: &[vk::VertexInputBindingDescription],
+    attributes: &[vk::VertexInputAttributeDescription],
+    stages: &[ShaderStageInterface],
+) -> Result<()> {
+    for (index, binding) in bindings.iter().enumerate() {
+        if bindings[..index]
+            .iter()
+            .any(|other| other.binding == binding.binding)
+        {
+            bail!(
+                "Vertex binding {} is described more than once",
+                binding.binding
+            );
+        }
+    }
+
+    for (index, attribute) in attributes.iter().enumerate() {
+        if !bindings
+            .iter()
+            .any(|binding| binding.binding == attribute.binding)
+        {
+            bail!(
+                "Vertex attribute at location {} uses binding {}, which has no vertex binding",
+                attribute.location,
+                attribute.binding
+            );
+        }
+        if attributes[..index]
+            .iter()
+            .any(|other| other.location == attribute.location)
+        {
+            bail!(
+                "Vertex attribute location {} is described more than once",
+                attribute.location
+            );
+        }
+    }
+
+    let vertex_inputs = stages
+        .iter()
+        .filter(|stage| stage.stage == vk::ShaderStageFlags::VERTEX)
+        .filter_map(|stage| stage.reflection)
+        .flat_map(|reflection| reflection.stage_inputs(vk::ShaderStageFlags::VERTEX));
+    for input in vertex_inputs {
+        if !attributes
+            .iter()
+            .any(|attribute| attribute.location == input.location)
+        {
+            bail!(
+                "Vertex shader input '{}' at location {} has no vertex attribute",
+                input.name.as_deref().unwrap_or("?"),
+                input.location
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Color attachments written by the fragment shader: one past its highest output location.
+fn fragment_output_count(stages: &[ShaderStageInterface]) -> u32 {
+    stages
+        .iter()
+        .filter(|stage| stage.stage == vk::ShaderStageFlags::FRAGMENT)
+        .filter_map(|stage| stage.reflection)
+        .flat_map(|reflection| reflection.stage_outputs(vk::ShaderStageFlags::FRAGMENT))
+        .map(|output| output.location + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// No attachments means "write every output unblended" and is filled in by `build`.
+fn check_color_blend_attachments(output_count: u32, attachment_count: usize) -> Result<()> {
+    if attachment_count > 0 && attachment_count < output_count as usize {
+        bail!(
+            "Fragment shader writes color location {} but only {} color blend attachment(s) are set",
+            output_count - 1,
+            attachment_count
+        );
+    }
+    Ok(())
+}
+
+fn check_dynamic_states(states: &[vk::DynamicState], fixed: FixedState) -> Result<()> {
+    for (index, state) in states.iter().enumerate() {
+        if states[..index].contains(state) {
+            bail!("Dynamic state {:?} is listed more than once", state);
+        }
+    }
+
+    let dynamic = |state: vk::DynamicState| states.contains(&state);
+    let exclusive = [
+        (
+            vk::DynamicState::VIEWPORT,
+            vk::DynamicState::VIEWPORT_WITH_COUNT,
+        ),
+        (
+            vk::DynamicState::SCISSOR,
+            vk::DynamicState::SCISSOR_WITH_COUNT,
+        ),
+    ];
+    for (first, second) in exclusive {
+        if dynamic(first) && dynamic(second) {
+            bail!("Dynamic states {:?} and {:?} are exclusive", first, second);
+        }
+    }
+
+    let contradictions = [
+        (
+            fixed.viewport,
+            vk::DynamicState::VIEWPORT,
+            "a fixed viewport is set",
+        ),
+        (
+            fixed.scissor,
+            vk::DynamicState::SCISSOR,
+            "a fixed scissor is set",
+        ),
+        (
+            fixed.blend_constants,
+            vk::DynamicState::BLEND_CONSTANTS,
+            "fixed blend constants are set",
+        ),
+        (
+            fixed.color_write_mask,
+            vk::DynamicState::COLOR_WRITE_MASK_EXT,
+            "a fixed color write mask is set",
+        ),
+        (
+            !fixed.depth_bounds_test,
+            vk::DynamicState::DEPTH_BOUNDS,
+            "the depth bounds test is disabled",
+        ),
+        (
+            !fixed.stencil_test,
+            vk::DynamicState::STENCIL_COMPARE_MASK,
+            "the stencil test is disabled",
+        ),
+        (
+            !fixed.stencil_test,
+            vk::DynamicState::STENCIL_WRITE_MASK,
+            "the stencil test is disabled",
+        ),
+        (
+            !fixed.stencil_test,
+            vk::DynamicState::STENCIL_REFERENCE,
+            "the stencil test is disabled",
+        ),
+    ];
+    for (contradicted, state, reason) in contradictions {
+        if contradicted && dynamic(state) {
+            bail!("Dynamic state {:?} has no effect: {}", state, reason);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::SPIRV_MAGIC;
+
+    fn reflect(code: &[u8]) -> ShaderReflection {
+        ShaderReflection::from_spirv(code).unwrap()
+    }
+
+    #[test]
+    fn reads_unaligned_spirv() {
+        let code = include_bytes!("../../bin/lightmapped.frag.spv");
+        let words = spirv_words(code).unwrap();
+        assert_eq!(words.len() * 4, code.len());
+        assert_eq!(words[0], SPIRV_MAGIC);
+
+        let mut shifted = vec![0u8];
+        shifted.extend_from_slice(code);
+        assert_eq!(spirv_words(&shifted[1..]).unwrap(), words);
+
+        let swapped: Vec<u8> = words.iter().flat_map(|word| word.to_be_bytes()).collect();
+        assert_eq!(spirv_words(&swapped).unwrap(), words);
+
+        assert!(spirv_words(&code[..code.len() - 1]).is_err());
+        assert!(spirv_words(&code[..8]).is_err());
+        let error = spirv_words(b"#version 450\nvoid main() {}\n").unwrap_err();
+        assert!(error.to_string().contains("magic number"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_mismatched_shader_stages() {
+        let vertex = reflect(include_bytes!("../../bin/lightmapped.vert.spv"));
+        let fragment = reflect(include_bytes!("../../bin/lightmapped.frag.spv"));
+        let stage = |stage, reflection| ShaderStageInterface {
+            stage,
+            entry_point: "main",
+            reflection: Some(reflection),
+        };
+
+        assert!(
+            check_shader_stages(&[
+                stage(vk::ShaderStageFlags::VERTEX, &vertex),
+                stage(vk::ShaderStageFlags::FRAGMENT, &fragment),
+            ])
+            .is_ok()
+        );
+
+        let swapped = check_shader_stages(&[
+            stage(vk::ShaderStageFlags::VERTEX, &fragment),
+            stage(vk::ShaderStageFlags::FRAGMENT, &vertex),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            swapped.to_string(),
+            "Shader added as VERTEX has no VERTEX entry point 'main' (it declares FRAGMENT 'main')"
+        );
+
+        assert!(check_shader_stages(&[stage(vk::ShaderStageFlags::FRAGMENT, &fragment)]).is_err());
+        assert!(
+            check_shader_stages(&[
+                stage(vk::ShaderStageFlags::VERTEX, &vertex),
+                stage(vk::ShaderStageFlags::VERTEX, &vertex),
+            ])
+            .is_err()
+        );
+        assert!(check_shader_stages(&[]).is_err());
+
+        // Fragment outputs need color blend attachments, unless none are given at all.
+        let stages = [stage(vk::ShaderStageFlags::FRAGMENT, &fragment)];
+        assert_eq!(fragment_output_count(&stages), 1);
+        assert!(check_color_blend_attachments(1, 0).is_ok());
+        assert!(check_color_blend_attachments(1, 1).is_ok());
+        assert!(check_color_blend_attachments(3, 2).is_err());
+    }
+
+    #[test]
+    fn rejects_vertex_input_without_bindings_or_attributes() {
+        let vertex = reflect(include_bytes!("../../bin/lightmapped.vert.spv"));
+        let stages = [ShaderStageInterface {
+            stage: vk::ShaderStageFlags::VERTEX,
+            entry_point: "main",
+            reflection: Some(&vertex),
+        }];
+        let binding = vk::VertexInputBindingDescription::default()
+            .binding(0)
+            .stride(48);
+        let attributes: Vec<_> = (0..4)
+            .map(|location| {
+                vk::VertexInputAttributeDescription::default()
+                    .location(location)
+                    .binding(0)
+                    .format(vk::Format::R32G32B32_SFLOAT)
+                    .offset(location * 12)
+            })
+            .collect();
+
+        assert!(check_vertex_input(&[binding], &attributes, &stages).is_ok());
+        assert_eq!(
+            check_vertex_input(&[binding], &attributes[..3], &stages)
+                .unwrap_err()
+                .to_string()
+                .split(" at ")
+                .nth(1),
+            Some("location 3 has no vertex attribute")
+        );
+        assert_eq!(
+            check_vertex_input(&[binding.binding(1)], &attributes, &stages)
+                .unwrap_err()
+                .to_string(),
+            "Vertex attribute at location 0 uses binding 0, which has no vertex binding"
+        );
+        assert!(check_vertex_input(&[binding, binding], &attributes, &stages).is_err());
+        let mut duplicated = attributes.clone();
+        duplicated[1].location = 0;
+        assert!(check_vertex_input(&[binding], &duplicated, &stages).is_err());
+    }
+
+    #[test]
+    fn rejects_contradicting_dynamic_states() {
+        let viewport_and_scissor = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        assert!(check_dynamic_states(&viewport_and_scissor, FixedState::default()).is_ok());
+
+        let fixed_viewport = FixedState {
+            viewport: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            check_dynamic_states(&viewport_and_scissor, fixed_viewport)
+                .unwrap_err()
+                .to_string(),
+            "Dynamic state VIEWPORT has no effect: a fixed viewport is set"
+        );
+
+        for states in [
+            &[vk::DynamicState::SCISSOR, vk::DynamicState::SCISSOR][..],
+            &[
+                vk::DynamicState::VIEWPORT,
+                vk::DynamicState::VIEWPORT_WITH_COUNT,
+            ],
+            &[vk::DynamicState::DEPTH_BOUNDS],
+            &[vk::DynamicState::STENCIL_REFERENCE],
+        ] {
+            assert!(
+                check_dynamic_states(states, FixedState::default()).is_err(),
+                "{:?}",
+                states
+            );
+        }
+
+        let stencil = FixedState {
+            stencil_test: true,
+            depth_bounds_test: true,
+            ..Default::default()
+        };
+        assert!(
+            check_dynamic_states(
+                &[
+                    vk::DynamicState::DEPTH_BOUNDS,
+                    vk::DynamicState::STENCIL_REFERENCE
+                ],
+                stencil
+            )
+            .is_ok()
+        );
+        let fixed_mask = FixedState {
+            color_write_mask: true,
+            ..Default::default()
+        };
+        assert!(
+            check_dynamic_states(&[vk::DynamicState::COLOR_WRITE_MASK_EXT], fixed_mask).is_err()
+        );
+        assert!(
+            check_dynamic_states(
+                &[vk::DynamicState::COLOR_WRITE_MASK_EXT],
+                FixedState::default()
+            )
+            .is_ok()
+        );
+    }
+}
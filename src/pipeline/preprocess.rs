@@ -0,0 +1,402 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Shader snippets available to `#include`: in-memory files first, then directories on disk.
+#[derive(Debug, Clone, Default)]
+pub struct ShaderIncludes {
+    files: HashMap<String, String>,
+    directories: Vec<PathBuf>,
+}
+
+impl ShaderIncludes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a virtual file, included as `#include "<name>"`.
+    pub fn with_file(mut self, name: impl Into<String>, source: impl Into<String>) -> Self {
+        self.add_file(name, source);
+        self
+    }
+
+    pub fn with_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.directories.push(directory.into());
+        self
+    }
+
+    pub fn add_file(&mut self, name: impl Into<String>, source: impl Into<String>) {
+        self.files
+            .insert(normalize(Path::new(&name.into())), source.into());
+    }
+
+    /// Finds `name` relative to the including file, then among the virtual files, then in
+    /// each directory. Returns the resolved name and the source.
+    fn resolve(&self, name: &str, from: &str) -> Option<(String, String)> {
+        let relative = Path::new(from)
+            .parent()
+            .map(|parent| normalize(&parent.join(name)));
+
+        for candidate in relative.iter().map(String::as_str).chain([name]) {
+            if let Some(source) = self.files.get(candidate) {
+                return Some((candidate.to_string(), source.clone()));
+            }
+        }
+
+        if let Some(relative) = &relative
+            && let Ok(source) = std::fs::read_to_string(relative)
+        {
+            return Some((relative.clone(), source));
+        }
+
+        self.directories.iter().find_map(|directory| {
+            let path = directory.join(name);
+            std::fs::read_to_string(&path)
+                .ok()
+                .map(|source| (normalize(&path), source))
+        })
+    }
+}
+
+/// Collapses `.` and `..` so the same file reached through different includes shares a name.
+fn normalize(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let absolute = path.starts_with('/');
+    let mut parts: Vec<&str> = Vec::new();
+
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." if parts.last().is_some_and(|last| *last != "..") => {
+                parts.pop();
+            }
+            ".." if absolute => {}
+            _ => parts.push(component),
+        }
+    }
+
+    let joined = parts.join("/");
+    if absolute {
+        format!("/{}", joined)
+    } else {
+        joined
+    }
+}
+
+/// File and 1-based line an expanded line came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u32,
+}
+
+/// Shader source with includes expanded and defines injected, plus the origin of each line.
+#[derive(Debug, Clone)]
+pub struct PreprocessedShader {
+    pub source: String,
+    lines: Vec<SourceLocation>,
+}
+
+impl PreprocessedShader {
+    /// Origin of 1-based `line` of the expanded source.
+    pub fn location(&self, line: u32) -> Option<&SourceLocation> {
+        self.lines.get((line as usize).checked_sub(1)?)
+    }
+
+    /// Rewrites `file:line:` and glslang's `0:line:` references in compiler output to the
+    /// original files and lines.
+    pub fn remap_errors(&self, message: &str) -> String {
+        message
+            .lines()
+            .map(|line| self.remap_line(line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn remap_line(&self, line: &str) -> String {
+        let bytes = line.as_bytes();
+
+        for (colon, _) in line.match_indices(':') {
+            let digits = bytes[colon + 1..]
+                .iter()
+                .take_while(|b| b.is_ascii_digit())
+                .count();
+            let end = colon + 1 + digits;
+            if digits == 0 || bytes.get(end) != Some(&b':') {
+                continue;
+            }
+
+            let Some(location) = line[colon + 1..end]
+                .parse()
+                .ok()
+                .and_then(|number| self.location(number))
+            else {
+                continue;
+            };
+
+            let start = line[..colon]
+                .rfind(char::is_whitespace)
+                .map_or(0, |space| space + 1);
+            return format!(
+                "{}{}:{}{}",
+                &line[..start],
+                location.file,
+                location.line,
+                &line[end..]
+            );
+        }
+
+        line.to_string()
+    }
+}
+
+/// Turns `NAME` or `NAME=VALUE` into a `#define` line, like `glslc -D`.
+pub fn define_directive(define: &str) -> String {
+    match define.split_once('=') {
+        Some((name, value)) => format!("#define {} {}", name.trim(), value.trim()),
+        None => format!("#define {}", define.trim()),
+    }
+}
+
+/// Expands `#include "file"` and `#include <file>` recursively, honoring `#pragma once`, and
+/// injects `defines` (see `define_directive`) right after `#version`.
+///
+/// Includes are resolved textually, including inside inactive `#if` blocks. The
+/// `GL_GOOGLE_include_directive` extension line is dropped since nothing is left to include.
+pub fn preprocess_shader(
+    name: &str,
+    source: &str,
+    includes: &ShaderIncludes,
+    defines: &[&str],
+) -> Result<PreprocessedShader> {
+    let has_version = source
+        .lines()
+        .any(|line| line.trim_start().starts_with("#version"));
+
+    let mut expander = Expander {
+        includes,
+        output: PreprocessedShader {
+            source: String::new(),
+            lines: Vec::new(),
+        },
+        stack: Vec::new(),
+        included_once: HashSet::new(),
+        defines,
+        defines_injected: false,
+    };
+
+    if !has_version {
+        expander.push_defines();
+    }
+    expander.expand(&normalize(Path::new(name)), source)?;
+
+    Ok(expander.output)
+}
+
+struct Expander<'a> {
+    includes: &'a ShaderIncludes,
+    output: PreprocessedShader,
+    stack: Vec<String>,
+    included_once: HashSet<String>,
+    defines: &'a [&'a str],
+    defines_injected: bool,
+}
+
+impl Expander<'_> {
+    fn expand(&mut self, file: &str, source: &str) -> Result<()> {
+        if self.stack.iter().any(|open| open == file) {
+            let mut cycle = self.stack.clone();
+            cycle.push(file.to_string());
+            return Err(anyhow::anyhow!("Include cycle: {}", cycle.join(" -> ")));
+        }
+        self.stack.push(file.to_string());
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index as u32 + 1;
+            let directive = line.trim_start();
+
+            if directive.starts_with("#pragma") && directive[7..].trim() == "once" {
+                self.included_once.insert(file.to_string());
+                continue;
+            }
+
+            if directive.starts_with("#extension")
+                && directive.contains("GL_GOOGLE_include_directive")
+            {
+                continue;
+            }
+
+            if let Some(rest) = directive.strip_prefix("#include") {
+                let target = parse_include_target(rest).ok_or_else(|| {
+                    anyhow::anyhow!("{}:{}: malformed #include", file, line_number)
+                })?;
+                let (resolved, included) =
+                    self.includes.resolve(target, file).ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "{}:{}: cannot find include '{}'",
+                            file,
+                            line_number,
+                            target
+                        )
+                    })?;
+
+                if !self.included_once.contains(&resolved) {
+                    self.expand(&resolved, &included)?;
+                }
+                continue;
+            }
+
+            self.push_line(line, file, line_number);
+
+            if !self.defines_injected && directive.starts_with("#version") {
+                self.push_defines();
+            }
+        }
+
+        self.stack.pop();
+        Ok(())
+    }
+
+    fn push_defines(&mut self) {
+        self.defines_injected = true;
+        for (index, define) in self.defines.iter().enumerate() {
+            self.push_line(&define_directive(define), "<defines>", index as u32 + 1);
+        }
+    }
+
+    fn push_line(&mut self, line: &str, file: &str, line_number: u32) {
+        self.output.source.push_str(line);
+        self.output.source.push('\n');
+        self.output.lines.push(SourceLocation {
+            file: file.to_string(),
+            line: line_number,
+        });
+    }
+}
+
+fn parse_include_target(rest: &str) -> Option<&str> {
+    let rest = rest.trim();
+    let (open, close) = match rest.chars().next()? {
+        '"' => ('"', '"'),
+        '<' => ('<', '>'),
+        _ => return None,
+    };
+    let inner = rest.strip_prefix(open)?;
+    let end = inner.find(close)?;
+    Some(&inner[..end]).filter(|target| !target.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn includes() -> ShaderIncludes {
+        ShaderIncludes::new()
+            .with_file(
+                "lighting.glsl",
+                "#pragma once\n#include \"math/consts.glsl\"\nvec3 light;\n",
+            )
+            .with_file("math/consts.glsl", "#pragma once\nconst float PI = 3.0;\n")
+            .with_file(
+                "math/helpers.glsl",
+                "#include \"consts.glsl\"\nfloat sq(float x);\n",
+            )
+    }
+
+    #[test]
+    fn expands_nested_includes_once() {
+        let source = "#version 450\n#extension GL_GOOGLE_include_directive : require\n#include \"lighting.glsl\"\n#include <math/helpers.glsl>\nvoid main() {}\n";
+        let shader = preprocess_shader("mesh.frag", source, &includes(), &[]).unwrap();
+
+        assert_eq!(
+            shader.source,
+            "#version 450\nconst float PI = 3.0;\nvec3 light;\nfloat sq(float x);\nvoid main() {}\n"
+        );
+        assert_eq!(
+            shader.location(2),
+            Some(&SourceLocation {
+                file: "math/consts.glsl".into(),
+                line: 2
+            })
+        );
+        assert_eq!(shader.location(5).unwrap().file, "mesh.frag");
+        assert_eq!(shader.location(5).unwrap().line, 5);
+        assert_eq!(shader.location(6), None);
+    }
+
+    #[test]
+    fn defines_follow_version_and_accept_values() {
+        let shader = preprocess_shader(
+            "a.vert",
+            "#version 450\nvoid main() {}\n",
+            &ShaderIncludes::new(),
+            &["SKINNED", "MAX_JOINTS = 64"],
+        )
+        .unwrap();
+
+        assert_eq!(
+            shader.source,
+            "#version 450\n#define SKINNED\n#define MAX_JOINTS 64\nvoid main() {}\n"
+        );
+        assert_eq!(shader.location(3).unwrap().file, "<defines>");
+
+        let without_version =
+            preprocess_shader("b.glsl", "float x;", &ShaderIncludes::new(), &["A"]).unwrap();
+        assert_eq!(without_version.source, "#define A\nfloat x;\n");
+    }
+
+    #[test]
+    fn reports_missing_and_cyclic_includes() {
+        let missing = preprocess_shader(
+            "a.frag",
+            "#version 450\n#include \"nope.glsl\"\n",
+            &ShaderIncludes::new(),
+            &[],
+        )
+        .unwrap_err();
+        assert_eq!(
+            missing.to_string(),
+            "a.frag:2: cannot find include 'nope.glsl'"
+        );
+
+        let cyclic = ShaderIncludes::new()
+            .with_file("a.glsl", "#include \"b.glsl\"\n")
+            .with_file("b.glsl", "#include \"a.glsl\"\n");
+        let error =
+            preprocess_shader("main.frag", "#include \"a.glsl\"\n", &cyclic, &[]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Include cycle: main.frag -> a.glsl -> b.glsl -> a.glsl"
+        );
+
+        let malformed = preprocess_shader("m.frag", "#include nope\n", &ShaderIncludes::new(), &[]);
+        assert!(malformed.unwrap_err().to_string().contains("malformed"));
+    }
+
+    #[test]
+    fn remaps_compiler_errors() {
+        let shader = preprocess_shader(
+            "shaders/mesh.frag",
+            "#version 450\n#include \"lighting.glsl\"\nvoid main() { oops }\n",
+            &includes(),
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            shader.remap_errors("ERROR: 0:3: 'light' : redefinition\nERROR: 1 compilation errors."),
+            "ERROR: lighting.glsl:3: 'light' : redefinition\nERROR: 1 compilation errors."
+        );
+        assert_eq!(
+            shader.remap_errors("/tmp/x/mesh_frag.frag:4: error: 'oops' : undeclared"),
+            "shaders/mesh.frag:3: error: 'oops' : undeclared"
+        );
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(normalize(Path::new("a/./b/../c.glsl")), "a/c.glsl");
+        assert_eq!(normalize(Path::new("../c.glsl")), "../c.glsl");
+        assert_eq!(normalize(Path::new("/x/../y.glsl")), "/y.glsl");
+    }
+}
@@ -0,0 +1,764 @@
+use anyhow::Result;
+use ash::vk;
+use std::collections::HashMap;
+use std::ffi::CString;
+
+use crate::assets::SPIRV_MAGIC;
+
+mod op {
+    pub const NAME: u16 = 5;
+    pub const ENTRY_POINT: u16 = 15;
+    pub const EXECUTION_MODE: u16 = 16;
+    pub const TYPE_INT: u16 = 21;
+    pub const TYPE_FLOAT: u16 = 22;
+    pub const TYPE_VECTOR: u16 = 23;
+    pub const TYPE_MATRIX: u16 = 24;
+    pub const TYPE_IMAGE: u16 = 25;
+    pub const TYPE_SAMPLER: u16 = 26;
+    pub const TYPE_SAMPLED_IMAGE: u16 = 27;
+    pub const TYPE_ARRAY: u16 = 28;
+    pub const TYPE_RUNTIME_ARRAY: u16 = 29;
+    pub const TYPE_STRUCT: u16 = 30;
+    pub const TYPE_POINTER: u16 = 32;
+    pub const CONSTANT: u16 = 43;
+    pub const VARIABLE: u16 = 59;
+    pub const DECORATE: u16 = 71;
+    pub const MEMBER_DECORATE: u16 = 72;
+    pub const DECORATE_STRING: u16 = 5632;
+    pub const TYPE_ACCELERATION_STRUCTURE: u16 = 5341;
+}
+
+mod decoration {
+    pub const BUFFER_BLOCK: u32 = 3;
+    pub const ARRAY_STRIDE: u32 = 6;
+    pub const MATRIX_STRIDE: u32 = 7;
+    pub const LOCATION: u32 = 30;
+    pub const BINDING: u32 = 33;
+    pub const DESCRIPTOR_SET: u32 = 34;
+    pub const OFFSET: u32 = 35;
+    pub const USER_SEMANTIC: u32 = 5635;
+}
+
+mod storage_class {
+    pub const INPUT: u32 = 1;
+    pub const OUTPUT: u32 = 3;
+    pub const UNIFORM: u32 = 2;
+    pub const PUSH_CONSTANT: u32 = 9;
+    pub const STORAGE_BUFFER: u32 = 12;
+}
+
+/// An entry point of a SPIR-V module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderEntryPoint {
+    pub name: String,
+    pub stage: vk::ShaderStageFlags,
+    /// Workgroup size of compute, task and mesh shaders.
+    pub local_size: Option<[u32; 3]>,
+}
+
+/// A descriptor declared by a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflectedBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    /// Array length; 0 for runtime-sized (bindless) arrays.
+    pub count: u32,
+    pub name: Option<String>,
+}
+
+/// A `location`-decorated input or output of a shader stage, e.g. a vertex attribute or a
+/// fragment color output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageInput {
+    pub location: u32,
+    /// Stages of the entry points reading the input.
+    pub stage: vk::ShaderStageFlags,
+    pub name: Option<String>,
+    /// HLSL semantic (`POSITION`, `TEXCOORD0`, ...), emitted by DXC with `-fspv-reflect`.
+    pub semantic: Option<String>,
+}
+
+/// Entry points, descriptors and push constants found in a SPIR-V module, enough to set up
+/// pipelines for shaders whose interface isn't known up front, like multi-entry rust-gpu
+/// modules.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShaderReflection {
+    pub entry_points: Vec<ShaderEntryPoint>,
+    pub bindings: Vec<ReflectedBinding>,
+    pub inputs: Vec<StageInput>,
+    pub outputs: Vec<StageInput>,
+    /// Size in bytes of the push constant block, if any.
+    pub push_constant_size: Option<u32>,
+}
+
+impl ShaderReflection {
+    pub fn from_spirv(code: &[u8]) -> Result<Self> {
+        if !code.len().is_multiple_of(4) || code.len() < 20 {
+            return Err(anyhow::anyhow!(
+                "SPIR-V size must be a multiple of 4 and hold a header"
+            ));
+        }
+
+        let words: Vec<u32> = code
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect();
+        Self::from_words(&words)
+    }
+
+    pub fn from_words(words: &[u32]) -> Result<Self> {
+        if words.first() != Some(&SPIRV_MAGIC) {
+            return Err(anyhow::anyhow!("Not a little-endian SPIR-V module"));
+        }
+
+        let mut module = Module::default();
+        let mut offset = 5;
+        while offset < words.len() {
+            let word_count = (words[offset] >> 16) as usize;
+            let opcode = (words[offset] & 0xffff) as u16;
+            if word_count == 0 || offset + word_count > words.len() {
+                return Err(anyhow::anyhow!(
+                    "Truncated SPIR-V instruction at word {}",
+                    offset
+                ));
+            }
+
+            module.record(opcode, &words[offset + 1..offset + word_count]);
+            offset += word_count;
+        }
+
+        Ok(module.reflect())
+    }
+
+    pub fn entry_point(&self, stage: vk::ShaderStageFlags) -> Option<&ShaderEntryPoint> {
+        self.entry_points.iter().find(|entry| entry.stage == stage)
+    }
+
+    /// Entry point name for `stage`, or `name` when the module has several for it, ready for
+    /// `with_shader_spv`.
+    pub fn entry_point_name(
+        &self,
+        stage: vk::ShaderStageFlags,
+        name: Option<&str>,
+    ) -> Result<CString> {
+        let entry = self
+            .entry_points
+            .iter()
+            .find(|entry| entry.stage == stage && name.is_none_or(|name| entry.name == name))
+            .ok_or_else(|| {
+                anyhow::anyhow!("Shader module has no {:?} entry point {:?}", stage, name)
+            })?;
+
+        CString::new(entry.name.as_str())
+            .map_err(|e| anyhow::anyhow!("Failed to convert entry point name: {}", e))
+    }
+
+    /// Union of every entry point's stage.
+    pub fn stages(&self) -> vk::ShaderStageFlags {
+        self.entry_points
+            .iter()
+            .fold(vk::ShaderStageFlags::empty(), |stages, entry| {
+                stages | entry.stage
+            })
+    }
+
+    /// Layout bindings of descriptor set `set`, visible to every stage of the module since
+    /// SPIR-V before 1.4 doesn't list the resources each entry point uses.
+    pub fn layout_bindings(&self, set: u32) -> Vec<vk::DescriptorSetLayoutBinding<'static>> {
+        let mut bindings: Vec<_> = self
+            .bindings
+            .iter()
+            .filter(|binding| binding.set == set)
+            .map(|binding| {
+                vk::DescriptorSetLayoutBinding::default()
+                    .binding(binding.binding)
+                    .descriptor_type(binding.descriptor_type)
+                    .descriptor_count(binding.count.max(1))
+                    .stage_flags(self.stages())
+            })
+            .collect();
+        bindings.sort_by_key(|binding| binding.binding);
+        bindings
+    }
+
+    /// Inputs of the `stage` entry point, sorted by location.
+    pub fn stage_inputs(&self, stage: vk::ShaderStageFlags) -> impl Iterator<Item = &StageInput> {
+        self.inputs
+            .iter()
+            .filter(move |input| input.stage.contains(stage))
+    }
+
+    /// Outputs of the `stage` entry point, sorted by location.
+    pub fn stage_outputs(&self, stage: vk::ShaderStageFlags) -> impl Iterator<Item = &StageInput> {
+        self.outputs
+            .iter()
+            .filter(move |output| output.stage.contains(stage))
+    }
+
+    pub fn push_constant_range(&self) -> Option<vk::PushConstantRange> {
+        self.push_constant_size.map(|size| {
+            vk::PushConstantRange::default()
+                .stage_flags(self.stages())
+                .offset(0)
+                .size(size)
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Type {
+    Scalar { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { sampled: u32, dim: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray { element: u32 },
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+    AccelerationStructure,
+}
+
+#[derive(Default)]
+struct Module {
+    names: HashMap<u32, String>,
+    entry_points: Vec<(u32, ShaderEntryPoint)>,
+    interfaces: Vec<(vk::ShaderStageFlags, Vec<u32>)>,
+    semantics: HashMap<u32, String>,
+    local_sizes: HashMap<u32, [u32; 3]>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    variables: Vec<(u32, u32, u32)>,
+    decorations: HashMap<(u32, u32), u32>,
+    buffer_blocks: Vec<u32>,
+    member_offsets: HashMap<(u32, u32), u32>,
+    member_matrix_strides: HashMap<(u32, u32), u32>,
+}
+
+fn read_string(words: &[u32]) -> (String, usize) {
+    let mut bytes = Vec::new();
+    for (index, word) in words.iter().enumerate() {
+        for byte in word.to_le_bytes() {
+            if byte == 0 {
+                return (String::from_utf8_lossy(&bytes).into_owned(), index + 1);
+            }
+            bytes.push(byte);
+        }
+    }
+    (String::from_utf8_lossy(&bytes).into_owned(), words.len())
+}
+
+fn execution_model_stage(model: u32) -> Option<vk::ShaderStageFlags> {
+    Some(match model {
+        0 => vk::ShaderStageFlags::VERTEX,
+        1 => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+        2 => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+        3 => vk::ShaderStageFlags::GEOMETRY,
+        4 => vk::ShaderStageFlags::FRAGMENT,
+        5 => vk::ShaderStageFlags::COMPUTE,
+        5364 => vk::ShaderStageFlags::TASK_EXT,
+        5365 => vk::ShaderStageFlags::MESH_EXT,
+        _ => return None,
+    })
+}
+
+impl Module {
+    fn record(&mut self, opcode: u16, operands: &[u32]) {
+        let operand = |index: usize| operands.get(index).copied().unwrap_or(0);
+
+        match opcode {
+            op::NAME if !operands.is_empty() => {
+                self.names
+                    .insert(operands[0], read_string(&operands[1..]).0);
+            }
+            op::ENTRY_POINT if operands.len() >= 3 => {
+                if let Some(stage) = execution_model_stage(operands[0]) {
+                    let (name, name_words) = read_string(&operands[2..]);
+                    self.interfaces
+                        .push((stage, operands[2 + name_words..].to_vec()));
+                    self.entry_points.push((
+                        operands[1],
+                        ShaderEntryPoint {
+                            name,
+                            stage,
+                            local_size: None,
+                        },
+                    ));
+                }
+            }
+            // LocalSize
+            op::EXECUTION_MODE if operand(1) == 17 && operands.len() >= 5 => {
+                self.local_sizes
+                    .insert(operands[0], [operands[2], operands[3], operands[4]]);
+            }
+            op::TYPE_INT | op::TYPE_FLOAT => {
+                self.types
+                    .insert(operand(0), Type::Scalar { width: operand(1) });
+            }
+            op::TYPE_VECTOR => {
+                self.types.insert(
+                    operand(0),
+                    Type::Vector {
+                        component: operand(1),
+                        count: operand(2),
+                    },
+                );
+            }
+            op::TYPE_MATRIX => {
+                self.types.insert(
+                    operand(0),
+                    Type::Matrix {
+                        column: operand(1),
+                        count: operand(2),
+                    },
+                );
+            }
+            op::TYPE_IMAGE => {
+                self.types.insert(
+                    operand(0),
+                    Type::Image {
+                        dim: operand(2),
+                        sampled: operand(6),
+                    },
+                );
+            }
+            op::TYPE_SAMPLER => {
+                self.types.insert(operand(0), Type::Sampler);
+            }
+            op::TYPE_SAMPLED_IMAGE => {
+                self.types.insert(operand(0), Type::SampledImage);
+            }
+            op::TYPE_ARRAY => {
+                self.types.insert(
+                    operand(0),
+                    Type::Array {
+                        element: operand(1),
+                        length: operand(2),
+                    },
+                );
+            }
+            op::TYPE_RUNTIME_ARRAY => {
+                self.types.insert(
+                    operand(0),
+                    Type::RuntimeArray {
+                        element: operand(1),
+                    },
+                );
+            }
+            op::TYPE_STRUCT if !operands.is_empty() => {
+                self.types.insert(
+                    operands[0],
+                    Type::Struct {
+                        members: operands[1..].to_vec(),
+                    },
+                );
+            }
+            op::TYPE_POINTER => {
+                self.types.insert(
+                    operand(0),
+                    Type::Pointer {
+                        pointee: operand(2),
+                    },
+                );
+            }
+            op::TYPE_ACCELERATION_STRUCTURE => {
+                self.types.insert(operand(0), Type::AccelerationStructure);
+            }
+            // Only 32-bit constants are needed, for array lengths.
+            op::CONSTANT if operands.len() >= 3 => {
+                self.constants.insert(operands[1], operands[2]);
+            }
+            op::VARIABLE if operands.len() >= 3 => {
+                self.variables.push((operands[0], operands[1], operands[2]));
+            }
+            op::DECORATE if operands.len() >= 2 => {
+                if operands[1] == decoration::BUFFER_BLOCK {
+                    self.buffer_blocks.push(operands[0]);
+                } else if operands.len() >= 3 {
+                    self.decorations
+                        .insert((operands[0], operands[1]), operands[2]);
+                }
+            }
+            op::DECORATE_STRING
+                if operands.len() >= 3 && operands[1] == decoration::USER_SEMANTIC =>
+            {
+                self.semantics
+                    .insert(operands[0], read_string(&operands[2..]).0);
+            }
+            op::MEMBER_DECORATE if operands.len() >= 4 => match operands[2] {
+                decoration::OFFSET => {
+                    self.member_offsets
+                        .insert((operands[0], operands[1]), operands[3]);
+                }
+                decoration::MATRIX_STRIDE => {
+                    self.member_matrix_strides
+                        .insert((operands[0], operands[1]), operands[3]);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn reflect(self) -> ShaderReflection {
+        let entry_points = self
+            .entry_points
+            .iter()
+            .map(|(function, entry)| ShaderEntryPoint {
+                local_size: self.local_sizes.get(function).copied(),
+                ..entry.clone()
+            })
+            .collect();
+
+        let mut bindings = Vec::new();
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        let mut push_constant_size = None;
+
+        for &(pointer_type, variable, storage) in &self.variables {
+            let Some(Type::Pointer { pointee }) = self.types.get(&pointer_type) else {
+                continue;
+            };
+
+            if storage == storage_class::INPUT || storage == storage_class::OUTPUT {
+                if let Some(&location) = self.decorations.get(&(variable, decoration::LOCATION)) {
+                    let interface = StageInput {
+                        location,
+                        stage: self.interface_stages(variable),
+                        name: self.names.get(&variable).cloned(),
+                        semantic: self.semantics.get(&variable).cloned(),
+                    };
+                    if storage == storage_class::INPUT {
+                        inputs.push(interface);
+                    } else {
+                        outputs.push(interface);
+                    }
+                }
+                continue;
+            }
+
+            if storage == storage_class::PUSH_CONSTANT {
+                push_constant_size = Some(self.size_of(*pointee, None));
+                continue;
+            }
+
+            let (Some(&set), Some(&binding)) = (
+                self.decorations
+                    .get(&(variable, decoration::DESCRIPTOR_SET)),
+                self.decorations.get(&(variable, decoration::BINDING)),
+            ) else {
+                continue;
+            };
+
+            let (element, count) = match self.types.get(pointee) {
+                Some(Type::Array { element, length }) => {
+                    (*element, self.constants.get(length).copied().unwrap_or(1))
+                }
+                Some(Type::RuntimeArray { element }) => (*element, 0),
+                _ => (*pointee, 1),
+            };
+
+            let Some(descriptor_type) = self.descriptor_type(element, storage) else {
+                continue;
+            };
+
+            bindings.push(ReflectedBinding {
+                set,
+                binding,
+                descriptor_type,
+                count,
+                name: self
+                    .names
+                    .get(&variable)
+                    .filter(|name| !name.is_empty())
+                    .or_else(|| self.names.get(&element))
+                    .cloned(),
+            });
+        }
+
+        bindings.sort_by_key(|binding| (binding.set, binding.binding));
+        inputs.sort_by_key(|input| input.location);
+        outputs.sort_by_key(|output| output.location);
+
+        ShaderReflection {
+            entry_points,
+            bindings,
+            inputs,
+            outputs,
+            push_constant_size,
+        }
+    }
+
+    /// Stages whose entry point interface lists `variable`, or every stage if none does.
+    fn interface_stages(&self, variable: u32) -> vk::ShaderStageFlags {
+        let stages = self
+            .interfaces
+            .iter()
+            .filter(|(_, interface)| interface.contains(&variable))
+            .fold(vk::ShaderStageFlags::empty(), |stages, (stage, _)| {
+                stages | *stage
+            });
+
+        if stages.is_empty() {
+            self.interfaces
+                .iter()
+                .fold(vk::ShaderStageFlags::empty(), |stages, (stage, _)| {
+                    stages | *stage
+                })
+        } else {
+            stages
+        }
+    }
+
+    fn descriptor_type(&self, type_id: u32, storage: u32) -> Option<vk::DescriptorType> {
+        const DIM_BUFFER: u32 = 5;
+        const DIM_SUBPASS_DATA: u32 = 6;
+
+        Some(match (self.types.get(&type_id)?, storage) {
+            (Type::Sampler, _) => vk::DescriptorType::SAMPLER,
+            (Type::SampledImage, _) => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            (Type::Image { dim, .. }, _) if *dim == DIM_SUBPASS_DATA => {
+                vk::DescriptorType::INPUT_ATTACHMENT
+            }
+            (Type::Image { dim, sampled }, _) if *dim == DIM_BUFFER => {
+                if *sampled == 2 {
+                    vk::DescriptorType::STORAGE_TEXEL_BUFFER
+                } else {
+                    vk::DescriptorType::UNIFORM_TEXEL_BUFFER
+                }
+            }
+            (Type::Image { sampled: 2, .. }, _) => vk::DescriptorType::STORAGE_IMAGE,
+            (Type::Image { .. }, _) => vk::DescriptorType::SAMPLED_IMAGE,
+            (Type::AccelerationStructure, _) => vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+            (Type::Struct { .. }, storage_class::STORAGE_BUFFER) => {
+                vk::DescriptorType::STORAGE_BUFFER
+            }
+            (Type::Struct { .. }, storage_class::UNIFORM) => {
+                if self.buffer_blocks.contains(&type_id) {
+                    vk::DescriptorType::STORAGE_BUFFER
+                } else {
+                    vk::DescriptorType::UNIFORM_BUFFER
+                }
+            }
+            _ => return None,
+        })
+    }
+
+    /// Byte size of a type laid out with explicit offsets and strides.
+    fn size_of(&self, type_id: u32, matrix_stride: Option<u32>) -> u32 {
+        match self.types.get(&type_id) {
+            Some(Type::Scalar { width }) => width / 8,
+            Some(Type::Vector { component, count }) => self.size_of(*component, None) * count,
+            Some(Type::Matrix { column, count }) => {
+                matrix_stride.unwrap_or_else(|| self.size_of(*column, None)) * count
+            }
+            Some(Type::Array { element, length }) => {
+                let length = self.constants.get(length).copied().unwrap_or(1);
+                let stride = self
+                    .decorations
+                    .get(&(type_id, decoration::ARRAY_STRIDE))
+                    .copied()
+                    .unwrap_or_else(|| self.size_of(*element, None));
+                stride * length
+            }
+            Some(Type::Struct { members }) => members
+                .iter()
+                .enumerate()
+                .map(|(index, member)| {
+                    let key = (type_id, index as u32);
+                    self.member_offsets.get(&key).copied().unwrap_or(0)
+                        + self.size_of(*member, self.member_matrix_strides.get(&key).copied())
+                })
+                .max()
+                .unwrap_or(0),
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reflects_compute_shader() {
+        let reflection =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/gpu_cull.comp.spv")).unwrap();
+
+        assert_eq!(
+            reflection.entry_points,
+            [ShaderEntryPoint {
+                name: "main".into(),
+                stage: vk::ShaderStageFlags::COMPUTE,
+                local_size: Some([64, 1, 1]),
+            }]
+        );
+        assert_eq!(
+            reflection
+                .bindings
+                .iter()
+                .map(|b| (b.set, b.binding, b.descriptor_type))
+                .collect::<Vec<_>>(),
+            (0..4)
+                .map(|binding| (0, binding, vk::DescriptorType::STORAGE_BUFFER))
+                .collect::<Vec<_>>()
+        );
+        // vec4 planes[6] + uint object_count.
+        assert_eq!(reflection.push_constant_size, Some(100));
+    }
+
+    #[test]
+    fn reflects_graphics_shaders() {
+        let vertex =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/lightmapped.vert.spv")).unwrap();
+        assert_eq!(vertex.stages(), vk::ShaderStageFlags::VERTEX);
+        assert_eq!(vertex.push_constant_size, Some(64));
+        assert!(vertex.bindings.is_empty());
+        assert_eq!(
+            vertex
+                .stage_inputs(vk::ShaderStageFlags::VERTEX)
+                .map(|input| input.location)
+                .collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+
+        let fragment =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/lightmapped.frag.spv")).unwrap();
+        assert_eq!(
+            fragment
+                .entry_point(vk::ShaderStageFlags::FRAGMENT)
+                .unwrap()
+                .name,
+            "main"
+        );
+        assert!(
+            fragment
+                .bindings
+                .iter()
+                .all(|b| b.descriptor_type == vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        );
+        assert_eq!(fragment.layout_bindings(0).len(), fragment.bindings.len());
+        assert_eq!(
+            fragment
+                .stage_outputs(vk::ShaderStageFlags::FRAGMENT)
+                .map(|output| output.location)
+                .collect::<Vec<_>>(),
+            [0]
+        );
+        assert!(
+            vertex
+                .stage_outputs(vk::ShaderStageFlags::FRAGMENT)
+                .next()
+                .is_none()
+        );
+    }
+
+    fn string_words(text: &str) -> Vec<u32> {
+        let mut bytes = text.as_bytes().to_vec();
+        bytes.push(0);
+        while !bytes.len().is_multiple_of(4) {
+            bytes.push(0);
+        }
+        bytes
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect()
+    }
+
+    fn instruction(opcode: u16, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode as u32];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    #[test]
+    fn finds_every_entry_point_of_a_multi_entry_module() {
+        let mut words = vec![SPIRV_MAGIC, 0x0001_0300, 0, 100, 0];
+        for (model, function, name) in [(0, 1, "main_vs"), (4, 2, "main_fs"), (5, 3, "cull")] {
+            let mut operands = vec![model, function];
+            operands.extend(string_words(name));
+            if model == 0 {
+                // Interface: the vertex input below.
+                operands.push(21);
+            }
+            words.extend(instruction(op::ENTRY_POINT, &operands));
+        }
+        words.extend(instruction(op::EXECUTION_MODE, &[3, 17, 8, 8, 1]));
+
+        // A runtime array of combined image samplers at set 1, binding 2.
+        words.extend(instruction(op::TYPE_SAMPLED_IMAGE, &[10, 9]));
+        words.extend(instruction(op::TYPE_RUNTIME_ARRAY, &[11, 10]));
+        words.extend(instruction(op::TYPE_POINTER, &[12, 0, 11]));
+        words.extend(instruction(op::VARIABLE, &[12, 13, 0]));
+        words.extend(instruction(
+            op::DECORATE,
+            &[13, decoration::DESCRIPTOR_SET, 1],
+        ));
+        words.extend(instruction(op::DECORATE, &[13, decoration::BINDING, 2]));
+        let mut name = vec![13];
+        name.extend(string_words("textures"));
+        words.extend(instruction(op::NAME, &name));
+
+        // A vertex input at location 0 carrying an HLSL semantic.
+        words.extend(instruction(
+            op::TYPE_POINTER,
+            &[20, storage_class::INPUT, 9],
+        ));
+        words.extend(instruction(op::VARIABLE, &[20, 21, storage_class::INPUT]));
+        words.extend(instruction(op::DECORATE, &[21, decoration::LOCATION, 0]));
+        let mut semantic = vec![21, decoration::USER_SEMANTIC];
+        semantic.extend(string_words("TEXCOORD0"));
+        words.extend(instruction(op::DECORATE_STRING, &semantic));
+
+        let reflection = ShaderReflection::from_words(&words).unwrap();
+
+        assert_eq!(
+            reflection
+                .entry_points
+                .iter()
+                .map(|e| (e.name.as_str(), e.stage))
+                .collect::<Vec<_>>(),
+            [
+                ("main_vs", vk::ShaderStageFlags::VERTEX),
+                ("main_fs", vk::ShaderStageFlags::FRAGMENT),
+                ("cull", vk::ShaderStageFlags::COMPUTE),
+            ]
+        );
+        assert_eq!(reflection.entry_points[2].local_size, Some([8, 8, 1]));
+        assert_eq!(
+            reflection.bindings,
+            [ReflectedBinding {
+                set: 1,
+                binding: 2,
+                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                count: 0,
+                name: Some("textures".into()),
+            }]
+        );
+        assert_eq!(reflection.layout_bindings(1)[0].descriptor_count, 1);
+        assert_eq!(
+            reflection.inputs,
+            [StageInput {
+                location: 0,
+                stage: vk::ShaderStageFlags::VERTEX,
+                name: None,
+                semantic: Some("TEXCOORD0".into()),
+            }]
+        );
+        assert_eq!(
+            reflection
+                .stage_inputs(vk::ShaderStageFlags::FRAGMENT)
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_modules() {
+        assert!(ShaderReflection::from_spirv(&[0; 3]).is_err());
+        assert!(ShaderReflection::from_words(&[0, 0, 0, 0, 0]).is_err());
+        assert!(ShaderReflection::from_words(&[SPIRV_MAGIC, 0, 0, 0, 0, 0x0005_0005]).is_err());
+    }
+}
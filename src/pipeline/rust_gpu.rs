@@ -0,0 +1,278 @@
+use anyhow::Result;
+use ash::vk;
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::{ShaderReflection, VulkanComputePipelineBuilder, VulkanPipelineBuilder};
+
+/// Target triple rust-gpu shader crates are built for by default.
+pub const RUST_GPU_TARGET: &str = "spirv-unknown-vulkan1.2";
+
+/// Environment variable pointing at `librustc_codegen_spirv`.
+pub const RUST_GPU_CODEGEN_BACKEND_ENV: &str = "RUSTGPU_CODEGEN_BACKEND";
+
+/// Includes the SPIR-V module of a shader crate compiled by [`RustGpuBuild`] in the build script.
+#[macro_export]
+macro_rules! include_rust_gpu_shader {
+    ($name:literal) => {
+        include_bytes!(env!(concat!("RUST_GPU_SPV_", $name)))
+    };
+}
+
+/// SPIR-V module produced by a rust-gpu crate. Such modules usually hold every stage of a
+/// material under its own entry point (`main_vs`, `main_fs`, ...), found here by reflection.
+pub struct RustGpuShader {
+    pub spirv: Vec<u8>,
+    pub reflection: ShaderReflection,
+}
+
+impl RustGpuShader {
+    pub fn from_spirv(spirv: &[u8]) -> Result<Self> {
+        let reflection = ShaderReflection::from_spirv(spirv)?;
+        if reflection.entry_points.is_empty() {
+            return Err(anyhow::anyhow!("rust-gpu module has no entry points"));
+        }
+
+        Ok(Self {
+            spirv: spirv.to_vec(),
+            reflection,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let spirv = std::fs::read(path).map_err(|e| {
+            anyhow::anyhow!("Failed to read rust-gpu shader {}: {}", path.display(), e)
+        })?;
+        Self::from_spirv(&spirv)
+    }
+
+    /// Name of the entry point for `stage`, or of `name` when the module has several for it.
+    pub fn entry_point(&self, stage: vk::ShaderStageFlags, name: Option<&str>) -> Result<CString> {
+        self.reflection.entry_point_name(stage, name)
+    }
+
+    /// Adds the vertex and fragment entry points of the module.
+    pub fn add_graphics_stages(
+        &self,
+        mut builder: VulkanPipelineBuilder,
+    ) -> Result<VulkanPipelineBuilder> {
+        for stage in [vk::ShaderStageFlags::VERTEX, vk::ShaderStageFlags::FRAGMENT] {
+            let entry = self.entry_point(stage, None)?;
+            builder = builder.with_shader_spv(&self.spirv, stage, Some(&entry))?;
+        }
+        Ok(builder)
+    }
+
+    pub fn add_compute_stage(
+        &self,
+        builder: VulkanComputePipelineBuilder,
+        name: Option<&str>,
+    ) -> Result<VulkanComputePipelineBuilder> {
+        let entry = self.entry_point(vk::ShaderStageFlags::COMPUTE, name)?;
+        builder.with_shader_spv(&self.spirv, Some(&entry))
+    }
+}
+
+/// Build-script helper compiling a rust-gpu shader crate to SPIR-V. The module path is exported
+/// as `RUST_GPU_SPV_<NAME>` for [`include_rust_gpu_shader!`].
+///
+/// ```ignore
+/// // build.rs
+/// RustGpuBuild::new("shaders/sky", "SKY").build()?;
+/// // renderer
+/// let shader = RustGpuShader::from_spirv(include_rust_gpu_shader!("SKY"))?;
+/// ```
+pub struct RustGpuBuild {
+    crate_path: PathBuf,
+    name: String,
+    target: String,
+    codegen_backend: Option<PathBuf>,
+    target_dir: Option<PathBuf>,
+    release: bool,
+}
+
+impl RustGpuBuild {
+    pub fn new(crate_path: impl Into<PathBuf>, name: &str) -> Self {
+        Self {
+            crate_path: crate_path.into(),
+            name: name.to_string(),
+            target: RUST_GPU_TARGET.to_string(),
+            codegen_backend: None,
+            target_dir: None,
+            release: true,
+        }
+    }
+
+    pub fn with_target(mut self, target: &str) -> Self {
+        self.target = target.to_string();
+        self
+    }
+
+    /// Path to `librustc_codegen_spirv`, read from `RUSTGPU_CODEGEN_BACKEND` when unset.
+    pub fn with_codegen_backend(mut self, path: impl Into<PathBuf>) -> Self {
+        self.codegen_backend = Some(path.into());
+        self
+    }
+
+    /// Defaults to `$OUT_DIR/rust-gpu` so the shader build doesn't lock the main target dir.
+    pub fn with_target_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.target_dir = Some(path.into());
+        self
+    }
+
+    pub fn with_release(mut self, release: bool) -> Self {
+        self.release = release;
+        self
+    }
+
+    /// Path of the produced module: `<target_dir>/<target>/<profile>/<crate>.spv`.
+    pub fn output_path(&self, target_dir: &Path) -> PathBuf {
+        let crate_name = self
+            .crate_path
+            .file_name()
+            .map(|name| name.to_string_lossy().replace('-', "_"))
+            .unwrap_or_default();
+
+        target_dir
+            .join(&self.target)
+            .join(if self.release { "release" } else { "debug" })
+            .join(format!("{}.spv", crate_name))
+    }
+
+    /// `cargo build` invocation of the shader crate.
+    pub fn command(&self, codegen_backend: &Path, target_dir: &Path) -> Command {
+        let mut command = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()));
+        command
+            .arg("build")
+            .arg("--manifest-path")
+            .arg(self.crate_path.join("Cargo.toml"))
+            .arg("--target")
+            .arg(&self.target)
+            .arg("--target-dir")
+            .arg(target_dir)
+            .arg("-Zbuild-std=core")
+            .arg("-Zbuild-std-features=compiler-builtins-mem");
+        if self.release {
+            command.arg("--release");
+        }
+
+        command.env(
+            "RUSTFLAGS",
+            format!(
+                "-Zcodegen-backend={} -Zcrate-attr=feature(register_tool) -Zcrate-attr=register_tool(rust_gpu)",
+                codegen_backend.display()
+            ),
+        );
+        // Flags of the outer build would leak into the shader crate otherwise.
+        command.env_remove("CARGO_ENCODED_RUSTFLAGS");
+        command
+    }
+
+    /// Compiles the shader crate and prints the cargo directives for the calling build script.
+    pub fn build(&self) -> Result<PathBuf> {
+        let codegen_backend = match &self.codegen_backend {
+            Some(path) => path.clone(),
+            None => std::env::var_os(RUST_GPU_CODEGEN_BACKEND_ENV)
+                .map(PathBuf::from)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "No rust-gpu codegen backend, set {}",
+                        RUST_GPU_CODEGEN_BACKEND_ENV
+                    )
+                })?,
+        };
+
+        let target_dir = match &self.target_dir {
+            Some(path) => path.clone(),
+            None => std::env::var_os("OUT_DIR")
+                .map(|dir| PathBuf::from(dir).join("rust-gpu"))
+                .ok_or_else(|| anyhow::anyhow!("OUT_DIR is not set, call from a build script"))?,
+        };
+
+        let status = self
+            .command(&codegen_backend, &target_dir)
+            .status()
+            .map_err(|e| anyhow::anyhow!("Failed to run cargo for rust-gpu shaders: {}", e))?;
+        if !status.success() {
+            return Err(anyhow::anyhow!(
+                "Failed to build rust-gpu shader crate {}: {}",
+                self.crate_path.display(),
+                status
+            ));
+        }
+
+        let output = self.output_path(&target_dir);
+        if !output.exists() {
+            return Err(anyhow::anyhow!(
+                "rust-gpu build produced no module at {}",
+                output.display()
+            ));
+        }
+
+        println!("cargo:rerun-if-changed={}", self.crate_path.display());
+        println!(
+            "cargo:rerun-if-env-changed={}",
+            RUST_GPU_CODEGEN_BACKEND_ENV
+        );
+        println!(
+            "cargo:rustc-env=RUST_GPU_SPV_{}={}",
+            self.name,
+            output.display()
+        );
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_entry_points_by_stage_and_name() {
+        let shader =
+            RustGpuShader::from_spirv(include_bytes!("../../bin/gpu_cull.comp.spv")).unwrap();
+
+        assert_eq!(
+            shader
+                .entry_point(vk::ShaderStageFlags::COMPUTE, None)
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            "main"
+        );
+        assert!(
+            shader
+                .entry_point(vk::ShaderStageFlags::COMPUTE, Some("other"))
+                .is_err()
+        );
+        assert!(
+            shader
+                .entry_point(vk::ShaderStageFlags::VERTEX, None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn build_command_targets_spirv() {
+        let build = RustGpuBuild::new("shaders/sky-shader", "SKY");
+        let command = build.command(Path::new("/codegen.so"), Path::new("/out"));
+        let args: Vec<_> = command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+
+        assert!(args.windows(2).any(|w| w == ["--target", RUST_GPU_TARGET]));
+        assert!(args.contains(&"--release".to_string()));
+        assert!(command.get_envs().any(|(key, value)| key == "RUSTFLAGS"
+            && value.is_some_and(|v| {
+                v.to_string_lossy()
+                    .contains("-Zcodegen-backend=/codegen.so")
+            })));
+        assert_eq!(
+            build.output_path(Path::new("/out")),
+            Path::new("/out/spirv-unknown-vulkan1.2/release/sky_shader.spv")
+        );
+    }
+}
@@ -0,0 +1,520 @@
+use anyhow::{Result, bail};
+use ash::{Device, vk};
+use std::ffi::CString;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::vulkan::{VulkanDevice, VulkanDeviceFeatures};
+
+pub const GRAPHICS_BACKEND_ENV: &str = "RVE_GRAPHICS_BACKEND";
+
+/// How graphics shaders and their fixed-function state reach the GPU.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GraphicsBackend {
+    /// `VulkanPipeline`s, with state baked in at creation.
+    #[default]
+    Pipelines,
+    /// `VulkanShaderObjects`, with every piece of state set while recording.
+    ShaderObjects,
+}
+
+impl GraphicsBackend {
+    /// Reads `RVE_GRAPHICS_BACKEND` (`pipelines` or `shader_objects`), defaulting to
+    /// pipelines. Shader objects fall back to pipelines on devices without them.
+    pub fn from_env(features: &VulkanDeviceFeatures) -> Result<Self> {
+        let backend = match std::env::var(GRAPHICS_BACKEND_ENV) {
+            Ok(value) => value.parse()?,
+            Err(_) => Self::default(),
+        };
+        Ok(backend.supported_by(features))
+    }
+
+    /// This backend, or `Pipelines` if the device can't use it.
+    pub fn supported_by(self, features: &VulkanDeviceFeatures) -> Self {
+        match self {
+            Self::ShaderObjects if !features.shader_object => Self::Pipelines,
+            backend => backend,
+        }
+    }
+}
+
+impl FromStr for GraphicsBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "pipelines" => Ok(Self::Pipelines),
+            "shader_objects" => Ok(Self::ShaderObjects),
+            other => Err(anyhow::anyhow!(
+                "Unknown graphics backend '{}', expected 'pipelines' or 'shader_objects'",
+                other
+            )),
+        }
+    }
+}
+
+/// Everything a graphics pipeline would fix at creation, set on every `bind` instead. Defaults
+/// match `VulkanPipelineBuilder`'s, and fields can be changed between draws without creating
+/// anything.
+#[derive(Debug, Clone)]
+pub struct ShaderObjectState {
+    pub vertex_bindings: Vec<vk::VertexInputBindingDescription>,
+    pub vertex_attributes: Vec<vk::VertexInputAttributeDescription>,
+    pub topology: vk::PrimitiveTopology,
+    pub primitive_restart_enable: bool,
+    pub polygon_mode: vk::PolygonMode,
+    pub cull_mode: vk::CullModeFlags,
+    pub front_face: vk::FrontFace,
+    pub line_width: f32,
+    pub rasterizer_discard_enable: bool,
+    pub rasterization_samples: vk::SampleCountFlags,
+    pub depth_test_enable: bool,
+    pub depth_write_enable: bool,
+    pub depth_compare_op: vk::CompareOp,
+    /// One entry per color attachment; the write mask is replaced by `color_write_mask`.
+    pub color_blend_attachments: Vec<vk::PipelineColorBlendAttachmentState>,
+    pub color_write_mask: vk::ColorComponentFlags,
+}
+
+impl Default for ShaderObjectState {
+    fn default() -> Self {
+        Self {
+            vertex_bindings: Vec::new(),
+            vertex_attributes: Vec::new(),
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            primitive_restart_enable: false,
+            polygon_mode: vk::PolygonMode::FILL,
+            cull_mode: vk::CullModeFlags::BACK,
+            front_face: vk::FrontFace::CLOCKWISE,
+            line_width: 1.0,
+            rasterizer_discard_enable: false,
+            rasterization_samples: vk::SampleCountFlags::TYPE_1,
+            depth_test_enable: false,
+            depth_write_enable: false,
+            depth_compare_op: vk::CompareOp::LESS,
+            color_blend_attachments: vec![vk::PipelineColorBlendAttachmentState::default()],
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+        }
+    }
+}
+
+impl ShaderObjectState {
+    pub fn with_vertex_binding(mut self, binding: vk::VertexInputBindingDescription) -> Self {
+        self.vertex_bindings.push(binding);
+        self
+    }
+
+    pub fn with_vertex_attribute(mut self, attribute: vk::VertexInputAttributeDescription) -> Self {
+        self.vertex_attributes.push(attribute);
+        self
+    }
+
+    pub fn with_topology(mut self, topology: vk::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn with_cull_mode(mut self, mode: vk::CullModeFlags) -> Self {
+        self.cull_mode = mode;
+        self
+    }
+
+    pub fn with_depth_test(mut self, write: bool, compare_op: vk::CompareOp) -> Self {
+        self.depth_test_enable = true;
+        self.depth_write_enable = write;
+        self.depth_compare_op = compare_op;
+        self
+    }
+
+    /// Same blending as `VulkanPipelineBuilder::with_alpha_blending`, for a single attachment.
+    pub fn with_alpha_blending(mut self) -> Self {
+        self.color_blend_attachments = vec![
+            vk::PipelineColorBlendAttachmentState::default()
+                .blend_enable(true)
+                .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+                .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+                .color_blend_op(vk::BlendOp::ADD)
+                .src_alpha_blend_factor(vk::BlendFactor::ONE)
+                .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
+                .alpha_blend_op(vk::BlendOp::ADD),
+        ];
+        self
+    }
+
+    /// Vertex input in the form `vkCmdSetVertexInputEXT` takes.
+    fn vertex_input(
+        &self,
+    ) -> (
+        Vec<vk::VertexInputBindingDescription2EXT<'static>>,
+        Vec<vk::VertexInputAttributeDescription2EXT<'static>>,
+    ) {
+        let bindings = self
+            .vertex_bindings
+            .iter()
+            .map(|binding| {
+                vk::VertexInputBindingDescription2EXT::default()
+                    .binding(binding.binding)
+                    .stride(binding.stride)
+                    .input_rate(binding.input_rate)
+                    .divisor(1)
+            })
+            .collect();
+        let attributes = self
+            .vertex_attributes
+            .iter()
+            .map(|attribute| {
+                vk::VertexInputAttributeDescription2EXT::default()
+                    .location(attribute.location)
+                    .binding(attribute.binding)
+                    .format(attribute.format)
+                    .offset(attribute.offset)
+            })
+            .collect();
+        (bindings, attributes)
+    }
+
+    /// Blend enables and equations per attachment.
+    fn blend(&self) -> (Vec<vk::Bool32>, Vec<vk::ColorBlendEquationEXT>) {
+        self.color_blend_attachments
+            .iter()
+            .map(|attachment| {
+                (
+                    attachment.blend_enable,
+                    vk::ColorBlendEquationEXT::default()
+                        .src_color_blend_factor(attachment.src_color_blend_factor)
+                        .dst_color_blend_factor(attachment.dst_color_blend_factor)
+                        .color_blend_op(attachment.color_blend_op)
+                        .src_alpha_blend_factor(attachment.src_alpha_blend_factor)
+                        .dst_alpha_blend_factor(attachment.dst_alpha_blend_factor)
+                        .alpha_blend_op(attachment.alpha_blend_op),
+                )
+            })
+            .unzip()
+    }
+
+    /// Sets every state shader objects need for a vertex and fragment shader drawing into
+    /// `extent`. Optional features this device doesn't enable, like depth clamp or logic ops,
+    /// are left alone as the spec requires.
+    fn record(
+        &self,
+        device: &Device,
+        loader: &ash::ext::shader_object::Device,
+        command_buffer: vk::CommandBuffer,
+        extent: vk::Extent2D,
+    ) {
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: extent.width as f32,
+            height: extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let scissor = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+        let (bindings, attributes) = self.vertex_input();
+        let (blend_enables, blend_equations) = self.blend();
+        let write_masks = vec![self.color_write_mask; self.color_blend_attachments.len()];
+
+        unsafe {
+            loader.cmd_set_viewport_with_count(command_buffer, &[viewport]);
+            loader.cmd_set_scissor_with_count(command_buffer, &[scissor]);
+            loader.cmd_set_vertex_input(command_buffer, &bindings, &attributes);
+            loader.cmd_set_primitive_topology(command_buffer, self.topology);
+            loader.cmd_set_primitive_restart_enable(command_buffer, self.primitive_restart_enable);
+            loader
+                .cmd_set_rasterizer_discard_enable(command_buffer, self.rasterizer_discard_enable);
+            if self.rasterizer_discard_enable {
+                return;
+            }
+
+            loader.cmd_set_polygon_mode(command_buffer, self.polygon_mode);
+            loader.cmd_set_cull_mode(command_buffer, self.cull_mode);
+            loader.cmd_set_front_face(command_buffer, self.front_face);
+            device.cmd_set_line_width(command_buffer, self.line_width);
+            loader.cmd_set_depth_bias_enable(command_buffer, false);
+            loader.cmd_set_rasterization_samples(command_buffer, self.rasterization_samples);
+            loader.cmd_set_sample_mask(command_buffer, self.rasterization_samples, &[u32::MAX]);
+            loader.cmd_set_alpha_to_coverage_enable(command_buffer, false);
+
+            loader.cmd_set_depth_test_enable(command_buffer, self.depth_test_enable);
+            loader.cmd_set_depth_write_enable(command_buffer, self.depth_write_enable);
+            loader.cmd_set_depth_compare_op(command_buffer, self.depth_compare_op);
+            loader.cmd_set_depth_bounds_test_enable(command_buffer, false);
+            loader.cmd_set_stencil_test_enable(command_buffer, false);
+
+            if !blend_enables.is_empty() {
+                loader.cmd_set_color_blend_enable(command_buffer, 0, &blend_enables);
+                loader.cmd_set_color_blend_equation(command_buffer, 0, &blend_equations);
+                loader.cmd_set_color_write_mask(command_buffer, 0, &write_masks);
+            }
+        }
+    }
+}
+
+/// Linked vertex and fragment shader objects drawn without a graphics pipeline: `bind` binds
+/// the shaders and sets all of `state`, so nothing is compiled when the state changes and no
+/// render pass is needed at creation. Draws must be recorded inside dynamic rendering, e.g.
+/// with `VulkanRenderer::draw_frame_threaded_dynamic_with`, not inside a render pass.
+pub struct VulkanShaderObjects {
+    shaders: Vec<(vk::ShaderStageFlags, vk::ShaderEXT)>,
+    pub layout: vk::PipelineLayout,
+    pub state: ShaderObjectState,
+    loader: ash::ext::shader_object::Device,
+    device: Arc<Device>,
+}
+
+pub struct ShaderObjectBuilder {
+    device: Arc<Device>,
+    loader: Option<ash::ext::shader_object::Device>,
+    /// SPIR-V per stage, as words so `pCode` is 4-byte aligned.
+    stages: Vec<(vk::ShaderStageFlags, Vec<u32>)>,
+    descriptor_set_layouts: Vec<vk::DescriptorSetLayout>,
+    push_constant_ranges: Vec<vk::PushConstantRange>,
+    state: ShaderObjectState,
+}
+
+impl ShaderObjectBuilder {
+    pub fn new(device: &VulkanDevice) -> Self {
+        Self {
+            device: device.device.clone(),
+            loader: device.shader_object.clone(),
+            stages: Vec::new(),
+            descriptor_set_layouts: Vec::new(),
+            push_constant_ranges: Vec::new(),
+            state: ShaderObjectState::default(),
+        }
+    }
+
+    pub fn with_vertex_spv(mut self, code: &[u8]) -> Result<Self> {
+        self.stages.push((
+            vk::ShaderStageFlags::VERTEX,
+            crate::pipeline::spirv_words(code)?,
+        ));
+        Ok(self)
+    }
+
+    pub fn with_fragment_spv(mut self, code: &[u8]) -> Result<Self> {
+        self.stages.push((
+            vk::ShaderStageFlags::FRAGMENT,
+            crate::pipeline::spirv_words(code)?,
+        ));
+        Ok(self)
+    }
+
+    pub fn with_descriptor_set_layout(mut self, layout: vk::DescriptorSetLayout) -> Self {
+        self.descriptor_set_layouts.push(layout);
+        self
+    }
+
+    pub fn with_push_constant_range(mut self, range: vk::PushConstantRange) -> Self {
+        self.push_constant_ranges.push(range);
+        self
+    }
+
+    pub fn with_state(mut self, state: ShaderObjectState) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn build(self) -> Result<VulkanShaderObjects> {
+        let Some(loader) = self.loader else {
+            bail!("VK_EXT_shader_object is not enabled on this device");
+        };
+        if !self
+            .stages
+            .iter()
+            .any(|(stage, _)| *stage == vk::ShaderStageFlags::VERTEX)
+        {
+            bail!("a vertex shader is required");
+        }
+
+        let entry_point = CString::new("main").unwrap();
+        let linked = self.stages.len() > 1;
+        let infos: Vec<vk::ShaderCreateInfoEXT> = self
+            .stages
+            .iter()
+            .map(|(stage, words)| {
+                let code = unsafe {
+                    std::slice::from_raw_parts(
+                        words.as_ptr().cast::<u8>(),
+                        std::mem::size_of_val(words.as_slice()),
+                    )
+                };
+                let next_stage = if *stage == vk::ShaderStageFlags::VERTEX && linked {
+                    vk::ShaderStageFlags::FRAGMENT
+                } else {
+                    vk::ShaderStageFlags::empty()
+                };
+                vk::ShaderCreateInfoEXT::default()
+                    .flags(if linked {
+                        vk::ShaderCreateFlagsEXT::LINK_STAGE
+                    } else {
+                        vk::ShaderCreateFlagsEXT::empty()
+                    })
+                    .stage(*stage)
+                    .next_stage(next_stage)
+                    .code_type(vk::ShaderCodeTypeEXT::SPIRV)
+                    .code(code)
+                    .name(&entry_point)
+                    .set_layouts(&self.descriptor_set_layouts)
+                    .push_constant_ranges(&self.push_constant_ranges)
+            })
+            .collect();
+
+        let shaders = match unsafe { loader.create_shaders(&infos, None) } {
+            Ok(shaders) => shaders,
+            Err((shaders, e)) => {
+                for shader in shaders
+                    .into_iter()
+                    .filter(|shader| *shader != vk::ShaderEXT::null())
+                {
+                    unsafe { loader.destroy_shader(shader, None) };
+                }
+                bail!("Failed to create shader objects: {}", e);
+            }
+        };
+
+        let layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(&self.descriptor_set_layouts)
+            .push_constant_ranges(&self.push_constant_ranges);
+        let layout = match unsafe { self.device.create_pipeline_layout(&layout_info, None) } {
+            Ok(layout) => layout,
+            Err(e) => {
+                for shader in shaders {
+                    unsafe { loader.destroy_shader(shader, None) };
+                }
+                bail!("Failed to create pipeline layout: {}", e);
+            }
+        };
+
+        Ok(VulkanShaderObjects {
+            shaders: self
+                .stages
+                .iter()
+                .map(|(stage, _)| *stage)
+                .zip(shaders)
+                .collect(),
+            layout,
+            state: self.state,
+            loader,
+            device: self.device,
+        })
+    }
+}
+
+impl VulkanShaderObjects {
+    /// Binds the shaders and sets all state for drawing into `extent`. Geometry and
+    /// tessellation aren't enabled on the device, so only these two stages need binding.
+    pub fn bind(&self, command_buffer: vk::CommandBuffer, extent: vk::Extent2D) {
+        let stages = [vk::ShaderStageFlags::VERTEX, vk::ShaderStageFlags::FRAGMENT];
+        let shaders = stages.map(|stage| {
+            self.shaders
+                .iter()
+                .find(|(shader_stage, _)| *shader_stage == stage)
+                .map_or(vk::ShaderEXT::null(), |(_, shader)| *shader)
+        });
+
+        unsafe {
+            self.loader
+                .cmd_bind_shaders(command_buffer, &stages, &shaders)
+        };
+        self.state
+            .record(&self.device, &self.loader, command_buffer, extent);
+    }
+
+    pub fn push_constants<T: Copy>(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        stage_flags: vk::ShaderStageFlags,
+        offset: u32,
+        constants: &T,
+    ) {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(constants as *const T as *const u8, std::mem::size_of::<T>())
+        };
+        unsafe {
+            self.device
+                .cmd_push_constants(command_buffer, self.layout, stage_flags, offset, bytes);
+        }
+    }
+
+    pub fn bind_descriptor_sets(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        first_set: u32,
+        descriptor_sets: &[vk::DescriptorSet],
+    ) {
+        unsafe {
+            self.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.layout,
+                first_set,
+                descriptor_sets,
+                &[],
+            );
+        }
+    }
+}
+
+impl Drop for VulkanShaderObjects {
+    fn drop(&mut self) {
+        unsafe {
+            for (_, shader) in &self.shaders {
+                self.loader.destroy_shader(*shader, None);
+            }
+            self.device.destroy_pipeline_layout(self.layout, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_falls_back_to_pipelines() {
+        let mut features = VulkanDeviceFeatures::default();
+        let backend: GraphicsBackend = "shader-objects".parse().unwrap();
+        assert_eq!(backend, GraphicsBackend::ShaderObjects);
+        assert_eq!(backend.supported_by(&features), GraphicsBackend::Pipelines);
+
+        features.shader_object = true;
+        assert_eq!(
+            backend.supported_by(&features),
+            GraphicsBackend::ShaderObjects
+        );
+        assert!("pipeline-less".parse::<GraphicsBackend>().is_err());
+    }
+
+    #[test]
+    fn converts_pipeline_state() {
+        let state = ShaderObjectState::default()
+            .with_vertex_binding(vk::VertexInputBindingDescription {
+                binding: 0,
+                stride: 32,
+                input_rate: vk::VertexInputRate::VERTEX,
+            })
+            .with_vertex_attribute(vk::VertexInputAttributeDescription {
+                location: 1,
+                binding: 0,
+                format: vk::Format::R32G32_SFLOAT,
+                offset: 24,
+            })
+            .with_alpha_blending();
+
+        let (bindings, attributes) = state.vertex_input();
+        assert_eq!((bindings[0].stride, bindings[0].divisor), (32, 1));
+        assert_eq!((attributes[0].location, attributes[0].offset), (1, 24));
+
+        let (enables, equations) = state.blend();
+        assert_eq!(enables, vec![vk::TRUE]);
+        assert_eq!(
+            equations[0].dst_color_blend_factor,
+            vk::BlendFactor::ONE_MINUS_SRC_ALPHA
+        );
+    }
+}
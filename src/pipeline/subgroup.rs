@@ -0,0 +1,558 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::pipeline::{
+    ShaderPermutationCache, VulkanComputePipeline, VulkanComputePipelineBuilder,
+};
+use crate::vulkan::{
+    VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanPhysicalDevice,
+};
+
+/// `WORKGROUP_SIZE` of the templates in `shaders/subgroup`.
+pub const SUBGROUP_WORKGROUP_SIZE: u32 = 256;
+
+/// Largest input of a `PrefixSum`: the block totals are scanned by a single workgroup.
+pub const MAX_PREFIX_SUM_COUNT: u32 = SUBGROUP_WORKGROUP_SIZE * SUBGROUP_WORKGROUP_SIZE;
+
+/// Key bits sorted per `RadixSort` pass, `RADIX_BITS` in `radix_sort.comp`.
+pub const RADIX_SORT_DIGIT_BITS: u32 = 4;
+const RADIX_SORT_DIGITS: u32 = 1 << RADIX_SORT_DIGIT_BITS;
+
+/// Largest input of a `RadixSort`: its per-block digit histograms go through one `PrefixSum`.
+pub const MAX_RADIX_SORT_COUNT: u32 =
+    MAX_PREFIX_SUM_COUNT / RADIX_SORT_DIGITS * SUBGROUP_WORKGROUP_SIZE;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PrefixSumPushConstants {
+    count: u32,
+    pass_index: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RadixSortPushConstants {
+    count: u32,
+    shift: u32,
+    block_count: u32,
+    pass_index: u32,
+}
+
+/// Reduction, prefix sum and radix sort pipelines built from `prefix_sum.comp`, `reduce.comp`
+/// and `radix_sort.comp` in `template_dir` (normally `shaders/subgroup`), compiled with the
+/// physical device's `SubgroupProperties::shader_defines`. Devices without subgroup arithmetic in compute get
+/// the shared-memory fallback of `subgroup.glsl`.
+pub struct SubgroupPipelines {
+    prefix_sum: VulkanComputePipeline,
+    reduce: VulkanComputePipeline,
+    radix_sort: VulkanComputePipeline,
+    prefix_sum_layout: VulkanDescriptorSetLayout,
+    reduce_layout: VulkanDescriptorSetLayout,
+    radix_sort_layout: VulkanDescriptorSetLayout,
+}
+
+impl SubgroupPipelines {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        shaders: &mut ShaderPermutationCache,
+        template_dir: &Path,
+    ) -> Result<Self> {
+        let defines = physical_device
+            .subgroup
+            .shader_defines(vk::ShaderStageFlags::COMPUTE);
+        let defines: Vec<&str> = defines.iter().map(String::as_str).collect();
+
+        let storage_layout = |count: u32| {
+            VulkanDescriptorSetLayout::new(
+                device,
+                &(0..count)
+                    .map(|binding| {
+                        VulkanDescriptorSetLayout::binding(
+                            binding,
+                            vk::DescriptorType::STORAGE_BUFFER,
+                            vk::ShaderStageFlags::COMPUTE,
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        };
+        let prefix_sum_layout = storage_layout(3)?;
+        let reduce_layout = storage_layout(2)?;
+        let radix_sort_layout = storage_layout(6)?;
+
+        let mut build = |template: &str, layout: &VulkanDescriptorSetLayout, size: usize| {
+            let spirv = shaders.get(
+                &template_dir.join(template),
+                vk::ShaderStageFlags::COMPUTE,
+                &defines,
+            )?;
+
+            VulkanComputePipelineBuilder::new(device)
+                .with_shader_spv(&spirv, None)?
+                .with_descriptor_set_layout(layout.layout)
+                .with_push_constant_range(
+                    vk::PushConstantRange::default()
+                        .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                        .offset(0)
+                        .size(size as u32),
+                )
+                .build()
+        };
+
+        let prefix_sum = build(
+            "prefix_sum.comp",
+            &prefix_sum_layout,
+            std::mem::size_of::<PrefixSumPushConstants>(),
+        )?;
+        let reduce = build("reduce.comp", &reduce_layout, std::mem::size_of::<u32>())?;
+        let radix_sort = build(
+            "radix_sort.comp",
+            &radix_sort_layout,
+            std::mem::size_of::<RadixSortPushConstants>(),
+        )?;
+
+        Ok(Self {
+            prefix_sum,
+            reduce,
+            radix_sort,
+            prefix_sum_layout,
+            reduce_layout,
+            radix_sort_layout,
+        })
+    }
+}
+
+/// Makes compute shader writes to `buffer` visible to the next dispatch.
+fn compute_barrier(buffer: &VulkanBuffer, command_buffer: vk::CommandBuffer) {
+    buffer.cmd_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::COMPUTE_SHADER,
+        vk::AccessFlags::SHADER_WRITE,
+        vk::PipelineStageFlags::COMPUTE_SHADER,
+        vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+    );
+}
+
+/// Exclusive prefix sum of `count` `u32`s from an input storage buffer into an output one,
+/// e.g. to compact culled draws or allocate per-item output ranges.
+pub struct PrefixSum {
+    /// Scanned block totals, followed by the sum of every input at `total_offset`.
+    pub block_sums: VulkanBuffer,
+    _descriptor_pool: VulkanDescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    count: u32,
+}
+
+impl PrefixSum {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        pipelines: &SubgroupPipelines,
+        input: &VulkanBuffer,
+        output: &VulkanBuffer,
+        count: u32,
+    ) -> Result<Self> {
+        if count > MAX_PREFIX_SUM_COUNT {
+            return Err(anyhow::anyhow!(
+                "Prefix sum of {} values exceeds the limit of {}",
+                count,
+                MAX_PREFIX_SUM_COUNT
+            ));
+        }
+
+        let block_sums = VulkanBuffer::new(
+            device,
+            physical_device,
+            ((SUBGROUP_WORKGROUP_SIZE + 1) as usize * std::mem::size_of::<u32>()) as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let descriptor_pool =
+            VulkanDescriptorPool::for_layout(device, &pipelines.prefix_sum_layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&pipelines.prefix_sum_layout)?;
+        for (binding, buffer) in [input, output, &block_sums].into_iter().enumerate() {
+            descriptor_pool.write_buffer(
+                descriptor_set,
+                binding as u32,
+                vk::DescriptorType::STORAGE_BUFFER,
+                buffer,
+            );
+        }
+
+        Ok(Self {
+            block_sums,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            count,
+        })
+    }
+
+    fn block_count(&self) -> u32 {
+        self.count.div_ceil(SUBGROUP_WORKGROUP_SIZE)
+    }
+
+    /// Byte offset of the sum of every input in `block_sums`.
+    pub fn total_offset(&self) -> vk::DeviceSize {
+        (self.block_count() as usize * std::mem::size_of::<u32>()) as vk::DeviceSize
+    }
+
+    /// Records the three scan passes. The output and `block_sums` are readable by later
+    /// compute dispatches afterwards; other consumers need their own barrier.
+    pub fn record(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        pipelines: &SubgroupPipelines,
+        output: &VulkanBuffer,
+    ) {
+        let pipeline = &pipelines.prefix_sum;
+        pipeline.bind(command_buffer);
+        pipeline.bind_descriptor_sets(command_buffer, 0, &[self.descriptor_set]);
+
+        let groups = [self.block_count().max(1), 1, self.block_count().max(1)];
+        for (pass_index, group_count) in groups.into_iter().enumerate() {
+            pipeline.push_constants(
+                command_buffer,
+                &PrefixSumPushConstants {
+                    count: self.count,
+                    pass_index: pass_index as u32,
+                },
+            );
+            pipeline.dispatch(command_buffer, group_count, 1, 1);
+
+            compute_barrier(&self.block_sums, command_buffer);
+            compute_barrier(output, command_buffer);
+        }
+    }
+}
+
+/// Sum of `count` `u32`s of a storage buffer, written to `total`.
+pub struct Reduction {
+    pub total: VulkanBuffer,
+    _descriptor_pool: VulkanDescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    count: u32,
+    device: Arc<Device>,
+}
+
+impl Reduction {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        pipelines: &SubgroupPipelines,
+        input: &VulkanBuffer,
+        count: u32,
+    ) -> Result<Self> {
+        let total = VulkanBuffer::new(
+            device,
+            physical_device,
+            std::mem::size_of::<u32>() as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_DST
+                | vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let descriptor_pool =
+            VulkanDescriptorPool::for_layout(device, &pipelines.reduce_layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&pipelines.reduce_layout)?;
+        for (binding, buffer) in [input, &total].into_iter().enumerate() {
+            descriptor_pool.write_buffer(
+                descriptor_set,
+                binding as u32,
+                vk::DescriptorType::STORAGE_BUFFER,
+                buffer,
+            );
+        }
+
+        Ok(Self {
+            total,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            count,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Records clearing `total` and the reduction, after which `total` is readable by later
+    /// compute dispatches.
+    pub fn record(&self, command_buffer: vk::CommandBuffer, pipelines: &SubgroupPipelines) {
+        unsafe {
+            self.device
+                .cmd_fill_buffer(command_buffer, self.total.buffer, 0, vk::WHOLE_SIZE, 0);
+        }
+        self.total.cmd_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+        );
+
+        let pipeline = &pipelines.reduce;
+        pipeline.bind(command_buffer);
+        pipeline.bind_descriptor_sets(command_buffer, 0, &[self.descriptor_set]);
+        pipeline.push_constants(command_buffer, &self.count);
+        pipeline.dispatch(
+            command_buffer,
+            self.count.div_ceil(SUBGROUP_WORKGROUP_SIZE).max(1),
+            1,
+            1,
+        );
+
+        compute_barrier(&self.total, command_buffer);
+    }
+}
+
+/// Passes needed to sort the low `key_bits` bits of the keys. Always even, so the sorted keys
+/// end up back in the caller's buffers rather than the scratch copies.
+pub fn radix_sort_passes(key_bits: u32) -> u32 {
+    key_bits.min(u32::BITS).div_ceil(2 * RADIX_SORT_DIGIT_BITS) * 2
+}
+
+/// Stable ascending sort of `count` `u32` keys with a `u32` payload each, both in storage
+/// buffers, e.g. particles by view depth or lights by cluster. Each pass counts one digit per
+/// block, scans the digit histograms with a `PrefixSum` and scatters the keys, ping-ponging
+/// through scratch buffers of the same size.
+pub struct RadixSort {
+    scratch_keys: VulkanBuffer,
+    scratch_values: VulkanBuffer,
+    histograms: VulkanBuffer,
+    offsets: VulkanBuffer,
+    scan: PrefixSum,
+    _descriptor_pool: VulkanDescriptorPool,
+    /// Keys to scratch, then scratch to keys.
+    descriptor_sets: [vk::DescriptorSet; 2],
+    count: u32,
+}
+
+impl RadixSort {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        pipelines: &SubgroupPipelines,
+        keys: &VulkanBuffer,
+        values: &VulkanBuffer,
+        count: u32,
+    ) -> Result<Self> {
+        if count == 0 || count > MAX_RADIX_SORT_COUNT {
+            return Err(anyhow::anyhow!(
+                "Radix sort of {} keys is outside 1..={}",
+                count,
+                MAX_RADIX_SORT_COUNT
+            ));
+        }
+
+        let storage = |count: u32| {
+            VulkanBuffer::new(
+                device,
+                physical_device,
+                (count as usize * std::mem::size_of::<u32>()) as vk::DeviceSize,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )
+        };
+        let histogram_count = count.div_ceil(SUBGROUP_WORKGROUP_SIZE) * RADIX_SORT_DIGITS;
+        let scratch_keys = storage(count)?;
+        let scratch_values = storage(count)?;
+        let histograms = storage(histogram_count)?;
+        let offsets = storage(histogram_count)?;
+        let scan = PrefixSum::new(
+            device,
+            physical_device,
+            pipelines,
+            &histograms,
+            &offsets,
+            histogram_count,
+        )?;
+
+        let descriptor_pool =
+            VulkanDescriptorPool::for_layout(device, &pipelines.radix_sort_layout, 2)?;
+        let descriptor_sets = [
+            descriptor_pool.allocate(&pipelines.radix_sort_layout)?,
+            descriptor_pool.allocate(&pipelines.radix_sort_layout)?,
+        ];
+        let bindings = [
+            [keys, values, &scratch_keys, &scratch_values],
+            [&scratch_keys, &scratch_values, keys, values],
+        ];
+        for (set, buffers) in descriptor_sets.into_iter().zip(bindings) {
+            for (binding, buffer) in buffers
+                .into_iter()
+                .chain([&histograms, &offsets])
+                .enumerate()
+            {
+                descriptor_pool.write_buffer(
+                    set,
+                    binding as u32,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    buffer,
+                );
+            }
+        }
+
+        Ok(Self {
+            scratch_keys,
+            scratch_values,
+            histograms,
+            offsets,
+            scan,
+            _descriptor_pool: descriptor_pool,
+            descriptor_sets,
+            count,
+        })
+    }
+
+    /// Records the sort of the low `key_bits` bits of the keys (32 for whole keys), after
+    /// which the keys and values passed to `new` are sorted and readable by later compute
+    /// dispatches; other consumers need their own barrier.
+    pub fn record(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        pipelines: &SubgroupPipelines,
+        keys: &VulkanBuffer,
+        values: &VulkanBuffer,
+        key_bits: u32,
+    ) {
+        let block_count = self.count.div_ceil(SUBGROUP_WORKGROUP_SIZE);
+        let pipeline = &pipelines.radix_sort;
+
+        for pass in 0..radix_sort_passes(key_bits) {
+            let descriptor_set = self.descriptor_sets[pass as usize % 2];
+            let (keys_out, values_out) = if pass % 2 == 0 {
+                (&self.scratch_keys, &self.scratch_values)
+            } else {
+                (keys, values)
+            };
+
+            for pass_index in 0..2 {
+                if pass_index == 1 {
+                    compute_barrier(&self.histograms, command_buffer);
+                    self.scan.record(command_buffer, pipelines, &self.offsets);
+                }
+
+                pipeline.bind(command_buffer);
+                pipeline.bind_descriptor_sets(command_buffer, 0, &[descriptor_set]);
+                pipeline.push_constants(
+                    command_buffer,
+                    &RadixSortPushConstants {
+                        count: self.count,
+                        shift: pass * RADIX_SORT_DIGIT_BITS,
+                        block_count,
+                        pass_index,
+                    },
+                );
+                pipeline.dispatch(command_buffer, block_count, 1, 1);
+            }
+
+            compute_barrier(keys_out, command_buffer);
+            compute_barrier(values_out, command_buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::{GlslcCompiler, ShaderReflection};
+    use crate::vulkan::SubgroupProperties;
+
+    #[test]
+    fn defines_follow_supported_operations() {
+        let subgroup = SubgroupProperties {
+            size: 32,
+            min_size: 8,
+            max_size: 64,
+            supported_stages: vk::ShaderStageFlags::COMPUTE,
+            supported_operations: vk::SubgroupFeatureFlags::BASIC
+                | vk::SubgroupFeatureFlags::ARITHMETIC,
+        };
+
+        assert_eq!(
+            subgroup.shader_defines(vk::ShaderStageFlags::COMPUTE),
+            [
+                "SUBGROUP_SIZE=32",
+                "SUBGROUP_MIN_SIZE=8",
+                "SUBGROUP_BASIC",
+                "SUBGROUP_ARITHMETIC"
+            ]
+        );
+        assert_eq!(
+            subgroup.shader_defines(vk::ShaderStageFlags::FRAGMENT),
+            ["SUBGROUP_SIZE=32", "SUBGROUP_MIN_SIZE=8"]
+        );
+        assert!(!SubgroupProperties::default().supports(
+            vk::SubgroupFeatureFlags::BASIC,
+            vk::ShaderStageFlags::COMPUTE
+        ));
+    }
+
+    #[test]
+    fn radix_sort_passes_round_to_even() {
+        assert_eq!(radix_sort_passes(32), 8);
+        assert_eq!(radix_sort_passes(64), 8);
+        assert_eq!(radix_sort_passes(16), 4);
+        assert_eq!(radix_sort_passes(12), 4);
+        assert_eq!(radix_sort_passes(1), 2);
+        assert_eq!(radix_sort_passes(0), 0);
+        assert_eq!(MAX_RADIX_SORT_COUNT, 1 << 20);
+    }
+
+    #[test]
+    fn templates_compile_with_and_without_subgroups() {
+        if std::process::Command::new("glslc")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            eprintln!("glslc not found, skipping template compilation");
+            return;
+        }
+
+        let templates = Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders/subgroup");
+        let mut shaders = ShaderPermutationCache::new(GlslcCompiler::new());
+        let subgroup = SubgroupProperties {
+            size: 32,
+            min_size: 32,
+            max_size: 32,
+            supported_stages: vk::ShaderStageFlags::COMPUTE,
+            supported_operations: vk::SubgroupFeatureFlags::BASIC
+                | vk::SubgroupFeatureFlags::ARITHMETIC,
+        };
+
+        for defines in [
+            Vec::new(),
+            subgroup.shader_defines(vk::ShaderStageFlags::COMPUTE),
+        ] {
+            let defines: Vec<&str> = defines.iter().map(String::as_str).collect();
+            for (file, push_constant_size, binding_count) in [
+                ("prefix_sum.comp", 8, 3),
+                ("reduce.comp", 4, 2),
+                ("radix_sort.comp", 16, 6),
+            ] {
+                let spirv = shaders
+                    .get(
+                        &templates.join(file),
+                        vk::ShaderStageFlags::COMPUTE,
+                        &defines,
+                    )
+                    .unwrap_or_else(|e| panic!("{} {:?}: {}", file, defines, e));
+
+                let reflection = ShaderReflection::from_spirv(&spirv).unwrap();
+                assert_eq!(reflection.push_constant_size, Some(push_constant_size));
+                assert_eq!(reflection.layout_bindings(0).len(), binding_count);
+                assert_eq!(
+                    reflection
+                        .entry_point(vk::ShaderStageFlags::COMPUTE)
+                        .unwrap()
+                        .local_size,
+                    Some([SUBGROUP_WORKGROUP_SIZE, 1, 1])
+                );
+            }
+        }
+    }
+}
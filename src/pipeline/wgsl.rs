@@ -0,0 +1,143 @@
+use anyhow::Result;
+use ash::vk;
+use std::ffi::CString;
+
+use crate::pipeline::ShaderReflection;
+
+/// WGSL source translated to a single SPIR-V module holding all of its entry points.
+///
+/// Clip-space Y is flipped on output like wgpu does, so vertex shaders written for wgpu work
+/// unchanged; `@group`/`@binding` become descriptor set and binding.
+pub struct WgslShader {
+    pub spirv: Vec<u8>,
+    pub reflection: ShaderReflection,
+}
+
+impl WgslShader {
+    /// `label` names the shader in error messages.
+    pub fn compile(source: &str, label: &str) -> Result<Self> {
+        let module = naga::front::wgsl::parse_str(source).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to parse {}: {}",
+                label,
+                e.emit_to_string_with_path(source, label)
+            )
+        })?;
+
+        let info = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to validate {}: {}",
+                label,
+                e.emit_to_string_with_path(source, label)
+            )
+        })?;
+
+        // Without a binding map, naga keeps `@group`/`@binding` as set and binding.
+        let options = naga::back::spv::Options {
+            lang_version: (1, 3),
+            ..Default::default()
+        };
+        let words = naga::back::spv::write_vec(&module, &info, &options, None)
+            .map_err(|e| anyhow::anyhow!("Failed to translate {} to SPIR-V: {}", label, e))?;
+
+        let spirv: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        let reflection = ShaderReflection::from_spirv(&spirv)?;
+
+        Ok(Self { spirv, reflection })
+    }
+
+    /// Name of the entry point for `stage`, or of `name` when the module has several for it.
+    pub fn entry_point(&self, stage: vk::ShaderStageFlags, name: Option<&str>) -> Result<CString> {
+        self.reflection.entry_point_name(stage, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHADER: &str = r#"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@group(0) @binding(0) var albedo: texture_2d<f32>;
+@group(0) @binding(1) var albedo_sampler: sampler;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(2) uv: vec2<f32>) -> VertexOutput {
+    return VertexOutput(vec4<f32>(position, 1.0), uv);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(albedo, albedo_sampler, in.uv);
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_main() {}
+"#;
+
+    #[test]
+    fn translates_every_entry_point() {
+        let shader = WgslShader::compile(SHADER, "test.wgsl").unwrap();
+
+        assert_eq!(
+            shader
+                .entry_point(vk::ShaderStageFlags::VERTEX, None)
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            "vs_main"
+        );
+        assert!(
+            shader
+                .entry_point(vk::ShaderStageFlags::FRAGMENT, None)
+                .is_ok()
+        );
+        assert_eq!(
+            shader
+                .reflection
+                .entry_point(vk::ShaderStageFlags::COMPUTE)
+                .unwrap()
+                .local_size,
+            Some([8, 8, 1])
+        );
+        assert_eq!(
+            shader
+                .reflection
+                .stage_inputs(vk::ShaderStageFlags::VERTEX)
+                .map(|input| input.location)
+                .collect::<Vec<_>>(),
+            [0, 2]
+        );
+        assert_eq!(
+            shader
+                .reflection
+                .bindings
+                .iter()
+                .map(|b| (b.set, b.binding, b.descriptor_type))
+                .collect::<Vec<_>>(),
+            [
+                (0, 0, vk::DescriptorType::SAMPLED_IMAGE),
+                (0, 1, vk::DescriptorType::SAMPLER),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_label() {
+        let error = WgslShader::compile("fn broken( {", "broken.wgsl")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.starts_with("Failed to parse broken.wgsl"));
+        assert!(error.contains("broken.wgsl:1:"));
+    }
+}
@@ -0,0 +1,285 @@
+use anyhow::Result;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::remote::JpegEncoder;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Viewers that can't take a frame within this long are disconnected.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+const BOUNDARY: &str = "frame";
+
+/// Connected viewers, shared by the accept, handshake and encode threads. The count is kept
+/// separately so `publish` never waits on the lock while frames are being sent.
+#[derive(Default)]
+struct Clients {
+    streams: Mutex<Vec<Arc<TcpStream>>>,
+    count: AtomicUsize,
+}
+
+impl Clients {
+    fn add(&self, stream: TcpStream) {
+        let mut streams = self.streams.lock().unwrap();
+        streams.push(Arc::new(stream));
+        self.count.store(streams.len(), Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> Vec<Arc<TcpStream>> {
+        self.streams.lock().unwrap().clone()
+    }
+
+    fn remove(&self, failed: &[Arc<TcpStream>]) {
+        let mut streams = self.streams.lock().unwrap();
+        streams.retain(|stream| !failed.iter().any(|f| Arc::ptr_eq(f, stream)));
+        self.count.store(streams.len(), Ordering::Relaxed);
+    }
+}
+
+struct Frame {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+/// Streams published frames as MJPEG over HTTP (`multipart/x-mixed-replace`), which browsers
+/// and tools like ffplay or VLC show directly, e.g. `http://server:8080/` for a headless render.
+///
+/// Frames are encoded on a background thread. If it is still busy with the previous frame,
+/// `publish` drops the new one, so a slow network never stalls the renderer. Viewers that stall
+/// for longer than a couple of seconds are disconnected.
+pub struct FrameStreamServer {
+    address: SocketAddr,
+    frames: Option<SyncSender<Frame>>,
+    clients: Arc<Clients>,
+    running: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl FrameStreamServer {
+    /// Listens on `address`; port 0 picks a free port, see `local_addr`. `quality` is the JPEG
+    /// quality in `[1, 100]`.
+    pub fn bind(address: impl ToSocketAddrs, quality: u8) -> Result<Self> {
+        let listener = TcpListener::bind(address)
+            .map_err(|e| anyhow::anyhow!("Failed to bind frame stream server: {}", e))?;
+        let address = listener
+            .local_addr()
+            .map_err(|e| anyhow::anyhow!("Failed to get frame stream server address: {}", e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| anyhow::anyhow!("Failed to configure frame stream server: {}", e))?;
+
+        let clients = Arc::new(Clients::default());
+        let running = Arc::new(AtomicBool::new(true));
+        let (frames, receiver) = mpsc::sync_channel(1);
+
+        let accept_clients = clients.clone();
+        let accept_running = running.clone();
+        let accept_thread = std::thread::Builder::new()
+            .name("frame stream accept".to_string())
+            .spawn(move || accept_connections(listener, accept_clients, accept_running))
+            .map_err(|e| anyhow::anyhow!("Failed to spawn frame stream thread: {}", e))?;
+
+        let encode_clients = clients.clone();
+        let encode_thread = std::thread::Builder::new()
+            .name("frame stream encode".to_string())
+            .spawn(move || encode_frames(receiver, encode_clients, JpegEncoder::new(quality)))
+            .map_err(|e| anyhow::anyhow!("Failed to spawn frame stream thread: {}", e))?;
+
+        Ok(Self {
+            address,
+            frames: Some(frames),
+            clients,
+            running,
+            threads: vec![accept_thread, encode_thread],
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.clients.count.load(Ordering::Relaxed)
+    }
+
+    /// Queues `width * height` RGBA8 texels, top row first, as the next frame. Returns false
+    /// when the frame was dropped because nobody is watching or the encoder is busy.
+    pub fn publish(&self, width: u32, height: u32, rgba: &[u8]) -> bool {
+        if self.client_count() == 0 {
+            return false;
+        }
+        let frame = Frame {
+            width,
+            height,
+            rgba: rgba.to_vec(),
+        };
+        match self.frames.as_ref().map(|frames| frames.try_send(frame)) {
+            Some(Ok(())) => true,
+            Some(Err(TrySendError::Full(_) | TrySendError::Disconnected(_))) | None => false,
+        }
+    }
+}
+
+impl Drop for FrameStreamServer {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        // Closing the channel ends the encode thread.
+        self.frames = None;
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn accept_connections(listener: TcpListener, clients: Arc<Clients>, running: Arc<AtomicBool>) {
+    while running.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                // Handshakes run on their own thread so a slow client doesn't hold up others.
+                let clients = clients.clone();
+                let running = running.clone();
+                let spawned = std::thread::Builder::new()
+                    .name("frame stream handshake".to_string())
+                    .spawn(move || match start_stream(stream) {
+                        Ok(stream) if running.load(Ordering::Relaxed) => clients.add(stream),
+                        Ok(_) => {}
+                        Err(e) => eprintln!("Failed to start frame stream: {}", e),
+                    });
+                if let Err(e) = spawned {
+                    eprintln!("Failed to spawn frame stream thread: {}", e);
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => eprintln!("Failed to accept frame stream connection: {}", e),
+        }
+    }
+}
+
+/// Skips the HTTP request and answers with the multipart header. Any path is accepted.
+fn start_stream(stream: TcpStream) -> std::io::Result<TcpStream> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut stream = stream;
+    stream.set_nodelay(true)?;
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: multipart/x-mixed-replace; boundary={}\r\n\
+         Cache-Control: no-cache\r\n\
+         Connection: close\r\n\r\n",
+        BOUNDARY
+    )?;
+    Ok(stream)
+}
+
+fn encode_frames(frames: Receiver<Frame>, clients: Arc<Clients>, encoder: JpegEncoder) {
+    for frame in frames {
+        let jpeg = match encoder.encode(frame.width, frame.height, &frame.rgba) {
+            Ok(jpeg) => jpeg,
+            Err(e) => {
+                eprintln!("Failed to encode streamed frame: {}", e);
+                continue;
+            }
+        };
+
+        let mut part = format!(
+            "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+            BOUNDARY,
+            jpeg.len()
+        )
+        .into_bytes();
+        part.extend(jpeg);
+        part.extend(b"\r\n");
+
+        // Clients that fail to take the frame have disconnected or stalled.
+        let failed: Vec<Arc<TcpStream>> = clients
+            .snapshot()
+            .into_iter()
+            .filter(|client| client.as_ref().write_all(&part).is_err())
+            .collect();
+        if !failed.is_empty() {
+            clients.remove(&failed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn streams_frames_to_http_clients() {
+        let server = FrameStreamServer::bind("127.0.0.1:0", 75).unwrap();
+        let rgba = vec![128; 16 * 8 * 4];
+        assert!(!server.publish(16, 8, &rgba));
+
+        let mut client = TcpStream::connect(server.local_addr()).unwrap();
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        while server.client_count() == 0 {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(server.publish(16, 8, &rgba));
+
+        let mut reader = BufReader::new(client);
+        let mut headers = String::new();
+        while !headers.ends_with("\r\n\r\n") {
+            reader.read_line(&mut headers).unwrap();
+        }
+        assert!(headers.starts_with("HTTP/1.1 200 OK"));
+        assert!(headers.contains("multipart/x-mixed-replace; boundary=frame"));
+
+        let mut part = String::new();
+        while !part.ends_with("\r\n\r\n") {
+            reader.read_line(&mut part).unwrap();
+        }
+        assert!(part.starts_with("--frame\r\nContent-Type: image/jpeg\r\n"));
+        let length: usize = part
+            .lines()
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut jpeg = vec![0; length];
+        reader.read_exact(&mut jpeg).unwrap();
+        assert_eq!(&jpeg[..2], [0xff, 0xd8]);
+        assert_eq!(&jpeg[length - 2..], [0xff, 0xd9]);
+    }
+
+    #[test]
+    fn slow_handshakes_do_not_block_other_clients() {
+        let server = FrameStreamServer::bind("127.0.0.1:0", 75).unwrap();
+        // Connects but never sends its request.
+        let _silent = TcpStream::connect(server.local_addr()).unwrap();
+        std::thread::sleep(POLL_INTERVAL * 2);
+
+        let start = std::time::Instant::now();
+        let mut client = TcpStream::connect(server.local_addr()).unwrap();
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        while server.client_count() == 0 {
+            assert!(start.elapsed() < HANDSHAKE_TIMEOUT / 2);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use anyhow::Result;
+
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+// Example tables from Annex K of the JPEG specification, in natural order.
+const LUMINANCE_QUANTIZATION: [u8; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113,
+    92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+const CHROMINANCE_QUANTIZATION: [u8; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+const DC_LUMINANCE_COUNTS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const DC_CHROMINANCE_COUNTS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const DC_VALUES: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+const AC_LUMINANCE_COUNTS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7d];
+const AC_LUMINANCE_VALUES: [u8; 162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xa1, 0x08, 0x23, 0x42, 0xb1, 0xc1, 0x15, 0x52, 0xd1, 0xf0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0a, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2a, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7,
+    0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5,
+    0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe1, 0xe2,
+    0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+];
+
+const AC_CHROMINANCE_COUNTS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+const AC_CHROMINANCE_VALUES: [u8; 162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xa1, 0xb1, 0xc1, 0x09, 0x23, 0x33, 0x52, 0xf0,
+    0x15, 0x62, 0x72, 0xd1, 0x0a, 0x16, 0x24, 0x34, 0xe1, 0x25, 0xf1, 0x17, 0x18, 0x19, 0x1a, 0x26,
+    0x27, 0x28, 0x29, 0x2a, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5,
+    0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3,
+    0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda,
+    0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+];
+
+/// Code and bit length per symbol.
+struct HuffmanTable {
+    codes: [(u16, u8); 256],
+}
+
+impl HuffmanTable {
+    fn new(counts: &[u8; 16], values: &[u8]) -> Self {
+        let mut codes = [(0, 0); 256];
+        let mut code = 0u16;
+        let mut values = values.iter();
+        for (length, &count) in counts.iter().enumerate() {
+            for _ in 0..count {
+                let value = *values.next().expect("Huffman counts exceed values");
+                codes[value as usize] = (code, length as u8 + 1);
+                code += 1;
+            }
+            code <<= 1;
+        }
+        Self { codes }
+    }
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, bits: u16, length: u8) {
+        self.buffer = (self.buffer << length) | (bits as u32 & ((1 << length) - 1));
+        self.count += length as u32;
+        while self.count >= 8 {
+            let byte = (self.buffer >> (self.count - 8)) as u8;
+            self.bytes.push(byte);
+            // 0xFF in entropy-coded data must be followed by a zero so it isn't read as a marker.
+            if byte == 0xff {
+                self.bytes.push(0);
+            }
+            self.count -= 8;
+        }
+        self.buffer &= (1 << self.count) - 1;
+    }
+
+    fn write_symbol(&mut self, table: &HuffmanTable, symbol: u8) {
+        let (code, length) = table.codes[symbol as usize];
+        self.write(code, length);
+    }
+
+    /// Pads the last byte with one bits.
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.write(0x7f, 8 - self.count as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Bit count of `|value|` and the value's additional bits, as JPEG encodes coefficients.
+fn magnitude(value: i32) -> (u8, u16) {
+    let category = 32 - value.unsigned_abs().leading_zeros();
+    let bits = if value < 0 {
+        value - 1 + (1 << category)
+    } else {
+        value
+    };
+    (category as u8, bits as u16)
+}
+
+/// Baseline (sequential, Huffman) JPEG encoder with 4:4:4 YCbCr and the example tables from
+/// the specification, for streaming frames where speed and simplicity beat file size.
+pub struct JpegEncoder {
+    quantization: [[u8; 64]; 2],
+    dc_tables: [HuffmanTable; 2],
+    ac_tables: [HuffmanTable; 2],
+    cosines: [[f32; 8]; 8],
+}
+
+impl JpegEncoder {
+    /// `quality` in `[1, 100]` scales the quantization tables like libjpeg does.
+    pub fn new(quality: u8) -> Self {
+        let quality = quality.clamp(1, 100) as u32;
+        let scale = if quality < 50 {
+            5000 / quality
+        } else {
+            200 - quality * 2
+        };
+        let scaled =
+            |table: &[u8; 64]| table.map(|q| ((q as u32 * scale + 50) / 100).clamp(1, 255) as u8);
+
+        let mut cosines = [[0.0; 8]; 8];
+        for (frequency, row) in cosines.iter_mut().enumerate() {
+            let normalization = if frequency == 0 {
+                std::f32::consts::FRAC_1_SQRT_2
+            } else {
+                1.0
+            };
+            for (sample, cosine) in row.iter_mut().enumerate() {
+                let angle =
+                    (2 * sample + 1) as f32 * frequency as f32 * std::f32::consts::PI / 16.0;
+                *cosine = 0.5 * normalization * angle.cos();
+            }
+        }
+
+        Self {
+            quantization: [
+                scaled(&LUMINANCE_QUANTIZATION),
+                scaled(&CHROMINANCE_QUANTIZATION),
+            ],
+            dc_tables: [
+                HuffmanTable::new(&DC_LUMINANCE_COUNTS, &DC_VALUES),
+                HuffmanTable::new(&DC_CHROMINANCE_COUNTS, &DC_VALUES),
+            ],
+            ac_tables: [
+                HuffmanTable::new(&AC_LUMINANCE_COUNTS, &AC_LUMINANCE_VALUES),
+                HuffmanTable::new(&AC_CHROMINANCE_COUNTS, &AC_CHROMINANCE_VALUES),
+            ],
+            cosines,
+        }
+    }
+
+    /// Encodes `width * height` RGBA8 texels, top row first. Alpha is ignored.
+    pub fn encode(&self, width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>> {
+        if width == 0 || height == 0 || width > u16::MAX as u32 || height > u16::MAX as u32 {
+            return Err(anyhow::anyhow!(
+                "Unsupported JPEG size {}x{}",
+                width,
+                height
+            ));
+        }
+        let (width, height) = (width as usize, height as usize);
+        if rgba.len() != width * height * 4 {
+            return Err(anyhow::anyhow!(
+                "Expected {} bytes of RGBA data, got {}",
+                width * height * 4,
+                rgba.len()
+            ));
+        }
+
+        let mut output = Vec::with_capacity(width * height / 4);
+        self.write_headers(&mut output, width as u16, height as u16);
+
+        let mut writer = BitWriter {
+            bytes: Vec::new(),
+            buffer: 0,
+            count: 0,
+        };
+        let mut previous_dc = [0i32; 3];
+        let mut block = [[0.0f32; 64]; 3];
+        for block_y in (0..height).step_by(8) {
+            for block_x in (0..width).step_by(8) {
+                // Edge blocks repeat the last row and column instead of padding with black.
+                for y in 0..8 {
+                    let row = (block_y + y).min(height - 1) * width;
+                    for x in 0..8 {
+                        let texel = (row + (block_x + x).min(width - 1)) * 4;
+                        let [r, g, b] = [0, 1, 2].map(|channel| rgba[texel + channel] as f32);
+                        block[0][y * 8 + x] = 0.299 * r + 0.587 * g + 0.114 * b - 128.0;
+                        block[1][y * 8 + x] = -0.168_736 * r - 0.331_264 * g + 0.5 * b;
+                        block[2][y * 8 + x] = 0.5 * r - 0.418_688 * g - 0.081_312 * b;
+                    }
+                }
+                for (component, samples) in block.iter().enumerate() {
+                    let table = component.min(1);
+                    self.encode_block(&mut writer, samples, table, &mut previous_dc[component]);
+                }
+            }
+        }
+
+        output.extend(writer.finish());
+        output.extend([0xff, 0xd9]);
+        Ok(output)
+    }
+
+    fn encode_block(
+        &self,
+        writer: &mut BitWriter,
+        samples: &[f32; 64],
+        table: usize,
+        previous_dc: &mut i32,
+    ) {
+        // Separable DCT: rows, then columns.
+        let mut rows = [0.0f32; 64];
+        for y in 0..8 {
+            for u in 0..8 {
+                rows[y * 8 + u] = (0..8)
+                    .map(|x| samples[y * 8 + x] * self.cosines[u][x])
+                    .sum();
+            }
+        }
+        let mut coefficients = [0i32; 64];
+        for (index, &natural) in ZIGZAG.iter().enumerate() {
+            let (v, u) = (natural / 8, natural % 8);
+            let value: f32 = (0..8).map(|y| rows[y * 8 + u] * self.cosines[v][y]).sum();
+            coefficients[index] = (value / self.quantization[table][natural] as f32).round() as i32;
+        }
+
+        let (category, bits) = magnitude(coefficients[0] - *previous_dc);
+        *previous_dc = coefficients[0];
+        writer.write_symbol(&self.dc_tables[table], category);
+        writer.write(bits, category);
+
+        let mut zeros = 0;
+        for &coefficient in &coefficients[1..] {
+            if coefficient == 0 {
+                zeros += 1;
+                continue;
+            }
+            while zeros >= 16 {
+                writer.write_symbol(&self.ac_tables[table], 0xf0);
+                zeros -= 16;
+            }
+            let (category, bits) = magnitude(coefficient);
+            writer.write_symbol(&self.ac_tables[table], (zeros << 4) | category);
+            writer.write(bits, category);
+            zeros = 0;
+        }
+        if zeros > 0 {
+            writer.write_symbol(&self.ac_tables[table], 0x00);
+        }
+    }
+
+    fn write_headers(&self, output: &mut Vec<u8>, width: u16, height: u16) {
+        output.extend([0xff, 0xd8]);
+        // JFIF APP0: version 1.1, no density, no thumbnail.
+        output.extend([0xff, 0xe0, 0, 16]);
+        output.extend(b"JFIF\0");
+        output.extend([1, 1, 0, 0, 1, 0, 1, 0, 0]);
+
+        output.extend([0xff, 0xdb, 0, 132]);
+        for (id, table) in self.quantization.iter().enumerate() {
+            output.push(id as u8);
+            output.extend(ZIGZAG.map(|natural| table[natural]));
+        }
+
+        output.extend([0xff, 0xc0, 0, 17, 8]);
+        output.extend(height.to_be_bytes());
+        output.extend(width.to_be_bytes());
+        output.extend([3, 1, 0x11, 0, 2, 0x11, 1, 3, 0x11, 1]);
+
+        let tables: [(u8, &[u8; 16], &[u8]); 4] = [
+            (0x00, &DC_LUMINANCE_COUNTS, &DC_VALUES),
+            (0x10, &AC_LUMINANCE_COUNTS, &AC_LUMINANCE_VALUES),
+            (0x01, &DC_CHROMINANCE_COUNTS, &DC_VALUES),
+            (0x11, &AC_CHROMINANCE_COUNTS, &AC_CHROMINANCE_VALUES),
+        ];
+        let length: usize = 2 + tables
+            .iter()
+            .map(|(_, _, values)| 17 + values.len())
+            .sum::<usize>();
+        output.extend([0xff, 0xc4]);
+        output.extend((length as u16).to_be_bytes());
+        for (class_and_id, counts, values) in tables {
+            output.push(class_and_id);
+            output.extend(counts);
+            output.extend(values);
+        }
+
+        output.extend([0xff, 0xda, 0, 12, 3, 1, 0x00, 2, 0x11, 3, 0x11, 0, 63, 0]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_a_baseline_image() {
+        for (counts, values) in [
+            (DC_LUMINANCE_COUNTS, &DC_VALUES[..]),
+            (DC_CHROMINANCE_COUNTS, &DC_VALUES[..]),
+            (AC_LUMINANCE_COUNTS, &AC_LUMINANCE_VALUES[..]),
+            (AC_CHROMINANCE_COUNTS, &AC_CHROMINANCE_VALUES[..]),
+        ] {
+            assert_eq!(
+                counts.iter().map(|&c| c as usize).sum::<usize>(),
+                values.len()
+            );
+        }
+        assert_eq!(magnitude(0), (0, 0));
+        assert_eq!(magnitude(5), (3, 0b101));
+        assert_eq!(magnitude(-5), (3, 0b010));
+
+        let (width, height) = (13, 9);
+        let rgba: Vec<u8> = (0..width * height)
+            .flat_map(|i| [(i * 7) as u8, (i * 3) as u8, 200, 255])
+            .collect();
+        let jpeg = JpegEncoder::new(85).encode(width, height, &rgba).unwrap();
+        assert_eq!(&jpeg[..2], [0xff, 0xd8]);
+        assert_eq!(&jpeg[6..11], b"JFIF\0");
+        assert_eq!(&jpeg[jpeg.len() - 2..], [0xff, 0xd9]);
+
+        // Nothing between the start of scan and the end may look like a marker.
+        let scan = jpeg.windows(2).position(|w| w == [0xff, 0xda]).unwrap() + 14;
+        let data = &jpeg[scan..jpeg.len() - 2];
+        assert!(data.windows(2).all(|w| w[0] != 0xff || w[1] == 0));
+
+        assert!(JpegEncoder::new(85).encode(2, 2, &[0; 15]).is_err());
+        assert!(JpegEncoder::new(85).encode(0, 2, &[]).is_err());
+    }
+}
@@ -0,0 +1,5 @@
+pub mod frame_stream;
+pub mod jpeg;
+
+pub use frame_stream::*;
+pub use jpeg::*;
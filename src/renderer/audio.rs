@@ -0,0 +1,357 @@
+use anyhow::Result;
+use ash::vk;
+
+use crate::vulkan::{
+    VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanPhysicalDevice,
+};
+
+/// Spectrum bands, matching `AUDIO_BANDS` in `shaders/audio.glsl`.
+pub const AUDIO_BANDS: usize = 32;
+/// Samples per FFT window of `AudioAnalyzer`.
+pub const AUDIO_FFT_SIZE: usize = 1024;
+
+const LOWEST_BAND_HZ: f32 = 20.0;
+const SILENCE_DB: f32 = -60.0;
+
+/// Per-frame audio features, laid out like `AudioData` in `shaders/audio.glsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AudioUniforms {
+    /// RMS amplitude, peak amplitude, beat pulse, seconds analyzed.
+    pub levels: [f32; 4],
+    pub bands: [[f32; 4]; AUDIO_BANDS / 4],
+}
+
+impl AudioUniforms {
+    pub fn band(&self, band: usize) -> f32 {
+        self.bands[band / 4][band % 4]
+    }
+}
+
+/// Turns audio into the smoothed levels visualizer shaders read through `AudioSet`.
+///
+/// Feed it mono samples with `push_samples`, e.g. downmixed from a cpal input or output
+/// callback, and call `update` once per frame. Code that already has a spectrum calls
+/// `update_from_spectrum` instead.
+#[derive(Debug, Clone)]
+pub struct AudioAnalyzer {
+    pub sample_rate: f32,
+    /// Seconds for a band to rise to a louder level.
+    pub attack: f32,
+    /// Seconds for a band to fall back after a sound stops.
+    pub release: f32,
+    /// Energy above the running average, as a ratio, that counts as a beat.
+    pub beat_threshold: f32,
+    window: Vec<f32>,
+    write: usize,
+    pending_sum_squares: f32,
+    pending_peak: f32,
+    pending_count: usize,
+    bands: [f32; AUDIO_BANDS],
+    rms: f32,
+    peak: f32,
+    beat: f32,
+    time: f32,
+    energy_average: f32,
+    since_beat: f32,
+}
+
+impl AudioAnalyzer {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            attack: 0.01,
+            release: 0.15,
+            beat_threshold: 1.5,
+            window: vec![0.0; AUDIO_FFT_SIZE],
+            write: 0,
+            pending_sum_squares: 0.0,
+            pending_peak: 0.0,
+            pending_count: 0,
+            bands: [0.0; AUDIO_BANDS],
+            rms: 0.0,
+            peak: 0.0,
+            beat: 0.0,
+            time: 0.0,
+            energy_average: 0.0,
+            since_beat: f32::INFINITY,
+        }
+    }
+
+    pub fn push_samples(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            self.window[self.write] = sample;
+            self.write = (self.write + 1) % AUDIO_FFT_SIZE;
+            self.pending_sum_squares += sample * sample;
+            self.pending_peak = self.pending_peak.max(sample.abs());
+        }
+        self.pending_count += samples.len();
+    }
+
+    /// Analyzes the latest `AUDIO_FFT_SIZE` samples and the amplitude of the samples pushed
+    /// since the last update, `dt` seconds ago.
+    pub fn update(&mut self, dt: f32) -> AudioUniforms {
+        let mut real: Vec<f32> = (0..AUDIO_FFT_SIZE)
+            .map(|i| {
+                let hann =
+                    0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / AUDIO_FFT_SIZE as f32).cos();
+                self.window[(self.write + i) % AUDIO_FFT_SIZE] * hann
+            })
+            .collect();
+        let mut imaginary = vec![0.0; AUDIO_FFT_SIZE];
+        fft(&mut real, &mut imaginary);
+
+        // A full-scale sine peaks at N / 4 through the Hann window.
+        let scale = 4.0 / AUDIO_FFT_SIZE as f32;
+        let magnitudes: Vec<f32> = real[..AUDIO_FFT_SIZE / 2]
+            .iter()
+            .zip(&imaginary)
+            .map(|(re, im)| (re * re + im * im).sqrt() * scale)
+            .collect();
+
+        let (rms, peak) = if self.pending_count > 0 {
+            (
+                (self.pending_sum_squares / self.pending_count as f32).sqrt(),
+                self.pending_peak,
+            )
+        } else {
+            (0.0, 0.0)
+        };
+        self.pending_sum_squares = 0.0;
+        self.pending_peak = 0.0;
+        self.pending_count = 0;
+
+        let bands = spectrum_bands(&magnitudes, self.sample_rate);
+        self.advance(bands, rms, peak, dt)
+    }
+
+    /// Uses a spectrum computed elsewhere: linear magnitudes where 1 is full scale, bin `i`
+    /// centered on `i * sample_rate / (2 * magnitudes.len())`.
+    pub fn update_from_spectrum(&mut self, magnitudes: &[f32], rms: f32, dt: f32) -> AudioUniforms {
+        let bands = spectrum_bands(magnitudes, self.sample_rate);
+        self.advance(bands, rms, rms * std::f32::consts::SQRT_2, dt)
+    }
+
+    fn advance(
+        &mut self,
+        bands: [f32; AUDIO_BANDS],
+        rms: f32,
+        peak: f32,
+        dt: f32,
+    ) -> AudioUniforms {
+        let attack = 1.0 - (-dt / self.attack.max(1e-4)).exp();
+        let release = 1.0 - (-dt / self.release.max(1e-4)).exp();
+        for (level, target) in self.bands.iter_mut().zip(bands) {
+            *level += (target - *level) * if target > *level { attack } else { release };
+        }
+        self.rms += (rms - self.rms) * if rms > self.rms { attack } else { release };
+        self.peak += (peak - self.peak) * if peak > self.peak { attack } else { release };
+
+        // Onsets: energy well above its running one-second average, at most four per second.
+        let energy = rms * rms;
+        self.beat *= (-dt * 8.0).exp();
+        self.since_beat += dt;
+        if energy > self.energy_average * self.beat_threshold
+            && energy > 1e-4
+            && self.since_beat > 0.25
+        {
+            self.beat = 1.0;
+            self.since_beat = 0.0;
+        }
+        self.energy_average += (energy - self.energy_average) * (1.0 - (-dt).exp());
+        self.time += dt;
+
+        let mut uniforms = AudioUniforms {
+            levels: [self.rms, self.peak, self.beat, self.time],
+            ..Default::default()
+        };
+        for (band, &level) in self.bands.iter().enumerate() {
+            uniforms.bands[band / 4][band % 4] = level;
+        }
+        uniforms
+    }
+}
+
+/// Groups FFT magnitudes into `AUDIO_BANDS` logarithmic bands from 20 Hz to Nyquist, taking
+/// the loudest bin of each and mapping -60..0 dB to 0..1.
+pub fn spectrum_bands(magnitudes: &[f32], sample_rate: f32) -> [f32; AUDIO_BANDS] {
+    let mut bands = [0.0; AUDIO_BANDS];
+    if magnitudes.is_empty() {
+        return bands;
+    }
+
+    let nyquist = sample_rate * 0.5;
+    let bin_hz = nyquist / magnitudes.len() as f32;
+    let edge = |band: usize| {
+        LOWEST_BAND_HZ * (nyquist / LOWEST_BAND_HZ).powf(band as f32 / AUDIO_BANDS as f32)
+    };
+
+    for (band, level) in bands.iter_mut().enumerate() {
+        let first = ((edge(band) / bin_hz).round() as usize).min(magnitudes.len() - 1);
+        let last = ((edge(band + 1) / bin_hz).round() as usize).clamp(first + 1, magnitudes.len());
+        let magnitude = magnitudes[first..last].iter().copied().fold(0.0, f32::max);
+        let db = 20.0 * magnitude.max(1e-6).log10();
+        *level = ((db - SILENCE_DB) / -SILENCE_DB).clamp(0.0, 1.0);
+    }
+    bands
+}
+
+/// In-place radix-2 FFT; both slices have the same power-of-two length.
+fn fft(real: &mut [f32], imaginary: &mut [f32]) {
+    let n = real.len();
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if j > i {
+            real.swap(i, j);
+            imaginary.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+    while size <= n {
+        let step = -std::f32::consts::TAU / size as f32;
+        for start in (0..n).step_by(size) {
+            for k in 0..size / 2 {
+                let (sin, cos) = (step * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + size / 2);
+                let re = real[b] * cos - imaginary[b] * sin;
+                let im = real[b] * sin + imaginary[b] * cos;
+                real[b] = real[a] - re;
+                imaginary[b] = imaginary[a] - im;
+                real[a] += re;
+                imaginary[a] += im;
+            }
+        }
+        size *= 2;
+    }
+}
+
+/// Uniform buffer with the latest `AudioUniforms` for shaders that include
+/// `shaders/audio.glsl`, visible to every graphics and compute stage.
+pub struct AudioSet {
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    _descriptor_pool: VulkanDescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    buffer: VulkanBuffer,
+}
+
+impl AudioSet {
+    pub fn new(device: &VulkanDevice, physical_device: &VulkanPhysicalDevice) -> Result<Self> {
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[VulkanDescriptorSetLayout::binding(
+                0,
+                vk::DescriptorType::UNIFORM_BUFFER,
+                vk::ShaderStageFlags::ALL_GRAPHICS | vk::ShaderStageFlags::COMPUTE,
+            )],
+        )?;
+
+        let buffer = VulkanBuffer::new_host_visible(
+            device,
+            physical_device,
+            std::mem::size_of::<AudioUniforms>() as vk::DeviceSize,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+        )?;
+        buffer.write(0, &[AudioUniforms::default()])?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(device, &descriptor_set_layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&descriptor_set_layout)?;
+        descriptor_pool.write_buffer(
+            descriptor_set,
+            0,
+            vk::DescriptorType::UNIFORM_BUFFER,
+            &buffer,
+        );
+
+        Ok(Self {
+            descriptor_set_layout,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            buffer,
+        })
+    }
+
+    pub fn descriptor_set_layout(&self) -> &VulkanDescriptorSetLayout {
+        &self.descriptor_set_layout
+    }
+
+    pub fn descriptor_set(&self) -> vk::DescriptorSet {
+        self.descriptor_set
+    }
+
+    /// Writes this frame's levels. No in-flight frame may still be reading the previous ones.
+    pub fn update(&self, uniforms: &AudioUniforms) -> Result<()> {
+        self.buffer.write(0, std::slice::from_ref(uniforms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f32, sample_rate: f32, amplitude: f32, count: usize) -> Vec<f32> {
+        (0..count)
+            .map(|i| amplitude * (std::f32::consts::TAU * frequency * i as f32 / sample_rate).sin())
+            .collect()
+    }
+
+    #[test]
+    fn fft_finds_the_tone_bin() {
+        let mut real = sine(8.0, 64.0, 1.0, 64);
+        let mut imaginary = vec![0.0; 64];
+        fft(&mut real, &mut imaginary);
+
+        let magnitude = |bin: usize| (real[bin].powi(2) + imaginary[bin].powi(2)).sqrt();
+        assert!((magnitude(8) - 32.0).abs() < 1e-3);
+        assert!(magnitude(3) < 1e-3);
+    }
+
+    #[test]
+    fn tone_lights_up_its_band() {
+        let sample_rate = 48000.0;
+        let mut analyzer = AudioAnalyzer::new(sample_rate);
+        analyzer.push_samples(&sine(1000.0, sample_rate, 0.5, AUDIO_FFT_SIZE));
+        let uniforms = analyzer.update(1.0);
+
+        let loudest = (0..AUDIO_BANDS)
+            .max_by(|&a, &b| uniforms.band(a).total_cmp(&uniforms.band(b)))
+            .unwrap();
+        let nyquist = sample_rate * 0.5;
+        let low =
+            LOWEST_BAND_HZ * (nyquist / LOWEST_BAND_HZ).powf(loudest as f32 / AUDIO_BANDS as f32);
+        let high = LOWEST_BAND_HZ
+            * (nyquist / LOWEST_BAND_HZ).powf((loudest + 1) as f32 / AUDIO_BANDS as f32);
+        assert!(
+            low * 0.9 <= 1000.0 && 1000.0 <= high * 1.1,
+            "{} {}",
+            low,
+            high
+        );
+
+        // -6 dB on a 60 dB scale.
+        assert!((uniforms.band(loudest) - 0.9).abs() < 0.05);
+        assert!((uniforms.levels[0] - 0.5 / std::f32::consts::SQRT_2).abs() < 1e-3);
+        assert_eq!(uniforms.levels[2], 1.0);
+    }
+
+    #[test]
+    fn levels_release_after_silence() {
+        let mut analyzer = AudioAnalyzer::new(48000.0);
+        analyzer.push_samples(&sine(440.0, 48000.0, 1.0, 4800));
+        let loud = analyzer.update(0.1);
+        analyzer.push_samples(&[0.0; 4800]);
+        let quiet = analyzer.update(0.1);
+
+        assert!(quiet.levels[0] < loud.levels[0]);
+        assert!(quiet.levels[0] > 0.0);
+        assert!(quiet.levels[2] < loud.levels[2]);
+        assert!((quiet.levels[3] - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn uniforms_match_the_shader_block() {
+        assert_eq!(std::mem::size_of::<AudioUniforms>(), 16 + AUDIO_BANDS * 4);
+    }
+}
@@ -0,0 +1,455 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::{IVec2, Mat4, Vec3, Vec4};
+use std::sync::Arc;
+
+use crate::pipeline::{VulkanComputePipeline, VulkanComputePipelineBuilder};
+use crate::renderer::GpuVertex;
+use crate::vulkan::{
+    RecordCommands, VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanPhysicalDevice,
+};
+
+/// Rectangular particle grid of a cloth, lying in the XZ plane of `transform` before it
+/// starts moving: columns along +X, rows along +Z, `spacing` apart.
+#[derive(Debug, Clone, Copy)]
+pub struct ClothGrid {
+    pub columns: u32,
+    pub rows: u32,
+    pub spacing: f32,
+    pub transform: Mat4,
+}
+
+impl ClothGrid {
+    pub fn particle_count(&self) -> u32 {
+        self.columns * self.rows
+    }
+
+    /// Index of the particle in `column` of `row`.
+    pub fn particle(&self, column: u32, row: u32) -> u32 {
+        row * self.columns + column
+    }
+
+    /// Rest positions with the inverse mass in w: 0 for `pinned` particles, 1 otherwise.
+    pub fn particles(&self, pinned: &[u32]) -> Vec<Vec4> {
+        (0..self.rows)
+            .flat_map(|row| (0..self.columns).map(move |column| (column, row)))
+            .map(|(column, row)| {
+                let position = self.transform.transform_point3(Vec3::new(
+                    column as f32 * self.spacing,
+                    0.0,
+                    row as f32 * self.spacing,
+                ));
+                let inverse_mass = if pinned.contains(&self.particle(column, row)) {
+                    0.0
+                } else {
+                    1.0
+                };
+                position.extend(inverse_mass)
+            })
+            .collect()
+    }
+
+    /// Two triangles per cell, counter-clockwise seen from the side the normals point to.
+    pub fn indices(&self) -> Vec<u32> {
+        let mut indices = Vec::new();
+        for row in 0..self.rows.saturating_sub(1) {
+            for column in 0..self.columns.saturating_sub(1) {
+                let corner = self.particle(column, row);
+                let right = corner + 1;
+                let below = corner + self.columns;
+                indices.extend_from_slice(&[corner, below, right, right, below, below + 1]);
+            }
+        }
+        indices
+    }
+}
+
+/// Vertex normals of a particle grid from central differences, exactly like the vertex pass
+/// of `cloth.comp`.
+pub fn cloth_normals(positions: &[Vec3], columns: u32, rows: u32) -> Vec<Vec3> {
+    let size = IVec2::new(columns as i32, rows as i32);
+    let at = |cell: IVec2| {
+        let cell = cell.clamp(IVec2::ZERO, size - 1);
+        positions[(cell.y * size.x + cell.x) as usize]
+    };
+
+    (0..positions.len() as i32)
+        .map(|index| {
+            let cell = IVec2::new(index % size.x, index / size.x);
+            let along_rows = at(cell + IVec2::Y) - at(cell - IVec2::Y);
+            let along_columns = at(cell + IVec2::X) - at(cell - IVec2::X);
+            along_rows.cross(along_columns).normalize_or_zero()
+        })
+        .collect()
+}
+
+/// Solver parameters of a `ClothSimulation`.
+#[derive(Debug, Clone, Copy)]
+pub struct ClothSettings {
+    pub gravity: Vec3,
+    /// Integration steps per `record`; more keep fast motion stable.
+    pub substeps: u32,
+    /// Constraint iterations per substep; more make the cloth less stretchy.
+    pub iterations: u32,
+    /// Fraction of each constraint error corrected per iteration, in 0..=1.
+    pub stiffness: f32,
+    /// Fraction of the velocity lost per substep.
+    pub damping: f32,
+    /// Sphere the cloth collides with: center and radius.
+    pub collider: Option<(Vec3, f32)>,
+}
+
+impl Default for ClothSettings {
+    fn default() -> Self {
+        Self {
+            gravity: Vec3::new(0.0, -9.81, 0.0),
+            substeps: 4,
+            iterations: 8,
+            stiffness: 0.8,
+            damping: 0.01,
+            collider: None,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ClothPushConstants {
+    gravity_dt: Vec4,
+    collider: Vec4,
+    columns: u32,
+    rows: u32,
+    spacing: f32,
+    stiffness: f32,
+    damping: f32,
+    pass_index: u32,
+}
+
+const INTEGRATE_PASS: u32 = 0;
+const SOLVE_INTO_SCRATCH_PASS: u32 = 1;
+const SOLVE_FROM_SCRATCH_PASS: u32 = 2;
+const VERTEX_PASS: u32 = 3;
+
+/// Compute pipeline shared by every `ClothSimulation`.
+pub struct ClothPipeline {
+    pipeline: VulkanComputePipeline,
+    layout: VulkanDescriptorSetLayout,
+}
+
+impl ClothPipeline {
+    pub fn new(device: &VulkanDevice) -> Result<Self> {
+        let layout = VulkanDescriptorSetLayout::new(
+            device,
+            &(0..4)
+                .map(|binding| {
+                    VulkanDescriptorSetLayout::binding(
+                        binding,
+                        vk::DescriptorType::STORAGE_BUFFER,
+                        vk::ShaderStageFlags::COMPUTE,
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )?;
+
+        let pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/cloth.comp.spv"), None)?
+            .with_descriptor_set_layout(layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(std::mem::size_of::<ClothPushConstants>() as u32),
+            )
+            .build()?;
+
+        Ok(Self { pipeline, layout })
+    }
+}
+
+/// Position-based cloth simulated by `cloth.comp`: Verlet integration, then Jacobi iterations
+/// over structural, shear and bend distance constraints between grid neighbours, then a pass
+/// writing `output` with recomputed normals.
+///
+/// `output` is a regular `GpuVertex` buffer in world space, so the cloth draws through the
+/// standard material pipeline: bind a material with `MaterialFeatures::DOUBLE_SIDED`, push an
+/// identity model transform and call `draw`.
+pub struct ClothSimulation {
+    pub output: VulkanBuffer,
+    pub index_buffer: VulkanBuffer,
+    pub settings: ClothSettings,
+    positions: VulkanBuffer,
+    previous: VulkanBuffer,
+    scratch: VulkanBuffer,
+    _descriptor_pool: VulkanDescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    grid: ClothGrid,
+    rest: Vec<Vec4>,
+    index_count: u32,
+    device: Arc<Device>,
+}
+
+impl ClothSimulation {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        pipeline: &ClothPipeline,
+        grid: ClothGrid,
+        pinned: &[u32],
+    ) -> Result<Self> {
+        if grid.columns < 2 || grid.rows < 2 {
+            return Err(anyhow::anyhow!(
+                "Cloth grid of {}x{} particles needs at least 2x2",
+                grid.columns,
+                grid.rows
+            ));
+        }
+        if let Some(particle) = pinned
+            .iter()
+            .find(|&&particle| particle >= grid.particle_count())
+        {
+            return Err(anyhow::anyhow!(
+                "Pinned particle {} is outside the {} particle grid",
+                particle,
+                grid.particle_count()
+            ));
+        }
+
+        let rest = grid.particles(pinned);
+        let particle_size = std::mem::size_of_val(rest.as_slice()) as vk::DeviceSize;
+        let storage = || {
+            VulkanBuffer::new_host_visible(
+                device,
+                physical_device,
+                particle_size,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+            )
+        };
+        let positions = storage()?;
+        let previous = storage()?;
+        let scratch = storage()?;
+        positions.write(0, &rest)?;
+        previous.write(0, &rest)?;
+
+        let output = VulkanBuffer::new(
+            device,
+            physical_device,
+            (grid.particle_count() as usize * std::mem::size_of::<GpuVertex>()) as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::VERTEX_BUFFER
+                | device.features.acceleration_structure_input_usage(),
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let indices = grid.indices();
+        let index_buffer = VulkanBuffer::new_host_visible(
+            device,
+            physical_device,
+            std::mem::size_of_val(indices.as_slice()) as vk::DeviceSize,
+            vk::BufferUsageFlags::INDEX_BUFFER
+                | vk::BufferUsageFlags::STORAGE_BUFFER
+                | device.features.acceleration_structure_input_usage(),
+        )?;
+        index_buffer.write(0, &indices)?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(device, &pipeline.layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&pipeline.layout)?;
+        for (binding, buffer) in [&positions, &previous, &scratch, &output]
+            .into_iter()
+            .enumerate()
+        {
+            descriptor_pool.write_buffer(
+                descriptor_set,
+                binding as u32,
+                vk::DescriptorType::STORAGE_BUFFER,
+                buffer,
+            );
+        }
+
+        Ok(Self {
+            output,
+            index_buffer,
+            settings: ClothSettings::default(),
+            positions,
+            previous,
+            scratch,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            grid,
+            rest,
+            index_count: indices.len() as u32,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn grid(&self) -> &ClothGrid {
+        &self.grid
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.index_count
+    }
+
+    /// Puts every particle back at rest. No in-flight frame may still be simulating.
+    pub fn reset(&self) -> Result<()> {
+        self.positions.write(0, &self.rest)?;
+        self.previous.write(0, &self.rest)
+    }
+
+    /// Records `delta_time` seconds of simulation, then a barrier making `output` readable as
+    /// vertices and by shaders. Must be recorded outside a render pass, before the passes
+    /// drawing the cloth.
+    pub fn record(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        pipeline: &ClothPipeline,
+        delta_time: f32,
+    ) {
+        let settings = &self.settings;
+        let substeps = settings.substeps.max(1);
+        let (center, radius) = settings.collider.unwrap_or((Vec3::ZERO, 0.0));
+        let constants = |pass_index: u32| ClothPushConstants {
+            gravity_dt: settings.gravity.extend(delta_time / substeps as f32),
+            collider: center.extend(radius),
+            columns: self.grid.columns,
+            rows: self.grid.rows,
+            spacing: self.grid.spacing,
+            stiffness: settings.stiffness.clamp(0.0, 1.0),
+            damping: settings.damping.clamp(0.0, 1.0),
+            pass_index,
+        };
+
+        let compute = &pipeline.pipeline;
+        let group_count = self.grid.particle_count().div_ceil(64);
+        let dispatch = |pass_index: u32| {
+            compute.push_constants(command_buffer, &constants(pass_index));
+            compute.dispatch(command_buffer, group_count, 1, 1);
+            for buffer in [&self.positions, &self.previous, &self.scratch] {
+                buffer.cmd_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::AccessFlags::SHADER_WRITE,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+                );
+            }
+        };
+
+        compute.bind(command_buffer);
+        compute.bind_descriptor_sets(command_buffer, 0, &[self.descriptor_set]);
+        for _ in 0..substeps {
+            dispatch(INTEGRATE_PASS);
+            for _ in 0..settings.iterations {
+                dispatch(SOLVE_INTO_SCRATCH_PASS);
+                dispatch(SOLVE_FROM_SCRATCH_PASS);
+            }
+        }
+
+        // The previous frame's passes may still be drawing the output.
+        self.output.cmd_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::VERTEX_INPUT | vk::PipelineStageFlags::VERTEX_SHADER,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ | vk::AccessFlags::SHADER_READ,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+        );
+        compute.push_constants(command_buffer, &constants(VERTEX_PASS));
+        compute.dispatch(command_buffer, group_count, 1, 1);
+        self.output.cmd_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::VERTEX_INPUT | vk::PipelineStageFlags::VERTEX_SHADER,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ | vk::AccessFlags::SHADER_READ,
+        );
+    }
+
+    /// Binds `output` and `index_buffer` and draws the cloth with the bound pipeline.
+    pub fn draw(&self, command_buffer: vk::CommandBuffer) {
+        self.device
+            .bind_vertex_buffers(command_buffer, 0, &[self.output.buffer], &[0]);
+        self.device.bind_index_buffer(
+            command_buffer,
+            self.index_buffer.buffer,
+            0,
+            vk::IndexType::UINT32,
+        );
+        self.device
+            .draw_indexed(command_buffer, self.index_count, 1, 0, 0, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ShaderReflection;
+
+    fn grid() -> ClothGrid {
+        ClothGrid {
+            columns: 4,
+            rows: 3,
+            spacing: 0.5,
+            transform: Mat4::from_translation(Vec3::new(0.0, 2.0, 0.0)),
+        }
+    }
+
+    #[test]
+    fn particles_lie_on_the_transformed_grid() {
+        let grid = grid();
+        let particles = grid.particles(&[grid.particle(0, 0), grid.particle(3, 0)]);
+
+        assert_eq!(particles.len(), 12);
+        assert_eq!(particles[0], Vec4::new(0.0, 2.0, 0.0, 0.0));
+        assert_eq!(particles[3], Vec4::new(1.5, 2.0, 0.0, 0.0));
+        assert_eq!(
+            particles[grid.particle(1, 2) as usize],
+            Vec4::new(0.5, 2.0, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn triangles_face_the_normals() {
+        let grid = grid();
+        let positions: Vec<Vec3> = grid.particles(&[]).iter().map(|p| p.truncate()).collect();
+        let normals = cloth_normals(&positions, grid.columns, grid.rows);
+        let indices = grid.indices();
+
+        assert_eq!(indices.len(), 3 * 2 * 6);
+        assert!(normals.iter().all(|normal| *normal == Vec3::Y));
+        for triangle in indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| positions[triangle[i] as usize]);
+            let face = (b - a).cross(c - a).normalize();
+            assert!(face.abs_diff_eq(Vec3::Y, 1e-6));
+        }
+    }
+
+    #[test]
+    fn normals_follow_a_fold() {
+        // Columns 0-1 flat, columns 2-3 bent down by 90 degrees around the x = 0.5 edge.
+        let positions: Vec<Vec3> = (0..3)
+            .flat_map(|row| {
+                [
+                    Vec3::new(0.0, 0.0, row as f32),
+                    Vec3::new(0.5, 0.0, row as f32),
+                    Vec3::new(0.5, -0.5, row as f32),
+                    Vec3::new(0.5, -1.0, row as f32),
+                ]
+            })
+            .collect();
+        let normals = cloth_normals(&positions, 4, 3);
+
+        assert_eq!(normals[0], Vec3::Y);
+        assert!(normals[3].abs_diff_eq(Vec3::X, 1e-6));
+    }
+
+    #[test]
+    fn shader_layout_matches_cloth_pipeline() {
+        let reflection =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/cloth.comp.spv")).unwrap();
+
+        assert_eq!(reflection.layout_bindings(0).len(), 4);
+        // The struct is padded to its 16-byte alignment; the range may exceed what's used.
+        assert_eq!(reflection.push_constant_size, Some(56));
+        assert!(std::mem::size_of::<ClothPushConstants>() >= 56);
+    }
+}
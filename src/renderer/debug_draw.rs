@@ -0,0 +1,270 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::{Mat4, Vec3, Vec4};
+use std::sync::Arc;
+
+use crate::pipeline::{VulkanPipeline, VulkanPipelineBuilder};
+use crate::vulkan::{RecordCommands, VulkanBuffer, VulkanDevice, VulkanPhysicalDevice};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DebugVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+impl DebugVertex {
+    pub fn binding_description(binding: u32) -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription::default()
+            .binding(binding)
+            .stride(std::mem::size_of::<Self>() as u32)
+            .input_rate(vk::VertexInputRate::VERTEX)
+    }
+
+    /// Locations 0..=1: position, color.
+    pub fn attribute_descriptions(binding: u32) -> [vk::VertexInputAttributeDescription; 2] {
+        [
+            vk::VertexInputAttributeDescription::default()
+                .binding(binding)
+                .location(0)
+                .format(vk::Format::R32G32B32_SFLOAT)
+                .offset(0),
+            vk::VertexInputAttributeDescription::default()
+                .binding(binding)
+                .location(1)
+                .format(vk::Format::R32G32B32A32_SFLOAT)
+                .offset(12),
+        ]
+    }
+}
+
+/// Immediate-mode line list, refilled every frame and drawn by `DebugDrawRenderer`.
+#[derive(Debug, Clone, Default)]
+pub struct DebugDraw {
+    vertices: Vec<DebugVertex>,
+}
+
+impl DebugDraw {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    pub fn vertices(&self) -> &[DebugVertex] {
+        &self.vertices
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.vertices.len() / 2
+    }
+
+    pub fn line(&mut self, a: Vec3, b: Vec3, color: Vec4) {
+        let color = color.to_array();
+        self.vertices.push(DebugVertex {
+            position: a.to_array(),
+            color,
+        });
+        self.vertices.push(DebugVertex {
+            position: b.to_array(),
+            color,
+        });
+    }
+
+    /// Red, green and blue lines along the X, Y and Z axes of `transform`.
+    pub fn axes(&mut self, transform: Mat4, length: f32) {
+        let origin = transform.w_axis.truncate();
+        self.line(
+            origin,
+            transform.transform_point3(Vec3::X * length),
+            Vec4::new(1.0, 0.0, 0.0, 1.0),
+        );
+        self.line(
+            origin,
+            transform.transform_point3(Vec3::Y * length),
+            Vec4::new(0.0, 1.0, 0.0, 1.0),
+        );
+        self.line(
+            origin,
+            transform.transform_point3(Vec3::Z * length),
+            Vec4::new(0.0, 0.0, 1.0, 1.0),
+        );
+    }
+
+    pub fn aabb(&mut self, min: Vec3, max: Vec3, color: Vec4) {
+        let corner = |i: usize| {
+            Vec3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        };
+        for i in 0..8 {
+            for axis in [1, 2, 4] {
+                if i & axis == 0 {
+                    self.line(corner(i), corner(i | axis), color);
+                }
+            }
+        }
+    }
+
+    /// Three great circles of `segments` lines each.
+    pub fn sphere(&mut self, center: Vec3, radius: f32, segments: u32, color: Vec4) {
+        let segments = segments.max(3);
+        for (u, v) in [(Vec3::X, Vec3::Y), (Vec3::Y, Vec3::Z), (Vec3::Z, Vec3::X)] {
+            let point = |i: u32| {
+                let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+                center + (u * angle.cos() + v * angle.sin()) * radius
+            };
+            for i in 0..segments {
+                self.line(point(i), point(i + 1), color);
+            }
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DebugLinePushConstants {
+    view_proj: Mat4,
+}
+
+/// Draws a `DebugDraw` line list with depth testing and without depth writes, so lines stay
+/// hidden behind opaque geometry without occluding each other. Overlays such as gizmo handles
+/// can opt out of depth testing with `with_depth_test`.
+pub struct DebugDrawRenderer {
+    pipeline: VulkanPipeline,
+    vertex_buffer: VulkanBuffer,
+    max_lines: usize,
+    line_count: u32,
+    device: Arc<Device>,
+}
+
+impl DebugDrawRenderer {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        max_lines: usize,
+    ) -> Result<Self> {
+        Self::with_depth_test(
+            device,
+            physical_device,
+            render_pass,
+            extent,
+            max_lines,
+            true,
+        )
+    }
+
+    /// Like `new`, drawing lines over everything already in the pass when `depth_test` is false.
+    pub fn with_depth_test(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        max_lines: usize,
+        depth_test: bool,
+    ) -> Result<Self> {
+        let vertex_buffer = VulkanBuffer::new_host_visible(
+            device,
+            physical_device,
+            (max_lines.max(1) * 2 * std::mem::size_of::<DebugVertex>()) as vk::DeviceSize,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+        )?;
+
+        let mut builder = VulkanPipelineBuilder::new(device)
+            .set_render_pass(render_pass)
+            .set_extent(extent)
+            .with_vertex_spv(include_bytes!("../../bin/debug_line.vert.spv"))?
+            .with_fragment_spv(include_bytes!("../../bin/debug_line.frag.spv"))?
+            .with_vertex_binding(DebugVertex::binding_description(0))
+            .with_topology(vk::PrimitiveTopology::LINE_LIST)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::VERTEX)
+                    .offset(0)
+                    .size(std::mem::size_of::<DebugLinePushConstants>() as u32),
+            )
+            .with_depth_test(depth_test, false, vk::CompareOp::LESS_OR_EQUAL)
+            .with_cull_mode(vk::CullModeFlags::NONE)
+            .with_alpha_blending()
+            .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]);
+
+        for attribute in DebugVertex::attribute_descriptions(0) {
+            builder = builder.with_vertex_attribute(attribute);
+        }
+
+        Ok(Self {
+            pipeline: builder.build()?,
+            vertex_buffer,
+            max_lines,
+            line_count: 0,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Copies the lines for the next `draw`, dropping any past `max_lines`. No in-flight frame
+    /// may still be drawing the previous lines.
+    pub fn upload(&mut self, debug_draw: &DebugDraw) -> Result<()> {
+        let line_count = debug_draw.line_count().min(self.max_lines);
+        self.vertex_buffer
+            .write(0, &debug_draw.vertices()[..line_count * 2])?;
+        self.line_count = line_count as u32;
+        Ok(())
+    }
+
+    /// Draws the uploaded lines inside the current render pass.
+    pub fn draw(&self, command_buffer: vk::CommandBuffer, view_proj: Mat4) {
+        if self.line_count == 0 {
+            return;
+        }
+
+        self.pipeline.bind(command_buffer);
+        self.pipeline.push_constants(
+            command_buffer,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            &DebugLinePushConstants { view_proj },
+        );
+        self.device
+            .bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer.buffer], &[0]);
+        self.device
+            .draw(command_buffer, self.line_count * 2, 1, 0, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ShaderReflection;
+
+    #[test]
+    fn aabb_draws_twelve_edges() {
+        let mut debug_draw = DebugDraw::new();
+        debug_draw.aabb(Vec3::ZERO, Vec3::ONE, Vec4::ONE);
+
+        assert_eq!(debug_draw.line_count(), 12);
+        for edge in debug_draw.vertices().chunks_exact(2) {
+            let length = Vec3::from(edge[0].position).distance(Vec3::from(edge[1].position));
+            assert_eq!(length, 1.0);
+        }
+
+        debug_draw.clear();
+        assert_eq!(debug_draw.line_count(), 0);
+    }
+
+    #[test]
+    fn shader_layout_matches_debug_lines() {
+        let reflection =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/debug_line.vert.spv")).unwrap();
+
+        assert_eq!(
+            reflection.push_constant_size,
+            Some(std::mem::size_of::<DebugLinePushConstants>() as u32)
+        );
+    }
+}
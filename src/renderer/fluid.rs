@@ -0,0 +1,599 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::{IVec3, Mat4, Vec3, Vec4};
+use std::sync::Arc;
+
+use crate::pipeline::{
+    RadixSort, SubgroupPipelines, VulkanComputePipeline, VulkanComputePipelineBuilder,
+    VulkanPipeline, VulkanPipelineBuilder,
+};
+use crate::vulkan::{
+    RecordCommands, VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanPhysicalDevice,
+};
+
+/// Parameters of a `FluidSimulation`, in meters, kilograms and seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct FluidSettings {
+    pub gravity: Vec3,
+    /// Kernel radius; also the spatial hash cell size.
+    pub smoothing_radius: f32,
+    pub particle_mass: f32,
+    pub rest_density: f32,
+    /// Pressure per unit of density above `rest_density`.
+    pub stiffness: f32,
+    pub viscosity: f32,
+    pub time_step: f32,
+    /// Steps of `time_step` per `record`.
+    pub substeps: u32,
+    /// Axis-aligned container the particles bounce inside.
+    pub bounds_min: Vec3,
+    pub bounds_max: Vec3,
+}
+
+impl Default for FluidSettings {
+    fn default() -> Self {
+        let smoothing_radius = 0.1;
+        let rest_density = 1000.0;
+        Self {
+            gravity: Vec3::new(0.0, -9.81, 0.0),
+            smoothing_radius,
+            // Particles at rest half a radius apart reach the rest density.
+            particle_mass: rest_density * (smoothing_radius * 0.5).powi(3),
+            rest_density,
+            stiffness: 200.0,
+            viscosity: 1.0,
+            time_step: 1.0 / 240.0,
+            substeps: 4,
+            bounds_min: Vec3::new(-1.0, 0.0, -0.5),
+            bounds_max: Vec3::new(1.0, 2.0, 0.5),
+        }
+    }
+}
+
+impl FluidSettings {
+    /// `count` particles stacked in a cube against the lower corner of the container, half a
+    /// smoothing radius apart, ready to collapse like a dam break.
+    pub fn dam_break(&self, count: u32) -> Vec<Vec4> {
+        let spacing = self.smoothing_radius * 0.5;
+        let side = (count as f32).cbrt().ceil().max(1.0) as u32;
+        (0..count)
+            .map(|index| {
+                let cell = Vec3::new(
+                    (index % side) as f32,
+                    (index / (side * side)) as f32,
+                    (index / side % side) as f32,
+                );
+                (self.bounds_min + (cell + 0.5) * spacing)
+                    .min(self.bounds_max)
+                    .extend(1.0)
+            })
+            .collect()
+    }
+}
+
+/// Bucket of the spatial hash table holding `cell`, exactly like `cell_hash` in `fluid.comp`.
+pub fn fluid_cell_hash(cell: IVec3, table_mask: u32) -> u32 {
+    let cell = cell.as_uvec3();
+    (cell.x.wrapping_mul(73856093) ^ cell.y.wrapping_mul(19349663) ^ cell.z.wrapping_mul(83492791))
+        & table_mask
+}
+
+/// Poly6 smoothing kernel used for densities, at squared distance `r2`.
+pub fn fluid_density_kernel(r2: f32, smoothing_radius: f32) -> f32 {
+    let h2 = smoothing_radius * smoothing_radius;
+    if r2 >= h2 {
+        return 0.0;
+    }
+    315.0 / (64.0 * std::f32::consts::PI * smoothing_radius.powi(9)) * (h2 - r2).powi(3)
+}
+
+/// Hash buckets for `count` particles: a power of two with at most one particle per two
+/// buckets, so the radix sort only has to sort `trailing_zeros` bits.
+fn hash_table_size(count: u32) -> u32 {
+    (count * 2).next_power_of_two()
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FluidPushConstants {
+    gravity_dt: Vec4,
+    bounds_min: Vec4,
+    bounds_max: Vec4,
+    rest_density: f32,
+    stiffness: f32,
+    viscosity: f32,
+    count: u32,
+    table_mask: u32,
+    pass_index: u32,
+}
+
+const HASH_PASS: u32 = 0;
+const CELL_RANGE_PASS: u32 = 1;
+const DENSITY_PASS: u32 = 2;
+const FORCE_PASS: u32 = 3;
+const INTEGRATE_PASS: u32 = 4;
+
+/// Direction of a `FluidSimulation::record_handoff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FluidHandoff {
+    /// From the simulation to `FluidRenderer`.
+    ToGraphics,
+    /// From `FluidRenderer` back to the next simulation step.
+    ToCompute,
+}
+
+/// SPH fluid simulated by `fluid.comp`, meant as a stress test of storage buffers and async
+/// compute more than as a production water solver.
+///
+/// Each step hashes the particles into a grid of `smoothing_radius` cells, sorts them by
+/// bucket with a `RadixSort`, finds the range of every bucket, then gathers densities and
+/// pressure and viscosity forces from the 27 surrounding cells before integrating. `record`
+/// only records compute and transfer commands, so it can go to `VulkanDevice::compute_queue`
+/// and overlap the graphics work of the frame, with `record_handoff` moving the buffers
+/// between the queues.
+pub struct FluidSimulation {
+    /// xyz: particle positions.
+    pub positions: VulkanBuffer,
+    /// xyz: particle velocities.
+    pub velocities: VulkanBuffer,
+    pub settings: FluidSettings,
+    _densities: VulkanBuffer,
+    _accelerations: VulkanBuffer,
+    cell_keys: VulkanBuffer,
+    cell_particles: VulkanBuffer,
+    cell_starts: VulkanBuffer,
+    _cell_ends: VulkanBuffer,
+    sort: RadixSort,
+    pipeline: VulkanComputePipeline,
+    _layout: VulkanDescriptorSetLayout,
+    _descriptor_pool: VulkanDescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    count: u32,
+    device: Arc<Device>,
+}
+
+impl FluidSimulation {
+    /// Creates the simulation with particles at `positions`, at rest.
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        subgroup_pipelines: &SubgroupPipelines,
+        settings: FluidSettings,
+        positions: &[Vec4],
+    ) -> Result<Self> {
+        let count = positions.len() as u32;
+        let table_size = hash_table_size(count);
+
+        let host_storage = |size: usize| {
+            VulkanBuffer::new_host_visible(
+                device,
+                physical_device,
+                size as vk::DeviceSize,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+            )
+        };
+        let device_storage = |size: usize, usage: vk::BufferUsageFlags| {
+            VulkanBuffer::new(
+                device,
+                physical_device,
+                size as vk::DeviceSize,
+                vk::BufferUsageFlags::STORAGE_BUFFER | usage,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )
+        };
+        let vec4_size = count as usize * std::mem::size_of::<Vec4>();
+        let u32_size = count as usize * std::mem::size_of::<u32>();
+
+        let position_buffer = host_storage(vec4_size)?;
+        position_buffer.write(0, positions)?;
+        let velocities = host_storage(vec4_size)?;
+        velocities.write(0, &vec![Vec4::ZERO; count as usize])?;
+
+        let densities = device_storage(u32_size * 2, vk::BufferUsageFlags::empty())?;
+        let accelerations = device_storage(vec4_size, vk::BufferUsageFlags::empty())?;
+        let cell_keys = device_storage(u32_size, vk::BufferUsageFlags::empty())?;
+        let cell_particles = device_storage(u32_size, vk::BufferUsageFlags::empty())?;
+        let table_bytes = table_size as usize * std::mem::size_of::<u32>();
+        let cell_starts = device_storage(table_bytes, vk::BufferUsageFlags::TRANSFER_DST)?;
+        let cell_ends = device_storage(table_bytes, vk::BufferUsageFlags::empty())?;
+
+        let sort = RadixSort::new(
+            device,
+            physical_device,
+            subgroup_pipelines,
+            &cell_keys,
+            &cell_particles,
+            count,
+        )?;
+
+        let layout = VulkanDescriptorSetLayout::new(
+            device,
+            &(0..8)
+                .map(|binding| {
+                    VulkanDescriptorSetLayout::binding(
+                        binding,
+                        vk::DescriptorType::STORAGE_BUFFER,
+                        vk::ShaderStageFlags::COMPUTE,
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(device, &layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&layout)?;
+        for (binding, buffer) in [
+            &position_buffer,
+            &velocities,
+            &densities,
+            &accelerations,
+            &cell_keys,
+            &cell_particles,
+            &cell_starts,
+            &cell_ends,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            descriptor_pool.write_buffer(
+                descriptor_set,
+                binding as u32,
+                vk::DescriptorType::STORAGE_BUFFER,
+                buffer,
+            );
+        }
+
+        let pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/fluid.comp.spv"), None)?
+            .with_descriptor_set_layout(layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(std::mem::size_of::<FluidPushConstants>() as u32),
+            )
+            .build()?;
+
+        Ok(Self {
+            positions: position_buffer,
+            velocities,
+            settings,
+            _densities: densities,
+            _accelerations: accelerations,
+            cell_keys,
+            cell_particles,
+            cell_starts,
+            _cell_ends: cell_ends,
+            sort,
+            pipeline,
+            _layout: layout,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            count,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn particle_count(&self) -> u32 {
+        self.count
+    }
+
+    /// Makes compute writes visible to the next pass.
+    fn record_barrier(&self, command_buffer: vk::CommandBuffer) {
+        let barrier = vk::MemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE);
+
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(),
+                std::slice::from_ref(&barrier),
+                &[],
+                &[],
+            );
+        }
+    }
+
+    /// Records `settings.substeps` simulation steps. Outside a render pass; the caller must
+    /// make sure no in-flight frame is still drawing the particles.
+    pub fn record(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        subgroup_pipelines: &SubgroupPipelines,
+    ) {
+        let settings = &self.settings;
+        let table_mask = hash_table_size(self.count) - 1;
+        let constants = |pass_index: u32| FluidPushConstants {
+            gravity_dt: settings.gravity.extend(settings.time_step),
+            bounds_min: settings.bounds_min.extend(settings.smoothing_radius),
+            bounds_max: settings.bounds_max.extend(settings.particle_mass),
+            rest_density: settings.rest_density,
+            stiffness: settings.stiffness,
+            viscosity: settings.viscosity,
+            count: self.count,
+            table_mask,
+            pass_index,
+        };
+
+        let group_count = self.count.div_ceil(64);
+        let dispatch = |pass_index: u32| {
+            self.pipeline.bind(command_buffer);
+            self.pipeline
+                .bind_descriptor_sets(command_buffer, 0, &[self.descriptor_set]);
+            self.pipeline
+                .push_constants(command_buffer, &constants(pass_index));
+            self.pipeline.dispatch(command_buffer, group_count, 1, 1);
+            self.record_barrier(command_buffer);
+        };
+
+        for _ in 0..settings.substeps.max(1) {
+            unsafe {
+                self.device.cmd_fill_buffer(
+                    command_buffer,
+                    self.cell_starts.buffer,
+                    0,
+                    vk::WHOLE_SIZE,
+                    u32::MAX,
+                );
+            }
+            dispatch(HASH_PASS);
+            self.sort.record(
+                command_buffer,
+                subgroup_pipelines,
+                &self.cell_keys,
+                &self.cell_particles,
+                table_mask.count_ones(),
+            );
+            dispatch(CELL_RANGE_PASS);
+            dispatch(DENSITY_PASS);
+            dispatch(FORCE_PASS);
+            dispatch(INTEGRATE_PASS);
+        }
+    }
+
+    /// Records the barrier handing `positions` and `velocities` between the simulation and
+    /// `FluidRenderer`. When the simulation runs on a compute queue of another family than
+    /// graphics, this is a queue family ownership transfer and must be recorded on both
+    /// queues, releasing on the source and acquiring on the destination after a semaphore;
+    /// otherwise it is a plain barrier recorded once.
+    pub fn record_handoff(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        handoff: FluidHandoff,
+        compute_family: u32,
+        graphics_family: u32,
+    ) {
+        let compute = (
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+            compute_family,
+        );
+        let graphics = (
+            vk::PipelineStageFlags::VERTEX_SHADER,
+            vk::AccessFlags::SHADER_READ,
+            graphics_family,
+        );
+        let (src, dst) = match handoff {
+            FluidHandoff::ToGraphics => (compute, graphics),
+            FluidHandoff::ToCompute => (graphics, compute),
+        };
+        let (src_family, dst_family) = if compute_family == graphics_family {
+            (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED)
+        } else {
+            (src.2, dst.2)
+        };
+
+        let barriers = [&self.positions, &self.velocities].map(|buffer| {
+            vk::BufferMemoryBarrier::default()
+                .src_access_mask(src.1)
+                .dst_access_mask(dst.1)
+                .src_queue_family_index(src_family)
+                .dst_queue_family_index(dst_family)
+                .buffer(buffer.buffer)
+                .offset(0)
+                .size(vk::WHOLE_SIZE)
+        });
+
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                src.0,
+                dst.0,
+                vk::DependencyFlags::empty(),
+                &[],
+                &barriers,
+                &[],
+            );
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ParticlePushConstants {
+    view_proj: Mat4,
+    /// w: sphere radius.
+    camera_right: Vec4,
+    /// w: speed drawn fully white.
+    camera_up: Vec4,
+}
+
+/// Draws the particles of a `FluidSimulation` as instanced sphere impostors: one
+/// camera-facing quad per particle, read straight from the simulation's storage buffers and
+/// tinted from blue to white with speed. Impostors keep the depth of their quad.
+pub struct FluidRenderer {
+    pub pipeline: VulkanPipeline,
+    /// World-space radius of the drawn spheres.
+    pub radius: f32,
+    /// Speed at which particles are drawn fully white.
+    pub max_speed: f32,
+    _layout: VulkanDescriptorSetLayout,
+    _descriptor_pool: VulkanDescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    count: u32,
+    device: Arc<Device>,
+}
+
+impl FluidRenderer {
+    pub fn new(
+        device: &VulkanDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        simulation: &FluidSimulation,
+    ) -> Result<Self> {
+        let layout = VulkanDescriptorSetLayout::new(
+            device,
+            &(0..2)
+                .map(|binding| {
+                    VulkanDescriptorSetLayout::binding(
+                        binding,
+                        vk::DescriptorType::STORAGE_BUFFER,
+                        vk::ShaderStageFlags::VERTEX,
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(device, &layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&layout)?;
+        for (binding, buffer) in [&simulation.positions, &simulation.velocities]
+            .into_iter()
+            .enumerate()
+        {
+            descriptor_pool.write_buffer(
+                descriptor_set,
+                binding as u32,
+                vk::DescriptorType::STORAGE_BUFFER,
+                buffer,
+            );
+        }
+
+        let pipeline = VulkanPipelineBuilder::new(device)
+            .set_render_pass(render_pass)
+            .set_extent(extent)
+            .with_vertex_spv(include_bytes!("../../bin/fluid_particle.vert.spv"))?
+            .with_fragment_spv(include_bytes!("../../bin/fluid_particle.frag.spv"))?
+            .with_descriptor_set_layout(layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::VERTEX)
+                    .offset(0)
+                    .size(std::mem::size_of::<ParticlePushConstants>() as u32),
+            )
+            .with_depth_test(true, true, vk::CompareOp::LESS)
+            .with_cull_mode(vk::CullModeFlags::NONE)
+            .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .build()?;
+
+        Ok(Self {
+            pipeline,
+            radius: simulation.settings.smoothing_radius * 0.25,
+            max_speed: 3.0,
+            _layout: layout,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            count: simulation.particle_count(),
+            device: device.device.clone(),
+        })
+    }
+
+    /// Draws every particle inside the current render pass. `view` is the camera's world to
+    /// view transform, which orients the impostors.
+    pub fn draw(&self, command_buffer: vk::CommandBuffer, view: Mat4, proj: Mat4) {
+        let camera = view.inverse();
+        let push_constants = ParticlePushConstants {
+            view_proj: proj * view,
+            camera_right: camera.x_axis.truncate().extend(self.radius),
+            camera_up: camera.y_axis.truncate().extend(self.max_speed),
+        };
+
+        self.pipeline.bind(command_buffer);
+        self.pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.descriptor_set]);
+        self.pipeline.push_constants(
+            command_buffer,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            &push_constants,
+        );
+        self.device.draw(command_buffer, 6, self.count, 0, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ShaderReflection;
+
+    #[test]
+    fn dam_break_fills_the_lower_corner() {
+        let settings = FluidSettings::default();
+        let particles = settings.dam_break(1000);
+        let spacing = settings.smoothing_radius * 0.5;
+
+        assert_eq!(particles.len(), 1000);
+        assert!(particles.iter().all(|p| {
+            p.truncate().cmpge(settings.bounds_min).all()
+                && p.truncate().cmple(settings.bounds_max).all()
+        }));
+        assert!(
+            (particles[1].truncate() - particles[0].truncate())
+                .abs_diff_eq(Vec3::new(spacing, 0.0, 0.0), 1e-6)
+        );
+        // A 10x10x10 block half a radius apart.
+        let top = particles.iter().map(|p| p.y).fold(0.0, f32::max);
+        assert!((top - (settings.bounds_min.y + 9.5 * spacing)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn cell_hash_stays_inside_the_table() {
+        let mask = hash_table_size(1000) - 1;
+        assert_eq!(mask, 2047);
+
+        for cell in [IVec3::ZERO, IVec3::new(-3, 7, 11), IVec3::splat(i32::MIN)] {
+            assert!(fluid_cell_hash(cell, mask) <= mask);
+        }
+        assert_ne!(
+            fluid_cell_hash(IVec3::new(1, 0, 0), mask),
+            fluid_cell_hash(IVec3::new(0, 1, 0), mask)
+        );
+    }
+
+    #[test]
+    fn density_kernel_integrates_to_one() {
+        let h = 0.1;
+        let steps = 200;
+        let dr = h / steps as f32;
+        let integral: f32 = (0..steps)
+            .map(|i| {
+                let r = (i as f32 + 0.5) * dr;
+                4.0 * std::f32::consts::PI * r * r * fluid_density_kernel(r * r, h) * dr
+            })
+            .sum();
+
+        assert!((integral - 1.0).abs() < 1e-3);
+        assert_eq!(fluid_density_kernel(h * h, h), 0.0);
+    }
+
+    #[test]
+    fn shader_layouts_match_fluid_pipelines() {
+        let simulation =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/fluid.comp.spv")).unwrap();
+        assert_eq!(simulation.layout_bindings(0).len(), 8);
+        // The struct is padded to its 16-byte alignment; the range may exceed what's used.
+        assert_eq!(simulation.push_constant_size, Some(72));
+        assert!(std::mem::size_of::<FluidPushConstants>() >= 72);
+
+        let particles =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/fluid_particle.vert.spv"))
+                .unwrap();
+        assert_eq!(particles.layout_bindings(0).len(), 2);
+        assert_eq!(
+            particles.push_constant_size,
+            Some(std::mem::size_of::<ParticlePushConstants>() as u32)
+        );
+    }
+}
@@ -0,0 +1,397 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::{Mat4, Vec4};
+use std::sync::Arc;
+
+use crate::pipeline::{
+    VulkanComputePipeline, VulkanComputePipelineBuilder, VulkanPipeline, VulkanPipelineBuilder,
+};
+use crate::renderer::{BindlessTextures, GpuScene, frustum_planes, gpu_driven_pipeline_builder};
+use crate::vulkan::{
+    RecordCommands, VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanPhysicalDevice,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct GpuDrawStateId(pub(crate) u32);
+
+/// Pipeline and vertex buffer a group of objects is drawn with, the state a
+/// `VK_NV_device_generated_commands` token sequence would bind per draw.
+struct GpuDrawState {
+    pipeline: VulkanPipeline,
+    vertex_buffer: Option<vk::Buffer>,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GeneratePushConstants {
+    planes: [Vec4; 6],
+    object_count: u32,
+    state_count: u32,
+}
+
+/// Splits the command buffer into one contiguous segment per draw state, sized by how many
+/// objects use it. Returns `(first_command, capacity)` per state.
+pub fn command_segments(state_counts: &[u32]) -> Vec<(u32, u32)> {
+    let mut first = 0;
+    state_counts
+        .iter()
+        .map(|&count| {
+            let segment = (first, count);
+            first += count;
+            segment
+        })
+        .collect()
+}
+
+/// Emulates device generated commands with multi-draw indirect: a compute pass culls the
+/// scene and bins every visible object's draw into the segment of its draw state, so the GPU
+/// picks the pipeline and vertex buffer of each draw, not only its parameters. Recording then
+/// costs one state bind and one indirect draw per state, whatever the object count.
+///
+/// Objects choose their state with `GpuScene::set_draw_state`; state pipelines come from
+/// `pipeline_builder` so they share the scene layout. Requires
+/// `VulkanDeviceFeatures::gpu_driven`.
+pub struct GpuCommandGenerator {
+    generate_pipeline: VulkanComputePipeline,
+    scene_layout: VulkanDescriptorSetLayout,
+    _descriptor_pool: VulkanDescriptorPool,
+    scene_set: vk::DescriptorSet,
+    command_buffer: VulkanBuffer,
+    count_buffer: VulkanBuffer,
+    state_buffer: VulkanBuffer,
+    states: Vec<GpuDrawState>,
+    segments: Vec<(u32, u32)>,
+    max_states: u32,
+    draw_indirect_count: bool,
+    device: Arc<Device>,
+}
+
+impl GpuCommandGenerator {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        scene: &GpuScene,
+        max_states: u32,
+    ) -> Result<Self> {
+        if !device.features.gpu_driven() {
+            return Err(anyhow::anyhow!(
+                "Device doesn't support GPU-driven rendering"
+            ));
+        }
+
+        let max_draws = scene.limits.max_objects.max(1);
+        let max_states = max_states.max(1);
+
+        let command_buffer = VulkanBuffer::new(
+            device,
+            physical_device,
+            (max_draws as usize * std::mem::size_of::<vk::DrawIndexedIndirectCommand>())
+                as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::INDIRECT_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let count_buffer = VulkanBuffer::new(
+            device,
+            physical_device,
+            (max_states as usize * std::mem::size_of::<u32>()) as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::INDIRECT_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let state_buffer = VulkanBuffer::new_host_visible(
+            device,
+            physical_device,
+            (max_states as usize * std::mem::size_of::<u32>()) as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+        )?;
+
+        // Bindings 0..=3 match `GpuDrivenRenderer`, so `gpu_driven.vert` works unchanged.
+        let stages = vk::ShaderStageFlags::COMPUTE | vk::ShaderStageFlags::VERTEX;
+        let scene_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &(0..5)
+                .map(|binding| {
+                    VulkanDescriptorSetLayout::binding(
+                        binding,
+                        vk::DescriptorType::STORAGE_BUFFER,
+                        stages,
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(device, &scene_layout, 1)?;
+        let scene_set = descriptor_pool.allocate(&scene_layout)?;
+
+        let buffers = [
+            &scene.object_buffer,
+            &scene.mesh_buffer,
+            &command_buffer,
+            &count_buffer,
+            &state_buffer,
+        ];
+        for (binding, buffer) in buffers.into_iter().enumerate() {
+            descriptor_pool.write_buffer(
+                scene_set,
+                binding as u32,
+                vk::DescriptorType::STORAGE_BUFFER,
+                buffer,
+            );
+        }
+
+        let generate_pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/gpu_generate.comp.spv"), None)?
+            .with_descriptor_set_layout(scene_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(std::mem::size_of::<GeneratePushConstants>() as u32),
+            )
+            .build()?;
+
+        Ok(Self {
+            generate_pipeline,
+            scene_layout,
+            _descriptor_pool: descriptor_pool,
+            scene_set,
+            command_buffer,
+            count_buffer,
+            state_buffer,
+            states: Vec::new(),
+            segments: Vec::new(),
+            max_states,
+            draw_indirect_count: device.features.draw_indirect_count,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Builder preset for state pipelines, to customize (shaders, rasterization, blending)
+    /// before passing the result to `add_state`.
+    pub fn pipeline_builder(
+        &self,
+        device: &VulkanDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        textures: &BindlessTextures,
+    ) -> Result<VulkanPipelineBuilder> {
+        gpu_driven_pipeline_builder(device, render_pass, extent, &self.scene_layout, textures)
+    }
+
+    /// Registers a draw state. `vertex_buffer` replaces the scene's merged vertex buffer for its
+    /// objects and must share its layout, e.g. a copy with animated vertices.
+    pub fn add_state(
+        &mut self,
+        pipeline: VulkanPipeline,
+        vertex_buffer: Option<vk::Buffer>,
+    ) -> Result<GpuDrawStateId> {
+        if self.states.len() as u32 >= self.max_states {
+            return Err(anyhow::anyhow!(
+                "Draw state limit of {} reached",
+                self.max_states
+            ));
+        }
+
+        self.states.push(GpuDrawState {
+            pipeline,
+            vertex_buffer,
+        });
+
+        Ok(GpuDrawStateId(self.states.len() as u32 - 1))
+    }
+
+    pub fn state_count(&self) -> u32 {
+        self.states.len() as u32
+    }
+
+    /// Resizes the per-state segments to the objects using each state. Call after
+    /// `GpuScene::upload`; like it, no in-flight frame may still be reading the state table.
+    pub fn update(&mut self, scene: &GpuScene) -> Result<()> {
+        let segments = command_segments(&scene.draw_state_counts(self.states.len()));
+        if segments != self.segments {
+            let first_commands: Vec<u32> = segments.iter().map(|(first, _)| *first).collect();
+            self.state_buffer.write(0, &first_commands)?;
+            self.segments = segments;
+        }
+        Ok(())
+    }
+
+    /// Records culling and command generation. Must be recorded outside a render pass, before
+    /// `record_draw`.
+    pub fn record_generate(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        scene: &GpuScene,
+        view_proj: Mat4,
+    ) {
+        let object_count = scene.object_count().min(scene.limits.max_objects);
+
+        // The previous frame's draws may still be reading the buffers about to be cleared.
+        for buffer in [&self.command_buffer, &self.count_buffer] {
+            buffer.cmd_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::DRAW_INDIRECT | vk::PipelineStageFlags::VERTEX_SHADER,
+                vk::AccessFlags::INDIRECT_COMMAND_READ | vk::AccessFlags::SHADER_READ,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_WRITE,
+            );
+        }
+
+        unsafe {
+            self.device.cmd_fill_buffer(
+                command_buffer,
+                self.command_buffer.buffer,
+                0,
+                vk::WHOLE_SIZE,
+                0,
+            );
+            self.device.cmd_fill_buffer(
+                command_buffer,
+                self.count_buffer.buffer,
+                0,
+                vk::WHOLE_SIZE,
+                0,
+            );
+        }
+
+        for buffer in [&self.command_buffer, &self.count_buffer] {
+            buffer.cmd_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+            );
+        }
+
+        let push_constants = GeneratePushConstants {
+            planes: frustum_planes(view_proj),
+            object_count,
+            state_count: self.segments.len() as u32,
+        };
+
+        self.generate_pipeline.bind(command_buffer);
+        self.generate_pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.scene_set]);
+        self.generate_pipeline
+            .push_constants(command_buffer, &push_constants);
+        self.generate_pipeline
+            .dispatch(command_buffer, object_count.div_ceil(64).max(1), 1, 1);
+
+        for buffer in [&self.command_buffer, &self.count_buffer] {
+            buffer.cmd_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::SHADER_WRITE,
+                vk::PipelineStageFlags::DRAW_INDIRECT,
+                vk::AccessFlags::INDIRECT_COMMAND_READ,
+            );
+        }
+    }
+
+    /// Records one state bind and indirect draw per non-empty state inside a begun render pass
+    /// with viewport and scissor set.
+    pub fn record_draw(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        scene: &GpuScene,
+        textures: &BindlessTextures,
+        view_proj: Mat4,
+    ) {
+        let stride = std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32;
+
+        self.device.bind_index_buffer(
+            command_buffer,
+            scene.index_buffer.buffer,
+            0,
+            vk::IndexType::UINT32,
+        );
+
+        for (index, (state, &(first, capacity))) in
+            self.states.iter().zip(&self.segments).enumerate()
+        {
+            if capacity == 0 {
+                continue;
+            }
+
+            state.pipeline.bind(command_buffer);
+            state.pipeline.bind_descriptor_sets(
+                command_buffer,
+                0,
+                &[self.scene_set, textures.descriptor_set],
+            );
+            state.pipeline.push_constants(
+                command_buffer,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                &view_proj,
+            );
+
+            let offset = first as vk::DeviceSize * stride as vk::DeviceSize;
+            self.device.bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[state.vertex_buffer.unwrap_or(scene.vertex_buffer.buffer)],
+                &[0],
+            );
+
+            // Without a GPU count, unused slots of the segment were zeroed into empty draws.
+            if self.draw_indirect_count {
+                self.device.draw_indexed_indirect_count(
+                    command_buffer,
+                    self.command_buffer.buffer,
+                    offset,
+                    self.count_buffer.buffer,
+                    (index * std::mem::size_of::<u32>()) as vk::DeviceSize,
+                    capacity,
+                    stride,
+                );
+            } else {
+                self.device.draw_indexed_indirect(
+                    command_buffer,
+                    self.command_buffer.buffer,
+                    offset,
+                    capacity,
+                    stride,
+                );
+            }
+        }
+    }
+
+    pub fn scene_layout(&self) -> &VulkanDescriptorSetLayout {
+        &self.scene_layout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ShaderReflection;
+
+    #[test]
+    fn segments_are_contiguous_and_sized_by_object_count() {
+        assert_eq!(
+            command_segments(&[3, 0, 5, 1]),
+            [(0, 3), (3, 0), (3, 5), (8, 1)]
+        );
+        assert!(command_segments(&[]).is_empty());
+    }
+
+    #[test]
+    fn generate_push_constants_cover_shader_block() {
+        let reflection =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/gpu_generate.comp.spv"))
+                .unwrap();
+
+        // vec4 planes[6], uint object_count, uint state_count.
+        assert_eq!(reflection.push_constant_size, Some(104));
+        assert!(std::mem::size_of::<GeneratePushConstants>() >= 104);
+        assert_eq!(reflection.layout_bindings(0).len(), 5);
+    }
+}
@@ -0,0 +1,834 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use std::sync::Arc;
+
+use crate::geometry::MeshVertex;
+use crate::jobs::JobSystem;
+use crate::pipeline::{
+    VulkanComputePipeline, VulkanComputePipelineBuilder, VulkanPipeline, VulkanPipelineBuilder,
+};
+use crate::renderer::GpuDrawStateId;
+use crate::vulkan::{
+    BlasGeometry, RecordCommands, VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout,
+    VulkanDevice, VulkanPhysicalDevice,
+};
+
+/// Returns the six frustum planes (left, right, bottom, top, near, far) of a view-projection
+/// matrix with a [0, 1] depth range, normalized and pointing inwards.
+pub fn frustum_planes(view_proj: Mat4) -> [Vec4; 6] {
+    let rows = [
+        view_proj.row(0),
+        view_proj.row(1),
+        view_proj.row(2),
+        view_proj.row(3),
+    ];
+
+    [
+        rows[3] + rows[0],
+        rows[3] - rows[0],
+        rows[3] + rows[1],
+        rows[3] - rows[1],
+        rows[2],
+        rows[3] - rows[2],
+    ]
+    .map(|plane| plane / plane.truncate().length())
+}
+
+/// Returns the indices of the bounding spheres (xyz center, w radius) inside the frustum of
+/// `view_proj`, tested in parallel chunks on `jobs`. Matches the test in `gpu_cull.comp`.
+pub fn cull_spheres(jobs: &JobSystem, view_proj: Mat4, spheres: &[Vec4]) -> Result<Vec<u32>> {
+    let planes = frustum_planes(view_proj);
+    let visible = jobs.parallel_map("cull", spheres, 256, |sphere| {
+        planes
+            .iter()
+            .all(|plane| plane.truncate().dot(sphere.truncate()) + plane.w >= -sphere.w)
+    })?;
+
+    Ok(visible
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, visible)| visible.then_some(index as u32))
+        .collect())
+}
+
+/// A runtime-indexed array of sampled textures shared by every draw.
+///
+/// Requires `VulkanDeviceFeatures::bindless`. Slots are partially bound, so unused slots may
+/// stay empty, and updates are allowed while the set is bound by in-flight frames as long as
+/// those frames do not access the updated slot.
+pub struct BindlessTextures {
+    pub layout: VulkanDescriptorSetLayout,
+    pub descriptor_set: vk::DescriptorSet,
+    _descriptor_pool: VulkanDescriptorPool,
+    capacity: u32,
+    next_slot: u32,
+    free_slots: Vec<u32>,
+    device: Arc<Device>,
+}
+
+impl BindlessTextures {
+    pub fn new(device: &VulkanDevice, capacity: u32) -> Result<Self> {
+        if !device.features.bindless {
+            return Err(anyhow::anyhow!(
+                "Device doesn't support bindless descriptor indexing"
+            ));
+        }
+
+        let layout = VulkanDescriptorSetLayout::with_binding_flags(
+            device,
+            &[vk::DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(capacity)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)],
+            &[vk::DescriptorBindingFlags::PARTIALLY_BOUND
+                | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND],
+            vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL,
+        )?;
+
+        let descriptor_pool = VulkanDescriptorPool::with_flags(
+            device,
+            &layout.pool_sizes(1),
+            1,
+            vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND,
+        )?;
+        let descriptor_set = descriptor_pool.allocate(&layout)?;
+
+        Ok(Self {
+            layout,
+            descriptor_set,
+            _descriptor_pool: descriptor_pool,
+            capacity,
+            next_slot: 0,
+            free_slots: Vec::new(),
+            device: device.device.clone(),
+        })
+    }
+
+    /// Registers a texture in `SHADER_READ_ONLY_OPTIMAL` and returns its shader index.
+    pub fn add(&mut self, view: vk::ImageView, sampler: vk::Sampler) -> Result<u32> {
+        let slot = match self.free_slots.pop() {
+            Some(slot) => slot,
+            None if self.next_slot < self.capacity => {
+                self.next_slot += 1;
+                self.next_slot - 1
+            }
+            None => {
+                return Err(anyhow::anyhow!(
+                    "Bindless texture limit of {} reached",
+                    self.capacity
+                ));
+            }
+        };
+
+        self.write(slot, view, sampler);
+        Ok(slot)
+    }
+
+    /// Points an existing slot at another texture, e.g. after a reload.
+    pub fn replace(&self, slot: u32, view: vk::ImageView, sampler: vk::Sampler) {
+        self.write(slot, view, sampler);
+    }
+
+    /// Frees a slot for reuse. The caller must make sure no in-flight frame still samples it.
+    pub fn remove(&mut self, slot: u32) {
+        if slot < self.next_slot && !self.free_slots.contains(&slot) {
+            self.free_slots.push(slot);
+        }
+    }
+
+    fn write(&self, slot: u32, view: vk::ImageView, sampler: vk::Sampler) {
+        let image_info = [vk::DescriptorImageInfo::default()
+            .image_view(view)
+            .sampler(sampler)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)];
+
+        let write = vk::WriteDescriptorSet::default()
+            .dst_set(self.descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(slot)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&image_info);
+
+        unsafe {
+            self.device
+                .update_descriptor_sets(std::slice::from_ref(&write), &[]);
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GpuVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+impl MeshVertex for GpuVertex {
+    fn position(&self) -> Vec3 {
+        Vec3::from(self.position)
+    }
+
+    fn normal(&self) -> Vec3 {
+        Vec3::from(self.normal)
+    }
+
+    fn uv(&self) -> Vec2 {
+        Vec2::from(self.uv)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GpuMeshId(pub(crate) u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GpuObjectId(u32);
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GpuMesh {
+    first_index: u32,
+    index_count: u32,
+    vertex_offset: i32,
+    padding: u32,
+    bounds: Vec4,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GpuObject {
+    model: Mat4,
+    /// Mesh index, bindless texture index, draw state index, unused.
+    indices: [u32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CullPushConstants {
+    planes: [Vec4; 6],
+    object_count: u32,
+}
+
+/// Capacities of a `GpuScene`; every buffer is allocated up front.
+#[derive(Debug, Clone, Copy)]
+pub struct GpuSceneLimits {
+    pub max_objects: u32,
+    pub max_meshes: u32,
+    pub max_vertices: u32,
+    pub max_indices: u32,
+}
+
+impl Default for GpuSceneLimits {
+    fn default() -> Self {
+        Self {
+            max_objects: 131_072,
+            max_meshes: 1024,
+            max_vertices: 1 << 20,
+            max_indices: 1 << 22,
+        }
+    }
+}
+
+/// Scene data living in GPU buffers: one merged vertex and index buffer, a mesh table and a
+/// per-object table. Changes are kept on the CPU until `upload`.
+pub struct GpuScene {
+    pub vertex_buffer: VulkanBuffer,
+    pub index_buffer: VulkanBuffer,
+    pub(crate) mesh_buffer: VulkanBuffer,
+    pub(crate) object_buffer: VulkanBuffer,
+    pub(crate) limits: GpuSceneLimits,
+    vertex_count: u32,
+    index_count: u32,
+    meshes: Vec<GpuMesh>,
+    objects: Vec<GpuObject>,
+    meshes_dirty: bool,
+    objects_dirty: bool,
+}
+
+impl GpuScene {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        limits: GpuSceneLimits,
+    ) -> Result<Self> {
+        let buffer = |size: usize, usage: vk::BufferUsageFlags| {
+            VulkanBuffer::new_host_visible(
+                device,
+                physical_device,
+                size.max(1) as vk::DeviceSize,
+                usage,
+            )
+        };
+
+        Ok(Self {
+            vertex_buffer: buffer(
+                limits.max_vertices as usize * std::mem::size_of::<GpuVertex>(),
+                vk::BufferUsageFlags::VERTEX_BUFFER
+                    | vk::BufferUsageFlags::STORAGE_BUFFER
+                    | device.features.acceleration_structure_input_usage(),
+            )?,
+            index_buffer: buffer(
+                limits.max_indices as usize * std::mem::size_of::<u32>(),
+                vk::BufferUsageFlags::INDEX_BUFFER
+                    | vk::BufferUsageFlags::STORAGE_BUFFER
+                    | device.features.acceleration_structure_input_usage(),
+            )?,
+            mesh_buffer: buffer(
+                limits.max_meshes as usize * std::mem::size_of::<GpuMesh>(),
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+            )?,
+            object_buffer: buffer(
+                limits.max_objects as usize * std::mem::size_of::<GpuObject>(),
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+            )?,
+            limits,
+            vertex_count: 0,
+            index_count: 0,
+            meshes: Vec::new(),
+            objects: Vec::new(),
+            meshes_dirty: false,
+            objects_dirty: false,
+        })
+    }
+
+    /// Appends a mesh to the merged geometry buffers. Geometry is written immediately, so this
+    /// must not race with in-flight frames reading the appended range (it never overlaps
+    /// existing meshes).
+    pub fn add_mesh(&mut self, vertices: &[GpuVertex], indices: &[u32]) -> Result<GpuMeshId> {
+        if self.meshes.len() as u32 >= self.limits.max_meshes
+            || self.vertex_count as usize + vertices.len() > self.limits.max_vertices as usize
+            || self.index_count as usize + indices.len() > self.limits.max_indices as usize
+        {
+            return Err(anyhow::anyhow!("GPU scene geometry limits exceeded"));
+        }
+
+        self.vertex_buffer.write(
+            self.vertex_count as vk::DeviceSize
+                * std::mem::size_of::<GpuVertex>() as vk::DeviceSize,
+            vertices,
+        )?;
+        self.index_buffer.write(
+            self.index_count as vk::DeviceSize * std::mem::size_of::<u32>() as vk::DeviceSize,
+            indices,
+        )?;
+
+        let (min, max) = vertices.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), v| {
+                let p = Vec3::from(v.position);
+                (min.min(p), max.max(p))
+            },
+        );
+        let center = if vertices.is_empty() {
+            Vec3::ZERO
+        } else {
+            (min + max) * 0.5
+        };
+        let radius = vertices
+            .iter()
+            .map(|v| Vec3::from(v.position).distance(center))
+            .fold(0.0, f32::max);
+
+        self.meshes.push(GpuMesh {
+            first_index: self.index_count,
+            index_count: indices.len() as u32,
+            vertex_offset: self.vertex_count as i32,
+            padding: 0,
+            bounds: center.extend(radius),
+        });
+
+        self.vertex_count += vertices.len() as u32;
+        self.index_count += indices.len() as u32;
+        self.meshes_dirty = true;
+
+        Ok(GpuMeshId(self.meshes.len() as u32 - 1))
+    }
+
+    pub fn add_object(
+        &mut self,
+        mesh: GpuMeshId,
+        transform: Mat4,
+        texture_index: u32,
+    ) -> Result<GpuObjectId> {
+        if self.objects.len() as u32 >= self.limits.max_objects {
+            return Err(anyhow::anyhow!(
+                "GPU scene object limit of {} reached",
+                self.limits.max_objects
+            ));
+        }
+
+        self.objects.push(GpuObject {
+            model: transform,
+            indices: [mesh.0, texture_index, 0, 0],
+        });
+        self.objects_dirty = true;
+
+        Ok(GpuObjectId(self.objects.len() as u32 - 1))
+    }
+
+    pub fn set_transform(&mut self, object: GpuObjectId, transform: Mat4) {
+        if let Some(gpu_object) = self.objects.get_mut(object.0 as usize) {
+            gpu_object.model = transform;
+            self.objects_dirty = true;
+        }
+    }
+
+    pub fn set_texture(&mut self, object: GpuObjectId, texture_index: u32) {
+        if let Some(gpu_object) = self.objects.get_mut(object.0 as usize) {
+            gpu_object.indices[1] = texture_index;
+            self.objects_dirty = true;
+        }
+    }
+
+    /// Selects the pipeline and vertex buffer `GpuCommandGenerator` draws the object with.
+    /// Objects start in state 0.
+    pub fn set_draw_state(&mut self, object: GpuObjectId, state: GpuDrawStateId) {
+        if let Some(gpu_object) = self.objects.get_mut(object.0 as usize) {
+            gpu_object.indices[2] = state.0;
+            self.objects_dirty = true;
+        }
+    }
+
+    /// Number of objects in each of the first `state_count` draw states.
+    pub(crate) fn draw_state_counts(&self, state_count: usize) -> Vec<u32> {
+        let mut counts = vec![0; state_count];
+        for object in &self.objects {
+            if let Some(count) = counts.get_mut(object.indices[2] as usize) {
+                *count += 1;
+            }
+        }
+        counts
+    }
+
+    /// Writes pending mesh and object changes. The caller must make sure no in-flight frame is
+    /// still reading the tables.
+    pub fn upload(&mut self) -> Result<()> {
+        if self.meshes_dirty {
+            self.mesh_buffer.write(0, &self.meshes)?;
+            self.meshes_dirty = false;
+        }
+
+        if self.objects_dirty {
+            self.object_buffer.write(0, &self.objects)?;
+            self.objects_dirty = false;
+        }
+
+        Ok(())
+    }
+
+    pub fn object_count(&self) -> u32 {
+        self.objects.len() as u32
+    }
+
+    /// Removes every object, keeping meshes; ids from `add_object` become invalid.
+    pub fn clear_objects(&mut self) {
+        self.objects.clear();
+        self.objects_dirty = true;
+    }
+
+    /// CPU frustum culling of every object, e.g. for CPU-side draw lists or to check the GPU
+    /// results.
+    pub fn cull_visible(&self, jobs: &JobSystem, view_proj: Mat4) -> Result<Vec<GpuObjectId>> {
+        let spheres: Vec<Vec4> = self
+            .objects
+            .iter()
+            .map(|object| {
+                let bounds = self.meshes[object.indices[0] as usize].bounds;
+                let center = object.model.transform_point3(bounds.truncate());
+                let scale = object
+                    .model
+                    .x_axis
+                    .truncate()
+                    .length()
+                    .max(object.model.y_axis.truncate().length())
+                    .max(object.model.z_axis.truncate().length());
+                center.extend(bounds.w * scale)
+            })
+            .collect();
+
+        Ok(cull_spheres(jobs, view_proj, &spheres)?
+            .into_iter()
+            .map(GpuObjectId)
+            .collect())
+    }
+
+    pub fn mesh_count(&self) -> u32 {
+        self.meshes.len() as u32
+    }
+
+    /// Triangles of `mesh` in the merged buffers, to build its BLAS from. Requires
+    /// `VulkanDeviceFeatures::acceleration_structure`.
+    pub fn blas_geometry(&self, mesh: GpuMeshId) -> Option<BlasGeometry> {
+        let index = mesh.0 as usize;
+        let gpu_mesh = self.meshes.get(index)?;
+        let end_vertex = self
+            .meshes
+            .get(index + 1)
+            .map_or(self.vertex_count as i32, |next| next.vertex_offset);
+
+        Some(BlasGeometry {
+            vertex_address: self.vertex_buffer.device_address(),
+            vertex_stride: std::mem::size_of::<GpuVertex>() as vk::DeviceSize,
+            max_vertex: (end_vertex - gpu_mesh.vertex_offset).max(1) as u32 - 1,
+            index_address: self.index_buffer.device_address(),
+            first_index: gpu_mesh.first_index,
+            triangle_count: gpu_mesh.index_count / 3,
+            first_vertex: gpu_mesh.vertex_offset as u32,
+        })
+    }
+
+    /// Mesh and transform of every object, in object order.
+    pub(crate) fn object_meshes(&self) -> impl Iterator<Item = (GpuMeshId, Mat4)> + '_ {
+        self.objects
+            .iter()
+            .map(|object| (GpuMeshId(object.indices[0]), object.model))
+    }
+}
+
+/// Graphics pipeline setup shared by the GPU-driven draws: `gpu_driven.vert/.frag`, the scene
+/// and bindless texture sets, a view-projection push constant and `GpuVertex` input.
+pub(crate) fn gpu_driven_pipeline_builder(
+    device: &VulkanDevice,
+    render_pass: vk::RenderPass,
+    extent: vk::Extent2D,
+    scene_layout: &VulkanDescriptorSetLayout,
+    textures: &BindlessTextures,
+) -> Result<VulkanPipelineBuilder> {
+    let mut pipeline_builder = VulkanPipelineBuilder::new(device)
+        .set_render_pass(render_pass)
+        .set_extent(extent)
+        .with_vertex_spv(include_bytes!("../../bin/gpu_driven.vert.spv"))?
+        .with_fragment_spv(include_bytes!("../../bin/gpu_driven.frag.spv"))?
+        .with_descriptor_set_layout(scene_layout.layout)
+        .with_descriptor_set_layout(textures.layout.layout)
+        .with_push_constant_range(
+            vk::PushConstantRange::default()
+                .stage_flags(vk::ShaderStageFlags::VERTEX)
+                .offset(0)
+                .size(std::mem::size_of::<Mat4>() as u32),
+        )
+        .with_vertex_binding(
+            vk::VertexInputBindingDescription::default()
+                .binding(0)
+                .stride(std::mem::size_of::<GpuVertex>() as u32)
+                .input_rate(vk::VertexInputRate::VERTEX),
+        )
+        .with_depth_test(true, true, vk::CompareOp::LESS)
+        .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]);
+
+    for (location, format, offset) in [
+        (0, vk::Format::R32G32B32_SFLOAT, 0),
+        (1, vk::Format::R32G32B32_SFLOAT, 12),
+        (2, vk::Format::R32G32_SFLOAT, 24),
+    ] {
+        pipeline_builder = pipeline_builder.with_vertex_attribute(
+            vk::VertexInputAttributeDescription::default()
+                .binding(0)
+                .location(location)
+                .format(format)
+                .offset(offset),
+        );
+    }
+
+    Ok(pipeline_builder)
+}
+
+/// GPU-driven backend: frustum culling in compute writes compacted indexed draw commands, and
+/// the whole scene is drawn with a single multi-draw indirect call using bindless textures.
+///
+/// Requires `VulkanDeviceFeatures::gpu_driven`. The draw count is read on the GPU when
+/// `draw_indirect_count` is available; otherwise unused command slots are zeroed and drawn as
+/// empty draws.
+pub struct GpuDrivenRenderer {
+    pub pipeline: VulkanPipeline,
+    cull_pipeline: VulkanComputePipeline,
+    scene_layout: VulkanDescriptorSetLayout,
+    _descriptor_pool: VulkanDescriptorPool,
+    scene_set: vk::DescriptorSet,
+    command_buffer: VulkanBuffer,
+    count_buffer: VulkanBuffer,
+    max_draws: u32,
+    draw_indirect_count: bool,
+    device: Arc<Device>,
+}
+
+impl GpuDrivenRenderer {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        scene: &GpuScene,
+        textures: &BindlessTextures,
+    ) -> Result<Self> {
+        if !device.features.gpu_driven() {
+            return Err(anyhow::anyhow!(
+                "Device doesn't support GPU-driven rendering"
+            ));
+        }
+
+        let max_draws = scene.limits.max_objects;
+
+        let command_buffer = VulkanBuffer::new(
+            device,
+            physical_device,
+            (max_draws.max(1) as usize * std::mem::size_of::<vk::DrawIndexedIndirectCommand>())
+                as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::INDIRECT_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let count_buffer = VulkanBuffer::new(
+            device,
+            physical_device,
+            std::mem::size_of::<u32>() as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::INDIRECT_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let stages = vk::ShaderStageFlags::COMPUTE | vk::ShaderStageFlags::VERTEX;
+        let scene_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[
+                VulkanDescriptorSetLayout::binding(0, vk::DescriptorType::STORAGE_BUFFER, stages),
+                VulkanDescriptorSetLayout::binding(1, vk::DescriptorType::STORAGE_BUFFER, stages),
+                VulkanDescriptorSetLayout::binding(2, vk::DescriptorType::STORAGE_BUFFER, stages),
+                VulkanDescriptorSetLayout::binding(3, vk::DescriptorType::STORAGE_BUFFER, stages),
+            ],
+        )?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(device, &scene_layout, 1)?;
+        let scene_set = descriptor_pool.allocate(&scene_layout)?;
+
+        let buffers = [
+            &scene.object_buffer,
+            &scene.mesh_buffer,
+            &command_buffer,
+            &count_buffer,
+        ];
+        for (binding, buffer) in buffers.into_iter().enumerate() {
+            descriptor_pool.write_buffer(
+                scene_set,
+                binding as u32,
+                vk::DescriptorType::STORAGE_BUFFER,
+                buffer,
+            );
+        }
+
+        let cull_pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/gpu_cull.comp.spv"), None)?
+            .with_descriptor_set_layout(scene_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(std::mem::size_of::<CullPushConstants>() as u32),
+            )
+            .build()?;
+
+        let pipeline =
+            gpu_driven_pipeline_builder(device, render_pass, extent, &scene_layout, textures)?
+                .build()?;
+
+        Ok(Self {
+            pipeline,
+            cull_pipeline,
+            scene_layout,
+            _descriptor_pool: descriptor_pool,
+            scene_set,
+            command_buffer,
+            count_buffer,
+            max_draws,
+            draw_indirect_count: device.features.draw_indirect_count,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Records culling. Must be recorded outside a render pass, before `record_draw`.
+    pub fn record_cull(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        scene: &GpuScene,
+        view_proj: Mat4,
+    ) {
+        let object_count = scene.object_count().min(self.max_draws);
+
+        // The previous frame's draw may still be reading the buffers about to be cleared.
+        for buffer in [&self.command_buffer, &self.count_buffer] {
+            buffer.cmd_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::DRAW_INDIRECT | vk::PipelineStageFlags::VERTEX_SHADER,
+                vk::AccessFlags::INDIRECT_COMMAND_READ | vk::AccessFlags::SHADER_READ,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_WRITE,
+            );
+        }
+
+        unsafe {
+            self.device.cmd_fill_buffer(
+                command_buffer,
+                self.command_buffer.buffer,
+                0,
+                vk::WHOLE_SIZE,
+                0,
+            );
+            self.device.cmd_fill_buffer(
+                command_buffer,
+                self.count_buffer.buffer,
+                0,
+                vk::WHOLE_SIZE,
+                0,
+            );
+        }
+
+        for buffer in [&self.command_buffer, &self.count_buffer] {
+            buffer.cmd_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+            );
+        }
+
+        let push_constants = CullPushConstants {
+            planes: frustum_planes(view_proj),
+            object_count,
+        };
+
+        self.cull_pipeline.bind(command_buffer);
+        self.cull_pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.scene_set]);
+        self.cull_pipeline
+            .push_constants(command_buffer, &push_constants);
+        self.cull_pipeline
+            .dispatch(command_buffer, object_count.div_ceil(64).max(1), 1, 1);
+
+        for buffer in [&self.command_buffer, &self.count_buffer] {
+            buffer.cmd_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::SHADER_WRITE,
+                vk::PipelineStageFlags::DRAW_INDIRECT,
+                vk::AccessFlags::INDIRECT_COMMAND_READ,
+            );
+        }
+    }
+
+    /// Records the indirect draw of every visible object inside a begun render pass with
+    /// viewport and scissor set.
+    pub fn record_draw(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        scene: &GpuScene,
+        textures: &BindlessTextures,
+        view_proj: Mat4,
+    ) {
+        let object_count = scene.object_count().min(self.max_draws);
+        if object_count == 0 {
+            return;
+        }
+
+        let stride = std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32;
+
+        self.pipeline.bind(command_buffer);
+        self.pipeline.bind_descriptor_sets(
+            command_buffer,
+            0,
+            &[self.scene_set, textures.descriptor_set],
+        );
+        self.pipeline
+            .push_constants(command_buffer, vk::ShaderStageFlags::VERTEX, 0, &view_proj);
+
+        self.device
+            .bind_vertex_buffers(command_buffer, 0, &[scene.vertex_buffer.buffer], &[0]);
+        self.device.bind_index_buffer(
+            command_buffer,
+            scene.index_buffer.buffer,
+            0,
+            vk::IndexType::UINT32,
+        );
+
+        if self.draw_indirect_count {
+            self.device.draw_indexed_indirect_count(
+                command_buffer,
+                self.command_buffer.buffer,
+                0,
+                self.count_buffer.buffer,
+                0,
+                object_count,
+                stride,
+            );
+        } else {
+            self.device.draw_indexed_indirect(
+                command_buffer,
+                self.command_buffer.buffer,
+                0,
+                object_count,
+                stride,
+            );
+        }
+    }
+
+    pub fn scene_layout(&self) -> &VulkanDescriptorSetLayout {
+        &self.scene_layout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cull_spheres_keeps_visible_objects() {
+        let jobs = JobSystem::new(2).unwrap();
+        let projection = Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, 0.1, 100.0);
+        let view = Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+
+        let spheres = [
+            Vec4::new(0.0, 0.0, -10.0, 1.0),
+            Vec4::new(0.0, 0.0, 10.0, 1.0),
+            Vec4::new(50.0, 0.0, -10.0, 1.0),
+            Vec4::new(10.5, 0.0, -10.0, 1.0),
+            Vec4::new(0.0, 0.0, -200.0, 1.0),
+        ];
+
+        assert_eq!(
+            cull_spheres(&jobs, projection * view, &spheres).unwrap(),
+            [0, 3]
+        );
+    }
+
+    #[test]
+    fn cull_spheres_matches_serial_results() {
+        let jobs = JobSystem::new(3).unwrap();
+        let view_proj = Mat4::perspective_rh(1.0, 1.5, 0.1, 50.0)
+            * Mat4::look_at_rh(Vec3::new(3.0, 2.0, 5.0), Vec3::ZERO, Vec3::Y);
+        let spheres: Vec<Vec4> = (0..2000)
+            .map(|i| {
+                let t = i as f32 * 0.37;
+                Vec4::new(t.sin() * 40.0, t.cos() * 20.0, (t * 0.5).sin() * 60.0, 0.5)
+            })
+            .collect();
+
+        let planes = frustum_planes(view_proj);
+        let expected: Vec<u32> = spheres
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| {
+                planes
+                    .iter()
+                    .all(|p| p.truncate().dot(s.truncate()) + p.w >= -s.w)
+            })
+            .map(|(i, _)| i as u32)
+            .collect();
+
+        assert_eq!(cull_spheres(&jobs, view_proj, &spheres).unwrap(), expected);
+    }
+}
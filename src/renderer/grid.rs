@@ -0,0 +1,161 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::{Mat4, Vec3, Vec4};
+use std::sync::Arc;
+
+use crate::pipeline::{VulkanPipeline, VulkanPipelineBuilder};
+use crate::vulkan::{RecordCommands, VulkanDevice};
+
+/// Appearance of the editor ground grid on the `y = 0` plane.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridSettings {
+    pub enabled: bool,
+    /// World-space spacing of minor lines.
+    pub cell_size: f32,
+    /// Minor cells between major lines.
+    pub major_every: u32,
+    /// Distance from the camera, along the plane, at which the grid has faded out.
+    pub fade_distance: f32,
+    /// Line width in pixels.
+    pub line_width: f32,
+    pub minor_color: Vec4,
+    pub major_color: Vec4,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cell_size: 1.0,
+            major_every: 10,
+            fade_distance: 100.0,
+            line_width: 1.0,
+            minor_color: Vec4::new(0.5, 0.5, 0.5, 0.35),
+            major_color: Vec4::new(0.7, 0.7, 0.7, 0.6),
+        }
+    }
+}
+
+/// Mirrors the `Grid` push constant block in `shaders/grid.glsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GridPushConstants {
+    view_proj: Mat4,
+    camera: Vec4,
+    params: Vec4,
+    minor_color: Vec4,
+    major_color: Vec4,
+}
+
+impl GridPushConstants {
+    fn new(settings: &GridSettings, view_proj: Mat4, camera_position: Vec3) -> Self {
+        Self {
+            view_proj,
+            camera: camera_position.extend(settings.fade_distance.max(f32::EPSILON)),
+            params: Vec4::new(
+                settings.cell_size.max(f32::EPSILON),
+                settings.major_every.max(1) as f32,
+                settings.line_width,
+                0.0,
+            ),
+            minor_color: settings.minor_color,
+            major_color: settings.major_color,
+        }
+    }
+}
+
+/// Infinite ground grid drawn with a single full-screen triangle: each pixel intersects its
+/// view ray with the `y = 0` plane and writes the hit's depth, so scene geometry in the same
+/// pass occludes the grid. Minor and major lines are antialiased in screen space, the world X
+/// and Z axes are colored, and the grid fades out with distance from the camera.
+pub struct InfiniteGrid {
+    pipeline: VulkanPipeline,
+    pub settings: GridSettings,
+    device: Arc<Device>,
+}
+
+impl InfiniteGrid {
+    /// `render_pass` needs a depth attachment for the grid to be occluded; without one the grid
+    /// is drawn over everything before it.
+    pub fn new(
+        device: &VulkanDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        settings: GridSettings,
+    ) -> Result<Self> {
+        let pipeline = VulkanPipelineBuilder::new(device)
+            .set_render_pass(render_pass)
+            .set_extent(extent)
+            .with_vertex_spv(include_bytes!("../../bin/grid.vert.spv"))?
+            .with_fragment_spv(include_bytes!("../../bin/grid.frag.spv"))?
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+                    .offset(0)
+                    .size(std::mem::size_of::<GridPushConstants>() as u32),
+            )
+            .with_depth_test(true, false, vk::CompareOp::LESS_OR_EQUAL)
+            .with_cull_mode(vk::CullModeFlags::NONE)
+            .with_alpha_blending()
+            .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .build()?;
+
+        Ok(Self {
+            pipeline,
+            settings,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Draws the grid inside the current render pass unless `settings.enabled` is off. Record
+    /// it after opaque geometry so the grid blends over the cleared background only where
+    /// nothing is in front of it.
+    pub fn draw(&self, command_buffer: vk::CommandBuffer, view_proj: Mat4, camera_position: Vec3) {
+        if !self.settings.enabled {
+            return;
+        }
+
+        self.pipeline.bind(command_buffer);
+        self.pipeline.push_constants(
+            command_buffer,
+            vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+            0,
+            &GridPushConstants::new(&self.settings, view_proj, camera_position),
+        );
+        self.device.draw(command_buffer, 3, 1, 0, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ShaderReflection;
+
+    #[test]
+    fn shader_layout_matches_grid_push_constants() {
+        let size = Some(std::mem::size_of::<GridPushConstants>() as u32);
+        for spirv in [
+            include_bytes!("../../bin/grid.vert.spv").as_slice(),
+            include_bytes!("../../bin/grid.frag.spv").as_slice(),
+        ] {
+            let reflection = ShaderReflection::from_spirv(spirv).unwrap();
+            assert_eq!(reflection.push_constant_size, size);
+        }
+    }
+
+    #[test]
+    fn push_constants_clamp_degenerate_settings() {
+        let settings = GridSettings {
+            cell_size: 0.0,
+            major_every: 0,
+            fade_distance: -1.0,
+            ..Default::default()
+        };
+        let constants = GridPushConstants::new(&settings, Mat4::IDENTITY, Vec3::new(1.0, 2.0, 3.0));
+
+        assert_eq!(constants.camera.truncate(), Vec3::new(1.0, 2.0, 3.0));
+        assert!(constants.camera.w > 0.0);
+        assert!(constants.params.x > 0.0);
+        assert_eq!(constants.params.y, 1.0);
+    }
+}
@@ -0,0 +1,350 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::{Mat4, Vec3, Vec4};
+use std::sync::Arc;
+
+use crate::geometry::MeshletMesh;
+use crate::pipeline::{
+    VulkanComputePipeline, VulkanComputePipelineBuilder, VulkanPipeline, VulkanPipelineBuilder,
+};
+use crate::renderer::{GpuVertex, frustum_planes};
+use crate::vulkan::{
+    RecordCommands, VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanPhysicalDevice,
+};
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GpuMeshletBounds {
+    sphere: Vec4,
+    cone: Vec4,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MeshletCullPushConstants {
+    planes: [Vec4; 6],
+    camera_position: Vec4,
+    meshlet_count: u32,
+    cone_culling: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MeshletDrawPushConstants {
+    mvp: Mat4,
+    model: Mat4,
+}
+
+/// Compute-based meshlet culling with vertex pulling, for devices without mesh shaders.
+///
+/// A compute pass tests each meshlet's bounding sphere against the frustum and its normal cone
+/// against the camera, then writes one non-indexed indirect draw per visible meshlet. The
+/// vertex shader fetches meshlet vertices and triangles from storage buffers.
+///
+/// Each renderer owns a single indirect command buffer and count buffer, so it draws one mesh
+/// instance per `record_cull`/`record_draw` pair; create one renderer per instance to draw
+/// several in the same frame.
+pub struct MeshletRenderer {
+    pub pipeline: VulkanPipeline,
+    pub cone_culling: bool,
+    cull_pipeline: VulkanComputePipeline,
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    _descriptor_pool: VulkanDescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    _buffers: Vec<VulkanBuffer>,
+    command_buffer: VulkanBuffer,
+    count_buffer: VulkanBuffer,
+    meshlet_count: u32,
+    draw_indirect_count: bool,
+    device: Arc<Device>,
+}
+
+impl MeshletRenderer {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        mesh: &MeshletMesh,
+        vertices: &[GpuVertex],
+    ) -> Result<Self> {
+        if !device.features.multi_draw_indirect {
+            return Err(anyhow::anyhow!(
+                "Device doesn't support multi-draw indirect"
+            ));
+        }
+
+        let meshlet_count = mesh.meshlets.len() as u32;
+
+        let storage = |data_len: usize| {
+            VulkanBuffer::new_host_visible(
+                device,
+                physical_device,
+                data_len.max(4) as vk::DeviceSize,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+            )
+        };
+
+        let meshlets: Vec<[u32; 4]> = mesh
+            .meshlets
+            .iter()
+            .map(|m| {
+                [
+                    m.vertex_offset,
+                    m.vertex_count,
+                    m.triangle_offset,
+                    m.triangle_count,
+                ]
+            })
+            .collect();
+        let bounds: Vec<GpuMeshletBounds> = mesh
+            .bounds
+            .iter()
+            .map(|b| GpuMeshletBounds {
+                sphere: b.center.extend(b.radius),
+                cone: b.cone_axis.extend(b.cone_cutoff),
+            })
+            .collect();
+        let triangles = mesh.packed_triangles();
+
+        let meshlet_buffer = storage(std::mem::size_of_val(meshlets.as_slice()))?;
+        meshlet_buffer.write(0, &meshlets)?;
+        let bounds_buffer = storage(std::mem::size_of_val(bounds.as_slice()))?;
+        bounds_buffer.write(0, &bounds)?;
+        let meshlet_vertex_buffer = storage(std::mem::size_of_val(mesh.vertices.as_slice()))?;
+        meshlet_vertex_buffer.write(0, &mesh.vertices)?;
+        let triangle_buffer = storage(std::mem::size_of_val(triangles.as_slice()))?;
+        triangle_buffer.write(0, &triangles)?;
+        let vertex_buffer = storage(std::mem::size_of_val(vertices))?;
+        vertex_buffer.write(0, vertices)?;
+
+        let indirect_usage = vk::BufferUsageFlags::STORAGE_BUFFER
+            | vk::BufferUsageFlags::INDIRECT_BUFFER
+            | vk::BufferUsageFlags::TRANSFER_DST;
+        let command_buffer = VulkanBuffer::new(
+            device,
+            physical_device,
+            (meshlet_count.max(1) as usize * std::mem::size_of::<vk::DrawIndirectCommand>())
+                as vk::DeviceSize,
+            indirect_usage,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+        let count_buffer = VulkanBuffer::new(
+            device,
+            physical_device,
+            std::mem::size_of::<u32>() as vk::DeviceSize,
+            indirect_usage,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let stages = vk::ShaderStageFlags::COMPUTE | vk::ShaderStageFlags::VERTEX;
+        let bindings: Vec<_> = (0..7)
+            .map(|binding| {
+                VulkanDescriptorSetLayout::binding(
+                    binding,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    stages,
+                )
+            })
+            .collect();
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(device, &bindings)?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(device, &descriptor_set_layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&descriptor_set_layout)?;
+
+        let set_buffers = [
+            &meshlet_buffer,
+            &bounds_buffer,
+            &command_buffer,
+            &count_buffer,
+            &meshlet_vertex_buffer,
+            &triangle_buffer,
+            &vertex_buffer,
+        ];
+        for (binding, buffer) in set_buffers.into_iter().enumerate() {
+            descriptor_pool.write_buffer(
+                descriptor_set,
+                binding as u32,
+                vk::DescriptorType::STORAGE_BUFFER,
+                buffer,
+            );
+        }
+
+        let cull_pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/meshlet_cull.comp.spv"), None)?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(std::mem::size_of::<MeshletCullPushConstants>() as u32),
+            )
+            .build()?;
+
+        let pipeline = VulkanPipelineBuilder::new(device)
+            .set_render_pass(render_pass)
+            .set_extent(extent)
+            .with_vertex_spv(include_bytes!("../../bin/meshlet.vert.spv"))?
+            .with_fragment_spv(include_bytes!("../../bin/meshlet.frag.spv"))?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::VERTEX)
+                    .offset(0)
+                    .size(std::mem::size_of::<MeshletDrawPushConstants>() as u32),
+            )
+            .with_depth_test(true, true, vk::CompareOp::LESS)
+            .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .build()?;
+
+        Ok(Self {
+            pipeline,
+            cone_culling: true,
+            cull_pipeline,
+            descriptor_set_layout,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            _buffers: vec![
+                meshlet_buffer,
+                bounds_buffer,
+                meshlet_vertex_buffer,
+                triangle_buffer,
+                vertex_buffer,
+            ],
+            command_buffer,
+            count_buffer,
+            meshlet_count,
+            draw_indirect_count: device.features.draw_indirect_count,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Records meshlet culling for one instance of the mesh. Must be recorded outside a render
+    /// pass, before `record_draw`.
+    pub fn record_cull(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        model: Mat4,
+        view_proj: Mat4,
+        camera_position: Vec3,
+    ) {
+        // The previous frame's draw may still be reading the buffers about to be cleared.
+        for buffer in [&self.command_buffer, &self.count_buffer] {
+            buffer.cmd_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::DRAW_INDIRECT | vk::PipelineStageFlags::VERTEX_SHADER,
+                vk::AccessFlags::INDIRECT_COMMAND_READ | vk::AccessFlags::SHADER_READ,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_WRITE,
+            );
+        }
+
+        unsafe {
+            self.device.cmd_fill_buffer(
+                command_buffer,
+                self.command_buffer.buffer,
+                0,
+                vk::WHOLE_SIZE,
+                0,
+            );
+            self.device.cmd_fill_buffer(
+                command_buffer,
+                self.count_buffer.buffer,
+                0,
+                vk::WHOLE_SIZE,
+                0,
+            );
+        }
+
+        for buffer in [&self.command_buffer, &self.count_buffer] {
+            buffer.cmd_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+            );
+        }
+
+        // Planes of view_proj * model are the world planes expressed in object space.
+        let push_constants = MeshletCullPushConstants {
+            planes: frustum_planes(view_proj * model),
+            camera_position: model
+                .inverse()
+                .transform_point3(camera_position)
+                .extend(1.0),
+            meshlet_count: self.meshlet_count,
+            cone_culling: self.cone_culling as u32,
+        };
+
+        self.cull_pipeline.bind(command_buffer);
+        self.cull_pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.descriptor_set]);
+        self.cull_pipeline
+            .push_constants(command_buffer, &push_constants);
+        self.cull_pipeline
+            .dispatch(command_buffer, self.meshlet_count.div_ceil(64).max(1), 1, 1);
+
+        for buffer in [&self.command_buffer, &self.count_buffer] {
+            buffer.cmd_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::SHADER_WRITE,
+                vk::PipelineStageFlags::DRAW_INDIRECT,
+                vk::AccessFlags::INDIRECT_COMMAND_READ,
+            );
+        }
+    }
+
+    /// Records the visible meshlets inside a begun render pass with viewport and scissor set.
+    pub fn record_draw(&self, command_buffer: vk::CommandBuffer, model: Mat4, view_proj: Mat4) {
+        if self.meshlet_count == 0 {
+            return;
+        }
+
+        let push_constants = MeshletDrawPushConstants {
+            mvp: view_proj * model,
+            model,
+        };
+        let stride = std::mem::size_of::<vk::DrawIndirectCommand>() as u32;
+
+        self.pipeline.bind(command_buffer);
+        self.pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.descriptor_set]);
+        self.pipeline.push_constants(
+            command_buffer,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            &push_constants,
+        );
+
+        if self.draw_indirect_count {
+            self.device.draw_indirect_count(
+                command_buffer,
+                self.command_buffer.buffer,
+                0,
+                self.count_buffer.buffer,
+                0,
+                self.meshlet_count,
+                stride,
+            );
+        } else {
+            self.device.draw_indirect(
+                command_buffer,
+                self.command_buffer.buffer,
+                0,
+                self.meshlet_count,
+                stride,
+            );
+        }
+    }
+
+    pub fn meshlet_count(&self) -> u32 {
+        self.meshlet_count
+    }
+
+    pub fn descriptor_set_layout(&self) -> &VulkanDescriptorSetLayout {
+        &self.descriptor_set_layout
+    }
+}
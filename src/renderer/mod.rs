@@ -1,3 +1,27 @@
+pub mod audio;
+pub mod cloth;
+pub mod debug_draw;
+pub mod fluid;
+pub mod generated_commands;
+pub mod gpu_driven;
+pub mod grid;
+pub mod meshlet_renderer;
+pub mod morph;
+pub mod path_tracer;
+pub mod present_thread;
 pub mod renderer;
+pub mod skinning;
 
-pub use renderer::*;
\ No newline at end of file
+pub use audio::*;
+pub use cloth::*;
+pub use debug_draw::*;
+pub use fluid::*;
+pub use generated_commands::*;
+pub use gpu_driven::*;
+pub use grid::*;
+pub use meshlet_renderer::*;
+pub use morph::*;
+pub use path_tracer::*;
+pub use present_thread::*;
+pub use renderer::*;
+pub use skinning::*;
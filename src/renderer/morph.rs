@@ -0,0 +1,477 @@
+use anyhow::Result;
+use ash::vk;
+use glam::{Vec3, Vec4};
+
+use crate::pipeline::{VulkanComputePipeline, VulkanComputePipelineBuilder};
+use crate::renderer::GpuVertex;
+use crate::vulkan::{
+    VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanPhysicalDevice,
+};
+
+/// One blend shape: per-vertex offsets added to the base mesh, scaled by the target's weight,
+/// as in glTF morph targets. Missing normal deltas leave the normals unchanged.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MorphTarget {
+    pub position_deltas: Vec<[f32; 3]>,
+    pub normal_deltas: Vec<[f32; 3]>,
+}
+
+/// Blends `targets` into `vertices` on the CPU exactly like `morph.comp`, for tests and as a
+/// fallback.
+pub fn morph_vertices(
+    vertices: &[GpuVertex],
+    targets: &[MorphTarget],
+    weights: &[f32],
+) -> Vec<GpuVertex> {
+    vertices
+        .iter()
+        .enumerate()
+        .map(|(index, vertex)| {
+            let mut position = Vec3::from(vertex.position);
+            let mut normal = Vec3::from(vertex.normal);
+            for (target, &weight) in targets.iter().zip(weights) {
+                if let Some(delta) = target.position_deltas.get(index) {
+                    position += Vec3::from(*delta) * weight;
+                }
+                if let Some(delta) = target.normal_deltas.get(index) {
+                    normal += Vec3::from(*delta) * weight;
+                }
+            }
+
+            GpuVertex {
+                position: position.to_array(),
+                normal: normal.normalize_or_zero().to_array(),
+                uv: vertex.uv,
+            }
+        })
+        .collect()
+}
+
+/// Interpolation of a glTF animation sampler.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MorphInterpolation {
+    Step,
+    #[default]
+    Linear,
+    /// Hermite spline; every keyframe stores an in-tangent, the value and an out-tangent.
+    CubicSpline,
+}
+
+/// Keyframed morph weights: the `weights` path of a glTF animation channel.
+///
+/// `values` holds `target_count` weights per keyframe, or three times that for
+/// `MorphInterpolation::CubicSpline` (in-tangents, values, out-tangents), as glTF stores the
+/// sampler output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MorphAnimation {
+    pub times: Vec<f32>,
+    pub values: Vec<f32>,
+    pub target_count: usize,
+    pub interpolation: MorphInterpolation,
+}
+
+impl MorphAnimation {
+    pub fn new(
+        times: Vec<f32>,
+        values: Vec<f32>,
+        target_count: usize,
+        interpolation: MorphInterpolation,
+    ) -> Result<Self> {
+        let per_key = match interpolation {
+            MorphInterpolation::CubicSpline => target_count * 3,
+            _ => target_count,
+        };
+        if times.is_empty() || values.len() != times.len() * per_key {
+            return Err(anyhow::anyhow!(
+                "Morph animation has {} values for {} keyframes of {} targets",
+                values.len(),
+                times.len(),
+                target_count
+            ));
+        }
+        if times.windows(2).any(|pair| pair[1] < pair[0]) {
+            return Err(anyhow::anyhow!(
+                "Morph animation keyframes aren't sorted by time"
+            ));
+        }
+
+        Ok(Self {
+            times,
+            values,
+            target_count,
+            interpolation,
+        })
+    }
+
+    /// Time of the last keyframe.
+    pub fn duration(&self) -> f32 {
+        self.times.last().copied().unwrap_or(0.0)
+    }
+
+    /// Weights of keyframe `key`; `part` selects the in-tangent (0), value (1) or out-tangent
+    /// (2) of cubic spline keyframes.
+    fn key(&self, key: usize, part: usize) -> &[f32] {
+        let count = self.target_count;
+        let start = match self.interpolation {
+            MorphInterpolation::CubicSpline => (key * 3 + part) * count,
+            _ => key * count,
+        };
+        &self.values[start..start + count]
+    }
+
+    /// Weights at `time`, clamped to the first and last keyframes. Loop by passing
+    /// `time % duration()`.
+    pub fn sample(&self, time: f32) -> Vec<f32> {
+        let last = self.times.len() - 1;
+        let next = self.times.partition_point(|&t| t <= time);
+        if next == 0 {
+            return self.key(0, 1).to_vec();
+        }
+        if next > last {
+            return self.key(last, 1).to_vec();
+        }
+
+        let previous = next - 1;
+        let delta = self.times[next] - self.times[previous];
+        let t = if delta > 0.0 {
+            (time - self.times[previous]) / delta
+        } else {
+            0.0
+        };
+
+        match self.interpolation {
+            MorphInterpolation::Step => self.key(previous, 1).to_vec(),
+            MorphInterpolation::Linear => self
+                .key(previous, 1)
+                .iter()
+                .zip(self.key(next, 1))
+                .map(|(a, b)| a + (b - a) * t)
+                .collect(),
+            MorphInterpolation::CubicSpline => {
+                let (t2, t3) = (t * t, t * t * t);
+                let start = self.key(previous, 1);
+                let out_tangent = self.key(previous, 2);
+                let end = self.key(next, 1);
+                let in_tangent = self.key(next, 0);
+                (0..self.target_count)
+                    .map(|i| {
+                        (2.0 * t3 - 3.0 * t2 + 1.0) * start[i]
+                            + (t3 - 2.0 * t2 + t) * delta * out_tangent[i]
+                            + (-2.0 * t3 + 3.0 * t2) * end[i]
+                            + (t3 - t2) * delta * in_tangent[i]
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MorphPushConstants {
+    vertex_count: u32,
+    target_count: u32,
+}
+
+/// Compute pipeline shared by every `MorphInstance`.
+pub struct MorphPipeline {
+    pipeline: VulkanComputePipeline,
+    layout: VulkanDescriptorSetLayout,
+}
+
+impl MorphPipeline {
+    pub fn new(device: &VulkanDevice) -> Result<Self> {
+        let layout = VulkanDescriptorSetLayout::new(
+            device,
+            &(0..4)
+                .map(|binding| {
+                    VulkanDescriptorSetLayout::binding(
+                        binding,
+                        vk::DescriptorType::STORAGE_BUFFER,
+                        vk::ShaderStageFlags::COMPUTE,
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )?;
+
+        let pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/morph.comp.spv"), None)?
+            .with_descriptor_set_layout(layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(std::mem::size_of::<MorphPushConstants>() as u32),
+            )
+            .build()?;
+
+        Ok(Self { pipeline, layout })
+    }
+}
+
+/// Base mesh and target deltas in storage buffers, shared by every `MorphInstance` of the
+/// mesh.
+pub struct MorphTargets {
+    base_vertices: VulkanBuffer,
+    deltas: VulkanBuffer,
+    vertex_count: u32,
+    target_count: u32,
+}
+
+impl MorphTargets {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        vertices: &[GpuVertex],
+        targets: &[MorphTarget],
+    ) -> Result<Self> {
+        if let Some((index, target)) = targets.iter().enumerate().find(|(_, target)| {
+            target.position_deltas.len() != vertices.len()
+                || !(target.normal_deltas.is_empty()
+                    || target.normal_deltas.len() == vertices.len())
+        }) {
+            return Err(anyhow::anyhow!(
+                "Morph target {} has {} position and {} normal deltas for {} vertices",
+                index,
+                target.position_deltas.len(),
+                target.normal_deltas.len(),
+                vertices.len()
+            ));
+        }
+
+        let storage = |size: usize| {
+            VulkanBuffer::new_host_visible(
+                device,
+                physical_device,
+                size.max(1) as vk::DeviceSize,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+            )
+        };
+
+        let base_vertices = storage(std::mem::size_of_val(vertices))?;
+        base_vertices.write(0, vertices)?;
+
+        let deltas: Vec<Vec4> = targets
+            .iter()
+            .flat_map(|target| {
+                (0..vertices.len()).flat_map(move |index| {
+                    let normal = target.normal_deltas.get(index).copied().unwrap_or_default();
+                    [
+                        Vec3::from(target.position_deltas[index]).extend(0.0),
+                        Vec3::from(normal).extend(0.0),
+                    ]
+                })
+            })
+            .collect();
+        let delta_buffer = storage(std::mem::size_of_val(deltas.as_slice()))?;
+        delta_buffer.write(0, &deltas)?;
+
+        Ok(Self {
+            base_vertices,
+            deltas: delta_buffer,
+            vertex_count: vertices.len() as u32,
+            target_count: targets.len() as u32,
+        })
+    }
+
+    pub fn vertex_count(&self) -> u32 {
+        self.vertex_count
+    }
+
+    pub fn target_count(&self) -> u32 {
+        self.target_count
+    }
+}
+
+/// One instance of a morphed mesh with its own weights, blended by a compute pre-pass into
+/// `output`, a `GpuVertex` buffer in model space drawn like `SkinnedMesh::output`.
+pub struct MorphInstance {
+    pub output: VulkanBuffer,
+    weights: VulkanBuffer,
+    _descriptor_pool: VulkanDescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    vertex_count: u32,
+    target_count: u32,
+}
+
+impl MorphInstance {
+    /// Creates an instance with every weight at 0, i.e. showing the base mesh.
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        pipeline: &MorphPipeline,
+        targets: &MorphTargets,
+    ) -> Result<Self> {
+        let weights = VulkanBuffer::new_host_visible(
+            device,
+            physical_device,
+            (targets.target_count.max(1) as usize * std::mem::size_of::<f32>()) as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+        )?;
+        weights.write(0, &vec![0.0f32; targets.target_count.max(1) as usize])?;
+
+        let output = VulkanBuffer::new(
+            device,
+            physical_device,
+            (targets.vertex_count.max(1) as usize * std::mem::size_of::<GpuVertex>())
+                as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::VERTEX_BUFFER
+                | device.features.acceleration_structure_input_usage(),
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(device, &pipeline.layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&pipeline.layout)?;
+        for (binding, buffer) in [&targets.base_vertices, &targets.deltas, &weights, &output]
+            .into_iter()
+            .enumerate()
+        {
+            descriptor_pool.write_buffer(
+                descriptor_set,
+                binding as u32,
+                vk::DescriptorType::STORAGE_BUFFER,
+                buffer,
+            );
+        }
+
+        Ok(Self {
+            output,
+            weights,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            vertex_count: targets.vertex_count,
+            target_count: targets.target_count,
+        })
+    }
+
+    /// Writes the weights for the next `record`, e.g. from `MorphAnimation::sample`. No
+    /// in-flight frame may still be morphing with the previous weights.
+    pub fn set_weights(&self, weights: &[f32]) -> Result<()> {
+        if weights.len() as u32 > self.target_count {
+            return Err(anyhow::anyhow!(
+                "{} weights for {} morph targets",
+                weights.len(),
+                self.target_count
+            ));
+        }
+        self.weights.write(0, weights)
+    }
+
+    /// Records the blend, then a barrier making `output` readable as vertices and by shaders.
+    /// Must be recorded outside a render pass, before the passes drawing the mesh.
+    pub fn record(&self, command_buffer: vk::CommandBuffer, pipeline: &MorphPipeline) {
+        // The previous frame's passes may still be drawing the output.
+        self.output.cmd_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::VERTEX_INPUT | vk::PipelineStageFlags::VERTEX_SHADER,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ | vk::AccessFlags::SHADER_READ,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+        );
+
+        pipeline.pipeline.bind(command_buffer);
+        pipeline
+            .pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.descriptor_set]);
+        pipeline.pipeline.push_constants(
+            command_buffer,
+            &MorphPushConstants {
+                vertex_count: self.vertex_count,
+                target_count: self.target_count,
+            },
+        );
+        pipeline
+            .pipeline
+            .dispatch(command_buffer, self.vertex_count.div_ceil(64).max(1), 1, 1);
+
+        self.output.cmd_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::VERTEX_INPUT | vk::PipelineStageFlags::VERTEX_SHADER,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ | vk::AccessFlags::SHADER_READ,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ShaderReflection;
+
+    #[test]
+    fn blends_targets_by_weight() {
+        let vertices = [GpuVertex {
+            position: [0.0, 0.0, 0.0],
+            normal: [0.0, 1.0, 0.0],
+            uv: [0.5, 0.5],
+        }];
+        let targets = [
+            MorphTarget {
+                position_deltas: vec![[1.0, 0.0, 0.0]],
+                normal_deltas: vec![[1.0, -1.0, 0.0]],
+            },
+            MorphTarget {
+                position_deltas: vec![[0.0, 2.0, 0.0]],
+                normal_deltas: Vec::new(),
+            },
+        ];
+
+        let morphed = morph_vertices(&vertices, &targets, &[0.5, 0.25]);
+
+        assert_eq!(morphed[0].position, [0.5, 0.5, 0.0]);
+        let normal = Vec3::from(morphed[0].normal);
+        assert!(normal.abs_diff_eq(Vec3::new(1.0, 1.0, 0.0).normalize(), 1e-6));
+        assert_eq!(morphed[0].uv, [0.5, 0.5]);
+    }
+
+    #[test]
+    fn samples_keyframes_like_gltf() {
+        let linear = MorphAnimation::new(
+            vec![0.0, 1.0, 3.0],
+            vec![0.0, 1.0, 1.0, 0.0, 0.5, 0.5],
+            2,
+            MorphInterpolation::Linear,
+        )
+        .unwrap();
+        assert_eq!(linear.duration(), 3.0);
+        assert_eq!(linear.sample(-1.0), [0.0, 1.0]);
+        assert_eq!(linear.sample(0.5), [0.5, 0.5]);
+        assert_eq!(linear.sample(2.0), [0.75, 0.25]);
+        assert_eq!(linear.sample(5.0), [0.5, 0.5]);
+
+        let step = MorphAnimation {
+            interpolation: MorphInterpolation::Step,
+            ..linear
+        };
+        assert_eq!(step.sample(0.99), [0.0, 1.0]);
+        assert_eq!(step.sample(1.0), [1.0, 0.0]);
+
+        // Zero tangents: smoothstep between the values.
+        let cubic = MorphAnimation::new(
+            vec![0.0, 2.0],
+            vec![0.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+            1,
+            MorphInterpolation::CubicSpline,
+        )
+        .unwrap();
+        assert_eq!(cubic.sample(1.0), [0.5]);
+        assert!((cubic.sample(0.5)[0] - 0.15625).abs() < 1e-6);
+
+        assert!(MorphAnimation::new(vec![0.0], vec![1.0], 2, MorphInterpolation::Linear).is_err());
+    }
+
+    #[test]
+    fn shader_layout_matches_morph_pipeline() {
+        let reflection =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/morph.comp.spv")).unwrap();
+
+        assert_eq!(reflection.layout_bindings(0).len(), 4);
+        assert_eq!(
+            reflection.push_constant_size,
+            Some(std::mem::size_of::<MorphPushConstants>() as u32)
+        );
+    }
+}
@@ -0,0 +1,341 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::{Mat4, Vec3, Vec4};
+use std::sync::Arc;
+
+use crate::pipeline::{VulkanComputePipeline, VulkanComputePipelineBuilder};
+use crate::renderer::{GpuMeshId, GpuScene};
+use crate::vulkan::{
+    Blas, BlasUsage, Tlas, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanImage, VulkanPhysicalDevice, cmd_acceleration_structure_barrier, tlas_instance,
+};
+
+/// Lighting of the reference view, the same model as `LightmapBakeSettings`.
+#[derive(Debug, Clone, Copy)]
+pub struct PathTracerSettings {
+    pub sun_direction: Vec3,
+    pub sun_color: Vec3,
+    pub sun_intensity: f32,
+    pub sky_color: Vec3,
+    /// Diffuse albedo of every surface.
+    pub albedo: f32,
+    /// Indirect bounces after the primary hit.
+    pub max_bounces: u32,
+}
+
+impl Default for PathTracerSettings {
+    fn default() -> Self {
+        Self {
+            sun_direction: Vec3::new(-0.4, -1.0, -0.3),
+            sun_color: Vec3::ONE,
+            sun_intensity: 3.0,
+            sky_color: Vec3::new(0.4, 0.5, 0.7),
+            albedo: 0.7,
+            max_bounces: 4,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TracePushConstants {
+    inverse_view_proj: Mat4,
+    /// w: intensity.
+    sun_direction: Vec4,
+    /// w: albedo.
+    sun_color: Vec4,
+    sky_color: Vec4,
+    sample_index: u32,
+    max_bounces: u32,
+}
+
+/// Progressive path tracer over a `GpuScene`, traced with ray queries from `path_trace.comp`.
+///
+/// Meant as a ground-truth view to validate the rasterized lighting against: while it is
+/// toggled on, call `record` every frame and display `output` instead of the rasterized
+/// image. Samples accumulate until the camera moves or `reset` is called.
+///
+/// Requires `VulkanDeviceFeatures::ray_query`.
+pub struct PathTracer {
+    /// `R32G32B32A32_SFLOAT` linear radiance, left in `GENERAL` layout.
+    pub output: VulkanImage,
+    pub settings: PathTracerSettings,
+    pipeline: VulkanComputePipeline,
+    _layout: VulkanDescriptorSetLayout,
+    _descriptor_pool: VulkanDescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    blases: Vec<Blas>,
+    tlas: Tlas,
+    sample_count: u32,
+    view_proj: Option<Mat4>,
+    device: Arc<Device>,
+}
+
+impl PathTracer {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        scene: &GpuScene,
+        extent: vk::Extent2D,
+    ) -> Result<Self> {
+        if !device.features.ray_query {
+            return Err(anyhow::anyhow!("Device doesn't support ray queries"));
+        }
+
+        let output = VulkanImage::new(
+            device,
+            physical_device,
+            extent,
+            vk::Format::R32G32B32A32_SFLOAT,
+            vk::ImageUsageFlags::STORAGE
+                | vk::ImageUsageFlags::SAMPLED
+                | vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::ImageAspectFlags::COLOR,
+        )?;
+
+        let layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[
+                VulkanDescriptorSetLayout::binding(
+                    0,
+                    vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    1,
+                    vk::DescriptorType::STORAGE_IMAGE,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    2,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    3,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    4,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    5,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+            ],
+        )?;
+
+        let tlas = Tlas::new(device, physical_device, scene.limits.max_objects)?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(device, &layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&layout)?;
+        descriptor_pool.write_acceleration_structure(
+            descriptor_set,
+            0,
+            tlas.acceleration_structure,
+        );
+        descriptor_pool.write_storage_image(descriptor_set, 1, output.view);
+        for (binding, buffer) in [
+            &scene.vertex_buffer,
+            &scene.index_buffer,
+            &scene.mesh_buffer,
+            &scene.object_buffer,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            descriptor_pool.write_buffer(
+                descriptor_set,
+                binding as u32 + 2,
+                vk::DescriptorType::STORAGE_BUFFER,
+                buffer,
+            );
+        }
+
+        let pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/path_trace.comp.spv"), None)?
+            .with_descriptor_set_layout(layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(std::mem::size_of::<TracePushConstants>() as u32),
+            )
+            .build()?;
+
+        Ok(Self {
+            output,
+            settings: PathTracerSettings::default(),
+            pipeline,
+            _layout: layout,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            blases: Vec::new(),
+            tlas,
+            sample_count: 0,
+            view_proj: None,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Samples accumulated in `output` so far.
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// Restarts accumulation, e.g. after changing `settings`.
+    pub fn reset(&mut self) {
+        self.sample_count = 0;
+    }
+
+    /// Builds BLASes for meshes added since the last call and rebuilds the TLAS from the
+    /// current objects, then restarts accumulation. Record after `GpuScene::upload`, outside a
+    /// render pass; the caller must make sure no in-flight frame is still tracing.
+    pub fn update_scene(
+        &mut self,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        command_buffer: vk::CommandBuffer,
+        scene: &GpuScene,
+    ) -> Result<()> {
+        let built = self.blases.len() as u32;
+        for mesh in built..scene.mesh_count() {
+            let geometry = scene
+                .blas_geometry(GpuMeshId(mesh))
+                .ok_or_else(|| anyhow::anyhow!("Missing geometry of mesh {}", mesh))?;
+
+            let mut blas = Blas::new(device, physical_device, geometry, BlasUsage::Static)?;
+            blas.cmd_build(command_buffer)?;
+            self.blases.push(blas);
+        }
+        if built < scene.mesh_count() {
+            cmd_acceleration_structure_barrier(
+                &self.device,
+                command_buffer,
+                vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR,
+                vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR,
+            );
+        }
+
+        let instances: Vec<_> = scene
+            .object_meshes()
+            .enumerate()
+            .map(|(object, (mesh, transform))| {
+                tlas_instance(transform, object as u32, &self.blases[mesh.0 as usize])
+            })
+            .collect();
+        self.tlas.set_instances(&instances)?;
+        self.tlas.cmd_build(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR,
+        );
+
+        self.reset();
+        Ok(())
+    }
+
+    /// Traces one more sample per pixel from the camera of `view_proj` and leaves `output`
+    /// readable by fragment and compute shaders. Accumulation restarts when the camera
+    /// changes.
+    pub fn record(&mut self, command_buffer: vk::CommandBuffer, view_proj: Mat4) {
+        if self.view_proj != Some(view_proj) {
+            self.view_proj = Some(view_proj);
+            self.reset();
+        }
+
+        let range = self.output.subresource_range();
+        // The first sample overwrites whatever the image held.
+        let old_layout = if self.sample_count == 0 {
+            vk::ImageLayout::UNDEFINED
+        } else {
+            vk::ImageLayout::GENERAL
+        };
+        self.output.cmd_transition(
+            command_buffer,
+            range,
+            old_layout,
+            vk::ImageLayout::GENERAL,
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+        );
+
+        let settings = &self.settings;
+        let push_constants = TracePushConstants {
+            inverse_view_proj: view_proj.inverse(),
+            sun_direction: settings
+                .sun_direction
+                .normalize_or_zero()
+                .extend(settings.sun_intensity),
+            sun_color: settings.sun_color.extend(settings.albedo),
+            sky_color: settings.sky_color.extend(1.0),
+            sample_index: self.sample_count,
+            max_bounces: settings.max_bounces,
+        };
+
+        let extent = self.output.extent;
+        self.pipeline.bind(command_buffer);
+        self.pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.descriptor_set]);
+        self.pipeline
+            .push_constants(command_buffer, &push_constants);
+        self.pipeline.dispatch(
+            command_buffer,
+            extent.width.div_ceil(8),
+            extent.height.div_ceil(8),
+            1,
+        );
+
+        self.output.cmd_transition(
+            command_buffer,
+            range,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::GENERAL,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        );
+
+        self.sample_count += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ShaderReflection;
+
+    #[test]
+    fn shader_layout_matches_path_tracer() {
+        let reflection =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/path_trace.comp.spv")).unwrap();
+
+        let bindings = reflection.layout_bindings(0);
+        assert_eq!(bindings.len(), 6);
+        assert_eq!(
+            bindings[0].descriptor_type,
+            vk::DescriptorType::ACCELERATION_STRUCTURE_KHR
+        );
+        assert_eq!(
+            bindings[1].descriptor_type,
+            vk::DescriptorType::STORAGE_IMAGE
+        );
+        // The struct is padded to its 16-byte alignment; the range may exceed what's used.
+        assert_eq!(reflection.push_constant_size, Some(120));
+        assert!(std::mem::size_of::<TracePushConstants>() >= 120);
+        assert_eq!(
+            reflection
+                .entry_point(vk::ShaderStageFlags::COMPUTE)
+                .unwrap()
+                .local_size,
+            Some([8, 8, 1])
+        );
+    }
+}
@@ -0,0 +1,682 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::vulkan::{DamageRegion, SuboptimalPolicy, VulkanDevice, VulkanSwapchain};
+
+/// An image the scene is rendered into for one frame slot. It must have `TRANSFER_SRC` usage
+/// and be left in `SHADER_READ_ONLY_OPTIMAL`, as `VulkanOffscreenTarget` does.
+#[derive(Debug, Clone, Copy)]
+pub struct PresentSource {
+    pub image: vk::Image,
+    pub extent: vk::Extent2D,
+}
+
+/// Sent to the present thread once a slot's scene submission has been made.
+struct PresentRequest {
+    slot: usize,
+    damage: DamageRegion,
+}
+
+/// Sent back once a slot's blit has been submitted; the slot is free once its fence signals.
+struct PresentDone {
+    slot: usize,
+    result: Result<()>,
+}
+
+/// Which slots have a frame queued or being presented by the present thread.
+#[derive(Debug, Clone, Default)]
+struct SlotStates {
+    in_flight: Vec<bool>,
+}
+
+impl SlotStates {
+    fn new(count: usize) -> Self {
+        Self {
+            in_flight: vec![false; count],
+        }
+    }
+
+    fn is_in_flight(&self, slot: usize) -> bool {
+        self.in_flight[slot]
+    }
+
+    fn queue(&mut self, slot: usize) -> Result<()> {
+        if std::mem::replace(&mut self.in_flight[slot], true) {
+            return Err(anyhow::anyhow!(
+                "Frame slot {} was presented twice without begin_frame",
+                slot
+            ));
+        }
+        Ok(())
+    }
+
+    fn release(&mut self, slot: usize) {
+        self.in_flight[slot] = false;
+    }
+}
+
+/// Vulkan objects the present thread owns while it runs.
+struct PresentWorker {
+    device: Arc<Device>,
+    swapchain_loader: ash::khr::swapchain::Device,
+    swapchain: vk::SwapchainKHR,
+    swapchain_images: Vec<vk::Image>,
+    swapchain_extent: vk::Extent2D,
+    suboptimal_policy: SuboptimalPolicy,
+    incremental_present: bool,
+    sources: Vec<PresentSource>,
+    graphics_queue: vk::Queue,
+    present_queue: vk::Queue,
+    queue_lock: Arc<Mutex<()>>,
+    out_of_date: Arc<AtomicBool>,
+    surface_lost: Arc<AtomicBool>,
+    command_buffers: Vec<vk::CommandBuffer>,
+    render_finished: Vec<vk::Semaphore>,
+    image_available: Vec<vk::Semaphore>,
+    blit_finished: Vec<vk::Semaphore>,
+    blit_fences: Vec<vk::Fence>,
+}
+
+/// Acquires swapchain images and presents them on a dedicated thread, so a present blocked on
+/// the compositor doesn't stall scene recording.
+///
+/// The main thread renders each frame slot into its own `PresentSource` and hands it over with
+/// `present`; the present thread acquires an image, blits the source into it and presents it.
+/// All submissions to the graphics queue while the thread runs must go through `submit`, since
+/// the two threads share it. Drop before the swapchain and the sources.
+pub struct PresentThread {
+    requests: Option<Sender<PresentRequest>>,
+    done: Receiver<PresentDone>,
+    thread: Option<JoinHandle<()>>,
+    slots: SlotStates,
+    queue_lock: Arc<Mutex<()>>,
+    out_of_date: Arc<AtomicBool>,
+    surface_lost: Arc<AtomicBool>,
+    present_queue: vk::Queue,
+    command_pool: vk::CommandPool,
+    render_finished: Vec<vk::Semaphore>,
+    image_available: Vec<vk::Semaphore>,
+    blit_finished: Vec<vk::Semaphore>,
+    blit_fences: Vec<vk::Fence>,
+    device: Arc<Device>,
+}
+
+impl PresentThread {
+    /// Starts the thread with one frame slot per source.
+    pub fn new(
+        device: &VulkanDevice,
+        swapchain: &VulkanSwapchain,
+        sources: &[PresentSource],
+    ) -> Result<Self> {
+        check_blit_target(
+            swapchain.usage,
+            swapchain.format.format,
+            swapchain.format_features,
+        )?;
+        let present_queue = device
+            .present_queue
+            .ok_or_else(|| anyhow::anyhow!("Device has no present queue"))?;
+        let graphics_family = device
+            .queue_family_indices
+            .graphics_family
+            .ok_or_else(|| anyhow::anyhow!("Device has no graphics queue family"))?;
+
+        let pool_info = vk::CommandPoolCreateInfo::default()
+            .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+            .queue_family_index(graphics_family);
+        let command_pool = unsafe {
+            device
+                .device
+                .create_command_pool(&pool_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create present command pool: {}", e))?
+        };
+
+        let (request_sender, requests) = mpsc::channel::<PresentRequest>();
+        let (done_sender, done) = mpsc::channel();
+
+        let mut thread = Self {
+            requests: None,
+            done,
+            thread: None,
+            slots: SlotStates::new(sources.len()),
+            queue_lock: Arc::new(Mutex::new(())),
+            out_of_date: Arc::new(AtomicBool::new(false)),
+            surface_lost: Arc::new(AtomicBool::new(false)),
+            present_queue,
+            command_pool,
+            render_finished: Vec::new(),
+            image_available: Vec::new(),
+            blit_finished: Vec::new(),
+            blit_fences: Vec::new(),
+            device: device.device.clone(),
+        };
+
+        let semaphore_info = vk::SemaphoreCreateInfo::default();
+        let fence_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
+        for _ in sources {
+            unsafe {
+                for semaphores in [
+                    &mut thread.render_finished,
+                    &mut thread.image_available,
+                    &mut thread.blit_finished,
+                ] {
+                    semaphores.push(
+                        device
+                            .device
+                            .create_semaphore(&semaphore_info, None)
+                            .map_err(|e| {
+                                anyhow::anyhow!("Failed to create present semaphore: {}", e)
+                            })?,
+                    );
+                }
+                thread.blit_fences.push(
+                    device
+                        .device
+                        .create_fence(&fence_info, None)
+                        .map_err(|e| anyhow::anyhow!("Failed to create present fence: {}", e))?,
+                );
+            }
+        }
+
+        let alloc_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(sources.len() as u32);
+        let command_buffers = unsafe {
+            device
+                .device
+                .allocate_command_buffers(&alloc_info)
+                .map_err(|e| anyhow::anyhow!("Failed to allocate present command buffers: {}", e))?
+        };
+
+        let worker = PresentWorker {
+            device: device.device.clone(),
+            swapchain_loader: swapchain.swapchain_loader.clone(),
+            swapchain: swapchain.swapchain,
+            swapchain_images: swapchain.images.clone(),
+            swapchain_extent: swapchain.extent,
+            suboptimal_policy: swapchain.suboptimal_policy,
+            incremental_present: device.features.incremental_present,
+            sources: sources.to_vec(),
+            graphics_queue: device.graphics_queue,
+            present_queue,
+            queue_lock: thread.queue_lock.clone(),
+            out_of_date: thread.out_of_date.clone(),
+            surface_lost: thread.surface_lost.clone(),
+            command_buffers,
+            render_finished: thread.render_finished.clone(),
+            image_available: thread.image_available.clone(),
+            blit_finished: thread.blit_finished.clone(),
+            blit_fences: thread.blit_fences.clone(),
+        };
+
+        let handle = std::thread::Builder::new()
+            .name("present".to_string())
+            .spawn(move || {
+                for request in requests {
+                    let result = worker.present(request.slot, &request.damage);
+                    if done_sender
+                        .send(PresentDone {
+                            slot: request.slot,
+                            result,
+                        })
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            })
+            .map_err(|e| anyhow::anyhow!("Failed to spawn present thread: {}", e))?;
+
+        thread.requests = Some(request_sender);
+        thread.thread = Some(handle);
+        Ok(thread)
+    }
+
+    pub fn frame_slots(&self) -> usize {
+        self.render_finished.len()
+    }
+
+    /// Waits until `slot`'s previous frame has been rendered and copied to the swapchain, so
+    /// its source image and command buffers can be reused. Returns the error of that frame's
+    /// acquire or present, if any.
+    pub fn begin_frame(&mut self, slot: usize) -> Result<()> {
+        let mut result = Ok(());
+        while self.slots.is_in_flight(slot) {
+            let done = self
+                .done
+                .recv()
+                .map_err(|_| anyhow::anyhow!("Present thread exited"))?;
+            self.slots.release(done.slot);
+            if done.slot == slot {
+                result = done.result;
+            } else if let Err(e) = done.result {
+                eprintln!("Failed to present frame slot {}: {}", done.slot, e);
+            }
+        }
+
+        let fence = self.blit_fences[slot];
+        unsafe {
+            self.device
+                .wait_for_fences(std::slice::from_ref(&fence), true, u64::MAX)
+                .map_err(|e| anyhow::anyhow!("Failed to wait for present fence: {}", e))?;
+        }
+        result
+    }
+
+    /// Semaphore the scene submission for `slot` must signal.
+    pub fn render_finished_semaphore(&self, slot: usize) -> vk::Semaphore {
+        self.render_finished[slot]
+    }
+
+    /// Submits to `queue`, holding the lock the present thread takes for its own submissions.
+    pub fn submit(
+        &self,
+        queue: vk::Queue,
+        submits: &[vk::SubmitInfo],
+        fence: vk::Fence,
+    ) -> Result<()> {
+        let _guard = self
+            .queue_lock
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Present queue lock poisoned"))?;
+        unsafe {
+            self.device
+                .queue_submit(queue, submits, fence)
+                .map_err(|e| anyhow::anyhow!("Failed to submit command buffer: {}", e))
+        }
+    }
+
+    /// Hands `slot` to the present thread. Its scene submission must already have been made
+    /// and signal `render_finished_semaphore(slot)`.
+    pub fn present(&mut self, slot: usize) -> Result<()> {
+        self.present_with_damage(slot, DamageRegion::new())
+    }
+
+    /// Like `present`, passing `damage` to the presentation engine when
+    /// `VK_KHR_incremental_present` is enabled. The whole image is still copied.
+    pub fn present_with_damage(&mut self, slot: usize, damage: DamageRegion) -> Result<()> {
+        self.slots.queue(slot)?;
+        self.requests
+            .as_ref()
+            .and_then(|requests| requests.send(PresentRequest { slot, damage }).ok())
+            .ok_or_else(|| anyhow::anyhow!("Present thread exited"))
+    }
+
+    /// Set once the swapchain no longer matches the surface; recreate it and the thread. A
+    /// suboptimal swapchain only sets it as its `SuboptimalPolicy` asks.
+    pub fn is_out_of_date(&self) -> bool {
+        self.out_of_date.load(Ordering::Relaxed)
+    }
+
+    /// Set once the surface was lost, e.g. when a compositor restarts or an Android activity
+    /// is paused. Frames are dropped until the surface, the swapchain and the thread are
+    /// recreated; the device and the sources can be kept.
+    pub fn is_surface_lost(&self) -> bool {
+        self.surface_lost.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for PresentThread {
+    fn drop(&mut self) {
+        self.requests = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+
+        unsafe {
+            // Only this thread's work is waited for, not the whole device, so the swapchain
+            // can be replaced during a resize while other queues keep running. Presents
+            // aren't fenced, so the present queue has to drain as well.
+            let _ = self
+                .device
+                .wait_for_fences(&self.blit_fences, true, u64::MAX);
+            if let Ok(_guard) = self.queue_lock.lock() {
+                let _ = self.device.queue_wait_idle(self.present_queue);
+            }
+
+            for semaphore in self
+                .render_finished
+                .iter()
+                .chain(&self.image_available)
+                .chain(&self.blit_finished)
+            {
+                self.device.destroy_semaphore(*semaphore, None);
+            }
+            for &fence in &self.blit_fences {
+                self.device.destroy_fence(fence, None);
+            }
+            self.device.destroy_command_pool(self.command_pool, None);
+        }
+    }
+}
+
+impl PresentWorker {
+    fn present(&self, slot: usize, damage: &DamageRegion) -> Result<()> {
+        let fence = self.blit_fences[slot];
+        unsafe {
+            self.device
+                .wait_for_fences(std::slice::from_ref(&fence), true, u64::MAX)
+                .map_err(|e| anyhow::anyhow!("Failed to wait for present fence: {}", e))?;
+        }
+
+        let acquired = unsafe {
+            self.swapchain_loader.acquire_next_image(
+                self.swapchain,
+                u64::MAX,
+                self.image_available[slot],
+                vk::Fence::null(),
+            )
+        };
+        let image_index = match acquired {
+            Ok((_, true)) if self.suboptimal_policy.drops_frame() => {
+                self.out_of_date.store(true, Ordering::Relaxed);
+                // The image stays acquired until the swapchain is destroyed, but both
+                // semaphores have to be waited on before they can be signaled again.
+                return self.submit(
+                    slot,
+                    &[self.render_finished[slot], self.image_available[slot]],
+                    &[
+                        vk::PipelineStageFlags::ALL_COMMANDS,
+                        vk::PipelineStageFlags::ALL_COMMANDS,
+                    ],
+                    &[],
+                    &[],
+                );
+            }
+            Ok((image_index, suboptimal)) => {
+                if suboptimal && self.suboptimal_policy.recreates() {
+                    self.out_of_date.store(true, Ordering::Relaxed);
+                }
+                image_index
+            }
+            Err(e @ (vk::Result::ERROR_OUT_OF_DATE_KHR | vk::Result::ERROR_SURFACE_LOST_KHR)) => {
+                self.mark_out_of_date(e);
+                // Nothing will be presented, but the scene's semaphore still has to be waited
+                // on before it can be signaled again.
+                return self.submit(
+                    slot,
+                    &[self.render_finished[slot]],
+                    &[vk::PipelineStageFlags::ALL_COMMANDS],
+                    &[],
+                    &[],
+                );
+            }
+            Err(e) => return Err(anyhow::anyhow!("Failed to acquire swapchain image: {}", e)),
+        };
+
+        let command_buffer = self.command_buffers[slot];
+        self.record_blit(
+            command_buffer,
+            self.sources[slot],
+            self.swapchain_images[image_index as usize],
+        )?;
+        self.submit(
+            slot,
+            &[self.render_finished[slot], self.image_available[slot]],
+            &[
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::TRANSFER,
+            ],
+            std::slice::from_ref(&command_buffer),
+            &[self.blit_finished[slot]],
+        )?;
+
+        let wait_semaphores = [self.blit_finished[slot]];
+        let swapchains = [self.swapchain];
+        let image_indices = [image_index];
+        let mut present_info = vk::PresentInfoKHR::default()
+            .wait_semaphores(&wait_semaphores)
+            .swapchains(&swapchains)
+            .image_indices(&image_indices);
+
+        let rects = self
+            .incremental_present
+            .then(|| damage.present_rects(self.swapchain_extent))
+            .flatten();
+        let regions = [vk::PresentRegionKHR::default().rectangles(rects.as_deref().unwrap_or(&[]))];
+        let mut present_regions = vk::PresentRegionsKHR::default().regions(&regions);
+        if rects.is_some() {
+            present_info = present_info.push_next(&mut present_regions);
+        }
+
+        let presented = {
+            let _guard = self
+                .queue_lock
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Present queue lock poisoned"))?;
+            unsafe {
+                self.swapchain_loader
+                    .queue_present(self.present_queue, &present_info)
+            }
+        };
+        match presented {
+            Ok(suboptimal) => {
+                if suboptimal && self.suboptimal_policy.recreates() {
+                    self.out_of_date.store(true, Ordering::Relaxed);
+                }
+                Ok(())
+            }
+            Err(e @ (vk::Result::ERROR_OUT_OF_DATE_KHR | vk::Result::ERROR_SURFACE_LOST_KHR)) => {
+                self.mark_out_of_date(e);
+                Ok(())
+            }
+            Err(e) => Err(anyhow::anyhow!("Failed to present swapchain image: {}", e)),
+        }
+    }
+
+    fn mark_out_of_date(&self, error: vk::Result) {
+        self.out_of_date.store(true, Ordering::Relaxed);
+        if error == vk::Result::ERROR_SURFACE_LOST_KHR {
+            self.surface_lost.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn submit(
+        &self,
+        slot: usize,
+        wait_semaphores: &[vk::Semaphore],
+        wait_stages: &[vk::PipelineStageFlags],
+        command_buffers: &[vk::CommandBuffer],
+        signal_semaphores: &[vk::Semaphore],
+    ) -> Result<()> {
+        let submit_info = vk::SubmitInfo::default()
+            .wait_semaphores(wait_semaphores)
+            .wait_dst_stage_mask(wait_stages)
+            .command_buffers(command_buffers)
+            .signal_semaphores(signal_semaphores);
+
+        let fence = self.blit_fences[slot];
+        let _guard = self
+            .queue_lock
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Present queue lock poisoned"))?;
+        unsafe {
+            self.device
+                .reset_fences(std::slice::from_ref(&fence))
+                .map_err(|e| anyhow::anyhow!("Failed to reset present fence: {}", e))?;
+            self.device
+                .queue_submit(
+                    self.graphics_queue,
+                    std::slice::from_ref(&submit_info),
+                    fence,
+                )
+                .map_err(|e| anyhow::anyhow!("Failed to submit present blit: {}", e))
+        }
+    }
+
+    fn record_blit(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        source: PresentSource,
+        target: vk::Image,
+    ) -> Result<()> {
+        let range = vk::ImageSubresourceRange::default()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .level_count(1)
+            .layer_count(1);
+        let barrier = |image, old_layout, new_layout, src_access, dst_access| {
+            vk::ImageMemoryBarrier::default()
+                .src_access_mask(src_access)
+                .dst_access_mask(dst_access)
+                .old_layout(old_layout)
+                .new_layout(new_layout)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(range)
+        };
+        let corner = |extent: vk::Extent2D| vk::Offset3D {
+            x: extent.width as i32,
+            y: extent.height as i32,
+            z: 1,
+        };
+        let layers = vk::ImageSubresourceLayers::default()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .layer_count(1);
+        let region = vk::ImageBlit::default()
+            .src_subresource(layers)
+            .src_offsets([vk::Offset3D::default(), corner(source.extent)])
+            .dst_subresource(layers)
+            .dst_offsets([vk::Offset3D::default(), corner(self.swapchain_extent)]);
+
+        unsafe {
+            self.device
+                .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())
+                .map_err(|e| anyhow::anyhow!("Failed to reset present command buffer: {}", e))?;
+            self.device
+                .begin_command_buffer(
+                    command_buffer,
+                    &vk::CommandBufferBeginInfo::default()
+                        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                )
+                .map_err(|e| anyhow::anyhow!("Failed to begin present command buffer: {}", e))?;
+
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[
+                    barrier(
+                        source.image,
+                        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                        vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                        vk::AccessFlags::TRANSFER_READ,
+                    ),
+                    barrier(
+                        target,
+                        vk::ImageLayout::UNDEFINED,
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                        vk::AccessFlags::empty(),
+                        vk::AccessFlags::TRANSFER_WRITE,
+                    ),
+                ],
+            );
+
+            self.device.cmd_blit_image(
+                command_buffer,
+                source.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                target,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                std::slice::from_ref(&region),
+                vk::Filter::LINEAR,
+            );
+
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[
+                    barrier(
+                        source.image,
+                        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                        vk::AccessFlags::TRANSFER_READ,
+                        vk::AccessFlags::empty(),
+                    ),
+                    barrier(
+                        target,
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                        vk::ImageLayout::PRESENT_SRC_KHR,
+                        vk::AccessFlags::TRANSFER_WRITE,
+                        vk::AccessFlags::empty(),
+                    ),
+                ],
+            );
+
+            self.device
+                .end_command_buffer(command_buffer)
+                .map_err(|e| anyhow::anyhow!("Failed to end present command buffer: {}", e))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Fails unless finished frames can be blitted into swapchain images with this usage, format
+/// and format features. The swapchain only requests transfer usage where the surface supports
+/// it.
+fn check_blit_target(
+    usage: vk::ImageUsageFlags,
+    format: vk::Format,
+    format_features: vk::FormatFeatureFlags,
+) -> Result<()> {
+    if !usage.contains(vk::ImageUsageFlags::TRANSFER_DST) {
+        return Err(anyhow::anyhow!(
+            "Surface does not support blitting frames into swapchain images"
+        ));
+    }
+    if !format_features.contains(vk::FormatFeatureFlags::BLIT_DST) {
+        return Err(anyhow::anyhow!(
+            "Swapchain format {:?} does not support blitting frames into it",
+            format
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_slots_handed_to_the_present_thread() {
+        let mut slots = SlotStates::new(2);
+        assert!(!slots.is_in_flight(0));
+
+        slots.queue(0).unwrap();
+        assert!(slots.is_in_flight(0));
+        assert!(!slots.is_in_flight(1));
+        assert!(slots.queue(0).is_err());
+
+        slots.release(0);
+        assert!(!slots.is_in_flight(0));
+        slots.queue(0).unwrap();
+    }
+
+    #[test]
+    fn requires_blittable_swapchain_images() {
+        let usage = vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST;
+        let format = vk::Format::B8G8R8A8_SRGB;
+        let features = vk::FormatFeatureFlags::COLOR_ATTACHMENT | vk::FormatFeatureFlags::BLIT_DST;
+        assert!(check_blit_target(usage, format, features).is_ok());
+        assert!(
+            check_blit_target(vk::ImageUsageFlags::COLOR_ATTACHMENT, format, features).is_err()
+        );
+        assert!(
+            check_blit_target(usage, format, vk::FormatFeatureFlags::COLOR_ATTACHMENT).is_err()
+        );
+    }
+}
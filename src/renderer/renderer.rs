@@ -3,17 +3,92 @@ use ash::{Device, vk};
 use std::sync::Arc;
 
 use crate::vulkan::{
-    FrameSyncObjects, VulkanCommandPool, VulkanDevice, VulkanFramebuffers, VulkanInstance,
-    VulkanRenderPass, VulkanSwapchain, VulkanSyncObjects,
+    DamageRegion, FrameSyncObjects, RenderPassEncoder, VulkanCommandPool, VulkanDevice,
+    VulkanFramebuffers, VulkanImage, VulkanInstance, VulkanOffscreenTarget, VulkanRenderPass,
+    VulkanSwapchain, VulkanSyncObjects,
 };
 
 use crate::pipeline::VulkanPipeline;
+use crate::renderer::PresentThread;
+
+/// Trade-off between input latency and GPU utilization.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LatencyMode {
+    /// Let the CPU run up to `max_frames_in_flight` frames ahead of the display.
+    #[default]
+    Throughput,
+    /// Start a frame only once the previous one has been shown, with `VK_KHR_present_wait`, or
+    /// at least finished on the GPU otherwise. Input is sampled as late as possible at the cost
+    /// of GPU idle time between frames.
+    LowLatency,
+}
+
+/// Debug view writing a single channel of the render target, to inspect what a pass puts
+/// in it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChannelIsolation {
+    #[default]
+    All,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+impl ChannelIsolation {
+    pub fn color_write_mask(self) -> vk::ColorComponentFlags {
+        match self {
+            Self::All => vk::ColorComponentFlags::RGBA,
+            Self::Red => vk::ColorComponentFlags::R,
+            Self::Green => vk::ColorComponentFlags::G,
+            Self::Blue => vk::ColorComponentFlags::B,
+            Self::Alpha => vk::ColorComponentFlags::A,
+        }
+    }
+
+    /// The next mode, wrapping around, for a hotkey that cycles through them.
+    pub fn next(self) -> Self {
+        match self {
+            Self::All => Self::Red,
+            Self::Red => Self::Green,
+            Self::Green => Self::Blue,
+            Self::Blue => Self::Alpha,
+            Self::Alpha => Self::All,
+        }
+    }
+}
+
+/// Where `record_command_buffer` records a frame.
+enum FrameTarget<'a> {
+    RenderPass {
+        render_pass: &'a VulkanRenderPass,
+        framebuffer: vk::Framebuffer,
+    },
+    /// A color image rendered with dynamic rendering.
+    Rendering(&'a VulkanImage),
+}
 
 pub struct VulkanRenderer {
     pub device: Arc<Device>,
     pub swapchain_loader: ash::khr::swapchain::Device,
     pub current_frame: usize,
     pub max_frames_in_flight: usize,
+    pub latency_mode: LatencyMode,
+    /// Applies to pipelines built `with_dynamic_color_write_mask`, and only when
+    /// `VK_EXT_extended_dynamic_state3` is enabled; the others keep their fixed mask.
+    pub channel_isolation: ChannelIsolation,
+    /// Present id of the last frame presented, when `present_wait` is loaded.
+    last_present_id: u64,
+    present_wait: Option<ash::khr::present_wait::Device>,
+    incremental_present: bool,
+    /// Damage hint for the next `present_frame`, taken when presenting.
+    present_damage: DamageRegion,
+    extended_dynamic_state3: Option<ash::ext::extended_dynamic_state3::Device>,
+    /// Set by `draw_frame` when acquire or present reports `VK_ERROR_SURFACE_LOST_KHR`.
+    surface_lost: bool,
+    /// Set by `draw_frame` when the swapchain is out of date, or suboptimal and its
+    /// `SuboptimalPolicy` asks for recreation.
+    out_of_date: bool,
 }
 
 impl VulkanRenderer {
@@ -26,9 +101,19 @@ impl VulkanRenderer {
             swapchain_loader,
             current_frame: 0,
             max_frames_in_flight: 3,
+            latency_mode: LatencyMode::default(),
+            channel_isolation: ChannelIsolation::default(),
+            last_present_id: 0,
+            present_wait: logical_device.present_wait.clone(),
+            incremental_present: logical_device.features.incremental_present,
+            present_damage: DamageRegion::new(),
+            extended_dynamic_state3: logical_device.extended_dynamic_state3.clone(),
+            surface_lost: false,
+            out_of_date: false,
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn draw_frame(
         &mut self,
         logical_device: &VulkanDevice,
@@ -39,33 +124,96 @@ impl VulkanRenderer {
         sync_objects: &VulkanSyncObjects,
         pipeline: &VulkanPipeline,
     ) -> Result<()> {
+        self.draw_frame_with(
+            logical_device,
+            swapchain,
+            render_pass,
+            framebuffers,
+            command_pool,
+            sync_objects,
+            |render_pass| draw_triangle(render_pass, pipeline),
+        )
+    }
+
+    /// Like `draw_frame`, with `record` recording the frame's draws inside the swapchain
+    /// image's render pass, viewport and scissor already set.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_frame_with(
+        &mut self,
+        logical_device: &VulkanDevice,
+        swapchain: &VulkanSwapchain,
+        render_pass: &VulkanRenderPass,
+        framebuffers: &VulkanFramebuffers,
+        command_pool: &VulkanCommandPool,
+        sync_objects: &VulkanSyncObjects,
+        record: impl FnOnce(&mut RenderPassEncoder),
+    ) -> Result<()> {
+        if self.latency_mode == LatencyMode::LowLatency {
+            self.wait_for_previous_frame(swapchain, sync_objects)?;
+        }
         sync_objects.wait_for_fence(self.current_frame)?;
 
         let frame_image_semaphore = sync_objects.image_available_semaphores
             [self.current_frame % sync_objects.max_frames_in_flight];
 
-        let (image_index, _is_suboptimal) = unsafe {
-            self.swapchain_loader
-                .acquire_next_image(
-                    swapchain.swapchain,
-                    u64::MAX,
-                    frame_image_semaphore,
-                    vk::Fence::null(),
-                )
-                .map_err(|e| anyhow::anyhow!("Failed to acquire swapchain image: {}", e))?
-        };
-
         let frame_sync = sync_objects.get_frame_sync_objects(self.current_frame);
+        let acquired = unsafe {
+            self.swapchain_loader.acquire_next_image(
+                swapchain.swapchain,
+                u64::MAX,
+                frame_image_semaphore,
+                vk::Fence::null(),
+            )
+        };
+        let image_index = match acquired {
+            Ok((_, true)) if swapchain.suboptimal_policy.drops_frame() => {
+                self.out_of_date = true;
+                // Nothing is rendered, but the acquire semaphore has to be waited on before
+                // it can be signaled again.
+                sync_objects.reset_fence(self.current_frame)?;
+                let wait_stages = [vk::PipelineStageFlags::ALL_COMMANDS];
+                let submit_info = vk::SubmitInfo::default()
+                    .wait_semaphores(std::slice::from_ref(&frame_image_semaphore))
+                    .wait_dst_stage_mask(&wait_stages);
+                unsafe {
+                    self.device
+                        .queue_submit(
+                            logical_device.graphics_queue,
+                            &[submit_info],
+                            frame_sync.in_flight_fence,
+                        )
+                        .map_err(|e| anyhow::anyhow!("Failed to submit dropped frame: {}", e))?;
+                }
+                return Ok(());
+            }
+            Ok((image_index, suboptimal)) => {
+                if suboptimal && swapchain.suboptimal_policy.recreates() {
+                    self.out_of_date = true;
+                }
+                image_index
+            }
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                self.out_of_date = true;
+                return Ok(());
+            }
+            Err(vk::Result::ERROR_SURFACE_LOST_KHR) => {
+                self.surface_lost = true;
+                return Ok(());
+            }
+            Err(e) => return Err(anyhow::anyhow!("Failed to acquire swapchain image: {}", e)),
+        };
 
         sync_objects.reset_fence(self.current_frame)?;
 
         self.record_command_buffer(
             command_pool,
-            render_pass,
-            framebuffers,
-            swapchain,
-            pipeline,
+            FrameTarget::RenderPass {
+                render_pass,
+                framebuffer: framebuffers.get_framebuffer(image_index as usize),
+            },
+            swapchain.extent,
             image_index as usize,
+            record,
         )?;
 
         self.submit_command_buffer(
@@ -82,60 +230,203 @@ impl VulkanRenderer {
         Ok(())
     }
 
-    fn record_command_buffer(
-        &self,
+    /// Like `draw_frame`, but renders into `targets[current_frame]` and leaves acquiring and
+    /// presenting to `present_thread`, so a blocking present doesn't stall this thread.
+    /// `command_pool` needs one command buffer and `targets` one entry per frame in flight,
+    /// matching the present thread's sources.
+    pub fn draw_frame_threaded(
+        &mut self,
+        logical_device: &VulkanDevice,
+        targets: &[VulkanOffscreenTarget],
         command_pool: &VulkanCommandPool,
-        render_pass: &VulkanRenderPass,
-        framebuffers: &VulkanFramebuffers,
-        swapchain: &VulkanSwapchain,
         pipeline: &VulkanPipeline,
-        image_index: usize,
+        present_thread: &mut PresentThread,
     ) -> Result<()> {
-        command_pool.reset_command_buffer(image_index)?;
-        command_pool.begin_command_buffer(image_index)?;
+        self.draw_frame_threaded_with(
+            logical_device,
+            targets,
+            command_pool,
+            present_thread,
+            |render_pass| draw_triangle(render_pass, pipeline),
+        )
+    }
 
-        command_pool.begin_render_pass(
-            image_index,
-            render_pass,
-            framebuffers.get_framebuffer(image_index),
-            &swapchain.extent,
-            [0.1, 0.1, 0.1, 1.0],
-        );
-
-        pipeline.bind(*command_pool.get_command_buffer(image_index));
-
-        let viewport = ash::vk::Viewport {
-            x: 0.0,
-            y: 0.0,
-            width: swapchain.extent.width as f32,
-            height: swapchain.extent.height as f32,
-            min_depth: 0.0,
-            max_depth: 1.0,
+    /// Like `draw_frame_threaded`, with `record` recording the frame's draws inside the
+    /// target's render pass, viewport and scissor already set.
+    pub fn draw_frame_threaded_with(
+        &mut self,
+        logical_device: &VulkanDevice,
+        targets: &[VulkanOffscreenTarget],
+        command_pool: &VulkanCommandPool,
+        present_thread: &mut PresentThread,
+        record: impl FnOnce(&mut RenderPassEncoder),
+    ) -> Result<()> {
+        self.draw_frame_threaded_into(
+            logical_device,
+            targets,
+            command_pool,
+            present_thread,
+            false,
+            record,
+        )
+    }
+
+    /// Like `draw_frame_threaded_with`, but records the frame with dynamic rendering into the
+    /// target's image instead of its render pass, which shader object draws require. Needs
+    /// `VulkanDeviceFeatures::dynamic_rendering`.
+    pub fn draw_frame_threaded_dynamic_with(
+        &mut self,
+        logical_device: &VulkanDevice,
+        targets: &[VulkanOffscreenTarget],
+        command_pool: &VulkanCommandPool,
+        present_thread: &mut PresentThread,
+        record: impl FnOnce(&mut RenderPassEncoder),
+    ) -> Result<()> {
+        if !logical_device.features.dynamic_rendering {
+            return Err(anyhow::anyhow!(
+                "Dynamic rendering is not enabled on this device"
+            ));
+        }
+        self.draw_frame_threaded_into(
+            logical_device,
+            targets,
+            command_pool,
+            present_thread,
+            true,
+            record,
+        )
+    }
+
+    fn draw_frame_threaded_into(
+        &mut self,
+        logical_device: &VulkanDevice,
+        targets: &[VulkanOffscreenTarget],
+        command_pool: &VulkanCommandPool,
+        present_thread: &mut PresentThread,
+        dynamic_rendering: bool,
+        record: impl FnOnce(&mut RenderPassEncoder),
+    ) -> Result<()> {
+        let frame = self.current_frame;
+        if self.latency_mode == LatencyMode::LowLatency {
+            // Present wait would need the swapchain, which the present thread owns, so only
+            // the GPU side of the previous frame is waited for.
+            present_thread.begin_frame(self.previous_frame())?;
+        }
+        present_thread.begin_frame(frame)?;
+
+        let target = &targets[frame];
+        let frame_target = if dynamic_rendering {
+            // Left in the same layout as the target's render pass leaves it.
+            FrameTarget::Rendering(&target.image)
+        } else {
+            FrameTarget::RenderPass {
+                render_pass: &target.render_pass,
+                framebuffer: target.framebuffer,
+            }
         };
+        self.record_command_buffer(command_pool, frame_target, target.extent(), frame, record)?;
+
+        let signal_semaphores = [present_thread.render_finished_semaphore(frame)];
+        let submit_info = vk::SubmitInfo::default()
+            .command_buffers(std::slice::from_ref(command_pool.get_command_buffer(frame)))
+            .signal_semaphores(&signal_semaphores);
+        present_thread.submit(
+            logical_device.graphics_queue,
+            std::slice::from_ref(&submit_info),
+            vk::Fence::null(),
+        )?;
+        present_thread.present_with_damage(frame, std::mem::take(&mut self.present_damage))?;
+
+        self.current_frame = (self.current_frame + 1) % self.max_frames_in_flight;
+
+        Ok(())
+    }
+
+    /// True once, after `draw_frame` found the surface lost. Frames are skipped until the
+    /// caller recreates the surface and the swapchain from the window.
+    pub fn take_surface_lost(&mut self) -> bool {
+        std::mem::take(&mut self.surface_lost)
+    }
+
+    /// True once, after `draw_frame` found the swapchain out of date, or suboptimal when
+    /// its `SuboptimalPolicy` recreates. The caller should recreate the swapchain before the
+    /// next frame.
+    pub fn take_out_of_date(&mut self) -> bool {
+        std::mem::take(&mut self.out_of_date)
+    }
 
-        let scissor = ash::vk::Rect2D {
-            offset: ash::vk::Offset2D { x: 0, y: 0 },
-            extent: swapchain.extent,
+    fn previous_frame(&self) -> usize {
+        (self.current_frame + self.max_frames_in_flight - 1) % self.max_frames_in_flight
+    }
+
+    /// Low-latency limiter: waits until the last presented frame is on screen, or until its
+    /// GPU work has finished when present wait isn't supported.
+    fn wait_for_previous_frame(
+        &self,
+        swapchain: &VulkanSwapchain,
+        sync_objects: &VulkanSyncObjects,
+    ) -> Result<()> {
+        let Some(present_wait) = &self.present_wait else {
+            return sync_objects.wait_for_fence(self.previous_frame());
         };
+        if self.last_present_id == 0 {
+            return Ok(());
+        }
 
-        unsafe {
-            command_pool.device.cmd_set_viewport(
-                *command_pool.get_command_buffer(image_index),
-                0,
-                &[viewport],
-            );
-
-            command_pool.device.cmd_set_scissor(
-                *command_pool.get_command_buffer(image_index),
-                0,
-                &[scissor],
-            );
+        // Bounded so a minimized or occluded window, which may never show the frame, doesn't
+        // hang the render loop.
+        const PRESENT_WAIT_TIMEOUT_NS: u64 = 100_000_000;
+        match unsafe {
+            present_wait.wait_for_present(
+                swapchain.swapchain,
+                self.last_present_id,
+                PRESENT_WAIT_TIMEOUT_NS,
+            )
+        } {
+            Ok(()) | Err(vk::Result::TIMEOUT) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => Ok(()),
+            Err(e) => Err(anyhow::anyhow!("Failed to wait for present: {}", e)),
         }
+    }
 
-        command_pool.draw(image_index, 3, 1);
+    fn record_command_buffer(
+        &self,
+        command_pool: &VulkanCommandPool,
+        target: FrameTarget,
+        extent: vk::Extent2D,
+        image_index: usize,
+        record: impl FnOnce(&mut RenderPassEncoder),
+    ) -> Result<()> {
+        let mut encoder = command_pool.encoder(image_index)?;
+
+        {
+            let clear_color = [0.1, 0.1, 0.1, 1.0];
+            let mut render_pass = match target {
+                FrameTarget::RenderPass {
+                    render_pass,
+                    framebuffer,
+                } => encoder.begin_render_pass(render_pass, framebuffer, extent, clear_color)?,
+                FrameTarget::Rendering(image) => encoder.begin_rendering(
+                    image,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    clear_color,
+                )?,
+            };
+            render_pass.set_viewport_and_scissor(extent);
+            // Dynamic state set before binding a pipeline stays valid for it, as long as no
+            // pipeline with a fixed mask is bound in between.
+            if let Some(extended_dynamic_state3) = &self.extended_dynamic_state3 {
+                unsafe {
+                    extended_dynamic_state3.cmd_set_color_write_mask(
+                        render_pass.command_buffer(),
+                        0,
+                        &[self.channel_isolation.color_write_mask()],
+                    );
+                }
+            }
+            record(&mut render_pass);
+        }
 
-        command_pool.end_render_pass(image_index);
-        command_pool.end_command_buffer(image_index)?;
+        encoder.finish()?;
 
         Ok(())
     }
@@ -156,7 +447,7 @@ impl VulkanRenderer {
         let submit_info = vk::SubmitInfo::default()
             .wait_semaphores(&wait_semaphores)
             .wait_dst_stage_mask(&wait_stages)
-            .command_buffers(std::slice::from_ref(&command_buffer))
+            .command_buffers(std::slice::from_ref(command_buffer))
             .signal_semaphores(&signal_semaphores);
 
         unsafe {
@@ -172,8 +463,14 @@ impl VulkanRenderer {
         Ok(())
     }
 
+    /// Hints which parts of the next presented frame changed. Ignored unless
+    /// `VK_KHR_incremental_present` is enabled; the whole frame must still be rendered.
+    pub fn set_present_damage(&mut self, damage: DamageRegion) {
+        self.present_damage = damage;
+    }
+
     fn present_frame(
-        &self,
+        &mut self,
         logical_device: &VulkanDevice,
         swapchain: &VulkanSwapchain,
         image_index: u32,
@@ -183,17 +480,53 @@ impl VulkanRenderer {
         let swapchains = [swapchain.swapchain];
         let image_indices = [image_index];
 
-        let present_info = vk::PresentInfoKHR::default()
+        let present_ids = [self.last_present_id + 1];
+        let mut present_id_info = vk::PresentIdKHR::default().present_ids(&present_ids);
+
+        let mut present_info = vk::PresentInfoKHR::default()
             .wait_semaphores(&wait_semaphores)
             .swapchains(&swapchains)
             .image_indices(&image_indices);
+        if self.present_wait.is_some() {
+            present_info = present_info.push_next(&mut present_id_info);
+            self.last_present_id += 1;
+        }
 
-        unsafe {
+        let damage = std::mem::take(&mut self.present_damage);
+        let rects = self
+            .incremental_present
+            .then(|| damage.present_rects(swapchain.extent))
+            .flatten();
+        let regions = [vk::PresentRegionKHR::default().rectangles(rects.as_deref().unwrap_or(&[]))];
+        let mut present_regions = vk::PresentRegionsKHR::default().regions(&regions);
+        if rects.is_some() {
+            present_info = present_info.push_next(&mut present_regions);
+        }
+
+        match unsafe {
             self.swapchain_loader
                 .queue_present(logical_device.present_queue.unwrap(), &present_info)
-                .map_err(|e| anyhow::anyhow!("Failed to present swapchain image: {}", e))?;
+        } {
+            Ok(suboptimal) => {
+                if suboptimal && swapchain.suboptimal_policy.recreates() {
+                    self.out_of_date = true;
+                }
+                Ok(())
+            }
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                self.out_of_date = true;
+                Ok(())
+            }
+            Err(vk::Result::ERROR_SURFACE_LOST_KHR) => {
+                self.surface_lost = true;
+                Ok(())
+            }
+            Err(e) => Err(anyhow::anyhow!("Failed to present swapchain image: {}", e)),
         }
-
-        Ok(())
     }
 }
+
+fn draw_triangle(render_pass: &mut RenderPassEncoder, pipeline: &VulkanPipeline) {
+    render_pass.bind_pipeline(pipeline.pipeline);
+    render_pass.draw(3, 1, 0, 0);
+}
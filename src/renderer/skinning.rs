@@ -0,0 +1,276 @@
+use anyhow::Result;
+use ash::vk;
+use glam::{Mat3, Mat4, Vec3};
+
+use crate::pipeline::{SkinVertex, VulkanComputePipeline, VulkanComputePipelineBuilder};
+use crate::renderer::GpuVertex;
+use crate::vulkan::{
+    VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanPhysicalDevice,
+};
+
+/// Skins `vertices` on the CPU exactly like `skinning.comp`, for tests and as a fallback.
+pub fn skin_vertices(
+    vertices: &[GpuVertex],
+    skin: &[SkinVertex],
+    joints: &[Mat4],
+) -> Vec<GpuVertex> {
+    vertices
+        .iter()
+        .zip(skin)
+        .map(|(vertex, skin)| {
+            let matrix = skin
+                .joints
+                .iter()
+                .zip(skin.weights)
+                .map(|(&joint, weight)| joints[joint as usize] * weight)
+                .fold(Mat4::ZERO, |sum, joint| sum + joint);
+
+            GpuVertex {
+                position: matrix
+                    .transform_point3(Vec3::from(vertex.position))
+                    .to_array(),
+                normal: (Mat3::from_mat4(matrix) * Vec3::from(vertex.normal))
+                    .normalize_or_zero()
+                    .to_array(),
+                uv: vertex.uv,
+            }
+        })
+        .collect()
+}
+
+/// Compute pipeline shared by every `SkinnedMesh`.
+pub struct SkinningPipeline {
+    pipeline: VulkanComputePipeline,
+    layout: VulkanDescriptorSetLayout,
+}
+
+impl SkinningPipeline {
+    pub fn new(device: &VulkanDevice) -> Result<Self> {
+        let layout = VulkanDescriptorSetLayout::new(
+            device,
+            &(0..4)
+                .map(|binding| {
+                    VulkanDescriptorSetLayout::binding(
+                        binding,
+                        vk::DescriptorType::STORAGE_BUFFER,
+                        vk::ShaderStageFlags::COMPUTE,
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )?;
+
+        let pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/skinning.comp.spv"), None)?
+            .with_descriptor_set_layout(layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(std::mem::size_of::<u32>() as u32),
+            )
+            .build()?;
+
+        Ok(Self { pipeline, layout })
+    }
+}
+
+/// Mesh skinned by a compute pre-pass into `output`, a `GpuVertex` buffer in model space.
+///
+/// Skinning once per frame lets the shadow, depth and main passes draw `output` with their
+/// regular static-mesh pipelines instead of each re-skinning in the vertex shader, and leaves
+/// animated positions in a buffer that acceleration structures can be refit from.
+/// `GpuCommandGenerator::add_state` takes `output.buffer` as a state vertex buffer.
+pub struct SkinnedMesh {
+    pub output: VulkanBuffer,
+    _rest_vertices: VulkanBuffer,
+    _skin_vertices: VulkanBuffer,
+    joints: VulkanBuffer,
+    _descriptor_pool: VulkanDescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    vertex_count: u32,
+    max_joints: u32,
+}
+
+impl SkinnedMesh {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        pipeline: &SkinningPipeline,
+        vertices: &[GpuVertex],
+        skin: &[SkinVertex],
+        max_joints: u32,
+    ) -> Result<Self> {
+        if vertices.len() != skin.len() {
+            return Err(anyhow::anyhow!(
+                "Skinned mesh has {} vertices but {} skin vertices",
+                vertices.len(),
+                skin.len()
+            ));
+        }
+        if let Some(joint) = skin
+            .iter()
+            .flat_map(|skin| skin.joints)
+            .find(|&joint| joint >= max_joints)
+        {
+            return Err(anyhow::anyhow!(
+                "Skin references joint {} beyond the {} joint palette",
+                joint,
+                max_joints
+            ));
+        }
+
+        let storage = |size: usize| {
+            VulkanBuffer::new_host_visible(
+                device,
+                physical_device,
+                size.max(1) as vk::DeviceSize,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+            )
+        };
+
+        let rest_vertices = storage(std::mem::size_of_val(vertices))?;
+        rest_vertices.write(0, vertices)?;
+
+        let skin_vertices = storage(std::mem::size_of_val(skin))?;
+        skin_vertices.write(0, skin)?;
+
+        let joints = storage(max_joints.max(1) as usize * std::mem::size_of::<Mat4>())?;
+        joints.write(0, &vec![Mat4::IDENTITY; max_joints.max(1) as usize])?;
+
+        let output = VulkanBuffer::new(
+            device,
+            physical_device,
+            std::mem::size_of_val(vertices).max(1) as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::VERTEX_BUFFER
+                | device.features.acceleration_structure_input_usage(),
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let descriptor_pool = VulkanDescriptorPool::for_layout(device, &pipeline.layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&pipeline.layout)?;
+        for (binding, buffer) in [&rest_vertices, &skin_vertices, &joints, &output]
+            .into_iter()
+            .enumerate()
+        {
+            descriptor_pool.write_buffer(
+                descriptor_set,
+                binding as u32,
+                vk::DescriptorType::STORAGE_BUFFER,
+                buffer,
+            );
+        }
+
+        Ok(Self {
+            output,
+            _rest_vertices: rest_vertices,
+            _skin_vertices: skin_vertices,
+            joints,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            vertex_count: vertices.len() as u32,
+            max_joints,
+        })
+    }
+
+    pub fn vertex_count(&self) -> u32 {
+        self.vertex_count
+    }
+
+    /// Writes the joint palette for the next `record`. No in-flight frame may still be
+    /// skinning with the previous palette.
+    pub fn set_joints(&self, joints: &[Mat4]) -> Result<()> {
+        if joints.len() as u32 > self.max_joints {
+            return Err(anyhow::anyhow!(
+                "{} joints exceed the {} joint palette",
+                joints.len(),
+                self.max_joints
+            ));
+        }
+        self.joints.write(0, joints)
+    }
+
+    /// Records skinning, then a barrier making `output` readable as vertices and by shaders.
+    /// Must be recorded outside a render pass, before the passes drawing the mesh.
+    pub fn record(&self, command_buffer: vk::CommandBuffer, pipeline: &SkinningPipeline) {
+        // The previous frame's passes may still be drawing the output.
+        self.output.cmd_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::VERTEX_INPUT | vk::PipelineStageFlags::VERTEX_SHADER,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ | vk::AccessFlags::SHADER_READ,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+        );
+
+        pipeline.pipeline.bind(command_buffer);
+        pipeline
+            .pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[self.descriptor_set]);
+        pipeline
+            .pipeline
+            .push_constants(command_buffer, &self.vertex_count);
+        pipeline
+            .pipeline
+            .dispatch(command_buffer, self.vertex_count.div_ceil(64).max(1), 1, 1);
+
+        self.output.cmd_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::VERTEX_INPUT | vk::PipelineStageFlags::VERTEX_SHADER,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ | vk::AccessFlags::SHADER_READ,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ShaderReflection;
+
+    fn vertex(position: [f32; 3]) -> GpuVertex {
+        GpuVertex {
+            position,
+            normal: [0.0, 1.0, 0.0],
+            uv: [0.25, 0.75],
+        }
+    }
+
+    fn bound_to(joints: [u32; 2], weights: [f32; 2]) -> SkinVertex {
+        SkinVertex {
+            joints: [joints[0], joints[1], 0, 0],
+            weights: [weights[0], weights[1], 0.0, 0.0],
+        }
+    }
+
+    #[test]
+    fn blends_joint_transforms_by_weight() {
+        let joints = [
+            Mat4::IDENTITY,
+            Mat4::from_translation(Vec3::new(2.0, 0.0, 0.0)),
+            Mat4::from_rotation_z(std::f32::consts::FRAC_PI_2),
+        ];
+        let vertices = [vertex([1.0, 0.0, 0.0]), vertex([1.0, 0.0, 0.0])];
+        let skin = [bound_to([0, 1], [0.5, 0.5]), bound_to([2, 0], [1.0, 0.0])];
+
+        let skinned = skin_vertices(&vertices, &skin, &joints);
+
+        assert!(Vec3::from(skinned[0].position).abs_diff_eq(Vec3::new(2.0, 0.0, 0.0), 1e-5));
+        assert!(Vec3::from(skinned[1].position).abs_diff_eq(Vec3::new(0.0, 1.0, 0.0), 1e-5));
+        assert!(Vec3::from(skinned[1].normal).abs_diff_eq(Vec3::new(-1.0, 0.0, 0.0), 1e-5));
+        assert_eq!(skinned[1].uv, [0.25, 0.75]);
+    }
+
+    #[test]
+    fn shader_layout_matches_skinning_pipeline() {
+        let reflection =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/skinning.comp.spv")).unwrap();
+
+        assert_eq!(reflection.layout_bindings(0).len(), 4);
+        assert_eq!(reflection.push_constant_size, Some(4));
+        // Vertices are read as 8 packed floats.
+        assert_eq!(std::mem::size_of::<GpuVertex>(), 32);
+        assert_eq!(std::mem::size_of::<SkinVertex>(), 32);
+    }
+}
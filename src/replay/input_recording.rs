@@ -0,0 +1,331 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use winit::event::{MouseButton, MouseScrollDelta, WindowEvent};
+use winit::keyboard::{ModifiersState, PhysicalKey};
+
+use crate::animation::CameraPose;
+
+pub const INPUT_RECORDING_VERSION: u32 = 1;
+
+/// A window event that can change what a frame renders, without winit's device ids and
+/// platform-specific key data so it can be saved and replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    Resized {
+        width: u32,
+        height: u32,
+    },
+    Focused(bool),
+    Key {
+        key: PhysicalKey,
+        pressed: bool,
+        repeat: bool,
+    },
+    Modifiers(ModifiersState),
+    CursorMoved {
+        x: f64,
+        y: f64,
+    },
+    MouseButton {
+        button: MouseButton,
+        pressed: bool,
+    },
+    MouseWheel(MouseScrollDelta),
+}
+
+impl InputEvent {
+    /// The replayable part of `event`, or `None` for events that don't affect rendering.
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        Some(match event {
+            WindowEvent::Resized(size) => Self::Resized {
+                width: size.width,
+                height: size.height,
+            },
+            WindowEvent::Focused(focused) => Self::Focused(*focused),
+            WindowEvent::KeyboardInput { event, .. } => Self::Key {
+                key: event.physical_key,
+                pressed: event.state.is_pressed(),
+                repeat: event.repeat,
+            },
+            WindowEvent::ModifiersChanged(modifiers) => Self::Modifiers(modifiers.state()),
+            WindowEvent::CursorMoved { position, .. } => Self::CursorMoved {
+                x: position.x,
+                y: position.y,
+            },
+            WindowEvent::MouseInput { state, button, .. } => Self::MouseButton {
+                button: *button,
+                pressed: state.is_pressed(),
+            },
+            WindowEvent::MouseWheel { delta, .. } => Self::MouseWheel(*delta),
+            _ => return None,
+        })
+    }
+}
+
+/// Everything outside the renderer that one frame depended on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrameInput {
+    pub frame: u64,
+    /// Seconds since the previous frame, as measured while recording.
+    pub delta_time: f32,
+    /// Seconds since the first frame, accumulated in `f64` so it doesn't lose precision.
+    pub time: f64,
+    /// Seed for anything random this frame; see `frame_seed`.
+    pub seed: u64,
+    /// Camera the frame was rendered with, after applying `events`.
+    pub camera: Option<CameraPose>,
+    /// Window events received before this frame, in order.
+    pub events: Vec<InputEvent>,
+}
+
+/// Per-frame inputs of a session, saved as RON. Floats round-trip exactly through RON, so
+/// replaying a recording feeds the application bit-identical values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputRecording {
+    pub version: u32,
+    pub session_seed: u64,
+    pub frames: Vec<FrameInput>,
+}
+
+impl InputRecording {
+    pub fn to_ron(&self) -> Result<String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| anyhow::anyhow!("Failed to serialize input recording: {}", e))
+    }
+
+    pub fn from_ron(source: &str) -> Result<Self> {
+        let recording: Self = ron::from_str(source)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input recording: {}", e))?;
+        if recording.version != INPUT_RECORDING_VERSION {
+            return Err(anyhow::anyhow!(
+                "Unsupported input recording version {}",
+                recording.version
+            ));
+        }
+        Ok(recording)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!("Failed to read input recording {}: {}", path.display(), e)
+        })?;
+        Self::from_ron(&source)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_ron()?).map_err(|e| {
+            anyhow::anyhow!("Failed to write input recording {}: {}", path.display(), e)
+        })
+    }
+
+    /// Total recorded time in seconds.
+    pub fn duration(&self) -> f64 {
+        self.frames.last().map_or(0.0, |frame| frame.time)
+    }
+}
+
+/// Seed for `frame` of a session, so every frame gets an independent but reproducible seed
+/// (SplitMix64 over the session seed and frame index).
+pub fn frame_seed(session_seed: u64, frame: u64) -> u64 {
+    let mut z =
+        session_seed.wrapping_add(frame.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Collects window events and per-frame state into an `InputRecording`.
+///
+/// Feed it every window event with `record_event`, then call `end_frame` once per frame with
+/// the measured frame time and use the returned inputs for the frame, exactly as a replay
+/// would.
+#[derive(Debug, Clone)]
+pub struct InputRecorder {
+    recording: InputRecording,
+    pending_events: Vec<InputEvent>,
+    time: f64,
+}
+
+impl InputRecorder {
+    pub fn new(session_seed: u64) -> Self {
+        Self {
+            recording: InputRecording {
+                version: INPUT_RECORDING_VERSION,
+                session_seed,
+                frames: Vec::new(),
+            },
+            pending_events: Vec::new(),
+            time: 0.0,
+        }
+    }
+
+    pub fn record_event(&mut self, event: &WindowEvent) {
+        if let Some(event) = InputEvent::from_window_event(event) {
+            self.pending_events.push(event);
+        }
+    }
+
+    /// Records a frame with the events received since the last one.
+    pub fn end_frame(&mut self, delta_time: f32, camera: Option<CameraPose>) -> &FrameInput {
+        let frame = self.recording.frames.len() as u64;
+        self.time += delta_time as f64;
+        self.recording.frames.push(FrameInput {
+            frame,
+            delta_time,
+            time: self.time,
+            seed: frame_seed(self.recording.session_seed, frame),
+            camera,
+            events: std::mem::take(&mut self.pending_events),
+        });
+        &self.recording.frames[frame as usize]
+    }
+
+    pub fn recording(&self) -> &InputRecording {
+        &self.recording
+    }
+
+    /// The recording so far. Events received after the last `end_frame` are dropped.
+    pub fn finish(self) -> InputRecording {
+        self.recording
+    }
+}
+
+/// Steps through an `InputRecording` one frame at a time. While replaying, ignore live window
+/// input and the measured frame time, and use each `FrameInput` instead.
+#[derive(Debug, Clone)]
+pub struct InputPlayer {
+    pub looping: bool,
+    recording: InputRecording,
+    next: usize,
+}
+
+impl InputPlayer {
+    pub fn new(recording: InputRecording) -> Self {
+        Self {
+            looping: false,
+            recording,
+            next: 0,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(InputRecording::load(path)?))
+    }
+
+    /// Inputs for the next frame, or `None` once the recording has ended.
+    pub fn next_frame(&mut self) -> Option<&FrameInput> {
+        if self.looping && self.next >= self.recording.frames.len() {
+            self.next = 0;
+        }
+        let frame = self.recording.frames.get(self.next)?;
+        self.next += 1;
+        Some(frame)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.next >= self.recording.frames.len()
+    }
+
+    pub fn restart(&mut self) {
+        self.next = 0;
+    }
+
+    pub fn recording(&self) -> &InputRecording {
+        &self.recording
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+    use winit::keyboard::KeyCode;
+
+    fn record_session() -> InputRecording {
+        let mut recorder = InputRecorder::new(42);
+        recorder.record_event(&WindowEvent::Focused(true));
+        recorder.record_event(&WindowEvent::ModifiersChanged(Default::default()));
+        recorder.end_frame(
+            0.1,
+            Some(CameraPose {
+                position: Vec3::new(1.0 / 3.0, -0.0, f32::MIN_POSITIVE),
+                target: Vec3::ZERO,
+                fov_y: std::f32::consts::FRAC_PI_3,
+            }),
+        );
+        recorder.record_event(&WindowEvent::CloseRequested);
+        recorder.end_frame(1.0 / 60.0, None);
+        recorder.finish()
+    }
+
+    #[test]
+    fn records_events_per_frame_with_seeds() {
+        let recording = record_session();
+
+        assert_eq!(recording.frames.len(), 2);
+        assert_eq!(
+            recording.frames[0].events,
+            vec![
+                InputEvent::Focused(true),
+                InputEvent::Modifiers(ModifiersState::empty())
+            ]
+        );
+        // Events that don't affect rendering aren't kept.
+        assert!(recording.frames[1].events.is_empty());
+        assert_eq!(
+            recording.frames[1].time,
+            0.1f32 as f64 + (1.0f32 / 60.0) as f64
+        );
+        assert_eq!(recording.frames[1].seed, frame_seed(42, 1));
+        assert_ne!(frame_seed(42, 0), frame_seed(42, 1));
+        assert_ne!(frame_seed(42, 0), frame_seed(43, 0));
+    }
+
+    #[test]
+    fn round_trips_bit_exactly() {
+        let mut recording = record_session();
+        recording.frames[1].events = vec![
+            InputEvent::Key {
+                key: PhysicalKey::Code(KeyCode::KeyW),
+                pressed: true,
+                repeat: false,
+            },
+            InputEvent::CursorMoved {
+                x: 0.1 + 0.2,
+                y: 1e-300,
+            },
+            InputEvent::MouseWheel(MouseScrollDelta::LineDelta(0.0, -1.5)),
+        ];
+
+        let loaded = InputRecording::from_ron(&recording.to_ron().unwrap()).unwrap();
+        assert_eq!(loaded, recording);
+
+        let camera = loaded.frames[0].camera.unwrap();
+        assert_eq!(camera.position.y.to_bits(), (-0.0f32).to_bits());
+        assert_eq!(camera.position.x.to_bits(), (1.0f32 / 3.0).to_bits());
+        assert_eq!(
+            loaded.frames[1].time.to_bits(),
+            recording.frames[1].time.to_bits()
+        );
+
+        let mut wrong_version = recording.clone();
+        wrong_version.version = INPUT_RECORDING_VERSION + 1;
+        assert!(InputRecording::from_ron(&wrong_version.to_ron().unwrap()).is_err());
+    }
+
+    #[test]
+    fn player_steps_through_frames() {
+        let mut player = InputPlayer::new(record_session());
+        assert_eq!(player.next_frame().unwrap().frame, 0);
+        assert_eq!(player.next_frame().unwrap().frame, 1);
+        assert!(player.next_frame().is_none());
+        assert!(player.is_finished());
+
+        player.looping = true;
+        assert!(!player.is_finished());
+        assert_eq!(player.next_frame().unwrap().frame, 0);
+    }
+}
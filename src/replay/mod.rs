@@ -0,0 +1,3 @@
+pub mod input_recording;
+
+pub use input_recording::*;
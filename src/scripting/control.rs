@@ -0,0 +1,234 @@
+use anyhow::Result;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How often blocked threads check whether the server is shutting down.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A runtime command received by a `ControlServer`. What each parameter or pass name refers
+/// to is up to the application, e.g. `"exposure"` to `TonemapSettings::exposure`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlCommand {
+    /// `set <name> <value>`
+    SetParameter { name: String, value: f32 },
+    /// `pass <name> on|off|toggle`; `enabled` is `None` for toggle.
+    SetPass { name: String, enabled: Option<bool> },
+    /// `reload_shaders`
+    ReloadShaders,
+    /// `screenshot [path]`
+    Screenshot { path: Option<PathBuf> },
+}
+
+impl ControlCommand {
+    pub fn parse(line: &str) -> Result<Self> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["set", name, value] => {
+                let value = value
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid value '{}'", value))?;
+                Ok(Self::SetParameter {
+                    name: name.to_string(),
+                    value,
+                })
+            }
+            ["pass", name, state] => {
+                let enabled = match *state {
+                    "on" => Some(true),
+                    "off" => Some(false),
+                    "toggle" => None,
+                    _ => return Err(anyhow::anyhow!("Expected on, off or toggle")),
+                };
+                Ok(Self::SetPass {
+                    name: name.to_string(),
+                    enabled,
+                })
+            }
+            ["reload_shaders"] => Ok(Self::ReloadShaders),
+            ["screenshot"] => Ok(Self::Screenshot { path: None }),
+            ["screenshot", path] => Ok(Self::Screenshot {
+                path: Some(PathBuf::from(path)),
+            }),
+            _ => Err(anyhow::anyhow!("Unknown command '{}'", line.trim())),
+        }
+    }
+}
+
+/// Accepts `ControlCommand`s over TCP so external tools can drive the renderer, e.g.
+/// `echo "set exposure 2" | nc localhost 7878`.
+///
+/// The protocol is one command per line; every line gets an `ok` or `error: <message>` reply.
+/// Commands are queued on background threads and handed out by `poll` on the render thread.
+pub struct ControlServer {
+    address: SocketAddr,
+    commands: Receiver<ControlCommand>,
+    running: Arc<AtomicBool>,
+    listener_thread: Option<JoinHandle<()>>,
+}
+
+impl ControlServer {
+    /// Listens on `address`; port 0 picks a free port, see `local_addr`.
+    pub fn bind(address: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(address)
+            .map_err(|e| anyhow::anyhow!("Failed to bind control server: {}", e))?;
+        let address = listener
+            .local_addr()
+            .map_err(|e| anyhow::anyhow!("Failed to get control server address: {}", e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| anyhow::anyhow!("Failed to configure control server: {}", e))?;
+
+        let (sender, commands) = mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+        let listener_running = running.clone();
+        let listener_thread = std::thread::Builder::new()
+            .name("control server".to_string())
+            .spawn(move || accept_connections(listener, sender, listener_running))
+            .map_err(|e| anyhow::anyhow!("Failed to spawn control server thread: {}", e))?;
+
+        Ok(Self {
+            address,
+            commands,
+            running,
+            listener_thread: Some(listener_thread),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Returns every command received since the last call without blocking.
+    pub fn poll(&self) -> Vec<ControlCommand> {
+        self.commands.try_iter().collect()
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.listener_thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn accept_connections(
+    listener: TcpListener,
+    sender: Sender<ControlCommand>,
+    running: Arc<AtomicBool>,
+) {
+    let mut connections = Vec::new();
+    while running.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let sender = sender.clone();
+                let running = running.clone();
+                connections.push(std::thread::spawn(move || {
+                    if let Err(e) = serve_connection(stream, sender, running) {
+                        eprintln!("Control connection failed: {}", e);
+                    }
+                }));
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => eprintln!("Failed to accept control connection: {}", e),
+        }
+        connections.retain(|connection: &JoinHandle<()>| !connection.is_finished());
+    }
+    for connection in connections {
+        let _ = connection.join();
+    }
+}
+
+fn serve_connection(
+    stream: TcpStream,
+    sender: Sender<ControlCommand>,
+    running: Arc<AtomicBool>,
+) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+
+    while running.load(Ordering::Relaxed) {
+        match reader.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            // A timeout may leave a partial line in `line`; keep it and read the rest.
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e),
+        }
+
+        if !line.trim().is_empty() {
+            let reply = match ControlCommand::parse(&line) {
+                Ok(command) => match sender.send(command) {
+                    Ok(()) => "ok".to_string(),
+                    Err(_) => "error: renderer stopped".to_string(),
+                },
+                Err(e) => format!("error: {}", e),
+            };
+            writeln!(writer, "{}", reply)?;
+        }
+        line.clear();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queues_commands_from_connections() {
+        let server = ControlServer::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(server.local_addr()).unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+
+        writer
+            .write_all(b"set exposure 2.5\npass bloom toggle\nfly away\nscreenshot shot.ppm\n")
+            .unwrap();
+        let mut replies = Vec::new();
+        for _ in 0..4 {
+            let mut reply = String::new();
+            reader.read_line(&mut reply).unwrap();
+            replies.push(reply.trim().to_string());
+        }
+        assert_eq!(
+            replies,
+            ["ok", "ok", "error: Unknown command 'fly away'", "ok"]
+        );
+
+        assert_eq!(
+            server.poll(),
+            [
+                ControlCommand::SetParameter {
+                    name: "exposure".to_string(),
+                    value: 2.5
+                },
+                ControlCommand::SetPass {
+                    name: "bloom".to_string(),
+                    enabled: None
+                },
+                ControlCommand::Screenshot {
+                    path: Some(PathBuf::from("shot.ppm"))
+                },
+            ]
+        );
+        assert!(server.poll().is_empty());
+
+        assert!(ControlCommand::parse("set exposure bright").is_err());
+        assert!(ControlCommand::parse("pass bloom maybe").is_err());
+        assert_eq!(
+            ControlCommand::parse("  reload_shaders \r\n").unwrap(),
+            ControlCommand::ReloadShaders
+        );
+    }
+}
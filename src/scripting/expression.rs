@@ -0,0 +1,236 @@
+use anyhow::Result;
+
+/// A numeric expression over the variable `t` (script time in seconds): numbers, `+ - * /`,
+/// unary minus, parentheses, `pi` and the functions `sin cos tan abs sqrt min max`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(f32),
+    Time,
+    Negate(Box<Expression>),
+    Binary(char, Box<Expression>, Box<Expression>),
+    Call(Function, Vec<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Abs,
+    Sqrt,
+    Min,
+    Max,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "sin" => Self::Sin,
+            "cos" => Self::Cos,
+            "tan" => Self::Tan,
+            "abs" => Self::Abs,
+            "sqrt" => Self::Sqrt,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            _ => return None,
+        })
+    }
+
+    fn arity(self) -> usize {
+        match self {
+            Self::Min | Self::Max => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut parser = Parser {
+            source,
+            position: 0,
+        };
+        let expression = parser.sum()?;
+        parser.skip_whitespace();
+        if parser.position < source.len() {
+            return Err(anyhow::anyhow!(
+                "Unexpected '{}' in '{}'",
+                &source[parser.position..],
+                source
+            ));
+        }
+        Ok(expression)
+    }
+
+    /// Whether the value changes over time, i.e. the expression mentions `t`.
+    pub fn is_animated(&self) -> bool {
+        match self {
+            Self::Number(_) => false,
+            Self::Time => true,
+            Self::Negate(operand) => operand.is_animated(),
+            Self::Binary(_, left, right) => left.is_animated() || right.is_animated(),
+            Self::Call(_, arguments) => arguments.iter().any(Self::is_animated),
+        }
+    }
+
+    pub fn evaluate(&self, time: f32) -> f32 {
+        match self {
+            Self::Number(value) => *value,
+            Self::Time => time,
+            Self::Negate(operand) => -operand.evaluate(time),
+            Self::Binary(operator, left, right) => {
+                let (left, right) = (left.evaluate(time), right.evaluate(time));
+                match operator {
+                    '+' => left + right,
+                    '-' => left - right,
+                    '*' => left * right,
+                    _ => left / right,
+                }
+            }
+            Self::Call(function, arguments) => {
+                let argument = |index: usize| arguments[index].evaluate(time);
+                match function {
+                    Function::Sin => argument(0).sin(),
+                    Function::Cos => argument(0).cos(),
+                    Function::Tan => argument(0).tan(),
+                    Function::Abs => argument(0).abs(),
+                    Function::Sqrt => argument(0).sqrt(),
+                    Function::Min => argument(0).min(argument(1)),
+                    Function::Max => argument(0).max(argument(1)),
+                }
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.source[self.position..];
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.source[self.position..].chars().next()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "Expected '{}' in '{}'",
+                expected,
+                self.source
+            ))
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expression> {
+        let mut left = self.product()?;
+        while let Some(operator @ ('+' | '-')) = self.peek() {
+            self.position += 1;
+            left = Expression::Binary(operator, Box::new(left), Box::new(self.product()?));
+        }
+        Ok(left)
+    }
+
+    fn product(&mut self) -> Result<Expression> {
+        let mut left = self.unary()?;
+        while let Some(operator @ ('*' | '/')) = self.peek() {
+            self.position += 1;
+            left = Expression::Binary(operator, Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression> {
+        if self.peek() == Some('-') {
+            self.position += 1;
+            return Ok(Expression::Negate(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expression> {
+        match self.peek() {
+            Some('(') => {
+                self.position += 1;
+                let expression = self.sum()?;
+                self.expect(')')?;
+                Ok(expression)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let rest = &self.source[self.position..];
+                let end = rest
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                    .unwrap_or(rest.len());
+                self.position += end;
+                rest[..end]
+                    .parse()
+                    .map(Expression::Number)
+                    .map_err(|_| anyhow::anyhow!("Invalid number '{}'", &rest[..end]))
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let rest = &self.source[self.position..];
+                let end = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                let name = &rest[..end];
+                self.position += end;
+                match name {
+                    "t" => Ok(Expression::Time),
+                    "pi" => Ok(Expression::Number(std::f32::consts::PI)),
+                    _ => {
+                        let function = Function::from_name(name)
+                            .ok_or_else(|| anyhow::anyhow!("Unknown name '{}'", name))?;
+                        self.expect('(')?;
+                        let mut arguments = vec![self.sum()?];
+                        while self.peek() == Some(',') {
+                            self.position += 1;
+                            arguments.push(self.sum()?);
+                        }
+                        self.expect(')')?;
+                        if arguments.len() != function.arity() {
+                            return Err(anyhow::anyhow!(
+                                "{} takes {} argument(s), got {}",
+                                name,
+                                function.arity(),
+                                arguments.len()
+                            ));
+                        }
+                        Ok(Expression::Call(function, arguments))
+                    }
+                }
+            }
+            Some(c) => Err(anyhow::anyhow!("Unexpected '{}' in '{}'", c, self.source)),
+            None => Err(anyhow::anyhow!("Missing value in '{}'", self.source)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_with_precedence_and_time() {
+        let expression = Expression::parse("1 + 2 * -(t - 0.5) / 4").unwrap();
+        assert!(expression.is_animated());
+        assert_eq!(expression.evaluate(2.5), 0.0);
+
+        let expression = Expression::parse("max(sin(pi / 2), 0.25) * 3").unwrap();
+        assert!(!expression.is_animated());
+        assert_eq!(expression.evaluate(0.0), 3.0);
+
+        for invalid in ["", "1 +", "(1", "foo", "min(1)", "1 2", "1..2"] {
+            assert!(Expression::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,7 @@
+pub mod control;
+pub mod expression;
+pub mod script;
+
+pub use control::*;
+pub use expression::*;
+pub use script::*;
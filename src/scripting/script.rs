@@ -0,0 +1,440 @@
+use anyhow::Result;
+use glam::{EulerRot, Quat, Vec3, Vec4};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::animation::CameraPose;
+use crate::scene::{Light, Scene};
+use crate::scripting::Expression;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    Camera,
+    Node(String),
+    Light(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Property {
+    Position,
+    LookAt,
+    Fov,
+    Translation,
+    Rotation,
+    Scale,
+    BaseColor,
+    AlphaCutoff,
+    Intensity,
+    Color,
+}
+
+impl Property {
+    fn parse(target: &Target, name: &str) -> Option<Self> {
+        Some(match (target, name) {
+            (Target::Camera, "position") => Self::Position,
+            (Target::Camera, "target") => Self::LookAt,
+            (Target::Camera, "fov") => Self::Fov,
+            (Target::Node(_), "translation") => Self::Translation,
+            (Target::Node(_), "rotation") => Self::Rotation,
+            (Target::Node(_), "scale") => Self::Scale,
+            (Target::Node(_), "base_color") => Self::BaseColor,
+            (Target::Node(_), "alpha_cutoff") => Self::AlphaCutoff,
+            (Target::Light(_), "intensity") => Self::Intensity,
+            (Target::Light(_), "color") => Self::Color,
+            _ => return None,
+        })
+    }
+
+    fn accepts(self, count: usize) -> bool {
+        match self {
+            Self::Fov | Self::AlphaCutoff | Self::Intensity => count == 1,
+            Self::Scale => count == 1 || count == 3,
+            Self::BaseColor => count == 4,
+            _ => count == 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Assignment {
+    line: usize,
+    target: Target,
+    property: Property,
+    values: Vec<Expression>,
+}
+
+/// A list of assignments to scene nodes, lights and the camera, evaluated every frame so
+/// values can be tweaked or animated without recompiling:
+///
+/// ```text
+/// # Orbit the camera and pulse the sun.
+/// camera position = 5 * sin(t), 2, 5 * cos(t)
+/// camera target = 0, 0, 0
+/// camera fov = 60
+/// node crate rotation = 0, t * 45, 0
+/// node "big crate" base_color = 1, 0.5 + 0.5 * sin(t), 0.5, 1
+/// light sun intensity = 3 + sin(t * 2)
+/// ```
+///
+/// Nodes: `translation`, `rotation` (Euler degrees, yaw-pitch-roll), `scale` (one or three
+/// values), `base_color` and `alpha_cutoff` (written as material overrides). Lights:
+/// `intensity` and `color`. Camera: `position`, `target` and `fov` (degrees). Values are
+/// `Expression`s over the time `t`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SceneScript {
+    assignments: Vec<Assignment>,
+}
+
+impl SceneScript {
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut assignments = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            assignments.push(
+                parse_assignment(line, line_number)
+                    .map_err(|e| anyhow::anyhow!("Line {}: {}", line_number, e))?,
+            );
+        }
+        Ok(Self { assignments })
+    }
+
+    /// Whether running the script at different times can give different results.
+    pub fn is_animated(&self) -> bool {
+        self.assignments
+            .iter()
+            .any(|assignment| assignment.values.iter().any(Expression::is_animated))
+    }
+
+    /// Applies every assignment at `time` seconds. Assignments after a failing one are still
+    /// applied; the first error is returned.
+    pub fn run(&self, scene: &mut Scene, camera: &mut CameraPose, time: f32) -> Result<()> {
+        let mut first_error = None;
+        for assignment in &self.assignments {
+            let values: Vec<f32> = assignment
+                .values
+                .iter()
+                .map(|value| value.evaluate(time))
+                .collect();
+            if let Err(e) = apply(assignment, &values, scene, camera) {
+                first_error.get_or_insert(anyhow::anyhow!("Line {}: {}", assignment.line, e));
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+fn parse_assignment(line: &str, line_number: usize) -> Result<Assignment> {
+    let (left, right) = line
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("Expected '<target> <property> = <values>'"))?;
+
+    let left = left.trim();
+    let (target, rest) = match left.split_once(char::is_whitespace) {
+        Some(("camera", rest)) => (Target::Camera, rest),
+        Some((kind @ ("node" | "light"), rest)) => {
+            let (name, rest) = parse_name(rest.trim())?;
+            let target = if kind == "node" {
+                Target::Node(name)
+            } else {
+                Target::Light(name)
+            };
+            (target, rest)
+        }
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Expected 'camera', 'node <name>' or 'light <name>'"
+            ));
+        }
+    };
+
+    let property_name = rest.trim();
+    let property = Property::parse(&target, property_name)
+        .ok_or_else(|| anyhow::anyhow!("Unknown property '{}'", property_name))?;
+
+    let values = split_values(right)
+        .into_iter()
+        .map(Expression::parse)
+        .collect::<Result<Vec<_>>>()?;
+    if !property.accepts(values.len()) {
+        return Err(anyhow::anyhow!(
+            "Wrong number of values ({}) for '{}'",
+            values.len(),
+            property_name
+        ));
+    }
+
+    Ok(Assignment {
+        line: line_number,
+        target,
+        property,
+        values,
+    })
+}
+
+/// A bare word or a double-quoted name, and the rest of the input.
+fn parse_name(input: &str) -> Result<(String, &str)> {
+    if let Some(quoted) = input.strip_prefix('"') {
+        let (name, rest) = quoted
+            .split_once('"')
+            .ok_or_else(|| anyhow::anyhow!("Unterminated name"))?;
+        return Ok((name.to_string(), rest));
+    }
+    let (name, rest) = input
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| anyhow::anyhow!("Missing property after '{}'", input))?;
+    Ok((name.to_string(), rest))
+}
+
+/// Splits at commas outside parentheses, so `max(a, b)` stays one value.
+fn split_values(input: &str) -> Vec<&str> {
+    let mut values = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in input.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                values.push(&input[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    values.push(&input[start..]);
+    values
+}
+
+fn apply(
+    assignment: &Assignment,
+    values: &[f32],
+    scene: &mut Scene,
+    camera: &mut CameraPose,
+) -> Result<()> {
+    let vec3 = || Vec3::new(values[0], values[1], values[2]);
+
+    let node = match &assignment.target {
+        Target::Camera => {
+            match assignment.property {
+                Property::Position => camera.position = vec3(),
+                Property::LookAt => camera.target = vec3(),
+                _ => camera.fov_y = values[0].to_radians(),
+            }
+            return Ok(());
+        }
+        Target::Node(name) | Target::Light(name) => {
+            let index = scene
+                .find(name)
+                .ok_or_else(|| anyhow::anyhow!("No node named '{}'", name))?;
+            &mut scene.nodes[index]
+        }
+    };
+
+    match assignment.property {
+        Property::Translation => node.transform.translation = vec3(),
+        Property::Rotation => {
+            node.transform.rotation = Quat::from_euler(
+                EulerRot::YXZ,
+                values[1].to_radians(),
+                values[0].to_radians(),
+                values[2].to_radians(),
+            );
+        }
+        Property::Scale if values.len() == 1 => node.transform.scale = Vec3::splat(values[0]),
+        Property::Scale => node.transform.scale = vec3(),
+        Property::BaseColor => {
+            node.material_overrides.base_color =
+                Some(Vec4::new(values[0], values[1], values[2], values[3]));
+        }
+        Property::AlphaCutoff => node.material_overrides.alpha_cutoff = Some(values[0]),
+        Property::Intensity | Property::Color => {
+            let light = node
+                .light
+                .as_mut()
+                .ok_or_else(|| anyhow::anyhow!("Node '{}' has no light", node.name))?;
+            let (Light::Directional { color, intensity }
+            | Light::Point {
+                color, intensity, ..
+            }
+            | Light::Spot {
+                color, intensity, ..
+            }) = light;
+            if assignment.property == Property::Intensity {
+                *intensity = values[0];
+            } else {
+                *color = vec3();
+            }
+        }
+        Property::Position | Property::LookAt | Property::Fov => unreachable!(),
+    }
+    Ok(())
+}
+
+/// A `SceneScript` loaded from a file and reparsed when the file changes.
+#[derive(Debug, Clone)]
+pub struct ScriptFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    script: SceneScript,
+}
+
+impl ScriptFile {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let modified = modified_time(&path);
+        let script = read_script(&path)?;
+        Ok(Self {
+            path,
+            modified,
+            script,
+        })
+    }
+
+    pub fn script(&self) -> &SceneScript {
+        &self.script
+    }
+
+    /// Reparses the file if its modification time changed. Returns whether the script was
+    /// replaced; on a parse error the previous script is kept and the error returned.
+    pub fn reload_if_changed(&mut self) -> Result<bool> {
+        let modified = modified_time(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return Ok(false);
+        }
+        // Record the new time even on failure so a broken file is not reparsed every poll.
+        self.modified = modified;
+        self.script = read_script(&self.path)?;
+        Ok(true)
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn read_script(path: &Path) -> Result<SceneScript> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read script {}: {}", path.display(), e))?;
+    SceneScript::parse(&source).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::SceneNode;
+
+    fn scene() -> Scene {
+        let mut scene = Scene::new();
+        scene.add_node(SceneNode::new("big crate"));
+        scene.add_node(SceneNode::new("sun").with_light(Light::Directional {
+            color: Vec3::ONE,
+            intensity: 1.0,
+        }));
+        scene
+    }
+
+    #[test]
+    fn applies_assignments_at_a_time() {
+        let script = SceneScript::parse(
+            "# comment\n\
+             camera position = 0, 2, 5 * cos(t)\n\
+             camera fov = 90\n\
+             node \"big crate\" translation = t, 0, -t  # trailing comment\n\
+             node \"big crate\" scale = 2\n\
+             node \"big crate\" base_color = 1, max(t, 0.5), 0, 1\n\
+             light sun intensity = 3\n",
+        )
+        .unwrap();
+        assert!(script.is_animated());
+
+        let mut scene = scene();
+        let mut camera = CameraPose {
+            position: Vec3::ZERO,
+            target: Vec3::ZERO,
+            fov_y: 1.0,
+        };
+        script.run(&mut scene, &mut camera, 0.0).unwrap();
+
+        assert_eq!(camera.position, Vec3::new(0.0, 2.0, 5.0));
+        assert_eq!(camera.fov_y, std::f32::consts::FRAC_PI_2);
+        let crate_node = &scene.nodes[0];
+        assert_eq!(crate_node.transform.translation, Vec3::ZERO);
+        assert_eq!(crate_node.transform.scale, Vec3::splat(2.0));
+        assert_eq!(
+            crate_node.material_overrides.base_color,
+            Some(Vec4::new(1.0, 0.5, 0.0, 1.0))
+        );
+        assert_eq!(
+            scene.nodes[1].light,
+            Some(Light::Directional {
+                color: Vec3::ONE,
+                intensity: 3.0
+            })
+        );
+
+        script.run(&mut scene, &mut camera, 2.0).unwrap();
+        assert_eq!(
+            scene.nodes[0].transform.translation,
+            Vec3::new(2.0, 0.0, -2.0)
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_line_numbers() {
+        let error = SceneScript::parse("camera fov = 60\nnode crate color = 1, 1, 1")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "Line 2: Unknown property 'color'");
+
+        assert!(SceneScript::parse("camera position = 1, 2").is_err());
+        assert!(SceneScript::parse("camera position").is_err());
+        assert!(SceneScript::parse("mesh crate scale = 1").is_err());
+
+        let script = SceneScript::parse(
+            "node missing scale = 1\nlight \"big crate\" intensity = 1\ncamera fov = 30",
+        )
+        .unwrap();
+        let mut scene = scene();
+        let mut camera = CameraPose {
+            position: Vec3::ZERO,
+            target: Vec3::ZERO,
+            fov_y: 1.0,
+        };
+        let error = script.run(&mut scene, &mut camera, 0.0).unwrap_err();
+        assert_eq!(error.to_string(), "Line 1: No node named 'missing'");
+        // Later assignments still ran.
+        assert_eq!(camera.fov_y, 30f32.to_radians());
+    }
+
+    #[test]
+    fn reloads_changed_files() {
+        let dir = std::env::temp_dir().join(format!("rve-script-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("scene.rvs");
+        std::fs::write(&path, "camera fov = 60").unwrap();
+
+        let mut file = ScriptFile::load(&path).unwrap();
+        assert!(!file.script().is_animated());
+        assert!(!file.reload_if_changed().unwrap());
+
+        // Make sure the modification time differs on coarse-grained file systems.
+        let later = SystemTime::now() + std::time::Duration::from_secs(2);
+        std::fs::write(&path, "camera fov = 60 + t").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(file.reload_if_changed().unwrap());
+        assert!(file.script().is_animated());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::Result;
+use ash::vk;
+use egui::epaint::{ClippedPrimitive, Primitive};
+use egui::{TexturesDelta, ViewportId};
+use winit::event::WindowEvent;
+use winit::window::Window;
+
+use crate::ui::{EguiRenderer, clip_rect_to_scissor};
+use crate::vulkan::{DamageRegion, DeletionQueue, VulkanDevice, VulkanPhysicalDevice};
+
+/// An egui context fed by winit events and drawn over the frame by an `EguiRenderer`.
+///
+/// Per frame: `run` the UI, then `record` outside the render pass that `draw` is recorded in.
+pub struct EguiOverlay {
+    pub context: egui::Context,
+    pub renderer: EguiRenderer,
+    pub visible: bool,
+    state: egui_winit::State,
+    primitives: Vec<ClippedPrimitive>,
+    textures_delta: TexturesDelta,
+    pixels_per_point: f32,
+    /// Screen areas covered by the UI this frame and the previous one, in points.
+    bounds: Vec<egui::Rect>,
+    previous_bounds: Vec<egui::Rect>,
+}
+
+impl EguiOverlay {
+    pub fn new(
+        device: &VulkanDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        window: &Window,
+    ) -> Result<Self> {
+        let context = egui::Context::default();
+        let state = egui_winit::State::new(
+            context.clone(),
+            ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            window.theme(),
+            None,
+        );
+
+        Ok(Self {
+            renderer: EguiRenderer::new(device, render_pass, extent)?,
+            context,
+            visible: true,
+            state,
+            primitives: Vec::new(),
+            textures_delta: TexturesDelta::default(),
+            pixels_per_point: window.scale_factor() as f32,
+            bounds: Vec::new(),
+            previous_bounds: Vec::new(),
+        })
+    }
+
+    /// Feeds a window event to egui. Returns true when egui consumed it, in which case the
+    /// application shouldn't also treat it as camera or game input.
+    pub fn on_window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        if !self.visible {
+            return false;
+        }
+        self.state.on_window_event(window, event).consumed
+    }
+
+    /// Runs one egui pass and tessellates its output for `record` and `draw`. While hidden,
+    /// nothing is drawn, but texture changes are still collected.
+    pub fn run(&mut self, window: &Window, ui: impl FnMut(&mut egui::Ui)) {
+        let input = self.state.take_egui_input(window);
+        let output = if self.visible {
+            self.context.run_ui(input, ui)
+        } else {
+            self.context.run_ui(input, |_| {})
+        };
+
+        self.state
+            .handle_platform_output(window, output.platform_output);
+        self.textures_delta.append(output.textures_delta);
+        self.pixels_per_point = output.pixels_per_point;
+        self.primitives = if self.visible {
+            self.context
+                .tessellate(output.shapes, output.pixels_per_point)
+        } else {
+            Vec::new()
+        };
+
+        self.previous_bounds = std::mem::take(&mut self.bounds);
+        self.bounds = self
+            .primitives
+            .iter()
+            .map(|primitive| match &primitive.primitive {
+                Primitive::Mesh(mesh) => mesh.calc_bounds().intersect(primitive.clip_rect),
+                Primitive::Callback(_) => primitive.clip_rect,
+            })
+            .filter(|rect| rect.is_positive())
+            .collect();
+    }
+
+    /// Framebuffer area the UI may have changed since the previous frame: where it is drawn now
+    /// plus where it was drawn before. Only a useful present hint when the scene underneath
+    /// didn't change either.
+    pub fn damage(&self, extent: vk::Extent2D) -> DamageRegion {
+        let mut damage = DamageRegion::new();
+        for rect in self.previous_bounds.iter().chain(&self.bounds) {
+            // Grown a little so rounding to whole pixels never loses an edge.
+            if let Some(scissor) =
+                clip_rect_to_scissor(rect.expand(1.0), self.pixels_per_point, extent)
+            {
+                damage.add(scissor);
+            }
+        }
+        damage
+    }
+
+    /// Records texture uploads and copies the UI geometry. Must be recorded outside a render
+    /// pass; resources replaced this frame are retired once `frame` has completed.
+    pub fn record(
+        &mut self,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        command_buffer: vk::CommandBuffer,
+        frame: u64,
+        extent: vk::Extent2D,
+        deletion_queue: &mut DeletionQueue,
+    ) -> Result<()> {
+        self.renderer.update_textures(
+            device,
+            physical_device,
+            command_buffer,
+            frame,
+            &self.textures_delta,
+            deletion_queue,
+        )?;
+        self.textures_delta.clear();
+        self.renderer.upload(
+            device,
+            physical_device,
+            frame,
+            &self.primitives,
+            self.pixels_per_point,
+            extent,
+            deletion_queue,
+        )
+    }
+
+    /// Draws the UI inside the current render pass.
+    pub fn draw(&self, command_buffer: vk::CommandBuffer, extent: vk::Extent2D) {
+        self.renderer.draw(command_buffer, extent);
+    }
+}
+
+impl Drop for EguiOverlay {
+    fn drop(&mut self) {
+        // Unapplied texture changes are meaningless once the renderer is gone.
+        self.textures_delta.clear();
+    }
+}
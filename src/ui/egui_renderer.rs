@@ -0,0 +1,635 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use egui::epaint::{ClippedPrimitive, ImageData, ImageDelta, Primitive, Vertex};
+use egui::{TextureFilter, TextureId, TexturesDelta};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::pipeline::{VulkanPipeline, VulkanPipelineBuilder};
+use crate::vulkan::{
+    DeletionQueue, RecordCommands, VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout,
+    VulkanDevice, VulkanImage, VulkanPhysicalDevice, VulkanSampler,
+};
+
+/// egui's vertex layout: position and uv in points, then an sRGB color with premultiplied
+/// alpha.
+fn vertex_binding_description() -> vk::VertexInputBindingDescription {
+    vk::VertexInputBindingDescription::default()
+        .binding(0)
+        .stride(std::mem::size_of::<Vertex>() as u32)
+        .input_rate(vk::VertexInputRate::VERTEX)
+}
+
+/// Locations 0..=2: position, uv, color.
+fn vertex_attribute_descriptions() -> [vk::VertexInputAttributeDescription; 3] {
+    [
+        vk::VertexInputAttributeDescription::default()
+            .location(0)
+            .format(vk::Format::R32G32_SFLOAT)
+            .offset(0),
+        vk::VertexInputAttributeDescription::default()
+            .location(1)
+            .format(vk::Format::R32G32_SFLOAT)
+            .offset(8),
+        vk::VertexInputAttributeDescription::default()
+            .location(2)
+            .format(vk::Format::R8G8B8A8_UNORM)
+            .offset(16),
+    ]
+}
+
+/// Scissor in framebuffer pixels for an egui clip rectangle in points, or `None` when nothing
+/// of it is on screen.
+pub fn clip_rect_to_scissor(
+    clip_rect: egui::Rect,
+    pixels_per_point: f32,
+    extent: vk::Extent2D,
+) -> Option<vk::Rect2D> {
+    let min_x = (clip_rect.min.x * pixels_per_point).round().max(0.0) as u32;
+    let min_y = (clip_rect.min.y * pixels_per_point).round().max(0.0) as u32;
+    let max_x = ((clip_rect.max.x * pixels_per_point).round().max(0.0) as u32).min(extent.width);
+    let max_y = ((clip_rect.max.y * pixels_per_point).round().max(0.0) as u32).min(extent.height);
+
+    if min_x >= max_x || min_y >= max_y {
+        return None;
+    }
+
+    Some(vk::Rect2D {
+        offset: vk::Offset2D {
+            x: min_x as i32,
+            y: min_y as i32,
+        },
+        extent: vk::Extent2D {
+            width: max_x - min_x,
+            height: max_y - min_y,
+        },
+    })
+}
+
+/// A texture egui can draw. Managed textures own their image; user textures only point at a
+/// view owned by the caller.
+struct EguiTexture {
+    image: Option<VulkanImage>,
+    descriptor_set: vk::DescriptorSet,
+    _descriptor_pool: VulkanDescriptorPool,
+}
+
+struct EguiDraw {
+    scissor: vk::Rect2D,
+    texture: TextureId,
+    first_index: u32,
+    index_count: u32,
+    vertex_offset: i32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct UiPushConstants {
+    screen_size: [f32; 2],
+}
+
+/// Draws egui output with Vulkan: keeps egui's textures in sync from its `TexturesDelta` and
+/// draws the tessellated meshes with premultiplied alpha blending into an sRGB attachment.
+///
+/// Caller-owned images can be shown in egui widgets through `register_user_texture`.
+pub struct EguiRenderer {
+    pipeline: VulkanPipeline,
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    linear_sampler: VulkanSampler,
+    nearest_sampler: VulkanSampler,
+    textures: HashMap<TextureId, EguiTexture>,
+    next_user_texture: u64,
+    vertex_buffer: Option<VulkanBuffer>,
+    index_buffer: Option<VulkanBuffer>,
+    draws: Vec<EguiDraw>,
+    screen_size: [f32; 2],
+    device: Arc<Device>,
+}
+
+impl EguiRenderer {
+    pub fn new(
+        device: &VulkanDevice,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+    ) -> Result<Self> {
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[VulkanDescriptorSetLayout::binding(
+                0,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                vk::ShaderStageFlags::FRAGMENT,
+            )],
+        )?;
+
+        let premultiplied = vk::PipelineColorBlendAttachmentState::default()
+            .color_write_mask(vk::ColorComponentFlags::RGBA)
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::ONE)
+            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_DST_ALPHA)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+            .alpha_blend_op(vk::BlendOp::ADD);
+
+        let mut builder = VulkanPipelineBuilder::new(device)
+            .set_render_pass(render_pass)
+            .set_extent(extent)
+            .with_vertex_spv(include_bytes!("../../bin/ui.vert.spv"))?
+            .with_fragment_spv(include_bytes!("../../bin/ui.frag.spv"))?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_vertex_binding(vertex_binding_description())
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::VERTEX)
+                    .offset(0)
+                    .size(std::mem::size_of::<UiPushConstants>() as u32),
+            )
+            .with_cull_mode(vk::CullModeFlags::NONE)
+            .with_color_blend_attachment(premultiplied)
+            .with_dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]);
+
+        for attribute in vertex_attribute_descriptions() {
+            builder = builder.with_vertex_attribute(attribute);
+        }
+
+        Ok(Self {
+            pipeline: builder.build()?,
+            descriptor_set_layout,
+            linear_sampler: VulkanSampler::linear_clamp(device)?,
+            nearest_sampler: VulkanSampler::nearest_clamp(device)?,
+            textures: HashMap::new(),
+            next_user_texture: 0,
+            vertex_buffer: None,
+            index_buffer: None,
+            draws: Vec::new(),
+            screen_size: [1.0, 1.0],
+            device: device.device.clone(),
+        })
+    }
+
+    /// Makes `view` drawable by egui, e.g. with `egui::Image::new((id, size))`. The view must be
+    /// in `SHADER_READ_ONLY_OPTIMAL` whenever the UI is drawn.
+    pub fn register_user_texture(
+        &mut self,
+        device: &VulkanDevice,
+        view: vk::ImageView,
+        filter: TextureFilter,
+    ) -> Result<TextureId> {
+        let id = TextureId::User(self.next_user_texture);
+        self.next_user_texture += 1;
+
+        let texture = self.create_texture(device, None, view, filter)?;
+        self.textures.insert(id, texture);
+        Ok(id)
+    }
+
+    /// Points a user texture at another view, e.g. after its target was resized. The
+    /// descriptor set is updated immediately, so no in-flight frame may still be drawing it.
+    pub fn update_user_texture(
+        &mut self,
+        id: TextureId,
+        view: vk::ImageView,
+        filter: TextureFilter,
+    ) -> Result<()> {
+        let texture = self
+            .textures
+            .get(&id)
+            .filter(|_| matches!(id, TextureId::User(_)))
+            .ok_or_else(|| anyhow::anyhow!("Unknown user texture {:?}", id))?;
+        self.write_descriptor(texture, view, filter);
+        Ok(())
+    }
+
+    /// Forgets a user texture once `frame` has completed. The caller still owns the view.
+    pub fn free_user_texture(
+        &mut self,
+        id: TextureId,
+        frame: u64,
+        deletion_queue: &mut DeletionQueue,
+    ) {
+        if matches!(id, TextureId::User(_))
+            && let Some(texture) = self.textures.remove(&id)
+        {
+            deletion_queue.retire(frame, texture);
+        }
+    }
+
+    /// Records the texture uploads and frees of `delta`. Must be recorded outside a render
+    /// pass and before `draw`. Staging buffers and replaced or freed images are retired
+    /// through `deletion_queue` once `frame` has completed.
+    pub fn update_textures(
+        &mut self,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        command_buffer: vk::CommandBuffer,
+        frame: u64,
+        delta: &TexturesDelta,
+        deletion_queue: &mut DeletionQueue,
+    ) -> Result<()> {
+        for (&id, image_deltas) in &delta.set {
+            for image_delta in image_deltas {
+                self.update_texture(
+                    device,
+                    physical_device,
+                    command_buffer,
+                    frame,
+                    id,
+                    image_delta,
+                    deletion_queue,
+                )?;
+            }
+        }
+
+        for id in &delta.free {
+            if let Some(texture) = self.textures.remove(id) {
+                deletion_queue.retire(frame, texture);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn update_texture(
+        &mut self,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        command_buffer: vk::CommandBuffer,
+        frame: u64,
+        id: TextureId,
+        delta: &ImageDelta,
+        deletion_queue: &mut DeletionQueue,
+    ) -> Result<()> {
+        let ImageData::Color(image) = &delta.image;
+        let [width, height] = image.size;
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        let staging = VulkanBuffer::new_host_visible(
+            device,
+            physical_device,
+            std::mem::size_of_val(image.pixels.as_slice()) as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+        )?;
+        staging.write(0, &image.pixels)?;
+
+        let (offset, old_layout) = match delta.pos {
+            Some([x, y]) => (
+                vk::Offset3D {
+                    x: x as i32,
+                    y: y as i32,
+                    z: 0,
+                },
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ),
+            None => {
+                let extent = vk::Extent2D {
+                    width: width as u32,
+                    height: height as u32,
+                };
+                let image = VulkanImage::new(
+                    device,
+                    physical_device,
+                    extent,
+                    vk::Format::R8G8B8A8_SRGB,
+                    vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+                    vk::ImageAspectFlags::COLOR,
+                )?;
+                let view = image.view;
+                let texture =
+                    self.create_texture(device, Some(image), view, delta.options.magnification)?;
+                if let Some(previous) = self.textures.insert(id, texture) {
+                    deletion_queue.retire(frame, previous);
+                }
+                (vk::Offset3D::default(), vk::ImageLayout::UNDEFINED)
+            }
+        };
+
+        let target = self
+            .textures
+            .get(&id)
+            .and_then(|texture| texture.image.as_ref())
+            .ok_or_else(|| anyhow::anyhow!("Partial update of unknown texture {:?}", id))?;
+        let range = target.subresource_range();
+
+        target.cmd_transition(
+            command_buffer,
+            range,
+            old_layout,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::SHADER_READ,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+        );
+
+        let region = vk::BufferImageCopy::default()
+            .image_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image_offset(offset)
+            .image_extent(vk::Extent3D {
+                width: width as u32,
+                height: height as u32,
+                depth: 1,
+            });
+
+        unsafe {
+            self.device.cmd_copy_buffer_to_image(
+                command_buffer,
+                staging.buffer,
+                target.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                std::slice::from_ref(&region),
+            );
+        }
+
+        target.cmd_transition(
+            command_buffer,
+            range,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        );
+
+        deletion_queue.retire(frame, staging);
+        Ok(())
+    }
+
+    /// Copies the tessellated primitives for the next `draw`, growing the vertex and index
+    /// buffers as needed; outgrown buffers are retired once `frame` has completed. No
+    /// in-flight frame may still be drawing the previous primitives.
+    #[allow(clippy::too_many_arguments)]
+    pub fn upload(
+        &mut self,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        frame: u64,
+        primitives: &[ClippedPrimitive],
+        pixels_per_point: f32,
+        extent: vk::Extent2D,
+        deletion_queue: &mut DeletionQueue,
+    ) -> Result<()> {
+        self.draws.clear();
+        self.screen_size = [
+            extent.width as f32 / pixels_per_point,
+            extent.height as f32 / pixels_per_point,
+        ];
+
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+
+        for primitive in primitives {
+            let Primitive::Mesh(mesh) = &primitive.primitive else {
+                continue;
+            };
+            let Some(scissor) = clip_rect_to_scissor(primitive.clip_rect, pixels_per_point, extent)
+            else {
+                continue;
+            };
+            if mesh.indices.is_empty() {
+                continue;
+            }
+
+            self.draws.push(EguiDraw {
+                scissor,
+                texture: mesh.texture_id,
+                first_index: indices.len() as u32,
+                index_count: mesh.indices.len() as u32,
+                vertex_offset: vertices.len() as i32,
+            });
+            vertices.extend_from_slice(&mesh.vertices);
+            indices.extend_from_slice(&mesh.indices);
+        }
+
+        let vertex_buffer = Self::reserve(
+            device,
+            physical_device,
+            &mut self.vertex_buffer,
+            std::mem::size_of_val(vertices.as_slice()) as vk::DeviceSize,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            frame,
+            deletion_queue,
+        )?;
+        vertex_buffer.write(0, &vertices)?;
+
+        let index_buffer = Self::reserve(
+            device,
+            physical_device,
+            &mut self.index_buffer,
+            std::mem::size_of_val(indices.as_slice()) as vk::DeviceSize,
+            vk::BufferUsageFlags::INDEX_BUFFER,
+            frame,
+            deletion_queue,
+        )?;
+        index_buffer.write(0, &indices)?;
+
+        Ok(())
+    }
+
+    /// Draws the uploaded primitives inside the current render pass. Sets a scissor per
+    /// primitive and restores a full-`extent` viewport and scissor afterwards.
+    pub fn draw(&self, command_buffer: vk::CommandBuffer, extent: vk::Extent2D) {
+        let (Some(vertex_buffer), Some(index_buffer)) = (&self.vertex_buffer, &self.index_buffer)
+        else {
+            return;
+        };
+        if self.draws.is_empty() {
+            return;
+        }
+
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: extent.width as f32,
+            height: extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+
+        self.pipeline.bind(command_buffer);
+        self.pipeline.push_constants(
+            command_buffer,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            &UiPushConstants {
+                screen_size: self.screen_size,
+            },
+        );
+
+        unsafe {
+            self.device
+                .cmd_set_viewport(command_buffer, 0, std::slice::from_ref(&viewport));
+            self.device
+                .bind_vertex_buffers(command_buffer, 0, &[vertex_buffer.buffer], &[0]);
+            self.device.bind_index_buffer(
+                command_buffer,
+                index_buffer.buffer,
+                0,
+                vk::IndexType::UINT32,
+            );
+        }
+
+        for draw in &self.draws {
+            let Some(texture) = self.textures.get(&draw.texture) else {
+                continue;
+            };
+
+            self.pipeline
+                .bind_descriptor_sets(command_buffer, 0, &[texture.descriptor_set]);
+            unsafe {
+                self.device
+                    .cmd_set_scissor(command_buffer, 0, std::slice::from_ref(&draw.scissor));
+                self.device.draw_indexed(
+                    command_buffer,
+                    draw.index_count,
+                    1,
+                    draw.first_index,
+                    draw.vertex_offset,
+                    0,
+                );
+            }
+        }
+
+        let scissor = vk::Rect2D {
+            offset: vk::Offset2D::default(),
+            extent,
+        };
+        unsafe {
+            self.device
+                .cmd_set_scissor(command_buffer, 0, std::slice::from_ref(&scissor));
+        }
+    }
+
+    fn create_texture(
+        &self,
+        device: &VulkanDevice,
+        image: Option<VulkanImage>,
+        view: vk::ImageView,
+        filter: TextureFilter,
+    ) -> Result<EguiTexture> {
+        let descriptor_pool =
+            VulkanDescriptorPool::for_layout(device, &self.descriptor_set_layout, 1)?;
+        let texture = EguiTexture {
+            descriptor_set: descriptor_pool.allocate(&self.descriptor_set_layout)?,
+            image,
+            _descriptor_pool: descriptor_pool,
+        };
+        self.write_descriptor(&texture, view, filter);
+        Ok(texture)
+    }
+
+    fn write_descriptor(&self, texture: &EguiTexture, view: vk::ImageView, filter: TextureFilter) {
+        let sampler = match filter {
+            TextureFilter::Linear => &self.linear_sampler,
+            TextureFilter::Nearest => &self.nearest_sampler,
+        };
+        let image_info = [vk::DescriptorImageInfo::default()
+            .image_view(view)
+            .sampler(sampler.sampler)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)];
+        let write = vk::WriteDescriptorSet::default()
+            .dst_set(texture.descriptor_set)
+            .dst_binding(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&image_info);
+
+        unsafe {
+            self.device
+                .update_descriptor_sets(std::slice::from_ref(&write), &[]);
+        }
+    }
+
+    /// Returns `buffer`, replaced by one of at least `size` bytes if it is too small.
+    #[allow(clippy::too_many_arguments)]
+    fn reserve<'a>(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        buffer: &'a mut Option<VulkanBuffer>,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        frame: u64,
+        deletion_queue: &mut DeletionQueue,
+    ) -> Result<&'a VulkanBuffer> {
+        if buffer.as_ref().is_none_or(|buffer| buffer.size < size) {
+            let grown = VulkanBuffer::new_host_visible(
+                device,
+                physical_device,
+                size.max(1).next_power_of_two(),
+                usage,
+            )?;
+            if let Some(previous) = buffer.replace(grown) {
+                deletion_queue.retire(frame, previous);
+            }
+        }
+
+        Ok(buffer.as_ref().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ShaderReflection;
+
+    #[test]
+    fn clip_rects_become_clamped_pixel_scissors() {
+        let extent = vk::Extent2D {
+            width: 800,
+            height: 600,
+        };
+        let rect = |min: [f32; 2], max: [f32; 2]| {
+            egui::Rect::from_min_max(egui::pos2(min[0], min[1]), egui::pos2(max[0], max[1]))
+        };
+
+        let scissor = clip_rect_to_scissor(rect([10.0, 20.0], [110.0, 70.0]), 2.0, extent).unwrap();
+        assert_eq!(scissor.offset, vk::Offset2D { x: 20, y: 40 });
+        assert_eq!(
+            scissor.extent,
+            vk::Extent2D {
+                width: 200,
+                height: 100
+            }
+        );
+
+        let clamped = clip_rect_to_scissor(rect([-50.0, 500.0], [1e6, 1e6]), 1.0, extent).unwrap();
+        assert_eq!(clamped.offset, vk::Offset2D { x: 0, y: 500 });
+        assert_eq!(
+            clamped.extent,
+            vk::Extent2D {
+                width: 800,
+                height: 100
+            }
+        );
+
+        assert!(clip_rect_to_scissor(rect([900.0, 0.0], [950.0, 10.0]), 1.0, extent).is_none());
+    }
+
+    #[test]
+    fn vertex_layout_matches_egui() {
+        let attributes = vertex_attribute_descriptions();
+        assert_eq!(std::mem::size_of::<Vertex>(), 20);
+        assert_eq!(
+            attributes[1].offset as usize,
+            std::mem::offset_of!(Vertex, uv)
+        );
+        assert_eq!(
+            attributes[2].offset as usize,
+            std::mem::offset_of!(Vertex, color)
+        );
+
+        let reflection =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/ui.vert.spv")).unwrap();
+        assert_eq!(
+            reflection.push_constant_size,
+            Some(std::mem::size_of::<UiPushConstants>() as u32)
+        );
+    }
+}
@@ -0,0 +1,443 @@
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+
+use crate::renderer::DebugDraw;
+use crate::scene::{Scene, Transform};
+
+/// Half-line from `origin` along unit `direction`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    /// Ray through `cursor`, in pixels from the top-left of a `viewport`-sized framebuffer,
+    /// for a camera with `view_proj` and Vulkan's `[0, 1]` depth range.
+    pub fn from_screen(cursor: Vec2, viewport: Vec2, view_proj: Mat4) -> Self {
+        let ndc = cursor / viewport * 2.0 - 1.0;
+        let inverse = view_proj.inverse();
+        let near = inverse.project_point3(ndc.extend(0.0));
+        let far = inverse.project_point3(ndc.extend(1.0));
+        Self {
+            origin: near,
+            direction: (far - near).normalize(),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    /// Distance along the ray to the plane through `point` with `normal`, if the ray hits it.
+    pub fn intersect_plane(&self, point: Vec3, normal: Vec3) -> Option<f32> {
+        let denominator = self.direction.dot(normal);
+        if denominator.abs() < 1e-6 {
+            return None;
+        }
+        let distance = (point - self.origin).dot(normal) / denominator;
+        (distance >= 0.0).then_some(distance)
+    }
+
+    /// Parameter along the line `origin + axis * t` of the point closest to the ray, and the
+    /// distance between the ray and that point. `None` when the two are parallel.
+    pub fn closest_on_line(&self, origin: Vec3, axis: Vec3) -> Option<(f32, f32)> {
+        let w = self.origin - origin;
+        let b = self.direction.dot(axis);
+        let a = axis.length_squared();
+        let denominator = a - b * b;
+        if denominator.abs() < 1e-6 {
+            return None;
+        }
+        let d = self.direction.dot(w);
+        let e = axis.dot(w);
+        let ray_distance = ((b * e - a * d) / denominator).max(0.0);
+        let t = (e + b * ray_distance) / a;
+        let distance = self.at(ray_distance).distance(origin + axis * t);
+        Some((t, distance))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GizmoMode {
+    #[default]
+    Translate,
+    Rotate,
+    Scale,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GizmoDrag {
+    node: usize,
+    axis: usize,
+    start: Transform,
+    /// Handles where the drag started, so the drag doesn't chase the node it moves.
+    handles: Handles,
+    /// Axis parameter for translate and scale, angle for rotate, where the drag started.
+    start_value: f32,
+}
+
+/// Translate, rotate and scale handles for the selected scene node, e.g. the one picked in
+/// `SceneInspector`. Handles follow the node's world-space axes and keep a constant size on
+/// screen.
+///
+/// Call `update` once per frame with the cursor ray and button state to hover and drag
+/// handles, then `draw` the handles into a `DebugDraw`, drawn by a `DebugDrawRenderer`
+/// created with or without depth testing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    /// Handle length as a fraction of the distance to the camera.
+    pub size: f32,
+    /// Pick distance as a fraction of the handle length.
+    pub pick_tolerance: f32,
+    hovered: Option<usize>,
+    drag: Option<GizmoDrag>,
+}
+
+impl Default for Gizmo {
+    fn default() -> Self {
+        Self {
+            mode: GizmoMode::default(),
+            size: 0.15,
+            pick_tolerance: 0.08,
+            hovered: None,
+            drag: None,
+        }
+    }
+}
+
+const AXIS_COLORS: [Vec4; 3] = [
+    Vec4::new(0.9, 0.2, 0.2, 1.0),
+    Vec4::new(0.2, 0.9, 0.2, 1.0),
+    Vec4::new(0.2, 0.4, 1.0, 1.0),
+];
+const HIGHLIGHT_COLOR: Vec4 = Vec4::new(1.0, 0.9, 0.1, 1.0);
+const CIRCLE_SEGMENTS: u32 = 48;
+const MIN_SCALE: f32 = 1e-3;
+
+impl Gizmo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_mode(mut self, mode: GizmoMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Axis under the cursor, or being dragged: 0 for X, 1 for Y, 2 for Z.
+    pub fn active_axis(&self) -> Option<usize> {
+        self.drag.map(|drag| drag.axis).or(self.hovered)
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// Hovers and drags handles of `node`. `pressed` is whether the primary button is held;
+    /// a drag starts when it goes down over a handle and ends when it is released. Returns
+    /// whether the node's transform changed, in which case `Scene::world_transforms` needs
+    /// recomputing.
+    pub fn update(
+        &mut self,
+        scene: &mut Scene,
+        node: Option<usize>,
+        ray: Ray,
+        camera_position: Vec3,
+        pressed: bool,
+    ) -> bool {
+        let Some(node) = node.filter(|&node| node < scene.nodes.len()) else {
+            self.hovered = None;
+            self.drag = None;
+            return false;
+        };
+        if !pressed || self.drag.is_some_and(|drag| drag.node != node) {
+            self.drag = None;
+        }
+
+        let world = node_world_transform(scene, node);
+        let parent = scene.nodes[node]
+            .parent
+            .map(|parent| node_world_transform(scene, parent))
+            .unwrap_or(Mat4::IDENTITY);
+        let handles = Handles::new(world, camera_position, self.size);
+
+        let Some(drag) = self.drag else {
+            self.hovered = self.pick(&handles, ray);
+            if pressed
+                && let Some(axis) = self.hovered
+                && let Some(start_value) = self.drag_value(&handles, axis, ray)
+            {
+                self.drag = Some(GizmoDrag {
+                    node,
+                    axis,
+                    start: scene.nodes[node].transform,
+                    handles,
+                    start_value,
+                });
+            }
+            return false;
+        };
+
+        let Some(value) = self.drag_value(&drag.handles, drag.axis, ray) else {
+            return false;
+        };
+        let mut transform = drag.start;
+        match self.mode {
+            GizmoMode::Translate => {
+                let offset = drag.handles.axes[drag.axis] * (value - drag.start_value);
+                transform.translation += parent.inverse().transform_vector3(offset);
+            }
+            GizmoMode::Rotate => {
+                let mut local_axis = Vec3::ZERO;
+                local_axis[drag.axis] = 1.0;
+                transform.rotation = (drag.start.rotation
+                    * Quat::from_axis_angle(local_axis, value - drag.start_value))
+                .normalize();
+            }
+            GizmoMode::Scale => {
+                if drag.start_value.abs() > 1e-6 {
+                    let factor = value / drag.start_value;
+                    transform.scale[drag.axis] =
+                        (drag.start.scale[drag.axis] * factor).max(MIN_SCALE);
+                }
+            }
+        }
+
+        let changed = transform != scene.nodes[node].transform;
+        scene.nodes[node].transform = transform;
+        changed
+    }
+
+    /// Adds the handles of `node` to `debug_draw`, highlighting the hovered or dragged one.
+    pub fn draw(
+        &self,
+        debug_draw: &mut DebugDraw,
+        scene: &Scene,
+        node: Option<usize>,
+        camera_position: Vec3,
+    ) {
+        let Some(node) = node.filter(|&node| node < scene.nodes.len()) else {
+            return;
+        };
+        let handles = Handles::new(
+            node_world_transform(scene, node),
+            camera_position,
+            self.size,
+        );
+        let active = self.active_axis();
+
+        for (axis, &axis_color) in AXIS_COLORS.iter().enumerate() {
+            let color = if active == Some(axis) {
+                HIGHLIGHT_COLOR
+            } else {
+                axis_color
+            };
+            let direction = handles.axes[axis];
+            let end = handles.origin + direction * handles.length;
+            let (u, v) = (handles.axes[(axis + 1) % 3], handles.axes[(axis + 2) % 3]);
+
+            match self.mode {
+                GizmoMode::Translate => {
+                    debug_draw.line(handles.origin, end, color);
+                    let base = end - direction * handles.length * 0.15;
+                    for side in [u, -u, v, -v] {
+                        debug_draw.line(end, base + side * handles.length * 0.05, color);
+                    }
+                }
+                GizmoMode::Rotate => {
+                    let point = |i: u32| {
+                        let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+                        handles.origin + (u * angle.cos() + v * angle.sin()) * handles.length
+                    };
+                    for i in 0..CIRCLE_SEGMENTS {
+                        debug_draw.line(point(i), point(i + 1), color);
+                    }
+                }
+                GizmoMode::Scale => {
+                    debug_draw.line(handles.origin, end, color);
+                    let half = handles.length * 0.05;
+                    for (a, b) in [(u, v), (v, -u), (-u, -v), (-v, u)] {
+                        debug_draw.line(end + (a + b) * half, end + (b - a) * half, color);
+                    }
+                }
+            }
+        }
+    }
+
+    fn pick(&self, handles: &Handles, ray: Ray) -> Option<usize> {
+        let tolerance = handles.length * self.pick_tolerance;
+        (0..3)
+            .filter_map(|axis| {
+                let distance = match self.mode {
+                    GizmoMode::Translate | GizmoMode::Scale => {
+                        let (t, distance) =
+                            ray.closest_on_line(handles.origin, handles.axes[axis])?;
+                        (0.0..=handles.length).contains(&t).then_some(distance)?
+                    }
+                    GizmoMode::Rotate => {
+                        let hit = ray.at(ray.intersect_plane(handles.origin, handles.axes[axis])?);
+                        (hit.distance(handles.origin) - handles.length).abs()
+                    }
+                };
+                (distance <= tolerance).then_some((axis, distance))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(axis, _)| axis)
+    }
+
+    /// Axis parameter for translate and scale, angle around the axis for rotate.
+    fn drag_value(&self, handles: &Handles, axis: usize, ray: Ray) -> Option<f32> {
+        let direction = handles.axes[axis];
+        match self.mode {
+            GizmoMode::Translate | GizmoMode::Scale => ray
+                .closest_on_line(handles.origin, direction)
+                .map(|(t, _)| t),
+            GizmoMode::Rotate => {
+                let hit = ray.at(ray.intersect_plane(handles.origin, direction)?);
+                let offset = hit - handles.origin;
+                let (u, v) = (handles.axes[(axis + 1) % 3], handles.axes[(axis + 2) % 3]);
+                Some(offset.dot(v).atan2(offset.dot(u)))
+            }
+        }
+    }
+}
+
+/// Handle placement for one node: its world origin and normalized world axes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Handles {
+    origin: Vec3,
+    axes: [Vec3; 3],
+    length: f32,
+}
+
+impl Handles {
+    fn new(world: Mat4, camera_position: Vec3, size: f32) -> Self {
+        let origin = world.w_axis.truncate();
+        let axis =
+            |column: Vec4, fallback: Vec3| column.truncate().try_normalize().unwrap_or(fallback);
+        Self {
+            origin,
+            axes: [
+                axis(world.x_axis, Vec3::X),
+                axis(world.y_axis, Vec3::Y),
+                axis(world.z_axis, Vec3::Z),
+            ],
+            length: (origin.distance(camera_position) * size).max(1e-3),
+        }
+    }
+}
+
+fn node_world_transform(scene: &Scene, node: usize) -> Mat4 {
+    let mut world = scene.nodes[node].transform.to_matrix();
+    let mut parent = scene.nodes[node].parent;
+    // Bounded by the node count so a malformed parent cycle can't hang.
+    for _ in 0..scene.nodes.len() {
+        let Some(index) = parent else {
+            break;
+        };
+        world = scene.nodes[index].transform.to_matrix() * world;
+        parent = scene.nodes[index].parent;
+    }
+    world
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::SceneNode;
+
+    fn scene() -> Scene {
+        let mut scene = Scene::new();
+        let parent = scene.add_node(SceneNode::new("parent").with_transform(Transform {
+            translation: Vec3::new(0.0, 0.0, -10.0),
+            ..Default::default()
+        }));
+        scene.add_node(SceneNode::new("child").with_parent(parent));
+        scene
+    }
+
+    fn ray_towards(camera: Vec3, point: Vec3) -> Ray {
+        Ray {
+            origin: camera,
+            direction: (point - camera).normalize(),
+        }
+    }
+
+    #[test]
+    fn screen_rays_pass_through_the_cursor() {
+        let view_proj = Mat4::perspective_rh(1.0, 2.0, 0.1, 100.0)
+            * Mat4::look_at_rh(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y);
+        let ray = Ray::from_screen(Vec2::new(400.0, 200.0), Vec2::new(800.0, 400.0), view_proj);
+        assert!(ray.origin.distance(Vec3::new(0.0, 0.0, 4.9)) < 1e-4);
+        assert!(ray.direction.distance(Vec3::NEG_Z) < 1e-4);
+
+        // Unflipped perspective_rh puts clip-space +Y at the bottom of the framebuffer.
+        let ray = Ray::from_screen(Vec2::new(400.0, 400.0), Vec2::new(800.0, 400.0), view_proj);
+        assert!(ray.direction.y > 0.0);
+    }
+
+    #[test]
+    fn drags_translate_handles_along_their_axis() {
+        let mut scene = scene();
+        let mut gizmo = Gizmo::new();
+        let camera = Vec3::new(0.0, 0.0, 0.0);
+        let length = 10.0 * gizmo.size;
+
+        let on_x = ray_towards(camera, Vec3::new(length * 0.5, 0.0, -10.0));
+        assert!(!gizmo.update(&mut scene, Some(1), on_x, camera, false));
+        assert_eq!(gizmo.active_axis(), Some(0));
+
+        assert!(!gizmo.update(&mut scene, Some(1), on_x, camera, true));
+        assert!(gizmo.is_dragging());
+        let moved = ray_towards(camera, Vec3::new(length * 0.5 + 2.0, 0.0, -10.0));
+        assert!(gizmo.update(&mut scene, Some(1), moved, camera, true));
+        let translation = scene.nodes[1].transform.translation;
+        assert!(
+            translation.distance(Vec3::new(2.0, 0.0, 0.0)) < 1e-3,
+            "{}",
+            translation
+        );
+
+        gizmo.update(&mut scene, Some(1), moved, camera, false);
+        assert!(!gizmo.is_dragging());
+
+        let miss = ray_towards(camera, Vec3::new(-3.0, -3.0, -10.0));
+        gizmo.update(&mut scene, Some(1), miss, camera, false);
+        assert_eq!(gizmo.active_axis(), None);
+    }
+
+    #[test]
+    fn rotates_and_scales_around_node_axes() {
+        let mut scene = scene();
+        let camera = Vec3::new(0.0, 0.0, 0.0);
+        let mut gizmo = Gizmo::new().with_mode(GizmoMode::Rotate);
+        let radius = 10.0 * gizmo.size;
+
+        // The Z ring faces the camera; drag a quarter turn from +X to +Y.
+        let start = ray_towards(camera, Vec3::new(radius, 0.0, -10.0));
+        gizmo.update(&mut scene, Some(1), start, camera, false);
+        assert_eq!(gizmo.active_axis(), Some(2));
+        gizmo.update(&mut scene, Some(1), start, camera, true);
+        let end = ray_towards(camera, Vec3::new(0.0, radius, -10.0));
+        assert!(gizmo.update(&mut scene, Some(1), end, camera, true));
+        let rotation = scene.nodes[1].transform.rotation;
+        assert!(rotation.angle_between(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2)) < 1e-3);
+        gizmo.update(&mut scene, Some(1), end, camera, false);
+
+        gizmo.mode = GizmoMode::Scale;
+        // The rotated node's X axis now points along world +Y.
+        let handle = ray_towards(camera, Vec3::new(0.0, radius * 0.5, -10.0));
+        gizmo.update(&mut scene, Some(1), handle, camera, false);
+        assert_eq!(gizmo.active_axis(), Some(0));
+        gizmo.update(&mut scene, Some(1), handle, camera, true);
+        let stretched = ray_towards(camera, Vec3::new(0.0, radius, -10.0));
+        gizmo.update(&mut scene, Some(1), stretched, camera, true);
+        let scale = scene.nodes[1].transform.scale;
+        assert!(scale.distance(Vec3::new(2.0, 1.0, 1.0)) < 1e-3, "{}", scale);
+
+        let mut debug_draw = DebugDraw::new();
+        gizmo.draw(&mut debug_draw, &scene, Some(1), camera);
+        assert_eq!(debug_draw.line_count(), 3 * 5);
+    }
+}
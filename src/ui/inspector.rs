@@ -0,0 +1,519 @@
+use egui::collapsing_header::CollapsingState;
+use egui::{ComboBox, DragValue, Ui};
+use glam::{EulerRot, Quat, Vec3, Vec4};
+
+use crate::effects::{TonemapOperator, TonemapSettings};
+use crate::scene::{Light, Scene, SceneCamera, Transform};
+
+/// What a `SceneInspector::show` call edited, so the caller only re-uploads what changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InspectorChanges {
+    /// Some node transform changed; `Scene::world_transforms` needs recomputing.
+    pub transforms: bool,
+    /// Nodes whose material overrides changed.
+    pub materials: Vec<usize>,
+    pub lights: bool,
+    pub cameras: bool,
+    /// The renderer settings section reported a change.
+    pub settings: bool,
+}
+
+impl InspectorChanges {
+    pub fn any(&self) -> bool {
+        *self != Self::default()
+    }
+}
+
+/// Editor window over a `Scene`: the node hierarchy, the selected node's transform, material
+/// overrides, light and camera, plus a section for renderer settings supplied by the caller.
+/// Edits are written straight into the scene, so they show up on the next frame.
+#[derive(Debug, Clone, Default)]
+pub struct SceneInspector {
+    pub open: bool,
+    selected: Option<usize>,
+    /// Euler angles shown for the selected node's rotation, kept between frames so editing
+    /// one angle doesn't make the others jump between equivalent decompositions.
+    euler: Option<(usize, Vec3)>,
+}
+
+impl SceneInspector {
+    pub fn new() -> Self {
+        Self {
+            open: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn select(&mut self, node: Option<usize>) {
+        self.selected = node;
+    }
+
+    /// Shows the inspector window. `settings` draws the renderer settings section, e.g. with
+    /// `tonemap_settings_ui` or checkboxes for effect toggles, and returns whether anything in
+    /// it changed.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        scene: &mut Scene,
+        settings: impl FnOnce(&mut Ui) -> bool,
+    ) -> InspectorChanges {
+        let mut changes = InspectorChanges::default();
+        if self.selected.is_some_and(|node| node >= scene.nodes.len()) {
+            self.selected = None;
+        }
+
+        let mut open = self.open;
+        egui::Window::new("Inspector")
+            .open(&mut open)
+            .default_width(280.0)
+            .show(ctx, |ui| {
+                egui::CollapsingHeader::new("Hierarchy")
+                    .default_open(true)
+                    .show(ui, |ui| self.hierarchy_ui(ui, scene));
+
+                egui::CollapsingHeader::new("Node")
+                    .default_open(true)
+                    .show(ui, |ui| match self.selected {
+                        Some(node) => self.node_ui(ui, scene, node, &mut changes),
+                        None => {
+                            ui.weak("No node selected");
+                        }
+                    });
+
+                egui::CollapsingHeader::new("Renderer")
+                    .default_open(true)
+                    .show(ui, |ui| changes.settings = settings(ui));
+            });
+        self.open = open;
+
+        changes
+    }
+
+    fn hierarchy_ui(&mut self, ui: &mut Ui, scene: &Scene) {
+        let (roots, children) = hierarchy(scene);
+        for root in roots {
+            self.node_tree_ui(ui, scene, &children, root);
+        }
+        if scene.nodes.is_empty() {
+            ui.weak("Empty scene");
+        }
+    }
+
+    fn node_tree_ui(&mut self, ui: &mut Ui, scene: &Scene, children: &[Vec<usize>], node: usize) {
+        let name = match scene.nodes[node].name.as_str() {
+            "" => format!("<node {}>", node),
+            name => name.to_string(),
+        };
+        let selected = self.selected == Some(node);
+
+        if children[node].is_empty() {
+            if ui.selectable_label(selected, name).clicked() {
+                self.selected = Some(node);
+            }
+            return;
+        }
+
+        let id = ui.make_persistent_id(("scene_node", node));
+        CollapsingState::load_with_default_open(ui.ctx(), id, true)
+            .show_header(ui, |ui| {
+                if ui.selectable_label(selected, name).clicked() {
+                    self.selected = Some(node);
+                }
+            })
+            .body(|ui| {
+                for &child in &children[node] {
+                    self.node_tree_ui(ui, scene, children, child);
+                }
+            });
+    }
+
+    fn node_ui(
+        &mut self,
+        ui: &mut Ui,
+        scene: &mut Scene,
+        index: usize,
+        changes: &mut InspectorChanges,
+    ) {
+        let node = &mut scene.nodes[index];
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut node.name);
+        });
+
+        let euler = match self.euler {
+            Some((node, euler)) if node == index => euler,
+            _ => Vec3::ZERO,
+        };
+        let mut euler = sync_euler(euler, node.transform.rotation);
+        if transform_ui(ui, &mut node.transform, &mut euler) {
+            changes.transforms = true;
+        }
+        self.euler = Some((index, euler));
+
+        ui.separator();
+        if let Some(material) = &node.material {
+            ui.label(format!("Material: {}", material.display()));
+        }
+        if material_overrides_ui(ui, node) {
+            changes.materials.push(index);
+        }
+
+        ui.separator();
+        if light_ui(ui, &mut node.light) {
+            changes.lights = true;
+        }
+
+        if let Some(camera) = &mut node.camera {
+            ui.separator();
+            if camera_ui(ui, camera) {
+                changes.cameras = true;
+            }
+        }
+    }
+}
+
+/// Root nodes and each node's children, in scene order. Nodes whose parent index doesn't come
+/// before them are treated as roots, matching `Scene::world_transforms`.
+fn hierarchy(scene: &Scene) -> (Vec<usize>, Vec<Vec<usize>>) {
+    let mut roots = Vec::new();
+    let mut children = vec![Vec::new(); scene.nodes.len()];
+    for (index, node) in scene.nodes.iter().enumerate() {
+        match node.parent.filter(|&parent| parent < index) {
+            Some(parent) => children[parent].push(index),
+            None => roots.push(index),
+        }
+    }
+    (roots, children)
+}
+
+/// Keeps the previously shown Euler angles while they still describe `rotation`, and
+/// decomposes `rotation` afresh once something else changed it.
+fn sync_euler(euler: Vec3, rotation: Quat) -> Vec3 {
+    let shown = Quat::from_euler(EulerRot::XYZ, euler.x, euler.y, euler.z);
+    if shown.dot(rotation).abs() > 1.0 - 1e-6 {
+        euler
+    } else {
+        Vec3::from(rotation.to_euler(EulerRot::XYZ))
+    }
+}
+
+fn vec3_ui(ui: &mut Ui, label: &str, value: &mut Vec3, speed: f32) -> bool {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        let mut changed = false;
+        for component in [&mut value.x, &mut value.y, &mut value.z] {
+            changed |= ui.add(DragValue::new(component).speed(speed)).changed();
+        }
+        changed
+    })
+    .inner
+}
+
+fn transform_ui(ui: &mut Ui, transform: &mut Transform, euler: &mut Vec3) -> bool {
+    let mut changed = vec3_ui(ui, "Translation", &mut transform.translation, 0.05);
+
+    let rotation_changed = ui
+        .horizontal(|ui| {
+            ui.label("Rotation");
+            let mut changed = false;
+            for angle in [&mut euler.x, &mut euler.y, &mut euler.z] {
+                changed |= ui.drag_angle(angle).changed();
+            }
+            changed
+        })
+        .inner;
+    if rotation_changed {
+        transform.rotation = Quat::from_euler(EulerRot::XYZ, euler.x, euler.y, euler.z);
+        changed = true;
+    }
+
+    changed | vec3_ui(ui, "Scale", &mut transform.scale, 0.01)
+}
+
+fn material_overrides_ui(ui: &mut Ui, node: &mut crate::scene::SceneNode) -> bool {
+    let overrides = &mut node.material_overrides;
+    let mut changed = false;
+
+    ui.horizontal(|ui| {
+        let mut enabled = overrides.base_color.is_some();
+        if ui.checkbox(&mut enabled, "Base color").changed() {
+            overrides.base_color = enabled.then_some(Vec4::ONE);
+            changed = true;
+        }
+        if let Some(base_color) = &mut overrides.base_color {
+            let mut rgba = base_color.to_array();
+            if ui.color_edit_button_rgba_unmultiplied(&mut rgba).changed() {
+                *base_color = Vec4::from_array(rgba);
+                changed = true;
+            }
+        }
+    });
+
+    ui.horizontal(|ui| {
+        let mut enabled = overrides.alpha_cutoff.is_some();
+        if ui.checkbox(&mut enabled, "Alpha cutoff").changed() {
+            overrides.alpha_cutoff = enabled.then_some(0.5);
+            changed = true;
+        }
+        if let Some(alpha_cutoff) = &mut overrides.alpha_cutoff {
+            changed |= ui.add(egui::Slider::new(alpha_cutoff, 0.0..=1.0)).changed();
+        }
+    });
+
+    for (label, path) in [
+        ("Albedo", &overrides.albedo),
+        ("Normal map", &overrides.normal_map),
+    ] {
+        if let Some(path) = path {
+            ui.label(format!("{}: {}", label, path.display()));
+        }
+    }
+
+    changed
+}
+
+fn light_kind(light: &Option<Light>) -> &'static str {
+    match light {
+        None => "None",
+        Some(Light::Directional { .. }) => "Directional",
+        Some(Light::Point { .. }) => "Point",
+        Some(Light::Spot { .. }) => "Spot",
+    }
+}
+
+fn light_ui(ui: &mut Ui, light: &mut Option<Light>) -> bool {
+    let mut changed = false;
+    let current = light_kind(light);
+
+    ComboBox::from_label("Light")
+        .selected_text(current)
+        .show_ui(ui, |ui| {
+            let kinds: [(&str, Option<Light>); 4] = [
+                ("None", None),
+                (
+                    "Directional",
+                    Some(Light::Directional {
+                        color: Vec3::ONE,
+                        intensity: 1.0,
+                    }),
+                ),
+                (
+                    "Point",
+                    Some(Light::Point {
+                        color: Vec3::ONE,
+                        intensity: 1.0,
+                        range: 10.0,
+                    }),
+                ),
+                (
+                    "Spot",
+                    Some(Light::Spot {
+                        color: Vec3::ONE,
+                        intensity: 1.0,
+                        range: 10.0,
+                        inner_angle: 0.3,
+                        outer_angle: 0.5,
+                    }),
+                ),
+            ];
+            for (kind, default) in kinds {
+                if ui.selectable_label(current == kind, kind).clicked() && current != kind {
+                    *light = default;
+                    changed = true;
+                }
+            }
+        });
+
+    let Some(light) = light else {
+        return changed;
+    };
+    let (color, intensity, range, angles) = match light {
+        Light::Directional { color, intensity } => (color, intensity, None, None),
+        Light::Point {
+            color,
+            intensity,
+            range,
+        } => (color, intensity, Some(range), None),
+        Light::Spot {
+            color,
+            intensity,
+            range,
+            inner_angle,
+            outer_angle,
+        } => (
+            color,
+            intensity,
+            Some(range),
+            Some((inner_angle, outer_angle)),
+        ),
+    };
+
+    ui.horizontal(|ui| {
+        ui.label("Color");
+        let mut rgb = color.to_array();
+        if ui.color_edit_button_rgb(&mut rgb).changed() {
+            *color = Vec3::from_array(rgb);
+            changed = true;
+        }
+    });
+    changed |= ui
+        .add(
+            egui::Slider::new(intensity, 0.0..=100.0)
+                .logarithmic(true)
+                .text("Intensity"),
+        )
+        .changed();
+    if let Some(range) = range {
+        changed |= ui
+            .add(
+                egui::Slider::new(range, 0.1..=1000.0)
+                    .logarithmic(true)
+                    .text("Range"),
+            )
+            .changed();
+    }
+    if let Some((inner_angle, outer_angle)) = angles {
+        ui.horizontal(|ui| {
+            ui.label("Cone");
+            changed |= ui.drag_angle(inner_angle).changed();
+            changed |= ui.drag_angle(outer_angle).changed();
+        });
+        *inner_angle = inner_angle.clamp(0.0, *outer_angle);
+    }
+
+    changed
+}
+
+fn camera_ui(ui: &mut Ui, camera: &mut SceneCamera) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Field of view");
+        changed |= ui.drag_angle(&mut camera.fov_y).changed();
+    });
+    ui.horizontal(|ui| {
+        ui.label("Near / far");
+        changed |= ui
+            .add(
+                DragValue::new(&mut camera.near)
+                    .speed(0.01)
+                    .range(1e-4..=camera.far),
+            )
+            .changed();
+        changed |= ui
+            .add(
+                DragValue::new(&mut camera.far)
+                    .speed(1.0)
+                    .range(camera.near..=1e6),
+            )
+            .changed();
+    });
+    changed
+}
+
+/// Renderer settings section for `TonemapSettings`. Returns whether anything changed.
+pub fn tonemap_settings_ui(ui: &mut Ui, settings: &mut TonemapSettings) -> bool {
+    let mut changed = ui
+        .add(
+            egui::Slider::new(&mut settings.exposure, 0.01..=16.0)
+                .logarithmic(true)
+                .text("Exposure"),
+        )
+        .changed();
+
+    ComboBox::from_label("Tonemap operator")
+        .selected_text(format!("{:?}", settings.operator))
+        .show_ui(ui, |ui| {
+            for operator in [
+                TonemapOperator::Aces,
+                TonemapOperator::Reinhard,
+                TonemapOperator::Clamp,
+            ] {
+                changed |= ui
+                    .selectable_value(&mut settings.operator, operator, format!("{:?}", operator))
+                    .changed();
+            }
+        });
+
+    changed
+        | ui.add(egui::Slider::new(&mut settings.lut_strength, 0.0..=1.0).text("LUT strength"))
+            .changed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::SceneNode;
+
+    fn test_scene() -> Scene {
+        let mut scene = Scene::new();
+        let root = scene.add_node(SceneNode::new("root"));
+        let arm = scene.add_node(SceneNode::new("arm").with_parent(root));
+        scene.add_node(SceneNode::new("hand").with_parent(arm));
+        scene.add_node(SceneNode::new("sun").with_light(Light::Directional {
+            color: Vec3::ONE,
+            intensity: 3.0,
+        }));
+        scene
+    }
+
+    #[test]
+    fn builds_hierarchy_from_parent_indices() {
+        let mut scene = test_scene();
+        // A forward parent reference is invalid and shown as a root.
+        scene.add_node(SceneNode::new("broken").with_parent(7));
+
+        let (roots, children) = hierarchy(&scene);
+        assert_eq!(roots, vec![0, 3, 4]);
+        assert_eq!(children[0], vec![1]);
+        assert_eq!(children[1], vec![2]);
+        assert!(children[2].is_empty());
+    }
+
+    #[test]
+    fn euler_angles_survive_equivalent_rotations() {
+        let euler = Vec3::new(0.3, -1.2, 2.0);
+        let rotation = Quat::from_euler(EulerRot::XYZ, euler.x, euler.y, euler.z);
+        assert_eq!(sync_euler(euler, rotation), euler);
+        assert_eq!(sync_euler(euler, -rotation), euler);
+
+        let other = Quat::from_rotation_y(0.5);
+        let synced = sync_euler(euler, other);
+        assert!(
+            Quat::from_euler(EulerRot::XYZ, synced.x, synced.y, synced.z).abs_diff_eq(other, 1e-5)
+        );
+    }
+
+    #[test]
+    fn shows_headless_without_changes() {
+        let ctx = egui::Context::default();
+        let mut scene = test_scene();
+        let before = scene.clone();
+        let mut inspector = SceneInspector::new();
+        inspector.select(Some(2));
+
+        let mut changes = InspectorChanges::default();
+        for _ in 0..2 {
+            ctx.run_ui(egui::RawInput::default(), |ui| {
+                changes = inspector.show(ui.ctx(), &mut scene, |ui| {
+                    tonemap_settings_ui(ui, &mut TonemapSettings::default())
+                });
+            })
+            .drop_without_applying_deltas();
+        }
+
+        assert!(!changes.any());
+        assert_eq!(scene, before);
+        assert_eq!(inspector.selected(), Some(2));
+
+        scene.nodes.truncate(2);
+        ctx.run_ui(egui::RawInput::default(), |ui| {
+            inspector.show(ui.ctx(), &mut scene, |_| false);
+        })
+        .drop_without_applying_deltas();
+        assert_eq!(inspector.selected(), None);
+    }
+}
@@ -0,0 +1,13 @@
+pub mod egui_overlay;
+pub mod egui_renderer;
+pub mod gizmo;
+pub mod inspector;
+pub mod perf_hud;
+pub mod texture_viewer;
+
+pub use egui_overlay::*;
+pub use egui_renderer::*;
+pub use gizmo::*;
+pub use inspector::*;
+pub use perf_hud::*;
+pub use texture_viewer::*;
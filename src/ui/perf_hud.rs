@@ -0,0 +1,236 @@
+use egui::{Color32, Pos2, Rect, Sense, Stroke, vec2};
+use std::collections::VecDeque;
+
+use crate::vulkan::GpuPassTiming;
+
+/// Work submitted in one frame, as counted by the renderer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameCounters {
+    pub draw_calls: u32,
+    pub triangles: u64,
+}
+
+/// Summary of the frame times in a `PerfHud`'s history, in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameTimeStats {
+    pub latest: f32,
+    pub average: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+/// On-screen performance overlay: a CPU frame-time graph, GPU pass bars from a `GpuProfiler`
+/// and the frame's draw and triangle counts. Toggle it with `visible`, e.g. from a key press.
+#[derive(Debug, Clone)]
+pub struct PerfHud {
+    pub visible: bool,
+    frame_times: VecDeque<f32>,
+    history: usize,
+    gpu_passes: Vec<GpuPassTiming>,
+    counters: FrameCounters,
+}
+
+impl PerfHud {
+    /// Keeps the last `history` frame times for the graph.
+    pub fn new(history: usize) -> Self {
+        Self {
+            visible: true,
+            frame_times: VecDeque::with_capacity(history),
+            history: history.max(1),
+            gpu_passes: Vec::new(),
+            counters: FrameCounters::default(),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn push_frame_time(&mut self, milliseconds: f32) {
+        if self.frame_times.len() == self.history {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(milliseconds);
+    }
+
+    /// Latest GPU pass timings, e.g. from `GpuProfiler::begin_frame`.
+    pub fn set_gpu_passes(&mut self, passes: Vec<GpuPassTiming>) {
+        self.gpu_passes = passes;
+    }
+
+    pub fn set_counters(&mut self, counters: FrameCounters) {
+        self.counters = counters;
+    }
+
+    pub fn stats(&self) -> Option<FrameTimeStats> {
+        let latest = *self.frame_times.back()?;
+        let (min, max, sum) = self
+            .frame_times
+            .iter()
+            .fold((f32::MAX, 0.0f32, 0.0), |(min, max, sum), &time| {
+                (min.min(time), max.max(time), sum + time)
+            });
+
+        Some(FrameTimeStats {
+            latest,
+            average: sum / self.frame_times.len() as f32,
+            min,
+            max,
+        })
+    }
+
+    pub fn show(&self, ctx: &egui::Context) {
+        if !self.visible {
+            return;
+        }
+
+        egui::Area::new(egui::Id::new("perf_hud"))
+            .anchor(egui::Align2::RIGHT_TOP, vec2(-8.0, 8.0))
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_width(260.0);
+                    if let Some(stats) = self.stats() {
+                        ui.monospace(format!(
+                            "CPU {:6.2} ms ({:4.0} fps)",
+                            stats.average,
+                            1000.0 / stats.average.max(1e-3)
+                        ));
+                        ui.monospace(format!(
+                            "    min {:.2} / max {:.2} ms",
+                            stats.min, stats.max
+                        ));
+                        self.frame_graph(ui, stats.max);
+                    }
+
+                    if !self.gpu_passes.is_empty() {
+                        ui.separator();
+                        self.gpu_bars(ui);
+                    }
+
+                    ui.separator();
+                    ui.monospace(format!(
+                        "{} draws, {} triangles",
+                        self.counters.draw_calls, self.counters.triangles
+                    ));
+                });
+            });
+    }
+
+    /// Frame times as a line, with guides at the 60 and 30 fps budgets.
+    fn frame_graph(&self, ui: &mut egui::Ui, max: f32) {
+        let (rect, _) = ui.allocate_exact_size(vec2(ui.available_width(), 60.0), Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, Color32::from_black_alpha(96));
+
+        let scale = max.max(1000.0 / 30.0) * 1.1;
+        let y = |time: f32| rect.bottom() - time / scale * rect.height();
+        for budget in [1000.0 / 60.0, 1000.0 / 30.0] {
+            painter.hline(
+                rect.x_range(),
+                y(budget),
+                Stroke::new(1.0, Color32::from_gray(80)),
+            );
+        }
+
+        let step = rect.width() / (self.history.max(2) - 1) as f32;
+        let first = self.history - self.frame_times.len();
+        let points: Vec<Pos2> = self
+            .frame_times
+            .iter()
+            .enumerate()
+            .map(|(i, &time)| Pos2::new(rect.left() + (first + i) as f32 * step, y(time)))
+            .collect();
+        painter.add(egui::Shape::line(
+            points,
+            Stroke::new(1.5, Color32::from_rgb(120, 220, 120)),
+        ));
+    }
+
+    /// One bar per pass, placed on a shared timeline of the frame's GPU work.
+    fn gpu_bars(&self, ui: &mut egui::Ui) {
+        let total = self
+            .gpu_passes
+            .iter()
+            .map(|pass| pass.start_ms + pass.duration_ms)
+            .fold(0.0, f64::max)
+            .max(1e-3);
+        ui.monospace(format!("GPU {:6.2} ms", total));
+
+        for (index, pass) in self.gpu_passes.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.add_sized(
+                    vec2(90.0, 14.0),
+                    egui::Label::new(egui::RichText::new(&pass.name).monospace()).truncate(),
+                );
+                let (rect, _) =
+                    ui.allocate_exact_size(vec2(ui.available_width() - 56.0, 12.0), Sense::hover());
+                let x = |ms: f64| rect.left() + (ms / total) as f32 * rect.width();
+                let bar = Rect::from_x_y_ranges(
+                    x(pass.start_ms)
+                        ..=x(pass.start_ms + pass.duration_ms).max(x(pass.start_ms) + 1.0),
+                    rect.y_range(),
+                );
+                ui.painter()
+                    .rect_filled(rect, 1.0, Color32::from_black_alpha(96));
+                ui.painter().rect_filled(bar, 1.0, pass_color(index));
+                ui.monospace(format!("{:5.2}", pass.duration_ms));
+            });
+        }
+    }
+}
+
+fn pass_color(index: usize) -> Color32 {
+    const COLORS: [Color32; 6] = [
+        Color32::from_rgb(230, 120, 90),
+        Color32::from_rgb(90, 170, 230),
+        Color32::from_rgb(230, 200, 80),
+        Color32::from_rgb(140, 210, 110),
+        Color32::from_rgb(190, 120, 220),
+        Color32::from_rgb(90, 210, 190),
+    ];
+    COLORS[index % COLORS.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_a_bounded_history() {
+        let mut hud = PerfHud::new(4);
+        assert!(hud.stats().is_none());
+
+        for time in [100.0, 10.0, 20.0, 30.0, 40.0] {
+            hud.push_frame_time(time);
+        }
+
+        let stats = hud.stats().unwrap();
+        assert_eq!(stats.latest, 40.0);
+        assert_eq!(stats.average, 25.0);
+        assert_eq!(stats.min, 10.0);
+        assert_eq!(stats.max, 40.0);
+    }
+
+    #[test]
+    fn shows_headless() {
+        let ctx = egui::Context::default();
+        let mut hud = PerfHud::new(8);
+        hud.push_frame_time(16.0);
+        hud.set_gpu_passes(vec![GpuPassTiming {
+            name: "main".to_string(),
+            start_ms: 0.0,
+            duration_ms: 4.0,
+        }]);
+        hud.set_counters(FrameCounters {
+            draw_calls: 12,
+            triangles: 3400,
+        });
+
+        ctx.run_ui(egui::RawInput::default(), |ui| hud.show(ui.ctx()))
+            .drop_without_applying_deltas();
+
+        hud.toggle();
+        assert!(!hud.visible);
+    }
+}
@@ -0,0 +1,612 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use egui::{ComboBox, TextureFilter, TextureId};
+use std::sync::Arc;
+
+use crate::pipeline::{VulkanPipeline, VulkanPipelineBuilder};
+use crate::ui::EguiRenderer;
+use crate::vulkan::{
+    DeletionQueue, RecordCommands, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanImage, VulkanOffscreenTarget, VulkanPhysicalDevice, VulkanSampler,
+};
+
+/// Which channels of the viewed image are shown. Single channels are shown as grayscale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureChannel {
+    Rgb,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+impl TextureChannel {
+    const ALL: [Self; 5] = [Self::Rgb, Self::Red, Self::Green, Self::Blue, Self::Alpha];
+
+    fn mask(self) -> [f32; 4] {
+        match self {
+            Self::Rgb => [1.0, 1.0, 1.0, 0.0],
+            Self::Red => [1.0, 0.0, 0.0, 0.0],
+            Self::Green => [0.0, 1.0, 0.0, 0.0],
+            Self::Blue => [0.0, 0.0, 1.0, 0.0],
+            Self::Alpha => [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+}
+
+/// How the selected image is displayed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureViewSettings {
+    pub mip: u32,
+    pub layer: u32,
+    pub channel: TextureChannel,
+    /// Values mapped to black and white, e.g. widened for HDR targets.
+    pub range: [f32; 2],
+    /// Shows depth images as linear view distance between `near` and `far`, assuming the
+    /// [0, 1] depth of `Mat4::perspective_rh`.
+    pub linearize_depth: bool,
+    pub reversed_depth: bool,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for TextureViewSettings {
+    fn default() -> Self {
+        Self {
+            mip: 0,
+            layer: 0,
+            channel: TextureChannel::Rgb,
+            range: [0.0, 1.0],
+            linearize_depth: true,
+            reversed_depth: false,
+            near: 0.1,
+            far: 100.0,
+        }
+    }
+}
+
+const GRAYSCALE: u32 = 1;
+const LINEARIZE_DEPTH: u32 = 2;
+const REVERSED_DEPTH: u32 = 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TextureViewPushConstants {
+    channel_mask: [f32; 4],
+    range: [f32; 2],
+    near: f32,
+    far: f32,
+    flags: u32,
+}
+
+impl TextureViewSettings {
+    fn push_constants(&self, aspect_mask: vk::ImageAspectFlags) -> TextureViewPushConstants {
+        let depth = aspect_mask.contains(vk::ImageAspectFlags::DEPTH);
+        let channel = if depth {
+            TextureChannel::Red
+        } else {
+            self.channel
+        };
+
+        let mut flags = 0;
+        if channel != TextureChannel::Rgb {
+            flags |= GRAYSCALE;
+        }
+        if depth && self.linearize_depth {
+            flags |= LINEARIZE_DEPTH;
+            if self.reversed_depth {
+                flags |= REVERSED_DEPTH;
+            }
+        }
+
+        TextureViewPushConstants {
+            channel_mask: channel.mask(),
+            range: self.range,
+            near: self.near,
+            far: self.far,
+            flags,
+        }
+    }
+}
+
+/// An image listed by the viewer. The viewer only keeps its handles, so it must be
+/// re-registered whenever the image is recreated, and unregistered before it is destroyed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RegisteredImage {
+    image: vk::Image,
+    format: vk::Format,
+    extent: vk::Extent2D,
+    mip_levels: u32,
+    array_layers: u32,
+    aspect_mask: vk::ImageAspectFlags,
+    layout: vk::ImageLayout,
+}
+
+impl RegisteredImage {
+    /// Images the viewer can sample through a float `sampler2D`.
+    fn is_viewable(&self, depth: u32) -> bool {
+        if depth > 1 {
+            return false;
+        }
+        if self.aspect_mask.contains(vk::ImageAspectFlags::DEPTH) {
+            return true;
+        }
+        // Integer formats need an integer sampler; their names all end in UINT or SINT.
+        let name = format!("{:?}", self.format);
+        !(name.ends_with("UINT") || name.ends_with("SINT"))
+    }
+
+    /// Aspect sampled by the viewer: the depth of depth/stencil images, else the color.
+    fn view_aspect(&self) -> vk::ImageAspectFlags {
+        if self.aspect_mask.contains(vk::ImageAspectFlags::DEPTH) {
+            vk::ImageAspectFlags::DEPTH
+        } else {
+            vk::ImageAspectFlags::COLOR
+        }
+    }
+}
+
+/// View of one mip and layer of a registered image, with its own descriptor set so switching
+/// the selection never rewrites a set an in-flight frame may be reading.
+struct SourceView {
+    image: vk::Image,
+    mip: u32,
+    layer: u32,
+    view: vk::ImageView,
+    descriptor_set: vk::DescriptorSet,
+    descriptor_pool: VulkanDescriptorPool,
+    device: Arc<Device>,
+}
+
+impl Drop for SourceView {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_image_view(self.view, None);
+        }
+    }
+}
+
+/// Debug panel listing registered images and render targets and showing any of them, one mip
+/// and layer at a time, through the egui overlay.
+///
+/// The selected image is drawn into a preview target by `record`, remapped by the panel's
+/// `TextureViewSettings`, so G-buffer channels, HDR targets and depth buffers all display
+/// as ordinary images.
+pub struct TextureViewer {
+    pub open: bool,
+    pub settings: TextureViewSettings,
+    images: Vec<(String, RegisteredImage)>,
+    selected: Option<String>,
+    source: Option<SourceView>,
+    preview: VulkanOffscreenTarget,
+    preview_ready: bool,
+    texture_id: TextureId,
+    pipeline: VulkanPipeline,
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    sampler: VulkanSampler,
+    device: Arc<Device>,
+}
+
+impl TextureViewer {
+    pub const PREVIEW_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        egui_renderer: &mut EguiRenderer,
+        preview_extent: vk::Extent2D,
+    ) -> Result<Self> {
+        let preview = VulkanOffscreenTarget::new(
+            device,
+            physical_device,
+            preview_extent,
+            Self::PREVIEW_FORMAT,
+        )?;
+        let texture_id = egui_renderer.register_user_texture(
+            device,
+            preview.image.view,
+            TextureFilter::Nearest,
+        )?;
+
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[VulkanDescriptorSetLayout::binding(
+                0,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                vk::ShaderStageFlags::FRAGMENT,
+            )],
+        )?;
+
+        let pipeline = VulkanPipelineBuilder::new(device)
+            .set_render_pass(preview.render_pass.render_pass)
+            .set_extent(preview_extent)
+            .with_vertex_spv(include_bytes!("../../bin/fullscreen.vert.spv"))?
+            .with_fragment_spv(include_bytes!("../../bin/texture_view.frag.spv"))?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .offset(0)
+                    .size(std::mem::size_of::<TextureViewPushConstants>() as u32),
+            )
+            .with_cull_mode(vk::CullModeFlags::NONE)
+            .build()?;
+
+        Ok(Self {
+            open: true,
+            settings: TextureViewSettings::default(),
+            images: Vec::new(),
+            selected: None,
+            source: None,
+            preview,
+            preview_ready: false,
+            texture_id,
+            pipeline,
+            descriptor_set_layout,
+            sampler: VulkanSampler::nearest_clamp(device)?,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Lists `image` under `name`, replacing any image registered under the same name, e.g.
+    /// after a resize. The image needs `SAMPLED` usage and must be in `layout` whenever
+    /// `record` runs. Images a float sampler can't read, integer formats and 3D images, are
+    /// skipped.
+    pub fn register(&mut self, name: &str, image: &VulkanImage, layout: vk::ImageLayout) {
+        let registered = RegisteredImage {
+            image: image.image,
+            format: image.format,
+            extent: image.extent,
+            mip_levels: image.mip_levels,
+            array_layers: image.array_layers,
+            aspect_mask: image.aspect_mask,
+            layout,
+        };
+        if !registered.is_viewable(image.depth) {
+            return;
+        }
+
+        match self.images.iter_mut().find(|(n, _)| n == name) {
+            Some((_, entry)) => *entry = registered,
+            None => self.images.push((name.to_string(), registered)),
+        }
+    }
+
+    /// Removes `name` from the list. The view of it, if shown, is destroyed once `frame` has
+    /// completed, so the image itself must outlive that frame.
+    pub fn unregister(&mut self, name: &str, frame: u64, deletion_queue: &mut DeletionQueue) {
+        let Some(index) = self.images.iter().position(|(n, _)| n == name) else {
+            return;
+        };
+        let (_, removed) = self.images.remove(index);
+
+        if self
+            .source
+            .as_ref()
+            .is_some_and(|source| source.image == removed.image)
+            && let Some(source) = self.source.take()
+        {
+            deletion_queue.retire(frame, source);
+        }
+        if self.selected.as_deref() == Some(name) {
+            self.selected = None;
+            self.preview_ready = false;
+        }
+    }
+
+    pub fn image_names(&self) -> impl Iterator<Item = &str> {
+        self.images.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn select(&mut self, name: Option<&str>) {
+        self.selected = name.map(str::to_string);
+        self.settings.mip = 0;
+        self.settings.layer = 0;
+    }
+
+    fn selected_image(&self) -> Option<&RegisteredImage> {
+        let name = self.selected.as_deref()?;
+        self.images
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, image)| image)
+    }
+
+    /// Shows the viewer window: the image list, display settings and the preview from the
+    /// last `record`.
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let mut open = self.open;
+        egui::Window::new("Textures")
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                let mut clicked = None;
+                egui::ScrollArea::vertical()
+                    .max_height(160.0)
+                    .show(ui, |ui| {
+                        for (name, image) in &self.images {
+                            let label = format!(
+                                "{}  {}x{} {:?}",
+                                name, image.extent.width, image.extent.height, image.format
+                            );
+                            let selected = self.selected.as_deref() == Some(name.as_str());
+                            if ui.selectable_label(selected, label).clicked() {
+                                clicked = Some(name.clone());
+                            }
+                        }
+                    });
+                if let Some(name) = clicked {
+                    self.select(Some(&name));
+                }
+
+                let Some(image) = self.selected_image().copied() else {
+                    ui.weak("No image selected");
+                    return;
+                };
+                ui.separator();
+                self.settings_ui(ui, &image);
+
+                if self.preview_ready {
+                    let width = ui.available_width();
+                    let mip_width = (image.extent.width >> self.settings.mip).max(1) as f32;
+                    let mip_height = (image.extent.height >> self.settings.mip).max(1) as f32;
+                    ui.image((
+                        self.texture_id,
+                        egui::vec2(width, width * mip_height / mip_width),
+                    ));
+                }
+            });
+        self.open = open;
+    }
+
+    fn settings_ui(&mut self, ui: &mut egui::Ui, image: &RegisteredImage) {
+        let settings = &mut self.settings;
+        if image.mip_levels > 1 {
+            ui.add(egui::Slider::new(&mut settings.mip, 0..=image.mip_levels - 1).text("Mip"));
+        }
+        if image.array_layers > 1 {
+            ui.add(
+                egui::Slider::new(&mut settings.layer, 0..=image.array_layers - 1).text("Layer"),
+            );
+        }
+
+        if image.aspect_mask.contains(vk::ImageAspectFlags::DEPTH) {
+            ui.checkbox(&mut settings.linearize_depth, "Linearize depth");
+            if settings.linearize_depth {
+                ui.checkbox(&mut settings.reversed_depth, "Reversed Z");
+                ui.horizontal(|ui| {
+                    ui.label("Near / far");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.near)
+                            .speed(0.01)
+                            .range(1e-4..=settings.far),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut settings.far)
+                            .speed(1.0)
+                            .range(settings.near..=1e6),
+                    );
+                });
+            }
+        } else {
+            ComboBox::from_label("Channel")
+                .selected_text(format!("{:?}", settings.channel))
+                .show_ui(ui, |ui| {
+                    for channel in TextureChannel::ALL {
+                        ui.selectable_value(
+                            &mut settings.channel,
+                            channel,
+                            format!("{:?}", channel),
+                        );
+                    }
+                });
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Range");
+            ui.add(egui::DragValue::new(&mut settings.range[0]).speed(0.01));
+            ui.add(egui::DragValue::new(&mut settings.range[1]).speed(0.01));
+        });
+    }
+
+    /// Draws the selected image into the preview. Must be recorded outside a render pass,
+    /// after the passes writing the image and before the overlay is drawn. Views of images no
+    /// longer shown are destroyed once `frame` has completed.
+    pub fn record(
+        &mut self,
+        device: &VulkanDevice,
+        command_buffer: vk::CommandBuffer,
+        frame: u64,
+        deletion_queue: &mut DeletionQueue,
+    ) -> Result<()> {
+        let Some(image) = self.selected_image().copied().filter(|_| self.open) else {
+            return Ok(());
+        };
+        self.settings.mip = self.settings.mip.min(image.mip_levels - 1);
+        self.settings.layer = self.settings.layer.min(image.array_layers - 1);
+
+        let (mip, layer) = (self.settings.mip, self.settings.layer);
+        let current = self
+            .source
+            .as_ref()
+            .is_some_and(|s| s.image == image.image && s.mip == mip && s.layer == layer);
+        if !current {
+            let source = self.create_source_view(device, &image)?;
+            if let Some(previous) = self.source.replace(source) {
+                deletion_queue.retire(frame, previous);
+            }
+        }
+        let Some(source) = &self.source else {
+            return Ok(());
+        };
+
+        let extent = self.preview.extent();
+        let clear_values = [vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: [0.0, 0.0, 0.0, 1.0],
+            },
+        }];
+        let render_pass_info = vk::RenderPassBeginInfo::default()
+            .render_pass(self.preview.render_pass.render_pass)
+            .framebuffer(self.preview.framebuffer)
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            })
+            .clear_values(&clear_values);
+
+        unsafe {
+            self.device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_info,
+                vk::SubpassContents::INLINE,
+            );
+        }
+
+        self.pipeline.bind(command_buffer);
+        self.pipeline
+            .bind_descriptor_sets(command_buffer, 0, &[source.descriptor_set]);
+        self.pipeline.push_constants(
+            command_buffer,
+            vk::ShaderStageFlags::FRAGMENT,
+            0,
+            &self.settings.push_constants(image.aspect_mask),
+        );
+
+        unsafe {
+            self.device.draw(command_buffer, 3, 1, 0, 0);
+            self.device.cmd_end_render_pass(command_buffer);
+        }
+
+        self.preview_ready = true;
+        Ok(())
+    }
+
+    fn create_source_view(
+        &self,
+        device: &VulkanDevice,
+        image: &RegisteredImage,
+    ) -> Result<SourceView> {
+        let (mip, layer) = (self.settings.mip, self.settings.layer);
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(image.image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(image.format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: image.view_aspect(),
+                base_mip_level: mip,
+                level_count: 1,
+                base_array_layer: layer,
+                layer_count: 1,
+            });
+
+        let view = unsafe {
+            self.device
+                .create_image_view(&view_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create image view: {}", e))?
+        };
+
+        let mut source = SourceView {
+            image: image.image,
+            mip,
+            layer,
+            view,
+            descriptor_set: vk::DescriptorSet::null(),
+            descriptor_pool: VulkanDescriptorPool::for_layout(
+                device,
+                &self.descriptor_set_layout,
+                1,
+            )?,
+            device: self.device.clone(),
+        };
+        source.descriptor_set = source
+            .descriptor_pool
+            .allocate(&self.descriptor_set_layout)?;
+        source.descriptor_pool.write_image(
+            source.descriptor_set,
+            0,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            source.view,
+            self.sampler.sampler,
+            image.layout,
+        );
+
+        Ok(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ShaderReflection;
+
+    fn registered(format: vk::Format, aspect_mask: vk::ImageAspectFlags) -> RegisteredImage {
+        RegisteredImage {
+            image: vk::Image::null(),
+            format,
+            extent: vk::Extent2D {
+                width: 4,
+                height: 4,
+            },
+            mip_levels: 1,
+            array_layers: 1,
+            aspect_mask,
+            layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        }
+    }
+
+    #[test]
+    fn only_float_sampleable_images_are_listed() {
+        let color = vk::ImageAspectFlags::COLOR;
+        let depth = vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL;
+
+        assert!(registered(vk::Format::R16G16B16A16_SFLOAT, color).is_viewable(1));
+        assert!(registered(vk::Format::R8G8B8A8_SRGB, color).is_viewable(1));
+        assert!(registered(vk::Format::D24_UNORM_S8_UINT, depth).is_viewable(1));
+        assert!(!registered(vk::Format::R32_UINT, color).is_viewable(1));
+        assert!(!registered(vk::Format::R16G16_SINT, color).is_viewable(1));
+        assert!(!registered(vk::Format::R8G8B8A8_UNORM, color).is_viewable(4));
+
+        assert_eq!(
+            registered(vk::Format::D24_UNORM_S8_UINT, depth).view_aspect(),
+            vk::ImageAspectFlags::DEPTH
+        );
+    }
+
+    #[test]
+    fn settings_select_channels_and_depth_mode() {
+        let mut settings = TextureViewSettings::default();
+        let color = settings.push_constants(vk::ImageAspectFlags::COLOR);
+        assert_eq!(color.flags, 0);
+        assert_eq!(color.channel_mask, [1.0, 1.0, 1.0, 0.0]);
+
+        settings.channel = TextureChannel::Alpha;
+        let alpha = settings.push_constants(vk::ImageAspectFlags::COLOR);
+        assert_eq!(alpha.flags, GRAYSCALE);
+        assert_eq!(alpha.channel_mask, [0.0, 0.0, 0.0, 1.0]);
+
+        // Depth always shows the red channel, whatever the color channel setting.
+        settings.reversed_depth = true;
+        let depth = settings.push_constants(vk::ImageAspectFlags::DEPTH);
+        assert_eq!(depth.flags, GRAYSCALE | LINEARIZE_DEPTH | REVERSED_DEPTH);
+        assert_eq!(depth.channel_mask, [1.0, 0.0, 0.0, 0.0]);
+
+        settings.linearize_depth = false;
+        assert_eq!(
+            settings.push_constants(vk::ImageAspectFlags::DEPTH).flags,
+            GRAYSCALE
+        );
+    }
+
+    #[test]
+    fn shader_layout_matches_push_constants() {
+        let reflection =
+            ShaderReflection::from_spirv(include_bytes!("../../bin/texture_view.frag.spv"))
+                .unwrap();
+
+        assert_eq!(
+            reflection.push_constant_size,
+            Some(std::mem::size_of::<TextureViewPushConstants>() as u32)
+        );
+    }
+}
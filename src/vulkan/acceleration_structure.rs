@@ -0,0 +1,817 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use glam::Mat4;
+use std::sync::Arc;
+
+use crate::vulkan::{VulkanBuffer, VulkanDevice, VulkanPhysicalDevice};
+
+/// Upper bound of `minAccelerationStructureScratchOffsetAlignment` across implementations.
+const SCRATCH_ALIGNMENT: vk::DeviceSize = 256;
+
+/// Indexed triangles (`R32G32B32_SFLOAT` positions, `u32` indices) a BLAS is built from. The
+/// buffers need `VulkanDeviceFeatures::acceleration_structure_input_usage`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlasGeometry {
+    pub vertex_address: vk::DeviceAddress,
+    pub vertex_stride: vk::DeviceSize,
+    /// Highest vertex index the triangles may reference, after `first_vertex`.
+    pub max_vertex: u32,
+    pub index_address: vk::DeviceAddress,
+    pub first_index: u32,
+    pub triangle_count: u32,
+    /// Added to every index, like `vertexOffset` of an indexed draw.
+    pub first_vertex: u32,
+}
+
+impl BlasGeometry {
+    pub fn new(
+        vertices: &VulkanBuffer,
+        vertex_stride: vk::DeviceSize,
+        vertex_count: u32,
+        indices: &VulkanBuffer,
+        index_count: u32,
+    ) -> Self {
+        Self {
+            vertex_address: vertices.device_address(),
+            vertex_stride,
+            max_vertex: vertex_count.saturating_sub(1),
+            index_address: indices.device_address(),
+            first_index: 0,
+            triangle_count: index_count / 3,
+            first_vertex: 0,
+        }
+    }
+
+    fn geometry(&self) -> vk::AccelerationStructureGeometryKHR<'static> {
+        let triangles = vk::AccelerationStructureGeometryTrianglesDataKHR::default()
+            .vertex_format(vk::Format::R32G32B32_SFLOAT)
+            .vertex_data(vk::DeviceOrHostAddressConstKHR {
+                device_address: self.vertex_address,
+            })
+            .vertex_stride(self.vertex_stride)
+            .max_vertex(self.max_vertex)
+            .index_type(vk::IndexType::UINT32)
+            .index_data(vk::DeviceOrHostAddressConstKHR {
+                device_address: self.index_address,
+            });
+
+        vk::AccelerationStructureGeometryKHR::default()
+            .geometry_type(vk::GeometryTypeKHR::TRIANGLES)
+            .geometry(vk::AccelerationStructureGeometryDataKHR { triangles })
+            .flags(vk::GeometryFlagsKHR::OPAQUE)
+    }
+
+    fn range(&self) -> vk::AccelerationStructureBuildRangeInfoKHR {
+        vk::AccelerationStructureBuildRangeInfoKHR::default()
+            .primitive_count(self.triangle_count)
+            .primitive_offset(self.first_index * std::mem::size_of::<u32>() as u32)
+            .first_vertex(self.first_vertex)
+    }
+}
+
+/// How a BLAS is expected to change after its first build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlasUsage {
+    /// Built once and traced many times; can be compacted.
+    Static,
+    /// Deforming geometry, refit most frames and rebuilt now and then.
+    Dynamic,
+}
+
+impl BlasUsage {
+    pub fn build_flags(self) -> vk::BuildAccelerationStructureFlagsKHR {
+        match self {
+            BlasUsage::Static => {
+                vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE
+                    | vk::BuildAccelerationStructureFlagsKHR::ALLOW_COMPACTION
+            }
+            BlasUsage::Dynamic => {
+                vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_BUILD
+                    | vk::BuildAccelerationStructureFlagsKHR::ALLOW_UPDATE
+            }
+        }
+    }
+}
+
+/// What `Blas::cmd_update` recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlasUpdate {
+    /// Bounding boxes refit in place: cheap, but traversal degrades as the geometry drifts
+    /// from the shape the hierarchy was built for.
+    Refit,
+    Rebuild,
+}
+
+/// Chooses between refitting and rebuilding a dynamic BLAS.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlasUpdatePolicy {
+    /// Deformation since the last build, relative to the mesh radius, above which the BLAS is
+    /// rebuilt.
+    pub rebuild_deformation: f32,
+    /// Refits after which the BLAS is rebuilt regardless of deformation.
+    pub max_refits: u32,
+}
+
+impl Default for BlasUpdatePolicy {
+    fn default() -> Self {
+        Self {
+            rebuild_deformation: 0.25,
+            max_refits: 120,
+        }
+    }
+}
+
+impl BlasUpdatePolicy {
+    pub fn decide(&self, deformation: f32, refits_since_build: u32) -> BlasUpdate {
+        if deformation > self.rebuild_deformation || refits_since_build >= self.max_refits {
+            BlasUpdate::Rebuild
+        } else {
+            BlasUpdate::Refit
+        }
+    }
+}
+
+/// Upper bound of how far any vertex within `radius` of the origin moved between two joint
+/// palettes, relative to `radius`; cheap to evaluate for skinned meshes every frame.
+pub fn joint_deformation(build_joints: &[Mat4], joints: &[Mat4], radius: f32) -> f32 {
+    if radius <= 0.0 {
+        return 0.0;
+    }
+
+    build_joints
+        .iter()
+        .zip(joints)
+        .map(|(build, current)| {
+            let delta = *current - *build;
+            let linear = [delta.x_axis, delta.y_axis, delta.z_axis]
+                .iter()
+                .map(|axis| axis.truncate().length_squared())
+                .sum::<f32>()
+                .sqrt();
+            delta.w_axis.truncate().length() + linear * radius
+        })
+        .fold(0.0, f32::max)
+        / radius
+}
+
+/// Records a barrier making acceleration structure builds visible to `dst_stage`, e.g. a
+/// later build reading them or shaders tracing rays.
+pub fn cmd_acceleration_structure_barrier(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    dst_stage: vk::PipelineStageFlags,
+    dst_access: vk::AccessFlags,
+) {
+    let barrier = vk::MemoryBarrier::default()
+        .src_access_mask(vk::AccessFlags::ACCELERATION_STRUCTURE_WRITE_KHR)
+        .dst_access_mask(dst_access);
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR,
+            dst_stage,
+            vk::DependencyFlags::empty(),
+            std::slice::from_ref(&barrier),
+            &[],
+            &[],
+        );
+    }
+}
+
+/// Bottom-level acceleration structure over one triangle mesh.
+///
+/// Requires `VulkanDeviceFeatures::acceleration_structure`.
+pub struct Blas {
+    pub acceleration_structure: vk::AccelerationStructureKHR,
+    buffer: VulkanBuffer,
+    scratch: Option<VulkanBuffer>,
+    geometry: BlasGeometry,
+    usage: BlasUsage,
+    built: bool,
+    refits_since_build: u32,
+    loader: ash::khr::acceleration_structure::Device,
+    device: Arc<Device>,
+}
+
+impl Blas {
+    /// Allocates the BLAS and its scratch memory; `cmd_build` records the first build.
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        geometry: BlasGeometry,
+        usage: BlasUsage,
+    ) -> Result<Self> {
+        let loader = device
+            .acceleration_structure
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Device doesn't support acceleration structures"))?;
+
+        let geometries = [geometry.geometry()];
+        let build_info = vk::AccelerationStructureBuildGeometryInfoKHR::default()
+            .ty(vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL)
+            .flags(usage.build_flags())
+            .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
+            .geometries(&geometries);
+
+        let mut sizes = vk::AccelerationStructureBuildSizesInfoKHR::default();
+        unsafe {
+            loader.get_acceleration_structure_build_sizes(
+                vk::AccelerationStructureBuildTypeKHR::DEVICE,
+                &build_info,
+                &[geometry.triangle_count],
+                &mut sizes,
+            );
+        }
+
+        let (acceleration_structure, buffer) = create_acceleration_structure(
+            device,
+            physical_device,
+            &loader,
+            sizes.acceleration_structure_size,
+            vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
+        )?;
+
+        let scratch_size = sizes.build_scratch_size.max(sizes.update_scratch_size);
+        let scratch = VulkanBuffer::new(
+            device,
+            physical_device,
+            scratch_size + SCRATCH_ALIGNMENT,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        let scratch = match scratch {
+            Ok(scratch) => scratch,
+            Err(e) => {
+                unsafe { loader.destroy_acceleration_structure(acceleration_structure, None) };
+                return Err(e);
+            }
+        };
+
+        Ok(Self {
+            acceleration_structure,
+            buffer,
+            scratch: Some(scratch),
+            geometry,
+            usage,
+            built: false,
+            refits_since_build: 0,
+            loader,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn device_address(&self) -> vk::DeviceAddress {
+        let info = vk::AccelerationStructureDeviceAddressInfoKHR::default()
+            .acceleration_structure(self.acceleration_structure);
+        unsafe { self.loader.get_acceleration_structure_device_address(&info) }
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        self.buffer.size
+    }
+
+    pub fn usage(&self) -> BlasUsage {
+        self.usage
+    }
+
+    pub fn refits_since_build(&self) -> u32 {
+        self.refits_since_build
+    }
+
+    /// Replaces the geometry for the next build, e.g. after the vertex buffer moved. Counts
+    /// may not grow beyond those the BLAS was created with.
+    pub fn set_geometry(&mut self, geometry: BlasGeometry) -> Result<()> {
+        if geometry.triangle_count > self.geometry.triangle_count {
+            return Err(anyhow::anyhow!(
+                "BLAS was sized for {} triangles, not {}",
+                self.geometry.triangle_count,
+                geometry.triangle_count
+            ));
+        }
+        self.geometry = geometry;
+        Ok(())
+    }
+
+    /// Records a full build. Follow with `cmd_barrier` before use.
+    pub fn cmd_build(&mut self, command_buffer: vk::CommandBuffer) -> Result<()> {
+        self.cmd_build_mode(command_buffer, vk::BuildAccelerationStructureModeKHR::BUILD)?;
+        self.built = true;
+        self.refits_since_build = 0;
+        Ok(())
+    }
+
+    /// Makes the last build, refit or compaction visible to `dst_stage`.
+    pub fn cmd_barrier(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        dst_stage: vk::PipelineStageFlags,
+        dst_access: vk::AccessFlags,
+    ) {
+        cmd_acceleration_structure_barrier(&self.device, command_buffer, dst_stage, dst_access);
+    }
+
+    /// Records a refit of a built `Dynamic` BLAS to the current vertex positions, keeping its
+    /// hierarchy. The triangle count and topology must not change.
+    pub fn cmd_refit(&mut self, command_buffer: vk::CommandBuffer) -> Result<()> {
+        if self.usage != BlasUsage::Dynamic {
+            return Err(anyhow::anyhow!("Only dynamic BLASes can be refit"));
+        }
+        if !self.built {
+            return Err(anyhow::anyhow!("BLAS must be built before it can be refit"));
+        }
+
+        self.cmd_build_mode(
+            command_buffer,
+            vk::BuildAccelerationStructureModeKHR::UPDATE,
+        )?;
+        self.refits_since_build += 1;
+        Ok(())
+    }
+
+    /// Records a refit or a rebuild, as `policy` decides from `deformation` (see
+    /// `joint_deformation`) and the refits since the last build.
+    pub fn cmd_update(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        deformation: f32,
+        policy: &BlasUpdatePolicy,
+    ) -> Result<BlasUpdate> {
+        let update = if self.built && self.usage == BlasUsage::Dynamic {
+            policy.decide(deformation, self.refits_since_build)
+        } else {
+            BlasUpdate::Rebuild
+        };
+
+        match update {
+            BlasUpdate::Refit => self.cmd_refit(command_buffer)?,
+            BlasUpdate::Rebuild => self.cmd_build(command_buffer)?,
+        }
+        Ok(update)
+    }
+
+    fn cmd_build_mode(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        mode: vk::BuildAccelerationStructureModeKHR,
+    ) -> Result<()> {
+        let scratch = self
+            .scratch
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Compacted BLASes can't be rebuilt"))?;
+        let scratch_address = scratch.device_address().next_multiple_of(SCRATCH_ALIGNMENT);
+
+        let geometries = [self.geometry.geometry()];
+        let mut build_info = vk::AccelerationStructureBuildGeometryInfoKHR::default()
+            .ty(vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL)
+            .flags(self.usage.build_flags())
+            .mode(mode)
+            .dst_acceleration_structure(self.acceleration_structure)
+            .geometries(&geometries)
+            .scratch_data(vk::DeviceOrHostAddressKHR {
+                device_address: scratch_address,
+            });
+        if mode == vk::BuildAccelerationStructureModeKHR::UPDATE {
+            build_info = build_info.src_acceleration_structure(self.acceleration_structure);
+        }
+
+        let ranges = [self.geometry.range()];
+        unsafe {
+            self.loader.cmd_build_acceleration_structures(
+                command_buffer,
+                std::slice::from_ref(&build_info),
+                &[&ranges],
+            );
+        }
+        Ok(())
+    }
+
+    /// Records the compacted size of this built `Static` BLAS into query `index`, for
+    /// `cmd_compact` once the result is read back.
+    pub fn cmd_write_compacted_size(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        queries: &CompactionQueries,
+        index: u32,
+    ) {
+        unsafe {
+            self.loader.cmd_write_acceleration_structures_properties(
+                command_buffer,
+                &[self.acceleration_structure],
+                vk::QueryType::ACCELERATION_STRUCTURE_COMPACTED_SIZE_KHR,
+                queries.query_pool,
+                index,
+            );
+        }
+    }
+
+    /// Records a copy of this BLAS into a new one of `compacted_size` bytes, without scratch
+    /// memory. Retire `self` (e.g. in a `DeletionQueue`) once the copy has executed.
+    pub fn cmd_compact(
+        &self,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        command_buffer: vk::CommandBuffer,
+        compacted_size: vk::DeviceSize,
+    ) -> Result<Blas> {
+        if self.usage != BlasUsage::Static || !self.built {
+            return Err(anyhow::anyhow!("Only built static BLASes can be compacted"));
+        }
+
+        let (acceleration_structure, buffer) = create_acceleration_structure(
+            device,
+            physical_device,
+            &self.loader,
+            compacted_size,
+            vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
+        )?;
+
+        let copy_info = vk::CopyAccelerationStructureInfoKHR::default()
+            .src(self.acceleration_structure)
+            .dst(acceleration_structure)
+            .mode(vk::CopyAccelerationStructureModeKHR::COMPACT);
+        unsafe {
+            self.loader
+                .cmd_copy_acceleration_structure(command_buffer, &copy_info);
+        }
+
+        Ok(Blas {
+            acceleration_structure,
+            buffer,
+            scratch: None,
+            geometry: self.geometry,
+            usage: self.usage,
+            built: true,
+            refits_since_build: 0,
+            loader: self.loader.clone(),
+            device: self.device.clone(),
+        })
+    }
+}
+
+impl Drop for Blas {
+    fn drop(&mut self) {
+        unsafe {
+            self.loader
+                .destroy_acceleration_structure(self.acceleration_structure, None);
+        }
+    }
+}
+
+/// Creates an acceleration structure of `size` bytes in a buffer of its own. Shared by the
+/// bottom and top levels.
+pub(crate) fn create_acceleration_structure(
+    device: &VulkanDevice,
+    physical_device: &VulkanPhysicalDevice,
+    loader: &ash::khr::acceleration_structure::Device,
+    size: vk::DeviceSize,
+    ty: vk::AccelerationStructureTypeKHR,
+) -> Result<(vk::AccelerationStructureKHR, VulkanBuffer)> {
+    let buffer = VulkanBuffer::new(
+        device,
+        physical_device,
+        size,
+        vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR
+            | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )?;
+
+    let create_info = vk::AccelerationStructureCreateInfoKHR::default()
+        .buffer(buffer.buffer)
+        .size(size)
+        .ty(ty);
+
+    let acceleration_structure = unsafe {
+        loader
+            .create_acceleration_structure(&create_info, None)
+            .map_err(|e| anyhow::anyhow!("Failed to create acceleration structure: {}", e))?
+    };
+
+    Ok((acceleration_structure, buffer))
+}
+
+/// Row-major 3x4 matrix of an affine `transform`, as TLAS instances store it.
+pub fn instance_transform(transform: Mat4) -> vk::TransformMatrixKHR {
+    let rows = [transform.row(0), transform.row(1), transform.row(2)];
+    vk::TransformMatrixKHR {
+        matrix: std::array::from_fn(|i| rows[i / 4][i % 4]),
+    }
+}
+
+/// Opaque, double-sided TLAS instance of `blas`. `custom_index` (24 bits) is what shaders
+/// read back as the instance custom index, e.g. a `GpuScene` object index.
+pub fn tlas_instance(
+    transform: Mat4,
+    custom_index: u32,
+    blas: &Blas,
+) -> vk::AccelerationStructureInstanceKHR {
+    vk::AccelerationStructureInstanceKHR {
+        transform: instance_transform(transform),
+        instance_custom_index_and_mask: vk::Packed24_8::new(custom_index, 0xff),
+        instance_shader_binding_table_record_offset_and_flags: vk::Packed24_8::new(
+            0,
+            vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE.as_raw() as u8,
+        ),
+        acceleration_structure_reference: vk::AccelerationStructureReferenceKHR {
+            device_handle: blas.device_address(),
+        },
+    }
+}
+
+/// Top-level acceleration structure over up to `max_instances` BLAS instances, rebuilt from
+/// a host-visible instance buffer.
+///
+/// Requires `VulkanDeviceFeatures::acceleration_structure`.
+pub struct Tlas {
+    pub acceleration_structure: vk::AccelerationStructureKHR,
+    _buffer: VulkanBuffer,
+    scratch: VulkanBuffer,
+    instance_buffer: VulkanBuffer,
+    max_instances: u32,
+    instance_count: u32,
+    loader: ash::khr::acceleration_structure::Device,
+    device: Arc<Device>,
+}
+
+impl Tlas {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        max_instances: u32,
+    ) -> Result<Self> {
+        let loader = device
+            .acceleration_structure
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Device doesn't support acceleration structures"))?;
+
+        let instance_buffer = VulkanBuffer::new_host_visible(
+            device,
+            physical_device,
+            max_instances.max(1) as vk::DeviceSize
+                * std::mem::size_of::<vk::AccelerationStructureInstanceKHR>() as vk::DeviceSize,
+            device.features.acceleration_structure_input_usage(),
+        )?;
+
+        let geometries = [Self::geometry(&instance_buffer)];
+        let build_info = vk::AccelerationStructureBuildGeometryInfoKHR::default()
+            .ty(vk::AccelerationStructureTypeKHR::TOP_LEVEL)
+            .flags(vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE)
+            .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
+            .geometries(&geometries);
+
+        let mut sizes = vk::AccelerationStructureBuildSizesInfoKHR::default();
+        unsafe {
+            loader.get_acceleration_structure_build_sizes(
+                vk::AccelerationStructureBuildTypeKHR::DEVICE,
+                &build_info,
+                &[max_instances],
+                &mut sizes,
+            );
+        }
+
+        let (acceleration_structure, buffer) = create_acceleration_structure(
+            device,
+            physical_device,
+            &loader,
+            sizes.acceleration_structure_size,
+            vk::AccelerationStructureTypeKHR::TOP_LEVEL,
+        )?;
+
+        let scratch = VulkanBuffer::new(
+            device,
+            physical_device,
+            sizes.build_scratch_size + SCRATCH_ALIGNMENT,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        let scratch = match scratch {
+            Ok(scratch) => scratch,
+            Err(e) => {
+                unsafe { loader.destroy_acceleration_structure(acceleration_structure, None) };
+                return Err(e);
+            }
+        };
+
+        Ok(Self {
+            acceleration_structure,
+            _buffer: buffer,
+            scratch,
+            instance_buffer,
+            max_instances,
+            instance_count: 0,
+            loader,
+            device: device.device.clone(),
+        })
+    }
+
+    fn geometry(instance_buffer: &VulkanBuffer) -> vk::AccelerationStructureGeometryKHR<'static> {
+        let instances = vk::AccelerationStructureGeometryInstancesDataKHR::default().data(
+            vk::DeviceOrHostAddressConstKHR {
+                device_address: instance_buffer.device_address(),
+            },
+        );
+
+        vk::AccelerationStructureGeometryKHR::default()
+            .geometry_type(vk::GeometryTypeKHR::INSTANCES)
+            .geometry(vk::AccelerationStructureGeometryDataKHR { instances })
+    }
+
+    pub fn instance_count(&self) -> u32 {
+        self.instance_count
+    }
+
+    /// Writes the instances for the next `cmd_build`. The caller must make sure no in-flight
+    /// frame is still building from the previous ones.
+    pub fn set_instances(
+        &mut self,
+        instances: &[vk::AccelerationStructureInstanceKHR],
+    ) -> Result<()> {
+        if instances.len() as u32 > self.max_instances {
+            return Err(anyhow::anyhow!(
+                "{} instances exceed the TLAS limit of {}",
+                instances.len(),
+                self.max_instances
+            ));
+        }
+
+        self.instance_buffer.write(0, instances)?;
+        self.instance_count = instances.len() as u32;
+        Ok(())
+    }
+
+    /// Records a rebuild over the current instances, whose BLASes must be built, then a
+    /// barrier making it visible to `dst_stage`.
+    pub fn cmd_build(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        dst_stage: vk::PipelineStageFlags,
+        dst_access: vk::AccessFlags,
+    ) {
+        let geometries = [Self::geometry(&self.instance_buffer)];
+        let build_info = vk::AccelerationStructureBuildGeometryInfoKHR::default()
+            .ty(vk::AccelerationStructureTypeKHR::TOP_LEVEL)
+            .flags(vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE)
+            .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
+            .dst_acceleration_structure(self.acceleration_structure)
+            .geometries(&geometries)
+            .scratch_data(vk::DeviceOrHostAddressKHR {
+                device_address: self
+                    .scratch
+                    .device_address()
+                    .next_multiple_of(SCRATCH_ALIGNMENT),
+            });
+
+        let ranges = [vk::AccelerationStructureBuildRangeInfoKHR::default()
+            .primitive_count(self.instance_count)];
+        unsafe {
+            self.loader.cmd_build_acceleration_structures(
+                command_buffer,
+                std::slice::from_ref(&build_info),
+                &[&ranges],
+            );
+        }
+
+        cmd_acceleration_structure_barrier(&self.device, command_buffer, dst_stage, dst_access);
+    }
+}
+
+impl Drop for Tlas {
+    fn drop(&mut self) {
+        unsafe {
+            self.loader
+                .destroy_acceleration_structure(self.acceleration_structure, None);
+        }
+    }
+}
+
+/// Compacted-size queries of BLASes built with `BlasUsage::Static`.
+pub struct CompactionQueries {
+    pub query_pool: vk::QueryPool,
+    capacity: u32,
+    device: Arc<Device>,
+}
+
+impl CompactionQueries {
+    pub fn new(device: &VulkanDevice, capacity: u32) -> Result<Self> {
+        let pool_info = vk::QueryPoolCreateInfo::default()
+            .query_type(vk::QueryType::ACCELERATION_STRUCTURE_COMPACTED_SIZE_KHR)
+            .query_count(capacity);
+
+        let query_pool = unsafe {
+            device
+                .device
+                .create_query_pool(&pool_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create query pool: {}", e))?
+        };
+
+        Ok(Self {
+            query_pool,
+            capacity,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Resets every query. Must be recorded before the sizes are written.
+    pub fn cmd_reset(&self, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            self.device
+                .cmd_reset_query_pool(command_buffer, self.query_pool, 0, self.capacity);
+        }
+    }
+
+    /// Compacted sizes of the first `count` queries, or `None` while some aren't available
+    /// yet.
+    pub fn read(&self, count: u32) -> Result<Option<Vec<vk::DeviceSize>>> {
+        let mut sizes = vec![0u64; count as usize];
+        let result = unsafe {
+            self.device.get_query_pool_results(
+                self.query_pool,
+                0,
+                &mut sizes,
+                vk::QueryResultFlags::TYPE_64,
+            )
+        };
+
+        match result {
+            Ok(()) => Ok(Some(sizes)),
+            Err(vk::Result::NOT_READY) => Ok(None),
+            Err(e) => Err(anyhow::anyhow!("Failed to read compacted sizes: {}", e)),
+        }
+    }
+}
+
+impl Drop for CompactionQueries {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_query_pool(self.query_pool, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    #[test]
+    fn usage_selects_build_flags() {
+        assert!(
+            BlasUsage::Static
+                .build_flags()
+                .contains(vk::BuildAccelerationStructureFlagsKHR::ALLOW_COMPACTION)
+        );
+        assert!(
+            BlasUsage::Dynamic
+                .build_flags()
+                .contains(vk::BuildAccelerationStructureFlagsKHR::ALLOW_UPDATE)
+        );
+    }
+
+    #[test]
+    fn policy_rebuilds_on_large_deformation_or_many_refits() {
+        let policy = BlasUpdatePolicy {
+            rebuild_deformation: 0.25,
+            max_refits: 10,
+        };
+
+        assert_eq!(policy.decide(0.1, 0), BlasUpdate::Refit);
+        assert_eq!(policy.decide(0.3, 0), BlasUpdate::Rebuild);
+        assert_eq!(policy.decide(0.1, 10), BlasUpdate::Rebuild);
+    }
+
+    #[test]
+    fn instance_transform_is_row_major() {
+        let transform = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0))
+            * Mat4::from_scale(Vec3::new(2.0, 2.0, 2.0));
+
+        assert_eq!(
+            instance_transform(transform).matrix,
+            [2.0, 0.0, 0.0, 1.0, 0.0, 2.0, 0.0, 2.0, 0.0, 0.0, 2.0, 3.0]
+        );
+    }
+
+    #[test]
+    fn joint_deformation_bounds_vertex_motion() {
+        let rest = [Mat4::IDENTITY, Mat4::IDENTITY];
+        assert_eq!(joint_deformation(&rest, &rest, 2.0), 0.0);
+
+        // Moving one joint by 1 unit on a mesh of radius 2.
+        let moved = [
+            Mat4::IDENTITY,
+            Mat4::from_translation(Vec3::new(0.0, 1.0, 0.0)),
+        ];
+        assert!((joint_deformation(&rest, &moved, 2.0) - 0.5).abs() < 1e-6);
+
+        // A quarter turn moves the far point by radius * sqrt(2), within the bound.
+        let rotated = [
+            Mat4::IDENTITY,
+            Mat4::from_rotation_z(std::f32::consts::FRAC_PI_2),
+        ];
+        let deformation = joint_deformation(&rest, &rotated, 2.0);
+        assert!(deformation >= std::f32::consts::SQRT_2 - 1e-5);
+    }
+}
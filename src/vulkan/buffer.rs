@@ -0,0 +1,254 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::sync::Arc;
+
+use crate::vulkan::{VulkanDevice, VulkanPhysicalDevice};
+
+pub struct VulkanBuffer {
+    pub buffer: vk::Buffer,
+    pub memory: vk::DeviceMemory,
+    pub size: vk::DeviceSize,
+    /// Properties of the memory type the buffer was allocated from, which may include more
+    /// than were asked for.
+    pub memory_flags: vk::MemoryPropertyFlags,
+    pub device: Arc<Device>,
+}
+
+impl VulkanBuffer {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        memory_properties: vk::MemoryPropertyFlags,
+    ) -> Result<Self> {
+        let buffer_info = vk::BufferCreateInfo::default()
+            .size(size)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+        let buffer = unsafe {
+            device
+                .device
+                .create_buffer(&buffer_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create buffer: {}", e))?
+        };
+
+        let requirements = unsafe { device.device.get_buffer_memory_requirements(buffer) };
+
+        let memory_type_index =
+            physical_device.find_memory_type(requirements.memory_type_bits, memory_properties)?;
+
+        let mut allocate_flags =
+            vk::MemoryAllocateFlagsInfo::default().flags(vk::MemoryAllocateFlags::DEVICE_ADDRESS);
+        let mut alloc_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type_index);
+        if usage.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) {
+            alloc_info = alloc_info.push_next(&mut allocate_flags);
+        }
+
+        let memory = unsafe {
+            device
+                .device
+                .allocate_memory(&alloc_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to allocate buffer memory: {}", e))?
+        };
+
+        unsafe {
+            device
+                .device
+                .bind_buffer_memory(buffer, memory, 0)
+                .map_err(|e| anyhow::anyhow!("Failed to bind buffer memory: {}", e))?
+        };
+
+        Ok(Self {
+            buffer,
+            memory,
+            size,
+            memory_flags: physical_device.memory_properties.memory_types
+                [memory_type_index as usize]
+                .property_flags,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn new_host_visible(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+    ) -> Result<Self> {
+        Self::new(
+            device,
+            physical_device,
+            size,
+            usage,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )
+    }
+
+    /// Device-local buffer for data streamed from the CPU, like loaded meshes. With resizable
+    /// BAR it is also host-visible, so `UploadBatcher` writes it directly instead of going
+    /// through a staging copy; otherwise, or if BAR memory runs out, it is plain device-local
+    /// memory. `usage` should include `TRANSFER_DST` for the fallback.
+    pub fn new_streaming(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+    ) -> Result<Self> {
+        if physical_device.has_resizable_bar() {
+            let bar = Self::new(
+                device,
+                physical_device,
+                size,
+                usage,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL
+                    | vk::MemoryPropertyFlags::HOST_VISIBLE
+                    | vk::MemoryPropertyFlags::HOST_COHERENT,
+            );
+            if bar.is_ok() {
+                return bar;
+            }
+        }
+        Self::new(
+            device,
+            physical_device,
+            size,
+            usage,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+    }
+
+    /// True if the buffer is in VRAM the CPU can write directly.
+    pub fn is_host_writable_vram(&self) -> bool {
+        self.memory_flags.contains(
+            vk::MemoryPropertyFlags::DEVICE_LOCAL
+                | vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )
+    }
+
+    /// Copies `data` into the buffer at `offset`. The buffer must be host visible and coherent.
+    pub fn write<T: Copy>(&self, offset: vk::DeviceSize, data: &[T]) -> Result<()> {
+        let byte_len = std::mem::size_of_val(data) as vk::DeviceSize;
+
+        if offset + byte_len > self.size {
+            return Err(anyhow::anyhow!(
+                "Buffer write of {} bytes at offset {} exceeds buffer size {}",
+                byte_len,
+                offset,
+                self.size
+            ));
+        }
+
+        if byte_len == 0 {
+            return Ok(());
+        }
+
+        unsafe {
+            let ptr = self
+                .device
+                .map_memory(self.memory, offset, byte_len, vk::MemoryMapFlags::empty())
+                .map_err(|e| anyhow::anyhow!("Failed to map buffer memory: {}", e))?;
+
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr() as *const u8,
+                ptr as *mut u8,
+                byte_len as usize,
+            );
+
+            self.device.unmap_memory(self.memory);
+        }
+
+        Ok(())
+    }
+
+    /// Copies `count` values out of the buffer at `offset`. The buffer must be host visible and
+    /// coherent, and the GPU must be done writing it.
+    pub fn read<T: Copy>(&self, offset: vk::DeviceSize, count: usize) -> Result<Vec<T>> {
+        let byte_len = (count * std::mem::size_of::<T>()) as vk::DeviceSize;
+
+        if offset + byte_len > self.size {
+            return Err(anyhow::anyhow!(
+                "Buffer read of {} bytes at offset {} exceeds buffer size {}",
+                byte_len,
+                offset,
+                self.size
+            ));
+        }
+
+        let mut data = Vec::with_capacity(count);
+        if byte_len == 0 {
+            return Ok(data);
+        }
+
+        unsafe {
+            let ptr = self
+                .device
+                .map_memory(self.memory, offset, byte_len, vk::MemoryMapFlags::empty())
+                .map_err(|e| anyhow::anyhow!("Failed to map buffer memory: {}", e))?;
+
+            std::ptr::copy_nonoverlapping(ptr as *const T, data.as_mut_ptr(), count);
+            data.set_len(count);
+
+            self.device.unmap_memory(self.memory);
+        }
+
+        Ok(data)
+    }
+
+    /// Records a pipeline barrier covering the whole buffer.
+    pub fn cmd_barrier(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        src_stage: vk::PipelineStageFlags,
+        src_access: vk::AccessFlags,
+        dst_stage: vk::PipelineStageFlags,
+        dst_access: vk::AccessFlags,
+    ) {
+        let barrier = vk::BufferMemoryBarrier::default()
+            .src_access_mask(src_access)
+            .dst_access_mask(dst_access)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .buffer(self.buffer)
+            .offset(0)
+            .size(vk::WHOLE_SIZE);
+
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                std::slice::from_ref(&barrier),
+                &[],
+            );
+        }
+    }
+
+    /// GPU address of the buffer, which must have `SHADER_DEVICE_ADDRESS` usage.
+    pub fn device_address(&self) -> vk::DeviceAddress {
+        let info = vk::BufferDeviceAddressInfo::default().buffer(self.buffer);
+        unsafe { self.device.get_buffer_device_address(&info) }
+    }
+
+    pub fn descriptor_info(&self) -> vk::DescriptorBufferInfo {
+        vk::DescriptorBufferInfo::default()
+            .buffer(self.buffer)
+            .offset(0)
+            .range(self.size)
+    }
+}
+
+impl Drop for VulkanBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_buffer(self.buffer, None);
+            self.device.free_memory(self.memory, None);
+        }
+    }
+}
@@ -0,0 +1,473 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::sync::Arc;
+
+use crate::pipeline::{VulkanComputePipeline, VulkanComputePipelineBuilder};
+use crate::vulkan::{
+    DeletionQueue, VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanPhysicalDevice,
+};
+
+/// Word written over the padding after every guarded buffer.
+pub const BUFFER_CANARY: u32 = 0xdead_beef;
+
+/// Bytes of canary after each guarded buffer.
+pub const BUFFER_CANARY_SIZE: vk::DeviceSize = 256;
+
+/// Largest `minStorageBufferOffsetAlignment` the spec allows, so canary regions can be bound
+/// on any device.
+const GUARD_ALIGNMENT: vk::DeviceSize = 256;
+
+/// An out-of-bounds write found by `BufferGuard`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferOverrun {
+    /// The check that found it; the overrun happened since the previous check.
+    pub pass: String,
+    pub buffer: String,
+    pub corrupted_words: u32,
+}
+
+impl std::fmt::Display for BufferOverrun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "'{}' wrote {} word(s) past the end of buffer '{}'",
+            self.pass, self.corrupted_words, self.buffer
+        )
+    }
+}
+
+/// Where a guarded buffer's canary lives, in bytes and words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct GuardLayout {
+    padded_size: vk::DeviceSize,
+    /// Start of the canary, right after the 4-byte aligned user size.
+    fill_offset: vk::DeviceSize,
+    /// Start of the range bound for the check, aligned down to `GUARD_ALIGNMENT`.
+    region_offset: vk::DeviceSize,
+    first_word: u32,
+    word_count: u32,
+}
+
+impl GuardLayout {
+    fn new(size: vk::DeviceSize) -> Self {
+        let fill_offset = size.next_multiple_of(4);
+        let padded_size = fill_offset + BUFFER_CANARY_SIZE;
+        let region_offset = fill_offset / GUARD_ALIGNMENT * GUARD_ALIGNMENT;
+        Self {
+            padded_size,
+            fill_offset,
+            region_offset,
+            first_word: ((fill_offset - region_offset) / 4) as u32,
+            word_count: ((padded_size - region_offset) / 4) as u32,
+        }
+    }
+
+    fn region_size(&self) -> vk::DeviceSize {
+        self.padded_size - self.region_offset
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CheckPushConstants {
+    first_word: u32,
+    word_count: u32,
+    result_index: u32,
+}
+
+struct GuardedBuffer {
+    name: String,
+    buffer: vk::Buffer,
+    layout: GuardLayout,
+    descriptor_set: vk::DescriptorSet,
+    descriptor_pool: VulkanDescriptorPool,
+    /// Set until the canary has been written.
+    needs_fill: bool,
+}
+
+/// One `check` recorded in a frame: the pass name and the buffers it looked at.
+struct GuardCheck {
+    pass: String,
+    buffers: Vec<(u32, String)>,
+}
+
+/// Debug mode that pads storage and uniform buffers with a canary and checks it after each
+/// pass with a compute shader, to catch GPU-driven code writing out of bounds.
+///
+/// Allocate buffers through `create_buffer`, call `begin_frame` at the start of every frame
+/// and `check` after each pass worth blaming. Checks are recorded outside render passes and
+/// serialize the GPU, so keep this for debug builds. For reads out of bounds, or bounds
+/// checking without changing allocations, see `ValidationOptions::gpu_assisted` instead.
+pub struct BufferGuard {
+    pipeline: VulkanComputePipeline,
+    descriptor_set_layout: VulkanDescriptorSetLayout,
+    results: VulkanBuffer,
+    buffers: Vec<Option<GuardedBuffer>>,
+    frames: Vec<Vec<GuardCheck>>,
+    current: usize,
+    max_buffers: u32,
+    max_checks: u32,
+    device: Arc<Device>,
+}
+
+impl BufferGuard {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        frames_in_flight: usize,
+        max_buffers: u32,
+        max_checks: u32,
+    ) -> Result<Self> {
+        let descriptor_set_layout = VulkanDescriptorSetLayout::new(
+            device,
+            &[
+                VulkanDescriptorSetLayout::binding(
+                    0,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+                VulkanDescriptorSetLayout::binding(
+                    1,
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    vk::ShaderStageFlags::COMPUTE,
+                ),
+            ],
+        )?;
+
+        let pipeline = VulkanComputePipelineBuilder::new(device)
+            .with_shader_spv(include_bytes!("../../bin/buffer_canary.comp.spv"), None)?
+            .with_descriptor_set_layout(descriptor_set_layout.layout)
+            .with_push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .size(std::mem::size_of::<CheckPushConstants>() as u32),
+            )
+            .build()?;
+
+        let frames_in_flight = frames_in_flight.max(1);
+        let result_count = frames_in_flight as u64 * max_checks as u64 * max_buffers as u64;
+        let results = VulkanBuffer::new_host_visible(
+            device,
+            physical_device,
+            (result_count.max(1) * 4) as vk::DeviceSize,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+        )?;
+        results.write(0, &vec![0u32; result_count as usize])?;
+
+        Ok(Self {
+            pipeline,
+            descriptor_set_layout,
+            results,
+            buffers: (0..max_buffers).map(|_| None).collect(),
+            frames: (0..frames_in_flight).map(|_| Vec::new()).collect(),
+            current: 0,
+            max_buffers,
+            max_checks,
+            device: device.device.clone(),
+        })
+    }
+
+    /// Creates a buffer of `size` usable bytes followed by a canary, and starts checking it.
+    /// The returned buffer's `size` excludes the canary, so `descriptor_info` and `write` stay
+    /// in bounds. Call `unregister` before dropping it.
+    pub fn create_buffer(
+        &mut self,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        name: &str,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        memory_properties: vk::MemoryPropertyFlags,
+    ) -> Result<VulkanBuffer> {
+        let slot = self
+            .buffers
+            .iter()
+            .position(Option::is_none)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Failed to guard buffer '{}': all {} slots are in use",
+                    name,
+                    self.max_buffers
+                )
+            })?;
+
+        let layout = GuardLayout::new(size);
+        let mut buffer = VulkanBuffer::new(
+            device,
+            physical_device,
+            layout.padded_size,
+            usage | vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+            memory_properties,
+        )?;
+
+        let descriptor_pool =
+            VulkanDescriptorPool::for_layout(device, &self.descriptor_set_layout, 1)?;
+        let descriptor_set = descriptor_pool.allocate(&self.descriptor_set_layout)?;
+        descriptor_pool.write_buffer_range(
+            descriptor_set,
+            0,
+            vk::DescriptorType::STORAGE_BUFFER,
+            &buffer,
+            layout.region_offset,
+            layout.region_size(),
+        );
+        descriptor_pool.write_buffer(
+            descriptor_set,
+            1,
+            vk::DescriptorType::STORAGE_BUFFER,
+            &self.results,
+        );
+
+        self.buffers[slot] = Some(GuardedBuffer {
+            name: name.to_string(),
+            buffer: buffer.buffer,
+            layout,
+            descriptor_set,
+            descriptor_pool,
+            needs_fill: true,
+        });
+
+        buffer.size = size;
+        Ok(buffer)
+    }
+
+    /// Stops checking `buffer`. Its descriptor set is released once `frame` has completed.
+    pub fn unregister(
+        &mut self,
+        buffer: &VulkanBuffer,
+        frame: u64,
+        deletion_queue: &mut DeletionQueue,
+    ) {
+        let slot = self.buffers.iter_mut().find(|slot| {
+            slot.as_ref()
+                .is_some_and(|guarded| guarded.buffer == buffer.buffer)
+        });
+        if let Some(guarded) = slot.and_then(Option::take) {
+            deletion_queue.retire(frame, guarded.descriptor_pool);
+        }
+    }
+
+    /// Starts frame slot `frame_index` and returns the overruns its checks found the last time
+    /// it was used. Record outside a render pass, after waiting on the slot's fence.
+    pub fn begin_frame(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+    ) -> Result<Vec<BufferOverrun>> {
+        self.current = frame_index % self.frames.len();
+        let frame_results = self.max_checks as usize * self.max_buffers as usize;
+        let offset = (self.current * frame_results * 4) as vk::DeviceSize;
+
+        let checks = std::mem::take(&mut self.frames[self.current]);
+        let overruns = if checks.is_empty() {
+            Vec::new()
+        } else {
+            let counts = self.results.read::<u32>(offset, frame_results)?;
+            overruns(&checks, &counts, self.max_buffers)
+        };
+
+        unsafe {
+            if frame_results > 0 {
+                self.device.cmd_fill_buffer(
+                    command_buffer,
+                    self.results.buffer,
+                    offset,
+                    (frame_results * 4) as vk::DeviceSize,
+                    0,
+                );
+            }
+            for guarded in self.buffers.iter_mut().flatten() {
+                if guarded.needs_fill {
+                    self.device.cmd_fill_buffer(
+                        command_buffer,
+                        guarded.buffer,
+                        guarded.layout.fill_offset,
+                        BUFFER_CANARY_SIZE,
+                        BUFFER_CANARY,
+                    );
+                    guarded.needs_fill = false;
+                }
+            }
+        }
+        self.cmd_memory_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+        );
+
+        Ok(overruns)
+    }
+
+    /// Checks every guarded buffer's canary and blames any damage on `pass`, then restores the
+    /// canaries. Record outside a render pass. Checks past `max_checks` in a frame are skipped.
+    pub fn check(&mut self, command_buffer: vk::CommandBuffer, pass: &str) {
+        let check_index = self.frames[self.current].len() as u32;
+        if check_index >= self.max_checks {
+            return;
+        }
+
+        self.cmd_memory_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::AccessFlags::MEMORY_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        );
+
+        self.pipeline.bind(command_buffer);
+        let first_result = (self.current as u32 * self.max_checks + check_index) * self.max_buffers;
+        let mut buffers = Vec::new();
+        for (index, guarded) in self.buffers.iter().enumerate() {
+            let Some(guarded) = guarded.as_ref().filter(|guarded| !guarded.needs_fill) else {
+                continue;
+            };
+            self.pipeline
+                .bind_descriptor_sets(command_buffer, 0, &[guarded.descriptor_set]);
+            self.pipeline.push_constants(
+                command_buffer,
+                &CheckPushConstants {
+                    first_word: guarded.layout.first_word,
+                    word_count: guarded.layout.word_count,
+                    result_index: first_result + index as u32,
+                },
+            );
+            let words = guarded.layout.word_count - guarded.layout.first_word;
+            self.pipeline
+                .dispatch(command_buffer, words.div_ceil(64), 1, 1);
+            buffers.push((index as u32, guarded.name.clone()));
+        }
+
+        self.cmd_memory_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::TRANSFER | vk::PipelineStageFlags::HOST,
+            vk::AccessFlags::TRANSFER_WRITE | vk::AccessFlags::HOST_READ,
+        );
+        for guarded in self.buffers.iter().flatten() {
+            unsafe {
+                self.device.cmd_fill_buffer(
+                    command_buffer,
+                    guarded.buffer,
+                    guarded.layout.fill_offset,
+                    BUFFER_CANARY_SIZE,
+                    BUFFER_CANARY,
+                );
+            }
+        }
+        self.cmd_memory_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+        );
+
+        self.frames[self.current].push(GuardCheck {
+            pass: pass.to_string(),
+            buffers,
+        });
+    }
+
+    fn cmd_memory_barrier(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        src_stage: vk::PipelineStageFlags,
+        src_access: vk::AccessFlags,
+        dst_stage: vk::PipelineStageFlags,
+        dst_access: vk::AccessFlags,
+    ) {
+        let barrier = vk::MemoryBarrier::default()
+            .src_access_mask(src_access)
+            .dst_access_mask(dst_access);
+
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                std::slice::from_ref(&barrier),
+                &[],
+                &[],
+            );
+        }
+    }
+}
+
+/// Turns one frame's corrupted word counts, laid out check-major, into overruns.
+fn overruns(checks: &[GuardCheck], counts: &[u32], max_buffers: u32) -> Vec<BufferOverrun> {
+    checks
+        .iter()
+        .enumerate()
+        .flat_map(|(check_index, check)| {
+            check.buffers.iter().filter_map(move |(index, name)| {
+                let count = counts[check_index * max_buffers as usize + *index as usize];
+                (count > 0).then(|| BufferOverrun {
+                    pass: check.pass.clone(),
+                    buffer: name.clone(),
+                    corrupted_words: count,
+                })
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lays_out_the_canary_after_the_buffer() {
+        let layout = GuardLayout::new(1000);
+        assert_eq!(layout.fill_offset, 1000);
+        assert_eq!(layout.padded_size, 1000 + BUFFER_CANARY_SIZE);
+        assert_eq!(layout.region_offset, 768);
+        assert_eq!(layout.first_word, 58);
+        assert_eq!(layout.word_count, 122);
+        assert_eq!(layout.region_size(), 488);
+
+        let unaligned = GuardLayout::new(6);
+        assert_eq!(unaligned.fill_offset, 8);
+        assert_eq!(unaligned.region_offset, 0);
+        assert_eq!(unaligned.first_word, 2);
+    }
+
+    #[test]
+    fn reports_overruns_per_check() {
+        let checks = [
+            GuardCheck {
+                pass: "cull".to_string(),
+                buffers: vec![(0, "objects".to_string()), (2, "draws".to_string())],
+            },
+            GuardCheck {
+                pass: "skinning".to_string(),
+                buffers: vec![(0, "objects".to_string())],
+            },
+        ];
+        let counts = [0, 0, 3, 1, 0, 0];
+
+        assert_eq!(
+            overruns(&checks, &counts, 3),
+            vec![
+                BufferOverrun {
+                    pass: "cull".to_string(),
+                    buffer: "draws".to_string(),
+                    corrupted_words: 3,
+                },
+                BufferOverrun {
+                    pass: "skinning".to_string(),
+                    buffer: "objects".to_string(),
+                    corrupted_words: 1,
+                },
+            ]
+        );
+        assert_eq!(
+            overruns(&checks, &counts, 3)[0].to_string(),
+            "'cull' wrote 3 word(s) past the end of buffer 'draws'"
+        );
+    }
+}
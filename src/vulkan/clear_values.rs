@@ -0,0 +1,255 @@
+use anyhow::{Result, bail};
+use ash::vk;
+
+/// Clear value of a single render pass attachment. The variant has to match the attachment
+/// format: `Uint`/`Sint` for integer color formats, `DepthStencil` for depth formats and
+/// `Float` for everything else.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttachmentClear {
+    Float([f32; 4]),
+    Uint([u32; 4]),
+    Sint([i32; 4]),
+    DepthStencil { depth: f32, stencil: u32 },
+}
+
+impl AttachmentClear {
+    /// The clear kind expected by attachments of `format`.
+    fn matches(&self, format: vk::Format) -> bool {
+        matches!(
+            (self, format_kind(format)),
+            (Self::Float(_), FormatKind::Float)
+                | (Self::Uint(_), FormatKind::Uint)
+                | (Self::Sint(_), FormatKind::Sint)
+                | (Self::DepthStencil { .. }, FormatKind::DepthStencil)
+        )
+    }
+
+    fn to_vk(self) -> vk::ClearValue {
+        match self {
+            Self::Float(float32) => vk::ClearValue {
+                color: vk::ClearColorValue { float32 },
+            },
+            Self::Uint(uint32) => vk::ClearValue {
+                color: vk::ClearColorValue { uint32 },
+            },
+            Self::Sint(int32) => vk::ClearValue {
+                color: vk::ClearColorValue { int32 },
+            },
+            Self::DepthStencil { depth, stencil } => vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue { depth, stencil },
+            },
+        }
+    }
+}
+
+/// Clear values for `VulkanCommandPool::begin_render_pass`, one per render pass attachment in
+/// attachment order, e.g. `ClearValues::new().with_color([0.1, 0.1, 0.1, 1.0]).with_depth(1.0)`
+/// for a `VulkanRenderPass::with_formats` pass with depth.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClearValues {
+    attachments: Vec<AttachmentClear>,
+}
+
+impl ClearValues {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_color(self, color: [f32; 4]) -> Self {
+        self.with_attachment(AttachmentClear::Float(color))
+    }
+
+    pub fn with_color_uint(self, color: [u32; 4]) -> Self {
+        self.with_attachment(AttachmentClear::Uint(color))
+    }
+
+    pub fn with_color_sint(self, color: [i32; 4]) -> Self {
+        self.with_attachment(AttachmentClear::Sint(color))
+    }
+
+    /// Depth clear with stencil 0.
+    pub fn with_depth(self, depth: f32) -> Self {
+        self.with_depth_stencil(depth, 0)
+    }
+
+    pub fn with_depth_stencil(self, depth: f32, stencil: u32) -> Self {
+        self.with_attachment(AttachmentClear::DepthStencil { depth, stencil })
+    }
+
+    pub fn with_attachment(mut self, clear: AttachmentClear) -> Self {
+        self.attachments.push(clear);
+        self
+    }
+
+    pub fn attachments(&self) -> &[AttachmentClear] {
+        &self.attachments
+    }
+
+    /// Checks that there is one clear value per attachment and that each suits its format.
+    pub fn validate(&self, attachment_formats: &[vk::Format]) -> Result<()> {
+        if self.attachments.len() != attachment_formats.len() {
+            bail!(
+                "{} clear value(s) given for a render pass with {} attachment(s)",
+                self.attachments.len(),
+                attachment_formats.len()
+            );
+        }
+
+        for (index, (clear, format)) in self.attachments.iter().zip(attachment_formats).enumerate()
+        {
+            if !clear.matches(*format) {
+                bail!(
+                    "Clear value {:?} does not suit attachment {} with format {:?}",
+                    clear,
+                    index,
+                    format
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_vk(&self) -> Vec<vk::ClearValue> {
+        self.attachments.iter().map(|clear| clear.to_vk()).collect()
+    }
+}
+
+impl From<[f32; 4]> for ClearValues {
+    fn from(color: [f32; 4]) -> Self {
+        Self::new().with_color(color)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FormatKind {
+    Float,
+    Uint,
+    Sint,
+    DepthStencil,
+}
+
+fn format_kind(format: vk::Format) -> FormatKind {
+    match format {
+        vk::Format::D16_UNORM
+        | vk::Format::X8_D24_UNORM_PACK32
+        | vk::Format::D32_SFLOAT
+        | vk::Format::S8_UINT
+        | vk::Format::D16_UNORM_S8_UINT
+        | vk::Format::D24_UNORM_S8_UINT
+        | vk::Format::D32_SFLOAT_S8_UINT => FormatKind::DepthStencil,
+        vk::Format::R8_UINT
+        | vk::Format::R8G8_UINT
+        | vk::Format::R8G8B8_UINT
+        | vk::Format::B8G8R8_UINT
+        | vk::Format::R8G8B8A8_UINT
+        | vk::Format::B8G8R8A8_UINT
+        | vk::Format::A8B8G8R8_UINT_PACK32
+        | vk::Format::A2R10G10B10_UINT_PACK32
+        | vk::Format::A2B10G10R10_UINT_PACK32
+        | vk::Format::R16_UINT
+        | vk::Format::R16G16_UINT
+        | vk::Format::R16G16B16_UINT
+        | vk::Format::R16G16B16A16_UINT
+        | vk::Format::R32_UINT
+        | vk::Format::R32G32_UINT
+        | vk::Format::R32G32B32_UINT
+        | vk::Format::R32G32B32A32_UINT
+        | vk::Format::R64_UINT
+        | vk::Format::R64G64_UINT
+        | vk::Format::R64G64B64_UINT
+        | vk::Format::R64G64B64A64_UINT => FormatKind::Uint,
+        vk::Format::R8_SINT
+        | vk::Format::R8G8_SINT
+        | vk::Format::R8G8B8_SINT
+        | vk::Format::B8G8R8_SINT
+        | vk::Format::R8G8B8A8_SINT
+        | vk::Format::B8G8R8A8_SINT
+        | vk::Format::A8B8G8R8_SINT_PACK32
+        | vk::Format::A2R10G10B10_SINT_PACK32
+        | vk::Format::A2B10G10R10_SINT_PACK32
+        | vk::Format::R16_SINT
+        | vk::Format::R16G16_SINT
+        | vk::Format::R16G16B16_SINT
+        | vk::Format::R16G16B16A16_SINT
+        | vk::Format::R32_SINT
+        | vk::Format::R32G32_SINT
+        | vk::Format::R32G32B32_SINT
+        | vk::Format::R32G32B32A32_SINT
+        | vk::Format::R64_SINT
+        | vk::Format::R64G64_SINT
+        | vk::Format::R64G64B64_SINT
+        | vk::Format::R64G64B64A64_SINT => FormatKind::Sint,
+        _ => FormatKind::Float,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_against_attachment_formats() {
+        let clear = ClearValues::new()
+            .with_color([0.1, 0.1, 0.1, 1.0])
+            .with_color_uint([0; 4])
+            .with_depth_stencil(1.0, 0);
+        let formats = [
+            vk::Format::B8G8R8A8_SRGB,
+            vk::Format::R32_UINT,
+            vk::Format::D32_SFLOAT_S8_UINT,
+        ];
+        assert!(clear.validate(&formats).is_ok());
+
+        let values = clear.to_vk();
+        assert_eq!(values.len(), 3);
+        unsafe {
+            assert_eq!(values[0].color.float32, [0.1, 0.1, 0.1, 1.0]);
+            assert_eq!(values[2].depth_stencil.depth, 1.0);
+        }
+
+        assert_eq!(
+            clear.validate(&formats[..2]).unwrap_err().to_string(),
+            "3 clear value(s) given for a render pass with 2 attachment(s)"
+        );
+        assert!(
+            clear
+                .validate(&[
+                    vk::Format::B8G8R8A8_SRGB,
+                    vk::Format::R32_SINT,
+                    vk::Format::D32_SFLOAT,
+                ])
+                .is_err()
+        );
+        assert!(
+            ClearValues::from([0.0; 4])
+                .validate(&[vk::Format::D32_SFLOAT])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn classifies_packed_and_wide_integer_formats() {
+        for format in [
+            vk::Format::R8G8B8_UINT,
+            vk::Format::A8B8G8R8_UINT_PACK32,
+            vk::Format::A2R10G10B10_UINT_PACK32,
+            vk::Format::R16G16B16_UINT,
+            vk::Format::R64G64B64A64_UINT,
+        ] {
+            let uint = ClearValues::new().with_color_uint([1; 4]);
+            assert!(uint.validate(&[format]).is_ok(), "{:?}", format);
+            assert!(ClearValues::from([0.0; 4]).validate(&[format]).is_err());
+        }
+        for format in [
+            vk::Format::B8G8R8_SINT,
+            vk::Format::A8B8G8R8_SINT_PACK32,
+            vk::Format::A2B10G10R10_SINT_PACK32,
+            vk::Format::R64_SINT,
+        ] {
+            let sint = ClearValues::new().with_color_sint([-1; 4]);
+            assert!(sint.validate(&[format]).is_ok(), "{:?}", format);
+            let uint = ClearValues::new().with_color_uint([1; 4]);
+            assert!(uint.validate(&[format]).is_err());
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use anyhow::Result;
+use ash::vk;
+use std::path::Path;
+
+/// Selects the color workflow: `srgb` (default) or `manual`.
+pub const COLOR_WORKFLOW_ENV: &str = "RVE_COLOR_WORKFLOW";
+
+/// UNORM formats and their sRGB-encoded twins.
+const SRGB_PAIRS: [(vk::Format, vk::Format); 9] = [
+    (vk::Format::R8_UNORM, vk::Format::R8_SRGB),
+    (vk::Format::R8G8_UNORM, vk::Format::R8G8_SRGB),
+    (vk::Format::R8G8B8A8_UNORM, vk::Format::R8G8B8A8_SRGB),
+    (vk::Format::B8G8R8A8_UNORM, vk::Format::B8G8R8A8_SRGB),
+    (
+        vk::Format::BC1_RGB_UNORM_BLOCK,
+        vk::Format::BC1_RGB_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::BC1_RGBA_UNORM_BLOCK,
+        vk::Format::BC1_RGBA_SRGB_BLOCK,
+    ),
+    (vk::Format::BC2_UNORM_BLOCK, vk::Format::BC2_SRGB_BLOCK),
+    (vk::Format::BC3_UNORM_BLOCK, vk::Format::BC3_SRGB_BLOCK),
+    (vk::Format::BC7_UNORM_BLOCK, vk::Format::BC7_SRGB_BLOCK),
+];
+
+/// File stem suffixes of textures holding data rather than color, loaded as linear.
+const LINEAR_SUFFIXES: [&str; 10] = [
+    "_normal",
+    "_n",
+    "_linear",
+    "_roughness",
+    "_metallic",
+    "_metalness",
+    "_ao",
+    "_occlusion",
+    "_height",
+    "_mask",
+];
+
+/// Encoding of the values stored in a texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Values are used as they are: normal maps, roughness and other data, HDR color.
+    Linear,
+    /// Display-encoded color, decoded to linear by the sampler: albedo and UI images.
+    Srgb,
+}
+
+impl ColorSpace {
+    /// `Srgb` for sRGB formats, `Linear` for everything else.
+    pub fn of_format(format: vk::Format) -> Self {
+        if SRGB_PAIRS.iter().any(|&(_, srgb)| srgb == format) {
+            Self::Srgb
+        } else {
+            Self::Linear
+        }
+    }
+
+    /// The twin of `format` with this encoding, or `format` itself when it has none.
+    pub fn apply(self, format: vk::Format) -> vk::Format {
+        SRGB_PAIRS
+            .iter()
+            .find(|&&(unorm, srgb)| unorm == format || srgb == format)
+            .map_or(format, |&(unorm, srgb)| match self {
+                Self::Linear => unorm,
+                Self::Srgb => srgb,
+            })
+    }
+
+    /// Guesses from the file name: data textures named like `brick_normal.ppm` or
+    /// `metal_roughness.ppm` are linear, everything else is treated as color.
+    pub fn from_texture_path(path: &Path) -> Self {
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if LINEAR_SUFFIXES.iter().any(|suffix| stem.ends_with(suffix)) {
+            Self::Linear
+        } else {
+            Self::Srgb
+        }
+    }
+}
+
+/// Where linear shading output gets display-encoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorWorkflow {
+    /// Render into sRGB formats, which encode on write; shaders output linear color.
+    #[default]
+    SrgbTarget,
+    /// Render into UNORM formats and encode in the last shader pass, e.g. the tonemapper.
+    /// Needed for storage swapchains, since sRGB formats rarely support storage.
+    ManualEncode,
+}
+
+impl ColorWorkflow {
+    /// The workflow a render target of `format` expects.
+    pub fn for_target(format: vk::Format) -> Self {
+        match ColorSpace::of_format(format) {
+            ColorSpace::Srgb => Self::SrgbTarget,
+            ColorSpace::Linear => Self::ManualEncode,
+        }
+    }
+
+    /// Reads `RVE_COLOR_WORKFLOW`, defaulting to `SrgbTarget`.
+    pub fn from_env() -> Result<Self> {
+        match std::env::var(COLOR_WORKFLOW_ENV) {
+            Ok(value) => value.parse(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Whether final passes have to encode sRGB themselves.
+    pub fn shader_encodes(self) -> bool {
+        self == Self::ManualEncode
+    }
+
+    /// Checks that a target of `format` matches what the shaders writing it expect: manual
+    /// encoding into an sRGB target encodes twice and washes colors out, while linear output
+    /// into a UNORM target is displayed too dark.
+    pub fn validate_target(self, format: vk::Format) -> Result<()> {
+        let expected = Self::for_target(format);
+        if expected != self {
+            return Err(anyhow::anyhow!(
+                "Shaders use the {:?} workflow but the target format {:?} expects {:?}",
+                self,
+                format,
+                expected
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for ColorWorkflow {
+    type Err = anyhow::Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        match source.trim().to_ascii_lowercase().as_str() {
+            "srgb" => Ok(Self::SrgbTarget),
+            "manual" | "linear" => Ok(Self::ManualEncode),
+            _ => Err(anyhow::anyhow!(
+                "Unknown color workflow '{}', expected 'srgb' or 'manual'",
+                source
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switches_formats_between_encodings() {
+        let albedo = vk::Format::R8G8B8A8_SRGB;
+        assert_eq!(ColorSpace::of_format(albedo), ColorSpace::Srgb);
+        assert_eq!(ColorSpace::Linear.apply(albedo), vk::Format::R8G8B8A8_UNORM);
+        assert_eq!(
+            ColorSpace::Srgb.apply(vk::Format::BC7_UNORM_BLOCK),
+            vk::Format::BC7_SRGB_BLOCK
+        );
+        // Formats without an sRGB twin stay as they are.
+        assert_eq!(
+            ColorSpace::Srgb.apply(vk::Format::R16G16B16A16_SFLOAT),
+            vk::Format::R16G16B16A16_SFLOAT
+        );
+        assert_eq!(
+            ColorSpace::of_format(vk::Format::R16G16B16A16_SFLOAT),
+            ColorSpace::Linear
+        );
+    }
+
+    #[test]
+    fn tags_data_textures_as_linear() {
+        let space = |path: &str| ColorSpace::from_texture_path(Path::new(path));
+        assert_eq!(space("textures/brick_Normal.ppm"), ColorSpace::Linear);
+        assert_eq!(space("metal_roughness.ppm"), ColorSpace::Linear);
+        assert_eq!(space("brick.ppm"), ColorSpace::Srgb);
+        assert_eq!(space("normal_brick.ppm"), ColorSpace::Srgb);
+    }
+
+    #[test]
+    fn validates_targets_against_the_workflow() {
+        assert!(
+            ColorWorkflow::SrgbTarget
+                .validate_target(vk::Format::B8G8R8A8_SRGB)
+                .is_ok()
+        );
+        assert!(
+            ColorWorkflow::SrgbTarget
+                .validate_target(vk::Format::B8G8R8A8_UNORM)
+                .is_err()
+        );
+        assert!(
+            ColorWorkflow::ManualEncode
+                .validate_target(vk::Format::A2B10G10R10_UNORM_PACK32)
+                .is_ok()
+        );
+        assert_eq!(
+            "Manual".parse::<ColorWorkflow>().unwrap(),
+            ColorWorkflow::ManualEncode
+        );
+        assert!("gamma".parse::<ColorWorkflow>().is_err());
+    }
+}
@@ -0,0 +1,474 @@
+use anyhow::Result;
+use ash::{Device, vk};
+
+use crate::vulkan::{ClearValues, RecordCommands, VulkanBuffer, VulkanImage, VulkanRenderPass};
+
+/// Records one command buffer of a `VulkanCommandPool`, from `VulkanCommandPool::encoder` to
+/// `finish`. Render passes are recorded through the `RenderPassEncoder` returned by
+/// `begin_render_pass`, which borrows the encoder until it is dropped, so commands outside the
+/// pass and a missing `cmd_end_render_pass` can't compile.
+pub struct CommandEncoder<'a> {
+    device: &'a Device,
+    command_buffer: vk::CommandBuffer,
+    finished: bool,
+}
+
+impl<'a> CommandEncoder<'a> {
+    /// Resets `command_buffer` and begins recording it.
+    pub(crate) fn begin(
+        device: &'a Device,
+        command_buffer: vk::CommandBuffer,
+        flags: vk::CommandBufferUsageFlags,
+    ) -> Result<Self> {
+        let begin_info = vk::CommandBufferBeginInfo::default().flags(flags);
+
+        unsafe {
+            device
+                .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())
+                .map_err(|e| anyhow::anyhow!("Failed to reset command buffer: {}", e))?;
+            device
+                .begin_command_buffer(command_buffer, &begin_info)
+                .map_err(|e| anyhow::anyhow!("Failed to begin command buffer: {}", e))?;
+        }
+
+        Ok(Self {
+            device,
+            command_buffer,
+            finished: false,
+        })
+    }
+
+    /// The command buffer being recorded, for passes that record through raw handles.
+    pub fn command_buffer(&self) -> vk::CommandBuffer {
+        self.command_buffer
+    }
+
+    /// Begins `render_pass` over the whole `extent`, clearing its attachments with
+    /// `clear_values`. The pass ends when the returned encoder is dropped.
+    pub fn begin_render_pass(
+        &mut self,
+        render_pass: &VulkanRenderPass,
+        framebuffer: vk::Framebuffer,
+        extent: vk::Extent2D,
+        clear_values: impl Into<ClearValues>,
+    ) -> Result<RenderPassEncoder<'_>> {
+        let clear_values = clear_values.into();
+        clear_values.validate(&render_pass.attachment_formats)?;
+        let clear_values = clear_values.to_vk();
+
+        let render_pass_info = vk::RenderPassBeginInfo::default()
+            .render_pass(render_pass.render_pass)
+            .framebuffer(framebuffer)
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            })
+            .clear_values(&clear_values);
+
+        unsafe {
+            self.device.cmd_begin_render_pass(
+                self.command_buffer,
+                &render_pass_info,
+                vk::SubpassContents::INLINE,
+            );
+        }
+
+        Ok(RenderPassEncoder {
+            device: self.device,
+            command_buffer: self.command_buffer,
+            rendering: None,
+        })
+    }
+
+    /// Begins dynamic rendering into the color `image` over its whole extent, clearing it with
+    /// `clear_values`, as `begin_render_pass` does with a render pass and framebuffer. Needs
+    /// `VulkanDeviceFeatures::dynamic_rendering`. Rendering ends when the returned encoder is
+    /// dropped, leaving the image in `final_layout` for fragment shader reads.
+    pub fn begin_rendering(
+        &mut self,
+        image: &VulkanImage,
+        final_layout: vk::ImageLayout,
+        clear_values: impl Into<ClearValues>,
+    ) -> Result<RenderPassEncoder<'_>> {
+        let clear_values = clear_values.into();
+        clear_values.validate(&[image.format])?;
+        let clear_value = clear_values.to_vk()[0];
+
+        // The image is cleared, so its previous contents are discarded.
+        self.pipeline_barrier(
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            &[],
+            &[],
+            &[color_barrier(
+                image.image,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                vk::AccessFlags::empty(),
+                vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            )],
+        );
+
+        let color_attachment = vk::RenderingAttachmentInfo::default()
+            .image_view(image.view)
+            .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .clear_value(clear_value);
+        let rendering_info = vk::RenderingInfo::default()
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: image.extent,
+            })
+            .layer_count(1)
+            .color_attachments(std::slice::from_ref(&color_attachment));
+
+        unsafe {
+            self.device
+                .cmd_begin_rendering(self.command_buffer, &rendering_info);
+        }
+
+        Ok(RenderPassEncoder {
+            device: self.device,
+            command_buffer: self.command_buffer,
+            rendering: Some((image.image, final_layout)),
+        })
+    }
+
+    pub fn bind_compute_pipeline(&mut self, pipeline: vk::Pipeline) {
+        self.device.bind_pipeline(
+            self.command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            pipeline,
+        );
+    }
+
+    pub fn bind_compute_descriptor_sets(
+        &mut self,
+        layout: vk::PipelineLayout,
+        first_set: u32,
+        descriptor_sets: &[vk::DescriptorSet],
+    ) {
+        self.device.bind_descriptor_sets(
+            self.command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            layout,
+            first_set,
+            descriptor_sets,
+            &[],
+        );
+    }
+
+    pub fn push_constants<T: Copy>(
+        &mut self,
+        layout: vk::PipelineLayout,
+        stage_flags: vk::ShaderStageFlags,
+        offset: u32,
+        constants: &T,
+    ) {
+        push_constants(
+            self.device,
+            self.command_buffer,
+            layout,
+            stage_flags,
+            offset,
+            constants,
+        );
+    }
+
+    pub fn dispatch(&mut self, x: u32, y: u32, z: u32) {
+        self.device.dispatch(self.command_buffer, x, y, z);
+    }
+
+    /// Global memory barrier from `src_access` in `src_stage` to `dst_access` in `dst_stage`.
+    pub fn memory_barrier(
+        &mut self,
+        src_stage: vk::PipelineStageFlags,
+        src_access: vk::AccessFlags,
+        dst_stage: vk::PipelineStageFlags,
+        dst_access: vk::AccessFlags,
+    ) {
+        let barrier = vk::MemoryBarrier::default()
+            .src_access_mask(src_access)
+            .dst_access_mask(dst_access);
+        self.pipeline_barrier(
+            src_stage,
+            dst_stage,
+            std::slice::from_ref(&barrier),
+            &[],
+            &[],
+        );
+    }
+
+    pub fn pipeline_barrier(
+        &mut self,
+        src_stage: vk::PipelineStageFlags,
+        dst_stage: vk::PipelineStageFlags,
+        memory_barriers: &[vk::MemoryBarrier],
+        buffer_barriers: &[vk::BufferMemoryBarrier],
+        image_barriers: &[vk::ImageMemoryBarrier],
+    ) {
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                self.command_buffer,
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                memory_barriers,
+                buffer_barriers,
+                image_barriers,
+            );
+        }
+    }
+
+    /// Ends recording and returns the command buffer, ready to submit.
+    pub fn finish(mut self) -> Result<vk::CommandBuffer> {
+        self.finished = true;
+        unsafe {
+            self.device
+                .end_command_buffer(self.command_buffer)
+                .map_err(|e| anyhow::anyhow!("Failed to end command buffer: {}", e))?
+        };
+        Ok(self.command_buffer)
+    }
+}
+
+impl Drop for CommandEncoder<'_> {
+    fn drop(&mut self) {
+        // An encoder dropped early, e.g. by `?`, still leaves the buffer executable so it can
+        // be reset next frame.
+        if !self.finished {
+            unsafe {
+                let _ = self.device.end_command_buffer(self.command_buffer);
+            }
+        }
+    }
+}
+
+/// Records the commands of a render pass begun by `CommandEncoder::begin_render_pass` or
+/// `begin_rendering`, and ends the pass when dropped.
+pub struct RenderPassEncoder<'a> {
+    device: &'a Device,
+    command_buffer: vk::CommandBuffer,
+    /// Image and final layout for dynamic rendering, transitioned after it ends.
+    rendering: Option<(vk::Image, vk::ImageLayout)>,
+}
+
+impl RenderPassEncoder<'_> {
+    pub fn command_buffer(&self) -> vk::CommandBuffer {
+        self.command_buffer
+    }
+
+    pub fn bind_pipeline(&mut self, pipeline: vk::Pipeline) {
+        self.device.bind_pipeline(
+            self.command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline,
+        );
+    }
+
+    /// Sets the dynamic viewport and scissor to cover `extent`, with depth range `[0, 1]`.
+    pub fn set_viewport_and_scissor(&mut self, extent: vk::Extent2D) {
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: extent.width as f32,
+            height: extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        self.set_viewport(viewport);
+        self.set_scissor(vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        });
+    }
+
+    pub fn set_viewport(&mut self, viewport: vk::Viewport) {
+        unsafe {
+            self.device
+                .cmd_set_viewport(self.command_buffer, 0, &[viewport]);
+        }
+    }
+
+    pub fn set_scissor(&mut self, scissor: vk::Rect2D) {
+        unsafe {
+            self.device
+                .cmd_set_scissor(self.command_buffer, 0, &[scissor]);
+        }
+    }
+
+    /// Binds `buffers` from offset 0 to consecutive vertex bindings starting at `first_binding`.
+    pub fn bind_vertex_buffers(&mut self, first_binding: u32, buffers: &[&VulkanBuffer]) {
+        let handles: Vec<vk::Buffer> = buffers.iter().map(|buffer| buffer.buffer).collect();
+        let offsets = vec![0; handles.len()];
+        self.device
+            .bind_vertex_buffers(self.command_buffer, first_binding, &handles, &offsets);
+    }
+
+    pub fn bind_index_buffer(&mut self, buffer: &VulkanBuffer, index_type: vk::IndexType) {
+        self.device
+            .bind_index_buffer(self.command_buffer, buffer.buffer, 0, index_type);
+    }
+
+    pub fn bind_descriptor_sets(
+        &mut self,
+        layout: vk::PipelineLayout,
+        first_set: u32,
+        descriptor_sets: &[vk::DescriptorSet],
+    ) {
+        self.device.bind_descriptor_sets(
+            self.command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            layout,
+            first_set,
+            descriptor_sets,
+            &[],
+        );
+    }
+
+    pub fn push_constants<T: Copy>(
+        &mut self,
+        layout: vk::PipelineLayout,
+        stage_flags: vk::ShaderStageFlags,
+        offset: u32,
+        constants: &T,
+    ) {
+        push_constants(
+            self.device,
+            self.command_buffer,
+            layout,
+            stage_flags,
+            offset,
+            constants,
+        );
+    }
+
+    pub fn draw(
+        &mut self,
+        vertex_count: u32,
+        instance_count: u32,
+        first_vertex: u32,
+        first_instance: u32,
+    ) {
+        self.device.draw(
+            self.command_buffer,
+            vertex_count,
+            instance_count,
+            first_vertex,
+            first_instance,
+        );
+    }
+
+    pub fn draw_indexed(
+        &mut self,
+        index_count: u32,
+        instance_count: u32,
+        first_index: u32,
+        vertex_offset: i32,
+        first_instance: u32,
+    ) {
+        self.device.draw_indexed(
+            self.command_buffer,
+            index_count,
+            instance_count,
+            first_index,
+            vertex_offset,
+            first_instance,
+        );
+    }
+
+    /// `draw_count` tightly packed `vk::DrawIndirectCommand`s from the start of `buffer`.
+    pub fn draw_indirect(&mut self, buffer: &VulkanBuffer, draw_count: u32) {
+        self.device.draw_indirect(
+            self.command_buffer,
+            buffer.buffer,
+            0,
+            draw_count,
+            std::mem::size_of::<vk::DrawIndirectCommand>() as u32,
+        );
+    }
+
+    /// `draw_count` tightly packed `vk::DrawIndexedIndirectCommand`s from the start of `buffer`.
+    pub fn draw_indexed_indirect(&mut self, buffer: &VulkanBuffer, draw_count: u32) {
+        self.device.draw_indexed_indirect(
+            self.command_buffer,
+            buffer.buffer,
+            0,
+            draw_count,
+            std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32,
+        );
+    }
+}
+
+impl Drop for RenderPassEncoder<'_> {
+    fn drop(&mut self) {
+        let Some((image, final_layout)) = self.rendering else {
+            unsafe {
+                self.device.cmd_end_render_pass(self.command_buffer);
+            }
+            return;
+        };
+
+        // Same dependency as the external one of `VulkanRenderPass::with_color_format`.
+        let barrier = color_barrier(
+            image,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            final_layout,
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            vk::AccessFlags::SHADER_READ,
+        );
+        unsafe {
+            self.device.cmd_end_rendering(self.command_buffer);
+            self.device.cmd_pipeline_barrier(
+                self.command_buffer,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        }
+    }
+}
+
+fn color_barrier(
+    image: vk::Image,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+    src_access: vk::AccessFlags,
+    dst_access: vk::AccessFlags,
+) -> vk::ImageMemoryBarrier<'static> {
+    vk::ImageMemoryBarrier::default()
+        .image(image)
+        .old_layout(old_layout)
+        .new_layout(new_layout)
+        .src_access_mask(src_access)
+        .dst_access_mask(dst_access)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .subresource_range(
+            vk::ImageSubresourceRange::default()
+                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                .level_count(1)
+                .layer_count(1),
+        )
+}
+
+fn push_constants<T: Copy>(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    layout: vk::PipelineLayout,
+    stage_flags: vk::ShaderStageFlags,
+    offset: u32,
+    constants: &T,
+) {
+    let bytes = unsafe {
+        std::slice::from_raw_parts(constants as *const T as *const u8, std::mem::size_of::<T>())
+    };
+
+    unsafe {
+        device.cmd_push_constants(command_buffer, layout, stage_flags, offset, bytes);
+    }
+}
@@ -2,7 +2,7 @@ use anyhow::Result;
 use ash::{Device, vk};
 use std::sync::Arc;
 
-use crate::vulkan::{QueueFamilyIndices, VulkanDevice, VulkanRenderPass};
+use crate::vulkan::{CommandEncoder, QueueFamilyIndices, VulkanDevice};
 
 pub struct VulkanCommandPool {
     pub command_pool: vk::CommandPool,
@@ -56,94 +56,82 @@ impl VulkanCommandPool {
         self.command_buffers.len()
     }
 
-    pub fn begin_command_buffer(&self, index: usize) -> Result<()> {
-        let command_buffer = self.get_command_buffer(index);
-
-        let begin_info =
-            vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::empty());
-
-        unsafe {
-            self.device
-                .begin_command_buffer(*command_buffer, &begin_info)
-                .map_err(|e| anyhow::anyhow!("Failed to begin command buffer: {}", e))?
-        };
-
-        Ok(())
+    /// Resets command buffer `index` and starts recording it.
+    pub fn encoder(&self, index: usize) -> Result<CommandEncoder<'_>> {
+        CommandEncoder::begin(
+            &self.device,
+            *self.get_command_buffer(index),
+            vk::CommandBufferUsageFlags::empty(),
+        )
     }
 
-    pub fn end_command_buffer(&self, index: usize) -> Result<()> {
-        let command_buffer = self.get_command_buffer(index);
+    /// Records `record` into a one-time command buffer, submits it to `queue` and waits for it
+    /// to finish. Meant for setup work like uploads, layout transitions and mipmap generation,
+    /// not per-frame rendering.
+    pub fn immediate_submit(
+        &self,
+        queue: vk::Queue,
+        record: impl FnOnce(&mut CommandEncoder),
+    ) -> Result<()> {
+        let alloc_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(self.command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(1);
 
-        unsafe {
+        let command_buffer = unsafe {
             self.device
-                .end_command_buffer(*command_buffer)
-                .map_err(|e| anyhow::anyhow!("Failed to end command buffer: {}", e))?
+                .allocate_command_buffers(&alloc_info)
+                .map_err(|e| anyhow::anyhow!("Failed to allocate command buffer: {}", e))?[0]
         };
 
-        Ok(())
-    }
-
-    pub fn reset_command_buffer(&self, index: usize) -> Result<()> {
-        let command_buffer = self.get_command_buffer(index);
+        let result = self.record_and_wait(queue, command_buffer, record);
 
         unsafe {
             self.device
-                .reset_command_buffer(*command_buffer, vk::CommandBufferResetFlags::empty())
-                .map_err(|e| anyhow::anyhow!("Failed to reset command buffer: {}", e))?
-        };
+                .free_command_buffers(self.command_pool, &[command_buffer]);
+        }
 
-        Ok(())
+        result
     }
 
-    pub fn begin_render_pass(
+    fn record_and_wait(
         &self,
-        command_buffer_index: usize,
-        render_pass: &VulkanRenderPass,
-        framebuffer: vk::Framebuffer,
-        extent: &vk::Extent2D,
-        clear_color: [f32; 4],
-    ) {
-        let command_buffer = self.get_command_buffer(command_buffer_index);
-
-        let clear_values = [vk::ClearValue {
-            color: vk::ClearColorValue {
-                float32: clear_color,
-            },
-        }];
-
-        let render_pass_info = vk::RenderPassBeginInfo::default()
-            .render_pass(render_pass.render_pass)
-            .framebuffer(framebuffer)
-            .render_area(vk::Rect2D {
-                offset: vk::Offset2D { x: 0, y: 0 },
-                extent: *extent,
-            })
-            .clear_values(&clear_values);
-
-        unsafe {
-            self.device.cmd_begin_render_pass(
-                *command_buffer,
-                &render_pass_info,
-                vk::SubpassContents::INLINE,
-            );
-        }
-    }
+        queue: vk::Queue,
+        command_buffer: vk::CommandBuffer,
+        record: impl FnOnce(&mut CommandEncoder),
+    ) -> Result<()> {
+        let mut encoder = CommandEncoder::begin(
+            &self.device,
+            command_buffer,
+            vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        )?;
+        record(&mut encoder);
+        encoder.finish()?;
+
+        let fence = unsafe {
+            self.device
+                .create_fence(&vk::FenceCreateInfo::default(), None)
+                .map_err(|e| anyhow::anyhow!("Failed to create fence: {}", e))?
+        };
 
-    pub fn end_render_pass(&self, command_buffer_index: usize) {
-        let command_buffer = self.get_command_buffer(command_buffer_index);
+        let submit_info =
+            vk::SubmitInfo::default().command_buffers(std::slice::from_ref(&command_buffer));
+        let result = unsafe {
+            self.device
+                .queue_submit(queue, std::slice::from_ref(&submit_info), fence)
+                .map_err(|e| anyhow::anyhow!("Failed to submit command buffer: {}", e))
+                .and_then(|()| {
+                    self.device
+                        .wait_for_fences(&[fence], true, u64::MAX)
+                        .map_err(|e| anyhow::anyhow!("Failed to wait for fence: {}", e))
+                })
+        };
 
         unsafe {
-            self.device.cmd_end_render_pass(*command_buffer);
+            self.device.destroy_fence(fence, None);
         }
-    }
-
-    pub fn draw(&self, command_buffer_index: usize, vertex_count: u32, instance_count: u32) {
-        let command_buffer = self.get_command_buffer(command_buffer_index);
 
-        unsafe {
-            self.device
-                .cmd_draw(*command_buffer, vertex_count, instance_count, 0, 0);
-        }
+        result
     }
 }
 
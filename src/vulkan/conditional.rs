@@ -0,0 +1,187 @@
+use anyhow::Result;
+use ash::vk;
+
+use crate::vulkan::{OcclusionQueries, VulkanBuffer, VulkanDevice, VulkanPhysicalDevice};
+
+/// 32-bit predicates read by conditional rendering, one per conditional pass; a pass is
+/// skipped when its predicate is zero.
+pub struct PredicateBuffer {
+    pub buffer: VulkanBuffer,
+    count: u32,
+}
+
+impl PredicateBuffer {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        count: u32,
+    ) -> Result<Self> {
+        let buffer = VulkanBuffer::new(
+            device,
+            physical_device,
+            predicate_offset(count.max(1)),
+            vk::BufferUsageFlags::CONDITIONAL_RENDERING_EXT
+                | vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        Ok(Self { buffer, count })
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Sets predicates `first..first + count` to `value`, e.g. to reset them before a compute
+    /// pass writes them. Must be recorded outside a render pass.
+    pub fn cmd_fill(
+        &self,
+        device: &VulkanDevice,
+        command_buffer: vk::CommandBuffer,
+        first: u32,
+        count: u32,
+        value: u32,
+    ) {
+        unsafe {
+            device.device.cmd_fill_buffer(
+                command_buffer,
+                self.buffer.buffer,
+                predicate_offset(first),
+                predicate_offset(count),
+                value,
+            );
+        }
+    }
+
+    /// Copies the sample counts of `count` occlusion queries into predicates starting at
+    /// `first`, so passes of occluded objects are skipped. Must be recorded outside a render
+    /// pass, after the queries ended; it waits for their results on the GPU.
+    pub fn cmd_copy_occlusion_results(
+        &self,
+        device: &VulkanDevice,
+        command_buffer: vk::CommandBuffer,
+        queries: &OcclusionQueries,
+        first_query: u32,
+        first: u32,
+        count: u32,
+    ) {
+        unsafe {
+            device.device.cmd_copy_query_pool_results(
+                command_buffer,
+                queries.query_pool,
+                first_query,
+                count,
+                self.buffer.buffer,
+                predicate_offset(first),
+                predicate_offset(1),
+                vk::QueryResultFlags::WAIT,
+            );
+        }
+    }
+
+    /// Makes predicate writes from `src_stage` visible to conditional rendering.
+    pub fn cmd_barrier(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        src_stage: vk::PipelineStageFlags,
+        src_access: vk::AccessFlags,
+    ) {
+        self.buffer.cmd_barrier(
+            command_buffer,
+            src_stage,
+            src_access,
+            vk::PipelineStageFlags::CONDITIONAL_RENDERING_EXT,
+            vk::AccessFlags::CONDITIONAL_RENDERING_READ_EXT,
+        );
+    }
+}
+
+/// Byte offset of predicate `index`.
+pub fn predicate_offset(index: u32) -> vk::DeviceSize {
+    index as vk::DeviceSize * std::mem::size_of::<u32>() as vk::DeviceSize
+}
+
+fn conditional_begin_info(
+    buffer: vk::Buffer,
+    index: u32,
+    inverted: bool,
+) -> vk::ConditionalRenderingBeginInfoEXT<'static> {
+    let flags = if inverted {
+        vk::ConditionalRenderingFlagsEXT::INVERTED
+    } else {
+        vk::ConditionalRenderingFlagsEXT::empty()
+    };
+
+    vk::ConditionalRenderingBeginInfoEXT::default()
+        .buffer(buffer)
+        .offset(predicate_offset(index))
+        .flags(flags)
+}
+
+/// Records conditional passes: command segments the GPU skips when their predicate is zero,
+/// without a CPU readback of the predicate.
+///
+/// When the device lacks `VK_EXT_conditional_rendering`, passes are always recorded
+/// unconditionally, which renders the same image at the cost of the skipped work.
+pub struct ConditionalRendering {
+    loader: Option<ash::ext::conditional_rendering::Device>,
+}
+
+impl ConditionalRendering {
+    pub fn new(device: &VulkanDevice) -> Self {
+        Self {
+            loader: device.conditional_rendering.clone(),
+        }
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.loader.is_some()
+    }
+
+    /// Records `pass` so that it only executes if predicate `index` is non-zero, or zero when
+    /// `inverted`. When begun inside a render pass, `pass` must stay in the same subpass;
+    /// otherwise it may begin and end whole render passes.
+    pub fn record<R>(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        predicates: &PredicateBuffer,
+        index: u32,
+        inverted: bool,
+        pass: impl FnOnce(vk::CommandBuffer) -> R,
+    ) -> R {
+        let Some(loader) = &self.loader else {
+            return pass(command_buffer);
+        };
+
+        let begin_info = conditional_begin_info(predicates.buffer.buffer, index, inverted);
+
+        // ash only exposes the raw function pointers of this extension.
+        unsafe { (loader.fp().cmd_begin_conditional_rendering_ext)(command_buffer, &begin_info) };
+        let result = pass(command_buffer);
+        unsafe { (loader.fp().cmd_end_conditional_rendering_ext)(command_buffer) };
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn predicates_are_tightly_packed_u32() {
+        assert_eq!(predicate_offset(0), 0);
+        assert_eq!(predicate_offset(3), 12);
+    }
+
+    #[test]
+    fn begin_info_points_at_predicate() {
+        let info = conditional_begin_info(vk::Buffer::null(), 5, false);
+        assert_eq!(info.offset, 20);
+        assert!(info.flags.is_empty());
+
+        let info = conditional_begin_info(vk::Buffer::null(), 0, true);
+        assert_eq!(info.flags, vk::ConditionalRenderingFlagsEXT::INVERTED);
+    }
+}
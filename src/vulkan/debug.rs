@@ -0,0 +1,198 @@
+use anyhow::Result;
+use ash::{Entry, Instance, vk};
+use std::ffi::{CStr, c_void};
+
+/// Set to any value to enable `ValidationOptions::shader_printf` through `from_env`.
+pub const SHADER_PRINTF_ENV: &str = "RVE_SHADER_PRINTF";
+
+/// Set to any value to enable `ValidationOptions::gpu_assisted` through `from_env`.
+pub const GPU_ASSISTED_VALIDATION_ENV: &str = "RVE_GPU_ASSISTED_VALIDATION";
+
+/// Validation layer settings used at instance creation. Only applied in debug builds, where the
+/// layer is enabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValidationOptions {
+    /// Makes the layer instrument shaders so `debugPrintfEXT` output reaches the debug
+    /// messenger. Shaders need `GL_EXT_debug_printf` and the device needs
+    /// `VK_KHR_shader_non_semantic_info` (see `VulkanDeviceFeatures::shader_printf`). Slows
+    /// every pipeline creation and submission, so leave it off unless debugging a shader.
+    pub shader_printf: bool,
+    /// GPU-assisted validation: the layer instruments shaders to report out-of-bounds buffer
+    /// and descriptor accesses. Older layers can't combine it with `shader_printf`, which then
+    /// wins. `BufferGuard` is a lighter alternative for catching stray writes.
+    pub gpu_assisted: bool,
+}
+
+impl ValidationOptions {
+    pub fn from_env() -> Self {
+        Self {
+            shader_printf: std::env::var_os(SHADER_PRINTF_ENV).is_some(),
+            gpu_assisted: std::env::var_os(GPU_ASSISTED_VALIDATION_ENV).is_some(),
+        }
+    }
+
+    /// Validation features to chain into the instance through `vk::ValidationFeaturesEXT`.
+    pub fn enabled_features(&self) -> Vec<vk::ValidationFeatureEnableEXT> {
+        let mut features = Vec::new();
+        if self.shader_printf {
+            features.push(vk::ValidationFeatureEnableEXT::DEBUG_PRINTF);
+        } else if self.gpu_assisted {
+            features.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED);
+            features.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT);
+        }
+        features
+    }
+}
+
+/// Where a message from the debug messenger is routed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugMessageKind {
+    ShaderPrintf,
+    Error,
+    Warning,
+    /// Info and verbose messages, which are dropped.
+    Ignored,
+}
+
+impl DebugMessageKind {
+    pub fn classify(
+        severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+        message_id_name: &str,
+    ) -> Self {
+        if message_id_name.contains("DEBUG-PRINTF") {
+            Self::ShaderPrintf
+        } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+            Self::Error
+        } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+            Self::Warning
+        } else {
+            Self::Ignored
+        }
+    }
+}
+
+/// The text a shader printed, without the object and message id preamble older layers put in
+/// front of it.
+pub fn shader_printf_text(message: &str) -> &str {
+    message
+        .split_once("MessageID = ")
+        .and_then(|(_, rest)| rest.split_once(" | "))
+        .map_or(message, |(_, text)| text)
+        .trim()
+}
+
+/// Routes validation layer messages to stdout and stderr, with shader printf output tagged so
+/// it stands out from validation errors.
+pub struct VulkanDebugMessenger {
+    loader: ash::ext::debug_utils::Instance,
+    messenger: vk::DebugUtilsMessengerEXT,
+}
+
+impl VulkanDebugMessenger {
+    pub fn new(entry: &Entry, instance: &Instance) -> Result<Self> {
+        let loader = ash::ext::debug_utils::Instance::new(entry, instance);
+
+        let create_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
+            .message_severity(
+                vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
+            )
+            .message_type(
+                vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                    | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                    | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+            )
+            .pfn_user_callback(Some(debug_callback));
+
+        let messenger = unsafe {
+            loader
+                .create_debug_utils_messenger(&create_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create debug messenger: {}", e))?
+        };
+
+        Ok(Self { loader, messenger })
+    }
+
+    /// Must be called before the instance is destroyed.
+    pub(crate) fn destroy(&mut self) {
+        unsafe {
+            self.loader
+                .destroy_debug_utils_messenger(self.messenger, None);
+        }
+    }
+}
+
+unsafe extern "system" fn debug_callback(
+    severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    _message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT<'_>,
+    _user_data: *mut c_void,
+) -> vk::Bool32 {
+    let Some(data) = (unsafe { callback_data.as_ref() }) else {
+        return vk::FALSE;
+    };
+    let text = |pointer: *const std::ffi::c_char| {
+        if pointer.is_null() {
+            String::new()
+        } else {
+            unsafe { CStr::from_ptr(pointer) }
+                .to_string_lossy()
+                .into_owned()
+        }
+    };
+    let id_name = text(data.p_message_id_name);
+    let message = text(data.p_message);
+
+    match DebugMessageKind::classify(severity, &id_name) {
+        DebugMessageKind::ShaderPrintf => println!("[shader] {}", shader_printf_text(&message)),
+        DebugMessageKind::Error => eprintln!("[vulkan error] {}", message),
+        DebugMessageKind::Warning => eprintln!("[vulkan warning] {}", message),
+        DebugMessageKind::Ignored => {}
+    }
+
+    vk::FALSE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_printf_messages_by_id() {
+        let info = vk::DebugUtilsMessageSeverityFlagsEXT::INFO;
+        assert_eq!(
+            DebugMessageKind::classify(info, "WARNING-DEBUG-PRINTF"),
+            DebugMessageKind::ShaderPrintf
+        );
+        assert_eq!(
+            DebugMessageKind::classify(info, "Loader Message"),
+            DebugMessageKind::Ignored
+        );
+        assert_eq!(
+            DebugMessageKind::classify(
+                vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+                "VUID-vkCmdDraw-None-08600"
+            ),
+            DebugMessageKind::Error
+        );
+
+        assert_eq!(
+            shader_printf_text(
+                "Validation Information: [ WARNING-DEBUG-PRINTF ] Object 0: handle = 0x1 \
+                 | MessageID = 0x76589099 | cell 3 density 0.5\n"
+            ),
+            "cell 3 density 0.5"
+        );
+        assert_eq!(shader_printf_text("a | b"), "a | b");
+        assert_eq!(ValidationOptions::default().enabled_features(), Vec::new());
+        assert_eq!(
+            ValidationOptions {
+                shader_printf: true,
+                gpu_assisted: true,
+            }
+            .enabled_features(),
+            vec![vk::ValidationFeatureEnableEXT::DEBUG_PRINTF]
+        );
+    }
+}
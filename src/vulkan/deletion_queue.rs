@@ -0,0 +1,99 @@
+use std::collections::VecDeque;
+
+/// Defers destruction of GPU resources until the frames that may use them have finished.
+///
+/// Frames are numbered by a monotonically increasing submission index supplied by the caller.
+/// Resources retired during frame `n` are destroyed by `flush` once frame `n` is known to be
+/// complete, i.e. once its in-flight fence has signaled.
+#[derive(Default)]
+pub struct DeletionQueue {
+    pending: VecDeque<(u64, Box<dyn FnOnce()>)>,
+}
+
+impl DeletionQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `destroy` once `frame` has completed.
+    pub fn defer(&mut self, frame: u64, destroy: impl FnOnce() + 'static) {
+        self.pending.push_back((frame, Box::new(destroy)));
+    }
+
+    /// Drops `resource` once `frame` has completed. Works with any wrapper that destroys its
+    /// Vulkan objects in `Drop`.
+    pub fn retire<T: 'static>(&mut self, frame: u64, resource: T) {
+        self.defer(frame, move || drop(resource));
+    }
+
+    /// Destroys everything retired during `completed_frame` or earlier.
+    pub fn flush(&mut self, completed_frame: u64) {
+        while self
+            .pending
+            .front()
+            .is_some_and(|(frame, _)| *frame <= completed_frame)
+        {
+            if let Some((_, destroy)) = self.pending.pop_front() {
+                destroy();
+            }
+        }
+    }
+
+    /// Destroys everything. The device must be idle.
+    pub fn flush_all(&mut self) {
+        while let Some((_, destroy)) = self.pending.pop_front() {
+            destroy();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+impl Drop for DeletionQueue {
+    fn drop(&mut self) {
+        self.flush_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn flushes_completed_frames_in_order() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut queue = DeletionQueue::new();
+
+        for frame in [1, 2, 3] {
+            let log = log.clone();
+            queue.defer(frame, move || log.borrow_mut().push(frame));
+        }
+
+        queue.flush(2);
+        assert_eq!(*log.borrow(), vec![1, 2]);
+        assert_eq!(queue.len(), 1);
+
+        queue.flush_all();
+        assert_eq!(*log.borrow(), vec![1, 2, 3]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn drop_destroys_pending_resources() {
+        let resource = Rc::new(());
+        let mut queue = DeletionQueue::new();
+        queue.retire(5, resource.clone());
+
+        assert_eq!(Rc::strong_count(&resource), 2);
+        drop(queue);
+        assert_eq!(Rc::strong_count(&resource), 1);
+    }
+}
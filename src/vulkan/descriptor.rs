@@ -0,0 +1,280 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::sync::Arc;
+
+use crate::vulkan::{VulkanBuffer, VulkanDevice};
+
+pub struct VulkanDescriptorSetLayout {
+    pub layout: vk::DescriptorSetLayout,
+    pub bindings: Vec<vk::DescriptorSetLayoutBinding<'static>>,
+    pub device: Arc<Device>,
+}
+
+impl VulkanDescriptorSetLayout {
+    pub fn new(
+        device: &VulkanDevice,
+        bindings: &[vk::DescriptorSetLayoutBinding<'static>],
+    ) -> Result<Self> {
+        Self::with_binding_flags(
+            device,
+            bindings,
+            &[],
+            vk::DescriptorSetLayoutCreateFlags::empty(),
+        )
+    }
+
+    /// Like `new` with per-binding flags, e.g. `PARTIALLY_BOUND | UPDATE_AFTER_BIND` for
+    /// bindless arrays. `binding_flags` is either empty or has one entry per binding.
+    pub fn with_binding_flags(
+        device: &VulkanDevice,
+        bindings: &[vk::DescriptorSetLayoutBinding<'static>],
+        binding_flags: &[vk::DescriptorBindingFlags],
+        flags: vk::DescriptorSetLayoutCreateFlags,
+    ) -> Result<Self> {
+        let mut binding_flags_info =
+            vk::DescriptorSetLayoutBindingFlagsCreateInfo::default().binding_flags(binding_flags);
+
+        let mut layout_info = vk::DescriptorSetLayoutCreateInfo::default()
+            .flags(flags)
+            .bindings(bindings);
+
+        if !binding_flags.is_empty() {
+            layout_info = layout_info.push_next(&mut binding_flags_info);
+        }
+
+        let layout = unsafe {
+            device
+                .device
+                .create_descriptor_set_layout(&layout_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create descriptor set layout: {}", e))?
+        };
+
+        Ok(Self {
+            layout,
+            bindings: bindings.to_vec(),
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn binding(
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        stage_flags: vk::ShaderStageFlags,
+    ) -> vk::DescriptorSetLayoutBinding<'static> {
+        vk::DescriptorSetLayoutBinding::default()
+            .binding(binding)
+            .descriptor_type(descriptor_type)
+            .descriptor_count(1)
+            .stage_flags(stage_flags)
+    }
+
+    /// Pool sizes large enough to allocate `set_count` sets of this layout.
+    pub fn pool_sizes(&self, set_count: u32) -> Vec<vk::DescriptorPoolSize> {
+        let mut pool_sizes: Vec<vk::DescriptorPoolSize> = Vec::new();
+
+        for binding in &self.bindings {
+            match pool_sizes
+                .iter_mut()
+                .find(|size| size.ty == binding.descriptor_type)
+            {
+                Some(size) => size.descriptor_count += binding.descriptor_count * set_count,
+                None => pool_sizes.push(
+                    vk::DescriptorPoolSize::default()
+                        .ty(binding.descriptor_type)
+                        .descriptor_count(binding.descriptor_count * set_count),
+                ),
+            }
+        }
+
+        pool_sizes
+    }
+}
+
+impl Drop for VulkanDescriptorSetLayout {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_descriptor_set_layout(self.layout, None);
+        }
+    }
+}
+
+pub struct VulkanDescriptorPool {
+    pub pool: vk::DescriptorPool,
+    pub device: Arc<Device>,
+}
+
+impl VulkanDescriptorPool {
+    pub fn new(
+        device: &VulkanDevice,
+        pool_sizes: &[vk::DescriptorPoolSize],
+        max_sets: u32,
+    ) -> Result<Self> {
+        Self::with_flags(
+            device,
+            pool_sizes,
+            max_sets,
+            vk::DescriptorPoolCreateFlags::empty(),
+        )
+    }
+
+    pub fn with_flags(
+        device: &VulkanDevice,
+        pool_sizes: &[vk::DescriptorPoolSize],
+        max_sets: u32,
+        flags: vk::DescriptorPoolCreateFlags,
+    ) -> Result<Self> {
+        let pool_info = vk::DescriptorPoolCreateInfo::default()
+            .flags(flags)
+            .pool_sizes(pool_sizes)
+            .max_sets(max_sets);
+
+        let pool = unsafe {
+            device
+                .device
+                .create_descriptor_pool(&pool_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create descriptor pool: {}", e))?
+        };
+
+        Ok(Self {
+            pool,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn for_layout(
+        device: &VulkanDevice,
+        layout: &VulkanDescriptorSetLayout,
+        set_count: u32,
+    ) -> Result<Self> {
+        Self::new(device, &layout.pool_sizes(set_count), set_count)
+    }
+
+    pub fn allocate(&self, layout: &VulkanDescriptorSetLayout) -> Result<vk::DescriptorSet> {
+        let layouts = [layout.layout];
+
+        let alloc_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.pool)
+            .set_layouts(&layouts);
+
+        let sets = unsafe {
+            self.device
+                .allocate_descriptor_sets(&alloc_info)
+                .map_err(|e| anyhow::anyhow!("Failed to allocate descriptor set: {}", e))?
+        };
+
+        Ok(sets[0])
+    }
+
+    pub fn write_buffer(
+        &self,
+        set: vk::DescriptorSet,
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        buffer: &VulkanBuffer,
+    ) {
+        self.write_buffer_range(set, binding, descriptor_type, buffer, 0, buffer.size);
+    }
+
+    /// Like `write_buffer` for `range` bytes at `offset`, which must respect the device's
+    /// offset alignment for `descriptor_type`.
+    pub fn write_buffer_range(
+        &self,
+        set: vk::DescriptorSet,
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        buffer: &VulkanBuffer,
+        offset: vk::DeviceSize,
+        range: vk::DeviceSize,
+    ) {
+        let buffer_info = [vk::DescriptorBufferInfo::default()
+            .buffer(buffer.buffer)
+            .offset(offset)
+            .range(range)];
+
+        let write = vk::WriteDescriptorSet::default()
+            .dst_set(set)
+            .dst_binding(binding)
+            .descriptor_type(descriptor_type)
+            .buffer_info(&buffer_info);
+
+        unsafe {
+            self.device
+                .update_descriptor_sets(std::slice::from_ref(&write), &[]);
+        }
+    }
+
+    pub fn write_image(
+        &self,
+        set: vk::DescriptorSet,
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        image_view: vk::ImageView,
+        sampler: vk::Sampler,
+        image_layout: vk::ImageLayout,
+    ) {
+        let image_info = [vk::DescriptorImageInfo::default()
+            .image_view(image_view)
+            .sampler(sampler)
+            .image_layout(image_layout)];
+
+        let write = vk::WriteDescriptorSet::default()
+            .dst_set(set)
+            .dst_binding(binding)
+            .descriptor_type(descriptor_type)
+            .image_info(&image_info);
+
+        unsafe {
+            self.device
+                .update_descriptor_sets(std::slice::from_ref(&write), &[]);
+        }
+    }
+
+    /// Binds `image_view` as a `STORAGE_IMAGE`, which must be in `GENERAL` when used.
+    pub fn write_storage_image(
+        &self,
+        set: vk::DescriptorSet,
+        binding: u32,
+        image_view: vk::ImageView,
+    ) {
+        self.write_image(
+            set,
+            binding,
+            vk::DescriptorType::STORAGE_IMAGE,
+            image_view,
+            vk::Sampler::null(),
+            vk::ImageLayout::GENERAL,
+        );
+    }
+
+    pub fn write_acceleration_structure(
+        &self,
+        set: vk::DescriptorSet,
+        binding: u32,
+        acceleration_structure: vk::AccelerationStructureKHR,
+    ) {
+        let acceleration_structures = [acceleration_structure];
+        let mut acceleration_structure_info =
+            vk::WriteDescriptorSetAccelerationStructureKHR::default()
+                .acceleration_structures(&acceleration_structures);
+
+        let write = vk::WriteDescriptorSet::default()
+            .dst_set(set)
+            .dst_binding(binding)
+            .descriptor_type(vk::DescriptorType::ACCELERATION_STRUCTURE_KHR)
+            .descriptor_count(1)
+            .push_next(&mut acceleration_structure_info);
+
+        unsafe {
+            self.device
+                .update_descriptor_sets(std::slice::from_ref(&write), &[]);
+        }
+    }
+}
+
+impl Drop for VulkanDescriptorPool {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_descriptor_pool(self.pool, None);
+        }
+    }
+}
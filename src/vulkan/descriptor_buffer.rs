@@ -0,0 +1,548 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::vulkan::{
+    MappedBuffer, VulkanBuffer, VulkanDescriptorPool, VulkanDescriptorSetLayout, VulkanDevice,
+    VulkanDeviceFeatures, VulkanInstance, VulkanPhysicalDevice,
+};
+
+pub const DESCRIPTOR_BACKEND_ENV: &str = "RVE_DESCRIPTOR_BACKEND";
+
+/// How descriptors reach shaders.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DescriptorBackend {
+    /// Sets allocated from `VulkanDescriptorPool`s and bound with `vkCmdBindDescriptorSets`.
+    #[default]
+    Sets,
+    /// Descriptors written into a `DescriptorBuffer` and bound by offset.
+    Buffer,
+}
+
+impl DescriptorBackend {
+    /// `Buffer` when the device supports `VK_EXT_descriptor_buffer`.
+    pub fn best(features: &VulkanDeviceFeatures) -> Self {
+        if features.descriptor_buffer {
+            Self::Buffer
+        } else {
+            Self::Sets
+        }
+    }
+
+    /// Reads `RVE_DESCRIPTOR_BACKEND` (`sets` or `buffer`), defaulting to the best backend.
+    /// Asking for `buffer` on a device without descriptor buffers falls back to `Sets`.
+    pub fn from_env(features: &VulkanDeviceFeatures) -> Result<Self> {
+        let backend = match std::env::var(DESCRIPTOR_BACKEND_ENV) {
+            Ok(value) => value.parse()?,
+            Err(_) => Self::best(features),
+        };
+        Ok(backend.supported_by(features))
+    }
+
+    /// This backend, or `Sets` if the device can't use it.
+    pub fn supported_by(self, features: &VulkanDeviceFeatures) -> Self {
+        match self {
+            Self::Buffer if !features.descriptor_buffer => Self::Sets,
+            backend => backend,
+        }
+    }
+
+    /// Flags descriptor set layouts need for this backend.
+    pub fn layout_flags(self) -> vk::DescriptorSetLayoutCreateFlags {
+        match self {
+            Self::Sets => vk::DescriptorSetLayoutCreateFlags::empty(),
+            Self::Buffer => vk::DescriptorSetLayoutCreateFlags::DESCRIPTOR_BUFFER_EXT,
+        }
+    }
+
+    /// Flags pipelines need for this backend.
+    pub fn pipeline_flags(self) -> vk::PipelineCreateFlags {
+        match self {
+            Self::Sets => vk::PipelineCreateFlags::empty(),
+            Self::Buffer => vk::PipelineCreateFlags::DESCRIPTOR_BUFFER_EXT,
+        }
+    }
+}
+
+impl FromStr for DescriptorBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "sets" => Ok(Self::Sets),
+            "buffer" => Ok(Self::Buffer),
+            other => Err(anyhow::anyhow!(
+                "Unknown descriptor backend '{}', expected 'sets' or 'buffer'",
+                other
+            )),
+        }
+    }
+}
+
+/// Sizes and alignment from `VkPhysicalDeviceDescriptorBufferPropertiesEXT`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DescriptorBufferProperties {
+    pub offset_alignment: vk::DeviceSize,
+    pub uniform_buffer_size: usize,
+    pub storage_buffer_size: usize,
+    pub sampled_image_size: usize,
+    pub storage_image_size: usize,
+    pub combined_image_sampler_size: usize,
+}
+
+impl DescriptorBufferProperties {
+    pub fn query(instance: &VulkanInstance, physical_device: &VulkanPhysicalDevice) -> Self {
+        let mut properties = vk::PhysicalDeviceDescriptorBufferPropertiesEXT::default();
+        let mut properties2 = vk::PhysicalDeviceProperties2::default().push_next(&mut properties);
+        unsafe {
+            instance
+                .instance
+                .get_physical_device_properties2(physical_device.physical_device, &mut properties2)
+        };
+        Self {
+            offset_alignment: properties.descriptor_buffer_offset_alignment,
+            uniform_buffer_size: properties.uniform_buffer_descriptor_size,
+            storage_buffer_size: properties.storage_buffer_descriptor_size,
+            sampled_image_size: properties.sampled_image_descriptor_size,
+            storage_image_size: properties.storage_image_descriptor_size,
+            combined_image_sampler_size: properties.combined_image_sampler_descriptor_size,
+        }
+    }
+
+    /// Bytes one descriptor of `descriptor_type` takes.
+    pub fn descriptor_size(&self, descriptor_type: vk::DescriptorType) -> Result<usize> {
+        match descriptor_type {
+            vk::DescriptorType::UNIFORM_BUFFER => Ok(self.uniform_buffer_size),
+            vk::DescriptorType::STORAGE_BUFFER => Ok(self.storage_buffer_size),
+            vk::DescriptorType::SAMPLED_IMAGE => Ok(self.sampled_image_size),
+            vk::DescriptorType::STORAGE_IMAGE => Ok(self.storage_image_size),
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER => Ok(self.combined_image_sampler_size),
+            other => Err(anyhow::anyhow!(
+                "Descriptor type {:?} is not supported in descriptor buffers",
+                other
+            )),
+        }
+    }
+
+    /// Stride between consecutive sets of a layout of `layout_size` bytes.
+    pub fn set_stride(&self, layout_size: vk::DeviceSize) -> vk::DeviceSize {
+        layout_size.next_multiple_of(self.offset_alignment.max(1))
+    }
+}
+
+/// Descriptors for `set_count` sets of one layout, written straight into a host-visible
+/// buffer instead of allocated from a pool. The layout must be created with
+/// `DescriptorBackend::Buffer.layout_flags()` and pipelines using it with
+/// `DescriptorBackend::Buffer.pipeline_flags()`.
+///
+/// Binding is two calls, `bind_buffer` once per command buffer and `bind_set` per draw or
+/// dispatch, which only sets an offset. Writes take effect immediately, so a set must not be
+/// rewritten while the GPU may still read it.
+pub struct DescriptorBuffer {
+    buffer: MappedBuffer,
+    address: vk::DeviceAddress,
+    loader: ash::ext::descriptor_buffer::Device,
+    properties: DescriptorBufferProperties,
+    set_stride: vk::DeviceSize,
+    set_count: u32,
+    /// Binding number, type and offset within a set.
+    bindings: Vec<(u32, vk::DescriptorType, vk::DeviceSize)>,
+}
+
+impl DescriptorBuffer {
+    pub fn new(
+        instance: &VulkanInstance,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        layout: &VulkanDescriptorSetLayout,
+        set_count: u32,
+    ) -> Result<Self> {
+        let loader = device
+            .descriptor_buffer
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Descriptor buffers are not enabled on this device"))?;
+        let properties = DescriptorBufferProperties::query(instance, physical_device);
+
+        let layout_size = unsafe { loader.get_descriptor_set_layout_size(layout.layout) };
+        let bindings = layout
+            .bindings
+            .iter()
+            .map(|binding| {
+                let offset = unsafe {
+                    loader.get_descriptor_set_layout_binding_offset(layout.layout, binding.binding)
+                };
+                (binding.binding, binding.descriptor_type, offset)
+            })
+            .collect();
+        let set_stride = properties.set_stride(layout_size);
+
+        let buffer = MappedBuffer::new(
+            device,
+            physical_device,
+            (set_stride * set_count.max(1) as vk::DeviceSize).max(4),
+            vk::BufferUsageFlags::RESOURCE_DESCRIPTOR_BUFFER_EXT
+                | vk::BufferUsageFlags::SAMPLER_DESCRIPTOR_BUFFER_EXT
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+
+        Ok(Self {
+            address: buffer.buffer.device_address(),
+            buffer,
+            loader,
+            properties,
+            set_stride,
+            set_count,
+            bindings,
+        })
+    }
+
+    pub fn set_count(&self) -> u32 {
+        self.set_count
+    }
+
+    /// Offset of `set` in the buffer, as passed to `vkCmdSetDescriptorBufferOffsetsEXT`.
+    pub fn set_offset(&self, set: u32) -> vk::DeviceSize {
+        set as vk::DeviceSize * self.set_stride
+    }
+
+    fn write(
+        &mut self,
+        set: u32,
+        binding: u32,
+        expected: &[vk::DescriptorType],
+        data: vk::DescriptorDataEXT,
+    ) -> Result<()> {
+        if set >= self.set_count {
+            return Err(anyhow::anyhow!(
+                "Descriptor set {} out of range for a buffer of {} sets",
+                set,
+                self.set_count
+            ));
+        }
+        let &(_, descriptor_type, offset) = self
+            .bindings
+            .iter()
+            .find(|(number, _, _)| *number == binding)
+            .ok_or_else(|| anyhow::anyhow!("Layout has no binding {}", binding))?;
+        if !expected.contains(&descriptor_type) {
+            return Err(anyhow::anyhow!(
+                "Binding {} is {:?}, not {:?}",
+                binding,
+                descriptor_type,
+                expected
+            ));
+        }
+
+        let mut descriptor = vec![0u8; self.properties.descriptor_size(descriptor_type)?];
+        let info = vk::DescriptorGetInfoEXT::default()
+            .ty(descriptor_type)
+            .data(data);
+        unsafe { self.loader.get_descriptor(&info, &mut descriptor) };
+        self.buffer
+            .write(self.set_offset(set) + offset, &descriptor)
+    }
+
+    /// Writes a uniform or storage buffer descriptor for the whole of `buffer`, which needs
+    /// `SHADER_DEVICE_ADDRESS` usage.
+    pub fn write_buffer(&mut self, set: u32, binding: u32, buffer: &VulkanBuffer) -> Result<()> {
+        let address_info = vk::DescriptorAddressInfoEXT::default()
+            .address(buffer.device_address())
+            .range(buffer.size);
+        let descriptor_type = self
+            .bindings
+            .iter()
+            .find(|(number, _, _)| *number == binding)
+            .map(|&(_, descriptor_type, _)| descriptor_type);
+        let data = if descriptor_type == Some(vk::DescriptorType::UNIFORM_BUFFER) {
+            vk::DescriptorDataEXT {
+                p_uniform_buffer: &address_info,
+            }
+        } else {
+            vk::DescriptorDataEXT {
+                p_storage_buffer: &address_info,
+            }
+        };
+        self.write(
+            set,
+            binding,
+            &[
+                vk::DescriptorType::UNIFORM_BUFFER,
+                vk::DescriptorType::STORAGE_BUFFER,
+            ],
+            data,
+        )
+    }
+
+    /// Writes a combined image sampler descriptor for an image in `SHADER_READ_ONLY_OPTIMAL`.
+    pub fn write_image(
+        &mut self,
+        set: u32,
+        binding: u32,
+        view: vk::ImageView,
+        sampler: vk::Sampler,
+    ) -> Result<()> {
+        let image_info = vk::DescriptorImageInfo::default()
+            .image_view(view)
+            .sampler(sampler)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        self.write(
+            set,
+            binding,
+            &[vk::DescriptorType::COMBINED_IMAGE_SAMPLER],
+            vk::DescriptorDataEXT {
+                p_combined_image_sampler: &image_info,
+            },
+        )
+    }
+
+    /// Writes a storage image descriptor for an image in `GENERAL`.
+    pub fn write_storage_image(
+        &mut self,
+        set: u32,
+        binding: u32,
+        view: vk::ImageView,
+    ) -> Result<()> {
+        let image_info = vk::DescriptorImageInfo::default()
+            .image_view(view)
+            .image_layout(vk::ImageLayout::GENERAL);
+        self.write(
+            set,
+            binding,
+            &[vk::DescriptorType::STORAGE_IMAGE],
+            vk::DescriptorDataEXT {
+                p_storage_image: &image_info,
+            },
+        )
+    }
+
+    /// Binds the buffer as descriptor buffer 0 of `command_buffer`.
+    pub fn bind_buffer(&self, command_buffer: vk::CommandBuffer) {
+        let binding_info = vk::DescriptorBufferBindingInfoEXT::default()
+            .address(self.address)
+            .usage(
+                vk::BufferUsageFlags::RESOURCE_DESCRIPTOR_BUFFER_EXT
+                    | vk::BufferUsageFlags::SAMPLER_DESCRIPTOR_BUFFER_EXT,
+            );
+        unsafe {
+            self.loader
+                .cmd_bind_descriptor_buffers(command_buffer, std::slice::from_ref(&binding_info))
+        };
+    }
+
+    /// Points set `first_set` of `pipeline_layout` at `set`, like binding a descriptor set.
+    pub fn bind_set(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        bind_point: vk::PipelineBindPoint,
+        pipeline_layout: vk::PipelineLayout,
+        first_set: u32,
+        set: u32,
+    ) {
+        unsafe {
+            self.loader.cmd_set_descriptor_buffer_offsets(
+                command_buffer,
+                bind_point,
+                pipeline_layout,
+                first_set,
+                &[0],
+                &[self.set_offset(set)],
+            )
+        };
+    }
+}
+
+/// CPU time spent recording descriptor binds with each backend.
+#[derive(Debug, Clone, Copy)]
+pub struct BindingOverhead {
+    pub binds: u32,
+    pub sets: Duration,
+    pub buffer: Duration,
+}
+
+impl BindingOverhead {
+    /// Records `binds` binds of a one-storage-buffer layout with each backend into
+    /// `command_buffer`, which must be recording, and times them. The layouts and sets bound
+    /// are destroyed on return, so the command buffer must be reset, not submitted.
+    pub fn measure(
+        instance: &VulkanInstance,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        command_buffer: vk::CommandBuffer,
+        binds: u32,
+    ) -> Result<Self> {
+        const SETS: u32 = 8;
+        let bindings = [VulkanDescriptorSetLayout::binding(
+            0,
+            vk::DescriptorType::STORAGE_BUFFER,
+            vk::ShaderStageFlags::COMPUTE,
+        )];
+        let storage = VulkanBuffer::new_host_visible(
+            device,
+            physical_device,
+            256,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        )?;
+
+        let set_layout = VulkanDescriptorSetLayout::new(device, &bindings)?;
+        let pool = VulkanDescriptorPool::for_layout(device, &set_layout, SETS)?;
+        let sets = (0..SETS)
+            .map(|_| {
+                let set = pool.allocate(&set_layout)?;
+                pool.write_buffer(set, 0, vk::DescriptorType::STORAGE_BUFFER, &storage);
+                Ok(set)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let buffer_layout = VulkanDescriptorSetLayout::with_binding_flags(
+            device,
+            &bindings,
+            &[],
+            DescriptorBackend::Buffer.layout_flags(),
+        )?;
+        let mut descriptor_buffer =
+            DescriptorBuffer::new(instance, device, physical_device, &buffer_layout, SETS)?;
+        for set in 0..SETS {
+            descriptor_buffer.write_buffer(set, 0, &storage)?;
+        }
+
+        let sets_layout = PipelineLayout::new(device, set_layout.layout)?;
+        let buffer_pipeline_layout = PipelineLayout::new(device, buffer_layout.layout)?;
+
+        let start = Instant::now();
+        for i in 0..binds {
+            unsafe {
+                device.device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    sets_layout.layout,
+                    0,
+                    &[sets[(i % SETS) as usize]],
+                    &[],
+                )
+            };
+        }
+        let sets_time = start.elapsed();
+
+        let start = Instant::now();
+        descriptor_buffer.bind_buffer(command_buffer);
+        for i in 0..binds {
+            descriptor_buffer.bind_set(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                buffer_pipeline_layout.layout,
+                0,
+                i % SETS,
+            );
+        }
+        let buffer_time = start.elapsed();
+
+        Ok(Self {
+            binds,
+            sets: sets_time,
+            buffer: buffer_time,
+        })
+    }
+}
+
+impl std::fmt::Display for BindingOverhead {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let per_bind = |time: Duration| time.as_nanos() as f64 / self.binds.max(1) as f64;
+        write!(
+            f,
+            "{} binds: descriptor sets {:.0} ns/bind, descriptor buffer {:.0} ns/bind",
+            self.binds,
+            per_bind(self.sets),
+            per_bind(self.buffer)
+        )
+    }
+}
+
+/// Pipeline layout with a single set, destroyed on drop.
+struct PipelineLayout {
+    layout: vk::PipelineLayout,
+    device: Arc<Device>,
+}
+
+impl PipelineLayout {
+    fn new(device: &VulkanDevice, set_layout: vk::DescriptorSetLayout) -> Result<Self> {
+        let set_layouts = [set_layout];
+        let layout_info = vk::PipelineLayoutCreateInfo::default().set_layouts(&set_layouts);
+        let layout = unsafe {
+            device
+                .device
+                .create_pipeline_layout(&layout_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create pipeline layout: {}", e))?
+        };
+        Ok(Self {
+            layout,
+            device: device.device.clone(),
+        })
+    }
+}
+
+impl Drop for PipelineLayout {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_pipeline_layout(self.layout, None) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_descriptor_sets() {
+        let mut features = VulkanDeviceFeatures::default();
+        assert_eq!(DescriptorBackend::best(&features), DescriptorBackend::Sets);
+        assert_eq!(
+            "Buffer"
+                .parse::<DescriptorBackend>()
+                .unwrap()
+                .supported_by(&features),
+            DescriptorBackend::Sets
+        );
+
+        features.descriptor_buffer = true;
+        assert_eq!(
+            DescriptorBackend::best(&features),
+            DescriptorBackend::Buffer
+        );
+        assert_eq!(
+            " sets ".parse::<DescriptorBackend>().unwrap(),
+            DescriptorBackend::Sets
+        );
+        assert!("pools".parse::<DescriptorBackend>().is_err());
+    }
+
+    #[test]
+    fn sizes_descriptors_and_sets() {
+        let properties = DescriptorBufferProperties {
+            offset_alignment: 64,
+            storage_buffer_size: 16,
+            combined_image_sampler_size: 48,
+            ..Default::default()
+        };
+        assert_eq!(properties.set_stride(100), 128);
+        assert_eq!(properties.set_stride(128), 128);
+        assert_eq!(
+            properties
+                .descriptor_size(vk::DescriptorType::STORAGE_BUFFER)
+                .unwrap(),
+            16
+        );
+        assert_eq!(
+            properties
+                .descriptor_size(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .unwrap(),
+            48
+        );
+        assert!(
+            properties
+                .descriptor_size(vk::DescriptorType::INPUT_ATTACHMENT)
+                .is_err()
+        );
+    }
+}
@@ -5,13 +5,115 @@ use std::sync::Arc;
 
 use crate::vulkan::{QueueFamilyIndices, VulkanInstance, VulkanPhysicalDevice};
 
+/// Optional features enabled at device creation when the physical device supports them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VulkanDeviceFeatures {
+    /// Runtime-sized, partially bound, update-after-bind sampled image arrays indexed
+    /// non-uniformly.
+    pub bindless: bool,
+    /// `multiDrawIndirect` and `drawIndirectFirstInstance`.
+    pub multi_draw_indirect: bool,
+    pub draw_indirect_count: bool,
+    /// `VK_EXT_conditional_rendering`.
+    pub conditional_rendering: bool,
+    /// `VK_EXT_transform_feedback`.
+    pub transform_feedback: bool,
+    /// `VK_KHR_acceleration_structure` with buffer device addresses.
+    pub acceleration_structure: bool,
+    /// `VK_KHR_ray_query`, tracing rays from any shader stage. Implies
+    /// `acceleration_structure`.
+    pub ray_query: bool,
+    /// `VK_KHR_shader_non_semantic_info` (core in Vulkan 1.3), which `debugPrintfEXT` needs.
+    /// Output only appears with `ValidationOptions::shader_printf` in a debug build.
+    pub shader_printf: bool,
+    /// `VK_KHR_present_id` and `VK_KHR_present_wait`, for waiting until a frame is shown.
+    pub present_wait: bool,
+    /// `VK_KHR_incremental_present`, for presenting with a `DamageRegion`.
+    pub incremental_present: bool,
+    /// `VK_EXT_extended_dynamic_state3` with `extendedDynamicState3ColorWriteMask`, for
+    /// changing color write masks without rebuilding pipelines.
+    pub dynamic_color_write_mask: bool,
+    /// `shaderFloat16` and `storageBuffer16BitAccess`, for half-precision arithmetic and
+    /// 16-bit members in storage buffers.
+    pub float16: bool,
+    /// `VK_EXT_descriptor_buffer` with buffer device addresses, for writing descriptors into
+    /// plain buffers instead of allocating sets from pools.
+    pub descriptor_buffer: bool,
+    /// `dynamicRendering` (Vulkan 1.3), for rendering into image views without render pass
+    /// or framebuffer objects; see `CommandEncoder::begin_rendering`.
+    pub dynamic_rendering: bool,
+    /// `VK_EXT_shader_object` on Vulkan 1.3, for drawing with shaders bound on their own and
+    /// all state set dynamically, without graphics pipelines. Implies `dynamic_rendering`,
+    /// which shader object draws must be recorded in.
+    pub shader_object: bool,
+}
+
+impl VulkanDeviceFeatures {
+    /// Everything the GPU-driven renderer needs.
+    pub fn gpu_driven(&self) -> bool {
+        self.bindless && self.multi_draw_indirect
+    }
+
+    /// Extra usage for buffers that acceleration structures may be built from, empty when
+    /// they aren't supported.
+    pub fn acceleration_structure_input_usage(&self) -> vk::BufferUsageFlags {
+        if self.acceleration_structure {
+            vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
+        } else {
+            vk::BufferUsageFlags::empty()
+        }
+    }
+}
+
+/// Queue handles a `VulkanDevice` submits to, one per family of its `QueueFamilyIndices`.
+#[derive(Debug, Clone, Copy)]
+pub struct VulkanQueues {
+    pub graphics: vk::Queue,
+    pub compute: Option<vk::Queue>,
+    pub transfer: Option<vk::Queue>,
+    pub present: Option<vk::Queue>,
+}
+
+impl VulkanQueues {
+    /// Queue 0 of each family, the only queue `VulkanDevice::new` creates per family.
+    fn first_of_families(device: &Device, queue_families: &QueueFamilyIndices) -> Self {
+        let first = |family: u32| unsafe { device.get_device_queue(family, 0) };
+        Self {
+            graphics: first(queue_families.graphics_family.unwrap()),
+            compute: queue_families.compute_family.map(first),
+            transfer: queue_families.transfer_family.map(first),
+            present: queue_families.present_family.map(first),
+        }
+    }
+}
+
 pub struct VulkanDevice {
     pub device: Arc<Device>,
+    pub features: VulkanDeviceFeatures,
+    /// Loaded when `features.conditional_rendering` is enabled.
+    pub conditional_rendering: Option<ash::ext::conditional_rendering::Device>,
+    /// Loaded when `features.transform_feedback` is enabled.
+    pub transform_feedback: Option<ash::ext::transform_feedback::Device>,
+    /// Loaded when `features.acceleration_structure` is enabled.
+    pub acceleration_structure: Option<ash::khr::acceleration_structure::Device>,
+    /// Loaded when `features.present_wait` is enabled.
+    pub present_wait: Option<ash::khr::present_wait::Device>,
+    /// Loaded when `features.dynamic_color_write_mask` is enabled.
+    pub extended_dynamic_state3: Option<ash::ext::extended_dynamic_state3::Device>,
+    /// Loaded when `features.descriptor_buffer` is enabled.
+    pub descriptor_buffer: Option<ash::ext::descriptor_buffer::Device>,
+    /// Loaded when `features.shader_object` is enabled.
+    pub shader_object: Option<ash::ext::shader_object::Device>,
     pub graphics_queue: vk::Queue,
     pub compute_queue: Option<vk::Queue>,
     pub transfer_queue: Option<vk::Queue>,
     pub present_queue: Option<vk::Queue>,
     pub queue_family_indices: QueueFamilyIndices,
+    /// Physical devices behind this device, more than one when created `with_device_group`.
+    pub device_count: u32,
+    /// False for devices wrapped with `from_raw`, which are left to their creator.
+    owned: bool,
 }
 
 impl VulkanDevice {
@@ -20,7 +122,19 @@ impl VulkanDevice {
         physical_device: &VulkanPhysicalDevice,
         queue_families: QueueFamilyIndices,
     ) -> Result<Self> {
-        let device_extensions = Self::get_required_device_extensions();
+        Self::with_device_group(instance, physical_device, queue_families, &[])
+    }
+
+    /// Like `new`, spanning every physical device of `group` when it has more than one, e.g.
+    /// linked GPUs from `DeviceGroup::containing`. `physical_device` must be one of them; its
+    /// features are the ones enabled. Commands run on all devices unless masked.
+    pub fn with_device_group(
+        instance: &VulkanInstance,
+        physical_device: &VulkanPhysicalDevice,
+        queue_families: QueueFamilyIndices,
+        group: &[vk::PhysicalDevice],
+    ) -> Result<Self> {
+        let mut device_extensions = Self::get_required_device_extensions();
 
         if !physical_device
             .check_device_extension_support(&instance.instance, &device_extensions)?
@@ -55,12 +169,274 @@ impl VulkanDevice {
             })
             .collect();
 
-        let device_features = vk::PhysicalDeviceFeatures::default().sampler_anisotropy(true);
+        // PhysicalDeviceVulkan11Features and Vulkan12Features may only be chained on Vulkan
+        // 1.2 devices; older devices report none of their features.
+        let vulkan_12 = physical_device.properties.api_version >= vk::API_VERSION_1_2;
+
+        let conditional_rendering_extension = physical_device
+            .supports_extension(&instance.instance, ash::ext::conditional_rendering::NAME)?;
+
+        let transform_feedback_extension = physical_device
+            .supports_extension(&instance.instance, ash::ext::transform_feedback::NAME)?;
+
+        let acceleration_structure_extensions = vulkan_12
+            && physical_device
+                .supports_extension(&instance.instance, ash::khr::acceleration_structure::NAME)?
+            && physical_device
+                .supports_extension(&instance.instance, ash::khr::deferred_host_operations::NAME)?;
+
+        let ray_query_extension = acceleration_structure_extensions
+            && physical_device.supports_extension(&instance.instance, ash::khr::ray_query::NAME)?;
+
+        let present_wait_extensions = physical_device
+            .supports_extension(&instance.instance, ash::khr::present_id::NAME)?
+            && physical_device
+                .supports_extension(&instance.instance, ash::khr::present_wait::NAME)?;
+
+        let incremental_present_extension = physical_device
+            .supports_extension(&instance.instance, ash::khr::incremental_present::NAME)?;
+
+        let extended_dynamic_state3_extension = physical_device
+            .supports_extension(&instance.instance, ash::ext::extended_dynamic_state3::NAME)?;
+
+        let descriptor_buffer_extension = physical_device
+            .supports_extension(&instance.instance, ash::ext::descriptor_buffer::NAME)?;
+
+        let vulkan_13 = physical_device.properties.api_version >= vk::API_VERSION_1_3;
+        // Needs dynamic rendering, which is only core from 1.3.
+        let shader_object_extension = vulkan_13
+            && physical_device
+                .supports_extension(&instance.instance, ash::ext::shader_object::NAME)?;
+        let non_semantic_info_extension = !vulkan_13
+            && physical_device
+                .supports_extension(&instance.instance, ash::khr::shader_non_semantic_info::NAME)?;
+
+        let mut supported_11 = vk::PhysicalDeviceVulkan11Features::default();
+        let mut supported_12 = vk::PhysicalDeviceVulkan12Features::default();
+        let mut supported_conditional =
+            vk::PhysicalDeviceConditionalRenderingFeaturesEXT::default();
+        let mut supported_transform_feedback =
+            vk::PhysicalDeviceTransformFeedbackFeaturesEXT::default();
+        let mut supported_acceleration_structure =
+            vk::PhysicalDeviceAccelerationStructureFeaturesKHR::default();
+        let mut supported_ray_query = vk::PhysicalDeviceRayQueryFeaturesKHR::default();
+        let mut supported_present_id = vk::PhysicalDevicePresentIdFeaturesKHR::default();
+        let mut supported_present_wait = vk::PhysicalDevicePresentWaitFeaturesKHR::default();
+        let mut supported_extended_dynamic_state3 =
+            vk::PhysicalDeviceExtendedDynamicState3FeaturesEXT::default();
+        let mut supported_descriptor_buffer =
+            vk::PhysicalDeviceDescriptorBufferFeaturesEXT::default();
+        let mut supported_shader_object = vk::PhysicalDeviceShaderObjectFeaturesEXT::default();
+        let mut supported_13 = vk::PhysicalDeviceVulkan13Features::default();
+        let mut supported = vk::PhysicalDeviceFeatures2::default();
+        if vulkan_12 {
+            supported = supported
+                .push_next(&mut supported_11)
+                .push_next(&mut supported_12);
+        }
+        if vulkan_13 {
+            supported = supported.push_next(&mut supported_13);
+        }
+        if conditional_rendering_extension {
+            supported = supported.push_next(&mut supported_conditional);
+        }
+        if transform_feedback_extension {
+            supported = supported.push_next(&mut supported_transform_feedback);
+        }
+        if acceleration_structure_extensions {
+            supported = supported.push_next(&mut supported_acceleration_structure);
+        }
+        if ray_query_extension {
+            supported = supported.push_next(&mut supported_ray_query);
+        }
+        if present_wait_extensions {
+            supported = supported
+                .push_next(&mut supported_present_id)
+                .push_next(&mut supported_present_wait);
+        }
+        if extended_dynamic_state3_extension {
+            supported = supported.push_next(&mut supported_extended_dynamic_state3);
+        }
+        if descriptor_buffer_extension {
+            supported = supported.push_next(&mut supported_descriptor_buffer);
+        }
+        if shader_object_extension {
+            supported = supported.push_next(&mut supported_shader_object);
+        }
+        unsafe {
+            instance
+                .instance
+                .get_physical_device_features2(physical_device.physical_device, &mut supported);
+        }
+        let supported_10 = supported.features;
+
+        let mut features = VulkanDeviceFeatures {
+            bindless: supported_12.runtime_descriptor_array == vk::TRUE
+                && supported_12.descriptor_binding_partially_bound == vk::TRUE
+                && supported_12.descriptor_binding_sampled_image_update_after_bind == vk::TRUE
+                && supported_12.shader_sampled_image_array_non_uniform_indexing == vk::TRUE,
+            multi_draw_indirect: supported_10.multi_draw_indirect == vk::TRUE
+                && supported_10.draw_indirect_first_instance == vk::TRUE,
+            draw_indirect_count: supported_12.draw_indirect_count == vk::TRUE,
+            conditional_rendering: conditional_rendering_extension
+                && supported_conditional.conditional_rendering == vk::TRUE,
+            transform_feedback: transform_feedback_extension
+                && supported_transform_feedback.transform_feedback == vk::TRUE,
+            acceleration_structure: acceleration_structure_extensions
+                && supported_acceleration_structure.acceleration_structure == vk::TRUE
+                && supported_12.buffer_device_address == vk::TRUE,
+            ray_query: false,
+            shader_printf: vulkan_13 || non_semantic_info_extension,
+            present_wait: present_wait_extensions
+                && supported_present_id.present_id == vk::TRUE
+                && supported_present_wait.present_wait == vk::TRUE,
+            incremental_present: incremental_present_extension,
+            dynamic_color_write_mask: extended_dynamic_state3_extension
+                && supported_extended_dynamic_state3.extended_dynamic_state3_color_write_mask
+                    == vk::TRUE,
+            float16: supported_12.shader_float16 == vk::TRUE
+                && supported_11.storage_buffer16_bit_access == vk::TRUE,
+            descriptor_buffer: descriptor_buffer_extension
+                && supported_descriptor_buffer.descriptor_buffer == vk::TRUE
+                && supported_12.buffer_device_address == vk::TRUE,
+            dynamic_rendering: vulkan_13 && supported_13.dynamic_rendering == vk::TRUE,
+            shader_object: false,
+        };
+        features.ray_query = features.acceleration_structure
+            && ray_query_extension
+            && supported_ray_query.ray_query == vk::TRUE;
+        features.shader_object = features.dynamic_rendering
+            && shader_object_extension
+            && supported_shader_object.shader_object == vk::TRUE;
+
+        let device_features = vk::PhysicalDeviceFeatures::default()
+            .sampler_anisotropy(true)
+            .multi_draw_indirect(features.multi_draw_indirect)
+            .draw_indirect_first_instance(features.multi_draw_indirect);
+
+        let mut vulkan_11_features = vk::PhysicalDeviceVulkan11Features::default()
+            .storage_buffer16_bit_access(features.float16);
+
+        let mut vulkan_12_features = vk::PhysicalDeviceVulkan12Features::default()
+            .shader_float16(features.float16)
+            .runtime_descriptor_array(features.bindless)
+            .descriptor_binding_partially_bound(features.bindless)
+            .descriptor_binding_sampled_image_update_after_bind(features.bindless)
+            .shader_sampled_image_array_non_uniform_indexing(features.bindless)
+            .draw_indirect_count(features.draw_indirect_count)
+            .buffer_device_address(features.acceleration_structure || features.descriptor_buffer);
 
-        let device_create_info = vk::DeviceCreateInfo::default()
+        let mut vulkan_13_features =
+            vk::PhysicalDeviceVulkan13Features::default().dynamic_rendering(true);
+
+        let mut conditional_rendering_features =
+            vk::PhysicalDeviceConditionalRenderingFeaturesEXT::default()
+                .conditional_rendering(true);
+        if features.conditional_rendering {
+            device_extensions.push(ash::ext::conditional_rendering::NAME.as_ptr());
+        }
+
+        let mut transform_feedback_features =
+            vk::PhysicalDeviceTransformFeedbackFeaturesEXT::default().transform_feedback(true);
+        if features.transform_feedback {
+            device_extensions.push(ash::ext::transform_feedback::NAME.as_ptr());
+        }
+
+        let mut acceleration_structure_features =
+            vk::PhysicalDeviceAccelerationStructureFeaturesKHR::default()
+                .acceleration_structure(true);
+        if features.acceleration_structure {
+            device_extensions.push(ash::khr::acceleration_structure::NAME.as_ptr());
+            device_extensions.push(ash::khr::deferred_host_operations::NAME.as_ptr());
+        }
+
+        let mut ray_query_features =
+            vk::PhysicalDeviceRayQueryFeaturesKHR::default().ray_query(true);
+        if features.ray_query {
+            device_extensions.push(ash::khr::ray_query::NAME.as_ptr());
+        }
+
+        let mut present_id_features =
+            vk::PhysicalDevicePresentIdFeaturesKHR::default().present_id(true);
+        let mut present_wait_features =
+            vk::PhysicalDevicePresentWaitFeaturesKHR::default().present_wait(true);
+        if features.present_wait {
+            device_extensions.push(ash::khr::present_id::NAME.as_ptr());
+            device_extensions.push(ash::khr::present_wait::NAME.as_ptr());
+        }
+
+        if features.incremental_present {
+            device_extensions.push(ash::khr::incremental_present::NAME.as_ptr());
+        }
+
+        let mut extended_dynamic_state3_features =
+            vk::PhysicalDeviceExtendedDynamicState3FeaturesEXT::default()
+                .extended_dynamic_state3_color_write_mask(true);
+        if features.dynamic_color_write_mask {
+            device_extensions.push(ash::ext::extended_dynamic_state3::NAME.as_ptr());
+        }
+
+        let mut descriptor_buffer_features =
+            vk::PhysicalDeviceDescriptorBufferFeaturesEXT::default().descriptor_buffer(true);
+        if features.descriptor_buffer {
+            device_extensions.push(ash::ext::descriptor_buffer::NAME.as_ptr());
+        }
+
+        let mut shader_object_features =
+            vk::PhysicalDeviceShaderObjectFeaturesEXT::default().shader_object(true);
+        if features.shader_object {
+            device_extensions.push(ash::ext::shader_object::NAME.as_ptr());
+        }
+
+        if non_semantic_info_extension {
+            device_extensions.push(ash::khr::shader_non_semantic_info::NAME.as_ptr());
+        }
+
+        let mut device_group_info =
+            vk::DeviceGroupDeviceCreateInfo::default().physical_devices(group);
+
+        let mut device_create_info = vk::DeviceCreateInfo::default()
             .queue_create_infos(&queue_create_infos)
             .enabled_extension_names(&device_extensions)
             .enabled_features(&device_features);
+        if group.len() > 1 {
+            device_create_info = device_create_info.push_next(&mut device_group_info);
+        }
+        if vulkan_12 {
+            device_create_info = device_create_info
+                .push_next(&mut vulkan_11_features)
+                .push_next(&mut vulkan_12_features);
+        }
+        if features.dynamic_rendering {
+            device_create_info = device_create_info.push_next(&mut vulkan_13_features);
+        }
+        if features.conditional_rendering {
+            device_create_info = device_create_info.push_next(&mut conditional_rendering_features);
+        }
+        if features.transform_feedback {
+            device_create_info = device_create_info.push_next(&mut transform_feedback_features);
+        }
+        if features.acceleration_structure {
+            device_create_info = device_create_info.push_next(&mut acceleration_structure_features);
+        }
+        if features.ray_query {
+            device_create_info = device_create_info.push_next(&mut ray_query_features);
+        }
+        if features.present_wait {
+            device_create_info = device_create_info
+                .push_next(&mut present_id_features)
+                .push_next(&mut present_wait_features);
+        }
+        if features.dynamic_color_write_mask {
+            device_create_info =
+                device_create_info.push_next(&mut extended_dynamic_state3_features);
+        }
+        if features.descriptor_buffer {
+            device_create_info = device_create_info.push_next(&mut descriptor_buffer_features);
+        }
+        if features.shader_object {
+            device_create_info = device_create_info.push_next(&mut shader_object_features);
+        }
 
         let device = unsafe {
             instance.instance.create_device(
@@ -70,29 +446,89 @@ impl VulkanDevice {
             )?
         };
 
-        let graphics_queue =
-            unsafe { device.get_device_queue(queue_families.graphics_family.unwrap(), 0) };
+        let queues = VulkanQueues::first_of_families(&device, &queue_families);
+        let mut device =
+            Self::from_device(instance, device, features, queue_families, queues, true);
+        device.device_count = group.len().max(1) as u32;
+        Ok(device)
+    }
 
-        let compute_queue = queue_families
-            .compute_family
-            .map(|family| unsafe { device.get_device_queue(family, 0) });
+    /// Wraps a device created by another engine so the pipeline builder, allocator and
+    /// renderers here can use it. `features` must only contain what the device was created
+    /// with, and `queues` are the host engine's queue handles, whatever their index within
+    /// their family. Submissions to those queues must be synchronized with the host engine's
+    /// own.
+    ///
+    /// # Safety
+    /// `device` must be a valid device created from `instance` and must outlive the returned
+    /// value; when `owned` is true it is destroyed on drop. Each of `queues` must belong to
+    /// `device` and to the matching family of `queue_families`.
+    pub unsafe fn from_raw(
+        instance: &VulkanInstance,
+        device: vk::Device,
+        queue_families: QueueFamilyIndices,
+        queues: VulkanQueues,
+        features: VulkanDeviceFeatures,
+        owned: bool,
+    ) -> Self {
+        let device = unsafe { Device::load(instance.instance.fp_v1_0(), device) };
+        Self::from_device(instance, device, features, queue_families, queues, owned)
+    }
+
+    fn from_device(
+        instance: &VulkanInstance,
+        device: Device,
+        features: VulkanDeviceFeatures,
+        queue_families: QueueFamilyIndices,
+        queues: VulkanQueues,
+        owned: bool,
+    ) -> Self {
+        let conditional_rendering = features
+            .conditional_rendering
+            .then(|| ash::ext::conditional_rendering::Device::new(&instance.instance, &device));
+
+        let transform_feedback = features
+            .transform_feedback
+            .then(|| ash::ext::transform_feedback::Device::new(&instance.instance, &device));
 
-        let transfer_queue = queue_families
-            .transfer_family
-            .map(|family| unsafe { device.get_device_queue(family, 0) });
+        let acceleration_structure = features
+            .acceleration_structure
+            .then(|| ash::khr::acceleration_structure::Device::new(&instance.instance, &device));
 
-        let present_queue = queue_families
-            .present_family
-            .map(|family| unsafe { device.get_device_queue(family, 0) });
+        let present_wait = features
+            .present_wait
+            .then(|| ash::khr::present_wait::Device::new(&instance.instance, &device));
 
-        Ok(Self {
+        let extended_dynamic_state3 = features
+            .dynamic_color_write_mask
+            .then(|| ash::ext::extended_dynamic_state3::Device::new(&instance.instance, &device));
+
+        let descriptor_buffer = features
+            .descriptor_buffer
+            .then(|| ash::ext::descriptor_buffer::Device::new(&instance.instance, &device));
+
+        let shader_object = features
+            .shader_object
+            .then(|| ash::ext::shader_object::Device::new(&instance.instance, &device));
+
+        Self {
             device: Arc::new(device),
-            graphics_queue,
-            compute_queue,
-            transfer_queue,
-            present_queue,
+            features,
+            conditional_rendering,
+            transform_feedback,
+            acceleration_structure,
+            present_wait,
+            extended_dynamic_state3,
+            descriptor_buffer,
+            shader_object,
+            graphics_queue: queues.graphics,
+            compute_queue: queues.compute,
+            transfer_queue: queues.transfer,
+            present_queue: queues.present,
             queue_family_indices: queue_families,
-        })
+            device_count: 1,
+            owned,
+        }
     }
 
     fn get_required_device_extensions() -> Vec<*const i8> {
@@ -109,6 +545,9 @@ impl VulkanDevice {
 
 impl Drop for VulkanDevice {
     fn drop(&mut self) {
+        if !self.owned {
+            return;
+        }
         unsafe {
             self.device.destroy_device(None);
         }
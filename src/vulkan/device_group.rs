@@ -0,0 +1,330 @@
+use anyhow::Result;
+use ash::vk;
+use std::str::FromStr;
+
+use crate::vulkan::{VulkanDevice, VulkanInstance, VulkanPhysicalDevice};
+
+pub const MULTI_GPU_ENV: &str = "RVE_MULTI_GPU";
+
+/// Physical devices the driver links into one logical device, e.g. GPUs bridged with
+/// SLI or CrossFire.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceGroup {
+    pub physical_devices: Vec<vk::PhysicalDevice>,
+    /// Whether memory can be allocated on a subset of the devices.
+    pub subset_allocation: bool,
+}
+
+impl DeviceGroup {
+    pub fn enumerate(instance: &VulkanInstance) -> Result<Vec<Self>> {
+        let instance = &instance.instance;
+        let count = unsafe { instance.enumerate_physical_device_groups_len() }
+            .map_err(|e| anyhow::anyhow!("Failed to enumerate device groups: {}", e))?;
+        let mut groups = vec![vk::PhysicalDeviceGroupProperties::default(); count];
+        unsafe { instance.enumerate_physical_device_groups(&mut groups) }
+            .map_err(|e| anyhow::anyhow!("Failed to enumerate device groups: {}", e))?;
+
+        Ok(groups
+            .iter()
+            .map(|group| Self {
+                physical_devices: group.physical_devices[..group.physical_device_count as usize]
+                    .to_vec(),
+                subset_allocation: group.subset_allocation == vk::TRUE,
+            })
+            .collect())
+    }
+
+    /// The group `physical_device` belongs to.
+    pub fn containing(
+        instance: &VulkanInstance,
+        physical_device: &VulkanPhysicalDevice,
+    ) -> Result<Option<Self>> {
+        Ok(Self::enumerate(instance)?.into_iter().find(|group| {
+            group
+                .physical_devices
+                .contains(&physical_device.physical_device)
+        }))
+    }
+
+    pub fn len(&self) -> usize {
+        self.physical_devices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.physical_devices.is_empty()
+    }
+
+    /// True if there is more than one GPU to spread work across.
+    pub fn is_linked(&self) -> bool {
+        self.len() > 1
+    }
+}
+
+/// How frames are spread across the GPUs of a device group.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MultiGpuMode {
+    /// Everything runs on the first GPU.
+    #[default]
+    Single,
+    /// Alternate frame rendering: each frame runs entirely on one GPU, in turn. Scales well
+    /// but adds a frame of latency per GPU, and the finished image must be copied to the
+    /// presenting GPU through peer memory.
+    AlternateFrame,
+    /// Split frame rendering: every GPU renders its own horizontal band of each frame.
+    /// Geometry work is repeated on every GPU.
+    SplitFrame,
+}
+
+impl MultiGpuMode {
+    /// Reads `RVE_MULTI_GPU` (`single`, `afr` or `sfr`), defaulting to `Single`. Any mode
+    /// falls back to `Single` on a device made of one GPU.
+    pub fn from_env(device: &VulkanDevice) -> Result<Self> {
+        let mode = match std::env::var(MULTI_GPU_ENV) {
+            Ok(value) => value.parse()?,
+            Err(_) => Self::default(),
+        };
+        Ok(if device.device_count > 1 {
+            mode
+        } else {
+            Self::Single
+        })
+    }
+
+    /// Device mask and per-device render areas for `frame`, as passed to
+    /// `VkDeviceGroupRenderPassBeginInfo` and `VkDeviceGroupSubmitInfo`.
+    pub fn frame(self, frame: u64, extent: vk::Extent2D, device_count: u32) -> MultiGpuFrame {
+        let device_count = device_count.clamp(1, 32);
+        let full = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+        match self {
+            Self::Single => MultiGpuFrame {
+                device_mask: 1,
+                render_areas: vec![full],
+            },
+            Self::AlternateFrame => {
+                let device = (frame % device_count as u64) as u32;
+                MultiGpuFrame {
+                    device_mask: 1 << device,
+                    render_areas: vec![full; device_count as usize],
+                }
+            }
+            Self::SplitFrame => MultiGpuFrame {
+                device_mask: all_devices(device_count),
+                render_areas: split_frame_areas(extent, device_count),
+            },
+        }
+    }
+}
+
+impl FromStr for MultiGpuMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "single" => Ok(Self::Single),
+            "afr" | "alternate_frame" => Ok(Self::AlternateFrame),
+            "sfr" | "split_frame" => Ok(Self::SplitFrame),
+            other => Err(anyhow::anyhow!(
+                "Unknown multi-GPU mode '{}', expected 'single', 'afr' or 'sfr'",
+                other
+            )),
+        }
+    }
+}
+
+fn all_devices(device_count: u32) -> u32 {
+    if device_count >= 32 {
+        u32::MAX
+    } else {
+        (1 << device_count) - 1
+    }
+}
+
+/// Horizontal bands covering `extent`, one per device; the last takes the remainder.
+pub fn split_frame_areas(extent: vk::Extent2D, device_count: u32) -> Vec<vk::Rect2D> {
+    let device_count = device_count.max(1);
+    let band = extent.height / device_count;
+    (0..device_count)
+        .map(|device| {
+            let y = device * band;
+            let height = if device + 1 == device_count {
+                extent.height - y
+            } else {
+                band
+            };
+            vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: y as i32 },
+                extent: vk::Extent2D {
+                    width: extent.width,
+                    height,
+                },
+            }
+        })
+        .collect()
+}
+
+/// Where one frame runs on a device group.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiGpuFrame {
+    /// Devices that execute the frame's command buffers.
+    pub device_mask: u32,
+    /// Render area of each device, indexed by device.
+    pub render_areas: Vec<vk::Rect2D>,
+}
+
+impl MultiGpuFrame {
+    /// Index of the device that renders the whole frame, for alternate frame rendering.
+    pub fn single_device(&self) -> Option<u32> {
+        self.device_mask
+            .is_power_of_two()
+            .then(|| self.device_mask.trailing_zeros())
+    }
+
+    /// Render pass begin info restricting the pass to this frame's devices and areas. The
+    /// areas only apply when every device renders; a single device renders everything.
+    pub fn render_pass_begin_info(&self) -> vk::DeviceGroupRenderPassBeginInfo<'_> {
+        let info = vk::DeviceGroupRenderPassBeginInfo::default().device_mask(self.device_mask);
+        if self.single_device().is_some() {
+            info
+        } else {
+            info.device_render_areas(&self.render_areas)
+        }
+    }
+}
+
+/// How the GPUs of `device` can access memory of heap `heap_index` that lives on another GPU
+/// of the group. Alternate frame rendering needs at least `COPY_DST` to gather frames on the
+/// presenting GPU.
+pub fn peer_memory_features(
+    device: &VulkanDevice,
+    heap_index: u32,
+    local_device: u32,
+    remote_device: u32,
+) -> vk::PeerMemoryFeatureFlags {
+    unsafe {
+        device
+            .device
+            .get_device_group_peer_memory_features(heap_index, local_device, remote_device)
+    }
+}
+
+/// Rolling GPU time of each device in a group, e.g. from per-device timestamp queries.
+#[derive(Debug, Clone, Default)]
+pub struct PerGpuTimings {
+    /// Exponential moving average in milliseconds and frames recorded, per device.
+    devices: Vec<(f32, u64)>,
+}
+
+impl PerGpuTimings {
+    /// Weight of the newest sample in the moving average.
+    const SMOOTHING: f32 = 0.1;
+
+    pub fn new(device_count: u32) -> Self {
+        Self {
+            devices: vec![(0.0, 0); device_count.max(1) as usize],
+        }
+    }
+
+    pub fn record(&mut self, device: u32, milliseconds: f32) {
+        let Some((average, frames)) = self.devices.get_mut(device as usize) else {
+            return;
+        };
+        *average = if *frames == 0 {
+            milliseconds
+        } else {
+            *average + (milliseconds - *average) * Self::SMOOTHING
+        };
+        *frames += 1;
+    }
+
+    /// Average milliseconds of `device`, `None` until it has rendered a frame.
+    pub fn average(&self, device: u32) -> Option<f32> {
+        self.devices
+            .get(device as usize)
+            .filter(|(_, frames)| *frames > 0)
+            .map(|(average, _)| *average)
+    }
+
+    pub fn frames(&self, device: u32) -> u64 {
+        self.devices
+            .get(device as usize)
+            .map_or(0, |(_, frames)| *frames)
+    }
+}
+
+impl std::fmt::Display for PerGpuTimings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (device, (average, frames)) in self.devices.iter().enumerate() {
+            if device > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "GPU{} {:.2} ms over {} frames", device, average, frames)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXTENT: vk::Extent2D = vk::Extent2D {
+        width: 1920,
+        height: 1081,
+    };
+
+    #[test]
+    fn alternates_frames_between_devices() {
+        let masks: Vec<u32> = (0..5)
+            .map(|frame| {
+                MultiGpuMode::AlternateFrame
+                    .frame(frame, EXTENT, 2)
+                    .device_mask
+            })
+            .collect();
+        assert_eq!(masks, vec![1, 2, 1, 2, 1]);
+        assert_eq!(
+            MultiGpuMode::AlternateFrame
+                .frame(3, EXTENT, 2)
+                .single_device(),
+            Some(1)
+        );
+        assert_eq!(MultiGpuMode::Single.frame(3, EXTENT, 2).device_mask, 1);
+    }
+
+    #[test]
+    fn splits_frames_into_bands() {
+        let frame = MultiGpuMode::SplitFrame.frame(0, EXTENT, 2);
+        assert_eq!(frame.device_mask, 0b11);
+        assert_eq!(frame.single_device(), None);
+
+        let areas = &frame.render_areas;
+        assert_eq!((areas[0].offset.y, areas[0].extent.height), (0, 540));
+        assert_eq!((areas[1].offset.y, areas[1].extent.height), (540, 541));
+        assert!(areas.iter().all(|area| area.extent.width == 1920));
+        assert_eq!(all_devices(32), u32::MAX);
+        assert!("mgpu".parse::<MultiGpuMode>().is_err());
+        assert_eq!(
+            "AFR".parse::<MultiGpuMode>().unwrap(),
+            MultiGpuMode::AlternateFrame
+        );
+    }
+
+    #[test]
+    fn averages_timings_per_device() {
+        let mut timings = PerGpuTimings::new(2);
+        timings.record(0, 10.0);
+        timings.record(0, 20.0);
+        timings.record(5, 1.0);
+
+        assert_eq!(timings.average(0), Some(11.0));
+        assert_eq!(timings.average(1), None);
+        assert_eq!(timings.frames(0), 2);
+        assert_eq!(
+            timings.to_string(),
+            "GPU0 11.00 ms over 2 frames, GPU1 0.00 ms over 0 frames"
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use anyhow::Result;
+use ash::vk;
+use std::ffi::CStr;
+
+use crate::vulkan::{VulkanInstance, VulkanPhysicalDevice, VulkanSurface};
+
+/// A resolution and refresh rate a display can be driven at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayMode {
+    pub mode: vk::DisplayModeKHR,
+    pub extent: vk::Extent2D,
+    /// Refresh rate in millihertz.
+    pub refresh_rate: u32,
+}
+
+/// Picks the mode matching `extent` if given, otherwise the largest one, preferring the
+/// highest refresh rate among equal resolutions.
+pub fn choose_display_mode(
+    modes: &[DisplayMode],
+    extent: Option<vk::Extent2D>,
+) -> Option<DisplayMode> {
+    modes
+        .iter()
+        .filter(|mode| extent.is_none_or(|extent| mode.extent == extent))
+        .max_by_key(|mode| {
+            (
+                mode.extent.width as u64 * mode.extent.height as u64,
+                mode.refresh_rate,
+            )
+        })
+        .copied()
+}
+
+/// A display attached directly to a physical device, usable without a window system through
+/// `VK_KHR_display`, e.g. for kiosk or embedded setups running from a VT.
+#[derive(Debug, Clone)]
+pub struct VulkanDisplay {
+    pub display: vk::DisplayKHR,
+    pub name: String,
+    /// Native resolution in pixels.
+    pub physical_resolution: vk::Extent2D,
+    pub supported_transforms: vk::SurfaceTransformFlagsKHR,
+    pub modes: Vec<DisplayMode>,
+}
+
+impl VulkanDisplay {
+    /// Instance extensions needed instead of `VulkanWindow::get_required_extensions`.
+    pub fn get_required_extensions() -> Vec<*const i8> {
+        vec![
+            ash::khr::surface::NAME.as_ptr(),
+            ash::khr::display::NAME.as_ptr(),
+        ]
+    }
+
+    /// Displays connected to `physical_device` with their modes. Displays owned by a running
+    /// window system are usually not listed.
+    pub fn enumerate(
+        instance: &VulkanInstance,
+        physical_device: &VulkanPhysicalDevice,
+    ) -> Result<Vec<Self>> {
+        let display_loader = ash::khr::display::Instance::new(&instance.entry, &instance.instance);
+
+        let properties = unsafe {
+            display_loader
+                .get_physical_device_display_properties(physical_device.physical_device)
+                .map_err(|e| anyhow::anyhow!("Failed to enumerate displays: {}", e))?
+        };
+
+        properties
+            .iter()
+            .map(|properties| {
+                let modes = unsafe {
+                    display_loader
+                        .get_display_mode_properties(
+                            physical_device.physical_device,
+                            properties.display,
+                        )
+                        .map_err(|e| anyhow::anyhow!("Failed to get display modes: {}", e))?
+                };
+
+                let name = unsafe { properties.display_name_as_c_str() }
+                    .map(CStr::to_string_lossy)
+                    .map_or_else(|| "Unknown display".to_string(), |name| name.into_owned());
+
+                Ok(Self {
+                    display: properties.display,
+                    name,
+                    physical_resolution: properties.physical_resolution,
+                    supported_transforms: properties.supported_transforms,
+                    modes: modes
+                        .iter()
+                        .map(|mode| DisplayMode {
+                            mode: mode.display_mode,
+                            extent: mode.parameters.visible_region,
+                            refresh_rate: mode.parameters.refresh_rate,
+                        })
+                        .collect(),
+                })
+            })
+            .collect()
+    }
+
+    /// Mode at the display's native resolution, or its largest one.
+    pub fn preferred_mode(&self) -> Option<DisplayMode> {
+        choose_display_mode(&self.modes, Some(self.physical_resolution))
+            .or_else(|| choose_display_mode(&self.modes, None))
+    }
+}
+
+impl VulkanSurface {
+    /// Surface covering `display` in `mode` on the first plane that can show it. Build the
+    /// swapchain on it as usual, with `mode.extent` as the window size.
+    pub fn from_display(
+        instance: &VulkanInstance,
+        physical_device: &VulkanPhysicalDevice,
+        display: &VulkanDisplay,
+        mode: DisplayMode,
+    ) -> Result<Self> {
+        let display_loader = ash::khr::display::Instance::new(&instance.entry, &instance.instance);
+
+        let planes = unsafe {
+            display_loader
+                .get_physical_device_display_plane_properties(physical_device.physical_device)
+                .map_err(|e| anyhow::anyhow!("Failed to enumerate display planes: {}", e))?
+        };
+
+        let mut plane_index = None;
+        for (index, plane) in planes.iter().enumerate() {
+            if plane.current_display != vk::DisplayKHR::null()
+                && plane.current_display != display.display
+            {
+                continue;
+            }
+            let supported = unsafe {
+                display_loader
+                    .get_display_plane_supported_displays(
+                        physical_device.physical_device,
+                        index as u32,
+                    )
+                    .map_err(|e| {
+                        anyhow::anyhow!("Failed to get displays supported by plane: {}", e)
+                    })?
+            };
+            if supported.contains(&display.display) {
+                plane_index = Some(index as u32);
+                break;
+            }
+        }
+        let plane_index = plane_index
+            .ok_or_else(|| anyhow::anyhow!("No display plane available for {}", display.name))?;
+
+        let capabilities = unsafe {
+            display_loader
+                .get_display_plane_capabilities(
+                    physical_device.physical_device,
+                    mode.mode,
+                    plane_index,
+                )
+                .map_err(|e| anyhow::anyhow!("Failed to get display plane capabilities: {}", e))?
+        };
+
+        let alpha_mode = [
+            vk::DisplayPlaneAlphaFlagsKHR::OPAQUE,
+            vk::DisplayPlaneAlphaFlagsKHR::GLOBAL,
+            vk::DisplayPlaneAlphaFlagsKHR::PER_PIXEL,
+            vk::DisplayPlaneAlphaFlagsKHR::PER_PIXEL_PREMULTIPLIED,
+        ]
+        .into_iter()
+        .find(|&alpha| capabilities.supported_alpha.contains(alpha))
+        .ok_or_else(|| anyhow::anyhow!("Display plane supports no alpha mode"))?;
+
+        let transform = if display
+            .supported_transforms
+            .contains(vk::SurfaceTransformFlagsKHR::IDENTITY)
+        {
+            vk::SurfaceTransformFlagsKHR::IDENTITY
+        } else {
+            // Lowest supported transform; a display supports at least one.
+            vk::SurfaceTransformFlagsKHR::from_raw(
+                display.supported_transforms.as_raw()
+                    & display.supported_transforms.as_raw().wrapping_neg(),
+            )
+        };
+
+        let create_info = vk::DisplaySurfaceCreateInfoKHR::default()
+            .display_mode(mode.mode)
+            .plane_index(plane_index)
+            .plane_stack_index(planes[plane_index as usize].current_stack_index)
+            .transform(transform)
+            .global_alpha(1.0)
+            .alpha_mode(alpha_mode)
+            .image_extent(mode.extent);
+
+        let surface = unsafe {
+            display_loader
+                .create_display_plane_surface(&create_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create display surface: {}", e))?
+        };
+
+        let surface_loader = ash::khr::surface::Instance::new(&instance.entry, &instance.instance);
+
+        println!(
+            "Display surface created on {} ({}x{} @ {:.2} Hz)",
+            display.name,
+            mode.extent.width,
+            mode.extent.height,
+            mode.refresh_rate as f32 / 1000.0
+        );
+
+        Ok(Self {
+            surface,
+            surface_loader,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mode(width: u32, height: u32, refresh_rate: u32) -> DisplayMode {
+        DisplayMode {
+            mode: vk::DisplayModeKHR::null(),
+            extent: vk::Extent2D { width, height },
+            refresh_rate,
+        }
+    }
+
+    #[test]
+    fn chooses_largest_then_fastest_mode() {
+        let modes = [
+            mode(1920, 1080, 60_000),
+            mode(1280, 720, 144_000),
+            mode(1920, 1080, 59_940),
+            mode(1280, 720, 60_000),
+        ];
+
+        assert_eq!(choose_display_mode(&modes, None), Some(modes[0]));
+        assert_eq!(
+            choose_display_mode(
+                &modes,
+                Some(vk::Extent2D {
+                    width: 1280,
+                    height: 720
+                })
+            ),
+            Some(modes[1])
+        );
+        assert_eq!(
+            choose_display_mode(
+                &modes,
+                Some(vk::Extent2D {
+                    width: 640,
+                    height: 480
+                })
+            ),
+            None
+        );
+        assert_eq!(choose_display_mode(&[], None), None);
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use ash::vk;
+
+use crate::vulkan::{MappedBuffer, VulkanDevice, VulkanPhysicalDevice};
+
+/// Offset of the next `size` bytes at `alignment` after `cursor`, and the cursor after them,
+/// or `None` when they don't fit in `capacity`.
+fn bump(
+    cursor: vk::DeviceSize,
+    size: vk::DeviceSize,
+    alignment: vk::DeviceSize,
+    capacity: vk::DeviceSize,
+) -> Option<(vk::DeviceSize, vk::DeviceSize)> {
+    let offset = cursor.next_multiple_of(alignment.max(1));
+    let end = offset.checked_add(size)?;
+    (end <= capacity).then_some((offset, end))
+}
+
+/// Per-frame linear allocator for data rewritten every frame, like per-draw uniforms bound
+/// as `UNIFORM_BUFFER_DYNAMIC` with the returned offsets. One persistently mapped buffer holds
+/// a region per frame in flight; `begin_frame` rewinds a region once its frame has finished
+/// on the GPU, and `flush` makes the frame's writes visible before submitting.
+pub struct FrameArena {
+    buffer: MappedBuffer,
+    frame_size: vk::DeviceSize,
+    frames: usize,
+    frame: usize,
+    cursor: vk::DeviceSize,
+    alignment: vk::DeviceSize,
+}
+
+impl FrameArena {
+    /// `frame_size` bytes for each of `frames` frames in flight, usable as uniform and
+    /// storage buffers.
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        frame_size: vk::DeviceSize,
+        frames: usize,
+    ) -> Result<Self> {
+        let limits = &physical_device.properties.limits;
+        let alignment = limits
+            .min_uniform_buffer_offset_alignment
+            .max(limits.min_storage_buffer_offset_alignment)
+            .max(1);
+        // Regions start on atom boundaries so flushing one never touches another.
+        let frame_size = frame_size
+            .next_multiple_of(alignment)
+            .next_multiple_of(limits.non_coherent_atom_size.max(1));
+        let frames = frames.max(1);
+
+        let buffer = MappedBuffer::new(
+            device,
+            physical_device,
+            frame_size * frames as vk::DeviceSize,
+            vk::BufferUsageFlags::UNIFORM_BUFFER | vk::BufferUsageFlags::STORAGE_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+        )?;
+
+        Ok(Self {
+            buffer,
+            frame_size,
+            frames,
+            frame: 0,
+            cursor: 0,
+            alignment,
+        })
+    }
+
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer.buffer.buffer
+    }
+
+    /// Bytes allocated in the current frame.
+    pub fn used(&self) -> vk::DeviceSize {
+        self.cursor
+    }
+
+    pub fn frame_size(&self) -> vk::DeviceSize {
+        self.frame_size
+    }
+
+    /// Descriptor for a `UNIFORM_BUFFER_DYNAMIC` or `STORAGE_BUFFER_DYNAMIC` binding of
+    /// `range` bytes, offset by what `push` returns.
+    pub fn dynamic_descriptor_info(&self, range: vk::DeviceSize) -> vk::DescriptorBufferInfo {
+        vk::DescriptorBufferInfo::default()
+            .buffer(self.buffer())
+            .offset(0)
+            .range(range)
+    }
+
+    /// Starts allocating from `frame`'s region, discarding what it held. The GPU must be done
+    /// with that frame.
+    pub fn begin_frame(&mut self, frame: usize) {
+        self.frame = frame % self.frames;
+        self.cursor = 0;
+    }
+
+    /// Copies `data` into the current frame and returns its offset in the buffer, aligned for
+    /// dynamic uniform and storage bindings.
+    pub fn push_slice<T: Copy>(&mut self, data: &[T]) -> Result<vk::DeviceSize> {
+        let size = std::mem::size_of_val(data) as vk::DeviceSize;
+        let Some((offset, end)) = bump(self.cursor, size, self.alignment, self.frame_size) else {
+            return Err(anyhow::anyhow!(
+                "Frame arena of {} bytes is full ({} used, {} requested)",
+                self.frame_size,
+                self.cursor,
+                size
+            ));
+        };
+        let offset = self.frame as vk::DeviceSize * self.frame_size + offset;
+        self.buffer.write(offset, data)?;
+        self.cursor = end;
+        Ok(offset)
+    }
+
+    pub fn push<T: Copy>(&mut self, value: &T) -> Result<vk::DeviceSize> {
+        self.push_slice(std::slice::from_ref(value))
+    }
+
+    /// Makes the current frame's writes visible to the GPU; call before submitting work that
+    /// reads them.
+    pub fn flush(&mut self) -> Result<()> {
+        self.buffer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bumps_aligned_offsets_until_full() {
+        assert_eq!(bump(0, 64, 256, 1024), Some((0, 64)));
+        assert_eq!(bump(64, 64, 256, 1024), Some((256, 320)));
+        assert_eq!(bump(320, 700, 256, 1024), None);
+        assert_eq!(bump(320, 512, 256, 1024), Some((512, 1024)));
+        assert_eq!(bump(10, 4, 0, 16), Some((10, 14)));
+        assert_eq!(bump(0, u64::MAX, 1, 16), None);
+    }
+}
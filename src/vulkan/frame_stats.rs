@@ -0,0 +1,491 @@
+use ash::{Device, vk};
+use std::ops::Sub;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Commands recorded through `RecordCommands`. Indirect draws count once per recorded
+/// command, since their draw and instance counts live on the GPU.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommandStats {
+    pub draw_calls: u64,
+    pub indirect_draws: u64,
+    pub instances: u64,
+    pub dispatches: u64,
+    pub pipeline_binds: u64,
+    pub descriptor_binds: u64,
+    pub buffer_binds: u64,
+}
+
+impl Sub for CommandStats {
+    type Output = Self;
+
+    fn sub(self, earlier: Self) -> Self {
+        Self {
+            draw_calls: self.draw_calls.saturating_sub(earlier.draw_calls),
+            indirect_draws: self.indirect_draws.saturating_sub(earlier.indirect_draws),
+            instances: self.instances.saturating_sub(earlier.instances),
+            dispatches: self.dispatches.saturating_sub(earlier.dispatches),
+            pipeline_binds: self.pipeline_binds.saturating_sub(earlier.pipeline_binds),
+            descriptor_binds: self
+                .descriptor_binds
+                .saturating_sub(earlier.descriptor_binds),
+            buffer_binds: self.buffer_binds.saturating_sub(earlier.buffer_binds),
+        }
+    }
+}
+
+struct Counters {
+    draw_calls: AtomicU64,
+    indirect_draws: AtomicU64,
+    instances: AtomicU64,
+    dispatches: AtomicU64,
+    pipeline_binds: AtomicU64,
+    descriptor_binds: AtomicU64,
+    buffer_binds: AtomicU64,
+}
+
+/// Running totals since startup. Commands may be recorded from several threads, so the
+/// counters are shared atomics rather than state threaded through every renderer.
+static COUNTERS: Counters = Counters {
+    draw_calls: AtomicU64::new(0),
+    indirect_draws: AtomicU64::new(0),
+    instances: AtomicU64::new(0),
+    dispatches: AtomicU64::new(0),
+    pipeline_binds: AtomicU64::new(0),
+    descriptor_binds: AtomicU64::new(0),
+    buffer_binds: AtomicU64::new(0),
+};
+
+fn count(counter: &AtomicU64, amount: u64) {
+    counter.fetch_add(amount, Ordering::Relaxed);
+}
+
+/// Totals of every command counted so far.
+pub fn command_totals() -> CommandStats {
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+    CommandStats {
+        draw_calls: load(&COUNTERS.draw_calls),
+        indirect_draws: load(&COUNTERS.indirect_draws),
+        instances: load(&COUNTERS.instances),
+        dispatches: load(&COUNTERS.dispatches),
+        pipeline_binds: load(&COUNTERS.pipeline_binds),
+        descriptor_binds: load(&COUNTERS.descriptor_binds),
+        buffer_binds: load(&COUNTERS.buffer_binds),
+    }
+}
+
+/// Counts a direct draw recorded without `RecordCommands`, e.g. through an extension loader.
+pub fn count_draw(instance_count: u32) {
+    count(&COUNTERS.draw_calls, 1);
+    count(&COUNTERS.instances, instance_count as u64);
+}
+
+/// Counts an indirect draw recorded without `RecordCommands`.
+pub fn count_indirect_draw() {
+    count(&COUNTERS.indirect_draws, 1);
+}
+
+/// Draw, dispatch and bind commands that also update the frame statistics. Renderers record
+/// through these instead of the raw `cmd_*` calls so `FrameStatsRecorder` sees their work.
+pub trait RecordCommands {
+    fn draw(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        vertex_count: u32,
+        instance_count: u32,
+        first_vertex: u32,
+        first_instance: u32,
+    );
+
+    fn draw_indexed(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        index_count: u32,
+        instance_count: u32,
+        first_index: u32,
+        vertex_offset: i32,
+        first_instance: u32,
+    );
+
+    fn draw_indirect(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        draw_count: u32,
+        stride: u32,
+    );
+
+    fn draw_indexed_indirect(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        draw_count: u32,
+        stride: u32,
+    );
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_indirect_count(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        count_buffer: vk::Buffer,
+        count_offset: vk::DeviceSize,
+        max_draw_count: u32,
+        stride: u32,
+    );
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_indexed_indirect_count(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        count_buffer: vk::Buffer,
+        count_offset: vk::DeviceSize,
+        max_draw_count: u32,
+        stride: u32,
+    );
+
+    fn dispatch(&self, command_buffer: vk::CommandBuffer, x: u32, y: u32, z: u32);
+
+    fn bind_pipeline(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        bind_point: vk::PipelineBindPoint,
+        pipeline: vk::Pipeline,
+    );
+
+    fn bind_descriptor_sets(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        bind_point: vk::PipelineBindPoint,
+        layout: vk::PipelineLayout,
+        first_set: u32,
+        descriptor_sets: &[vk::DescriptorSet],
+        dynamic_offsets: &[u32],
+    );
+
+    fn bind_vertex_buffers(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        first_binding: u32,
+        buffers: &[vk::Buffer],
+        offsets: &[vk::DeviceSize],
+    );
+
+    fn bind_index_buffer(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        index_type: vk::IndexType,
+    );
+}
+
+impl RecordCommands for Device {
+    fn draw(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        vertex_count: u32,
+        instance_count: u32,
+        first_vertex: u32,
+        first_instance: u32,
+    ) {
+        count_draw(instance_count);
+        unsafe {
+            self.cmd_draw(
+                command_buffer,
+                vertex_count,
+                instance_count,
+                first_vertex,
+                first_instance,
+            );
+        }
+    }
+
+    fn draw_indexed(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        index_count: u32,
+        instance_count: u32,
+        first_index: u32,
+        vertex_offset: i32,
+        first_instance: u32,
+    ) {
+        count_draw(instance_count);
+        unsafe {
+            self.cmd_draw_indexed(
+                command_buffer,
+                index_count,
+                instance_count,
+                first_index,
+                vertex_offset,
+                first_instance,
+            );
+        }
+    }
+
+    fn draw_indirect(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        draw_count: u32,
+        stride: u32,
+    ) {
+        count_indirect_draw();
+        unsafe {
+            self.cmd_draw_indirect(command_buffer, buffer, offset, draw_count, stride);
+        }
+    }
+
+    fn draw_indexed_indirect(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        draw_count: u32,
+        stride: u32,
+    ) {
+        count_indirect_draw();
+        unsafe {
+            self.cmd_draw_indexed_indirect(command_buffer, buffer, offset, draw_count, stride);
+        }
+    }
+
+    fn draw_indirect_count(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        count_buffer: vk::Buffer,
+        count_offset: vk::DeviceSize,
+        max_draw_count: u32,
+        stride: u32,
+    ) {
+        count_indirect_draw();
+        unsafe {
+            self.cmd_draw_indirect_count(
+                command_buffer,
+                buffer,
+                offset,
+                count_buffer,
+                count_offset,
+                max_draw_count,
+                stride,
+            );
+        }
+    }
+
+    fn draw_indexed_indirect_count(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        count_buffer: vk::Buffer,
+        count_offset: vk::DeviceSize,
+        max_draw_count: u32,
+        stride: u32,
+    ) {
+        count_indirect_draw();
+        unsafe {
+            self.cmd_draw_indexed_indirect_count(
+                command_buffer,
+                buffer,
+                offset,
+                count_buffer,
+                count_offset,
+                max_draw_count,
+                stride,
+            );
+        }
+    }
+
+    fn dispatch(&self, command_buffer: vk::CommandBuffer, x: u32, y: u32, z: u32) {
+        count(&COUNTERS.dispatches, 1);
+        unsafe {
+            self.cmd_dispatch(command_buffer, x, y, z);
+        }
+    }
+
+    fn bind_pipeline(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        bind_point: vk::PipelineBindPoint,
+        pipeline: vk::Pipeline,
+    ) {
+        count(&COUNTERS.pipeline_binds, 1);
+        unsafe {
+            self.cmd_bind_pipeline(command_buffer, bind_point, pipeline);
+        }
+    }
+
+    fn bind_descriptor_sets(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        bind_point: vk::PipelineBindPoint,
+        layout: vk::PipelineLayout,
+        first_set: u32,
+        descriptor_sets: &[vk::DescriptorSet],
+        dynamic_offsets: &[u32],
+    ) {
+        count(&COUNTERS.descriptor_binds, 1);
+        unsafe {
+            self.cmd_bind_descriptor_sets(
+                command_buffer,
+                bind_point,
+                layout,
+                first_set,
+                descriptor_sets,
+                dynamic_offsets,
+            );
+        }
+    }
+
+    fn bind_vertex_buffers(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        first_binding: u32,
+        buffers: &[vk::Buffer],
+        offsets: &[vk::DeviceSize],
+    ) {
+        count(&COUNTERS.buffer_binds, 1);
+        unsafe {
+            self.cmd_bind_vertex_buffers(command_buffer, first_binding, buffers, offsets);
+        }
+    }
+
+    fn bind_index_buffer(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        index_type: vk::IndexType,
+    ) {
+        count(&COUNTERS.buffer_binds, 1);
+        unsafe {
+            self.cmd_bind_index_buffer(command_buffer, buffer, offset, index_type);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PassStats {
+    pub name: String,
+    pub stats: CommandStats,
+}
+
+/// Commands recorded in one frame, in total and per pass.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    pub total: CommandStats,
+    pub passes: Vec<PassStats>,
+}
+
+/// Splits the command counts into frames and named passes by snapshotting the totals at each
+/// boundary. Passes recorded on several threads at once are attributed to whichever pass is
+/// open, so open passes around serial recording only.
+#[derive(Debug, Clone, Default)]
+pub struct FrameStatsRecorder {
+    frame_start: CommandStats,
+    open_pass: Option<(String, CommandStats)>,
+    passes: Vec<PassStats>,
+    last: FrameStats,
+}
+
+impl FrameStatsRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.frame_start = command_totals();
+        self.open_pass = None;
+        self.passes.clear();
+    }
+
+    /// Starts attributing commands to `name`, ending the open pass if there is one.
+    pub fn begin_pass(&mut self, name: &str) {
+        self.end_pass();
+        self.open_pass = Some((name.to_string(), command_totals()));
+    }
+
+    pub fn end_pass(&mut self) {
+        if let Some((name, start)) = self.open_pass.take() {
+            self.passes.push(PassStats {
+                name,
+                stats: command_totals() - start,
+            });
+        }
+    }
+
+    /// Ends the frame and returns its statistics, also kept as `last`.
+    pub fn end_frame(&mut self) -> &FrameStats {
+        self.end_pass();
+        self.last = FrameStats {
+            total: command_totals() - self.frame_start,
+            passes: std::mem::take(&mut self.passes),
+        };
+        &self.last
+    }
+
+    /// Statistics of the last completed frame.
+    pub fn last(&self) -> &FrameStats {
+        &self.last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The only test touching the shared counters, so nothing else moves them meanwhile.
+    #[test]
+    fn splits_counts_into_frames_and_passes() {
+        let mut recorder = FrameStatsRecorder::new();
+        recorder.begin_frame();
+
+        recorder.begin_pass("shadow");
+        count_draw(3);
+        count(&COUNTERS.pipeline_binds, 1);
+        recorder.begin_pass("main");
+        count_draw(1);
+        count_indirect_draw();
+        count(&COUNTERS.buffer_binds, 2);
+        recorder.end_pass();
+        count(&COUNTERS.dispatches, 1);
+
+        let frame = recorder.end_frame().clone();
+        assert_eq!(frame.passes.len(), 2);
+        assert_eq!(frame.passes[0].name, "shadow");
+        assert_eq!(
+            frame.passes[0].stats,
+            CommandStats {
+                draw_calls: 1,
+                instances: 3,
+                pipeline_binds: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(frame.passes[1].stats.indirect_draws, 1);
+        assert_eq!(frame.passes[1].stats.buffer_binds, 2);
+        assert_eq!(
+            frame.total,
+            CommandStats {
+                draw_calls: 2,
+                indirect_draws: 1,
+                instances: 4,
+                dispatches: 1,
+                pipeline_binds: 1,
+                descriptor_binds: 0,
+                buffer_binds: 2,
+            }
+        );
+        assert_eq!(recorder.last(), &frame);
+
+        recorder.begin_frame();
+        assert_eq!(recorder.end_frame().total, CommandStats::default());
+    }
+}
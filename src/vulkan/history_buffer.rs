@@ -0,0 +1,228 @@
+use anyhow::Result;
+use ash::vk;
+use glam::Mat4;
+
+use crate::vulkan::{StorageImage, VulkanDevice, VulkanPhysicalDevice};
+
+/// How far the camera may move between two frames before the history is treated as belonging
+/// to another shot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraCutThresholds {
+    /// World units.
+    pub max_translation: f32,
+    /// Radians between the two view directions.
+    pub max_rotation: f32,
+}
+
+impl Default for CameraCutThresholds {
+    fn default() -> Self {
+        Self {
+            max_translation: 2.0,
+            max_rotation: 30f32.to_radians(),
+        }
+    }
+}
+
+impl CameraCutThresholds {
+    /// Whether going from view matrix `previous` to `current` is a cut.
+    pub fn is_cut(&self, previous: &Mat4, current: &Mat4) -> bool {
+        let (previous, current) = (previous.inverse(), current.inverse());
+        let translation = previous
+            .w_axis
+            .truncate()
+            .distance(current.w_axis.truncate());
+        // Views look down -Z.
+        let (previous_forward, current_forward) = (
+            -previous.z_axis.truncate().normalize_or_zero(),
+            -current.z_axis.truncate().normalize_or_zero(),
+        );
+        let rotation = previous_forward
+            .dot(current_forward)
+            .clamp(-1.0, 1.0)
+            .acos();
+        translation > self.max_translation || rotation > self.max_rotation
+    }
+}
+
+/// Which image is current and how much history the other one holds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct HistoryState {
+    current: usize,
+    accumulated: u32,
+    last_view: Option<Mat4>,
+}
+
+impl HistoryState {
+    const NEW: Self = Self {
+        current: 0,
+        accumulated: 0,
+        last_view: None,
+    };
+
+    fn observe_camera(&mut self, thresholds: &CameraCutThresholds, view: Mat4) -> bool {
+        let cut = self
+            .last_view
+            .is_some_and(|previous| thresholds.is_cut(&previous, &view));
+        if cut {
+            self.accumulated = 0;
+        }
+        self.last_view = Some(view);
+        cut
+    }
+
+    fn advance(&mut self) {
+        self.current = 1 - self.current;
+        self.accumulated = self.accumulated.saturating_add(1);
+    }
+}
+
+/// Double-buffered image for temporal effects (denoising, TAA, SSR, auto-exposure): each frame
+/// writes `current` while reading last frame's result from `previous`, then `advance` swaps
+/// them. Tracks whether `previous` holds usable history, which it doesn't after creation, a
+/// resize, `invalidate` or a camera cut seen by `observe_camera`; effects then ignore it and
+/// start accumulating from scratch.
+///
+/// Descriptor sets can't follow the swap, so effects allocate one per `current_index` and
+/// bind the one matching the frame.
+pub struct HistoryBuffer {
+    images: [StorageImage; 2],
+    format: vk::Format,
+    state: HistoryState,
+    pub camera_cut: CameraCutThresholds,
+}
+
+impl HistoryBuffer {
+    /// See `StorageImage::new` for the format requirements.
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        extent: vk::Extent2D,
+        format: vk::Format,
+    ) -> Result<Self> {
+        Ok(Self {
+            images: [
+                StorageImage::new(device, physical_device, extent, format)?,
+                StorageImage::new(device, physical_device, extent, format)?,
+            ],
+            format,
+            state: HistoryState::NEW,
+            camera_cut: CameraCutThresholds::default(),
+        })
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.images[0].extent()
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    /// Recreates both images at `extent`, dropping the history. Returns whether the images
+    /// changed, in which case descriptor sets referring to them must be written again. The
+    /// old images must not be in use by the GPU anymore.
+    pub fn resize(
+        &mut self,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        extent: vk::Extent2D,
+    ) -> Result<bool> {
+        if extent == self.extent() {
+            return Ok(false);
+        }
+        *self = Self {
+            camera_cut: self.camera_cut,
+            ..Self::new(device, physical_device, extent, self.format)?
+        };
+        Ok(true)
+    }
+
+    /// Index of the image written this frame.
+    pub fn current_index(&self) -> usize {
+        self.state.current
+    }
+
+    pub fn image(&self, index: usize) -> &StorageImage {
+        &self.images[index]
+    }
+
+    pub fn current(&self) -> &StorageImage {
+        &self.images[self.state.current]
+    }
+
+    pub fn previous(&self) -> &StorageImage {
+        &self.images[1 - self.state.current]
+    }
+
+    /// Both images mutably, current first, to record their barriers.
+    pub fn split_mut(&mut self) -> (&mut StorageImage, &mut StorageImage) {
+        let [first, second] = &mut self.images;
+        match self.state.current {
+            0 => (first, second),
+            _ => (second, first),
+        }
+    }
+
+    /// Whether `previous` holds history that can be blended in.
+    pub fn is_valid(&self) -> bool {
+        self.state.accumulated > 0
+    }
+
+    /// Frames accumulated since the history was last dropped, saturating. Effects that
+    /// average, like progressive accumulation, weigh the new frame by `1 / (n + 1)`.
+    pub fn accumulated_frames(&self) -> u32 {
+        self.state.accumulated
+    }
+
+    /// Drops the history, e.g. after a teleport or a change of settings the history was
+    /// computed with.
+    pub fn invalidate(&mut self) {
+        self.state.accumulated = 0;
+    }
+
+    /// Compares `view` with the one of the previous frame and drops the history on a camera
+    /// cut. Returns whether there was one.
+    pub fn observe_camera(&mut self, view: Mat4) -> bool {
+        self.state.observe_camera(&self.camera_cut, view)
+    }
+
+    /// Ends the frame: what was written becomes `previous` and holds one more frame of history.
+    pub fn advance(&mut self) {
+        self.state.advance();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    #[test]
+    fn detects_camera_cuts() {
+        let thresholds = CameraCutThresholds::default();
+        let view = |eye: Vec3, target: Vec3| Mat4::look_at_rh(eye, target, Vec3::Y);
+        let start = view(Vec3::ZERO, Vec3::NEG_Z);
+
+        assert!(!thresholds.is_cut(&start, &view(Vec3::X * 0.5, Vec3::NEG_Z)));
+        assert!(!thresholds.is_cut(&start, &view(Vec3::ZERO, Vec3::new(0.2, 0.0, -1.0))));
+        assert!(thresholds.is_cut(&start, &view(Vec3::X * 5.0, Vec3::NEG_Z)));
+        assert!(thresholds.is_cut(&start, &view(Vec3::ZERO, Vec3::X)));
+    }
+
+    #[test]
+    fn history_is_valid_after_a_frame_until_a_cut() {
+        let thresholds = CameraCutThresholds::default();
+        let mut state = HistoryState::NEW;
+        assert!(!state.observe_camera(&thresholds, Mat4::IDENTITY));
+        assert_eq!(state.accumulated, 0);
+
+        state.advance();
+        state.advance();
+        assert_eq!((state.current, state.accumulated), (0, 2));
+
+        assert!(!state.observe_camera(&thresholds, Mat4::IDENTITY));
+        assert_eq!(state.accumulated, 2);
+        assert!(state.observe_camera(&thresholds, Mat4::from_translation(Vec3::X * 10.0)));
+        assert_eq!(state.accumulated, 0);
+    }
+}
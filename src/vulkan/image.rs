@@ -0,0 +1,370 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::sync::Arc;
+
+use crate::vulkan::{VulkanDevice, VulkanPhysicalDevice};
+
+/// Creation parameters for images that need more than a single 2D subresource.
+#[derive(Debug, Clone, Copy)]
+pub struct VulkanImageInfo {
+    pub extent: vk::Extent2D,
+    /// Greater than 1 for 3D images.
+    pub depth: u32,
+    pub format: vk::Format,
+    pub usage: vk::ImageUsageFlags,
+    pub aspect_mask: vk::ImageAspectFlags,
+    pub mip_levels: u32,
+    pub array_layers: u32,
+    pub cube_compatible: bool,
+    /// Views a single-layer image as `TYPE_2D_ARRAY` rather than `TYPE_2D`.
+    pub force_array_view: bool,
+}
+
+impl VulkanImageInfo {
+    pub fn new(
+        extent: vk::Extent2D,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> Self {
+        Self {
+            extent,
+            depth: 1,
+            format,
+            usage,
+            aspect_mask,
+            mip_levels: 1,
+            array_layers: 1,
+            cube_compatible: false,
+            force_array_view: false,
+        }
+    }
+
+    /// Six-layer cube map with a square `size`.
+    pub fn cube(size: u32, format: vk::Format, usage: vk::ImageUsageFlags) -> Self {
+        Self {
+            array_layers: 6,
+            cube_compatible: true,
+            ..Self::new(
+                vk::Extent2D {
+                    width: size,
+                    height: size,
+                },
+                format,
+                usage,
+                vk::ImageAspectFlags::COLOR,
+            )
+        }
+    }
+
+    /// Single-layer 3D color image, e.g. a color grading LUT.
+    pub fn volume(extent: vk::Extent3D, format: vk::Format, usage: vk::ImageUsageFlags) -> Self {
+        Self {
+            depth: extent.depth.max(1),
+            ..Self::new(
+                vk::Extent2D {
+                    width: extent.width,
+                    height: extent.height,
+                },
+                format,
+                usage,
+                vk::ImageAspectFlags::COLOR,
+            )
+        }
+    }
+
+    /// 2D array with `layers` layers of `extent`, e.g. terrain splats or a decal atlas. Views
+    /// as `TYPE_2D_ARRAY` even with a single layer, so shaders can always use `sampler2DArray`.
+    pub fn array(
+        extent: vk::Extent2D,
+        layers: u32,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+    ) -> Self {
+        Self {
+            array_layers: layers.max(1),
+            force_array_view: true,
+            ..Self::new(extent, format, usage, vk::ImageAspectFlags::COLOR)
+        }
+    }
+
+    pub fn with_mip_levels(mut self, mip_levels: u32) -> Self {
+        self.mip_levels = mip_levels.max(1);
+        self
+    }
+
+    pub fn with_array_layers(mut self, array_layers: u32) -> Self {
+        self.array_layers = array_layers.max(1);
+        self
+    }
+
+    fn image_type(&self) -> vk::ImageType {
+        if self.depth > 1 {
+            vk::ImageType::TYPE_3D
+        } else {
+            vk::ImageType::TYPE_2D
+        }
+    }
+
+    fn view_type(&self) -> vk::ImageViewType {
+        if self.depth > 1 {
+            vk::ImageViewType::TYPE_3D
+        } else if self.cube_compatible && self.array_layers == 6 {
+            vk::ImageViewType::CUBE
+        } else if self.array_layers > 1 || self.force_array_view {
+            vk::ImageViewType::TYPE_2D_ARRAY
+        } else {
+            vk::ImageViewType::TYPE_2D
+        }
+    }
+}
+
+pub struct VulkanImage {
+    pub image: vk::Image,
+    pub memory: vk::DeviceMemory,
+    pub view: vk::ImageView,
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+    pub depth: u32,
+    pub aspect_mask: vk::ImageAspectFlags,
+    pub mip_levels: u32,
+    pub array_layers: u32,
+    pub device: Arc<Device>,
+}
+
+impl VulkanImage {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> Result<Self> {
+        Self::from_info(
+            device,
+            physical_device,
+            &VulkanImageInfo::new(extent, format, usage, aspect_mask),
+        )
+    }
+
+    pub fn from_info(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        info: &VulkanImageInfo,
+    ) -> Result<Self> {
+        let image = Self::create_unbound(device, info)?;
+
+        let requirements = unsafe { device.device.get_image_memory_requirements(image) };
+
+        let memory_type_index = physical_device.find_memory_type(
+            requirements.memory_type_bits,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let alloc_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type_index);
+
+        let memory = unsafe {
+            device
+                .device
+                .allocate_memory(&alloc_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to allocate image memory: {}", e))?
+        };
+
+        unsafe {
+            device
+                .device
+                .bind_image_memory(image, memory, 0)
+                .map_err(|e| anyhow::anyhow!("Failed to bind image memory: {}", e))?
+        };
+
+        let mut vulkan_image = Self::from_bound(device, image, info)?;
+        vulkan_image.memory = memory;
+        Ok(vulkan_image)
+    }
+
+    /// Creates an image for `info` without memory, for callers that bind it themselves, e.g.
+    /// to alias memory between images. Pass it to `from_bound` once bound.
+    pub fn create_unbound(device: &VulkanDevice, info: &VulkanImageInfo) -> Result<vk::Image> {
+        let flags = if info.cube_compatible {
+            vk::ImageCreateFlags::CUBE_COMPATIBLE
+        } else {
+            vk::ImageCreateFlags::empty()
+        };
+
+        let image_info = vk::ImageCreateInfo::default()
+            .flags(flags)
+            .image_type(info.image_type())
+            .format(info.format)
+            .extent(vk::Extent3D {
+                width: info.extent.width,
+                height: info.extent.height,
+                depth: info.depth,
+            })
+            .mip_levels(info.mip_levels)
+            .array_layers(info.array_layers)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(info.usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+
+        unsafe {
+            device
+                .device
+                .create_image(&image_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create image: {}", e))
+        }
+    }
+
+    /// Wraps an image from `create_unbound` that is already bound to memory owned elsewhere
+    /// and creates its view. Dropping it destroys the image but leaves the memory alone.
+    pub fn from_bound(
+        device: &VulkanDevice,
+        image: vk::Image,
+        info: &VulkanImageInfo,
+    ) -> Result<Self> {
+        let mut vulkan_image = Self {
+            image,
+            memory: vk::DeviceMemory::null(),
+            view: vk::ImageView::null(),
+            format: info.format,
+            extent: info.extent,
+            depth: info.depth,
+            aspect_mask: info.aspect_mask,
+            mip_levels: info.mip_levels,
+            array_layers: info.array_layers,
+            device: device.device.clone(),
+        };
+
+        vulkan_image.view =
+            vulkan_image.create_view(info.view_type(), vulkan_image.subresource_range())?;
+
+        Ok(vulkan_image)
+    }
+
+    /// Creates an additional view of this image. The caller owns the returned view and must
+    /// destroy it before the image is dropped.
+    pub fn create_view(
+        &self,
+        view_type: vk::ImageViewType,
+        subresource_range: vk::ImageSubresourceRange,
+    ) -> Result<vk::ImageView> {
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(self.image)
+            .view_type(view_type)
+            .format(self.format)
+            .subresource_range(subresource_range);
+
+        let view = unsafe {
+            self.device
+                .create_image_view(&view_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create image view: {}", e))?
+        };
+
+        Ok(view)
+    }
+
+    /// Creates a `TYPE_2D` view of one array layer, e.g. to render into a shadow cascade or
+    /// show a layer in a debug view. The caller owns the returned view, as with `create_view`.
+    pub fn create_layer_view(&self, layer: u32) -> Result<vk::ImageView> {
+        if layer >= self.array_layers {
+            return Err(anyhow::anyhow!(
+                "Layer {} out of range for an image with {} layers",
+                layer,
+                self.array_layers
+            ));
+        }
+        self.create_view(
+            vk::ImageViewType::TYPE_2D,
+            vk::ImageSubresourceRange {
+                base_array_layer: layer,
+                layer_count: 1,
+                ..self.subresource_range()
+            },
+        )
+    }
+
+    pub fn new_depth(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        extent: vk::Extent2D,
+        format: vk::Format,
+    ) -> Result<Self> {
+        Self::new(
+            device,
+            physical_device,
+            extent,
+            format,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::ImageAspectFlags::DEPTH,
+        )
+    }
+
+    pub fn subresource_range(&self) -> vk::ImageSubresourceRange {
+        vk::ImageSubresourceRange {
+            aspect_mask: self.aspect_mask,
+            base_mip_level: 0,
+            level_count: self.mip_levels,
+            base_array_layer: 0,
+            layer_count: self.array_layers,
+        }
+    }
+
+    /// Records a pipeline barrier transitioning `subresource_range` of this image.
+    #[allow(clippy::too_many_arguments)]
+    pub fn cmd_transition(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        subresource_range: vk::ImageSubresourceRange,
+        old_layout: vk::ImageLayout,
+        new_layout: vk::ImageLayout,
+        src_stage: vk::PipelineStageFlags,
+        src_access: vk::AccessFlags,
+        dst_stage: vk::PipelineStageFlags,
+        dst_access: vk::AccessFlags,
+    ) {
+        let barrier = vk::ImageMemoryBarrier::default()
+            .old_layout(old_layout)
+            .new_layout(new_layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(self.image)
+            .subresource_range(subresource_range)
+            .src_access_mask(src_access)
+            .dst_access_mask(dst_access);
+
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                std::slice::from_ref(&barrier),
+            );
+        }
+    }
+}
+
+impl Drop for VulkanImage {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_image_view(self.view, None);
+            self.device.destroy_image(self.image, None);
+            self.device.free_memory(self.memory, None);
+        }
+    }
+}
+
+pub fn has_stencil_component(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::D32_SFLOAT_S8_UINT
+            | vk::Format::D24_UNORM_S8_UINT
+            | vk::Format::D16_UNORM_S8_UINT
+    )
+}
@@ -0,0 +1,635 @@
+use anyhow::{Result, bail};
+use ash::vk;
+
+use crate::vulkan::{VulkanBuffer, VulkanImage};
+
+/// Layout of an image together with the pipeline stage and access that last used, or will next
+/// use, it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageAccess {
+    pub layout: vk::ImageLayout,
+    pub stage: vk::PipelineStageFlags,
+    pub access: vk::AccessFlags,
+}
+
+impl ImageAccess {
+    /// Nothing to wait for, and contents that may be discarded.
+    pub const UNDEFINED: Self = Self::new(
+        vk::ImageLayout::UNDEFINED,
+        vk::PipelineStageFlags::TOP_OF_PIPE,
+        vk::AccessFlags::empty(),
+    );
+
+    pub const COLOR_ATTACHMENT: Self = Self::new(
+        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+    );
+
+    pub const PRESENT: Self = Self::new(
+        vk::ImageLayout::PRESENT_SRC_KHR,
+        vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+        vk::AccessFlags::empty(),
+    );
+
+    pub const fn new(
+        layout: vk::ImageLayout,
+        stage: vk::PipelineStageFlags,
+        access: vk::AccessFlags,
+    ) -> Self {
+        Self {
+            layout,
+            stage,
+            access,
+        }
+    }
+
+    /// Sampled in `stage` from `SHADER_READ_ONLY_OPTIMAL`.
+    pub const fn sampled(stage: vk::PipelineStageFlags) -> Self {
+        Self::new(
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            stage,
+            vk::AccessFlags::SHADER_READ,
+        )
+    }
+
+    /// Whether moving to `next` needs a barrier: layout changes, any hazard involving a write
+    /// and reads in stages the last barrier didn't cover do. Repeated reads don't.
+    pub fn needs_barrier(&self, next: &Self) -> bool {
+        let writes = vk::AccessFlags::SHADER_WRITE
+            | vk::AccessFlags::TRANSFER_WRITE
+            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+            | vk::AccessFlags::HOST_WRITE;
+        self.layout != next.layout
+            || self.access.intersects(writes)
+            || next.access.intersects(writes)
+            || !self.stage.contains(next.stage)
+    }
+}
+
+/// Mip level, array layers and texel box of an image taking part in a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageRegion {
+    pub mip_level: u32,
+    pub base_layer: u32,
+    pub layer_count: u32,
+    pub offset: vk::Offset3D,
+    pub extent: vk::Extent3D,
+}
+
+impl ImageRegion {
+    /// Every layer of mip level 0.
+    pub fn whole(image: &VulkanImage) -> Self {
+        Self::mip(image, 0)
+    }
+
+    /// Every layer of `mip_level`, with the extent halved per level.
+    pub fn mip(image: &VulkanImage, mip_level: u32) -> Self {
+        Self {
+            mip_level,
+            base_layer: 0,
+            layer_count: image.array_layers,
+            offset: vk::Offset3D::default(),
+            extent: mip_extent(image, mip_level),
+        }
+    }
+
+    pub fn with_layers(mut self, base_layer: u32, layer_count: u32) -> Self {
+        self.base_layer = base_layer;
+        self.layer_count = layer_count;
+        self
+    }
+
+    /// Restricts the region to a rectangle of a 2D image.
+    pub fn with_rect(mut self, offset: vk::Offset2D, extent: vk::Extent2D) -> Self {
+        self.offset = vk::Offset3D {
+            x: offset.x,
+            y: offset.y,
+            z: 0,
+        };
+        self.extent = vk::Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        };
+        self
+    }
+
+    fn end(&self) -> vk::Offset3D {
+        vk::Offset3D {
+            x: self.offset.x + self.extent.width as i32,
+            y: self.offset.y + self.extent.height as i32,
+            z: self.offset.z + self.extent.depth as i32,
+        }
+    }
+
+    fn subresource_layers(&self, aspect_mask: vk::ImageAspectFlags) -> vk::ImageSubresourceLayers {
+        vk::ImageSubresourceLayers {
+            aspect_mask,
+            mip_level: self.mip_level,
+            base_array_layer: self.base_layer,
+            layer_count: self.layer_count,
+        }
+    }
+
+    fn subresource_range(&self, aspect_mask: vk::ImageAspectFlags) -> vk::ImageSubresourceRange {
+        vk::ImageSubresourceRange {
+            aspect_mask,
+            base_mip_level: self.mip_level,
+            level_count: 1,
+            base_array_layer: self.base_layer,
+            layer_count: self.layer_count,
+        }
+    }
+
+    fn validate(&self, image: &VulkanImage) -> Result<()> {
+        if self.mip_level >= image.mip_levels {
+            bail!(
+                "Mip level {} out of range, image has {}",
+                self.mip_level,
+                image.mip_levels
+            );
+        }
+        if self.layer_count == 0 || self.base_layer + self.layer_count > image.array_layers {
+            bail!(
+                "Layers {}..{} out of range, image has {}",
+                self.base_layer,
+                self.base_layer + self.layer_count,
+                image.array_layers
+            );
+        }
+
+        let size = mip_extent(image, self.mip_level);
+        let end = self.end();
+        if self.extent.width == 0 || self.extent.height == 0 || self.extent.depth == 0 {
+            bail!("Empty image region {:?}", self.extent);
+        }
+        if self.offset.x < 0
+            || self.offset.y < 0
+            || self.offset.z < 0
+            || end.x > size.width as i32
+            || end.y > size.height as i32
+            || end.z > size.depth as i32
+        {
+            bail!(
+                "Image region {:?}..{:?} exceeds mip {} of {}x{}x{}",
+                self.offset,
+                end,
+                self.mip_level,
+                size.width,
+                size.height,
+                size.depth
+            );
+        }
+        Ok(())
+    }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        self.mip_level == other.mip_level
+            && self.base_layer < other.base_layer + other.layer_count
+            && other.base_layer < self.base_layer + self.layer_count
+    }
+}
+
+fn mip_extent(image: &VulkanImage, mip_level: u32) -> vk::Extent3D {
+    vk::Extent3D {
+        width: (image.extent.width >> mip_level).max(1),
+        height: (image.extent.height >> mip_level).max(1),
+        depth: (image.depth >> mip_level).max(1),
+    }
+}
+
+/// One side of a transfer: the region used, the access the transfer waits for and the access
+/// the image is handed to afterwards. Whatever layout the image is in, the helpers below move
+/// the region to the transfer layout and then to `after.layout`.
+#[derive(Clone, Copy)]
+pub struct TransferImage<'a> {
+    pub image: &'a VulkanImage,
+    pub region: ImageRegion,
+    /// Layout and last access before the transfer. `ImageAccess::UNDEFINED` discards the
+    /// contents of the region, which is what destinations that are fully overwritten want.
+    pub before: ImageAccess,
+    /// Layout and first access after the transfer.
+    pub after: ImageAccess,
+}
+
+impl<'a> TransferImage<'a> {
+    /// All layers of mip level 0.
+    pub fn new(image: &'a VulkanImage, before: ImageAccess, after: ImageAccess) -> Self {
+        Self {
+            image,
+            region: ImageRegion::whole(image),
+            before,
+            after,
+        }
+    }
+
+    pub fn with_region(mut self, region: ImageRegion) -> Self {
+        self.region = region;
+        self
+    }
+
+    fn validate(&self) -> Result<()> {
+        self.region.validate(self.image)?;
+        if self.after.layout == vk::ImageLayout::UNDEFINED {
+            bail!("An image can't be moved to the UNDEFINED layout after a transfer");
+        }
+        Ok(())
+    }
+
+    fn cmd_begin(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        layout: vk::ImageLayout,
+        access: vk::AccessFlags,
+    ) {
+        self.image.cmd_transition(
+            command_buffer,
+            self.region.subresource_range(self.image.aspect_mask),
+            self.before.layout,
+            layout,
+            self.before.stage,
+            self.before.access,
+            vk::PipelineStageFlags::TRANSFER,
+            access,
+        );
+    }
+
+    fn cmd_end(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        layout: vk::ImageLayout,
+        access: vk::AccessFlags,
+    ) {
+        self.image.cmd_transition(
+            command_buffer,
+            self.region.subresource_range(self.image.aspect_mask),
+            layout,
+            self.after.layout,
+            vk::PipelineStageFlags::TRANSFER,
+            access,
+            self.after.stage,
+            self.after.access,
+        );
+    }
+
+    fn subresource_layers(&self) -> vk::ImageSubresourceLayers {
+        self.region.subresource_layers(self.image.aspect_mask)
+    }
+}
+
+/// Size in bytes of one texel of uncompressed `format`, or `None` for formats this helper
+/// doesn't know (block-compressed, multi-planar, packed depth-stencil).
+pub fn format_texel_size(format: vk::Format) -> Option<u32> {
+    let size = match format {
+        vk::Format::R8_UNORM
+        | vk::Format::R8_SNORM
+        | vk::Format::R8_UINT
+        | vk::Format::R8_SINT
+        | vk::Format::R8_SRGB
+        | vk::Format::S8_UINT => 1,
+        vk::Format::R8G8_UNORM
+        | vk::Format::R8G8_SNORM
+        | vk::Format::R8G8_UINT
+        | vk::Format::R8G8_SINT
+        | vk::Format::R16_UNORM
+        | vk::Format::R16_SFLOAT
+        | vk::Format::R16_UINT
+        | vk::Format::R16_SINT
+        | vk::Format::D16_UNORM => 2,
+        vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SNORM
+        | vk::Format::R8G8B8A8_UINT
+        | vk::Format::R8G8B8A8_SINT
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::A2B10G10R10_UNORM_PACK32
+        | vk::Format::B10G11R11_UFLOAT_PACK32
+        | vk::Format::R16G16_UNORM
+        | vk::Format::R16G16_SFLOAT
+        | vk::Format::R32_SFLOAT
+        | vk::Format::R32_UINT
+        | vk::Format::R32_SINT
+        | vk::Format::D32_SFLOAT
+        | vk::Format::X8_D24_UNORM_PACK32 => 4,
+        vk::Format::R16G16B16A16_UNORM
+        | vk::Format::R16G16B16A16_SFLOAT
+        | vk::Format::R16G16B16A16_UINT
+        | vk::Format::R32G32_SFLOAT
+        | vk::Format::R32G32_UINT => 8,
+        vk::Format::R32G32B32_SFLOAT => 12,
+        vk::Format::R32G32B32A32_SFLOAT | vk::Format::R32G32B32A32_UINT => 16,
+        _ => return None,
+    };
+    Some(size)
+}
+
+fn region_bytes(region: &ImageRegion, format: vk::Format) -> Option<vk::DeviceSize> {
+    let texels = region.extent.width as vk::DeviceSize
+        * region.extent.height as vk::DeviceSize
+        * region.extent.depth as vk::DeviceSize
+        * region.layer_count as vk::DeviceSize;
+    format_texel_size(format).map(|size| texels * size as vk::DeviceSize)
+}
+
+fn validate_pair(source: &TransferImage, destination: &TransferImage) -> Result<()> {
+    source.validate()?;
+    destination.validate()?;
+    if source.image.image == destination.image.image && source.region.overlaps(&destination.region)
+    {
+        bail!("Source and destination regions of the same image overlap");
+    }
+    if source.image.aspect_mask != destination.image.aspect_mask {
+        bail!(
+            "Can't transfer between {:?} and {:?} aspects",
+            source.image.aspect_mask,
+            destination.image.aspect_mask
+        );
+    }
+    if source.region.layer_count != destination.region.layer_count {
+        bail!(
+            "Source has {} layers but destination has {}",
+            source.region.layer_count,
+            destination.region.layer_count
+        );
+    }
+    Ok(())
+}
+
+/// Records a scaled, filtered blit from `source` to `destination`, converting between formats,
+/// with the barriers around it. Both formats need the `BLIT_SRC` / `BLIT_DST` format features;
+/// depth and stencil images only blit between identical formats with `NEAREST` filtering.
+pub fn cmd_blit_image(
+    command_buffer: vk::CommandBuffer,
+    source: &TransferImage,
+    destination: &TransferImage,
+    filter: vk::Filter,
+) -> Result<()> {
+    validate_pair(source, destination)?;
+    if !source
+        .image
+        .aspect_mask
+        .contains(vk::ImageAspectFlags::COLOR)
+        && (source.image.format != destination.image.format || filter != vk::Filter::NEAREST)
+    {
+        bail!("Depth and stencil blits need identical formats and NEAREST filtering");
+    }
+
+    let blit = vk::ImageBlit::default()
+        .src_subresource(source.subresource_layers())
+        .src_offsets([source.region.offset, source.region.end()])
+        .dst_subresource(destination.subresource_layers())
+        .dst_offsets([destination.region.offset, destination.region.end()]);
+
+    source.cmd_begin(
+        command_buffer,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        vk::AccessFlags::TRANSFER_READ,
+    );
+    destination.cmd_begin(
+        command_buffer,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::AccessFlags::TRANSFER_WRITE,
+    );
+
+    unsafe {
+        source.image.device.cmd_blit_image(
+            command_buffer,
+            source.image.image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            destination.image.image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            std::slice::from_ref(&blit),
+            filter,
+        );
+    }
+
+    source.cmd_end(
+        command_buffer,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        vk::AccessFlags::TRANSFER_READ,
+    );
+    destination.cmd_end(
+        command_buffer,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::AccessFlags::TRANSFER_WRITE,
+    );
+    Ok(())
+}
+
+/// Records an unscaled copy of `source.region` into `destination`, with the barriers around it.
+/// Only the destination offset is used; both formats need the same texel size.
+pub fn cmd_copy_image(
+    command_buffer: vk::CommandBuffer,
+    source: &TransferImage,
+    destination: &TransferImage,
+) -> Result<()> {
+    let destination = TransferImage {
+        region: ImageRegion {
+            extent: source.region.extent,
+            ..destination.region
+        },
+        ..*destination
+    };
+    validate_pair(source, &destination)?;
+
+    let (source_format, destination_format) = (source.image.format, destination.image.format);
+    let compatible = match (
+        format_texel_size(source_format),
+        format_texel_size(destination_format),
+    ) {
+        (Some(a), Some(b)) => a == b,
+        _ => source_format == destination_format,
+    };
+    if !compatible {
+        bail!(
+            "Can't copy between {:?} and {:?}, texel sizes differ",
+            source_format,
+            destination_format
+        );
+    }
+
+    let copy = vk::ImageCopy::default()
+        .src_subresource(source.subresource_layers())
+        .src_offset(source.region.offset)
+        .dst_subresource(destination.subresource_layers())
+        .dst_offset(destination.region.offset)
+        .extent(source.region.extent);
+
+    source.cmd_begin(
+        command_buffer,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        vk::AccessFlags::TRANSFER_READ,
+    );
+    destination.cmd_begin(
+        command_buffer,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::AccessFlags::TRANSFER_WRITE,
+    );
+
+    unsafe {
+        source.image.device.cmd_copy_image(
+            command_buffer,
+            source.image.image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            destination.image.image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            std::slice::from_ref(&copy),
+        );
+    }
+
+    source.cmd_end(
+        command_buffer,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        vk::AccessFlags::TRANSFER_READ,
+    );
+    destination.cmd_end(
+        command_buffer,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::AccessFlags::TRANSFER_WRITE,
+    );
+    Ok(())
+}
+
+fn validate_buffer_range(
+    image: &TransferImage,
+    buffer: &VulkanBuffer,
+    offset: vk::DeviceSize,
+) -> Result<()> {
+    image.validate()?;
+    if let Some(size) = region_bytes(&image.region, image.image.format)
+        && offset + size > buffer.size
+    {
+        bail!(
+            "Buffer holds {} bytes, need {} from offset {}",
+            buffer.size,
+            size,
+            offset
+        );
+    }
+    Ok(())
+}
+
+fn buffer_image_copy(image: &TransferImage, offset: vk::DeviceSize) -> vk::BufferImageCopy {
+    vk::BufferImageCopy::default()
+        .buffer_offset(offset)
+        .image_subresource(image.subresource_layers())
+        .image_offset(image.region.offset)
+        .image_extent(image.region.extent)
+}
+
+/// Records an upload of tightly packed texels from `buffer` at `offset` into
+/// `destination.region`, with the barriers around it. Writes to `buffer` before this must
+/// already be visible to transfers, e.g. host writes before the submit.
+pub fn cmd_copy_buffer_to_image(
+    command_buffer: vk::CommandBuffer,
+    buffer: &VulkanBuffer,
+    offset: vk::DeviceSize,
+    destination: &TransferImage,
+) -> Result<()> {
+    validate_buffer_range(destination, buffer, offset)?;
+
+    destination.cmd_begin(
+        command_buffer,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::AccessFlags::TRANSFER_WRITE,
+    );
+    unsafe {
+        destination.image.device.cmd_copy_buffer_to_image(
+            command_buffer,
+            buffer.buffer,
+            destination.image.image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            std::slice::from_ref(&buffer_image_copy(destination, offset)),
+        );
+    }
+    destination.cmd_end(
+        command_buffer,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::AccessFlags::TRANSFER_WRITE,
+    );
+    Ok(())
+}
+
+/// Records a readback of `source.region` into `buffer` at `offset` as tightly packed texels,
+/// with the image barriers around it. For host reads, follow with
+/// `buffer.cmd_barrier(.., TRANSFER, TRANSFER_WRITE, HOST, HOST_READ)`.
+pub fn cmd_copy_image_to_buffer(
+    command_buffer: vk::CommandBuffer,
+    source: &TransferImage,
+    buffer: &VulkanBuffer,
+    offset: vk::DeviceSize,
+) -> Result<()> {
+    validate_buffer_range(source, buffer, offset)?;
+
+    source.cmd_begin(
+        command_buffer,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        vk::AccessFlags::TRANSFER_READ,
+    );
+    unsafe {
+        source.image.device.cmd_copy_image_to_buffer(
+            command_buffer,
+            source.image.image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            buffer.buffer,
+            std::slice::from_ref(&buffer_image_copy(source, offset)),
+        );
+    }
+    source.cmd_end(
+        command_buffer,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        vk::AccessFlags::TRANSFER_READ,
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(mip_level: u32, base_layer: u32, layer_count: u32) -> ImageRegion {
+        ImageRegion {
+            mip_level,
+            base_layer,
+            layer_count,
+            offset: vk::Offset3D::default(),
+            extent: vk::Extent3D {
+                width: 4,
+                height: 4,
+                depth: 1,
+            },
+        }
+    }
+
+    #[test]
+    fn regions_overlap_only_on_shared_mips_and_layers() {
+        assert!(region(0, 0, 6).overlaps(&region(0, 5, 1)));
+        assert!(!region(0, 0, 5).overlaps(&region(0, 5, 1)));
+        assert!(!region(0, 0, 6).overlaps(&region(1, 0, 6)));
+    }
+
+    #[test]
+    fn buffer_sizes_follow_region_and_format() {
+        let region = region(0, 0, 2).with_rect(
+            vk::Offset2D { x: 8, y: 8 },
+            vk::Extent2D {
+                width: 16,
+                height: 8,
+            },
+        );
+
+        assert_eq!(region.end(), vk::Offset3D { x: 24, y: 16, z: 1 });
+        assert_eq!(
+            region_bytes(&region, vk::Format::R8G8B8A8_UNORM),
+            Some(16 * 8 * 2 * 4)
+        );
+        assert_eq!(
+            region_bytes(&region, vk::Format::R16G16B16A16_SFLOAT),
+            Some(16 * 8 * 2 * 8)
+        );
+        assert_eq!(region_bytes(&region, vk::Format::BC7_UNORM_BLOCK), None);
+    }
+}
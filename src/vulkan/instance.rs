@@ -1,14 +1,29 @@
 use anyhow::Result;
 use ash::{Entry, Instance, vk};
-use std::ffi::{CStr, CString};
+use std::ffi::CString;
+
+use crate::vulkan::{ValidationOptions, VulkanDebugMessenger};
 
 pub struct VulkanInstance {
     pub entry: Entry,
     pub instance: Instance,
+    /// Present in debug builds, where the validation layer is enabled.
+    pub debug_messenger: Option<VulkanDebugMessenger>,
+    /// False for instances wrapped with `from_raw`, which are left to their creator.
+    owned: bool,
 }
 
 impl VulkanInstance {
     pub fn new(window_extensions: &[*const i8]) -> Result<Self> {
+        Self::with_validation(window_extensions, ValidationOptions::default())
+    }
+
+    /// Like `new`, with extra validation layer features. `validation` is ignored in release
+    /// builds.
+    pub fn with_validation(
+        window_extensions: &[*const i8],
+        validation: ValidationOptions,
+    ) -> Result<Self> {
         let entry = unsafe { Entry::load()? };
 
         let app_name = CString::new("Vulkan Experiments")?;
@@ -29,24 +44,64 @@ impl VulkanInstance {
         }
 
         let layer_names = if cfg!(debug_assertions) {
-            vec![CStr::from_bytes_with_nul(b"VK_LAYER_KHRONOS_validation\0")?.as_ptr()]
+            vec![c"VK_LAYER_KHRONOS_validation".as_ptr()]
         } else {
             vec![]
         };
 
-        let create_info = vk::InstanceCreateInfo::default()
+        let validation_features = validation.enabled_features();
+        let mut validation_features_info =
+            vk::ValidationFeaturesEXT::default().enabled_validation_features(&validation_features);
+
+        let mut create_info = vk::InstanceCreateInfo::default()
             .application_info(&app_info)
             .enabled_extension_names(&extensions)
             .enabled_layer_names(&layer_names);
+        if cfg!(debug_assertions) && !validation_features.is_empty() {
+            create_info = create_info.push_next(&mut validation_features_info);
+        }
 
         let instance = unsafe { entry.create_instance(&create_info, None)? };
 
-        Ok(Self { entry, instance })
+        let debug_messenger = if cfg!(debug_assertions) {
+            Some(VulkanDebugMessenger::new(&entry, &instance)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            entry,
+            instance,
+            debug_messenger,
+            owned: true,
+        })
+    }
+
+    /// Wraps an instance created by another engine. No debug messenger is installed; the host
+    /// owns validation.
+    ///
+    /// # Safety
+    /// `instance` must be a valid instance created through `entry` and must outlive the
+    /// returned value; when `owned` is true it is destroyed on drop.
+    pub unsafe fn from_raw(entry: Entry, instance: vk::Instance, owned: bool) -> Self {
+        let instance = unsafe { Instance::load(entry.static_fn(), instance) };
+        Self {
+            entry,
+            instance,
+            debug_messenger: None,
+            owned,
+        }
     }
 }
 
 impl Drop for VulkanInstance {
     fn drop(&mut self) {
+        if let Some(messenger) = &mut self.debug_messenger {
+            messenger.destroy();
+        }
+        if !self.owned {
+            return;
+        }
         unsafe {
             self.instance.destroy_instance(None);
         };
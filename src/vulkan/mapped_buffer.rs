@@ -0,0 +1,240 @@
+use anyhow::Result;
+use ash::vk;
+use std::ops::Range;
+
+use crate::vulkan::{VulkanBuffer, VulkanDevice, VulkanPhysicalDevice};
+
+/// Widens `range` of a mapped allocation to `atom` boundaries, as flushes and invalidations of
+/// non-coherent memory require (`nonCoherentAtomSize`). Returns the offset and size to pass to
+/// Vulkan; the size is `WHOLE_SIZE` when the rounded end would pass `size`, since the
+/// allocation backing the buffer may not extend that far.
+pub fn align_mapped_range(
+    range: Range<vk::DeviceSize>,
+    atom: vk::DeviceSize,
+    size: vk::DeviceSize,
+) -> (vk::DeviceSize, vk::DeviceSize) {
+    let atom = atom.max(1);
+    let start = range.start / atom * atom;
+    let end = range.end.div_ceil(atom) * atom;
+    if end > size {
+        (start, vk::WHOLE_SIZE)
+    } else {
+        (start, end - start)
+    }
+}
+
+/// Host-visible buffer mapped for its whole lifetime, instead of on every access like
+/// `VulkanBuffer::write`. On non-coherent memory, writes are tracked and made visible to the
+/// GPU with `flush`, and GPU writes must be pulled in with `invalidate` before reading.
+pub struct MappedBuffer {
+    pub buffer: VulkanBuffer,
+    pointer: *mut u8,
+    coherent: bool,
+    atom_size: vk::DeviceSize,
+    /// Bytes written since the last flush, on non-coherent memory.
+    dirty: Option<Range<vk::DeviceSize>>,
+}
+
+// The mapping is plain memory owned by the buffer; `&mut self` guards writes.
+unsafe impl Send for MappedBuffer {}
+
+impl MappedBuffer {
+    /// `memory_properties` must include `HOST_VISIBLE`. Coherence is decided by the memory
+    /// type the driver picks, which may be coherent even when it wasn't asked for.
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        memory_properties: vk::MemoryPropertyFlags,
+    ) -> Result<Self> {
+        if !memory_properties.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) {
+            return Err(anyhow::anyhow!(
+                "Mapped buffers need HOST_VISIBLE memory, got {:?}",
+                memory_properties
+            ));
+        }
+        let buffer = VulkanBuffer::new(device, physical_device, size, usage, memory_properties)?;
+
+        let pointer = unsafe {
+            buffer
+                .device
+                .map_memory(
+                    buffer.memory,
+                    0,
+                    vk::WHOLE_SIZE,
+                    vk::MemoryMapFlags::empty(),
+                )
+                .map_err(|e| anyhow::anyhow!("Failed to map buffer memory: {}", e))?
+        } as *mut u8;
+
+        Ok(Self {
+            coherent: buffer
+                .memory_flags
+                .contains(vk::MemoryPropertyFlags::HOST_COHERENT),
+            atom_size: physical_device.properties.limits.non_coherent_atom_size,
+            dirty: None,
+            pointer,
+            buffer,
+        })
+    }
+
+    /// Prefers `HOST_CACHED` memory, fast to read back on the CPU and usually not coherent,
+    /// falling back to any host-visible memory.
+    pub fn new_cached(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+    ) -> Result<Self> {
+        Self::new(
+            device,
+            physical_device,
+            size,
+            usage,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_CACHED,
+        )
+        .or_else(|_| {
+            Self::new(
+                device,
+                physical_device,
+                size,
+                usage,
+                vk::MemoryPropertyFlags::HOST_VISIBLE,
+            )
+        })
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        self.buffer.size
+    }
+
+    pub fn is_coherent(&self) -> bool {
+        self.coherent
+    }
+
+    fn check_range(&self, offset: vk::DeviceSize, len: vk::DeviceSize) -> Result<()> {
+        if offset + len > self.buffer.size {
+            return Err(anyhow::anyhow!(
+                "Mapped access of {} bytes at offset {} exceeds buffer size {}",
+                len,
+                offset,
+                self.buffer.size
+            ));
+        }
+        Ok(())
+    }
+
+    /// Copies `data` to `offset`. Not visible to the GPU until `flush` on non-coherent
+    /// memory, and the GPU must not be reading the range.
+    pub fn write<T: Copy>(&mut self, offset: vk::DeviceSize, data: &[T]) -> Result<()> {
+        let len = std::mem::size_of_val(data) as vk::DeviceSize;
+        self.check_range(offset, len)?;
+        if len == 0 {
+            return Ok(());
+        }
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr() as *const u8,
+                self.pointer.add(offset as usize),
+                len as usize,
+            );
+        }
+        if !self.coherent {
+            let written = offset..offset + len;
+            self.dirty = Some(match self.dirty.take() {
+                Some(dirty) => dirty.start.min(written.start)..dirty.end.max(written.end),
+                None => written,
+            });
+        }
+        Ok(())
+    }
+
+    /// Copies `count` values from `offset`. On non-coherent memory, call `invalidate` first
+    /// once the GPU is done writing.
+    pub fn read<T: Copy>(&self, offset: vk::DeviceSize, count: usize) -> Result<Vec<T>> {
+        let len = (count * std::mem::size_of::<T>()) as vk::DeviceSize;
+        self.check_range(offset, len)?;
+
+        let mut data = Vec::with_capacity(count);
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                self.pointer.add(offset as usize) as *const T,
+                data.as_mut_ptr(),
+                count,
+            );
+            data.set_len(count);
+        }
+        Ok(data)
+    }
+
+    /// Makes everything written since the last flush visible to the GPU. A no-op on coherent
+    /// memory; otherwise must happen before the submission that reads the data.
+    pub fn flush(&mut self) -> Result<()> {
+        match self.dirty.take() {
+            Some(range) => self.flush_range(range),
+            None => Ok(()),
+        }
+    }
+
+    /// Flushes `range`, widened to `nonCoherentAtomSize`.
+    pub fn flush_range(&self, range: Range<vk::DeviceSize>) -> Result<()> {
+        if self.coherent || range.is_empty() {
+            return Ok(());
+        }
+        let (offset, size) = align_mapped_range(range, self.atom_size, self.buffer.size);
+        let memory_range = vk::MappedMemoryRange::default()
+            .memory(self.buffer.memory)
+            .offset(offset)
+            .size(size);
+        unsafe {
+            self.buffer
+                .device
+                .flush_mapped_memory_ranges(std::slice::from_ref(&memory_range))
+                .map_err(|e| anyhow::anyhow!("Failed to flush mapped memory: {}", e))
+        }
+    }
+
+    /// Makes GPU writes to `range` visible to `read`. A no-op on coherent memory.
+    pub fn invalidate(&self, range: Range<vk::DeviceSize>) -> Result<()> {
+        if self.coherent || range.is_empty() {
+            return Ok(());
+        }
+        let (offset, size) = align_mapped_range(range, self.atom_size, self.buffer.size);
+        let memory_range = vk::MappedMemoryRange::default()
+            .memory(self.buffer.memory)
+            .offset(offset)
+            .size(size);
+        unsafe {
+            self.buffer
+                .device
+                .invalidate_mapped_memory_ranges(std::slice::from_ref(&memory_range))
+                .map_err(|e| anyhow::anyhow!("Failed to invalidate mapped memory: {}", e))
+        }
+    }
+}
+
+impl Drop for MappedBuffer {
+    fn drop(&mut self) {
+        unsafe { self.buffer.device.unmap_memory(self.buffer.memory) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn widens_ranges_to_atoms() {
+        assert_eq!(align_mapped_range(70..130, 64, 1024), (64, 128));
+        assert_eq!(align_mapped_range(128..192, 64, 1024), (128, 64));
+        // Past the end of the buffer the allocation may be smaller than a whole atom.
+        assert_eq!(
+            align_mapped_range(960..1000, 256, 1000),
+            (768, vk::WHOLE_SIZE)
+        );
+        assert_eq!(align_mapped_range(3..5, 1, 8), (3, 2));
+        assert_eq!(align_mapped_range(3..5, 0, 8), (3, 2));
+    }
+}
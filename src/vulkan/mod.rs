@@ -1,19 +1,77 @@
+pub mod acceleration_structure;
+pub mod buffer;
+pub mod buffer_guard;
+pub mod clear_values;
+pub mod color_space;
+pub mod command_encoder;
 pub mod command_pool;
+pub mod conditional;
+pub mod debug;
+pub mod deletion_queue;
+pub mod descriptor;
+pub mod descriptor_buffer;
 pub mod device;
+pub mod device_group;
+pub mod display;
+pub mod frame_arena;
+pub mod frame_stats;
 pub mod framebuffers;
+pub mod history_buffer;
+pub mod image;
+pub mod image_transfer;
 pub mod instance;
+pub mod mapped_buffer;
+pub mod offscreen;
+pub mod parallel_commands;
 pub mod physical_device;
+pub mod present_damage;
+pub mod profiler;
+pub mod query;
 pub mod render_pass;
+pub mod sampler;
+pub mod storage_image;
 pub mod surface;
 pub mod swapchain;
 pub mod sync;
+pub mod transform_feedback;
+pub mod transient;
+pub mod upload;
 
+pub use acceleration_structure::*;
+pub use buffer::*;
+pub use buffer_guard::*;
+pub use clear_values::*;
+pub use color_space::*;
+pub use command_encoder::*;
 pub use command_pool::*;
+pub use conditional::*;
+pub use debug::*;
+pub use deletion_queue::*;
+pub use descriptor::*;
+pub use descriptor_buffer::*;
 pub use device::*;
+pub use device_group::*;
+pub use display::*;
+pub use frame_arena::*;
+pub use frame_stats::*;
 pub use framebuffers::*;
+pub use history_buffer::*;
+pub use image::*;
+pub use image_transfer::*;
 pub use instance::*;
+pub use mapped_buffer::*;
+pub use offscreen::*;
+pub use parallel_commands::*;
 pub use physical_device::*;
+pub use present_damage::*;
+pub use profiler::*;
+pub use query::*;
 pub use render_pass::*;
+pub use sampler::*;
+pub use storage_image::*;
 pub use surface::*;
 pub use swapchain::*;
 pub use sync::*;
+pub use transform_feedback::*;
+pub use transient::*;
+pub use upload::*;
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::sync::Arc;
+
+use crate::vulkan::{VulkanDevice, VulkanImage, VulkanPhysicalDevice, VulkanRenderPass};
+
+/// A color image with its own render pass and framebuffer, left in `SHADER_READ_ONLY_OPTIMAL`
+/// after rendering so it can be sampled by later passes or copied, e.g. by a `PresentThread`.
+pub struct VulkanOffscreenTarget {
+    pub framebuffer: vk::Framebuffer,
+    pub render_pass: VulkanRenderPass,
+    pub image: VulkanImage,
+    pub device: Arc<Device>,
+}
+
+impl VulkanOffscreenTarget {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        extent: vk::Extent2D,
+        format: vk::Format,
+    ) -> Result<Self> {
+        let image = VulkanImage::new(
+            device,
+            physical_device,
+            extent,
+            format,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT
+                | vk::ImageUsageFlags::SAMPLED
+                | vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::ImageAspectFlags::COLOR,
+        )?;
+
+        let render_pass = VulkanRenderPass::with_color_format(
+            device,
+            format,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )?;
+
+        let attachments = [image.view];
+
+        let framebuffer_info = vk::FramebufferCreateInfo::default()
+            .render_pass(render_pass.render_pass)
+            .attachments(&attachments)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+
+        let framebuffer = unsafe {
+            device
+                .device
+                .create_framebuffer(&framebuffer_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create offscreen framebuffer: {}", e))?
+        };
+
+        Ok(Self {
+            framebuffer,
+            render_pass,
+            image,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.image.extent
+    }
+}
+
+impl Drop for VulkanOffscreenTarget {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_framebuffer(self.framebuffer, None);
+        }
+    }
+}
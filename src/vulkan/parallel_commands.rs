@@ -0,0 +1,164 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::sync::Arc;
+
+use crate::jobs::JobSystem;
+use crate::vulkan::VulkanDevice;
+
+/// Records secondary command buffers for one render pass on worker threads.
+///
+/// Every frame in flight owns one command pool per chunk, so chunks never share a pool across
+/// threads and a frame's pools are only reset once its fence has signaled. The render pass must
+/// be begun with `vk::SubpassContents::SECONDARY_COMMAND_BUFFERS` before executing the result.
+pub struct ParallelCommandRecorder {
+    pools: Vec<Vec<vk::CommandPool>>,
+    buffers: Vec<Vec<vk::CommandBuffer>>,
+    device: Arc<Device>,
+}
+
+impl ParallelCommandRecorder {
+    pub fn new(device: &VulkanDevice, frames_in_flight: usize, max_chunks: usize) -> Result<Self> {
+        let graphics_family = device
+            .queue_family_indices
+            .graphics_family
+            .ok_or_else(|| anyhow::anyhow!("Device has no graphics queue family"))?;
+
+        let mut recorder = Self {
+            pools: Vec::with_capacity(frames_in_flight),
+            buffers: Vec::with_capacity(frames_in_flight),
+            device: device.device.clone(),
+        };
+
+        for _ in 0..frames_in_flight {
+            let mut pools = Vec::with_capacity(max_chunks);
+            let mut buffers = Vec::with_capacity(max_chunks);
+
+            for _ in 0..max_chunks {
+                let pool_info = vk::CommandPoolCreateInfo::default()
+                    .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+                    .queue_family_index(graphics_family);
+
+                let pool = unsafe {
+                    device
+                        .device
+                        .create_command_pool(&pool_info, None)
+                        .map_err(|e| {
+                            anyhow::anyhow!("Failed to create secondary command pool: {}", e)
+                        })?
+                };
+                pools.push(pool);
+
+                let alloc_info = vk::CommandBufferAllocateInfo::default()
+                    .command_pool(pool)
+                    .level(vk::CommandBufferLevel::SECONDARY)
+                    .command_buffer_count(1);
+
+                let buffer = unsafe {
+                    device
+                        .device
+                        .allocate_command_buffers(&alloc_info)
+                        .map_err(|e| {
+                            anyhow::anyhow!("Failed to allocate secondary command buffer: {}", e)
+                        })?[0]
+                };
+                buffers.push(buffer);
+            }
+
+            recorder.pools.push(pools);
+            recorder.buffers.push(buffers);
+        }
+
+        Ok(recorder)
+    }
+
+    pub fn max_chunks(&self) -> usize {
+        self.buffers.first().map_or(0, Vec::len)
+    }
+
+    /// Records `chunk_count` secondary command buffers in parallel, calling
+    /// `record(chunk_index, command_buffer)` inside `subpass` of `render_pass`, and executes
+    /// them in chunk order on `primary`. Must be called after `frame`'s fence has signaled.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record(
+        &self,
+        jobs: &JobSystem,
+        frame: usize,
+        primary: vk::CommandBuffer,
+        render_pass: vk::RenderPass,
+        subpass: u32,
+        framebuffer: vk::Framebuffer,
+        chunk_count: usize,
+        record: impl Fn(usize, vk::CommandBuffer) -> Result<()> + Sync,
+    ) -> Result<()> {
+        if chunk_count > self.max_chunks() {
+            return Err(anyhow::anyhow!(
+                "Requested {} command chunks, but the recorder has {}",
+                chunk_count,
+                self.max_chunks()
+            ));
+        }
+
+        for &pool in &self.pools[frame][..chunk_count] {
+            unsafe {
+                self.device
+                    .reset_command_pool(pool, vk::CommandPoolResetFlags::empty())
+                    .map_err(|e| {
+                        anyhow::anyhow!("Failed to reset secondary command pool: {}", e)
+                    })?;
+            }
+        }
+
+        let buffers = &self.buffers[frame][..chunk_count];
+        let chunks: Vec<usize> = (0..chunk_count).collect();
+        let results = jobs.parallel_map("record commands", &chunks, 1, |&chunk| {
+            let buffer = buffers[chunk];
+
+            let inheritance = vk::CommandBufferInheritanceInfo::default()
+                .render_pass(render_pass)
+                .subpass(subpass)
+                .framebuffer(framebuffer);
+            let begin_info = vk::CommandBufferBeginInfo::default()
+                .flags(
+                    vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT
+                        | vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE,
+                )
+                .inheritance_info(&inheritance);
+
+            unsafe {
+                self.device
+                    .begin_command_buffer(buffer, &begin_info)
+                    .map_err(|e| {
+                        anyhow::anyhow!("Failed to begin secondary command buffer: {}", e)
+                    })?;
+            }
+
+            record(chunk, buffer)?;
+
+            unsafe {
+                self.device
+                    .end_command_buffer(buffer)
+                    .map_err(|e| anyhow::anyhow!("Failed to end secondary command buffer: {}", e))
+            }
+        })?;
+
+        results.into_iter().collect::<Result<Vec<()>>>()?;
+
+        if !buffers.is_empty() {
+            unsafe {
+                self.device.cmd_execute_commands(primary, buffers);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for ParallelCommandRecorder {
+    fn drop(&mut self) {
+        unsafe {
+            for pool in self.pools.iter().flatten() {
+                self.device.destroy_command_pool(*pool, None);
+            }
+        }
+    }
+}
@@ -4,14 +4,165 @@ use std::ffi::CStr;
 
 use crate::vulkan::{VulkanInstance, VulkanSurface};
 
+/// Subgroup capabilities of a physical device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubgroupProperties {
+    /// Default subgroup size.
+    pub size: u32,
+    /// Smallest size a subgroup may have when the size varies, e.g. in compute shaders
+    /// built for Vulkan 1.3.
+    pub min_size: u32,
+    pub max_size: u32,
+    pub supported_stages: vk::ShaderStageFlags,
+    pub supported_operations: vk::SubgroupFeatureFlags,
+}
+
+impl Default for SubgroupProperties {
+    /// Vulkan 1.0 devices: every invocation is its own subgroup.
+    fn default() -> Self {
+        Self {
+            size: 1,
+            min_size: 1,
+            max_size: 1,
+            supported_stages: vk::ShaderStageFlags::empty(),
+            supported_operations: vk::SubgroupFeatureFlags::empty(),
+        }
+    }
+}
+
+impl SubgroupProperties {
+    /// Whether every operation in `operations` is available in `stage`.
+    pub fn supports(
+        &self,
+        operations: vk::SubgroupFeatureFlags,
+        stage: vk::ShaderStageFlags,
+    ) -> bool {
+        self.supported_stages.contains(stage) && self.supported_operations.contains(operations)
+    }
+
+    /// Defines for shaders of `stage` (see `shaders/subgroup/subgroup.glsl`): `SUBGROUP_SIZE`
+    /// and `SUBGROUP_MIN_SIZE`, plus `SUBGROUP_<OPERATION>` for each operation class usable in
+    /// `stage`.
+    pub fn shader_defines(&self, stage: vk::ShaderStageFlags) -> Vec<String> {
+        let mut defines = vec![
+            format!("SUBGROUP_SIZE={}", self.size),
+            format!("SUBGROUP_MIN_SIZE={}", self.min_size),
+        ];
+
+        if self.supported_stages.contains(stage) {
+            for (operation, name) in [
+                (vk::SubgroupFeatureFlags::BASIC, "SUBGROUP_BASIC"),
+                (vk::SubgroupFeatureFlags::VOTE, "SUBGROUP_VOTE"),
+                (vk::SubgroupFeatureFlags::ARITHMETIC, "SUBGROUP_ARITHMETIC"),
+                (vk::SubgroupFeatureFlags::BALLOT, "SUBGROUP_BALLOT"),
+                (vk::SubgroupFeatureFlags::SHUFFLE, "SUBGROUP_SHUFFLE"),
+                (
+                    vk::SubgroupFeatureFlags::SHUFFLE_RELATIVE,
+                    "SUBGROUP_SHUFFLE_RELATIVE",
+                ),
+                (vk::SubgroupFeatureFlags::CLUSTERED, "SUBGROUP_CLUSTERED"),
+                (vk::SubgroupFeatureFlags::QUAD, "SUBGROUP_QUAD"),
+            ] {
+                // Every other class builds on the basic operations.
+                if self
+                    .supported_operations
+                    .contains(operation | vk::SubgroupFeatureFlags::BASIC)
+                {
+                    defines.push(name.to_string());
+                }
+            }
+        }
+
+        defines
+    }
+
+    fn query(instance: &Instance, physical_device: vk::PhysicalDevice, api_version: u32) -> Self {
+        if api_version < vk::API_VERSION_1_1 {
+            return Self::default();
+        }
+
+        let mut subgroup = vk::PhysicalDeviceSubgroupProperties::default();
+        let mut size_control = vk::PhysicalDeviceSubgroupSizeControlProperties::default();
+        let mut properties = vk::PhysicalDeviceProperties2::default().push_next(&mut subgroup);
+        // Core in Vulkan 1.3 only.
+        let size_control_supported = api_version >= vk::API_VERSION_1_3;
+        if size_control_supported {
+            properties = properties.push_next(&mut size_control);
+        }
+        unsafe { instance.get_physical_device_properties2(physical_device, &mut properties) };
+
+        let (min_size, max_size) = if size_control_supported {
+            (
+                size_control.min_subgroup_size,
+                size_control.max_subgroup_size,
+            )
+        } else {
+            (subgroup.subgroup_size, subgroup.subgroup_size)
+        };
+
+        Self {
+            size: subgroup.subgroup_size,
+            min_size,
+            max_size,
+            supported_stages: subgroup.supported_stages,
+            supported_operations: subgroup.supported_operations,
+        }
+    }
+}
+
 pub struct VulkanPhysicalDevice {
     pub physical_device: vk::PhysicalDevice,
     pub properties: vk::PhysicalDeviceProperties,
     pub features: vk::PhysicalDeviceFeatures,
     pub memory_properties: vk::PhysicalDeviceMemoryProperties,
+    pub subgroup: SubgroupProperties,
 }
 
 impl VulkanPhysicalDevice {
+    /// Wraps a physical device chosen elsewhere, e.g. by a host engine.
+    pub fn from_raw(vulkan_instance: &VulkanInstance, physical_device: vk::PhysicalDevice) -> Self {
+        let instance = &vulkan_instance.instance;
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        Self {
+            physical_device,
+            properties,
+            features: unsafe { instance.get_physical_device_features(physical_device) },
+            memory_properties: unsafe {
+                instance.get_physical_device_memory_properties(physical_device)
+            },
+            subgroup: SubgroupProperties::query(instance, physical_device, properties.api_version),
+        }
+    }
+
+    /// Every physical device, in the driver's order.
+    pub fn enumerate(vulkan_instance: &VulkanInstance) -> Result<Vec<Self>> {
+        let physical_devices = unsafe { vulkan_instance.instance.enumerate_physical_devices() }
+            .map_err(|e| anyhow::anyhow!("Failed to enumerate physical devices: {}", e))?;
+        Ok(physical_devices
+            .into_iter()
+            .map(|physical_device| Self::from_raw(vulkan_instance, physical_device))
+            .collect())
+    }
+
+    /// Indices of the devices to try when switching away from `current`: the ones after it
+    /// in `devices`, then the ones before it, so repeated switches cycle through them all.
+    pub fn switch_order(devices: &[vk::PhysicalDevice], current: vk::PhysicalDevice) -> Vec<usize> {
+        let start = devices
+            .iter()
+            .position(|&device| device == current)
+            .map_or(0, |index| index + 1);
+        (0..devices.len())
+            .map(|offset| (start + offset) % devices.len())
+            .filter(|&index| devices[index] != current)
+            .collect()
+    }
+
+    pub fn name(&self) -> String {
+        unsafe { CStr::from_ptr(self.properties.device_name.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
     pub fn select_best_device(vulkan_instance: &VulkanInstance) -> Result<Self> {
         let instance = &vulkan_instance.instance;
 
@@ -49,12 +200,27 @@ impl VulkanPhysicalDevice {
                 "Selected device: {}",
                 unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }.to_string_lossy()
             );
+            match device_local_host_visible_heap(&memory_properties) {
+                Some(size) if size > SMALL_BAR_SIZE => {
+                    println!("Resizable BAR: {} MiB host-visible VRAM", size >> 20)
+                }
+                Some(size) => println!(
+                    "Resizable BAR: off ({} MiB window), streaming through staging",
+                    size >> 20
+                ),
+                None => println!("Resizable BAR: unavailable, streaming through staging"),
+            }
 
             Ok(Self {
                 physical_device,
                 properties,
                 features,
                 memory_properties,
+                subgroup: SubgroupProperties::query(
+                    instance,
+                    physical_device,
+                    properties.api_version,
+                ),
             })
         } else {
             Err(anyhow::anyhow!("No best device found"))
@@ -150,6 +316,102 @@ impl VulkanPhysicalDevice {
 
         Ok(true)
     }
+
+    /// Whether the device exposes `extension`, for optional extensions.
+    pub fn supports_extension(&self, instance: &Instance, extension: &CStr) -> Result<bool> {
+        let available_extensions =
+            unsafe { instance.enumerate_device_extension_properties(self.physical_device)? };
+
+        Ok(available_extensions.iter().any(|ext| {
+            let ext_name = unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) };
+            ext_name == extension
+        }))
+    }
+
+    pub fn find_memory_type(
+        &self,
+        type_filter: u32,
+        properties: vk::MemoryPropertyFlags,
+    ) -> Result<u32> {
+        let memory_types = &self.memory_properties.memory_types
+            [..self.memory_properties.memory_type_count as usize];
+
+        memory_types
+            .iter()
+            .enumerate()
+            .find(|(index, memory_type)| {
+                type_filter & (1 << index) != 0 && memory_type.property_flags.contains(properties)
+            })
+            .map(|(index, _)| index as u32)
+            .ok_or_else(|| anyhow::anyhow!("No suitable memory type for {:?}", properties))
+    }
+
+    /// Size of the largest heap whose memory can be both `DEVICE_LOCAL` and `HOST_VISIBLE`.
+    pub fn device_local_host_visible_heap(&self) -> Option<vk::DeviceSize> {
+        device_local_host_visible_heap(&self.memory_properties)
+    }
+
+    /// True if the CPU can map all of VRAM (resizable BAR, or unified memory on integrated
+    /// GPUs) rather than the 256 MiB window most discrete GPUs expose without it.
+    pub fn has_resizable_bar(&self) -> bool {
+        self.device_local_host_visible_heap()
+            .is_some_and(|size| size > SMALL_BAR_SIZE)
+    }
+
+    pub fn find_supported_format(
+        &self,
+        instance: &Instance,
+        candidates: &[vk::Format],
+        tiling: vk::ImageTiling,
+        features: vk::FormatFeatureFlags,
+    ) -> Result<vk::Format> {
+        for &format in candidates {
+            let properties = unsafe {
+                instance.get_physical_device_format_properties(self.physical_device, format)
+            };
+
+            let supported = match tiling {
+                vk::ImageTiling::LINEAR => properties.linear_tiling_features.contains(features),
+                _ => properties.optimal_tiling_features.contains(features),
+            };
+
+            if supported {
+                return Ok(format);
+            }
+        }
+
+        Err(anyhow::anyhow!(
+            "No supported format among {:?}",
+            candidates
+        ))
+    }
+
+    pub fn find_depth_format(&self, instance: &Instance) -> Result<vk::Format> {
+        self.find_supported_format(
+            instance,
+            &[
+                vk::Format::D32_SFLOAT,
+                vk::Format::D32_SFLOAT_S8_UINT,
+                vk::Format::D24_UNORM_S8_UINT,
+            ],
+            vk::ImageTiling::OPTIMAL,
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+        )
+    }
+}
+
+/// Size of the host-visible VRAM window on GPUs without resizable BAR.
+const SMALL_BAR_SIZE: vk::DeviceSize = 256 * 1024 * 1024;
+
+fn device_local_host_visible_heap(
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+) -> Option<vk::DeviceSize> {
+    let bar = vk::MemoryPropertyFlags::DEVICE_LOCAL | vk::MemoryPropertyFlags::HOST_VISIBLE;
+    memory_properties.memory_types[..memory_properties.memory_type_count as usize]
+        .iter()
+        .filter(|memory_type| memory_type.property_flags.contains(bar))
+        .map(|memory_type| memory_properties.memory_heaps[memory_type.heap_index as usize].size)
+        .max()
 }
 
 #[derive(Debug, Clone)]
@@ -165,3 +427,76 @@ impl QueueFamilyIndices {
         self.graphics_family.is_some() && self.present_family.is_some()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_properties(
+        heaps: &[vk::DeviceSize],
+        types: &[(vk::MemoryPropertyFlags, u32)],
+    ) -> vk::PhysicalDeviceMemoryProperties {
+        let mut properties = vk::PhysicalDeviceMemoryProperties {
+            memory_heap_count: heaps.len() as u32,
+            memory_type_count: types.len() as u32,
+            ..Default::default()
+        };
+        for (heap, &size) in properties.memory_heaps.iter_mut().zip(heaps) {
+            heap.size = size;
+        }
+        for (memory_type, &(property_flags, heap_index)) in
+            properties.memory_types.iter_mut().zip(types)
+        {
+            memory_type.property_flags = property_flags;
+            memory_type.heap_index = heap_index;
+        }
+        properties
+    }
+
+    #[test]
+    fn cycles_through_other_devices() {
+        use ash::vk::Handle;
+
+        let devices: Vec<vk::PhysicalDevice> = (1..=3).map(vk::PhysicalDevice::from_raw).collect();
+        assert_eq!(
+            VulkanPhysicalDevice::switch_order(&devices, devices[1]),
+            vec![2, 0]
+        );
+        assert_eq!(
+            VulkanPhysicalDevice::switch_order(&devices, devices[2]),
+            vec![0, 1]
+        );
+        assert_eq!(
+            VulkanPhysicalDevice::switch_order(&devices[..1], devices[0]),
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    fn finds_host_visible_vram() {
+        let local = vk::MemoryPropertyFlags::DEVICE_LOCAL;
+        let host = vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+        let gib = 1024 * 1024 * 1024;
+
+        let no_bar = memory_properties(&[8 * gib, 16 * gib], &[(local, 0), (host, 1)]);
+        assert_eq!(device_local_host_visible_heap(&no_bar), None);
+
+        let small_bar = memory_properties(
+            &[8 * gib, 16 * gib, SMALL_BAR_SIZE],
+            &[(local, 0), (host, 1), (local | host, 2)],
+        );
+        assert_eq!(
+            device_local_host_visible_heap(&small_bar),
+            Some(SMALL_BAR_SIZE)
+        );
+
+        let resizable_bar = memory_properties(
+            &[8 * gib, 16 * gib],
+            &[(local, 0), (host, 1), (local | host, 0)],
+        );
+        assert_eq!(
+            device_local_host_visible_heap(&resizable_bar),
+            Some(8 * gib)
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use ash::vk;
+
+/// More rectangles than this are merged into their bounding box; compositors gain little from
+/// long lists.
+const MAX_DAMAGE_RECTS: usize = 16;
+
+/// Parts of the frame that changed since the last present, passed to the presentation engine
+/// with `VK_KHR_incremental_present` so it can skip copying or scanning out the rest.
+///
+/// This is only a hint: pixels outside the region must still match the previously presented
+/// image. An empty region means no hint, so the whole image is presented.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DamageRegion {
+    rects: Vec<vk::Rect2D>,
+    full: bool,
+}
+
+impl DamageRegion {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The whole image changed.
+    pub fn full() -> Self {
+        Self {
+            rects: Vec::new(),
+            full: true,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.full
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.full && self.rects.is_empty()
+    }
+
+    pub fn rects(&self) -> &[vk::Rect2D] {
+        &self.rects
+    }
+
+    /// Adds `rect`, merging it with the rectangles it overlaps.
+    pub fn add(&mut self, rect: vk::Rect2D) {
+        if self.full || rect.extent.width == 0 || rect.extent.height == 0 {
+            return;
+        }
+
+        let mut merged = rect;
+        self.rects.retain(|existing| {
+            if overlaps(existing, &merged) {
+                merged = bounds(existing, &merged);
+                false
+            } else {
+                true
+            }
+        });
+        self.rects.push(merged);
+
+        if self.rects.len() > MAX_DAMAGE_RECTS {
+            let all = self
+                .rects
+                .iter()
+                .fold(self.rects[0], |all, rect| bounds(&all, rect));
+            self.rects = vec![all];
+        }
+    }
+
+    pub fn merge(&mut self, other: &DamageRegion) {
+        if other.full {
+            *self = Self::full();
+            return;
+        }
+        for rect in &other.rects {
+            self.add(*rect);
+        }
+    }
+
+    /// Rectangles clamped to `extent` for `vk::PresentRegionKHR`, or `None` when the whole
+    /// image should be presented.
+    pub fn present_rects(&self, extent: vk::Extent2D) -> Option<Vec<vk::RectLayerKHR>> {
+        if self.is_full() || self.is_empty() {
+            return None;
+        }
+
+        let rects: Vec<_> = self
+            .rects
+            .iter()
+            .filter_map(|rect| {
+                let (max_x, max_y) = max_corner(rect);
+                let min_x = rect.offset.x.clamp(0, extent.width as i32) as u32;
+                let min_y = rect.offset.y.clamp(0, extent.height as i32) as u32;
+                let max_x = max_x.clamp(0, extent.width as i32) as u32;
+                let max_y = max_y.clamp(0, extent.height as i32) as u32;
+                (min_x < max_x && min_y < max_y).then(|| vk::RectLayerKHR {
+                    offset: vk::Offset2D {
+                        x: min_x as i32,
+                        y: min_y as i32,
+                    },
+                    extent: vk::Extent2D {
+                        width: max_x - min_x,
+                        height: max_y - min_y,
+                    },
+                    layer: 0,
+                })
+            })
+            .collect();
+
+        // Everything was off screen; nothing visible changed, but an empty list would mean
+        // "everything", so keep one pixel.
+        Some(if rects.is_empty() {
+            vec![vk::RectLayerKHR {
+                offset: vk::Offset2D::default(),
+                extent: vk::Extent2D {
+                    width: 1,
+                    height: 1,
+                },
+                layer: 0,
+            }]
+        } else {
+            rects
+        })
+    }
+}
+
+fn overlaps(a: &vk::Rect2D, b: &vk::Rect2D) -> bool {
+    let (a_max_x, a_max_y) = max_corner(a);
+    let (b_max_x, b_max_y) = max_corner(b);
+    a.offset.x < b_max_x && b.offset.x < a_max_x && a.offset.y < b_max_y && b.offset.y < a_max_y
+}
+
+fn bounds(a: &vk::Rect2D, b: &vk::Rect2D) -> vk::Rect2D {
+    let (a_max_x, a_max_y) = max_corner(a);
+    let (b_max_x, b_max_y) = max_corner(b);
+    let x = a.offset.x.min(b.offset.x);
+    let y = a.offset.y.min(b.offset.y);
+    vk::Rect2D {
+        offset: vk::Offset2D { x, y },
+        extent: vk::Extent2D {
+            width: (a_max_x.max(b_max_x) - x) as u32,
+            height: (a_max_y.max(b_max_y) - y) as u32,
+        },
+    }
+}
+
+fn max_corner(rect: &vk::Rect2D) -> (i32, i32) {
+    (
+        rect.offset.x + rect.extent.width as i32,
+        rect.offset.y + rect.extent.height as i32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> vk::Rect2D {
+        vk::Rect2D {
+            offset: vk::Offset2D { x, y },
+            extent: vk::Extent2D { width, height },
+        }
+    }
+
+    #[test]
+    fn merges_overlapping_rects() {
+        let mut damage = DamageRegion::new();
+        assert!(damage.is_empty());
+
+        damage.add(rect(0, 0, 10, 10));
+        damage.add(rect(100, 100, 10, 10));
+        damage.add(rect(5, 5, 10, 10));
+        damage.add(rect(0, 0, 0, 10));
+
+        assert_eq!(
+            damage.rects(),
+            &[rect(100, 100, 10, 10), rect(0, 0, 15, 15)]
+        );
+
+        // The 17th rectangle collapses everything into one.
+        for i in 0..MAX_DAMAGE_RECTS as i32 - 1 {
+            damage.add(rect(200 + i * 20, 0, 10, 10));
+        }
+        assert_eq!(damage.rects(), &[rect(0, 0, 490, 110)]);
+
+        damage.merge(&DamageRegion::full());
+        assert!(damage.is_full());
+        damage.add(rect(0, 0, 1, 1));
+        assert!(damage.rects().is_empty());
+    }
+
+    #[test]
+    fn clamps_present_rects_to_the_image() {
+        let extent = vk::Extent2D {
+            width: 100,
+            height: 50,
+        };
+        assert!(DamageRegion::new().present_rects(extent).is_none());
+        assert!(DamageRegion::full().present_rects(extent).is_none());
+
+        let mut damage = DamageRegion::new();
+        damage.add(rect(-10, 40, 30, 30));
+        damage.add(rect(500, 0, 10, 10));
+        let rects = damage.present_rects(extent).unwrap();
+        assert_eq!(rects.len(), 1);
+        assert_eq!(rects[0].offset, vk::Offset2D { x: 0, y: 40 });
+        assert_eq!(
+            rects[0].extent,
+            vk::Extent2D {
+                width: 20,
+                height: 10
+            }
+        );
+    }
+}
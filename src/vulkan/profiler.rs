@@ -0,0 +1,159 @@
+use anyhow::Result;
+use ash::vk;
+
+use crate::vulkan::{GpuTimer, VulkanDevice, VulkanPhysicalDevice};
+
+/// GPU time of one named pass, relative to the first pass of its frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuPassTiming {
+    pub name: String,
+    pub start_ms: f64,
+    pub duration_ms: f64,
+}
+
+/// Identifies a pass opened with `GpuProfiler::begin_pass`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GpuPassId(Option<u32>);
+
+struct ProfilerFrame {
+    timer: GpuTimer,
+    passes: Vec<String>,
+}
+
+/// Named GPU pass timings from timestamp queries, with one query pool per frame in flight so
+/// a frame's results are read only after its fence has signaled.
+pub struct GpuProfiler {
+    frames: Vec<ProfilerFrame>,
+    current: usize,
+    max_passes: u32,
+}
+
+impl GpuProfiler {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        frames_in_flight: usize,
+        max_passes: u32,
+    ) -> Result<Self> {
+        let frames = (0..frames_in_flight.max(1))
+            .map(|_| {
+                Ok(ProfilerFrame {
+                    timer: GpuTimer::new(device, physical_device, max_passes * 2)?,
+                    passes: Vec::new(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            frames,
+            current: 0,
+            max_passes,
+        })
+    }
+
+    /// Starts profiling frame slot `frame_index` and returns the pass timings it recorded the
+    /// last time it was used, or `None` if there are none. Record outside a render pass, after
+    /// waiting on the slot's fence.
+    pub fn begin_frame(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+    ) -> Result<Option<Vec<GpuPassTiming>>> {
+        self.current = frame_index % self.frames.len();
+        let frame = &mut self.frames[self.current];
+
+        let timings = if frame.passes.is_empty() {
+            None
+        } else {
+            frame
+                .timer
+                .read(frame.passes.len() as u32 * 2)?
+                .map(|timestamps| {
+                    pass_timings(&frame.passes, &timestamps, |start, end| {
+                        frame.timer.elapsed_ms(start, end)
+                    })
+                })
+        };
+
+        frame.passes.clear();
+        frame.timer.cmd_reset(command_buffer);
+        Ok(timings)
+    }
+
+    /// Opens a pass named `name`. Passes past `max_passes` in a frame aren't timed.
+    pub fn begin_pass(&mut self, command_buffer: vk::CommandBuffer, name: &str) -> GpuPassId {
+        let frame = &mut self.frames[self.current];
+        let index = frame.passes.len() as u32;
+        if index >= self.max_passes {
+            return GpuPassId(None);
+        }
+
+        frame.passes.push(name.to_string());
+        frame.timer.cmd_write(
+            command_buffer,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            index * 2,
+        );
+        GpuPassId(Some(index))
+    }
+
+    pub fn end_pass(&self, command_buffer: vk::CommandBuffer, pass: GpuPassId) {
+        if let GpuPassId(Some(index)) = pass {
+            self.frames[self.current].timer.cmd_write(
+                command_buffer,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                index * 2 + 1,
+            );
+        }
+    }
+}
+
+/// Pairs each pass with its begin and end timestamps, measuring starts from the earliest
+/// begin.
+fn pass_timings(
+    passes: &[String],
+    timestamps: &[u64],
+    elapsed_ms: impl Fn(u64, u64) -> f64,
+) -> Vec<GpuPassTiming> {
+    let origin = timestamps.iter().step_by(2).copied().min().unwrap_or(0);
+    passes
+        .iter()
+        .zip(timestamps.chunks_exact(2))
+        .map(|(name, pair)| GpuPassTiming {
+            name: name.clone(),
+            start_ms: elapsed_ms(origin, pair[0]),
+            duration_ms: elapsed_ms(pair[0], pair[1]),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairs_timestamps_into_pass_timings() {
+        let passes = ["shadow".to_string(), "main".to_string()];
+        // 1 tick = 1 microsecond.
+        let timestamps = [1_000, 3_000, 3_500, 10_000];
+        let timings = pass_timings(&passes, &timestamps, |start, end| {
+            end.saturating_sub(start) as f64 / 1000.0
+        });
+
+        assert_eq!(
+            timings,
+            vec![
+                GpuPassTiming {
+                    name: "shadow".to_string(),
+                    start_ms: 0.0,
+                    duration_ms: 2.0,
+                },
+                GpuPassTiming {
+                    name: "main".to_string(),
+                    start_ms: 2.5,
+                    duration_ms: 6.5,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::sync::Arc;
+
+use crate::vulkan::{VulkanDevice, VulkanPhysicalDevice};
+
+/// GPU timestamps written from command buffers, read back once the GPU is done with them.
+pub struct GpuTimer {
+    pub query_pool: vk::QueryPool,
+    capacity: u32,
+    timestamp_period: f32,
+    device: Arc<Device>,
+}
+
+impl GpuTimer {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        capacity: u32,
+    ) -> Result<Self> {
+        let limits = &physical_device.properties.limits;
+        if limits.timestamp_compute_and_graphics == vk::FALSE || limits.timestamp_period == 0.0 {
+            return Err(anyhow::anyhow!("Device doesn't support timestamp queries"));
+        }
+
+        let pool_info = vk::QueryPoolCreateInfo::default()
+            .query_type(vk::QueryType::TIMESTAMP)
+            .query_count(capacity);
+
+        let query_pool = unsafe {
+            device
+                .device
+                .create_query_pool(&pool_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create query pool: {}", e))?
+        };
+
+        Ok(Self {
+            query_pool,
+            capacity,
+            timestamp_period: limits.timestamp_period,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Resets every query. Must be recorded outside a render pass, before the writes.
+    pub fn cmd_reset(&self, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            self.device
+                .cmd_reset_query_pool(command_buffer, self.query_pool, 0, self.capacity);
+        }
+    }
+
+    /// Writes timestamp `index` once every previous command has reached `stage`.
+    pub fn cmd_write(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        stage: vk::PipelineStageFlags,
+        index: u32,
+    ) {
+        unsafe {
+            self.device
+                .cmd_write_timestamp(command_buffer, stage, self.query_pool, index);
+        }
+    }
+
+    /// Raw timestamps of the first `count` queries, or `None` while some aren't available
+    /// yet.
+    pub fn read(&self, count: u32) -> Result<Option<Vec<u64>>> {
+        let mut timestamps = vec![0u64; count as usize];
+        let result = unsafe {
+            self.device.get_query_pool_results(
+                self.query_pool,
+                0,
+                &mut timestamps,
+                vk::QueryResultFlags::TYPE_64,
+            )
+        };
+
+        match result {
+            Ok(()) => Ok(Some(timestamps)),
+            Err(vk::Result::NOT_READY) => Ok(None),
+            Err(e) => Err(anyhow::anyhow!("Failed to read timestamps: {}", e)),
+        }
+    }
+
+    /// Converts the difference between two timestamps to milliseconds.
+    pub fn elapsed_ms(&self, start: u64, end: u64) -> f64 {
+        end.saturating_sub(start) as f64 * self.timestamp_period as f64 / 1_000_000.0
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_query_pool(self.query_pool, None);
+        }
+    }
+}
+
+/// Occlusion queries counting the samples that pass depth testing, e.g. for bounding-box
+/// draws whose results drive conditional rendering.
+pub struct OcclusionQueries {
+    pub query_pool: vk::QueryPool,
+    capacity: u32,
+    device: Arc<Device>,
+}
+
+impl OcclusionQueries {
+    pub fn new(device: &VulkanDevice, capacity: u32) -> Result<Self> {
+        let pool_info = vk::QueryPoolCreateInfo::default()
+            .query_type(vk::QueryType::OCCLUSION)
+            .query_count(capacity);
+
+        let query_pool = unsafe {
+            device
+                .device
+                .create_query_pool(&pool_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create query pool: {}", e))?
+        };
+
+        Ok(Self {
+            query_pool,
+            capacity,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Resets every query. Must be recorded outside a render pass, before the queries.
+    pub fn cmd_reset(&self, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            self.device
+                .cmd_reset_query_pool(command_buffer, self.query_pool, 0, self.capacity);
+        }
+    }
+
+    /// Starts counting samples for query `index`; the draws until `cmd_end` are measured.
+    pub fn cmd_begin(&self, command_buffer: vk::CommandBuffer, index: u32) {
+        unsafe {
+            self.device.cmd_begin_query(
+                command_buffer,
+                self.query_pool,
+                index,
+                vk::QueryControlFlags::empty(),
+            );
+        }
+    }
+
+    pub fn cmd_end(&self, command_buffer: vk::CommandBuffer, index: u32) {
+        unsafe {
+            self.device
+                .cmd_end_query(command_buffer, self.query_pool, index);
+        }
+    }
+}
+
+impl Drop for OcclusionQueries {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_query_pool(self.query_pool, None);
+        }
+    }
+}
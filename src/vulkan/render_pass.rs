@@ -6,41 +6,110 @@ use crate::vulkan::{VulkanDevice, VulkanSwapchain};
 
 pub struct VulkanRenderPass {
     pub render_pass: vk::RenderPass,
+    /// Format of every attachment, in attachment order.
+    pub attachment_formats: Vec<vk::Format>,
     pub device: Arc<Device>,
 }
 
 impl VulkanRenderPass {
     pub fn new(device: &VulkanDevice, swapchain: &VulkanSwapchain) -> Result<Self> {
+        Self::with_color_format(
+            device,
+            swapchain.format.format,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+        )
+    }
+
+    /// Single color attachment render pass that leaves the attachment in `final_layout`, e.g.
+    /// `SHADER_READ_ONLY_OPTIMAL` for offscreen targets sampled by a later pass.
+    pub fn with_color_format(
+        device: &VulkanDevice,
+        format: vk::Format,
+        final_layout: vk::ImageLayout,
+    ) -> Result<Self> {
+        Self::with_formats(device, format, None, final_layout)
+    }
+
+    /// Like `with_color_format`, optionally adding a cleared depth attachment at index 1. The
+    /// depth attachment is stored and left in `DEPTH_STENCIL_ATTACHMENT_OPTIMAL`.
+    pub fn with_formats(
+        device: &VulkanDevice,
+        format: vk::Format,
+        depth_format: Option<vk::Format>,
+        final_layout: vk::ImageLayout,
+    ) -> Result<Self> {
         let color_attachment = vk::AttachmentDescription::default()
-            .format(swapchain.format.format)
+            .format(format)
             .samples(vk::SampleCountFlags::TYPE_1)
             .load_op(vk::AttachmentLoadOp::CLEAR)
             .store_op(vk::AttachmentStoreOp::STORE)
             .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
             .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
             .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
+            .final_layout(final_layout);
 
         let color_attachment_ref = vk::AttachmentReference::default()
             .attachment(0)
             .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
 
-        let subpass = vk::SubpassDescription::default()
+        let mut attachments = vec![color_attachment];
+
+        let depth_attachment_ref = vk::AttachmentReference::default()
+            .attachment(1)
+            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+
+        let mut subpass = vk::SubpassDescription::default()
             .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
             .color_attachments(std::slice::from_ref(&color_attachment_ref));
 
-        let dependency = vk::SubpassDependency::default()
-            .src_subpass(vk::SUBPASS_EXTERNAL)
-            .dst_subpass(0)
-            .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-            .src_access_mask(vk::AccessFlags::empty())
-            .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-            .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE);
+        let mut stage_mask = vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT;
+        let mut access_mask = vk::AccessFlags::COLOR_ATTACHMENT_WRITE;
+
+        if let Some(depth_format) = depth_format {
+            attachments.push(
+                vk::AttachmentDescription::default()
+                    .format(depth_format)
+                    .samples(vk::SampleCountFlags::TYPE_1)
+                    .load_op(vk::AttachmentLoadOp::CLEAR)
+                    .store_op(vk::AttachmentStoreOp::STORE)
+                    .stencil_load_op(vk::AttachmentLoadOp::CLEAR)
+                    .stencil_store_op(vk::AttachmentStoreOp::STORE)
+                    .initial_layout(vk::ImageLayout::UNDEFINED)
+                    .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL),
+            );
+
+            subpass = subpass.depth_stencil_attachment(&depth_attachment_ref);
+            stage_mask |= vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+            access_mask |= vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE;
+        }
+
+        let mut dependencies = vec![
+            vk::SubpassDependency::default()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(stage_mask)
+                .src_access_mask(vk::AccessFlags::empty())
+                .dst_stage_mask(stage_mask)
+                .dst_access_mask(access_mask),
+        ];
+
+        if final_layout == vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL {
+            dependencies.push(
+                vk::SubpassDependency::default()
+                    .src_subpass(0)
+                    .dst_subpass(vk::SUBPASS_EXTERNAL)
+                    .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                    .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                    .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ),
+            );
+        }
 
         let render_pass_create_info = vk::RenderPassCreateInfo::default()
-            .attachments(std::slice::from_ref(&color_attachment))
+            .attachments(&attachments)
             .subpasses(std::slice::from_ref(&subpass))
-            .dependencies(std::slice::from_ref(&dependency));
+            .dependencies(&dependencies);
 
         let render_pass = unsafe {
             device
@@ -50,6 +119,10 @@ impl VulkanRenderPass {
 
         Ok(Self {
             render_pass,
+            attachment_formats: attachments
+                .iter()
+                .map(|attachment| attachment.format)
+                .collect(),
             device: device.device.clone(),
         })
     }
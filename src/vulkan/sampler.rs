@@ -0,0 +1,65 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::sync::Arc;
+
+use crate::vulkan::VulkanDevice;
+
+pub struct VulkanSampler {
+    pub sampler: vk::Sampler,
+    pub device: Arc<Device>,
+}
+
+impl VulkanSampler {
+    pub fn new(
+        device: &VulkanDevice,
+        filter: vk::Filter,
+        address_mode: vk::SamplerAddressMode,
+    ) -> Result<Self> {
+        let sampler_info = vk::SamplerCreateInfo::default()
+            .mag_filter(filter)
+            .min_filter(filter)
+            .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
+            .address_mode_u(address_mode)
+            .address_mode_v(address_mode)
+            .address_mode_w(address_mode)
+            .min_lod(0.0)
+            .max_lod(vk::LOD_CLAMP_NONE)
+            .border_color(vk::BorderColor::FLOAT_TRANSPARENT_BLACK);
+
+        let sampler = unsafe {
+            device
+                .device
+                .create_sampler(&sampler_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create sampler: {}", e))?
+        };
+
+        Ok(Self {
+            sampler,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn linear_clamp(device: &VulkanDevice) -> Result<Self> {
+        Self::new(
+            device,
+            vk::Filter::LINEAR,
+            vk::SamplerAddressMode::CLAMP_TO_EDGE,
+        )
+    }
+
+    pub fn nearest_clamp(device: &VulkanDevice) -> Result<Self> {
+        Self::new(
+            device,
+            vk::Filter::NEAREST,
+            vk::SamplerAddressMode::CLAMP_TO_EDGE,
+        )
+    }
+}
+
+impl Drop for VulkanSampler {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_sampler(self.sampler, None);
+        }
+    }
+}
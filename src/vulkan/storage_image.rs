@@ -0,0 +1,197 @@
+use anyhow::Result;
+use ash::vk;
+
+use crate::vulkan::{
+    ImageAccess, VulkanDescriptorPool, VulkanDevice, VulkanImage, VulkanPhysicalDevice,
+};
+
+/// Color image that compute shaders write through a `STORAGE_IMAGE` descriptor and later passes
+/// read, either with `imageLoad` (blur chains) or sampled (procedural textures). Tracks the
+/// layout and last access so each `cmd_prepare_*` records just the barrier that is needed.
+///
+/// Tracking follows recording order, so record uses of one image in the order they execute,
+/// e.g. on a single command buffer, and call `reset` when starting a new frame from scratch.
+pub struct StorageImage {
+    pub image: VulkanImage,
+    state: ImageAccess,
+}
+
+impl StorageImage {
+    /// Storage image usable as a sampled image and copy source too. `format` needs
+    /// `STORAGE_IMAGE` support with optimal tiling, e.g. `R8G8B8A8_UNORM` or
+    /// `R16G16B16A16_SFLOAT`; check with `VulkanPhysicalDevice::find_supported_format`.
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        extent: vk::Extent2D,
+        format: vk::Format,
+    ) -> Result<Self> {
+        let image = VulkanImage::new(
+            device,
+            physical_device,
+            extent,
+            format,
+            vk::ImageUsageFlags::STORAGE
+                | vk::ImageUsageFlags::SAMPLED
+                | vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::ImageAspectFlags::COLOR,
+        )?;
+
+        Ok(Self {
+            image,
+            state: ImageAccess::UNDEFINED,
+        })
+    }
+
+    pub fn view(&self) -> vk::ImageView {
+        self.image.view
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.image.extent
+    }
+
+    /// Layout after the barriers recorded so far.
+    pub fn layout(&self) -> vk::ImageLayout {
+        self.state.layout
+    }
+
+    /// Forgets the recorded layout, so the next `cmd_prepare_write` discards the contents.
+    pub fn reset(&mut self) {
+        self.state = ImageAccess::UNDEFINED;
+    }
+
+    /// Moves the image to `GENERAL` for `imageStore` (and `imageLoad`) in `stage`, waiting
+    /// for earlier reads and writes.
+    pub fn cmd_prepare_write(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        stage: vk::PipelineStageFlags,
+    ) {
+        self.cmd_access(
+            command_buffer,
+            ImageAccess {
+                layout: vk::ImageLayout::GENERAL,
+                stage,
+                access: vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+            },
+        );
+    }
+
+    /// Makes earlier writes visible to `imageLoad` in `stage`, staying in `GENERAL`, e.g. for
+    /// the next pass of a compute blur chain.
+    pub fn cmd_prepare_storage_read(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        stage: vk::PipelineStageFlags,
+    ) {
+        self.cmd_access(
+            command_buffer,
+            ImageAccess {
+                layout: vk::ImageLayout::GENERAL,
+                stage,
+                access: vk::AccessFlags::SHADER_READ,
+            },
+        );
+    }
+
+    /// Moves the image to `SHADER_READ_ONLY_OPTIMAL` for sampling in `stage`, after earlier
+    /// writes.
+    pub fn cmd_prepare_sampled_read(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        stage: vk::PipelineStageFlags,
+    ) {
+        self.cmd_access(
+            command_buffer,
+            ImageAccess {
+                layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                stage,
+                access: vk::AccessFlags::SHADER_READ,
+            },
+        );
+    }
+
+    /// Binds the image as a `STORAGE_IMAGE` descriptor, in `GENERAL`.
+    pub fn write_storage_descriptor(
+        &self,
+        descriptor_pool: &VulkanDescriptorPool,
+        set: vk::DescriptorSet,
+        binding: u32,
+    ) {
+        descriptor_pool.write_storage_image(set, binding, self.image.view);
+    }
+
+    /// Binds the image as a `COMBINED_IMAGE_SAMPLER` descriptor, in
+    /// `SHADER_READ_ONLY_OPTIMAL`; see `cmd_prepare_sampled_read`.
+    pub fn write_sampled_descriptor(
+        &self,
+        descriptor_pool: &VulkanDescriptorPool,
+        set: vk::DescriptorSet,
+        binding: u32,
+        sampler: vk::Sampler,
+    ) {
+        descriptor_pool.write_image(
+            set,
+            binding,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            self.image.view,
+            sampler,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+    }
+
+    fn cmd_access(&mut self, command_buffer: vk::CommandBuffer, next: ImageAccess) {
+        if self.state.needs_barrier(&next) {
+            self.image.cmd_transition(
+                command_buffer,
+                self.image.subresource_range(),
+                self.state.layout,
+                next.layout,
+                self.state.stage,
+                self.state.access,
+                next.stage,
+                next.access,
+            );
+            self.state = next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn barriers_only_around_writes_and_layout_changes() {
+        let compute = vk::PipelineStageFlags::COMPUTE_SHADER;
+        let write = ImageAccess::new(
+            vk::ImageLayout::GENERAL,
+            compute,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+        );
+        let load = ImageAccess::new(
+            vk::ImageLayout::GENERAL,
+            compute,
+            vk::AccessFlags::SHADER_READ,
+        );
+        let sample = ImageAccess::new(
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        );
+
+        assert!(ImageAccess::UNDEFINED.needs_barrier(&write));
+        assert!(write.needs_barrier(&write));
+        assert!(write.needs_barrier(&load));
+        assert!(!load.needs_barrier(&load));
+        assert!(load.needs_barrier(&ImageAccess::new(
+            vk::ImageLayout::GENERAL,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        )));
+        assert!(load.needs_barrier(&sample));
+        assert!(!sample.needs_barrier(&sample));
+        assert!(sample.needs_barrier(&write));
+    }
+}
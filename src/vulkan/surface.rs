@@ -11,10 +11,25 @@ pub struct VulkanSurface {
     pub surface_loader: ash::khr::surface::Instance,
 }
 
+/// Instance extensions needed for surfaces on `display`'s window system, for windows that
+/// don't come from winit.
+pub fn required_surface_extensions(display: &impl HasDisplayHandle) -> Result<Vec<*const i8>> {
+    let extensions = ash_window::enumerate_required_extensions(display.display_handle()?.as_raw())
+        .map_err(|e| anyhow::anyhow!("Failed to get surface extensions: {}", e))?;
+    Ok(extensions.to_vec())
+}
+
 impl VulkanSurface {
     pub fn new(instance: &VulkanInstance, vulkan_window: &VulkanWindow) -> Result<Self> {
-        let window = vulkan_window.window();
+        Self::from_window_handle(instance, vulkan_window.window())
+    }
 
+    /// Surface for any window exposing raw-window-handle handles, e.g. an SDL2 or GLFW window
+    /// created by a host application. The window must outlive the surface.
+    pub fn from_window_handle(
+        instance: &VulkanInstance,
+        window: &(impl HasDisplayHandle + HasWindowHandle),
+    ) -> Result<Self> {
         let surface = unsafe {
             ash_window::create_surface(
                 &instance.entry,
@@ -22,7 +37,8 @@ impl VulkanSurface {
                 window.display_handle()?.as_raw(),
                 window.window_handle()?.as_raw(),
                 None,
-            )?
+            )
+            .map_err(|e| anyhow::anyhow!("Failed to create surface: {}", e))?
         };
 
         let surface_loader = ash::khr::surface::Instance::new(&instance.entry, &instance.instance);
@@ -2,7 +2,134 @@ use anyhow::Result;
 use ash::{Device, vk};
 use std::sync::Arc;
 
-use crate::vulkan::{VulkanDevice, VulkanInstance, VulkanPhysicalDevice, VulkanSurface};
+use crate::vulkan::{
+    ColorWorkflow, VulkanDevice, VulkanInstance, VulkanPhysicalDevice, VulkanSurface,
+};
+
+pub const SUBOPTIMAL_POLICY_ENV: &str = "RVE_SUBOPTIMAL";
+
+/// What to do when acquire or present returns `VK_SUBOPTIMAL_KHR`: the swapchain still works
+/// but no longer matches the surface exactly, e.g. after a rotation or a compositor-driven
+/// resize. An out-of-date swapchain is always recreated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SuboptimalPolicy {
+    /// Drops the frame as soon as a suboptimal image is acquired and recreates the swapchain
+    /// before the next one, so nothing is shown stretched or in the old orientation.
+    RecreateImmediately,
+    /// Finishes and presents the frame, then recreates the swapchain before the next one.
+    #[default]
+    RecreateAtEndOfFrame,
+    /// Keeps presenting to the swapchain until it goes out of date. Avoids hitches from
+    /// recreation on platforms that report suboptimal often, at the cost of scaled output.
+    Ignore,
+}
+
+impl SuboptimalPolicy {
+    /// Reads `RVE_SUBOPTIMAL` (`immediate`, `end_of_frame` or `ignore`), defaulting to
+    /// `RecreateAtEndOfFrame`.
+    pub fn from_env() -> Result<Self> {
+        match std::env::var(SUBOPTIMAL_POLICY_ENV) {
+            Ok(value) => value.parse(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Whether a suboptimal acquire or present asks for the swapchain to be recreated.
+    pub fn recreates(self) -> bool {
+        self != Self::Ignore
+    }
+
+    /// Whether the frame that acquired a suboptimal image is dropped instead of presented.
+    pub fn drops_frame(self) -> bool {
+        self == Self::RecreateImmediately
+    }
+}
+
+impl std::str::FromStr for SuboptimalPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        match source
+            .trim()
+            .to_ascii_lowercase()
+            .replace('-', "_")
+            .as_str()
+        {
+            "immediate" | "immediately" => Ok(Self::RecreateImmediately),
+            "end_of_frame" | "frame_end" => Ok(Self::RecreateAtEndOfFrame),
+            "ignore" => Ok(Self::Ignore),
+            _ => Err(anyhow::anyhow!(
+                "Unknown suboptimal policy '{}', expected 'immediate', 'end_of_frame' or 'ignore'",
+                source
+            )),
+        }
+    }
+}
+
+/// Swapchain creation settings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SwapchainConfig {
+    /// Images to request, e.g. 2 for double and 3 for triple buffering, clamped to what the
+    /// surface supports. `None` asks for one more than the surface minimum.
+    pub min_image_count: Option<u32>,
+    /// Creates the images with `STORAGE` usage so compute shaders can write them directly, see
+    /// `VulkanSwapchain::cmd_prepare_compute_write`. sRGB formats rarely support storage, so
+    /// a UNORM format is picked instead and shaders have to encode sRGB themselves.
+    pub storage: bool,
+    /// Picks an sRGB or a UNORM format. Ignored with `storage`, which always needs
+    /// `ColorWorkflow::ManualEncode`.
+    pub workflow: ColorWorkflow,
+    /// Followed by the renderer and the present thread presenting to the swapchain.
+    pub suboptimal_policy: SuboptimalPolicy,
+    /// Swapchain being replaced, e.g. on resize. Lets the driver reuse its resources and keep
+    /// showing its last image until the new one presents; it must be destroyed afterwards.
+    pub old_swapchain: vk::SwapchainKHR,
+}
+
+impl SwapchainConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_min_image_count(mut self, count: u32) -> Self {
+        self.min_image_count = Some(count);
+        self
+    }
+
+    pub fn with_storage(mut self) -> Self {
+        self.storage = true;
+        self.workflow = ColorWorkflow::ManualEncode;
+        self
+    }
+
+    pub fn with_workflow(mut self, workflow: ColorWorkflow) -> Self {
+        self.workflow = workflow;
+        self
+    }
+
+    pub fn with_suboptimal_policy(mut self, policy: SuboptimalPolicy) -> Self {
+        self.suboptimal_policy = policy;
+        self
+    }
+
+    pub fn with_old_swapchain(mut self, old_swapchain: vk::SwapchainKHR) -> Self {
+        self.old_swapchain = old_swapchain;
+        self
+    }
+
+    /// Image count to request from a surface with `capabilities`.
+    pub fn image_count(&self, capabilities: &vk::SurfaceCapabilitiesKHR) -> u32 {
+        let requested = self
+            .min_image_count
+            .unwrap_or(capabilities.min_image_count + 1)
+            .max(capabilities.min_image_count);
+        if capabilities.max_image_count > 0 {
+            requested.min(capabilities.max_image_count)
+        } else {
+            requested
+        }
+    }
+}
 
 pub struct VulkanSwapchain {
     pub swapchain: vk::SwapchainKHR,
@@ -12,6 +139,12 @@ pub struct VulkanSwapchain {
     pub image_views: Vec<vk::ImageView>,
     pub format: vk::SurfaceFormatKHR,
     pub extent: vk::Extent2D,
+    /// Image count passed at creation. The driver may create more; see `image_count`.
+    pub requested_image_count: u32,
+    pub usage: vk::ImageUsageFlags,
+    /// Optimal tiling features of `format`, e.g. whether it can be blitted into.
+    pub format_features: vk::FormatFeatureFlags,
+    pub suboptimal_policy: SuboptimalPolicy,
 }
 
 impl VulkanSwapchain {
@@ -22,22 +155,81 @@ impl VulkanSwapchain {
         surface: &VulkanSurface,
         window_width: u32,
         window_height: u32,
+    ) -> Result<Self> {
+        Self::with_config(
+            instance,
+            device,
+            physical_device,
+            surface,
+            window_width,
+            window_height,
+            SwapchainConfig::default(),
+        )
+    }
+
+    pub fn with_config(
+        instance: &VulkanInstance,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        surface: &VulkanSurface,
+        window_width: u32,
+        window_height: u32,
+        config: SwapchainConfig,
     ) -> Result<Self> {
         let swapchain_loader = ash::khr::swapchain::Device::new(&instance.instance, &device.device);
 
-        let surface_format = Self::choose_surface_format(surface, physical_device)?;
+        let available_formats = surface.get_formats(physical_device)?;
+        let workflow = if config.storage {
+            ColorWorkflow::ManualEncode
+        } else {
+            config.workflow
+        };
+        let surface_format =
+            choose_surface_format(&available_formats, config.storage, workflow, |format| {
+                let properties = unsafe {
+                    instance.instance.get_physical_device_format_properties(
+                        physical_device.physical_device,
+                        format,
+                    )
+                };
+                properties
+                    .optimal_tiling_features
+                    .contains(vk::FormatFeatureFlags::STORAGE_IMAGE)
+            })?;
+
+        let format_features = unsafe {
+            instance
+                .instance
+                .get_physical_device_format_properties(
+                    physical_device.physical_device,
+                    surface_format.format,
+                )
+                .optimal_tiling_features
+        };
 
         let present_mode = Self::choose_present_mode(surface, physical_device)?;
 
         let extent = Self::choose_extent(surface, physical_device, window_width, window_height)?;
 
         let capabilities = surface.get_capabilities(physical_device)?;
-        let mut image_count = capabilities.min_image_count + 1;
-        if capabilities.max_image_count > 0 && image_count > capabilities.max_image_count {
-            image_count = capabilities.max_image_count;
+        let image_count = config.image_count(&capabilities);
+
+        // Transfer usage lets a `PresentThread` blit finished frames into the images.
+        let mut image_usage = vk::ImageUsageFlags::COLOR_ATTACHMENT
+            | (capabilities.supported_usage_flags & vk::ImageUsageFlags::TRANSFER_DST);
+        if config.storage {
+            if !capabilities
+                .supported_usage_flags
+                .contains(vk::ImageUsageFlags::STORAGE)
+            {
+                return Err(anyhow::anyhow!(
+                    "Surface does not support storage usage for swapchain images"
+                ));
+            }
+            image_usage |= vk::ImageUsageFlags::STORAGE;
         }
 
-        println!("Creating swapchain with {} images", image_count);
+        println!("Creating swapchain with at least {} images", image_count);
         println!(
             "Format: {:?}, Present mode: {:?}",
             surface_format, present_mode
@@ -51,12 +243,13 @@ impl VulkanSwapchain {
             .image_color_space(surface_format.color_space)
             .image_extent(extent)
             .image_array_layers(1)
-            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+            .image_usage(image_usage)
             .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
             .pre_transform(capabilities.current_transform)
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
             .present_mode(present_mode)
-            .clipped(true);
+            .clipped(true)
+            .old_swapchain(config.old_swapchain);
 
         let swapchain = unsafe { swapchain_loader.create_swapchain(&create_info, None)? };
 
@@ -64,6 +257,10 @@ impl VulkanSwapchain {
 
         let image_views = Self::create_image_views(&device.device, &images, surface_format.format)?;
 
+        if images.len() as u32 != image_count {
+            println!("Swapchain created {} images", images.len());
+        }
+
         Ok(Self {
             swapchain,
             swapchain_loader,
@@ -72,24 +269,93 @@ impl VulkanSwapchain {
             image_views,
             format: surface_format,
             extent,
+            requested_image_count: image_count,
+            usage: image_usage,
+            format_features,
+            suboptimal_policy: config.suboptimal_policy,
         })
     }
 
-    fn choose_surface_format(
-        surface: &VulkanSurface,
-        physical_device: &VulkanPhysicalDevice,
-    ) -> Result<vk::SurfaceFormatKHR> {
-        let available_formats = surface.get_formats(physical_device)?;
+    /// Number of images the driver actually created.
+    pub fn image_count(&self) -> usize {
+        self.images.len()
+    }
 
-        for format in &available_formats {
-            if format.format == vk::Format::B8G8R8A8_SRGB
-                && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
-            {
-                return Ok(*format);
-            }
-        }
+    /// Transitions image `image_index` to `GENERAL` for compute shader writes, discarding its
+    /// contents. Needs a swapchain created with `SwapchainConfig::with_storage`; the acquire
+    /// semaphore should then be waited on at `COMPUTE_SHADER`.
+    pub fn cmd_prepare_compute_write(&self, command_buffer: vk::CommandBuffer, image_index: usize) {
+        self.cmd_transition(
+            command_buffer,
+            image_index,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+            (
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::empty(),
+            ),
+            (
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::SHADER_WRITE,
+            ),
+        );
+    }
+
+    /// Transitions image `image_index` from `GENERAL` to `PRESENT_SRC_KHR` once compute
+    /// shaders finished writing it.
+    pub fn cmd_prepare_present(&self, command_buffer: vk::CommandBuffer, image_index: usize) {
+        self.cmd_transition(
+            command_buffer,
+            image_index,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+            (
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::SHADER_WRITE,
+            ),
+            (
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::AccessFlags::empty(),
+            ),
+        );
+    }
 
-        Ok(available_formats[0])
+    fn cmd_transition(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        image_index: usize,
+        old_layout: vk::ImageLayout,
+        new_layout: vk::ImageLayout,
+        (src_stage, src_access): (vk::PipelineStageFlags, vk::AccessFlags),
+        (dst_stage, dst_access): (vk::PipelineStageFlags, vk::AccessFlags),
+    ) {
+        let barrier = vk::ImageMemoryBarrier::default()
+            .old_layout(old_layout)
+            .new_layout(new_layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(self.images[image_index])
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .src_access_mask(src_access)
+            .dst_access_mask(dst_access);
+
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                std::slice::from_ref(&barrier),
+            );
+        }
     }
 
     fn choose_present_mode(
@@ -164,6 +430,63 @@ impl VulkanSwapchain {
 
         Ok(image_views)
     }
+
+    /// Workflow the chosen format implies, which may differ from the requested one when the
+    /// surface lacks a matching format.
+    pub fn workflow(&self) -> ColorWorkflow {
+        ColorWorkflow::for_target(self.format.format)
+    }
+}
+
+/// Prefers 8-bit sRGB formats, or 8-bit UNORM ones for `ColorWorkflow::ManualEncode`. For
+/// storage images, only formats `supports_storage` accepts are considered, preferring 8-bit
+/// UNORM ones.
+fn choose_surface_format(
+    available_formats: &[vk::SurfaceFormatKHR],
+    storage: bool,
+    workflow: ColorWorkflow,
+    supports_storage: impl Fn(vk::Format) -> bool,
+) -> Result<vk::SurfaceFormatKHR> {
+    if !storage {
+        let preferred = match workflow {
+            ColorWorkflow::SrgbTarget => [vk::Format::B8G8R8A8_SRGB, vk::Format::R8G8B8A8_SRGB],
+            ColorWorkflow::ManualEncode => [vk::Format::B8G8R8A8_UNORM, vk::Format::R8G8B8A8_UNORM],
+        };
+        return preferred
+            .iter()
+            .find_map(|preferred| {
+                available_formats.iter().find(|format| {
+                    format.format == *preferred
+                        && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
+                })
+            })
+            .or(available_formats.first())
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("Surface has no formats"));
+    }
+
+    let storage_formats: Vec<_> = available_formats
+        .iter()
+        .filter(|format| supports_storage(format.format))
+        .collect();
+    [vk::Format::B8G8R8A8_UNORM, vk::Format::R8G8B8A8_UNORM]
+        .iter()
+        .find_map(|preferred| {
+            storage_formats
+                .iter()
+                .find(|format| format.format == *preferred)
+        })
+        .or(storage_formats.first())
+        .map(|format| **format)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "No surface format supports storage images (available: {:?})",
+                available_formats
+                    .iter()
+                    .map(|format| format.format)
+                    .collect::<Vec<_>>()
+            )
+        })
 }
 
 impl Drop for VulkanSwapchain {
@@ -181,3 +504,102 @@ impl Drop for VulkanSwapchain {
         println!("Swapchain destroyed");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capabilities(min: u32, max: u32) -> vk::SurfaceCapabilitiesKHR {
+        vk::SurfaceCapabilitiesKHR {
+            min_image_count: min,
+            max_image_count: max,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn clamps_image_count_to_surface_limits() {
+        let default = SwapchainConfig::new();
+        assert_eq!(default.image_count(&capabilities(2, 8)), 3);
+        assert_eq!(default.image_count(&capabilities(3, 3)), 3);
+
+        let double = SwapchainConfig::new().with_min_image_count(2);
+        assert_eq!(double.image_count(&capabilities(2, 8)), 2);
+        assert_eq!(double.image_count(&capabilities(3, 8)), 3);
+
+        // No maximum.
+        let many = SwapchainConfig::new().with_min_image_count(6);
+        assert_eq!(many.image_count(&capabilities(2, 0)), 6);
+        assert_eq!(many.image_count(&capabilities(2, 4)), 4);
+    }
+
+    #[test]
+    fn parses_suboptimal_policies() {
+        assert_eq!(
+            "Immediate".parse::<SuboptimalPolicy>().unwrap(),
+            SuboptimalPolicy::RecreateImmediately
+        );
+        assert_eq!(
+            "end-of-frame".parse::<SuboptimalPolicy>().unwrap(),
+            SuboptimalPolicy::RecreateAtEndOfFrame
+        );
+        assert!("later".parse::<SuboptimalPolicy>().is_err());
+
+        assert!(SuboptimalPolicy::default().recreates());
+        assert!(!SuboptimalPolicy::default().drops_frame());
+        assert!(SuboptimalPolicy::RecreateImmediately.drops_frame());
+        assert!(!SuboptimalPolicy::Ignore.recreates());
+    }
+
+    #[test]
+    fn picks_storage_capable_surface_formats() {
+        let format = |format| vk::SurfaceFormatKHR {
+            format,
+            color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        };
+        let available = [
+            format(vk::Format::B8G8R8A8_SRGB),
+            format(vk::Format::A2B10G10R10_UNORM_PACK32),
+            format(vk::Format::B8G8R8A8_UNORM),
+        ];
+        let not_srgb = |format| format != vk::Format::B8G8R8A8_SRGB;
+
+        let srgb = ColorWorkflow::SrgbTarget;
+        assert_eq!(
+            choose_surface_format(&available, false, srgb, not_srgb).unwrap(),
+            available[0]
+        );
+        assert_eq!(
+            choose_surface_format(&available, true, srgb, not_srgb).unwrap(),
+            available[2]
+        );
+        assert_eq!(
+            choose_surface_format(&available[..2], true, srgb, not_srgb).unwrap(),
+            available[1]
+        );
+        assert!(choose_surface_format(&available[..1], true, srgb, not_srgb).is_err());
+    }
+
+    #[test]
+    fn picks_unorm_surface_formats_for_manual_encoding() {
+        let format = |format| vk::SurfaceFormatKHR {
+            format,
+            color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        };
+        let available = [
+            format(vk::Format::B8G8R8A8_SRGB),
+            format(vk::Format::R8G8B8A8_UNORM),
+        ];
+        let manual = ColorWorkflow::ManualEncode;
+
+        assert_eq!(
+            choose_surface_format(&available, false, manual, |_| true).unwrap(),
+            available[1]
+        );
+        // Falls back to whatever the surface offers.
+        assert_eq!(
+            choose_surface_format(&available[..1], false, manual, |_| true).unwrap(),
+            available[0]
+        );
+    }
+}
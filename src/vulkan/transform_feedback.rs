@@ -0,0 +1,340 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::sync::Arc;
+
+use crate::vulkan::{VulkanBuffer, VulkanDevice, VulkanPhysicalDevice, count_draw};
+
+/// Vertex written by `transform_capture.vert`: world-space position and normal, and uv.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CapturedVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+/// `xfb_stride` of `transform_capture.vert`.
+pub const CAPTURED_VERTEX_STRIDE: u32 = 32;
+
+/// Buffer receiving captured vertices, with the counter transform feedback uses to resume
+/// capture and to draw what was captured.
+pub struct TransformFeedbackBuffer {
+    pub buffer: VulkanBuffer,
+    pub counter: VulkanBuffer,
+}
+
+impl TransformFeedbackBuffer {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        size: vk::DeviceSize,
+    ) -> Result<Self> {
+        let buffer = VulkanBuffer::new(
+            device,
+            physical_device,
+            size,
+            vk::BufferUsageFlags::TRANSFORM_FEEDBACK_BUFFER_EXT
+                | vk::BufferUsageFlags::VERTEX_BUFFER
+                | vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let counter = VulkanBuffer::new(
+            device,
+            physical_device,
+            std::mem::size_of::<u32>() as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFORM_FEEDBACK_COUNTER_BUFFER_EXT
+                | vk::BufferUsageFlags::INDIRECT_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        Ok(Self { buffer, counter })
+    }
+
+    /// Makes captured vertices and the byte counter visible to later draws.
+    pub fn cmd_barrier(&self, command_buffer: vk::CommandBuffer) {
+        self.buffer.cmd_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFORM_FEEDBACK_EXT,
+            vk::AccessFlags::TRANSFORM_FEEDBACK_WRITE_EXT,
+            vk::PipelineStageFlags::VERTEX_INPUT | vk::PipelineStageFlags::VERTEX_SHADER,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ | vk::AccessFlags::SHADER_READ,
+        );
+        self.counter.cmd_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFORM_FEEDBACK_EXT,
+            vk::AccessFlags::TRANSFORM_FEEDBACK_COUNTER_WRITE_EXT,
+            vk::PipelineStageFlags::DRAW_INDIRECT | vk::PipelineStageFlags::TRANSFORM_FEEDBACK_EXT,
+            vk::AccessFlags::INDIRECT_COMMAND_READ
+                | vk::AccessFlags::TRANSFORM_FEEDBACK_COUNTER_READ_EXT,
+        );
+    }
+}
+
+/// Records draws whose vertex output is captured into buffers instead of, or on top of,
+/// being rasterized. Pipelines need `xfb_*` qualified outputs, and usually
+/// `with_rasterizer_discard` when only the capture matters.
+///
+/// Requires `VulkanDeviceFeatures::transform_feedback`.
+pub struct TransformFeedback {
+    loader: ash::ext::transform_feedback::Device,
+}
+
+impl TransformFeedback {
+    pub fn new(device: &VulkanDevice) -> Result<Self> {
+        let loader = device
+            .transform_feedback
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Device doesn't support transform feedback"))?;
+
+        Ok(Self { loader })
+    }
+
+    /// Records `pass` inside a render pass with `targets` bound to `xfb_buffer` 0, 1, ...
+    /// Capture starts at the beginning of each buffer, or after the previously captured data
+    /// when `resume` is set. Counters are written at the end for `cmd_draw_captured`.
+    pub fn record<R>(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        targets: &[&TransformFeedbackBuffer],
+        resume: bool,
+        pass: impl FnOnce(vk::CommandBuffer) -> R,
+    ) -> R {
+        let buffers: Vec<vk::Buffer> = targets.iter().map(|t| t.buffer.buffer).collect();
+        let offsets = vec![0; targets.len()];
+        let sizes: Vec<vk::DeviceSize> = targets.iter().map(|t| t.buffer.size).collect();
+        let counters: Vec<vk::Buffer> = targets.iter().map(|t| t.counter.buffer).collect();
+        let counter_offsets = vec![0; targets.len()];
+
+        let fp = self.loader.fp();
+        unsafe {
+            (fp.cmd_bind_transform_feedback_buffers_ext)(
+                command_buffer,
+                0,
+                buffers.len() as u32,
+                buffers.as_ptr(),
+                offsets.as_ptr(),
+                sizes.as_ptr(),
+            );
+
+            // Without counter buffers capture starts at the bound offsets.
+            let (counter_count, counter_ptr, counter_offset_ptr) = if resume {
+                (
+                    counters.len() as u32,
+                    counters.as_ptr(),
+                    counter_offsets.as_ptr(),
+                )
+            } else {
+                (0, std::ptr::null(), std::ptr::null())
+            };
+            (fp.cmd_begin_transform_feedback_ext)(
+                command_buffer,
+                0,
+                counter_count,
+                counter_ptr,
+                counter_offset_ptr,
+            );
+        }
+
+        let result = pass(command_buffer);
+
+        unsafe {
+            (fp.cmd_end_transform_feedback_ext)(
+                command_buffer,
+                0,
+                counters.len() as u32,
+                counters.as_ptr(),
+                counter_offsets.as_ptr(),
+            );
+        }
+
+        result
+    }
+
+    /// Draws the vertices last captured into `source`, read from its counter on the GPU.
+    /// `source.buffer` must be bound as the vertex buffer and `vertex_stride` must match the
+    /// capture stride.
+    pub fn cmd_draw_captured(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        source: &TransformFeedbackBuffer,
+        vertex_stride: u32,
+        instance_count: u32,
+    ) {
+        count_draw(instance_count);
+        unsafe {
+            (self.loader.fp().cmd_draw_indirect_byte_count_ext)(
+                command_buffer,
+                instance_count,
+                0,
+                source.counter.buffer,
+                0,
+                0,
+                vertex_stride,
+            );
+        }
+    }
+
+    /// Begins counting primitives on vertex stream 0 for query `index` of `queries`.
+    pub fn cmd_begin_query(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        queries: &PrimitiveQueries,
+        index: u32,
+    ) {
+        unsafe {
+            (self.loader.fp().cmd_begin_query_indexed_ext)(
+                command_buffer,
+                queries.query_pool,
+                index,
+                vk::QueryControlFlags::empty(),
+                0,
+            );
+        }
+    }
+
+    pub fn cmd_end_query(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        queries: &PrimitiveQueries,
+        index: u32,
+    ) {
+        unsafe {
+            (self.loader.fp().cmd_end_query_indexed_ext)(
+                command_buffer,
+                queries.query_pool,
+                index,
+                0,
+            );
+        }
+    }
+}
+
+/// Primitives counted by a transform feedback stream query.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrimitiveCounts {
+    /// Primitives written to the capture buffers.
+    pub written: u64,
+    /// Primitives generated, including those that didn't fit in the buffers.
+    pub generated: u64,
+}
+
+impl PrimitiveCounts {
+    /// Whether the capture buffers were too small for every primitive.
+    pub fn overflowed(&self) -> bool {
+        self.generated > self.written
+    }
+}
+
+/// Converts `[written, generated]` query results into counts.
+pub fn primitive_counts(results: &[[u64; 2]]) -> Vec<PrimitiveCounts> {
+    results
+        .iter()
+        .map(|&[written, generated]| PrimitiveCounts { written, generated })
+        .collect()
+}
+
+/// Transform feedback stream queries, recorded with `TransformFeedback::cmd_begin_query`,
+/// to debug procedural geometry and detect overflowing capture buffers.
+pub struct PrimitiveQueries {
+    pub query_pool: vk::QueryPool,
+    capacity: u32,
+    device: Arc<Device>,
+}
+
+impl PrimitiveQueries {
+    pub fn new(device: &VulkanDevice, capacity: u32) -> Result<Self> {
+        let pool_info = vk::QueryPoolCreateInfo::default()
+            .query_type(vk::QueryType::TRANSFORM_FEEDBACK_STREAM_EXT)
+            .query_count(capacity);
+
+        let query_pool = unsafe {
+            device
+                .device
+                .create_query_pool(&pool_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create query pool: {}", e))?
+        };
+
+        Ok(Self {
+            query_pool,
+            capacity,
+            device: device.device.clone(),
+        })
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Resets every query. Must be recorded outside a render pass, before the queries.
+    pub fn cmd_reset(&self, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            self.device
+                .cmd_reset_query_pool(command_buffer, self.query_pool, 0, self.capacity);
+        }
+    }
+
+    /// Counts of the first `count` queries, or `None` while some aren't available yet.
+    pub fn read(&self, count: u32) -> Result<Option<Vec<PrimitiveCounts>>> {
+        // Each query yields two values; ash reads one query per slice element.
+        let mut results = vec![[0u64; 2]; count as usize];
+        let result = unsafe {
+            self.device.get_query_pool_results(
+                self.query_pool,
+                0,
+                &mut results,
+                vk::QueryResultFlags::TYPE_64,
+            )
+        };
+
+        match result {
+            Ok(()) => Ok(Some(primitive_counts(&results))),
+            Err(vk::Result::NOT_READY) => Ok(None),
+            Err(e) => Err(anyhow::anyhow!("Failed to read primitive counts: {}", e)),
+        }
+    }
+}
+
+impl Drop for PrimitiveQueries {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_query_pool(self.query_pool, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captured_vertex_matches_shader_stride() {
+        assert_eq!(
+            std::mem::size_of::<CapturedVertex>() as u32,
+            CAPTURED_VERTEX_STRIDE
+        );
+    }
+
+    #[test]
+    fn splits_query_results_into_counts() {
+        let counts = primitive_counts(&[[10, 10], [4, 6]]);
+
+        assert_eq!(
+            counts,
+            [
+                PrimitiveCounts {
+                    written: 10,
+                    generated: 10
+                },
+                PrimitiveCounts {
+                    written: 4,
+                    generated: 6
+                },
+            ]
+        );
+        assert!(!counts[0].overflowed());
+        assert!(counts[1].overflowed());
+    }
+}
@@ -0,0 +1,339 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::sync::Arc;
+
+use crate::vulkan::{VulkanDevice, VulkanImage, VulkanImageInfo, VulkanPhysicalDevice};
+
+/// An image needed only between two passes of a frame, inclusive, by pass index.
+#[derive(Debug, Clone, Copy)]
+pub struct TransientImageDesc {
+    pub info: VulkanImageInfo,
+    pub first_pass: usize,
+    pub last_pass: usize,
+}
+
+impl TransientImageDesc {
+    pub fn new(info: VulkanImageInfo, first_pass: usize, last_pass: usize) -> Self {
+        Self {
+            info,
+            first_pass,
+            last_pass: last_pass.max(first_pass),
+        }
+    }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        self.first_pass <= other.last_pass && other.first_pass <= self.last_pass
+    }
+}
+
+/// Memory shared by images whose lifetimes don't overlap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AliasBlock {
+    pub size: vk::DeviceSize,
+    pub memory_type_bits: u32,
+    /// Indices of the images placed in the block, by first pass.
+    pub images: Vec<usize>,
+}
+
+/// Placement of transient images into shared memory blocks.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AliasPlan {
+    pub blocks: Vec<AliasBlock>,
+    /// Block of each image.
+    pub block_of: Vec<usize>,
+    /// Sum of the image sizes, the memory used without aliasing.
+    pub unaliased_size: vk::DeviceSize,
+}
+
+impl AliasPlan {
+    /// Places each image in the first block whose images it outlives or precedes and whose
+    /// memory types it accepts, largest images first so small ones fill in behind them.
+    pub fn new(descs: &[TransientImageDesc], requirements: &[vk::MemoryRequirements]) -> Self {
+        let mut order: Vec<usize> = (0..descs.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(requirements[i].size));
+
+        let mut plan = Self {
+            block_of: vec![0; descs.len()],
+            unaliased_size: requirements.iter().map(|r| r.size).sum(),
+            ..Default::default()
+        };
+        for image in order {
+            let requirement = requirements[image];
+            let block = plan.blocks.iter().position(|block| {
+                block.memory_type_bits & requirement.memory_type_bits != 0
+                    && block
+                        .images
+                        .iter()
+                        .all(|&other| !descs[other].overlaps(&descs[image]))
+            });
+            let block = match block {
+                Some(block) => block,
+                None => {
+                    plan.blocks.push(AliasBlock {
+                        size: 0,
+                        memory_type_bits: requirement.memory_type_bits,
+                        images: Vec::new(),
+                    });
+                    plan.blocks.len() - 1
+                }
+            };
+
+            let block_info = &mut plan.blocks[block];
+            // Every image is bound at offset 0, so the block only needs the strictest
+            // alignment for its allocation, which `vkAllocateMemory` always satisfies.
+            block_info.size = block_info.size.max(requirement.size);
+            block_info.memory_type_bits &= requirement.memory_type_bits;
+            block_info.images.push(image);
+            plan.block_of[image] = block;
+        }
+        for block in &mut plan.blocks {
+            block.images.sort_by_key(|&i| descs[i].first_pass);
+        }
+        plan
+    }
+
+    /// Memory used with aliasing.
+    pub fn aliased_size(&self) -> vk::DeviceSize {
+        self.blocks.iter().map(|block| block.size).sum()
+    }
+
+    /// True if `image` reuses memory an earlier image of the frame wrote.
+    pub fn follows_alias(&self, image: usize) -> bool {
+        self.blocks[self.block_of[image]].images.first() != Some(&image)
+    }
+}
+
+impl std::fmt::Display for AliasPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mib = |bytes: vk::DeviceSize| bytes as f64 / (1024.0 * 1024.0);
+        write!(
+            f,
+            "{} transient images in {} blocks: {:.1} MiB instead of {:.1} MiB",
+            self.block_of.len(),
+            self.blocks.len(),
+            mib(self.aliased_size()),
+            mib(self.unaliased_size)
+        )
+    }
+}
+
+/// Transient render targets for one frame's passes, sharing memory between images whose
+/// lifetimes don't overlap, e.g. the intermediate targets of a long post-processing chain.
+///
+/// Aliased images lose their contents when another image in the same memory is written, so
+/// each must start its lifetime with `cmd_begin`, which discards the old contents and waits
+/// for the previous occupant's last use.
+pub struct TransientImagePool {
+    images: Vec<VulkanImage>,
+    plan: AliasPlan,
+    memory: Vec<vk::DeviceMemory>,
+    device: Arc<Device>,
+}
+
+impl TransientImagePool {
+    pub fn new(
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        descs: &[TransientImageDesc],
+    ) -> Result<Self> {
+        let mut pool = Self {
+            images: Vec::with_capacity(descs.len()),
+            plan: AliasPlan::default(),
+            memory: Vec::new(),
+            device: device.device.clone(),
+        };
+
+        // On failure, dropping `raw` and `pool` cleans up what was created so far.
+        let mut raw = RawImages {
+            images: Vec::with_capacity(descs.len()),
+            device: &device.device,
+        };
+        for desc in descs {
+            raw.images
+                .push(VulkanImage::create_unbound(device, &desc.info)?);
+        }
+        let requirements: Vec<vk::MemoryRequirements> = raw
+            .images
+            .iter()
+            .map(|&image| unsafe { device.device.get_image_memory_requirements(image) })
+            .collect();
+        pool.plan = AliasPlan::new(descs, &requirements);
+
+        for block in &pool.plan.blocks {
+            let memory_type_index = physical_device.find_memory_type(
+                block.memory_type_bits,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?;
+            let alloc_info = vk::MemoryAllocateInfo::default()
+                .allocation_size(block.size)
+                .memory_type_index(memory_type_index);
+            let memory = unsafe {
+                device
+                    .device
+                    .allocate_memory(&alloc_info, None)
+                    .map_err(|e| anyhow::anyhow!("Failed to allocate transient memory: {}", e))?
+            };
+            pool.memory.push(memory);
+        }
+
+        for (index, &image) in raw.images.iter().enumerate() {
+            unsafe {
+                device
+                    .device
+                    .bind_image_memory(image, pool.memory[pool.plan.block_of[index]], 0)
+                    .map_err(|e| anyhow::anyhow!("Failed to bind transient image memory: {}", e))?
+            };
+        }
+        // Popped in order; whatever isn't wrapped yet is still destroyed by `raw`.
+        raw.images.reverse();
+        while let Some(image) = raw.images.pop() {
+            let index = pool.images.len();
+            pool.images
+                .push(VulkanImage::from_bound(device, image, &descs[index].info)?);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn image(&self, index: usize) -> &VulkanImage {
+        &self.images[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    pub fn plan(&self) -> &AliasPlan {
+        &self.plan
+    }
+
+    /// Starts `index`'s lifetime by transitioning it from `UNDEFINED` to `layout`. If it
+    /// shares memory with an earlier image of the frame, the barrier also waits for all
+    /// earlier work to finish with that memory; otherwise only for the previous frame's
+    /// reads through `src_stage`.
+    pub fn cmd_begin(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        index: usize,
+        layout: vk::ImageLayout,
+        dst_stage: vk::PipelineStageFlags,
+        dst_access: vk::AccessFlags,
+    ) {
+        let (src_stage, src_access) = if self.plan.follows_alias(index) {
+            (
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::AccessFlags::MEMORY_WRITE,
+            )
+        } else {
+            (dst_stage, vk::AccessFlags::empty())
+        };
+        let image = &self.images[index];
+        image.cmd_transition(
+            command_buffer,
+            image.subresource_range(),
+            vk::ImageLayout::UNDEFINED,
+            layout,
+            src_stage,
+            src_access,
+            dst_stage,
+            dst_access,
+        );
+    }
+}
+
+impl Drop for TransientImagePool {
+    fn drop(&mut self) {
+        self.images.clear();
+        unsafe {
+            for &memory in &self.memory {
+                self.device.free_memory(memory, None);
+            }
+        }
+    }
+}
+
+/// Unbound images destroyed if pool creation fails before they are wrapped.
+struct RawImages<'a> {
+    images: Vec<vk::Image>,
+    device: &'a Device,
+}
+
+impl Drop for RawImages<'_> {
+    fn drop(&mut self) {
+        for &image in &self.images {
+            unsafe { self.device.destroy_image(image, None) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desc(first_pass: usize, last_pass: usize) -> TransientImageDesc {
+        TransientImageDesc::new(
+            VulkanImageInfo::new(
+                vk::Extent2D {
+                    width: 1,
+                    height: 1,
+                },
+                vk::Format::R16G16B16A16_SFLOAT,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT,
+                vk::ImageAspectFlags::COLOR,
+            ),
+            first_pass,
+            last_pass,
+        )
+    }
+
+    fn requirement(size: vk::DeviceSize, memory_type_bits: u32) -> vk::MemoryRequirements {
+        vk::MemoryRequirements {
+            size,
+            alignment: 256,
+            memory_type_bits,
+        }
+    }
+
+    #[test]
+    fn aliases_disjoint_lifetimes() {
+        // A post chain: bloom downsample (0-1), blur (1-2), DoF (2-3), final (3-4).
+        let descs = [desc(0, 1), desc(1, 2), desc(2, 3), desc(3, 4)];
+        let requirements = [
+            requirement(100, 0b11),
+            requirement(400, 0b11),
+            requirement(200, 0b11),
+            requirement(300, 0b11),
+        ];
+        let plan = AliasPlan::new(&descs, &requirements);
+
+        assert_eq!(plan.blocks.len(), 2);
+        assert_eq!(plan.block_of, vec![1, 0, 1, 0]);
+        assert_eq!(plan.blocks[0].images, vec![1, 3]);
+        assert_eq!(plan.blocks[1].images, vec![0, 2]);
+        assert_eq!(plan.aliased_size(), 600);
+        assert_eq!(plan.unaliased_size, 1000);
+        assert!(!plan.follows_alias(0) && !plan.follows_alias(1));
+        assert!(plan.follows_alias(2) && plan.follows_alias(3));
+    }
+
+    #[test]
+    fn keeps_incompatible_memory_apart() {
+        let descs = [desc(0, 0), desc(1, 1), desc(2, 2)];
+        let requirements = [
+            requirement(100, 0b01),
+            requirement(100, 0b10),
+            requirement(50, 0b11),
+        ];
+        let plan = AliasPlan::new(&descs, &requirements);
+
+        assert_eq!(plan.blocks.len(), 2);
+        assert_eq!(plan.blocks[0].images, vec![0, 2]);
+        assert_eq!(plan.blocks[0].memory_type_bits, 0b01);
+        assert_eq!(plan.blocks[1].images, vec![1]);
+        assert_eq!(AliasPlan::new(&[], &[]).aliased_size(), 0);
+    }
+}
@@ -0,0 +1,543 @@
+use anyhow::Result;
+use ash::{Device, vk};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::vulkan::{VulkanBuffer, VulkanDevice, VulkanImage, VulkanPhysicalDevice};
+
+/// Identifies one submitted upload batch.
+pub type UploadBatchId = u64;
+
+/// Bytes uploaded by writing host-visible VRAM directly versus through staging copies.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct UploadStats {
+    pub direct_bytes: u64,
+    pub direct_uploads: u32,
+    pub staged_bytes: u64,
+    pub staged_uploads: u32,
+}
+
+impl std::fmt::Display for UploadStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+        write!(
+            f,
+            "{:.1} MiB in {} direct writes, {:.1} MiB in {} staged copies",
+            mib(self.direct_bytes),
+            self.direct_uploads,
+            mib(self.staged_bytes),
+            self.staged_uploads
+        )
+    }
+}
+
+enum OwnershipAcquire {
+    Buffer {
+        buffer: vk::Buffer,
+        dst_stage: vk::PipelineStageFlags,
+        dst_access: vk::AccessFlags,
+    },
+    Image {
+        image: vk::Image,
+        range: vk::ImageSubresourceRange,
+    },
+}
+
+struct UploadBatch {
+    id: UploadBatchId,
+    command_buffer: vk::CommandBuffer,
+    fence: vk::Fence,
+    staging: Vec<VulkanBuffer>,
+    acquires: Vec<OwnershipAcquire>,
+}
+
+/// Batches staging copies into device-local buffers and images and submits them on the
+/// transfer queue, falling back to the graphics queue when the device has no separate one.
+///
+/// Uploads are recorded into an open batch until `submit`; `poll` reports finished batches
+/// and frees their staging memory. With a dedicated transfer family, ownership of every
+/// destination is released to the graphics family, and `record_acquires` must be recorded on
+/// the graphics queue before the resources are used.
+pub struct UploadBatcher {
+    queue: vk::Queue,
+    queue_family: u32,
+    graphics_family: u32,
+    command_pool: vk::CommandPool,
+    recording: Option<UploadBatch>,
+    in_flight: VecDeque<UploadBatch>,
+    pending_acquires: Vec<OwnershipAcquire>,
+    free_command_buffers: Vec<(vk::CommandBuffer, vk::Fence)>,
+    next_id: UploadBatchId,
+    stats: UploadStats,
+    device: Arc<Device>,
+}
+
+impl UploadBatcher {
+    pub fn new(device: &VulkanDevice) -> Result<Self> {
+        let families = &device.queue_family_indices;
+        let graphics_family = families
+            .graphics_family
+            .ok_or_else(|| anyhow::anyhow!("Device has no graphics queue family"))?;
+        let (queue_family, queue) = match (families.transfer_family, device.transfer_queue) {
+            (Some(family), Some(queue)) => (family, queue),
+            _ => (graphics_family, device.graphics_queue),
+        };
+
+        let pool_info = vk::CommandPoolCreateInfo::default()
+            .flags(
+                vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER
+                    | vk::CommandPoolCreateFlags::TRANSIENT,
+            )
+            .queue_family_index(queue_family);
+
+        let command_pool = unsafe {
+            device
+                .device
+                .create_command_pool(&pool_info, None)
+                .map_err(|e| anyhow::anyhow!("Failed to create upload command pool: {}", e))?
+        };
+
+        Ok(Self {
+            queue,
+            queue_family,
+            graphics_family,
+            command_pool,
+            recording: None,
+            in_flight: VecDeque::new(),
+            pending_acquires: Vec::new(),
+            free_command_buffers: Vec::new(),
+            next_id: 0,
+            stats: UploadStats::default(),
+            device: device.device.clone(),
+        })
+    }
+
+    /// True if uploads run on a different queue family than graphics and need
+    /// `record_acquires`.
+    pub fn transfers_ownership(&self) -> bool {
+        self.queue_family != self.graphics_family
+    }
+
+    pub fn stats(&self) -> UploadStats {
+        self.stats
+    }
+
+    /// Id the open batch will have once submitted.
+    pub fn current_batch(&self) -> UploadBatchId {
+        self.next_id
+    }
+
+    /// Copies `data` into `dst` at offset 0. `dst_stage`/`dst_access` describe the first use
+    /// on the graphics queue, e.g. vertex input for vertex buffers.
+    ///
+    /// Buffers in host-visible VRAM (see `VulkanBuffer::new_streaming`) are written directly,
+    /// without a copy or ownership transfer; the write is visible to the next submission. The
+    /// GPU must not be using `dst` either way.
+    pub fn upload_buffer<T: Copy>(
+        &mut self,
+        physical_device: &VulkanPhysicalDevice,
+        device: &VulkanDevice,
+        dst: &VulkanBuffer,
+        data: &[T],
+        dst_stage: vk::PipelineStageFlags,
+        dst_access: vk::AccessFlags,
+    ) -> Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let size = std::mem::size_of_val(data) as u64;
+        if dst.is_host_writable_vram() {
+            dst.write(0, data)?;
+            self.stats.direct_bytes += size;
+            self.stats.direct_uploads += 1;
+            return Ok(());
+        }
+        self.stats.staged_bytes += size;
+        self.stats.staged_uploads += 1;
+
+        let staging = self.staging(device, physical_device, data)?;
+        let command_buffer = self.begin()?;
+        let ownership = self.ownership();
+
+        let region = vk::BufferCopy::default().size(std::mem::size_of_val(data) as vk::DeviceSize);
+        let barrier = vk::BufferMemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(if ownership.is_some() {
+                vk::AccessFlags::empty()
+            } else {
+                dst_access
+            })
+            .src_queue_family_index(ownership.map_or(vk::QUEUE_FAMILY_IGNORED, |o| o.0))
+            .dst_queue_family_index(ownership.map_or(vk::QUEUE_FAMILY_IGNORED, |o| o.1))
+            .buffer(dst.buffer)
+            .offset(0)
+            .size(vk::WHOLE_SIZE);
+
+        unsafe {
+            self.device.cmd_copy_buffer(
+                command_buffer,
+                staging.buffer,
+                dst.buffer,
+                std::slice::from_ref(&region),
+            );
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                if ownership.is_some() {
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE
+                } else {
+                    dst_stage
+                },
+                vk::DependencyFlags::empty(),
+                &[],
+                std::slice::from_ref(&barrier),
+                &[],
+            );
+        }
+
+        let batch = self.recording.as_mut().expect("batch is open");
+        batch.staging.push(staging);
+        if ownership.is_some() {
+            batch.acquires.push(OwnershipAcquire::Buffer {
+                buffer: dst.buffer,
+                dst_stage,
+                dst_access,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Copies tightly packed texels into mip 0, layer 0 of `dst` and leaves it in
+    /// `SHADER_READ_ONLY_OPTIMAL` for fragment shaders. Block-compressed formats are copied
+    /// as-is.
+    pub fn upload_image(
+        &mut self,
+        physical_device: &VulkanPhysicalDevice,
+        device: &VulkanDevice,
+        dst: &VulkanImage,
+        data: &[u8],
+    ) -> Result<()> {
+        self.upload_layers(physical_device, device, dst, data, 1)
+    }
+
+    /// Like `upload_image` for every array layer of `dst`: `data` holds the layers one after
+    /// another, each the same size, e.g. `TextureArrayAsset::data`.
+    pub fn upload_image_layers(
+        &mut self,
+        physical_device: &VulkanPhysicalDevice,
+        device: &VulkanDevice,
+        dst: &VulkanImage,
+        data: &[u8],
+    ) -> Result<()> {
+        let layers = dst.array_layers as usize;
+        if data.is_empty() || !data.len().is_multiple_of(layers) {
+            return Err(anyhow::anyhow!(
+                "Texture data of {} bytes doesn't split into {} layers",
+                data.len(),
+                layers
+            ));
+        }
+        self.upload_layers(physical_device, device, dst, data, dst.array_layers)
+    }
+
+    fn upload_layers(
+        &mut self,
+        physical_device: &VulkanPhysicalDevice,
+        device: &VulkanDevice,
+        dst: &VulkanImage,
+        data: &[u8],
+        layer_count: u32,
+    ) -> Result<()> {
+        let layer_size = (data.len() / layer_count as usize) as vk::DeviceSize;
+        let staging = self.staging(device, physical_device, data)?;
+        let command_buffer = self.begin()?;
+        let ownership = self.ownership();
+        let range = dst.subresource_range();
+
+        dst.cmd_transition(
+            command_buffer,
+            range,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::AccessFlags::empty(),
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+        );
+
+        let regions: Vec<vk::BufferImageCopy> = (0..layer_count)
+            .map(|layer| {
+                vk::BufferImageCopy::default()
+                    .buffer_offset(layer as vk::DeviceSize * layer_size)
+                    .image_subresource(vk::ImageSubresourceLayers {
+                        aspect_mask: dst.aspect_mask,
+                        mip_level: 0,
+                        base_array_layer: layer,
+                        layer_count: 1,
+                    })
+                    .image_extent(vk::Extent3D {
+                        width: dst.extent.width,
+                        height: dst.extent.height,
+                        depth: dst.depth,
+                    })
+            })
+            .collect();
+
+        let barrier = vk::ImageMemoryBarrier::default()
+            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .src_queue_family_index(ownership.map_or(vk::QUEUE_FAMILY_IGNORED, |o| o.0))
+            .dst_queue_family_index(ownership.map_or(vk::QUEUE_FAMILY_IGNORED, |o| o.1))
+            .image(dst.image)
+            .subresource_range(range)
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(if ownership.is_some() {
+                vk::AccessFlags::empty()
+            } else {
+                vk::AccessFlags::SHADER_READ
+            });
+
+        unsafe {
+            self.device.cmd_copy_buffer_to_image(
+                command_buffer,
+                staging.buffer,
+                dst.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &regions,
+            );
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                if ownership.is_some() {
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE
+                } else {
+                    vk::PipelineStageFlags::FRAGMENT_SHADER
+                },
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                std::slice::from_ref(&barrier),
+            );
+        }
+
+        let batch = self.recording.as_mut().expect("batch is open");
+        batch.staging.push(staging);
+        if ownership.is_some() {
+            batch.acquires.push(OwnershipAcquire::Image {
+                image: dst.image,
+                range,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Submits the open batch, if any, and returns its id.
+    pub fn submit(&mut self) -> Result<Option<UploadBatchId>> {
+        let Some(batch) = self.recording.take() else {
+            return Ok(None);
+        };
+
+        unsafe {
+            self.device
+                .end_command_buffer(batch.command_buffer)
+                .map_err(|e| anyhow::anyhow!("Failed to end upload command buffer: {}", e))?;
+
+            let submit_info = vk::SubmitInfo::default()
+                .command_buffers(std::slice::from_ref(&batch.command_buffer));
+            self.device
+                .queue_submit(self.queue, &[submit_info], batch.fence)
+                .map_err(|e| anyhow::anyhow!("Failed to submit uploads: {}", e))?;
+        }
+
+        let id = batch.id;
+        self.in_flight.push_back(batch);
+        Ok(Some(id))
+    }
+
+    /// Returns the ids of batches that finished executing, oldest first, and frees their
+    /// staging buffers.
+    pub fn poll(&mut self) -> Result<Vec<UploadBatchId>> {
+        let mut completed = Vec::new();
+
+        while let Some(batch) = self.in_flight.front() {
+            let signaled = unsafe {
+                self.device
+                    .get_fence_status(batch.fence)
+                    .map_err(|e| anyhow::anyhow!("Failed to query upload fence: {}", e))?
+            };
+            if !signaled {
+                break;
+            }
+
+            let batch = self.in_flight.pop_front().expect("front exists");
+            completed.push(batch.id);
+            self.pending_acquires.extend(batch.acquires);
+            self.free_command_buffers
+                .push((batch.command_buffer, batch.fence));
+        }
+
+        Ok(completed)
+    }
+
+    /// Blocks until every submitted batch has finished.
+    pub fn wait_idle(&mut self) -> Result<Vec<UploadBatchId>> {
+        let fences: Vec<vk::Fence> = self.in_flight.iter().map(|b| b.fence).collect();
+        if !fences.is_empty() {
+            unsafe {
+                self.device
+                    .wait_for_fences(&fences, true, u64::MAX)
+                    .map_err(|e| anyhow::anyhow!("Failed to wait for uploads: {}", e))?;
+            }
+        }
+        self.poll()
+    }
+
+    /// Records queue family acquire barriers for every completed upload on a graphics
+    /// command buffer. Does nothing when uploads share the graphics queue family.
+    pub fn record_acquires(&mut self, command_buffer: vk::CommandBuffer) {
+        for acquire in self.pending_acquires.drain(..) {
+            match acquire {
+                OwnershipAcquire::Buffer {
+                    buffer,
+                    dst_stage,
+                    dst_access,
+                } => {
+                    let barrier = vk::BufferMemoryBarrier::default()
+                        .dst_access_mask(dst_access)
+                        .src_queue_family_index(self.queue_family)
+                        .dst_queue_family_index(self.graphics_family)
+                        .buffer(buffer)
+                        .offset(0)
+                        .size(vk::WHOLE_SIZE);
+                    unsafe {
+                        self.device.cmd_pipeline_barrier(
+                            command_buffer,
+                            vk::PipelineStageFlags::TOP_OF_PIPE,
+                            dst_stage,
+                            vk::DependencyFlags::empty(),
+                            &[],
+                            std::slice::from_ref(&barrier),
+                            &[],
+                        );
+                    }
+                }
+                OwnershipAcquire::Image { image, range } => {
+                    let barrier = vk::ImageMemoryBarrier::default()
+                        .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                        .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                        .src_queue_family_index(self.queue_family)
+                        .dst_queue_family_index(self.graphics_family)
+                        .image(image)
+                        .subresource_range(range)
+                        .dst_access_mask(vk::AccessFlags::SHADER_READ);
+                    unsafe {
+                        self.device.cmd_pipeline_barrier(
+                            command_buffer,
+                            vk::PipelineStageFlags::TOP_OF_PIPE,
+                            vk::PipelineStageFlags::FRAGMENT_SHADER,
+                            vk::DependencyFlags::empty(),
+                            &[],
+                            &[],
+                            std::slice::from_ref(&barrier),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn ownership(&self) -> Option<(u32, u32)> {
+        self.transfers_ownership()
+            .then_some((self.queue_family, self.graphics_family))
+    }
+
+    fn staging<T: Copy>(
+        &self,
+        device: &VulkanDevice,
+        physical_device: &VulkanPhysicalDevice,
+        data: &[T],
+    ) -> Result<VulkanBuffer> {
+        let staging = VulkanBuffer::new_host_visible(
+            device,
+            physical_device,
+            std::mem::size_of_val(data).max(4) as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+        )?;
+        staging.write(0, data)?;
+        Ok(staging)
+    }
+
+    fn begin(&mut self) -> Result<vk::CommandBuffer> {
+        if let Some(batch) = &self.recording {
+            return Ok(batch.command_buffer);
+        }
+
+        let (command_buffer, fence) = match self.free_command_buffers.pop() {
+            Some((command_buffer, fence)) => {
+                unsafe {
+                    self.device
+                        .reset_fences(&[fence])
+                        .map_err(|e| anyhow::anyhow!("Failed to reset upload fence: {}", e))?;
+                    self.device
+                        .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())
+                        .map_err(|e| {
+                            anyhow::anyhow!("Failed to reset upload command buffer: {}", e)
+                        })?;
+                }
+                (command_buffer, fence)
+            }
+            None => unsafe {
+                let alloc_info = vk::CommandBufferAllocateInfo::default()
+                    .command_pool(self.command_pool)
+                    .level(vk::CommandBufferLevel::PRIMARY)
+                    .command_buffer_count(1);
+                let command_buffer =
+                    self.device
+                        .allocate_command_buffers(&alloc_info)
+                        .map_err(|e| {
+                            anyhow::anyhow!("Failed to allocate upload command buffer: {}", e)
+                        })?[0];
+                let fence = self
+                    .device
+                    .create_fence(&vk::FenceCreateInfo::default(), None)
+                    .map_err(|e| anyhow::anyhow!("Failed to create upload fence: {}", e))?;
+                (command_buffer, fence)
+            },
+        };
+
+        let begin_info = vk::CommandBufferBeginInfo::default()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        unsafe {
+            self.device
+                .begin_command_buffer(command_buffer, &begin_info)
+                .map_err(|e| anyhow::anyhow!("Failed to begin upload command buffer: {}", e))?;
+        }
+
+        self.recording = Some(UploadBatch {
+            id: self.next_id,
+            command_buffer,
+            fence,
+            staging: Vec::new(),
+            acquires: Vec::new(),
+        });
+        self.next_id += 1;
+
+        Ok(command_buffer)
+    }
+}
+
+impl Drop for UploadBatcher {
+    fn drop(&mut self) {
+        let _ = self.submit();
+        let _ = self.wait_idle();
+
+        unsafe {
+            for (_, fence) in self.free_command_buffers.drain(..) {
+                self.device.destroy_fence(fence, None);
+            }
+            self.device.destroy_command_pool(self.command_pool, None);
+        }
+    }
+}
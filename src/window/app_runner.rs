@@ -0,0 +1,221 @@
+use anyhow::Result;
+use std::time::Instant;
+use winit::application::ApplicationHandler;
+use winit::dpi::LogicalSize;
+use winit::event::{DeviceEvent, DeviceId, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::window::{Window, WindowId};
+
+use crate::{BackgroundMode, FrameSchedule, PowerSaver, RedrawMode, VulkanWindow};
+
+/// Callbacks driven by an `AppRunner`. Only `on_init` is required.
+pub trait AppCallbacks {
+    /// Called once the window exists; create the instance, surface and renderer here. An error
+    /// exits the event loop and is returned from `AppRunner::run`.
+    fn on_init(&mut self, window: &VulkanWindow) -> Result<()>;
+
+    /// Called once per frame before `on_render`, with the seconds since the previous frame.
+    fn on_update(&mut self, _window: &VulkanWindow, _delta_time: f32) {}
+
+    /// Called when the window needs a new frame. Errors are reported and the loop continues.
+    /// With `RedrawMode::OnDemand`, call `VulkanWindow::request_redraw` to get one.
+    fn on_render(&mut self, _window: &VulkanWindow) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when the window's inner size changes, in physical pixels.
+    fn on_resize(&mut self, _window: &VulkanWindow, _width: u32, _height: u32) {}
+
+    /// Called for every window event, before the runner handles it, e.g. for input or a UI.
+    fn on_event(&mut self, _window: &VulkanWindow, _event: &WindowEvent) {}
+
+    /// Called for raw device input while the window is focused, e.g. unaccelerated
+    /// `DeviceEvent::MouseMotion` for mouse-look with a locked cursor.
+    fn on_device_event(&mut self, _window: &VulkanWindow, _event: &DeviceEvent) {}
+
+    /// Called before the event loop exits, while the window still exists; wait for the GPU to
+    /// go idle here.
+    fn on_exit(&mut self) {}
+}
+
+/// Owns the winit event loop and window and forwards events to `AppCallbacks`, so binaries
+/// don't each re-implement `ApplicationHandler`.
+pub struct AppRunner {
+    title: String,
+    size: LogicalSize<f64>,
+    resizable: bool,
+    background_mode: BackgroundMode,
+    redraw_mode: RedrawMode,
+}
+
+impl Default for AppRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AppRunner {
+    pub fn new() -> Self {
+        Self {
+            title: "Vulkan Experiments".to_string(),
+            size: LogicalSize::new(1280.0, 720.0),
+            resizable: true,
+            background_mode: BackgroundMode::default(),
+            redraw_mode: RedrawMode::default(),
+        }
+    }
+
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    pub fn with_size(mut self, width: f64, height: f64) -> Self {
+        self.size = LogicalSize::new(width, height);
+        self
+    }
+
+    pub fn with_resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    /// Renders less or not at all while the window is unfocused or occluded.
+    pub fn with_background_mode(mut self, mode: BackgroundMode) -> Self {
+        self.background_mode = mode;
+        self
+    }
+
+    /// Renders continuously, or only when the application requests it.
+    pub fn with_redraw_mode(mut self, mode: RedrawMode) -> Self {
+        self.redraw_mode = mode;
+        self
+    }
+
+    /// Runs `app` until the window is closed or `on_init` fails.
+    pub fn run<A: AppCallbacks>(self, app: A) -> Result<()> {
+        let event_loop =
+            EventLoop::new().map_err(|e| anyhow::anyhow!("Failed to create event loop: {}", e))?;
+
+        let mut handler = RunnerHandler {
+            app,
+            window: None,
+            power_saver: PowerSaver::new(self.background_mode),
+            runner: self,
+            last_frame: None,
+            error: None,
+        };
+        event_loop
+            .run_app(&mut handler)
+            .map_err(|e| anyhow::anyhow!("Event loop failed: {}", e))?;
+
+        match handler.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+struct RunnerHandler<A> {
+    // Declared before `window` so the application's surface is dropped before the window.
+    app: A,
+    window: Option<VulkanWindow>,
+    power_saver: PowerSaver,
+    runner: AppRunner,
+    last_frame: Option<Instant>,
+    error: Option<anyhow::Error>,
+}
+
+impl<A: AppCallbacks> RunnerHandler<A> {
+    fn initialize(&mut self, event_loop: &ActiveEventLoop) -> Result<()> {
+        let attributes = Window::default_attributes()
+            .with_title(self.runner.title.clone())
+            .with_inner_size(self.runner.size)
+            .with_resizable(self.runner.resizable);
+        let window = VulkanWindow::with_attributes(event_loop, attributes)?;
+        self.app.on_init(&window)?;
+        self.window = Some(window);
+        Ok(())
+    }
+
+    fn exit(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(window) = &mut self.window {
+            window.stop();
+            self.app.on_exit();
+        }
+        event_loop.exit();
+    }
+}
+
+impl<A: AppCallbacks> ApplicationHandler for RunnerHandler<A> {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_none()
+            && self.error.is_none()
+            && let Err(e) = self.initialize(event_loop)
+        {
+            self.error = Some(anyhow::anyhow!("Failed to initialize: {}", e));
+            event_loop.exit();
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        let Some(window) = &self.window else {
+            return;
+        };
+        self.app.on_event(window, &event);
+        self.power_saver.handle_window_event(&event);
+
+        match event {
+            WindowEvent::CloseRequested => self.exit(event_loop),
+            WindowEvent::Resized(size) => {
+                self.app.on_resize(window, size.width, size.height);
+                // Not every platform follows a resize with a redraw on its own.
+                window.request_redraw();
+            }
+            WindowEvent::ScaleFactorChanged { .. } => window.request_redraw(),
+            WindowEvent::RedrawRequested => {
+                let now = Instant::now();
+                let delta_time = self
+                    .last_frame
+                    .map_or(0.0, |last| now.duration_since(last).as_secs_f32());
+                self.last_frame = Some(now);
+
+                self.app.on_update(window, delta_time);
+                if let Err(e) = self.app.on_render(window) {
+                    eprintln!("Failed to render frame: {}", e);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn device_event(&mut self, _: &ActiveEventLoop, _: DeviceId, event: DeviceEvent) {
+        if let Some(window) = &self.window {
+            self.app.on_device_event(window, &event);
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(window) = &mut self.window else {
+            return;
+        };
+        if self.runner.redraw_mode == RedrawMode::OnDemand {
+            // Frames only come from `request_redraw` and the platform's own redraw requests,
+            // so there is nothing to schedule in the background either.
+            event_loop.set_control_flow(ControlFlow::Wait);
+            return;
+        }
+        match self.power_saver.schedule(self.last_frame, Instant::now()) {
+            FrameSchedule::Now => {
+                event_loop.set_control_flow(ControlFlow::Wait);
+                window.on_render();
+            }
+            FrameSchedule::At(next) => event_loop.set_control_flow(ControlFlow::WaitUntil(next)),
+            FrameSchedule::Suspended => {
+                event_loop.set_control_flow(ControlFlow::Wait);
+                // The first frame after resuming shouldn't simulate the whole pause.
+                self.last_frame = None;
+            }
+        }
+    }
+}
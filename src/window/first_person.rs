@@ -0,0 +1,244 @@
+use glam::{Mat4, Vec2, Vec3};
+use winit::event::{DeviceEvent, ElementState, MouseButton, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+use crate::VulkanWindow;
+use crate::animation::CameraPose;
+
+/// Pitch limit short of straight up or down, where `look_to_rh` degenerates.
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct MovementKeys {
+    forward: bool,
+    back: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    sprint: bool,
+}
+
+/// Fly camera with mouse-look. Clicking the window locks and hides the cursor; Escape or
+/// losing focus releases it. Look comes from raw `DeviceEvent::MouseMotion`, which keeps
+/// arriving when the cursor is locked and isn't affected by pointer acceleration. WASD moves,
+/// Space and Left Ctrl rise and sink, Left Shift sprints.
+///
+/// Forward `AppCallbacks::on_event` and `on_device_event` to `handle_window_event` and
+/// `handle_device_event`, then call `update` from `on_update`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FirstPersonController {
+    pub position: Vec3,
+    /// Radians around +Y, zero looking down -Z and increasing to the right.
+    pub yaw: f32,
+    /// Radians above the horizon.
+    pub pitch: f32,
+    /// Vertical field of view in radians.
+    pub fov_y: f32,
+    /// World units per second.
+    pub move_speed: f32,
+    pub sprint_multiplier: f32,
+    /// Radians per count of raw mouse motion.
+    pub sensitivity: f32,
+    pub invert_y: bool,
+    captured: bool,
+    keys: MovementKeys,
+    mouse_delta: Vec2,
+}
+
+impl FirstPersonController {
+    pub fn new(position: Vec3, target: Vec3) -> Self {
+        let mut controller = Self {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            fov_y: 60f32.to_radians(),
+            move_speed: 5.0,
+            sprint_multiplier: 4.0,
+            sensitivity: 0.002,
+            invert_y: false,
+            captured: false,
+            keys: MovementKeys::default(),
+            mouse_delta: Vec2::ZERO,
+        };
+        controller.look_at(target);
+        controller
+    }
+
+    pub fn look_at(&mut self, target: Vec3) {
+        let Some(direction) = (target - self.position).try_normalize() else {
+            return;
+        };
+        self.yaw = direction.x.atan2(-direction.z);
+        self.pitch = direction.y.asin().clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Whether the cursor is locked and mouse motion turns the camera.
+    pub fn is_captured(&self) -> bool {
+        self.captured
+    }
+
+    /// Locks or releases the cursor. Failing to lock it, e.g. on a platform without cursor
+    /// grabs, leaves the controller uncaptured.
+    pub fn set_captured(&mut self, window: &VulkanWindow, captured: bool) {
+        if captured == self.captured {
+            return;
+        }
+        match window.set_cursor_locked(captured) {
+            Ok(()) => self.captured = captured,
+            Err(e) => {
+                eprintln!("{}", e);
+                self.captured = false;
+            }
+        }
+        self.mouse_delta = Vec2::ZERO;
+    }
+
+    pub fn handle_window_event(&mut self, window: &VulkanWindow, event: &WindowEvent) {
+        match event {
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => self.set_captured(window, true),
+            WindowEvent::Focused(false) => {
+                self.keys = MovementKeys::default();
+                self.set_captured(window, false);
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                if event.physical_key == PhysicalKey::Code(KeyCode::Escape)
+                    && event.state.is_pressed()
+                {
+                    self.set_captured(window, false);
+                }
+                self.handle_key(event.physical_key, event.state.is_pressed());
+            }
+            _ => {}
+        }
+    }
+
+    pub fn handle_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            self.handle_mouse_motion(*delta);
+        }
+    }
+
+    /// Accumulates raw mouse motion for the next `update`, ignored while uncaptured.
+    pub fn handle_mouse_motion(&mut self, (x, y): (f64, f64)) {
+        if self.captured {
+            self.mouse_delta += Vec2::new(x as f32, y as f32);
+        }
+    }
+
+    pub fn handle_key(&mut self, key: PhysicalKey, pressed: bool) {
+        let PhysicalKey::Code(code) = key else {
+            return;
+        };
+        let key = match code {
+            KeyCode::KeyW => &mut self.keys.forward,
+            KeyCode::KeyS => &mut self.keys.back,
+            KeyCode::KeyA => &mut self.keys.left,
+            KeyCode::KeyD => &mut self.keys.right,
+            KeyCode::Space => &mut self.keys.up,
+            KeyCode::ControlLeft => &mut self.keys.down,
+            KeyCode::ShiftLeft => &mut self.keys.sprint,
+            _ => return,
+        };
+        *key = pressed;
+    }
+
+    /// Applies the mouse motion since the last update and moves along the held keys.
+    pub fn update(&mut self, delta_time: f32) {
+        let look = self.mouse_delta * self.sensitivity;
+        self.mouse_delta = Vec2::ZERO;
+        self.yaw = (self.yaw + look.x).rem_euclid(std::f32::consts::TAU);
+        let pitch_delta = if self.invert_y { look.y } else { -look.y };
+        self.pitch = (self.pitch + pitch_delta).clamp(-MAX_PITCH, MAX_PITCH);
+
+        let axis = |positive: bool, negative: bool| positive as i32 as f32 - negative as i32 as f32;
+        let (sin, cos) = self.yaw.sin_cos();
+        let forward = Vec3::new(sin, 0.0, -cos);
+        let right = Vec3::new(cos, 0.0, sin);
+        let direction = forward * axis(self.keys.forward, self.keys.back)
+            + right * axis(self.keys.right, self.keys.left)
+            + Vec3::Y * axis(self.keys.up, self.keys.down);
+
+        let mut speed = self.move_speed;
+        if self.keys.sprint {
+            speed *= self.sprint_multiplier;
+        }
+        self.position += direction.normalize_or_zero() * speed * delta_time;
+    }
+
+    /// Unit view direction.
+    pub fn forward(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        Vec3::new(sin_yaw * cos_pitch, sin_pitch, -cos_yaw * cos_pitch)
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_to_rh(self.position, self.forward(), Vec3::Y)
+    }
+
+    pub fn pose(&self) -> CameraPose {
+        CameraPose {
+            position: self.position,
+            target: self.position + self.forward(),
+            fov_y: self.fov_y,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looks_at_its_initial_target() {
+        let target = Vec3::new(3.0, 1.0, -4.0);
+        let controller = FirstPersonController::new(Vec3::new(0.0, 1.0, 0.0), target);
+
+        assert!(controller.forward().distance(Vec3::new(0.6, 0.0, -0.8)) < 1e-5);
+        assert!(
+            controller
+                .view()
+                .abs_diff_eq(controller.pose().view(), 1e-5)
+        );
+    }
+
+    #[test]
+    fn mouse_look_turns_only_while_captured() {
+        let mut controller = FirstPersonController::new(Vec3::ZERO, Vec3::NEG_Z);
+        controller.handle_mouse_motion((100.0, 0.0));
+        controller.update(0.0);
+        assert_eq!(controller.yaw, 0.0);
+
+        controller.captured = true;
+        controller.handle_mouse_motion((500.0, 0.0));
+        controller.handle_mouse_motion((285.0, 1e4));
+        controller.update(0.0);
+        // 785 counts at 0.002 rad/count turn a quarter turn right, towards +X.
+        assert!((controller.yaw - std::f32::consts::FRAC_PI_2).abs() < 1e-3);
+        assert_eq!(controller.pitch, -MAX_PITCH);
+    }
+
+    #[test]
+    fn moves_along_the_view_direction_on_the_ground_plane() {
+        let mut controller = FirstPersonController::new(Vec3::ZERO, Vec3::new(1.0, -1.0, 0.0));
+        controller.handle_key(PhysicalKey::Code(KeyCode::KeyW), true);
+        controller.handle_key(PhysicalKey::Code(KeyCode::KeyD), true);
+        controller.update(1.0);
+
+        let expected = Vec3::new(1.0, 0.0, 1.0).normalize() * controller.move_speed;
+        assert!(controller.position.distance(expected) < 1e-4);
+
+        controller.handle_key(PhysicalKey::Code(KeyCode::KeyW), false);
+        controller.handle_key(PhysicalKey::Code(KeyCode::KeyD), false);
+        controller.handle_key(PhysicalKey::Code(KeyCode::ShiftLeft), true);
+        controller.handle_key(PhysicalKey::Code(KeyCode::Space), true);
+        controller.update(0.5);
+        let rise = controller.move_speed * controller.sprint_multiplier * 0.5;
+        assert!((controller.position.y - rise).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,123 @@
+/// Frame times within this many seconds of a whole number of refresh intervals are snapped to
+/// it, removing timer jitter from vsynced frames.
+const SNAP_TOLERANCE: f64 = 0.0005;
+
+/// Longest frame time fed to the simulation, so a stall doesn't cause a burst of steps.
+const MAX_FRAME_TIME: f64 = 0.25;
+
+/// Turns measured frame times into fixed simulation steps aligned with the display refresh.
+///
+/// Once the refresh rate is known, each frame's time is snapped to whole refresh intervals and
+/// the fixed step is adjusted so a whole number of steps fits in one interval (or a whole
+/// number of intervals in one step), so every displayed frame runs the same number of steps.
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+    requested_step: f64,
+    step: f64,
+    refresh_interval: Option<f64>,
+    accumulator: f64,
+}
+
+impl FramePacer {
+    /// Pacer running the simulation at about `rate` Hz.
+    pub fn new(rate: f64) -> Self {
+        let step = 1.0 / rate;
+        Self {
+            requested_step: step,
+            step,
+            refresh_interval: None,
+            accumulator: 0.0,
+        }
+    }
+
+    /// Refresh rate of the display in Hz, e.g. from `VulkanWindow::refresh_rate`. `None`
+    /// falls back to the requested rate and unsnapped frame times.
+    pub fn set_refresh_rate(&mut self, refresh_rate: Option<f64>) {
+        self.refresh_interval = refresh_rate
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| 1.0 / rate);
+        self.step = match self.refresh_interval {
+            Some(interval) if self.requested_step <= interval => {
+                interval / (interval / self.requested_step).round().max(1.0)
+            }
+            Some(interval) => interval * (self.requested_step / interval).round(),
+            None => self.requested_step,
+        };
+    }
+
+    pub fn refresh_rate(&self) -> Option<f64> {
+        self.refresh_interval.map(|interval| 1.0 / interval)
+    }
+
+    /// Fixed step in seconds, aligned with the refresh rate when known.
+    pub fn step(&self) -> f64 {
+        self.step
+    }
+
+    /// `frame_time` snapped to whole refresh intervals when close to one, and clamped.
+    pub fn frame_delta(&self, frame_time: f64) -> f64 {
+        let frame_time = frame_time.clamp(0.0, MAX_FRAME_TIME);
+        let Some(interval) = self.refresh_interval else {
+            return frame_time;
+        };
+        let intervals = (frame_time / interval).round();
+        if intervals >= 1.0 && (frame_time - intervals * interval).abs() <= SNAP_TOLERANCE {
+            intervals * interval
+        } else {
+            frame_time
+        }
+    }
+
+    /// Adds a measured frame time and returns how many fixed steps to run this frame.
+    pub fn advance(&mut self, frame_time: f64) -> u32 {
+        self.accumulator += self.frame_delta(frame_time);
+        // The small bias keeps snapped frames from losing a step to float error.
+        let steps = ((self.accumulator / self.step) + 1e-9).floor();
+        self.accumulator = (self.accumulator - steps * self.step).max(0.0);
+        steps as u32
+    }
+
+    /// How far between the last step and the next one the display is, for interpolating
+    /// rendered state.
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator / self.step).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligns_step_with_refresh_rate() {
+        let mut pacer = FramePacer::new(60.0);
+        pacer.set_refresh_rate(Some(144.0));
+        assert!((pacer.step() - 2.0 / 144.0).abs() < 1e-12);
+
+        pacer.set_refresh_rate(Some(240.0));
+        assert!((pacer.step() - 4.0 / 240.0).abs() < 1e-12);
+
+        let mut physics = FramePacer::new(120.0);
+        physics.set_refresh_rate(Some(59.94));
+        assert!((physics.step() - 1.0 / 59.94 / 2.0).abs() < 1e-12);
+
+        physics.set_refresh_rate(None);
+        assert!((physics.step() - 1.0 / 120.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn runs_the_same_steps_every_vsynced_frame() {
+        let mut pacer = FramePacer::new(120.0);
+        pacer.set_refresh_rate(Some(60.0));
+
+        for jitter in [0.0003, -0.0004, 0.0001, -0.0002] {
+            assert_eq!(pacer.advance(1.0 / 60.0 + jitter), 2);
+            assert_eq!(pacer.alpha(), 0.0);
+        }
+        // A missed vsync runs two frames' worth.
+        assert_eq!(pacer.advance(2.0 / 60.0 + 0.0002), 4);
+
+        // Stalls are clamped.
+        assert_eq!(pacer.advance(10.0), (MAX_FRAME_TIME * 120.0) as u32);
+    }
+}
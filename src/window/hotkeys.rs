@@ -0,0 +1,324 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use winit::event::WindowEvent;
+use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
+
+/// Names a hotkey settings file to load instead of `DEFAULT_HOTKEYS_PATH`.
+pub const HOTKEYS_ENV: &str = "RVE_HOTKEYS";
+pub const DEFAULT_HOTKEYS_PATH: &str = "hotkeys.ron";
+
+pub const ACTION_TOGGLE_WIREFRAME: &str = "toggle_wireframe";
+pub const ACTION_CAPTURE_SCREENSHOT: &str = "capture_screenshot";
+pub const ACTION_RELOAD_SHADERS: &str = "reload_shaders";
+pub const ACTION_CYCLE_COLOR_CHANNELS: &str = "cycle_color_channels";
+pub const ACTION_SWITCH_DEVICE: &str = "switch_device";
+
+/// A key with the exact modifiers that must be held, written like `Ctrl+Shift+F5`. Keys use
+/// winit's `KeyCode` names (`F5`, `KeyS`, `Digit1`, `Escape`); single letters and digits are
+/// accepted as shorthand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyCombo {
+    pub key: KeyCode,
+    pub modifiers: ModifiersState,
+}
+
+impl KeyCombo {
+    pub fn new(key: KeyCode) -> Self {
+        Self {
+            key,
+            modifiers: ModifiersState::empty(),
+        }
+    }
+
+    pub fn with_modifiers(mut self, modifiers: ModifiersState) -> Self {
+        self.modifiers = modifiers;
+        self
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = anyhow::Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        let mut parts: Vec<&str> = source.split('+').map(str::trim).collect();
+        let key = parts.pop().filter(|key| !key.is_empty());
+        let Some(key) = key else {
+            anyhow::bail!("Missing key in hotkey '{}'", source);
+        };
+
+        let mut modifiers = ModifiersState::empty();
+        for modifier in parts {
+            modifiers |= match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => ModifiersState::CONTROL,
+                "shift" => ModifiersState::SHIFT,
+                "alt" => ModifiersState::ALT,
+                "super" | "cmd" | "logo" => ModifiersState::SUPER,
+                _ => anyhow::bail!("Unknown modifier '{}' in hotkey '{}'", modifier, source),
+            };
+        }
+
+        let name = match key.as_bytes() {
+            [letter] if letter.is_ascii_alphabetic() => {
+                format!("Key{}", letter.to_ascii_uppercase() as char)
+            }
+            [digit] if digit.is_ascii_digit() => format!("Digit{}", *digit as char),
+            _ => key.to_string(),
+        };
+        // `KeyCode` deserializes from its variant name, which is also what `Display` writes.
+        let key: KeyCode = ron::from_str(&name)
+            .map_err(|_| anyhow::anyhow!("Unknown key '{}' in hotkey '{}'", key, source))?;
+
+        Ok(Self { key, modifiers })
+    }
+}
+
+impl TryFrom<String> for KeyCombo {
+    type Error = anyhow::Error;
+
+    fn try_from(source: String) -> Result<Self> {
+        source.parse()
+    }
+}
+
+impl From<KeyCombo> for String {
+    fn from(combo: KeyCombo) -> Self {
+        combo.to_string()
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (ModifiersState::CONTROL, "Ctrl"),
+            (ModifiersState::SHIFT, "Shift"),
+            (ModifiersState::ALT, "Alt"),
+            (ModifiersState::SUPER, "Super"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}+", name)?;
+            }
+        }
+        write!(f, "{:?}", self.key)
+    }
+}
+
+/// Action names bound to key combos, e.g. `reload_shaders` to `F5`. An action can have several
+/// combos and a combo can trigger several actions.
+///
+/// Settings files are RON maps from action to combos, merged over the defaults so they only
+/// need to list what they change; an empty list unbinds an action:
+///
+/// ```ron
+/// {
+///     "capture_screenshot": ["F12", "Ctrl+P"],
+///     "toggle_wireframe": [],
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct HotkeyMap {
+    bindings: BTreeMap<String, Vec<KeyCombo>>,
+}
+
+impl Default for HotkeyMap {
+    fn default() -> Self {
+        Self::empty()
+            .with_binding(ACTION_TOGGLE_WIREFRAME, KeyCombo::new(KeyCode::F1))
+            .with_binding(ACTION_CYCLE_COLOR_CHANNELS, KeyCombo::new(KeyCode::F2))
+            .with_binding(ACTION_SWITCH_DEVICE, KeyCombo::new(KeyCode::F3))
+            .with_binding(ACTION_RELOAD_SHADERS, KeyCombo::new(KeyCode::F5))
+            .with_binding(ACTION_CAPTURE_SCREENSHOT, KeyCombo::new(KeyCode::F12))
+    }
+}
+
+impl HotkeyMap {
+    /// A map without any bindings, unlike `default`.
+    pub fn empty() -> Self {
+        Self {
+            bindings: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_binding(mut self, action: &str, combo: KeyCombo) -> Self {
+        self.bind(action, combo);
+        self
+    }
+
+    pub fn bind(&mut self, action: &str, combo: KeyCombo) {
+        let combos = self.bindings.entry(action.to_string()).or_default();
+        if !combos.contains(&combo) {
+            combos.push(combo);
+        }
+    }
+
+    /// Replaces every combo bound to `action`.
+    pub fn set_bindings(&mut self, action: &str, combos: Vec<KeyCombo>) {
+        self.bindings.insert(action.to_string(), combos);
+    }
+
+    pub fn bindings(&self, action: &str) -> &[KeyCombo] {
+        self.bindings.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Actions bound to `combo`, in name order.
+    pub fn actions(&self, combo: KeyCombo) -> impl Iterator<Item = &str> {
+        self.bindings
+            .iter()
+            .filter(move |(_, combos)| combos.contains(&combo))
+            .map(|(action, _)| action.as_str())
+    }
+
+    /// Parses a settings file and merges it over the default bindings.
+    pub fn from_ron(source: &str) -> Result<Self> {
+        let overrides: Self =
+            ron::from_str(source).map_err(|e| anyhow::anyhow!("Failed to parse hotkeys: {}", e))?;
+        let mut map = Self::default();
+        map.bindings.extend(overrides.bindings);
+        Ok(map)
+    }
+
+    pub fn to_ron(&self) -> Result<String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| anyhow::anyhow!("Failed to serialize hotkeys: {}", e))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read hotkeys {}: {}", path.display(), e))?;
+        Self::from_ron(&source)
+    }
+
+    /// Loads the file named by `RVE_HOTKEYS`, or `hotkeys.ron` if it exists, falling back to
+    /// the defaults.
+    pub fn from_env() -> Result<Self> {
+        match std::env::var_os(HOTKEYS_ENV) {
+            Some(path) => Self::load(path),
+            None if Path::new(DEFAULT_HOTKEYS_PATH).exists() => Self::load(DEFAULT_HOTKEYS_PATH),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_ron()?)
+            .map_err(|e| anyhow::anyhow!("Failed to write hotkeys {}: {}", path.display(), e))
+    }
+}
+
+/// Turns window events into triggered actions of a `HotkeyMap`. Forward
+/// `AppCallbacks::on_event` to `handle_window_event` and drain `take_triggered` once per frame.
+#[derive(Debug, Clone, Default)]
+pub struct Hotkeys {
+    pub map: HotkeyMap,
+    modifiers: ModifiersState,
+    triggered: Vec<String>,
+}
+
+impl Hotkeys {
+    pub fn new(map: HotkeyMap) -> Self {
+        Self {
+            map,
+            modifiers: ModifiersState::empty(),
+            triggered: Vec::new(),
+        }
+    }
+
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::Focused(false) => self.modifiers = ModifiersState::empty(),
+            WindowEvent::KeyboardInput { event, .. }
+                if event.state.is_pressed() && !event.repeat =>
+            {
+                self.handle_key(event.physical_key);
+            }
+            _ => {}
+        }
+    }
+
+    /// Triggers the actions bound to `key` with the modifiers currently held.
+    pub fn handle_key(&mut self, key: PhysicalKey) {
+        let PhysicalKey::Code(key) = key else {
+            return;
+        };
+        let combo = KeyCombo::new(key).with_modifiers(self.modifiers);
+        self.triggered
+            .extend(self.map.actions(combo).map(str::to_string));
+    }
+
+    pub fn set_modifiers(&mut self, modifiers: ModifiersState) {
+        self.modifiers = modifiers;
+    }
+
+    /// Actions triggered since the last call, in key press order.
+    pub fn take_triggered(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.triggered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_prints_key_combos() {
+        let combo: KeyCombo = "ctrl + Shift+s".parse().unwrap();
+        assert_eq!(combo.key, KeyCode::KeyS);
+        assert_eq!(
+            combo.modifiers,
+            ModifiersState::CONTROL | ModifiersState::SHIFT
+        );
+        assert_eq!(combo.to_string(), "Ctrl+Shift+KeyS");
+        assert_eq!(combo.to_string().parse::<KeyCombo>().unwrap(), combo);
+
+        assert_eq!(
+            "F5".parse::<KeyCombo>().unwrap(),
+            KeyCombo::new(KeyCode::F5)
+        );
+        assert!("Hyper+F5".parse::<KeyCombo>().is_err());
+        assert!("Ctrl+".parse::<KeyCombo>().is_err());
+        assert!("Ctrl+NotAKey".parse::<KeyCombo>().is_err());
+    }
+
+    #[test]
+    fn settings_override_default_bindings() {
+        let map = HotkeyMap::from_ron(
+            r#"{ "capture_screenshot": ["F12", "Ctrl+P"], "toggle_wireframe": [] }"#,
+        )
+        .unwrap();
+
+        assert_eq!(map.bindings(ACTION_TOGGLE_WIREFRAME), &[]);
+        assert_eq!(
+            map.bindings(ACTION_RELOAD_SHADERS),
+            &[KeyCombo::new(KeyCode::F5)]
+        );
+        assert_eq!(map.bindings(ACTION_CAPTURE_SCREENSHOT).len(), 2);
+        assert_eq!(HotkeyMap::from_ron(&map.to_ron().unwrap()).unwrap(), map);
+        assert!(HotkeyMap::from_ron(r#"{ "reload_shaders": ["Ctrl+Nope"] }"#).is_err());
+    }
+
+    #[test]
+    fn triggers_actions_only_with_exact_modifiers() {
+        let mut hotkeys = Hotkeys::new(
+            HotkeyMap::default().with_binding(ACTION_CAPTURE_SCREENSHOT, "Ctrl+P".parse().unwrap()),
+        );
+
+        hotkeys.handle_key(PhysicalKey::Code(KeyCode::KeyP));
+        hotkeys.handle_key(PhysicalKey::Code(KeyCode::F5));
+        hotkeys.set_modifiers(ModifiersState::CONTROL);
+        hotkeys.handle_key(PhysicalKey::Code(KeyCode::KeyP));
+        hotkeys.handle_key(PhysicalKey::Code(KeyCode::F5));
+
+        assert_eq!(
+            hotkeys.take_triggered(),
+            [ACTION_RELOAD_SHADERS, ACTION_CAPTURE_SCREENSHOT]
+        );
+        assert!(hotkeys.take_triggered().is_empty());
+    }
+}
@@ -1,3 +1,19 @@
+pub mod app_runner;
+pub mod first_person;
+pub mod frame_pacer;
+pub mod hotkeys;
+pub mod monitor;
+pub mod power_saving;
+pub mod redraw;
+pub mod resize;
 pub mod window;
 
+pub use app_runner::*;
+pub use first_person::*;
+pub use frame_pacer::*;
+pub use hotkeys::*;
+pub use monitor::*;
+pub use power_saving::*;
+pub use redraw::*;
+pub use resize::*;
 pub use window::*;
@@ -0,0 +1,120 @@
+use anyhow::Result;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::monitor::{MonitorHandle, VideoModeHandle};
+use winit::window::Fullscreen;
+
+use crate::VulkanWindow;
+
+/// How `VulkanWindow::place_on_monitor` shows the window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WindowPlacement {
+    /// Centered on the monitor, keeping its size.
+    #[default]
+    Windowed,
+    /// Covering the monitor at its current mode.
+    Borderless,
+    /// Taking over the monitor at its largest, fastest video mode.
+    Exclusive,
+}
+
+/// A monitor as reported by the window system.
+#[derive(Debug, Clone)]
+pub struct MonitorInfo {
+    pub handle: MonitorHandle,
+    pub name: String,
+    pub position: PhysicalPosition<i32>,
+    pub size: PhysicalSize<u32>,
+    pub scale_factor: f64,
+    /// `None` when the platform doesn't report it.
+    pub refresh_rate_millihertz: Option<u32>,
+}
+
+impl MonitorInfo {
+    fn new(handle: MonitorHandle) -> Self {
+        Self {
+            name: handle
+                .name()
+                .unwrap_or_else(|| "Unknown monitor".to_string()),
+            position: handle.position(),
+            size: handle.size(),
+            scale_factor: handle.scale_factor(),
+            refresh_rate_millihertz: handle.refresh_rate_millihertz(),
+            handle,
+        }
+    }
+
+    pub fn refresh_rate(&self) -> Option<f64> {
+        self.refresh_rate_millihertz
+            .map(|millihertz| millihertz as f64 / 1000.0)
+    }
+}
+
+impl VulkanWindow {
+    /// Monitors available to the window, primary first when the platform reports one.
+    pub fn monitors(&self) -> Vec<MonitorInfo> {
+        let window = self.window();
+        let primary = window.primary_monitor();
+        let mut monitors: Vec<_> = window.available_monitors().map(MonitorInfo::new).collect();
+        if let Some(primary) = primary
+            && let Some(index) = monitors
+                .iter()
+                .position(|monitor| monitor.handle == primary)
+        {
+            monitors[..=index].rotate_right(1);
+        }
+        monitors
+    }
+
+    /// Moves the window onto `monitor`, optionally fullscreen.
+    pub fn place_on_monitor(
+        &self,
+        monitor: &MonitorInfo,
+        placement: WindowPlacement,
+    ) -> Result<()> {
+        let window = self.window();
+        match placement {
+            WindowPlacement::Windowed => {
+                window.set_fullscreen(None);
+                let size = window.outer_size();
+                window.set_outer_position(PhysicalPosition::new(
+                    monitor.position.x + (monitor.size.width as i32 - size.width as i32) / 2,
+                    monitor.position.y + (monitor.size.height as i32 - size.height as i32) / 2,
+                ));
+            }
+            WindowPlacement::Borderless => {
+                window.set_fullscreen(Some(Fullscreen::Borderless(Some(monitor.handle.clone()))));
+            }
+            WindowPlacement::Exclusive => {
+                let mode = best_video_mode(monitor.handle.video_modes()).ok_or_else(|| {
+                    anyhow::anyhow!("Monitor {} has no video modes", monitor.name)
+                })?;
+                window.set_fullscreen(Some(Fullscreen::Exclusive(mode)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Monitor the window is currently on.
+    pub fn current_monitor(&self) -> Option<MonitorInfo> {
+        self.window().current_monitor().map(MonitorInfo::new)
+    }
+
+    /// Refresh rate in Hz of the monitor the window is on, for `FramePacer::set_refresh_rate`.
+    /// In exclusive fullscreen this is the rate of the chosen video mode.
+    pub fn refresh_rate(&self) -> Option<f64> {
+        if let Some(Fullscreen::Exclusive(mode)) = self.window().fullscreen() {
+            return Some(mode.refresh_rate_millihertz() as f64 / 1000.0);
+        }
+        self.current_monitor()?.refresh_rate()
+    }
+}
+
+fn best_video_mode(modes: impl Iterator<Item = VideoModeHandle>) -> Option<VideoModeHandle> {
+    modes.max_by_key(|mode| {
+        (
+            mode.size().width as u64 * mode.size().height as u64,
+            mode.refresh_rate_millihertz(),
+            mode.bit_depth(),
+        )
+    })
+}
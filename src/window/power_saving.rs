@@ -0,0 +1,158 @@
+use anyhow::Result;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use winit::event::WindowEvent;
+
+pub const BACKGROUND_MODE_ENV: &str = "RVE_BACKGROUND";
+
+/// How an `AppRunner` renders while its window is unfocused or occluded.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BackgroundMode {
+    /// Keeps rendering as fast as in the foreground.
+    #[default]
+    Full,
+    /// Renders at most this many frames per second, e.g. so a tool left open on a laptop
+    /// keeps updating without draining the battery.
+    Throttled(f64),
+    /// Stops rendering until the window is focused or visible again. Occluded windows are
+    /// not shown anyway, but their frames still cost GPU time.
+    Suspended,
+}
+
+impl BackgroundMode {
+    /// Reads `RVE_BACKGROUND` (`full`, `suspend` or a frame rate like `10`), defaulting to
+    /// `Full`.
+    pub fn from_env() -> Result<Self> {
+        match std::env::var(BACKGROUND_MODE_ENV) {
+            Ok(value) => value.parse(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+}
+
+impl FromStr for BackgroundMode {
+    type Err = anyhow::Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        match source.trim().to_ascii_lowercase().as_str() {
+            "full" => Ok(Self::Full),
+            "suspend" | "suspended" => Ok(Self::Suspended),
+            rate => match rate.parse::<f64>() {
+                Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(Self::Throttled(rate)),
+                _ => Err(anyhow::anyhow!(
+                    "Unknown background mode '{}', expected 'full', 'suspend' or a frame rate",
+                    source
+                )),
+            },
+        }
+    }
+}
+
+/// When the runner should render next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameSchedule {
+    /// Request a redraw right away.
+    Now,
+    /// Sleep until this instant, unless an event arrives first.
+    At(Instant),
+    /// Sleep until the next event.
+    Suspended,
+}
+
+/// Tracks focus and occlusion from window events and applies a `BackgroundMode` to them.
+#[derive(Debug, Clone)]
+pub struct PowerSaver {
+    pub mode: BackgroundMode,
+    focused: bool,
+    occluded: bool,
+}
+
+impl PowerSaver {
+    pub fn new(mode: BackgroundMode) -> Self {
+        Self {
+            mode,
+            focused: true,
+            occluded: false,
+        }
+    }
+
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::Focused(focused) => self.focused = *focused,
+            WindowEvent::Occluded(occluded) => self.occluded = *occluded,
+            _ => {}
+        }
+    }
+
+    /// True while the window is unfocused or occluded.
+    pub fn is_background(&self) -> bool {
+        !self.focused || self.occluded
+    }
+
+    /// When to render the frame after one rendered at `last_frame`. Focus and visibility
+    /// events wake the event loop, so the foreground rate resumes on the next one.
+    pub fn schedule(&self, last_frame: Option<Instant>, now: Instant) -> FrameSchedule {
+        if !self.is_background() {
+            return FrameSchedule::Now;
+        }
+        match self.mode {
+            BackgroundMode::Full => FrameSchedule::Now,
+            BackgroundMode::Suspended => FrameSchedule::Suspended,
+            BackgroundMode::Throttled(rate) => {
+                let next = last_frame.map(|last| last + Duration::from_secs_f64(1.0 / rate));
+                match next {
+                    Some(next) if next > now => FrameSchedule::At(next),
+                    _ => FrameSchedule::Now,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttles_and_suspends_in_the_background() {
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let mut saver = PowerSaver::new(BackgroundMode::Throttled(10.0));
+        assert_eq!(saver.schedule(Some(ms(0)), ms(1)), FrameSchedule::Now);
+
+        saver.handle_window_event(&WindowEvent::Focused(false));
+        assert_eq!(
+            saver.schedule(Some(ms(0)), ms(1)),
+            FrameSchedule::At(ms(100))
+        );
+        assert_eq!(saver.schedule(Some(ms(0)), ms(100)), FrameSchedule::Now);
+        assert_eq!(saver.schedule(None, ms(1)), FrameSchedule::Now);
+
+        saver.mode = BackgroundMode::Suspended;
+        assert_eq!(saver.schedule(Some(ms(0)), ms(1)), FrameSchedule::Suspended);
+
+        saver.handle_window_event(&WindowEvent::Focused(true));
+        saver.handle_window_event(&WindowEvent::Occluded(true));
+        assert!(saver.is_background());
+        saver.handle_window_event(&WindowEvent::Occluded(false));
+        assert_eq!(saver.schedule(Some(ms(0)), ms(1)), FrameSchedule::Now);
+    }
+
+    #[test]
+    fn parses_background_modes() {
+        assert_eq!(
+            "Full".parse::<BackgroundMode>().unwrap(),
+            BackgroundMode::Full
+        );
+        assert_eq!(
+            "suspend".parse::<BackgroundMode>().unwrap(),
+            BackgroundMode::Suspended
+        );
+        assert_eq!(
+            " 15 ".parse::<BackgroundMode>().unwrap(),
+            BackgroundMode::Throttled(15.0)
+        );
+        assert!("0".parse::<BackgroundMode>().is_err());
+        assert!("sleep".parse::<BackgroundMode>().is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::Result;
+use std::str::FromStr;
+
+pub const REDRAW_MODE_ENV: &str = "RVE_REDRAW";
+
+/// When an `AppRunner` renders frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RedrawMode {
+    /// Renders a new frame as soon as the previous one is done, for games and animations.
+    #[default]
+    Continuous,
+    /// Renders only when the application calls `VulkanWindow::request_redraw`, e.g. after
+    /// input changed the scene or a UI asked to repaint, or when the window is resized or
+    /// exposed. The event loop sleeps in between, which suits editors and other tools.
+    /// Animating applications keep requesting redraws until they settle.
+    OnDemand,
+}
+
+impl RedrawMode {
+    /// Reads `RVE_REDRAW` (`continuous` or `on_demand`), defaulting to `Continuous`.
+    pub fn from_env() -> Result<Self> {
+        match std::env::var(REDRAW_MODE_ENV) {
+            Ok(value) => value.parse(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+}
+
+impl FromStr for RedrawMode {
+    type Err = anyhow::Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        match source
+            .trim()
+            .to_ascii_lowercase()
+            .replace('-', "_")
+            .as_str()
+        {
+            "continuous" => Ok(Self::Continuous),
+            "on_demand" | "demand" => Ok(Self::OnDemand),
+            _ => Err(anyhow::anyhow!(
+                "Unknown redraw mode '{}', expected 'continuous' or 'on_demand'",
+                source
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_redraw_modes() {
+        assert_eq!(
+            "Continuous".parse::<RedrawMode>().unwrap(),
+            RedrawMode::Continuous
+        );
+        assert_eq!(
+            "on-demand".parse::<RedrawMode>().unwrap(),
+            RedrawMode::OnDemand
+        );
+        assert!("lazy".parse::<RedrawMode>().is_err());
+    }
+}
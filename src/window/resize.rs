@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+
+/// How long the window size has to stay unchanged before a resize is considered finished.
+pub const DEFAULT_RESIZE_SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// Collapses the stream of resize events sent while a window is dragged into one resize once
+/// the size stops changing.
+///
+/// Until then, render targets can stay at the last size they were allocated for and be scaled
+/// to the window, instead of being reallocated, with a wait for the GPU, on every event.
+#[derive(Debug, Clone)]
+pub struct ResizeDebouncer {
+    settle_time: Duration,
+    pending: Option<(u32, u32)>,
+    last_event: Option<Instant>,
+}
+
+impl Default for ResizeDebouncer {
+    fn default() -> Self {
+        Self::new(DEFAULT_RESIZE_SETTLE_TIME)
+    }
+}
+
+impl ResizeDebouncer {
+    pub fn new(settle_time: Duration) -> Self {
+        Self {
+            settle_time,
+            pending: None,
+            last_event: None,
+        }
+    }
+
+    /// Records a resize event to `width` x `height` physical pixels received at `now`.
+    pub fn resized(&mut self, width: u32, height: u32, now: Instant) {
+        self.pending = Some((width, height));
+        self.last_event = Some(now);
+    }
+
+    /// True while a resize is waiting to settle.
+    pub fn is_resizing(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// The final size once no event arrived for the settle time, returned only once per
+    /// resize. Minimized windows (zero area) never settle, so nothing is allocated for them.
+    pub fn settle(&mut self, now: Instant) -> Option<(u32, u32)> {
+        let last_event = self.last_event?;
+        let (width, height) = self.pending?;
+        if width == 0 || height == 0 || now.duration_since(last_event) < self.settle_time {
+            return None;
+        }
+        self.pending = None;
+        Some((width, height))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settles_on_the_last_size() {
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let mut resize = ResizeDebouncer::new(Duration::from_millis(100));
+        assert_eq!(resize.settle(ms(0)), None);
+
+        resize.resized(800, 600, ms(0));
+        resize.resized(820, 610, ms(40));
+        resize.resized(900, 700, ms(80));
+        assert!(resize.is_resizing());
+        assert_eq!(resize.settle(ms(150)), None);
+        assert_eq!(resize.settle(ms(180)), Some((900, 700)));
+        assert_eq!(resize.settle(ms(500)), None);
+        assert!(!resize.is_resizing());
+    }
+
+    #[test]
+    fn waits_while_minimized() {
+        let start = Instant::now();
+        let mut resize = ResizeDebouncer::default();
+
+        resize.resized(0, 0, start);
+        assert_eq!(resize.settle(start + Duration::from_secs(1)), None);
+        resize.resized(640, 480, start + Duration::from_secs(2));
+        assert_eq!(
+            resize.settle(start + Duration::from_secs(3)),
+            Some((640, 480))
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use anyhow::Result;
 use winit::dpi::LogicalSize;
 use winit::event_loop::ActiveEventLoop;
-use winit::window::Window;
+use winit::window::{CursorGrabMode, Window, WindowAttributes};
 
 pub struct VulkanWindow {
     window: Window,
@@ -15,6 +15,13 @@ impl VulkanWindow {
             .with_inner_size(LogicalSize::new(1280.0, 720.0))
             .with_resizable(true);
 
+        Self::with_attributes(event_loop, window_attributes)
+    }
+
+    pub fn with_attributes(
+        event_loop: &ActiveEventLoop,
+        window_attributes: WindowAttributes,
+    ) -> Result<Self> {
         let window = event_loop
             .create_window(window_attributes)
             .map_err(|e| anyhow::anyhow!("Failed to create window: {}", e))?;
@@ -29,6 +36,24 @@ impl VulkanWindow {
         &self.window
     }
 
+    /// Locks the cursor in place and hides it for mouse-look, or releases and shows it again.
+    /// X11 and Windows can't lock the cursor, so it is confined to the window there instead;
+    /// either way read mouse-look from `DeviceEvent::MouseMotion` rather than cursor positions.
+    pub fn set_cursor_locked(&self, locked: bool) -> Result<()> {
+        if locked {
+            self.window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| self.window.set_cursor_grab(CursorGrabMode::Confined))
+                .map_err(|e| anyhow::anyhow!("Failed to grab cursor: {}", e))?;
+        } else {
+            self.window
+                .set_cursor_grab(CursorGrabMode::None)
+                .map_err(|e| anyhow::anyhow!("Failed to release cursor: {}", e))?;
+        }
+        self.window.set_cursor_visible(!locked);
+        Ok(())
+    }
+
     pub fn stop(&mut self) {
         self.running = false;
     }
@@ -37,6 +62,12 @@ impl VulkanWindow {
         self.window.request_redraw();
     }
 
+    /// Asks for a frame, e.g. after the scene changed with `RedrawMode::OnDemand`. Requests
+    /// made before the frame is rendered are merged into one.
+    pub fn request_redraw(&self) {
+        self.window.request_redraw();
+    }
+
     pub fn get_required_extensions() -> Vec<*const i8> {
         let mut extensions = vec![ash::khr::surface::NAME.as_ptr()];
 